@@ -0,0 +1,180 @@
+// Collects the API changelog from the `// api-change <date> <kind>: <description>`
+// annotations in the route and model code, see models::changelog
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+const ANNOTATED_DIRS: [&str; 2] = ["src/routes", "src/models"];
+const ANNOTATION: &str = "// api-change ";
+const KINDS: [(&str, &str); 4] = [
+    ("added", "Added"),
+    ("changed", "Changed"),
+    ("deprecated", "Deprecated"),
+    ("removed", "Removed"),
+];
+const ROUTE_METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+// Lines of attributes and comments allowed between an annotation and its item
+const MAX_ANNOTATION_DISTANCE: usize = 12;
+
+struct Change {
+    date: String,
+    kind: &'static str,
+    item: String,
+    endpoint: Option<String>,
+    description: String,
+    source: String,
+}
+
+fn main() {
+    let mut changes = Vec::new();
+    for dir in ANNOTATED_DIRS {
+        println!("cargo:rerun-if-changed={}", dir);
+        let mut files: Vec<_> = fs::read_dir(dir)
+            .unwrap_or_else(|e| panic!("cannot read {}: {}", dir, e))
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().map_or(false, |ext| ext == "rs"))
+            .collect();
+        files.sort();
+        for path in files {
+            println!("cargo:rerun-if-changed={}", path.display());
+            collect(&path, &mut changes);
+        }
+    }
+    // Newest first, in source order within a day
+    changes.sort_by(|a, b| b.date.cmp(&a.date));
+
+    let mut generated = String::from("&[\n");
+    for change in &changes {
+        writeln!(
+            generated,
+            "    ApiChange {{ date: {:?}, kind: ApiChangeKind::{}, item: {:?}, endpoint: {:?}, \
+             description: {:?}, source: {:?} }},",
+            change.date,
+            change.kind,
+            change.item,
+            change.endpoint,
+            change.description,
+            change.source
+        )
+        .unwrap();
+    }
+    generated.push(']');
+
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("api_changelog.rs"), generated).unwrap();
+}
+
+fn collect(path: &Path, changes: &mut Vec<Change>) {
+    let source = path.display().to_string();
+    let text = fs::read_to_string(path).unwrap_or_else(|e| panic!("cannot read {}: {}", source, e));
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+
+    let mut current_struct = None;
+    for (index, line) in lines.iter().enumerate() {
+        if let Some(name) = declared_name(line, "struct ") {
+            current_struct = Some(name);
+        }
+        let Some(annotation) = line.strip_prefix(ANNOTATION) else {
+            continue;
+        };
+        let line_number = index + 1;
+
+        let (header, description) = annotation
+            .split_once(':')
+            .unwrap_or_else(|| fail(&source, line_number, "missing description"));
+        let mut header = header.split_whitespace();
+        let date = header
+            .next()
+            .unwrap_or_else(|| fail(&source, line_number, "missing date"));
+        if !valid_date(date) {
+            fail(&source, line_number, "invalid date");
+        }
+        let kind = header
+            .next()
+            .unwrap_or_else(|| fail(&source, line_number, "missing kind"));
+        let kind = KINDS
+            .iter()
+            .find(|(name, _)| *name == kind)
+            .map(|(_, variant)| *variant)
+            .unwrap_or_else(|| fail(&source, line_number, "unknown kind"));
+        let description = description.trim();
+        if description.is_empty() {
+            fail(&source, line_number, "empty description");
+        }
+
+        // The annotated item is the next declaration, past comments and attributes
+        let mut attributes = String::new();
+        let mut item = None;
+        for line in lines[index + 1..].iter().take(MAX_ANNOTATION_DISTANCE) {
+            if line.starts_with("//") || line.is_empty() {
+                continue;
+            }
+            let declared = declared_name(line, "fn ")
+                .or_else(|| declared_name(line, "struct "))
+                .or_else(|| declared_name(line, "enum "));
+            if let Some(name) = declared {
+                item = Some(name);
+                break;
+            }
+            if let Some((field, _)) = line
+                .strip_prefix("pub ")
+                .and_then(|rest| rest.split_once(':'))
+            {
+                let owner = current_struct.clone().unwrap_or_default();
+                item = Some(format!("{}.{}", owner, field.trim()));
+                break;
+            }
+            attributes.push_str(line);
+        }
+        let item = item.unwrap_or_else(|| fail(&source, line_number, "no declaration after"));
+
+        changes.push(Change {
+            date: date.to_string(),
+            kind,
+            item,
+            endpoint: route_endpoint(&attributes),
+            description: description.to_string(),
+            source: source.clone(),
+        });
+    }
+}
+
+fn fail(source: &str, line: usize, reason: &str) -> ! {
+    panic!(
+        "{}:{}: {} in api-change annotation, expected \
+         `// api-change YYYY-MM-DD <added|changed|deprecated|removed>: <description>`",
+        source, line, reason
+    )
+}
+
+// Name declared by the line, e.g. `pub async fn name(` or `pub struct Name {`
+fn declared_name(line: &str, keyword: &str) -> Option<String> {
+    let line = line.strip_prefix("pub ")?;
+    let line = line.strip_prefix("async ").unwrap_or(line);
+    let rest = line.strip_prefix(keyword)?;
+    let name: String = rest
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    (!name.is_empty()).then_some(name)
+}
+
+// `METHOD /api/path` of a Rocket route attribute, without its query
+fn route_endpoint(attributes: &str) -> Option<String> {
+    ROUTE_METHODS.iter().find_map(|method| {
+        let start = attributes.find(&format!("#[{}(", method))?;
+        let rest = &attributes[start..];
+        let path = rest.split('"').nth(1)?;
+        let path = path.split('?').next().unwrap_or(path);
+        Some(format!("{} /api{}", method.to_uppercase(), path))
+    })
+}
+
+fn valid_date(date: &str) -> bool {
+    let parts: Vec<&str> = date.split('-').collect();
+    matches!(parts.as_slice(), [year, month, day]
+        if year.len() == 4 && month.len() == 2 && day.len() == 2
+            && parts.iter().all(|part| part.chars().all(|c| c.is_ascii_digit()))
+            && (1..=12).contains(&month.parse::<u32>().unwrap_or(0))
+            && (1..=31).contains(&day.parse::<u32>().unwrap_or(0)))
+}
@@ -0,0 +1,397 @@
+-- Initial schema: every table of the application as of the switch to migrations.
+-- IF NOT EXISTS lets databases created by the former util/create_database.sql
+-- adopt the migrations without being recreated.
+
+-- Table aircraft
+create table IF NOT EXISTS aircraft
+(
+    aircraft_id     int                    not null
+        primary key,
+    capacity        int                    not null,
+    seats_per_row   int          default 6  not null,
+    exit_rows       varchar(255) default '' not null,
+    accessible_rows varchar(255) default '' not null
+);
+
+-- Table: User
+create table IF NOT EXISTS user
+(
+    id       int auto_increment
+        primary key,
+    username char(255)                             not null,
+    password char(255)                             not null,
+    role     enum ('ADMIN', 'USER') default 'USER' not null,
+    constraint user_username_uindex
+        unique (username)
+);
+
+-- Table Customer Info
+create table IF NOT EXISTS customer_info
+(
+    id         int                     not null
+        primary key,
+    name       char(255)               not null,
+    birth_date date                    not null,
+    gender     enum ('male', 'female') not null,
+    email      varchar(255)            null,
+    email_verified boolean default false   not null,
+    phone      varchar(32)             null,
+    constraint customer_info_user_id_fk
+        foreign key (id) references user (id)
+            on delete cascade
+);
+
+-- Table email verification token: single-use tokens proving the user owns an address
+create table IF NOT EXISTS email_verification_token
+(
+    id         int auto_increment
+        primary key,
+    user_id    int          not null,
+    email      varchar(255) not null,
+    token_hash char(255)    not null,
+    created_at datetime     not null,
+    expires_at datetime     not null,
+    used_at    datetime     null,
+    constraint email_verification_token_user_id_fk
+        foreign key (user_id) references user (id)
+            on delete cascade
+);
+
+-- Table password reset token: single-use tokens to set a new password
+create table IF NOT EXISTS password_reset_token
+(
+    id         int auto_increment
+        primary key,
+    user_id    int       not null,
+    token_hash char(255) not null,
+    created_at datetime  not null,
+    expires_at datetime  not null,
+    used_at    datetime  null,
+    constraint password_reset_token_user_id_fk
+        foreign key (user_id) references user (id)
+            on delete cascade
+);
+
+-- Table flightRoute route
+create table IF NOT EXISTS flight_route
+(
+    flight_number    int                        not null
+        primary key,
+    departure_city   char(255)                  not null,
+    destination_city char(255)                  not null,
+    departure_time   time                       not null,
+    arrival_time     time                       not null,
+    aircraft_id      int                        not null,
+    overbooking      decimal(4, 2) default 0.00 not null,
+    start_date       date                       not null,
+    end_date         date                       null,
+    um_quota         int           default 4    not null,
+    base_fare        decimal(10, 2) default 0.00 not null,
+    operating_days   char(7)       default '1234567' not null,
+    constraint flight_route_aircraft_aircraft_id_fk
+        foreign key (aircraft_id) references aircraft (aircraft_id)
+            on update cascade on delete cascade
+);
+
+-- Table fare: price of each fare class on a flight route, sold for a section of seat rows
+create table IF NOT EXISTS fare
+(
+    flight_number int                                       not null,
+    fare_class    enum ('ECONOMY', 'BUSINESS', 'FIRST')     not null,
+    base_price    decimal(10, 2)                            not null,
+    currency      char(3)                   default 'CAD'   not null,
+    first_row     int                                       null,
+    last_row      int                                       null,
+    primary key (flight_number, fare_class),
+    constraint fare_flight_route_flight_number_fk
+        foreign key (flight_number) references flight_route (flight_number)
+            on update cascade on delete cascade
+);
+
+-- Table flight
+create table IF NOT EXISTS flight
+(
+    flight_id         int auto_increment
+        primary key,
+    flight_number     int  not null,
+    flight_date       date not null,
+    available_tickets int  not null,
+    version           int  null,
+    status            enum ('SCHEDULED', 'DELAYED', 'BOARDING', 'DEPARTED', 'CANCELLED') default 'SCHEDULED' not null,
+    delay_minutes     int          default 0 not null,
+    delay_reason      varchar(255) null,
+    status_updated_at datetime     null,
+    -- Aircraft swapped in for this flight, the route's aircraft when null
+    aircraft_id       int          null,
+    -- Set by an admin close-out, the tickets and seats of the flight are frozen from then on
+    closed_at         datetime     null,
+    -- Bumped by MySQL on every change, partners sync availability from it
+    updated_at        datetime(6)  default current_timestamp(6) not null on update current_timestamp(6),
+    constraint flight_flight_route_flight_number_fk
+        foreign key (flight_number) references flight_route (flight_number)
+            on update cascade on delete cascade,
+    constraint flight_aircraft_aircraft_id_fk
+        foreign key (aircraft_id) references aircraft (aircraft_id)
+            on update cascade
+);
+
+-- Table flight seat info
+create table IF NOT EXISTS seat_info
+(
+    flight_id   int                                                             not null,
+    seat_number int                                                             not null,
+    seat_status enum ('AVAILABLE', 'UNAVAILABLE', 'BOOKED', 'HELD') default 'AVAILABLE' not null,
+    version     int                                         default 0           not null,
+    held_by     int                                                             null,
+    held_until  datetime                                                        null,
+    updated_at  datetime(6) default current_timestamp(6) not null on update current_timestamp(6),
+    constraint seat_info_flight_id_seat_number_uindex
+        unique (flight_id, seat_number),
+    constraint seat_info_flight_flight_id_fk
+        foreign key (flight_id) references flight (flight_id)
+            on delete cascade,
+    primary key (flight_id, seat_number)
+);
+
+-- Table booking: tickets booked together in one request
+create table IF NOT EXISTS booking
+(
+    id          int auto_increment
+        primary key,
+    customer_id int                                                    not null,
+    status      enum ('PENDING_PAYMENT', 'CONFIRMED', 'EXPIRED', 'CANCELLED') not null,
+    created_at  datetime                                               not null,
+    constraint booking_customer_info_id_fk
+        foreign key (customer_id) references customer_info (id)
+            on delete cascade
+);
+
+-- Table payment
+create table IF NOT EXISTS payment
+(
+    id                 int auto_increment
+        primary key,
+    booking_id         int                                                  not null,
+    amount             decimal(10, 2)                                       not null,
+    currency           char(3)                                              not null,
+    status             enum ('PENDING', 'PROCESSING', 'CAPTURED', 'EXPIRED') not null,
+    provider           char(64)                                             null,
+    provider_reference char(255)                                            null,
+    created_at         datetime                                             not null,
+    expires_at         datetime                                             not null,
+    captured_at        datetime                                             null,
+    constraint payment_booking_id_fk
+        foreign key (booking_id) references booking (id)
+            on delete cascade
+);
+
+-- Table ticket
+create table IF NOT EXISTS ticket
+(
+    id            int auto_increment
+        primary key,
+    customer_id   int  not null,
+    flight_id     int  not null,
+    seat_number   int  null,
+    flight_date   date not null,
+    flight_number int  not null,
+    unaccompanied_minor boolean default false not null,
+    booking_id    int  null,
+    fare_class    enum ('ECONOMY', 'BUSINESS', 'FIRST') default 'ECONOMY' not null,
+    price         decimal(10, 2)                        default 0.00      not null,
+    currency      char(3)                               default 'CAD'     not null,
+    overbooked    boolean                               default false     not null,
+    needs_rebooking boolean                             default false     not null,
+    constraint ticket_customer_info_id_fk
+        foreign key (customer_id) references customer_info (id)
+            on delete cascade,
+    constraint ticket_flight_id_fk
+        foreign key (flight_id) references flight (flight_id)
+            on delete cascade,
+    constraint ticket_seat_info_flight_id_seat_number_fk
+        foreign key (flight_id, seat_number) references seat_info (flight_id, seat_number),
+    constraint ticket_booking_id_fk
+        foreign key (booking_id) references booking (id)
+            on delete set null
+);
+
+-- Table location: localized city names
+create table IF NOT EXISTS location
+(
+    city     char(255) not null,
+    language char(8)   not null,
+    name     char(255) not null,
+    primary key (city, language)
+);
+
+-- Table route stats: daily search and booking counters per route
+create table IF NOT EXISTS route_stats
+(
+    departure_city   char(255)     not null,
+    destination_city char(255)     not null,
+    stat_date        date          not null,
+    searches         int default 0 not null,
+    bookings         int default 0 not null,
+    primary key (departure_city, destination_city, stat_date)
+);
+
+-- Table flight view: flights recently viewed by each user
+create table IF NOT EXISTS flight_view
+(
+    user_id   int         not null,
+    flight_id int         not null,
+    viewed_at datetime(6) not null,
+    primary key (user_id, flight_id),
+    constraint flight_view_user_id_fk
+        foreign key (user_id) references user (id)
+            on delete cascade,
+    constraint flight_view_flight_id_fk
+        foreign key (flight_id) references flight (flight_id)
+            on delete cascade
+);
+
+-- Table route audit: changes made by admins to flight routes
+create table IF NOT EXISTS route_audit
+(
+    id            int auto_increment
+        primary key,
+    flight_number int          not null,
+    admin_id      int          not null,
+    field         char(64)     not null,
+    old_value     varchar(255) null,
+    new_value     varchar(255) null,
+    changed_at    datetime     not null,
+    constraint route_audit_flight_route_flight_number_fk
+        foreign key (flight_number) references flight_route (flight_number)
+            on update cascade on delete cascade,
+    constraint route_audit_user_id_fk
+        foreign key (admin_id) references user (id)
+);
+
+-- Table unaccompanied minor guardian contact
+create table IF NOT EXISTS unaccompanied_minor
+(
+    ticket_id             int       not null
+        primary key,
+    guardian_name         char(255) not null,
+    guardian_phone        char(32)  not null,
+    guardian_relationship char(64)  not null,
+    constraint unaccompanied_minor_ticket_id_fk
+        foreign key (ticket_id) references ticket (id)
+            on delete cascade
+);
+
+-- Table rebooking: passengers moved off cancelled flights
+create table IF NOT EXISTS rebooking
+(
+    id                 int auto_increment
+        primary key,
+    ticket_id          int                         not null,
+    customer_id        int                         not null,
+    flight_id          int                         not null,
+    rebooked_ticket_id int                         null,
+    status             enum ('REBOOKED', 'FAILED') not null,
+    reason             varchar(255)                null,
+    created_at         datetime                    not null,
+    constraint rebooking_customer_info_id_fk
+        foreign key (customer_id) references customer_info (id)
+            on delete cascade,
+    constraint rebooking_flight_id_fk
+        foreign key (flight_id) references flight (flight_id)
+            on delete cascade,
+    constraint rebooking_ticket_id_fk
+        foreign key (rebooked_ticket_id) references ticket (id)
+            on delete set null
+);
+
+-- Table funnel event: booking funnel steps reached by anonymous client sessions
+create table IF NOT EXISTS funnel_event
+(
+    id            bigint auto_increment
+        primary key,
+    session_id    varchar(64)                                                                  not null,
+    step          enum ('SEARCH', 'SEAT_MAP_VIEWED', 'BOOKING_ATTEMPTED', 'BOOKING_CONFIRMED') not null,
+    flight_number int                                                                          null,
+    created_at    datetime                                                                     not null
+);
+
+-- Table bump: passengers bumped from oversold flights
+create table IF NOT EXISTS bump
+(
+    id                 int auto_increment
+        primary key,
+    ticket_id          int      not null,
+    customer_id        int      not null,
+    flight_id          int      not null,
+    rebooked_ticket_id int      null,
+    admin_id           int      not null,
+    bumped_at          datetime not null,
+    constraint bump_customer_info_id_fk
+        foreign key (customer_id) references customer_info (id)
+            on delete cascade,
+    constraint bump_flight_id_fk
+        foreign key (flight_id) references flight (flight_id)
+            on delete cascade,
+    constraint bump_ticket_id_fk
+        foreign key (rebooked_ticket_id) references ticket (id)
+            on delete set null,
+    constraint bump_user_id_fk
+        foreign key (admin_id) references user (id)
+);
+
+-- Table seat reassignment: seats of passengers moved by an aircraft swap
+create table IF NOT EXISTS seat_reassignment
+(
+    id              int auto_increment
+        primary key,
+    flight_id       int                                 not null,
+    ticket_id       int                                 not null,
+    customer_id     int                                 not null,
+    old_seat_number int                                 not null,
+    new_seat_number int                                 null,
+    status          enum ('SAME', 'MOVED', 'UNMAPPED') not null,
+    admin_id        int                                 not null,
+    reassigned_at   datetime                            not null,
+    constraint seat_reassignment_customer_info_id_fk
+        foreign key (customer_id) references customer_info (id)
+            on delete cascade,
+    constraint seat_reassignment_flight_id_fk
+        foreign key (flight_id) references flight (flight_id)
+            on delete cascade,
+    constraint seat_reassignment_user_id_fk
+        foreign key (admin_id) references user (id)
+);
+
+-- Table ticket correction: changes made by admins to tickets of closed flights
+create table IF NOT EXISTS ticket_correction
+(
+    id              int auto_increment
+        primary key,
+    ticket_id       int                                                          not null,
+    flight_id       int                                                          not null,
+    admin_id        int                                                          not null,
+    reason          enum ('SEAT_CHANGED_ONBOARD', 'DATA_ENTRY_ERROR', 'OPERATIONAL') not null,
+    old_seat_number int                                                          null,
+    new_seat_number int                                                          null,
+    note            varchar(255)                                                 null,
+    corrected_at    datetime                                                     not null,
+    constraint ticket_correction_ticket_id_fk
+        foreign key (ticket_id) references ticket (id)
+            on delete cascade,
+    constraint ticket_correction_flight_id_fk
+        foreign key (flight_id) references flight (flight_id)
+            on delete cascade,
+    constraint ticket_correction_user_id_fk
+        foreign key (admin_id) references user (id)
+);
+
+-- Table partner api key: keys of travel agencies using the partner endpoints
+create table IF NOT EXISTS partner_api_key
+(
+    id           int auto_increment
+        primary key,
+    partner_name varchar(255) not null,
+    key_hash     char(64)     not null,
+    created_at   datetime     not null,
+    revoked_at   datetime     null
+);
@@ -0,0 +1,11 @@
+-- Booking reference (PNR) of every ticket, quoted by customers instead of the ticket id
+alter table ticket
+    add column booking_reference char(6) null,
+    add constraint ticket_booking_reference_uindex
+        unique (booking_reference);
+
+-- Tickets sold before get a reference derived from their id. Multiplying by a
+-- number coprime with 36^6 keeps the references distinct.
+update ticket
+set booking_reference = lpad(conv(mod(id * 1299709, 2176782336), 10, 36), 6, '0')
+where booking_reference is null;
@@ -0,0 +1,11 @@
+-- Check-in: when the passenger checked in and their place in the boarding order,
+-- numbered from 1 per flight in check-in order
+alter table ticket
+    add column checked_in_at datetime null,
+    add column boarding_sequence int null,
+    add constraint ticket_boarding_sequence_uindex
+        unique (flight_id, boarding_sequence);
+
+-- Departure gate, printed on boarding passes once assigned
+alter table flight
+    add column gate varchar(8) null;
@@ -0,0 +1,20 @@
+-- Fare hold: a fee paid to keep the price and the tickets of an unpaid booking for
+-- longer than the normal payment window. A booking can be held once.
+create table IF NOT EXISTS fare_hold
+(
+    id                 int auto_increment
+        primary key,
+    booking_id         int            not null,
+    hours              int            not null,
+    fee                decimal(10, 2) not null,
+    currency           char(3)        not null,
+    provider           char(64)       not null,
+    provider_reference char(255)      not null,
+    created_at         datetime       not null,
+    expires_at         datetime       not null,
+    constraint fare_hold_booking_id_uindex
+        unique (booking_id),
+    constraint fare_hold_booking_id_fk
+        foreign key (booking_id) references booking (id)
+            on delete cascade
+);
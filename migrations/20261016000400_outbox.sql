@@ -0,0 +1,12 @@
+-- Transactional outbox: domain events are written in the transaction of the change they
+-- describe, and published to the event bus by the dispatcher once committed
+create table IF NOT EXISTS outbox_event
+(
+    id           bigint auto_increment
+        primary key,
+    event_type   varchar(64) not null,
+    payload      json        not null,
+    created_at   datetime    not null,
+    published_at datetime    null,
+    index outbox_event_published_at_index (published_at, id)
+);
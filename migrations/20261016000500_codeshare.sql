@@ -0,0 +1,11 @@
+-- Table codeshare: flight numbers of partner airlines marketing the flights of a route
+create table IF NOT EXISTS codeshare
+(
+    flight_number           int     not null,
+    marketing_carrier       char(2) not null,
+    marketing_flight_number int     not null,
+    primary key (marketing_carrier, marketing_flight_number),
+    constraint codeshare_flight_route_flight_number_fk
+        foreign key (flight_number) references flight_route (flight_number)
+            on update cascade on delete cascade
+);
@@ -0,0 +1,7 @@
+-- Data region of customers and bookings, for deployments where the data of some regions
+-- has to be kept apart. Rows created before belong to the default region.
+alter table customer_info
+    add column data_region varchar(16) default 'global' not null;
+
+alter table booking
+    add column data_region varchar(16) default 'global' not null;
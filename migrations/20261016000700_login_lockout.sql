@@ -0,0 +1,18 @@
+-- Every login attempt, kept for auditing
+create table IF NOT EXISTS login_attempt
+(
+    id           bigint auto_increment
+        primary key,
+    username     char(255)   not null,
+    ip_address   varchar(45) null,
+    succeeded    tinyint(1)  not null,
+    attempted_at datetime    not null,
+    index login_attempt_username_index (username, attempted_at)
+);
+
+-- Failed logins since the last successful one, and the end of the lockout they caused
+alter table user
+    add column failed_login_count int default 0 not null;
+
+alter table user
+    add column locked_until datetime null;
@@ -0,0 +1,8 @@
+-- Registrations used to create the user before its customer info without a transaction,
+-- so a failure in between left a user that could not book and kept the username taken.
+-- Remove those customers. Admins are kept, their actions refer to them.
+delete u
+from user u
+    left join customer_info c on c.id = u.id
+where c.id is null
+  and u.role = 'USER';
@@ -0,0 +1,7 @@
+-- Public id of every ticket, a ULID shown to customers and partners instead of the
+-- auto-increment id, which gives away how many tickets were sold. Tickets sold before
+-- get theirs from the server at startup.
+alter table ticket
+    add column public_id char(26) null,
+    add constraint ticket_public_id_uindex
+        unique (public_id);
@@ -0,0 +1,35 @@
+-- Refund of a cancelled paid ticket. The ticket is deleted when cancelled, so its
+-- booking reference and flight are kept to show the customer what was refunded.
+create table IF NOT EXISTS refund
+(
+    id                 int auto_increment
+        primary key,
+    payment_id         int                                                    not null,
+    customer_id        int                                                    not null,
+    ticket_id          int                                                    not null,
+    booking_reference  char(6)                                                null,
+    flight_number      int                                                    not null,
+    flight_date        date                                                   not null,
+    fare_class         enum ('ECONOMY', 'BUSINESS', 'FIRST')                  not null,
+    ticket_price       decimal(10, 2)                                         not null,
+    amount             decimal(10, 2)                                         not null,
+    currency           char(3)                                                not null,
+    status             enum ('PENDING', 'PROCESSING', 'REFUNDED', 'FAILED')   not null,
+    provider           char(64)                                               null,
+    provider_reference char(255)                                              null,
+    failure_reason     varchar(255)                                           null,
+    created_at         datetime                                               not null,
+    processed_at       datetime                                               null,
+    constraint refund_payment_id_fk
+        foreign key (payment_id) references payment (id)
+            on delete cascade,
+    constraint refund_customer_id_fk
+        foreign key (customer_id) references customer_info (id)
+            on delete cascade
+);
+
+create index refund_customer_id_index
+    on refund (customer_id, created_at);
+
+create index refund_status_index
+    on refund (status);
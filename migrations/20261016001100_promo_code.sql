@@ -0,0 +1,48 @@
+-- Promo codes giving a discount on the fare of a booking. A fixed discount is in the
+-- currency of the code, a percentage discount applies to every ticket of the booking.
+create table IF NOT EXISTS promo_code
+(
+    id                           int auto_increment
+        primary key,
+    code                         varchar(32)                   not null,
+    discount_type                enum ('PERCENTAGE', 'FIXED')  not null,
+    discount_value               decimal(10, 2)                not null,
+    currency                     char(3)                       null,
+    expires_at                   datetime                      null,
+    max_redemptions              int                           null,
+    max_redemptions_per_customer int                           null,
+    redemptions                  int default 0                 not null,
+    created_by                   int                           not null,
+    created_at                   datetime                      not null,
+    constraint promo_code_code_uindex
+        unique (code),
+    constraint promo_code_created_by_fk
+        foreign key (created_by) references user (id)
+);
+
+-- Use of a promo code by a booking. Removed again when the booking expires unpaid.
+create table IF NOT EXISTS promo_redemption
+(
+    id            int auto_increment
+        primary key,
+    promo_code_id int            not null,
+    booking_id    int            not null,
+    customer_id   int            not null,
+    discount      decimal(10, 2) not null,
+    currency      char(3)        not null,
+    redeemed_at   datetime       not null,
+    constraint promo_redemption_booking_id_uindex
+        unique (booking_id),
+    constraint promo_redemption_promo_code_id_fk
+        foreign key (promo_code_id) references promo_code (id)
+            on delete cascade,
+    constraint promo_redemption_booking_id_fk
+        foreign key (booking_id) references booking (id)
+            on delete cascade,
+    constraint promo_redemption_customer_id_fk
+        foreign key (customer_id) references customer_info (id)
+            on delete cascade
+);
+
+create index promo_redemption_customer_id_index
+    on promo_redemption (promo_code_id, customer_id);
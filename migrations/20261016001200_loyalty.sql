@@ -0,0 +1,35 @@
+-- Loyalty points of a customer. The balance is the sum of the ledger entries of the
+-- customer, kept here so redemptions can lock it.
+create table IF NOT EXISTS loyalty_account
+(
+    customer_id int           not null
+        primary key,
+    balance     int default 0 not null,
+    updated_at  datetime      not null,
+    constraint loyalty_account_customer_id_fk
+        foreign key (customer_id) references customer_info (id)
+            on delete cascade
+);
+
+-- Every change of a loyalty balance. Points are earned once per flown ticket and
+-- given back once per refunded ticket, the ticket itself may be gone by then.
+create table IF NOT EXISTS loyalty_ledger
+(
+    id          int auto_increment
+        primary key,
+    customer_id int                                 not null,
+    entry_type  enum ('EARN', 'REDEEM', 'REFUND')   not null,
+    points      int                                 not null,
+    ticket_id   int                                 null,
+    booking_id  int                                 null,
+    description varchar(255)                        not null,
+    created_at  datetime                            not null,
+    constraint loyalty_ledger_ticket_id_entry_type_uindex
+        unique (ticket_id, entry_type),
+    constraint loyalty_ledger_customer_id_fk
+        foreign key (customer_id) references customer_info (id)
+            on delete cascade
+);
+
+create index loyalty_ledger_customer_id_index
+    on loyalty_ledger (customer_id, id);
@@ -0,0 +1,20 @@
+-- Booking curve of each flight: tickets booked on each number of days before departure,
+-- counted from the booking events. Revenue managers forecast demand from the curves of
+-- past flights of a route.
+create table IF NOT EXISTS booking_curve
+(
+    flight_id             int           not null,
+    days_before_departure int           not null,
+    bookings              int default 0 not null,
+    primary key (flight_id, days_before_departure),
+    constraint booking_curve_flight_id_fk
+        foreign key (flight_id) references flight (flight_id)
+            on delete cascade
+);
+
+-- Curves of the tickets booked before the events were counted
+insert into booking_curve (flight_id, days_before_departure, bookings)
+select t.flight_id, greatest(datediff(t.flight_date, date(b.created_at)), 0), count(*)
+from ticket t
+         join booking b on b.id = t.booking_id
+group by t.flight_id, greatest(datediff(t.flight_date, date(b.created_at)), 0);
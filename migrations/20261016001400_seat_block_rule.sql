@@ -0,0 +1,27 @@
+-- Seats of a route kept out of sale on the flights between start_date and end_date,
+-- e.g. every middle seat. Enabled rules are applied to flights when they are generated.
+create table IF NOT EXISTS seat_block_rule
+(
+    id            int auto_increment
+        primary key,
+    flight_number int                                not null,
+    rule          enum ('MIDDLE_SEATS', 'LAST_ROW')  not null,
+    start_date    date                               not null,
+    end_date      date                               null,
+    enabled       boolean default true               not null,
+    created_by    int                                not null,
+    created_at    datetime                           not null,
+    updated_at    datetime                           not null,
+    constraint seat_block_rule_flight_route_flight_number_fk
+        foreign key (flight_number) references flight_route (flight_number)
+            on update cascade on delete cascade,
+    constraint seat_block_rule_user_id_fk
+        foreign key (created_by) references user (id)
+);
+
+-- Rule that blocked the seat, so disabling the rule gives back only the seats it took
+alter table seat_info
+    add column block_rule_id int null,
+    add constraint seat_info_seat_block_rule_id_fk
+        foreign key (block_rule_id) references seat_block_rule (id)
+            on delete set null;
@@ -0,0 +1,36 @@
+-- Rows with more legroom than the others, comma separated like exit_rows
+alter table aircraft
+    add column extra_legroom_rows varchar(255) default '' not null;
+
+-- Price of a seat charged when a customer picks it for their ticket. What a ticket
+-- already paid for seats counts towards the next seat picked for it. The ticket is
+-- deleted when cancelled, the charge is kept.
+create table IF NOT EXISTS seat_charge
+(
+    id                 int auto_increment
+        primary key,
+    ticket_id          int                                                   null,
+    customer_id        int                                                   not null,
+    flight_id          int                                                   not null,
+    seat_number        int                                                   not null,
+    amount             decimal(10, 2)                                        not null,
+    currency           char(3)                                               not null,
+    status             enum ('PENDING', 'CAPTURED', 'FAILED', 'REFUNDED')    not null,
+    provider           char(64)                                              null,
+    provider_reference char(255)                                             null,
+    failure_reason     varchar(255)                                          null,
+    created_at         datetime                                              not null,
+    captured_at        datetime                                              null,
+    constraint seat_charge_ticket_id_fk
+        foreign key (ticket_id) references ticket (id)
+            on delete set null,
+    constraint seat_charge_customer_id_fk
+        foreign key (customer_id) references customer_info (id)
+            on delete cascade,
+    constraint seat_charge_flight_id_fk
+        foreign key (flight_id) references flight (flight_id)
+            on delete cascade
+);
+
+create index seat_charge_ticket_id_index
+    on seat_charge (ticket_id, status);
@@ -0,0 +1,3 @@
+-- Special service request codes of the passenger on the ticket, e.g. WCHR,VGML
+alter table ticket
+    add column ssr_codes varchar(255) default '' not null;
@@ -0,0 +1,19 @@
+-- Table op_up: passengers booked into a higher cabin at their own fare because the
+-- cabin of their fare class was sold out
+create table IF NOT EXISTS op_up
+(
+    id                int auto_increment
+        primary key,
+    ticket_id         int                                   not null,
+    flight_id         int                                   not null,
+    booked_fare_class enum ('ECONOMY', 'BUSINESS', 'FIRST') not null,
+    cabin_fare_class  enum ('ECONOMY', 'BUSINESS', 'FIRST') not null,
+    reason            varchar(255)                          not null,
+    created_at        datetime                              not null,
+    constraint op_up_ticket_id_fk
+        foreign key (ticket_id) references ticket (id)
+            on delete cascade,
+    constraint op_up_flight_id_fk
+        foreign key (flight_id) references flight (flight_id)
+            on delete cascade
+);
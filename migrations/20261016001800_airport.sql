@@ -0,0 +1,58 @@
+-- Table airport: airports by IATA code, with the city searches also match on
+create table IF NOT EXISTS airport
+(
+    code     char(3)      not null
+        primary key,
+    city     char(255)    not null,
+    name     varchar(255) not null,
+    timezone varchar(64)  not null
+);
+
+insert ignore into airport (code, city, name, timezone)
+values ('YYZ', 'Toronto', 'Toronto Pearson International Airport', 'America/Toronto'),
+       ('YUL', 'Montreal', 'Montreal-Trudeau International Airport', 'America/Toronto'),
+       ('YOW', 'Ottawa', 'Ottawa Macdonald-Cartier International Airport', 'America/Toronto'),
+       ('YQB', 'Quebec City', 'Quebec City Jean Lesage International Airport', 'America/Toronto'),
+       ('YHZ', 'Halifax', 'Halifax Stanfield International Airport', 'America/Halifax'),
+       ('YWG', 'Winnipeg', 'Winnipeg James Armstrong Richardson International Airport',
+        'America/Winnipeg'),
+       ('YYC', 'Calgary', 'Calgary International Airport', 'America/Edmonton'),
+       ('YEG', 'Edmonton', 'Edmonton International Airport', 'America/Edmonton'),
+       ('YVR', 'Vancouver', 'Vancouver International Airport', 'America/Vancouver'),
+       ('JFK', 'New York', 'John F. Kennedy International Airport', 'America/New_York'),
+       ('LAX', 'Los Angeles', 'Los Angeles International Airport', 'America/Los_Angeles');
+
+-- Airports a route flies between. Routes of cities without a known airport keep none.
+alter table flight_route
+    add column departure_airport   char(3) null,
+    add column destination_airport char(3) null,
+    add constraint flight_route_departure_airport_fk
+        foreign key (departure_airport) references airport (code)
+            on update cascade,
+    add constraint flight_route_destination_airport_fk
+        foreign key (destination_airport) references airport (code)
+            on update cascade;
+
+-- Routes stored with an airport code for a city get the city name and the airport
+update flight_route fr
+    join airport a on fr.departure_city = a.code
+set fr.departure_city    = a.city,
+    fr.departure_airport = a.code;
+
+update flight_route fr
+    join airport a on fr.destination_city = a.code
+set fr.destination_city    = a.city,
+    fr.destination_airport = a.code;
+
+-- and routes of a city with one known airport get that airport
+update flight_route fr
+    join airport a on fr.departure_city = a.city
+set fr.departure_airport = a.code
+where fr.departure_airport is null
+  and (select count(*) from airport other where other.city = a.city) = 1;
+
+update flight_route fr
+    join airport a on fr.destination_city = a.city
+set fr.destination_airport = a.code
+where fr.destination_airport is null
+  and (select count(*) from airport other where other.city = a.city) = 1;
@@ -0,0 +1,19 @@
+-- Table booking_request: bookings queued in the queued booking mode, polled by the
+-- client until the booking is confirmed or failed
+create table IF NOT EXISTS booking_request
+(
+    request_id   char(26)                                 not null
+        primary key,
+    customer_id  int                                      not null,
+    status       enum ('PENDING', 'CONFIRMED', 'FAILED')  not null,
+    booking_id   int                                      null,
+    error        varchar(255)                             null,
+    created_at   datetime                                 not null,
+    completed_at datetime                                 null,
+    constraint booking_request_customer_id_fk
+        foreign key (customer_id) references customer_info (id)
+            on delete cascade,
+    constraint booking_request_booking_id_fk
+        foreign key (booking_id) references booking (id)
+            on delete set null
+);
@@ -0,0 +1,39 @@
+-- Table booking_saga: bookings being placed, deleted in the transaction that creates the
+-- booking or reverts it. One left behind was interrupted, e.g. by a crash, and is
+-- reverted by the recovery task.
+create table IF NOT EXISTS booking_saga
+(
+    id          int auto_increment
+        primary key,
+    customer_id int      not null,
+    created_at  datetime not null,
+    constraint booking_saga_customer_id_fk
+        foreign key (customer_id) references customer_info (id)
+            on delete cascade
+);
+
+create index booking_saga_created_at_index
+    on booking_saga (created_at);
+
+-- Tickets a booking saga took from the flight inventory, one per leg. Those no ticket of
+-- the saga was booked with are given back when the saga ends.
+create table IF NOT EXISTS booking_saga_claim
+(
+    id        int auto_increment
+        primary key,
+    saga_id   int not null,
+    flight_id int not null,
+    constraint booking_saga_claim_saga_id_fk
+        foreign key (saga_id) references booking_saga (id)
+            on delete cascade,
+    constraint booking_saga_claim_flight_id_fk
+        foreign key (flight_id) references flight (flight_id)
+            on delete cascade
+);
+
+-- Saga that booked the ticket, cleared once the booking is created
+alter table ticket
+    add column booking_saga_id int null,
+    add constraint ticket_booking_saga_id_fk
+        foreign key (booking_saga_id) references booking_saga (id)
+            on delete set null;
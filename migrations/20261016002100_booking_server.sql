@@ -0,0 +1,13 @@
+-- Table booking_server: servers queuing bookings, each seen again every minute while it
+-- runs. The bookings still pending of a server no longer seen were lost with it.
+create table IF NOT EXISTS booking_server
+(
+    server_id char(26) not null
+        primary key,
+    seen_at   datetime not null
+);
+
+-- Server whose queue holds the booking, null for bookings queued before servers were
+-- recorded
+alter table booking_request
+    add column server_id char(26) null;
@@ -0,0 +1,45 @@
+// Compare the live database schema with what the application expects.
+//
+// Usage: check_schema
+//
+// Exits with a non-zero status when the schema has drifted, printing how to fix it.
+use airline_booking_system::utils::config::AppConfig;
+use airline_booking_system::utils::schema_check;
+use dotenv::dotenv;
+use std::process::ExitCode;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv().ok();
+
+    let config = match AppConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let pool = config
+        .database
+        .connect()
+        .await
+        .expect("Failed to connect to database");
+
+    match schema_check::check_schema(&pool).await {
+        Ok(drifts) if drifts.is_empty() => {
+            println!("Database schema is up to date");
+            ExitCode::SUCCESS
+        }
+        Ok(drifts) => {
+            println!("Found {} schema differences:", drifts.len());
+            for drift in &drifts {
+                println!("  {}", drift);
+            }
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            eprintln!("Failed to check database schema: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,97 @@
+// Replay the booking operation log against a restored database snapshot.
+//
+// Usage: replay_operations <log-path> [--since <YYYY-MM-DDTHH:MM:SS>]
+//
+// Only operations recorded at or after --since (the time of the snapshot, in UTC)
+// are replayed. Without it, the whole log is replayed.
+use airline_booking_system::services::operation_log::{self, OperationLog};
+use airline_booking_system::services::payment_service::{MockPaymentProvider, PaymentService};
+use airline_booking_system::services::ticket_service::TicketService;
+use airline_booking_system::utils::config::AppConfig;
+use chrono::NaiveDateTime;
+use dotenv::dotenv;
+use std::process::ExitCode;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv().ok();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (path, since) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("{}", message);
+            eprintln!("Usage: replay_operations <log-path> [--since <YYYY-MM-DDTHH:MM:SS>]");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let records = match OperationLog::read(&path).await {
+        Ok(records) => records,
+        Err(e) => {
+            eprintln!("Failed to read operation log {}: {}", path, e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let config = match AppConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let pool = config
+        .database
+        .connect()
+        .await
+        .expect("Failed to connect to database");
+    // Replayed operations must not be logged again. Replayed payments are not
+    // captured again, the provider is never called.
+    let ticket_service = TicketService::new(pool.clone());
+    let payment_service = PaymentService::new(pool, std::sync::Arc::new(MockPaymentProvider));
+
+    match operation_log::replay(&ticket_service, &payment_service, &records, since).await {
+        Ok(summary) => {
+            println!(
+                "Replayed {} operations, skipped {}, failed {}",
+                summary.replayed,
+                summary.skipped,
+                summary.failed.len()
+            );
+            for failure in &summary.failed {
+                println!("  {}", failure);
+            }
+            if summary.failed.is_empty() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(e) => {
+            eprintln!("Replay failed: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<(String, Option<NaiveDateTime>), String> {
+    let mut path = None;
+    let mut since = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--since" => {
+                let value = args.next().ok_or("Missing value for --since")?;
+                since = Some(
+                    value
+                        .parse::<NaiveDateTime>()
+                        .map_err(|e| format!("Invalid --since {}: {}", value, e))?,
+                );
+            }
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => return Err(format!("Unexpected argument {}", arg)),
+        }
+    }
+    Ok((path.ok_or("Missing log path")?, since))
+}
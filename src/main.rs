@@ -8,51 +8,354 @@ mod services;
 mod swagger;
 mod utils;
 
+use crate::models::booking_rules::{BookingRules, DEFAULT_BOOKING_RULES_PATH};
 use crate::swagger::swagger_ui;
+use crate::utils::config::AppConfig;
+use crate::utils::tunables::{ConfigReloader, SharedTunables};
 use dotenv::dotenv;
-use rocket::fairing::AdHoc;
+use rocket::data::{ByteUnit, Limits};
 use rocket_okapi::openapi_get_routes;
 use rocket_okapi::swagger_ui::*;
-use sqlx::MySqlPool;
+use std::path::Path;
 
 #[launch]
 async fn rocket() -> _ {
     dotenv().ok();
+    utils::telemetry::init();
+
+    // Settings from config.toml and the environment, all checked before anything starts
+    let config = AppConfig::load().expect("Invalid configuration");
+    utils::jwt::configure(config.auth.clone());
+
+    // Limits and cache TTLs follow changes of the configuration file without a restart
+    let tunables = SharedTunables::new(config.tunables());
+    let config_reloader = ConfigReloader::new(config.clone(), tunables.clone());
+    config_reloader.spawn_watcher(std::time::Duration::from_secs(10));
 
     // Connect to the database
-    let pool =
-        MySqlPool::connect(&std::env::var("DATABASE_URL").expect("DATABASE_URL must be set"))
-            .await
-            .expect("Failed to connect to database");
+    let pool = config
+        .database
+        .connect()
+        .await
+        .expect("Failed to connect to database");
+
+    // Apply the schema migrations the database has not seen yet
+    utils::migrations::run(&pool)
+        .await
+        .expect("Failed to run database migrations");
+
+    // Report schema drift now instead of as opaque errors deep in requests
+    match utils::schema_check::check_schema(&pool).await {
+        Ok(drifts) if !drifts.is_empty() => {
+            tracing::warn!("database schema does not match what the application expects");
+            for drift in &drifts {
+                tracing::warn!(drift = %drift, "schema drift");
+            }
+        }
+        Ok(_) => {}
+        Err(e) => tracing::error!(error = %e, "failed to check database schema"),
+    }
+
+    // Event bus connecting the services to background consumers
+    let event_bus = services::event_bus::EventBus::new();
 
     // Initialize the user service
-    let user_service = services::user_service::UserService::new(pool.clone());
-    let flight_service = services::flight_service::FlightService::new(pool.clone());
-    let ticket_service = services::ticket_service::TicketService::new(pool.clone());
+    let user_service = services::user_service::UserService::new(pool.clone())
+        .with_event_bus(event_bus.clone())
+        .with_bcrypt_cost(config.auth.bcrypt_cost)
+        .with_lockout(
+            config.auth.lockout_threshold,
+            chrono::Duration::minutes(config.auth.lockout_minutes),
+        )
+        .with_data_region(config.residency.region.clone());
+    // Repeated searches and seat maps are answered from memory for a few seconds,
+    // bookings drop the answers of the flights they change
+    let search_cache = services::search_cache::SearchCache::new(tunables.clone());
+    let flight_service = services::flight_service::FlightService::new(pool.clone())
+        .with_event_bus(event_bus.clone())
+        .with_tunables(tunables.clone())
+        .with_search_cache(search_cache.clone());
+    // Log booking operations for replay after restoring a database snapshot
+    let operation_log = services::operation_log::OperationLog::open(
+        std::env::var("OPERATION_LOG_PATH").unwrap_or_else(|_| {
+            services::operation_log::DEFAULT_OPERATION_LOG_PATH.to_string()
+        }),
+    )
+    .await
+    .expect("Failed to open operation log");
+    // Booking policy from the rules file, the built-in one when there is none
+    let booking_rules_path = std::env::var("BOOKING_RULES_PATH")
+        .ok()
+        .or_else(|| {
+            Path::new(DEFAULT_BOOKING_RULES_PATH)
+                .exists()
+                .then(|| DEFAULT_BOOKING_RULES_PATH.to_string())
+        });
+    let booking_rules = match booking_rules_path {
+        Some(path) => BookingRules::load(path).expect("Failed to load booking rules"),
+        None => BookingRules::default(),
+    };
+    let ticket_service = services::ticket_service::TicketService::new(pool.clone())
+        .with_operation_log(operation_log.clone())
+        .with_rules(booking_rules)
+        .with_seat_booking_attempts(config.limits.seat_booking_attempts)
+        .with_data_region(config.residency.region.clone())
+        .with_search_cache(search_cache);
+    // Lock seats in Redis while booking them when configured, so the servers keep
+    // customers fighting over a seat out of MySQL. Without the redis feature a configured
+    // URL fails the config validation rather than leaving the seats unlocked.
+    #[cfg(feature = "redis")]
+    let ticket_service = match &config.limits.seat_lock_redis_url {
+        Some(redis_url) => ticket_service.with_seat_lock(std::sync::Arc::new(
+            utils::seat_lock::RedisSeatLock::connect(redis_url)
+                .await
+                .expect("Failed to connect to the seat lock Redis"),
+        )),
+        None => ticket_service,
+    };
+    // Tickets sold before public ids get theirs before anyone can look them up
+    match ticket_service.assign_public_ids().await {
+        Ok(0) => {}
+        Ok(assigned) => tracing::info!(assigned, "assigned public ids to tickets"),
+        Err(e) => tracing::error!(error = %e, "failed to assign public ids to tickets"),
+    }
+    let admin_service = services::admin_service::AdminService::new(pool.clone())
+        .with_event_bus(event_bus.clone());
+    let aircraft_service = services::aircraft_service::AircraftService::new(pool.clone());
+
+    // Capture payments with the mock provider and release unpaid bookings every minute
+    let payment_service = services::payment_service::PaymentService::new(
+        pool.clone(),
+        std::sync::Arc::new(services::payment_service::MockPaymentProvider),
+    )
+    .with_operation_log(operation_log);
+    payment_service.spawn_expiry_task(ticket_service.clone(), std::time::Duration::from_secs(60));
+    let operation_service = services::operation_service::OperationService::new(pool.clone());
+    let promo_code_service = services::promo_code_service::PromoCodeService::new(pool.clone());
+    let report_service = services::report_service::ReportService::new(pool.clone());
+    let seat_block_service = services::seat_block_service::SeatBlockService::new(pool.clone());
+    // Credit loyalty points for departed flights every 5 minutes
+    let loyalty_service = services::loyalty_service::LoyaltyService::new(pool.clone());
+    loyalty_service.spawn_accrual_task(std::time::Duration::from_secs(300));
+    // Give expired seat holds back every 30 seconds
+    ticket_service.spawn_hold_expiry_task(std::time::Duration::from_secs(30));
+    // Revert bookings interrupted halfway, e.g. by a crash, every minute
+    ticket_service.spawn_booking_recovery_task(std::time::Duration::from_secs(60));
+    // In the queued booking mode, bookings are applied by workers, those of a flight
+    // one after the other. Any server reports the status of the queued bookings.
+    let booking_requests = services::booking_queue::BookingRequests::new(pool.clone());
+    // Every minute, record this server as running and fail the queued bookings lost
+    // with a server that stopped
+    booking_requests.spawn_sweeper(std::time::Duration::from_secs(60));
+    let booking_queue = (config.booking.mode == utils::config::BookingMode::Queued).then(|| {
+        services::booking_queue::BookingQueue::start(
+            ticket_service.clone(),
+            booking_requests.clone(),
+            config.booking.queue_workers,
+            config.booking.queue_capacity,
+        )
+        .with_user_share(config.booking.queue_user_share_percent)
+    });
+    let route_stats_service = services::route_stats_service::RouteStatsService::new(pool.clone());
+    route_stats_service.spawn_aggregator(&event_bus);
+    // Store booking funnel events for the conversion report
+    let funnel_service = services::funnel_service::FunnelService::new(pool.clone());
+    funnel_service.spawn_recorder(&event_bus);
+    // Email passengers about their bookings, cancellations and delays
+    let notification_service = services::notification_service::NotificationService::new(
+        pool.clone(),
+        services::notification_service::transport_for(&config.notification)
+            .expect("Invalid notification settings"),
+    );
+    notification_service.spawn_sender(&event_bus);
+    // Publish the booking, cancellation and seat change events once their transaction
+    // has committed, after the consumers above are subscribed
+    services::outbox::OutboxDispatcher::new(pool.clone(), event_bus.clone())
+        .spawn_dispatcher(std::time::Duration::from_millis(500));
+    // Day-by-day availability for travel agency partners
+    let partner_service = services::partner_service::PartnerService::new(pool.clone())
+        .with_tunables(tunables.clone())
+        .with_excluded_regions(&config.residency.partner_excluded_regions);
+    // Copy of the inventory partners can book against with X-Sandbox: true
+    let sandbox_service = match config.database.connect_sandbox().await {
+        Some(sandbox_pool) => {
+            let sandbox_pool = sandbox_pool.expect("Failed to connect to sandbox database");
+            utils::migrations::run(&sandbox_pool)
+                .await
+                .expect("Failed to run sandbox database migrations");
+            Some(
+                services::sandbox_service::SandboxService::new(pool.clone(), sandbox_pool)
+                    .with_live_configuration(&ticket_service, &user_service, &partner_service),
+            )
+        }
+        None => None,
+    };
+    let mut health_service = services::health_service::HealthService::new(pool.clone())
+        .with_event_bus(event_bus.clone());
+    if let Some(replica) = config.database.replica() {
+        health_service =
+            health_service.with_replica(replica.expect("Invalid REPLICA_DATABASE_URL"));
+    }
+
+    // Generated documents, downloaded through signed links
+    let file_service = services::file_service::FileService::new(
+        services::file_service::storage_for(&config.storage),
+    )
+    .with_url_ttl(std::time::Duration::from_secs(
+        config.storage.download_url_ttl_seconds,
+    ));
+
+    // Materialize upcoming flights from the route schedules every hour
+    let schedule_service = services::schedule_service::ScheduleService::new(pool.clone());
+    schedule_service.spawn_scheduler(
+        std::time::Duration::from_secs(60 * 60),
+        services::schedule_service::DEFAULT_HORIZON_DAYS,
+    );
+
+    // Limit overlapping booking requests per user and per IP
+    let booking_limiters = utils::concurrency_limiter::BookingLimiters::new(tunables.clone());
+    // and the rate of login, registration and booking requests
+    let rate_limit_store: std::sync::Arc<dyn utils::rate_limiter::RateLimitStore> =
+        match &config.limits.rate_limit_redis_url {
+            #[cfg(feature = "redis")]
+            Some(redis_url) => std::sync::Arc::new(
+                utils::rate_limiter::RedisRateLimitStore::connect(redis_url)
+                    .await
+                    .expect("Failed to connect to the rate limit Redis"),
+            ),
+            _ => std::sync::Arc::new(utils::rate_limiter::InMemoryRateLimitStore::new()),
+        };
+    let rate_limiter = utils::rate_limiter::RateLimiter::new(rate_limit_store, tunables.clone());
+
+    // Count the requests for the service level objectives and alert when their error
+    // budget burns too fast, checking every minute
+    let request_metrics = utils::metrics::RequestMetrics::new(std::time::Duration::from_millis(
+        config.slo.booking_p99_latency_ms,
+    ));
+    let slo_service = services::slo_service::SloService::new(
+        request_metrics.clone(),
+        config.slo.clone(),
+        services::slo_service::alerter_for(&config.slo),
+    );
+    slo_service.spawn_monitor(std::time::Duration::from_secs(60));
+
+    let figment = rocket::Config::figment().merge((
+        "limits",
+        Limits::default().limit("json", ByteUnit::from(config.limits.json_bytes)),
+    ));
 
-    rocket::build()
+    rocket::custom(figment)
         .manage(user_service)
         .manage(flight_service)
         .manage(ticket_service)
+        .manage(booking_queue)
+        .manage(booking_requests)
+        .manage(route_stats_service)
+        .manage(admin_service)
+        .manage(aircraft_service)
+        .manage(payment_service)
+        .manage(operation_service)
+        .manage(promo_code_service)
+        .manage(report_service)
+        .manage(seat_block_service)
+        .manage(loyalty_service)
+        .manage(booking_limiters)
+        .manage(rate_limiter)
+        .manage(funnel_service)
+        .manage(partner_service)
+        .manage(sandbox_service)
+        .manage(health_service)
+        .manage(file_service)
+        .manage(config_reloader)
+        .manage(slo_service)
+        // Request guards publish on the bus too
+        .manage(event_bus)
         .mount(
             "/api",
             openapi_get_routes![
                 routes::user_route::register,
                 routes::user_route::login,
+                routes::user_route::get_experiments,
+                routes::user_route::get_profile,
+                routes::user_route::update_profile,
+                routes::user_route::change_password,
+                routes::user_route::forgot_password,
+                routes::user_route::reset_password,
+                routes::user_route::verify_email,
+                routes::user_route::resend_email_verification,
+                routes::user_route::create_support_token,
                 routes::flight_route::search_flights,
+                routes::flight_route::get_price_calendar,
                 routes::flight_route::get_available_seats,
+                routes::flight_route::get_seat_map_svg,
+                routes::flight_route::get_trending_destinations,
+                routes::flight_route::get_recent_flights,
                 routes::ticket_route::book_ticket,
+                routes::ticket_route::get_booking_status,
+                routes::ticket_route::validate_booking,
                 routes::ticket_route::book_seat_for_ticket,
+                routes::ticket_route::change_seat,
+                routes::ticket_route::hold_seat,
                 routes::ticket_route::get_history,
+                routes::ticket_route::get_ticket_by_reference,
+                routes::ticket_route::get_rebooking_options,
+                routes::ticket_route::rebook_ticket,
+                routes::ticket_route::check_in,
+                routes::ticket_route::get_boarding_pass,
+                routes::ticket_route::create_boarding_pass_link,
+                routes::file_route::download_file,
+                routes::payment_route::confirm_payment,
+                routes::payment_route::hold_fare,
+                routes::payment_route::pay_with_points,
+                routes::payment_route::cancel_ticket,
+                routes::payment_route::list_refunds,
+                routes::operation_route::wait_for_operation,
+                routes::loyalty_route::get_loyalty_balance,
+                routes::admin_route::update_route_overbooking,
+                routes::admin_route::find_duplicate_users,
+                routes::admin_route::merge_users,
+                routes::admin_route::unlock_user,
+                routes::admin_route::bump_overbooked_passengers,
+                routes::admin_route::update_flight_status,
+                routes::admin_route::rebook_cancelled_flight,
+                routes::admin_route::create_aircraft,
+                routes::admin_route::update_aircraft,
+                routes::admin_route::swap_aircraft,
+                routes::admin_route::flight_manifest,
+                routes::admin_route::close_flight,
+                routes::admin_route::correct_ticket,
+                routes::admin_route::route_audit,
+                routes::admin_route::funnel_report,
+                routes::admin_route::occupancy_report,
+                routes::admin_route::revenue_report,
+                routes::admin_route::route_forecast,
+                routes::admin_route::support_view_bookings,
+                routes::admin_route::create_partner_key,
+                routes::admin_route::revoke_partner_key,
+                routes::admin_route::create_promo_code,
+                routes::admin_route::list_promo_codes,
+                routes::admin_route::create_seat_block_rule,
+                routes::admin_route::list_seat_block_rules,
+                routes::admin_route::update_seat_block_rule,
+                routes::admin_route::reset_sandbox,
+                routes::admin_route::diagnostics,
+                routes::admin_route::slo_report,
+                routes::admin_route::reload_config,
+                routes::partner_route::route_availability,
+                routes::partner_route::partner_changes,
+                routes::partner_route::create_sandbox_booking,
+                routes::changelog_route::get_changelog,
             ],
         )
         .mount("/swagger", make_swagger_ui(&swagger_ui()))
-        .attach(AdHoc::on_response("CORS", |_, res| {
-            Box::pin(async move {
-                res.set_header(rocket::http::Header::new(
-                    "Access-Control-Allow-Origin",
-                    "*",
-                ));
-            })
-        }))
+        .mount("/", routes![routes::health_route::health, routes::health_route::ready])
+        // Rate limited requests get a Retry-After header
+        .register("/", catchers![utils::rate_limiter::too_many_requests])
+        // Before the request tracing, which logs the status of the final response
+        .attach(utils::cors::Cors::new(config.cors))
+        .attach(utils::statement_budget::StatementBudget::new(
+            config.limits.statements_per_request,
+        ))
+        .attach(utils::telemetry::RequestTracing)
+        .attach(utils::metrics::RecordMetrics(request_metrics))
 }
@@ -0,0 +1,261 @@
+use crate::models::fare::FareClass;
+use rust_decimal::Decimal;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+// Fee charged for seats in an exit row
+pub const EXIT_ROW_FEE: Decimal = Decimal::from_parts(2500, 0, 0, false, 2);
+
+// Fee charged for seats in the other rows with extra legroom
+pub const EXTRA_LEGROOM_FEE: Decimal = Decimal::from_parts(1500, 0, 0, false, 2);
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct Aircraft {
+    pub aircraft_id: i32,
+    pub capacity: i32,
+    pub seats_per_row: i32,
+    // Comma separated row numbers, e.g. "12,13"
+    pub exit_rows: String,
+    pub accessible_rows: String,
+    pub extra_legroom_rows: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+pub enum SeatPosition {
+    Window,
+    Middle,
+    Aisle,
+}
+
+// Attributes of a single seat derived from the aircraft layout and pricing
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct SeatAttributes {
+    pub seat_number: i32,
+    pub row: i32,
+    pub position: SeatPosition,
+    pub exit_row: bool,
+    pub accessible: bool,
+    // api-change 2026-10-16 added: Whether the seat has extra legroom
+    pub extra_legroom: bool,
+    // Price of picking the seat, charged when it is selected for a ticket
+    pub fee: Decimal,
+    // Fare class whose cabin section the seat belongs to, when known
+    // api-change 2026-10-16 added: Cabin section of the seat
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fare_class: Option<FareClass>,
+}
+
+// Cabin layout of an aircraft. Seats are numbered row by row starting from 1,
+// with the aisle in the middle of each row
+#[derive(Debug)]
+pub struct SeatLayout {
+    seats_per_row: i32,
+    exit_rows: Vec<i32>,
+    accessible_rows: Vec<i32>,
+    extra_legroom_rows: Vec<i32>,
+}
+
+impl SeatLayout {
+    pub fn from_aircraft(aircraft: &Aircraft) -> Self {
+        SeatLayout {
+            seats_per_row: aircraft.seats_per_row.max(1),
+            exit_rows: parse_rows(&aircraft.exit_rows),
+            accessible_rows: parse_rows(&aircraft.accessible_rows),
+            extra_legroom_rows: parse_rows(&aircraft.extra_legroom_rows),
+        }
+    }
+
+    pub fn seats_per_row(&self) -> i32 {
+        self.seats_per_row
+    }
+
+    pub fn row(&self, seat_number: i32) -> i32 {
+        (seat_number - 1) / self.seats_per_row + 1
+    }
+
+    pub fn position(&self, seat_number: i32) -> SeatPosition {
+        let column = (seat_number - 1) % self.seats_per_row;
+        let half = self.seats_per_row / 2;
+        if column == 0 || column == self.seats_per_row - 1 {
+            SeatPosition::Window
+        } else if column == half - 1 || column == half {
+            SeatPosition::Aisle
+        } else {
+            SeatPosition::Middle
+        }
+    }
+
+    pub fn attributes(&self, seat_number: i32) -> SeatAttributes {
+        let row = self.row(seat_number);
+        let exit_row = self.exit_rows.contains(&row);
+        // Exit rows have extra legroom too, and cost more
+        let extra_legroom = exit_row || self.extra_legroom_rows.contains(&row);
+        let fee = if exit_row {
+            EXIT_ROW_FEE
+        } else if extra_legroom {
+            EXTRA_LEGROOM_FEE
+        } else {
+            Decimal::ZERO
+        };
+        SeatAttributes {
+            seat_number,
+            row,
+            position: self.position(seat_number),
+            exit_row,
+            accessible: self.accessible_rows.contains(&row),
+            extra_legroom,
+            fee,
+            fare_class: None,
+        }
+    }
+
+    // Free seat of this layout closest in kind to a seat of another layout. Passengers in
+    // accessible seats only go to accessible seats, then the same position and exit row
+    // are preferred, then the nearest row.
+    pub fn nearest_equivalent(
+        &self,
+        seat: &SeatAttributes,
+        free_seats: impl IntoIterator<Item = i32>,
+    ) -> Option<i32> {
+        free_seats
+            .into_iter()
+            .map(|seat_number| self.attributes(seat_number))
+            .filter(|candidate| candidate.accessible || !seat.accessible)
+            .min_by_key(|candidate| {
+                (
+                    candidate.position != seat.position,
+                    candidate.exit_row != seat.exit_row,
+                    (candidate.row - seat.row).abs(),
+                    candidate.seat_number,
+                )
+            })
+            .map(|candidate| candidate.seat_number)
+    }
+}
+
+fn parse_rows(rows: &str) -> Vec<i32> {
+    rows.split(',')
+        .filter_map(|row| row.trim().parse().ok())
+        .collect()
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SwapAircraftRequest {
+    pub aircraft_id: i32,
+}
+
+// What happened to the seat of a passenger when the aircraft of their flight was swapped
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+pub enum SeatReassignmentStatus {
+    Same,
+    Moved,
+    // No equivalent seat on the new aircraft, an agent has to seat the passenger
+    Unmapped,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SeatReassignment {
+    pub ticket_id: i32,
+    pub customer_id: i32,
+    pub old_seat_number: i32,
+    pub new_seat_number: Option<i32>,
+    pub status: SeatReassignmentStatus,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SwapAircraftResponse {
+    pub flight_id: i32,
+    pub previous_aircraft_id: i32,
+    pub aircraft_id: i32,
+    pub capacity: i32,
+    pub available_tickets: i32,
+    // One entry per seated passenger
+    pub reassignments: Vec<SeatReassignment>,
+    pub unmapped: usize,
+}
+
+// Cabin of an aircraft as set by admins. Seats are numbered row by row from 1, rows are
+// listed by number, e.g. [12, 13].
+#[derive(Debug, Clone, Validate, Deserialize, JsonSchema)]
+pub struct AircraftLayoutRequest {
+    #[validate(range(min = 1, max = 1000))]
+    pub capacity: i32,
+    #[serde(default = "default_seats_per_row")]
+    #[validate(range(min = 1, max = 12))]
+    pub seats_per_row: i32,
+    #[serde(default)]
+    pub exit_rows: Vec<i32>,
+    #[serde(default)]
+    pub accessible_rows: Vec<i32>,
+    #[serde(default)]
+    pub extra_legroom_rows: Vec<i32>,
+}
+
+fn default_seats_per_row() -> i32 {
+    6
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CreateAircraftRequest {
+    pub aircraft_id: i32,
+    #[serde(flatten)]
+    pub layout: AircraftLayoutRequest,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct AircraftResponse {
+    pub aircraft_id: i32,
+    pub capacity: i32,
+    pub seats_per_row: i32,
+    pub exit_rows: Vec<i32>,
+    pub accessible_rows: Vec<i32>,
+    pub extra_legroom_rows: Vec<i32>,
+    // Flights not yet departed whose seats were added or removed for a new capacity
+    pub updated_flights: usize,
+}
+
+impl AircraftLayoutRequest {
+    // Rows of the cabin that do not exist, e.g. an exit row past the last row
+    pub fn unknown_rows(&self) -> Vec<i32> {
+        let last_row = (self.capacity + self.seats_per_row - 1) / self.seats_per_row;
+        let mut rows: Vec<i32> = [
+            &self.exit_rows,
+            &self.accessible_rows,
+            &self.extra_legroom_rows,
+        ]
+        .into_iter()
+        .flatten()
+        .copied()
+        .filter(|row| !(1..=last_row).contains(row))
+        .collect();
+        rows.sort_unstable();
+        rows.dedup();
+        rows
+    }
+}
+
+// Rows stored comma separated, as parse_rows reads them back
+pub fn format_rows(rows: &[i32]) -> String {
+    let mut rows = rows.to_vec();
+    rows.sort_unstable();
+    rows.dedup();
+    rows.iter()
+        .map(|row| row.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl Aircraft {
+    pub fn response(&self, updated_flights: usize) -> AircraftResponse {
+        AircraftResponse {
+            aircraft_id: self.aircraft_id,
+            capacity: self.capacity,
+            seats_per_row: self.seats_per_row,
+            exit_rows: parse_rows(&self.exit_rows),
+            accessible_rows: parse_rows(&self.accessible_rows),
+            extra_legroom_rows: parse_rows(&self.extra_legroom_rows),
+            updated_flights,
+        }
+    }
+}
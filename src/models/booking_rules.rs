@@ -0,0 +1,157 @@
+use crate::models::ticket::{MIN_UNACCOMPANIED_AGE, UNACCOMPANIED_MINOR_AGE};
+use crate::utils::error::{AppError, AppResult};
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use std::path::Path;
+
+// Rules file read at startup when BOOKING_RULES_PATH is not set
+pub const DEFAULT_BOOKING_RULES_PATH: &str = "util/booking_rules.toml";
+
+// Booking policy, read from a TOML or JSON file so it can change without a new build.
+// Settings left out of the file keep their default, which is the built-in policy.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BookingRules {
+    pub advance_purchase: AdvancePurchaseRules,
+    // Most flights in one booking request, unlimited when not set
+    pub max_legs: Option<usize>,
+    pub duplicate_booking: DuplicatePolicy,
+    pub minors: MinorRules,
+    pub op_up: OpUpRules,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdvancePurchaseRules {
+    // Sales close this many hours before departure
+    pub min_hours_before_departure: Option<i64>,
+    // Flights further out than this many days cannot be booked yet
+    pub max_days_before_departure: Option<i64>,
+}
+
+// What to do when the customer already holds a ticket for a requested flight
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    #[default]
+    Reject,
+    Allow,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MinorRules {
+    // Customers younger than this at the flight date travel as unaccompanied minors
+    pub unaccompanied_minor_age: i32,
+    // Children younger than this cannot travel alone at all
+    pub min_unaccompanied_age: i32,
+    pub direct_flights_only: bool,
+}
+
+impl Default for MinorRules {
+    fn default() -> Self {
+        MinorRules {
+            unaccompanied_minor_age: UNACCOMPANIED_MINOR_AGE,
+            min_unaccompanied_age: MIN_UNACCOMPANIED_AGE,
+            direct_flights_only: true,
+        }
+    }
+}
+
+// Booking passengers into a higher cabin at their own fare when the cabin of their fare
+// class is sold out (an op-up), off by default
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OpUpRules {
+    pub enabled: bool,
+}
+
+// What the rules need to know about a requested flight
+#[derive(Debug, Clone)]
+pub struct LegFacts {
+    pub flight_number: i32,
+    // None when the flight is unknown, which is reported elsewhere
+    pub departure: Option<NaiveDateTime>,
+}
+
+impl BookingRules {
+    // Read the rules from a .json file, or a TOML file for any other extension
+    pub fn load(path: impl AsRef<Path>) -> AppResult<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            AppError::ValidationError(format!("Cannot read rules file {}: {}", path.display(), e))
+        })?;
+        let rules = if path.extension().map_or(false, |extension| extension == "json") {
+            Self::from_json(&contents)?
+        } else {
+            Self::from_toml(&contents)?
+        };
+        Ok(rules)
+    }
+
+    pub fn from_toml(contents: &str) -> AppResult<Self> {
+        let rules: BookingRules = toml::from_str(contents)
+            .map_err(|e| AppError::ValidationError(format!("Invalid booking rules: {}", e)))?;
+        rules.validated()
+    }
+
+    pub fn from_json(contents: &str) -> AppResult<Self> {
+        let rules: BookingRules = serde_json::from_str(contents)
+            .map_err(|e| AppError::ValidationError(format!("Invalid booking rules: {}", e)))?;
+        rules.validated()
+    }
+
+    fn validated(self) -> AppResult<Self> {
+        if self.max_legs == Some(0) {
+            return Err(AppError::ValidationError(
+                "Invalid booking rules: max_legs must be at least 1".into(),
+            ));
+        }
+        if self.minors.min_unaccompanied_age > self.minors.unaccompanied_minor_age {
+            return Err(AppError::ValidationError(
+                "Invalid booking rules: min_unaccompanied_age is above unaccompanied_minor_age"
+                    .into(),
+            ));
+        }
+        Ok(self)
+    }
+
+    // Check the itinerary-wide rules, returning the reason of every rule broken
+    pub fn evaluate(&self, legs: &[LegFacts], now: NaiveDateTime) -> Vec<String> {
+        let mut violations = Vec::new();
+
+        if let Some(max_legs) = self.max_legs {
+            if legs.len() > max_legs {
+                violations.push(format!(
+                    "At most {} flights can be booked at once",
+                    max_legs
+                ));
+            }
+        }
+
+        for leg in legs {
+            let Some(departure) = leg.departure else {
+                continue;
+            };
+            let until_departure = departure - now;
+            if let Some(hours) = self.advance_purchase.min_hours_before_departure {
+                if until_departure < chrono::Duration::hours(hours) {
+                    violations.push(format!(
+                        "Flight {} can no longer be booked, sales close {} hours before departure",
+                        leg.flight_number, hours
+                    ));
+                }
+            }
+            if let Some(days) = self.advance_purchase.max_days_before_departure {
+                if until_departure > chrono::Duration::days(days) {
+                    violations.push(format!(
+                        "Flight {} cannot be booked yet, sales open {} days before departure",
+                        leg.flight_number, days
+                    ));
+                }
+            }
+        }
+
+        violations
+    }
+}
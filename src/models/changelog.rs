@@ -0,0 +1,53 @@
+use chrono::NaiveDate;
+use rocket::FromFormField;
+use schemars::JsonSchema;
+use serde::Serialize;
+use strum_macros::Display;
+
+// Kind of an API-visible change
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Display, JsonSchema, FromFormField)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ApiChangeKind {
+    Added,
+    Changed,
+    Deprecated,
+    Removed,
+}
+
+// A change integrators may need to act on. The changelog is generated at build time
+// from `api-change` comments above the route or model item that changed, e.g.
+//     `// api-change 2026-10-16 added: The seat map can be fetched as an SVG image`
+// where the kind is added, changed, deprecated or removed. A malformed annotation
+// fails the build.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ApiChange {
+    pub date: &'static str,
+    pub kind: ApiChangeKind,
+    // Route handler, model or `Model.field` the annotation is on
+    pub item: &'static str,
+    // Method and path when the item is a route
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<&'static str>,
+    pub description: &'static str,
+    pub source: &'static str,
+}
+
+// Newest first
+pub static API_CHANGES: &[ApiChange] = include!(concat!(env!("OUT_DIR"), "/api_changelog.rs"));
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ApiChangelogResponse {
+    pub changes: Vec<&'static ApiChange>,
+}
+
+// Changes made on or after the date, of the kind when given
+pub fn changelog(since: Option<NaiveDate>, kind: Option<ApiChangeKind>) -> ApiChangelogResponse {
+    let since = since.map(|date| date.format("%Y-%m-%d").to_string());
+    let changes = API_CHANGES
+        .iter()
+        .filter(|change| since.as_deref().map_or(true, |since| change.date >= since))
+        .filter(|change| kind.map_or(true, |kind| change.kind == kind))
+        .collect();
+    ApiChangelogResponse { changes }
+}
@@ -0,0 +1,92 @@
+use crate::models::fare::FareClass;
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// Check-in opens this many hours before departure
+pub const CHECKIN_OPENS_HOURS: i64 = 24;
+
+// and closes this many minutes before departure, when boarding is prepared
+pub const CHECKIN_CLOSES_MINUTES: i64 = 45;
+
+// Airline designator printed on boarding passes
+pub const CARRIER_CODE: &str = "AB";
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CheckinRequest {
+    pub booking_reference: String,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct BoardingPass {
+    pub ticket_id: i32,
+    pub booking_reference: String,
+    pub passenger_name: String,
+    pub flight_number: i32,
+    pub flight_date: NaiveDate,
+    pub departure_city: String,
+    pub destination_city: String,
+    // Scheduled departure including any delay
+    pub departure: NaiveDateTime,
+    // Not yet assigned when absent
+    pub gate: Option<String>,
+    pub seat_number: i32,
+    pub fare_class: FareClass,
+    // Place in the boarding order, by check-in order
+    pub sequence_number: i32,
+    // Contents of the barcode scanned at the gate
+    pub barcode: String,
+    pub checked_in_at: NaiveDateTime,
+}
+
+impl BoardingPass {
+    // Barcode in the layout of the mandatory items of the IATA bar coded boarding pass
+    // (BCBP), so gate scanners can read it. Routes have no airport codes, the first
+    // letters of the cities stand in for them.
+    pub fn barcode_data(&self) -> String {
+        let compartment = match self.fare_class {
+            FareClass::Economy => 'Y',
+            FareClass::Business => 'J',
+            FareClass::First => 'F',
+        };
+        format!(
+            "M1{:<20}E{:<7}{}{}{:<3}{:0>4} {:03}{}{:0>4}{:0>5}1",
+            bcbp_name(&self.passenger_name),
+            self.booking_reference,
+            city_code(&self.departure_city),
+            city_code(&self.destination_city),
+            CARRIER_CODE,
+            self.flight_number % 10_000,
+            self.flight_date.ordinal(),
+            compartment,
+            self.seat_number % 10_000,
+            self.sequence_number % 100_000,
+        )
+    }
+}
+
+// Passenger name as "SURNAME/GIVEN NAMES", uppercase and cut to the 20 characters of
+// the field
+fn bcbp_name(name: &str) -> String {
+    let mut parts: Vec<&str> = name.split_whitespace().collect();
+    let name = match parts.pop() {
+        Some(surname) if !parts.is_empty() => format!("{}/{}", surname, parts.join(" ")),
+        Some(surname) => surname.to_string(),
+        None => String::new(),
+    };
+    name.to_uppercase()
+        .chars()
+        .filter(char::is_ascii)
+        .take(20)
+        .collect()
+}
+
+fn city_code(city: &str) -> String {
+    let code: String = city
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .take(3)
+        .collect::<String>()
+        .to_uppercase();
+    format!("{:<3}", code)
+}
@@ -0,0 +1,11 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+// Outcome of reloading the configuration
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct ConfigReloadResponse {
+    // Settings now in effect with a new value
+    pub changed: Vec<String>,
+    // Settings changed in the configuration that only apply after a restart
+    pub restart_required: Vec<String>,
+}
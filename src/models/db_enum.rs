@@ -0,0 +1,146 @@
+use crate::models::aircraft::SeatReassignmentStatus;
+use crate::models::fare::FareClass;
+use crate::models::flight::{FlightStatus, SeatStatus};
+use crate::models::funnel::FunnelStep;
+use crate::models::loyalty::LoyaltyEntryType;
+use crate::models::payment::{PaymentStatus, RefundStatus};
+use crate::models::promo::DiscountType;
+use crate::models::seat_block::SeatBlockRuleKind;
+use crate::models::ssr::SsrCode;
+use crate::models::ticket::{BookingRequestStatus, CorrectionReason, RebookingStatus};
+use crate::models::user::Role;
+
+// Conversion between an enum and the string stored for it in the database.
+// Use this instead of Display or hand-written matches when building queries.
+pub trait DbEnum: Sized + Copy + 'static {
+    // Every variant, in declaration order
+    const VARIANTS: &'static [Self];
+
+    fn as_db_str(&self) -> &'static str;
+
+    // MySQL compares ENUM values case-insensitively, and so does this
+    fn from_db_str(value: &str) -> Option<Self> {
+        Self::VARIANTS
+            .iter()
+            .copied()
+            .find(|variant| variant.as_db_str().eq_ignore_ascii_case(value))
+    }
+}
+
+// The match makes the compiler reject a mapping that misses a variant,
+// and VARIANTS is built from the same list
+macro_rules! db_enum {
+    ($ty:ident { $($variant:ident => $value:literal),+ $(,)? }) => {
+        impl DbEnum for $ty {
+            const VARIANTS: &'static [Self] = &[$($ty::$variant),+];
+
+            fn as_db_str(&self) -> &'static str {
+                match self {
+                    $($ty::$variant => $value),+
+                }
+            }
+        }
+    };
+}
+
+db_enum!(SeatStatus {
+    Available => "AVAILABLE",
+    Booked => "BOOKED",
+    Unavailable => "UNAVAILABLE",
+    Held => "HELD",
+});
+
+db_enum!(Role {
+    User => "USER",
+    Admin => "ADMIN",
+});
+
+db_enum!(PaymentStatus {
+    Pending => "PENDING",
+    Processing => "PROCESSING",
+    Captured => "CAPTURED",
+    Expired => "EXPIRED",
+});
+
+db_enum!(RefundStatus {
+    Pending => "PENDING",
+    Processing => "PROCESSING",
+    Refunded => "REFUNDED",
+    Failed => "FAILED",
+});
+
+db_enum!(LoyaltyEntryType {
+    Earn => "EARN",
+    Redeem => "REDEEM",
+    Refund => "REFUND",
+});
+
+db_enum!(SeatBlockRuleKind {
+    MiddleSeats => "MIDDLE_SEATS",
+    LastRow => "LAST_ROW",
+});
+
+db_enum!(SsrCode {
+    WheelchairRamp => "WCHR",
+    WheelchairSteps => "WCHS",
+    WheelchairCabin => "WCHC",
+    Infant => "INFT",
+    Blind => "BLND",
+    Deaf => "DEAF",
+    VegetarianMeal => "VGML",
+    KosherMeal => "KSML",
+    MuslimMeal => "MOML",
+    HinduMeal => "HNML",
+    GlutenFreeMeal => "GFML",
+    DiabeticMeal => "DBML",
+    ChildMeal => "CHML",
+});
+
+db_enum!(DiscountType {
+    Percentage => "PERCENTAGE",
+    Fixed => "FIXED",
+});
+
+db_enum!(FareClass {
+    Economy => "ECONOMY",
+    Business => "BUSINESS",
+    First => "FIRST",
+});
+
+db_enum!(FunnelStep {
+    Search => "SEARCH",
+    SeatMapViewed => "SEAT_MAP_VIEWED",
+    BookingAttempted => "BOOKING_ATTEMPTED",
+    BookingConfirmed => "BOOKING_CONFIRMED",
+});
+
+db_enum!(FlightStatus {
+    Scheduled => "SCHEDULED",
+    Delayed => "DELAYED",
+    Boarding => "BOARDING",
+    Departed => "DEPARTED",
+    Cancelled => "CANCELLED",
+});
+
+db_enum!(RebookingStatus {
+    Rebooked => "REBOOKED",
+    Failed => "FAILED",
+});
+
+db_enum!(BookingRequestStatus {
+    Pending => "PENDING",
+    Confirmed => "CONFIRMED",
+    Failed => "FAILED",
+});
+
+db_enum!(SeatReassignmentStatus {
+    Same => "SAME",
+    Moved => "MOVED",
+    Unmapped => "UNMAPPED",
+});
+
+db_enum!(CorrectionReason {
+    SeatChangedOnboard => "SEAT_CHANGED_ONBOARD",
+    DataEntryError => "DATA_ENTRY_ERROR",
+    Operational => "OPERATIONAL",
+});
@@ -0,0 +1,143 @@
+use rust_decimal::Decimal;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+// Fare Class Enum
+#[derive(
+    Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, Display, JsonSchema, sqlx::Type,
+)]
+#[sqlx(type_name = "varchar")]
+pub enum FareClass {
+    #[default]
+    #[sqlx(rename = "ECONOMY")]
+    #[strum(serialize = "ECONOMY")]
+    Economy,
+    #[sqlx(rename = "BUSINESS")]
+    #[strum(serialize = "BUSINESS")]
+    Business,
+    #[sqlx(rename = "FIRST")]
+    #[strum(serialize = "FIRST")]
+    First,
+}
+
+impl FareClass {
+    // Classes of the cabins above this one, nearest first
+    pub fn higher_classes(self) -> &'static [FareClass] {
+        match self {
+            FareClass::Economy => &[FareClass::Business, FareClass::First],
+            FareClass::Business => &[FareClass::First],
+            FareClass::First => &[],
+        }
+    }
+}
+
+// Percentage of the ticket price refunded when a paid ticket is cancelled, by fare
+// class, from the most hours before departure down. Cancelling later than the last
+// entry of the class refunds nothing.
+pub const REFUND_RULES: [(FareClass, i64, i64); 8] = [
+    (FareClass::First, 24, 100),
+    (FareClass::First, 0, 50),
+    (FareClass::Business, 72, 100),
+    (FareClass::Business, 24, 75),
+    (FareClass::Business, 0, 25),
+    (FareClass::Economy, 168, 80),
+    (FareClass::Economy, 72, 50),
+    (FareClass::Economy, 24, 25),
+];
+
+// Share of the price refunded for a ticket of the fare class cancelled the given
+// number of hours before departure, between 0 and 1
+pub fn refund_share(fare_class: FareClass, hours_before_departure: i64) -> Decimal {
+    REFUND_RULES
+        .iter()
+        .find(|(class, min_hours, _)| *class == fare_class && hours_before_departure >= *min_hours)
+        .map_or(Decimal::ZERO, |(_, _, percent)| Decimal::new(*percent, 2))
+}
+
+// Price of a fare class on a flight route. The fare class is sold for the seats
+// in rows first_row..=last_row, or the whole cabin when no rows are given.
+#[allow(dead_code)]
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Fare {
+    pub flight_number: i32,
+    pub fare_class: FareClass,
+    pub base_price: Decimal,
+    pub currency: String,
+    pub first_row: Option<i32>,
+    pub last_row: Option<i32>,
+}
+
+impl Fare {
+    // Whether seats in the given row belong to the section of this fare class
+    pub fn covers_row(&self, row: i32) -> bool {
+        self.first_row.map_or(true, |first_row| row >= first_row)
+            && self.last_row.map_or(true, |last_row| row <= last_row)
+    }
+
+    pub fn has_section(&self) -> bool {
+        self.first_row.is_some() || self.last_row.is_some()
+    }
+
+    // Rows of the section, for error messages
+    pub fn section_rows(&self) -> String {
+        match (self.first_row, self.last_row) {
+            (Some(first_row), Some(last_row)) if first_row == last_row => {
+                format!("row {}", first_row)
+            }
+            (Some(first_row), Some(last_row)) => format!("rows {} to {}", first_row, last_row),
+            (Some(first_row), None) => format!("rows {} and behind", first_row),
+            (None, Some(last_row)) => format!("rows up to {}", last_row),
+            (None, None) => "any row".to_string(),
+        }
+    }
+}
+
+// Fare class whose cabin section holds the row. Sections given by rows take precedence
+// over a fare sold for the whole cabin, None when no fare of the route covers the row.
+pub fn seat_class(fares: &[Fare], row: i32) -> Option<FareClass> {
+    fares
+        .iter()
+        .filter(|fare| fare.has_section())
+        .chain(fares.iter().filter(|fare| !fare.has_section()))
+        .find(|fare| fare.covers_row(row))
+        .map(|fare| fare.fare_class)
+}
+
+// Seats of the cabin of a fare class on a flight. Op-ups are passengers moved up from
+// a sold out lower cabin at their own fare, they take seats of this cabin.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct CabinInventory {
+    pub fare_class: FareClass,
+    pub seats: i64,
+    pub tickets_sold: i64,
+    // Of the tickets sold
+    pub op_ups: i64,
+    // Seats left to sell, including as paid upgrades
+    pub free_seats: i64,
+}
+
+// Price of one fare class, as shown to customers
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct FarePrice {
+    pub fare_class: FareClass,
+    pub price: Decimal,
+    pub currency: String,
+}
+
+impl From<&Fare> for FarePrice {
+    fn from(fare: &Fare) -> Self {
+        FarePrice {
+            fare_class: fare.fare_class,
+            price: fare.base_price,
+            currency: fare.currency.clone(),
+        }
+    }
+}
+
+// Fares offered on a flight route
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct RouteFares {
+    pub flight_number: i32,
+    pub fares: Vec<FarePrice>,
+}
@@ -0,0 +1,13 @@
+use chrono::NaiveDateTime;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+// Path of the route serving stored files, followed by the token of the link
+pub const FILE_ROUTE_PREFIX: &str = "/api/files/";
+
+// Link downloading a stored document without logging in, until expires_at
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct FileLink {
+    pub url: String,
+    pub expires_at: NaiveDateTime,
+}
@@ -1,8 +1,16 @@
-use chrono::{NaiveDate, NaiveTime};
+use crate::models::aircraft::SeatAttributes;
+use crate::models::fare::{FareClass, FarePrice, RouteFares};
+use crate::models::ssr::SsrCode;
+use crate::models::ticket::RebookingSummary;
+use chrono::{
+    DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone,
+};
+use chrono_tz::Tz;
+use rocket::FromFormField;
+use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use strum_macros::Display;
 
 #[allow(dead_code)]
 #[derive(Debug, sqlx::FromRow)]
@@ -16,6 +24,29 @@ pub struct FlightRoute {
     pub overbooking: Decimal,
     pub start_date: NaiveDate,
     pub end_date: Option<NaiveDate>,
+    pub operating_days: String,
+}
+
+// Days of the week a route flies, as ISO weekday digits (1 = Monday), e.g. "135" for
+// Monday, Wednesday and Friday. The search query checks the same thing with
+// INSTR(fr.operating_days, WEEKDAY(f.flight_date) + 1).
+pub const EVERY_DAY: &str = "1234567";
+
+// Dates on which a route operates
+#[derive(Debug, Clone)]
+pub struct RouteSchedule {
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    pub operating_days: String,
+}
+
+impl RouteSchedule {
+    pub fn operates_on(&self, date: NaiveDate) -> bool {
+        let weekday = char::from_digit(date.weekday().number_from_monday(), 10);
+        date >= self.start_date
+            && self.end_date.map_or(true, |end_date| date <= end_date)
+            && weekday.map_or(false, |weekday| self.operating_days.contains(weekday))
+    }
 }
 
 #[allow(dead_code)]
@@ -28,34 +59,307 @@ pub struct Flight {
     pub version: Option<i32>,
 }
 
-#[derive(Debug, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, JsonSchema, Default)]
 pub struct FlightSearchQuery {
     pub departure_city: String,
     pub destination_city: String,
     pub departure_date: NaiveDate,
     pub end_date: Option<NaiveDate>,
+    // Language of the city names in the query and the results, e.g. "fr"
+    #[serde(default)]
+    pub language: Option<String>,
+    // List a flight sold under partner flight numbers once, with the partner numbers
+    // in its codeshares, instead of once per flight number. Defaults to true.
+    #[serde(default)]
+    pub collapse_codeshares: Option<bool>,
+    // api-change 2026-10-16 added: Sort order of search results
+    #[serde(default)]
+    pub sort_by: Option<SearchSort>,
+    // Scheduled departure time of day the flights leave at or after, and at or before
+    // api-change 2026-10-16 added: Departure time window of a search
+    #[serde(default)]
+    pub departure_after: Option<NaiveTime>,
+    #[serde(default)]
+    pub departure_before: Option<NaiveTime>,
+    // Most stops on the way. Every route flies non-stop, so no flight is left out.
+    // api-change 2026-10-16 added: Most stops of the flights found
+    #[serde(default)]
+    pub max_stops: Option<u32>,
+    // Page of the results: at most `limit` flights after skipping `offset` of them. All
+    // the flights when no limit is given.
+    // api-change 2026-10-16 added: Pages of search results
+    #[serde(default)]
+    pub limit: Option<u32>,
+    #[serde(default)]
+    pub offset: Option<u32>,
 }
 
-#[derive(Debug, Serialize, JsonSchema)]
+// Order of search results. Flights leave in date and time order, unless sorted by the
+// cheapest fare of their route or by how long they fly, ties in departure order.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, FromFormField, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchSort {
+    Price,
+    #[default]
+    #[field(value = "departure_time")]
+    DepartureTime,
+    Duration,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
 pub struct FlightSearchResponse {
     pub flights: Vec<FlightDetail>,
+    // Fares of the routes in the results, one entry per flight number
+    pub fares: Vec<RouteFares>,
+    // Flights found over all the pages, each counted once whatever its codeshares
+    // api-change 2026-10-16 added: Flights found by a search over all pages
+    pub total: i64,
+}
+
+// Days of a month to search, each with one flight picked by the sort order
+#[derive(Debug, Clone)]
+pub struct PriceCalendarQuery {
+    pub departure_city: String,
+    pub destination_city: String,
+    // First day of the month
+    pub month: NaiveDate,
+    pub language: Option<String>,
+    // Pick the cheapest flight of a day by default
+    pub sort_by: Option<SearchSort>,
+}
+
+impl PriceCalendarQuery {
+    // Last day of the month
+    pub fn month_end(&self) -> NaiveDate {
+        let next_month = if self.month.month() == 12 {
+            NaiveDate::from_ymd_opt(self.month.year() + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd_opt(self.month.year(), self.month.month() + 1, 1)
+        };
+        next_month
+            .and_then(|date| date.pred_opt())
+            .unwrap_or(self.month)
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PriceCalendarResponse {
+    // YYYY-MM
+    pub month: String,
+    // Every day of the month in order, with or without flights
+    pub days: Vec<CalendarDay>,
 }
 
-// Single Flight Detail in FlightSearchResponse
 #[derive(Debug, Serialize, JsonSchema)]
+pub struct CalendarDay {
+    pub date: NaiveDate,
+    // Flights with tickets left that day
+    pub flights: i64,
+    // Cheapest flight of the day, or the first or shortest one depending on the sort
+    pub flight: Option<FlightDetail>,
+    // Cheapest fare of that flight
+    pub lowest_fare: Option<FarePrice>,
+}
+
+// Flight of a streamed search. The fares of a route come with its first flight only.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct FlightSearchRow {
+    #[serde(flatten)]
+    pub flight: FlightDetail,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fares: Option<Vec<FarePrice>>,
+}
+
+// Single Flight Detail in FlightSearchResponse
+#[derive(Debug, Clone, Serialize, JsonSchema)]
 pub struct FlightDetail {
     pub flight_id: i32,
     pub flight_number: i32,
     pub departure_city: String,
     pub destination_city: String,
+    // IATA codes of the airports, when known
+    // api-change 2026-10-16 added: Airport codes of a flight
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub departure_airport: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination_airport: Option<String>,
     pub departure_time: NaiveTime,
     pub arrival_time: NaiveTime,
+    // Scheduled departure and arrival with the UTC offsets of the airports, and the
+    // minutes in between, when the timezones of both airports are known
+    // api-change 2026-10-16 added: Departure and arrival times with UTC offsets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub departure_at: Option<DateTime<FixedOffset>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arrival_at: Option<DateTime<FixedOffset>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_minutes: Option<i64>,
     pub available_tickets: i32,
     pub flight_date: NaiveDate,
+    pub status: FlightStatus,
+    // Minutes the departure is expected to be late
+    pub delay_minutes: i32,
+    // Partner flight number this entry is sold under, when codeshares are not collapsed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub marketed_as: Option<MarketingFlight>,
+    // Partner flight numbers of the flight, when codeshares are collapsed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub codeshares: Vec<MarketingFlight>,
+}
+
+// Scheduled departure and arrival of a flight as instants, with the UTC offsets of
+// their airports
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlightTimes {
+    pub departure_at: DateTime<FixedOffset>,
+    pub arrival_at: DateTime<FixedOffset>,
+    pub duration_minutes: i64,
+}
+
+impl FlightTimes {
+    // Route times are local to their airport. The arrival is the first time the clock
+    // at the destination shows the arrival time after departure, as no flight lasts a
+    // day. None when a timezone is unknown.
+    pub fn new(
+        flight_date: NaiveDate,
+        departure_time: NaiveTime,
+        arrival_time: NaiveTime,
+        departure_timezone: Option<&str>,
+        destination_timezone: Option<&str>,
+    ) -> Option<Self> {
+        let departure_tz: Tz = departure_timezone?.parse().ok()?;
+        let destination_tz: Tz = destination_timezone?.parse().ok()?;
+
+        let departure_at = local_instant(departure_tz, flight_date.and_time(departure_time))?;
+        let arrival_date = departure_at.with_timezone(&destination_tz).date_naive();
+        let mut arrival_at = local_instant(destination_tz, arrival_date.and_time(arrival_time))?;
+        if arrival_at <= departure_at {
+            let next_day = arrival_date.succ_opt()?;
+            arrival_at = local_instant(destination_tz, next_day.and_time(arrival_time))?;
+        }
+
+        Some(FlightTimes {
+            departure_at: departure_at.with_timezone(&departure_at.offset().fix()),
+            arrival_at: arrival_at.with_timezone(&arrival_at.offset().fix()),
+            duration_minutes: (arrival_at - departure_at).num_minutes(),
+        })
+    }
+}
+
+// Departure of a flight in UTC, which deadlines are checked against. Route times are
+// local to the departure airport; routes without a known airport timezone keep them
+// as UTC
+pub fn departure_utc(
+    flight_date: NaiveDate,
+    departure_time: NaiveTime,
+    departure_timezone: Option<&str>,
+) -> NaiveDateTime {
+    let local = flight_date.and_time(departure_time);
+    departure_timezone
+        .and_then(|timezone| timezone.parse::<Tz>().ok())
+        .and_then(|tz| local_instant(tz, local))
+        .map_or(local, |departure| departure.naive_utc())
+}
+
+// Instant a local time stands for, the earlier one when the clocks go back and an hour
+// later when they skip it
+fn local_instant(tz: Tz, local: NaiveDateTime) -> Option<DateTime<Tz>> {
+    tz.from_local_datetime(&local).earliest().or_else(|| {
+        tz.from_local_datetime(&(local + chrono::Duration::hours(1)))
+            .earliest()
+    })
+}
+
+// Flight number of a partner airline selling a flight operated by us
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct MarketingFlight {
+    pub carrier_code: String,
+    pub flight_number: i32,
+}
+
+// Entries of a flight in search results: the flight listing its codeshares once when
+// collapsed, otherwise the flight followed by one copy per partner flight number
+pub fn codeshare_entries(
+    flight: FlightDetail,
+    codeshares: &[MarketingFlight],
+    collapse: bool,
+) -> Vec<FlightDetail> {
+    if collapse {
+        return vec![FlightDetail {
+            codeshares: codeshares.to_vec(),
+            ..flight
+        }];
+    }
+    let mut entries = Vec::with_capacity(codeshares.len() + 1);
+    for codeshare in codeshares {
+        entries.push(FlightDetail {
+            marketed_as: Some(codeshare.clone()),
+            ..flight.clone()
+        });
+    }
+    entries.insert(0, flight);
+    entries
+}
+
+// Operational status of a flight
+#[derive(
+    Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, JsonSchema, sqlx::Type,
+)]
+#[sqlx(type_name = "varchar")]
+pub enum FlightStatus {
+    #[default]
+    #[sqlx(rename = "SCHEDULED")]
+    Scheduled,
+    #[sqlx(rename = "DELAYED")]
+    Delayed,
+    #[sqlx(rename = "BOARDING")]
+    Boarding,
+    #[sqlx(rename = "DEPARTED")]
+    Departed,
+    #[sqlx(rename = "CANCELLED")]
+    Cancelled,
+}
+
+impl FlightStatus {
+    // Departed and cancelled flights cannot change status anymore
+    pub fn is_final(&self) -> bool {
+        matches!(self, FlightStatus::Departed | FlightStatus::Cancelled)
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct UpdateFlightStatusRequest {
+    pub status: FlightStatus,
+    // Required when the status is Delayed, the current delay is kept otherwise
+    #[serde(default)]
+    pub delay_minutes: Option<i32>,
+    #[serde(default)]
+    pub reason: Option<String>,
+    // Departure gate, the current gate is kept when absent
+    #[serde(default)]
+    pub gate: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct UpdateFlightStatusResponse {
+    pub flight_id: i32,
+    pub flight_number: i32,
+    pub flight_date: NaiveDate,
+    pub previous_status: FlightStatus,
+    pub status: FlightStatus,
+    pub delay_minutes: i32,
+    pub delay_reason: Option<String>,
+    pub gate: Option<String>,
+    // Tickets marked for rebooking because the flight was cancelled
+    pub tickets_to_rebook: u64,
+    // Outcome of rebooking the passengers of a cancelled flight
+    pub rebooking: Option<RebookingSummary>,
 }
 
 // Seat Status Enum
-#[derive(Debug, PartialEq, Serialize, Deserialize, Display, sqlx::Type)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "varchar")]
 pub enum SeatStatus {
     #[sqlx(rename = "AVAILABLE")]
@@ -63,10 +367,151 @@ pub enum SeatStatus {
     #[sqlx(rename = "BOOKED")]
     Booked,
     #[sqlx(rename = "UNAVAILABLE")]
-    Unavailable
+    Unavailable,
+    // Reserved for a customer for a few minutes while they complete payment
+    #[sqlx(rename = "HELD")]
+    Held,
 }
 
-#[derive(Debug, Serialize, JsonSchema)]
+#[derive(Debug, Clone, Serialize, JsonSchema)]
 pub struct AvailableSeatsResponse {
+    // Flat list of available seat numbers, kept for compatibility
     pub available_seats: Vec<i32>,
+    // Available seats with their layout and pricing attributes
+    pub seats: Vec<SeatAttributes>,
+    // Booked and held seats, left out when the seat map shows availability only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub occupied_seats: Option<Vec<i32>>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TrendingDestination {
+    pub departure_city: String,
+    pub destination_city: String,
+    pub searches: i64,
+    pub bookings: i64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TrendingDestinationsResponse {
+    // Length of the time window in days
+    pub days: i64,
+    pub destinations: Vec<TrendingDestination>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RecentFlightsResponse {
+    // Most recently viewed first
+    pub flights: Vec<FlightDetail>,
+}
+
+// Highest accepted overbooking ratio for a route
+pub const MAX_OVERBOOKING: Decimal = Decimal::from_parts(50, 0, 0, false, 2);
+
+// Number of tickets that can be sold for an aircraft capacity and overbooking ratio
+pub fn overbooked_capacity(capacity: i32, overbooking: Decimal) -> i32 {
+    (Decimal::from(capacity) * (Decimal::ONE + overbooking))
+        .floor()
+        .to_i32()
+        .unwrap_or(capacity)
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct UpdateOverbookingRequest {
+    // Ratio of extra tickets sold over capacity, e.g. 0.10 for 10%
+    pub overbooking: Decimal,
+    // Also adjust the remaining tickets of flights that have not departed yet
+    #[serde(default)]
+    pub apply_to_open_flights: bool,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct UpdateOverbookingResponse {
+    pub flight_number: i32,
+    pub previous_overbooking: Decimal,
+    pub overbooking: Decimal,
+    pub updated_flights: u64,
+}
+
+// A change made by an admin to a flight route
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RouteAuditEntry {
+    pub id: i32,
+    pub flight_number: i32,
+    pub admin_id: i32,
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub changed_at: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Default)]
+pub struct BumpRequest {
+    // Date of the flight of the same route to rebook bumped passengers on.
+    // Defaults to the next flight of the route with tickets left.
+    #[serde(default)]
+    pub rebook_flight_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BumpedPassenger {
+    pub customer_id: i32,
+    pub bumped_ticket_id: i32,
+    // Absent when no later flight had tickets left
+    pub rebooked_ticket_id: Option<i32>,
+    pub rebooked_flight_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BumpResponse {
+    pub flight_number: i32,
+    pub flight_date: NaiveDate,
+    // Physical seats on the aircraft
+    pub capacity: i32,
+    // Tickets sold before bumping
+    pub tickets_sold: i64,
+    pub bumped: Vec<BumpedPassenger>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct FlightCloseOutResponse {
+    pub flight_id: i32,
+    pub flight_number: i32,
+    pub flight_date: NaiveDate,
+    pub closed_at: NaiveDateTime,
+    pub tickets: i64,
+    // Seat holds given up by the close-out
+    pub released_holds: u64,
+}
+
+// Ticketed passenger of a flight as ground staff see them
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ManifestPassenger {
+    pub ticket_id: i32,
+    pub booking_reference: Option<String>,
+    pub customer_id: i32,
+    pub name: String,
+    pub birth_date: NaiveDate,
+    // None while the passenger has no seat yet
+    pub seat_number: Option<i32>,
+    pub fare_class: FareClass,
+    pub checked_in: bool,
+    pub checked_in_at: Option<NaiveDateTime>,
+    pub boarding_sequence: Option<i32>,
+    pub ssr_codes: Vec<SsrCode>,
+    pub unaccompanied_minor: bool,
+    // Sold beyond the physical seats, may be bumped
+    pub overbooked: bool,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct FlightManifestResponse {
+    pub flight_id: i32,
+    pub flight_number: i32,
+    pub flight_date: NaiveDate,
+    pub status: FlightStatus,
+    pub gate: Option<String>,
+    pub checked_in: usize,
+    // Seated passengers by seat number, then the passengers without a seat
+    pub passengers: Vec<ManifestPassenger>,
 }
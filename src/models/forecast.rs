@@ -0,0 +1,118 @@
+use chrono::NaiveDate;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+// Most recent departed flights of a route whose booking curves make up the history
+pub const FORECAST_HISTORY_FLIGHTS: i64 = 20;
+
+// Pace at or above which a flight books unusually fast, and at or below which unusually slow
+pub const HIGH_PACE: f64 = 1.25;
+pub const LOW_PACE: f64 = 0.75;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PaceFlag {
+    High,
+    Normal,
+    Low,
+}
+
+// Bookings made on each number of days before the departure of a flight
+#[derive(Debug, Clone, Default)]
+pub struct BookingCurve {
+    pub points: Vec<(i64, i64)>,
+}
+
+impl BookingCurve {
+    // Bookings made at least `days_before_departure` days before departure
+    pub fn booked_by(&self, days_before_departure: i64) -> i64 {
+        self.points
+            .iter()
+            .filter(|(days, _)| *days >= days_before_departure)
+            .map(|(_, bookings)| bookings)
+            .sum()
+    }
+
+    pub fn total(&self) -> i64 {
+        self.points.iter().map(|(_, bookings)| bookings).sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct Projection {
+    // Bookings the past flights had made by the same number of days before departure,
+    // absent without history
+    pub expected_booked: Option<f64>,
+    // Bookings so far over the expected bookings, absent when none were expected
+    pub pace: Option<f64>,
+    // Bookings so far plus the bookings the past flights still made from here on
+    pub projected_bookings: i64,
+    pub flag: PaceFlag,
+}
+
+// Project the final bookings of a flight from its bookings so far and the booking curves
+// of past flights of the route
+pub fn project(history: &[BookingCurve], booked: i64, days_to_departure: i64) -> Projection {
+    if history.is_empty() {
+        return Projection {
+            expected_booked: None,
+            pace: None,
+            projected_bookings: booked,
+            flag: PaceFlag::Normal,
+        };
+    }
+
+    let flights = history.len() as f64;
+    let expected_booked = history
+        .iter()
+        .map(|curve| curve.booked_by(days_to_departure) as f64)
+        .sum::<f64>()
+        / flights;
+    let pickup = history
+        .iter()
+        .map(|curve| (curve.total() - curve.booked_by(days_to_departure)) as f64)
+        .sum::<f64>()
+        / flights;
+
+    let pace = (expected_booked > 0.0).then(|| booked as f64 / expected_booked);
+    let flag = match pace {
+        Some(pace) if pace >= HIGH_PACE => PaceFlag::High,
+        Some(pace) if pace <= LOW_PACE => PaceFlag::Low,
+        Some(_) => PaceFlag::Normal,
+        // Nothing was booked this early before, any booking is ahead of the curve
+        None if booked > 0 => PaceFlag::High,
+        None => PaceFlag::Normal,
+    };
+
+    Projection {
+        expected_booked: Some(expected_booked),
+        pace,
+        projected_bookings: booked + pickup.round() as i64,
+        flag,
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct FlightForecast {
+    pub flight_id: i32,
+    pub flight_date: NaiveDate,
+    pub days_to_departure: i64,
+    pub capacity: i32,
+    // Tickets booked so far
+    pub booked: i64,
+    #[serde(flatten)]
+    pub projection: Projection,
+    // Projected bookings over capacity, above 1 when demand exceeds the aircraft
+    pub projected_load_factor: f64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RouteForecastResponse {
+    pub flight_number: i32,
+    pub departure_city: String,
+    pub destination_city: String,
+    // Departed flights whose booking curves the forecast is based on
+    pub history_flights: i64,
+    // Upcoming flights of the route, earliest first
+    pub flights: Vec<FlightForecast>,
+}
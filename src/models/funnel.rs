@@ -0,0 +1,30 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// Steps of the booking funnel, in the order a session goes through them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FunnelStep {
+    Search,
+    SeatMapViewed,
+    BookingAttempted,
+    BookingConfirmed,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct FunnelStepReport {
+    pub step: FunnelStep,
+    // Distinct sessions that reached the step
+    pub sessions: i64,
+    // Sessions of this step over sessions of the previous one, absent for the first step
+    // or when the previous step had no sessions
+    pub conversion_rate: Option<f64>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct FunnelReport {
+    pub days: i64,
+    pub steps: Vec<FunnelStepReport>,
+    // Confirmed bookings over searches
+    pub overall_conversion_rate: Option<f64>,
+}
@@ -0,0 +1,49 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+// Version of the running build
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct BuildInfo {
+    pub version: String,
+    // Commit the binary was built from, when GIT_SHA was set at build time
+    pub git_sha: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct HealthResponse {
+    // "ok", or "unavailable" when the database does not answer
+    pub status: String,
+    pub database: bool,
+    // Round trip of the database ping, None when it failed
+    pub database_latency_ms: Option<u64>,
+    pub uptime_seconds: u64,
+    pub build: BuildInfo,
+}
+
+// Outcome of one diagnostic check
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    // Not configured on this instance
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct DiagnosticCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    // What was measured, or why the check failed
+    pub detail: String,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DiagnosticsResponse {
+    // Fail when any check failed
+    pub status: CheckStatus,
+    pub checks: Vec<DiagnosticCheck>,
+    pub uptime_seconds: u64,
+    pub build: BuildInfo,
+}
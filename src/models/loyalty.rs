@@ -0,0 +1,84 @@
+use crate::models::fare::FareClass;
+use chrono::NaiveDateTime;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+// Points earned per whole unit of the fare paid for a flown ticket, by fare class
+pub const EARN_RATES: [(FareClass, i32); 3] = [
+    (FareClass::Economy, 5),
+    (FareClass::Business, 10),
+    (FareClass::First, 15),
+];
+
+// Points paying for one unit of a fare, in the default currency only
+pub const POINTS_PER_CURRENCY_UNIT: i32 = 100;
+
+// Provider recorded on payments made with points
+pub const LOYALTY_PROVIDER: &str = "loyalty";
+
+// Ledger entries shown with the balance, newest first
+pub const RECENT_LEDGER_ENTRIES: i64 = 50;
+
+// Loyalty Ledger Entry Type Enum
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Display, JsonSchema, sqlx::Type)]
+#[sqlx(type_name = "varchar")]
+pub enum LoyaltyEntryType {
+    // Points for a flown ticket
+    #[sqlx(rename = "EARN")]
+    #[strum(serialize = "EARN")]
+    Earn,
+    // Points spent on a booking
+    #[sqlx(rename = "REDEEM")]
+    #[strum(serialize = "REDEEM")]
+    Redeem,
+    // Points given back for a cancelled ticket paid with points
+    #[sqlx(rename = "REFUND")]
+    #[strum(serialize = "REFUND")]
+    Refund,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct LoyaltyLedgerEntry {
+    pub entry_id: i32,
+    pub entry_type: LoyaltyEntryType,
+    // Negative for redemptions
+    pub points: i32,
+    pub ticket_id: Option<i32>,
+    pub booking_id: Option<i32>,
+    pub description: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct LoyaltyBalanceResponse {
+    pub balance: i32,
+    // What the balance pays for, in currency
+    pub value: Decimal,
+    pub currency: String,
+    pub recent_entries: Vec<LoyaltyLedgerEntry>,
+}
+
+// Points earned for flying a ticket of the fare class bought for the price
+pub fn earned_points(fare_class: FareClass, price: Decimal) -> i32 {
+    let rate = EARN_RATES
+        .iter()
+        .find(|(class, _)| *class == fare_class)
+        .map_or(0, |(_, rate)| *rate);
+    price.floor().to_i32().unwrap_or(0).saturating_mul(rate)
+}
+
+// Points paying for the amount, rounded up to a whole point
+pub fn points_for_amount(amount: Decimal) -> i32 {
+    (amount * Decimal::from(POINTS_PER_CURRENCY_UNIT))
+        .ceil()
+        .to_i32()
+        .unwrap_or(i32::MAX)
+}
+
+// Value of the points in currency
+pub fn points_value(points: i32) -> Decimal {
+    Decimal::new(points as i64 * 100 / POINTS_PER_CURRENCY_UNIT as i64, 2)
+}
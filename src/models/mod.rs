@@ -1,3 +1,24 @@
+pub mod aircraft;
+pub mod booking_rules;
+pub mod changelog;
+pub mod checkin;
+pub mod config;
+pub mod db_enum;
+pub mod fare;
+pub mod file;
 pub mod flight;
+pub mod forecast;
+pub mod funnel;
+pub mod health;
+pub mod loyalty;
+pub mod operation;
+pub mod partner;
+pub mod promo;
+pub mod report;
+pub mod payment;
+pub mod sandbox;
+pub mod seat_block;
+pub mod slo;
+pub mod ssr;
 pub mod ticket;
 pub mod user;
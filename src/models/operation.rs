@@ -0,0 +1,56 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Longest a client can wait for an operation in one request, in seconds
+pub const MAX_WAIT_SECONDS: u64 = 30;
+
+// Operations that finish after the request that started them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationKind {
+    // A booking waiting for its payment
+    Booking,
+    // A refund of a cancelled ticket being paid back
+    Refund,
+}
+
+// Id of an operation, such as "booking-42" for the booking with id 42
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperationId {
+    pub kind: OperationKind,
+    pub id: i32,
+}
+
+impl OperationId {
+    pub fn parse(value: &str) -> Option<Self> {
+        let (kind, id) = value.split_once('-')?;
+        let kind = match kind {
+            "booking" => OperationKind::Booking,
+            "refund" => OperationKind::Refund,
+            _ => return None,
+        };
+        let id = id.parse().ok()?;
+        Some(OperationId { kind, id })
+    }
+}
+
+impl fmt::Display for OperationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            OperationKind::Booking => "booking",
+            OperationKind::Refund => "refund",
+        };
+        write!(f, "{}-{}", kind, self.id)
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct OperationStatusResponse {
+    pub operation_id: String,
+    pub kind: OperationKind,
+    // Status of the booking or refund, e.g. PENDING_PAYMENT or REFUNDED
+    pub status: String,
+    // Whether the status is final and will not change anymore
+    pub done: bool,
+}
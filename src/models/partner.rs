@@ -0,0 +1,109 @@
+use crate::models::flight::FlightStatus;
+use crate::utils::error::{AppError, AppResult};
+use chrono::{NaiveDate, NaiveDateTime};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use validator::Validate;
+
+// Longest period a single availability request may cover
+pub const MAX_AVAILABILITY_DAYS: u32 = 90;
+
+// Period covered when the partner does not ask for a number of days
+pub const DEFAULT_AVAILABILITY_DAYS: u32 = 30;
+
+#[derive(Debug, Clone)]
+pub struct RouteAvailabilityQuery {
+    pub departure_city: String,
+    pub destination_city: String,
+    pub start_date: NaiveDate,
+    pub days: u32,
+}
+
+// Seats left on a route for one day, over all its flights that can still be booked
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct DayAvailability {
+    pub date: NaiveDate,
+    pub flights: i64,
+    pub available_seats: i64,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct RouteAvailabilityResponse {
+    pub departure_city: String,
+    pub destination_city: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    // One entry per day of the period, days without flights included
+    pub days: Vec<DayAvailability>,
+}
+
+#[derive(Debug, Validate, Deserialize, JsonSchema)]
+pub struct CreatePartnerKeyRequest {
+    #[validate(length(min = 1, max = 255))]
+    pub partner_name: String,
+}
+
+// The key itself is only returned here, it cannot be looked up again
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PartnerKeyResponse {
+    pub key_id: i32,
+    pub partner_name: String,
+    pub api_key: String,
+}
+
+// Most changes returned by one sync request
+pub const MAX_CHANGES_PER_PAGE: u32 = 5000;
+
+// Changes returned by one sync request when the partner does not ask for a number
+pub const DEFAULT_CHANGES_PER_PAGE: u32 = 1000;
+
+// Position in the change feed: the change time and flight of the last change seen.
+// Partners treat it as an opaque string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChangeCursor {
+    pub changed_at: NaiveDateTime,
+    pub flight_id: i32,
+}
+
+impl ChangeCursor {
+    pub fn parse(cursor: &str) -> AppResult<Self> {
+        let invalid = || AppError::BadRequest("Invalid change cursor".into());
+        let (micros, flight_id) = cursor.split_once('-').ok_or_else(invalid)?;
+        let micros: i64 = micros.parse().map_err(|_| invalid())?;
+        let changed_at = chrono::DateTime::from_timestamp_micros(micros)
+            .ok_or_else(invalid)?
+            .naive_utc();
+        Ok(ChangeCursor {
+            changed_at,
+            flight_id: flight_id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl fmt::Display for ChangeCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.changed_at.and_utc().timestamp_micros(),
+            self.flight_id
+        )
+    }
+}
+
+// Current inventory of a flight that changed since the cursor of the request
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct FlightChange {
+    // Resume from here to get only the changes after this one
+    pub cursor: String,
+    pub flight_id: i32,
+    pub flight_number: i32,
+    pub flight_date: NaiveDate,
+    pub status: FlightStatus,
+    pub available_tickets: i32,
+    pub available_seats: i64,
+    pub tickets_sold: i64,
+    pub version: Option<i32>,
+    pub changed_at: NaiveDateTime,
+}
@@ -0,0 +1,206 @@
+use crate::models::fare::FareClass;
+use crate::models::ticket::SeatBookingRequest;
+use chrono::{NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+// How long a booking can stay unpaid before its tickets are released
+pub const PAYMENT_TIMEOUT_MINUTES: i64 = 15;
+
+// Currency of all fares
+pub const DEFAULT_CURRENCY: &str = "CAD";
+
+// Fare hold lengths on offer, in hours, with their fee
+pub const FARE_HOLD_OPTIONS: [(i64, Decimal); 2] = [
+    (24, Decimal::from_parts(1500, 0, 0, false, 2)),
+    (48, Decimal::from_parts(2500, 0, 0, false, 2)),
+];
+
+// Fee for holding a fare the given number of hours, None when the length is not offered
+pub fn fare_hold_fee(hours: i64) -> Option<Decimal> {
+    FARE_HOLD_OPTIONS
+        .iter()
+        .find(|(option, _)| *option == hours)
+        .map(|(_, fee)| *fee)
+}
+
+// Payment Status Enum
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Display, JsonSchema, sqlx::Type)]
+#[sqlx(type_name = "varchar")]
+pub enum PaymentStatus {
+    #[sqlx(rename = "PENDING")]
+    #[strum(serialize = "PENDING")]
+    Pending,
+    #[sqlx(rename = "PROCESSING")]
+    #[strum(serialize = "PROCESSING")]
+    Processing,
+    #[sqlx(rename = "CAPTURED")]
+    #[strum(serialize = "CAPTURED")]
+    Captured,
+    #[sqlx(rename = "EXPIRED")]
+    #[strum(serialize = "EXPIRED")]
+    Expired,
+}
+
+// Payment due for a booking, returned when the booking is created
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PaymentSummary {
+    pub payment_id: i32,
+    pub amount: Decimal,
+    pub currency: String,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ConfirmPaymentRequest {
+    // Token of the payment method issued by the payment provider
+    pub payment_token: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PaymentResponse {
+    pub booking_id: i32,
+    pub payment_id: i32,
+    pub amount: Decimal,
+    pub currency: String,
+    pub status: PaymentStatus,
+    pub provider_reference: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FareHoldRequest {
+    // 24 or 48
+    pub hours: i64,
+    // Token of the payment method the fee is charged to
+    pub payment_token: String,
+}
+
+// The price and tickets of the booking are kept until expires_at, when the booking is
+// released unless it was paid
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct FareHoldResponse {
+    pub booking_id: i32,
+    pub hours: i64,
+    pub fee: Decimal,
+    pub currency: String,
+    pub expires_at: NaiveDateTime,
+    pub provider_reference: String,
+}
+
+// Payment passed to a payment provider for capture
+#[derive(Debug)]
+pub struct PaymentCapture {
+    pub payment_id: i32,
+    pub amount: Decimal,
+    pub currency: String,
+    pub payment_token: String,
+}
+
+// Seat picked for a ticket, paid with the payment token when the seat has a price
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SeatSelectionRequest {
+    #[serde(flatten)]
+    pub seat: SeatBookingRequest,
+    // Token of the payment method the price of the seat is charged to
+    #[serde(default)]
+    pub payment_token: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SeatSelectionResponse {
+    pub success: bool,
+    pub seat_number: i32,
+    pub seat_price: Decimal,
+    // Price of the seat less what the ticket already paid for seats
+    pub amount_charged: Decimal,
+    pub currency: String,
+    // None when nothing was charged
+    pub provider_reference: Option<String>,
+}
+
+// New seat of a ticket named by its id, None to give the seat up
+// api-change 2026-10-16 added: Seat change by ticket id
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SeatChangeRequest {
+    pub seat_number: Option<i32>,
+    // Token of the payment method the price of a priced seat is charged to
+    #[serde(default)]
+    pub payment_token: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SeatChangeResponse {
+    pub ticket_id: i32,
+    pub old_seat_number: Option<i32>,
+    pub new_seat_number: Option<i32>,
+    pub amount_charged: Decimal,
+    pub currency: String,
+    // None when nothing was charged
+    pub provider_reference: Option<String>,
+}
+
+// Price of a seat for a ticket, and what the ticket already paid for seats
+#[derive(Debug)]
+pub struct SeatPriceQuote {
+    pub ticket_id: i32,
+    pub flight_id: i32,
+    pub seat_price: Decimal,
+    pub paid: Decimal,
+}
+
+// Refund Status Enum
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Display, JsonSchema, sqlx::Type)]
+#[sqlx(type_name = "varchar")]
+pub enum RefundStatus {
+    #[sqlx(rename = "PENDING")]
+    #[strum(serialize = "PENDING")]
+    Pending,
+    #[sqlx(rename = "PROCESSING")]
+    #[strum(serialize = "PROCESSING")]
+    Processing,
+    #[sqlx(rename = "REFUNDED")]
+    #[strum(serialize = "REFUNDED")]
+    Refunded,
+    #[sqlx(rename = "FAILED")]
+    #[strum(serialize = "FAILED")]
+    Failed,
+}
+
+// Refund of a cancelled ticket, as the customer tracks it
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RefundResponse {
+    pub refund_id: i32,
+    pub ticket_id: i32,
+    pub booking_reference: Option<String>,
+    pub flight_number: i32,
+    pub flight_date: NaiveDate,
+    pub fare_class: FareClass,
+    pub ticket_price: Decimal,
+    pub amount: Decimal,
+    pub currency: String,
+    pub status: RefundStatus,
+    pub provider_reference: Option<String>,
+    pub failure_reason: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub processed_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TicketCancellationResponse {
+    pub ticket_id: i32,
+    pub booking_reference: String,
+    // None when the ticket was not paid or its fare refunds nothing this close to departure
+    pub refund: Option<RefundResponse>,
+}
+
+// Refund passed to a payment provider, paid back to the method of the captured payment
+#[derive(Debug)]
+pub struct RefundExecution {
+    pub refund_id: i32,
+    pub amount: Decimal,
+    pub currency: String,
+    // Provider's reference of the captured payment
+    pub capture_reference: String,
+}
@@ -0,0 +1,99 @@
+use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+use validator::Validate;
+
+// Promo Discount Type Enum
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Display, JsonSchema, sqlx::Type)]
+#[sqlx(type_name = "varchar")]
+pub enum DiscountType {
+    // Percentage off the price of every ticket
+    #[sqlx(rename = "PERCENTAGE")]
+    #[strum(serialize = "PERCENTAGE")]
+    Percentage,
+    // Amount off the total of the booking
+    #[sqlx(rename = "FIXED")]
+    #[strum(serialize = "FIXED")]
+    Fixed,
+}
+
+#[derive(Debug, Validate, Deserialize, JsonSchema)]
+pub struct CreatePromoCodeRequest {
+    #[validate(length(min = 1, max = 32))]
+    pub code: String,
+    pub discount_type: DiscountType,
+    // Percentage between 0 and 100, or the amount off in the currency of the code
+    pub discount_value: Decimal,
+    // Required for fixed discounts
+    #[serde(default)]
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub expires_at: Option<NaiveDateTime>,
+    // Bookings the code can be used for in total, unlimited when absent
+    #[serde(default)]
+    #[validate(range(min = 1))]
+    pub max_redemptions: Option<i32>,
+    // Bookings each customer can use the code for, unlimited when absent
+    #[serde(default)]
+    #[validate(range(min = 1))]
+    pub max_redemptions_per_customer: Option<i32>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PromoCodeResponse {
+    pub promo_code_id: i32,
+    pub code: String,
+    pub discount_type: DiscountType,
+    pub discount_value: Decimal,
+    pub currency: Option<String>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub max_redemptions: Option<i32>,
+    pub max_redemptions_per_customer: Option<i32>,
+    pub redemptions: i32,
+    pub created_at: NaiveDateTime,
+}
+
+// Discount a promo code gave a booking
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct AppliedPromoCode {
+    pub code: String,
+    pub discount: Decimal,
+    pub currency: String,
+}
+
+// Codes are matched without regard to case or surrounding spaces
+pub fn normalize_code(code: &str) -> String {
+    code.trim().to_ascii_uppercase()
+}
+
+// Prices of the tickets of a booking after the discount. A percentage comes off every
+// ticket, a fixed amount off the tickets in order until it is used up, so no ticket
+// costs less than nothing and refunds give back what was actually paid.
+pub fn discounted_prices(
+    prices: &[Decimal],
+    discount_type: DiscountType,
+    discount_value: Decimal,
+) -> Vec<Decimal> {
+    match discount_type {
+        DiscountType::Percentage => {
+            let share = (Decimal::ONE_HUNDRED - discount_value) / Decimal::ONE_HUNDRED;
+            prices
+                .iter()
+                .map(|price| (price * share).round_dp(2))
+                .collect()
+        }
+        DiscountType::Fixed => {
+            let mut remaining = discount_value;
+            prices
+                .iter()
+                .map(|price| {
+                    let cut = remaining.min(*price);
+                    remaining -= cut;
+                    price - cut
+                })
+                .collect()
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use crate::utils::csv::CsvRow;
+use chrono::NaiveDate;
+use rocket::FromFormField;
+use rust_decimal::Decimal;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+// Longest date range a report covers
+pub const MAX_REPORT_DAYS: i64 = 366;
+
+#[derive(Debug, Clone, Copy, PartialEq, FromFormField, JsonSchema)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+}
+
+// Flights departing in the range, of one route when a flight number is given
+#[derive(Debug, Clone)]
+pub struct ReportQuery {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub flight_number: Option<i32>,
+}
+
+// How full a flight of a route is on a day
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct FlightOccupancy {
+    pub flight_number: i32,
+    pub flight_date: NaiveDate,
+    pub departure_city: String,
+    pub destination_city: String,
+    pub capacity: i32,
+    pub tickets_sold: i64,
+    // Tickets sold over seats, above 1 when the flight is overbooked
+    pub load_factor: f64,
+}
+
+// Flights of a route over the whole range
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct RouteOccupancy {
+    pub flight_number: i32,
+    pub departure_city: String,
+    pub destination_city: String,
+    pub flights: i64,
+    pub seats: i64,
+    pub tickets_sold: i64,
+    pub load_factor: f64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct OccupancyReportResponse {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub routes: Vec<RouteOccupancy>,
+    // By date, then flight number
+    pub flights: Vec<FlightOccupancy>,
+}
+
+// Paid sales of a flight on a day in one currency. Cancelled tickets and refunded seat
+// charges do not count.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct FlightRevenue {
+    pub flight_number: i32,
+    pub flight_date: NaiveDate,
+    pub departure_city: String,
+    pub destination_city: String,
+    pub currency: String,
+    pub tickets_sold: i64,
+    pub ticket_revenue: Decimal,
+    pub seat_revenue: Decimal,
+    pub total_revenue: Decimal,
+}
+
+// Sales of a route over the whole range in one currency
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct RouteRevenue {
+    pub flight_number: i32,
+    pub departure_city: String,
+    pub destination_city: String,
+    pub currency: String,
+    pub tickets_sold: i64,
+    pub ticket_revenue: Decimal,
+    pub seat_revenue: Decimal,
+    pub total_revenue: Decimal,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RevenueReportResponse {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub routes: Vec<RouteRevenue>,
+    // By date, then flight number and currency
+    pub flights: Vec<FlightRevenue>,
+}
+
+pub fn load_factor(tickets_sold: i64, seats: i64) -> f64 {
+    if seats > 0 {
+        tickets_sold as f64 / seats as f64
+    } else {
+        0.0
+    }
+}
+
+// Totals of the flights of each route, by flight number
+pub fn route_occupancy(flights: &[FlightOccupancy]) -> Vec<RouteOccupancy> {
+    let mut routes: Vec<RouteOccupancy> = Vec::new();
+    for flight in flights {
+        let index = match routes
+            .iter()
+            .position(|route| route.flight_number == flight.flight_number)
+        {
+            Some(index) => index,
+            None => {
+                routes.push(RouteOccupancy {
+                    flight_number: flight.flight_number,
+                    departure_city: flight.departure_city.clone(),
+                    destination_city: flight.destination_city.clone(),
+                    flights: 0,
+                    seats: 0,
+                    tickets_sold: 0,
+                    load_factor: 0.0,
+                });
+                routes.len() - 1
+            }
+        };
+        let route = &mut routes[index];
+        route.flights += 1;
+        route.seats += i64::from(flight.capacity);
+        route.tickets_sold += flight.tickets_sold;
+        route.load_factor = load_factor(route.tickets_sold, route.seats);
+    }
+    routes.sort_by_key(|route| route.flight_number);
+    routes
+}
+
+// Totals of the flights of each route and currency, by flight number and currency
+pub fn route_revenue(flights: &[FlightRevenue]) -> Vec<RouteRevenue> {
+    let mut routes: Vec<RouteRevenue> = Vec::new();
+    for flight in flights {
+        let index = match routes.iter().position(|route| {
+            route.flight_number == flight.flight_number && route.currency == flight.currency
+        }) {
+            Some(index) => index,
+            None => {
+                routes.push(RouteRevenue {
+                    flight_number: flight.flight_number,
+                    departure_city: flight.departure_city.clone(),
+                    destination_city: flight.destination_city.clone(),
+                    currency: flight.currency.clone(),
+                    tickets_sold: 0,
+                    ticket_revenue: Decimal::ZERO,
+                    seat_revenue: Decimal::ZERO,
+                    total_revenue: Decimal::ZERO,
+                });
+                routes.len() - 1
+            }
+        };
+        let route = &mut routes[index];
+        route.tickets_sold += flight.tickets_sold;
+        route.ticket_revenue += flight.ticket_revenue;
+        route.seat_revenue += flight.seat_revenue;
+        route.total_revenue += flight.total_revenue;
+    }
+    routes.sort_by(|a, b| (a.flight_number, &a.currency).cmp(&(b.flight_number, &b.currency)));
+    routes
+}
+
+impl CsvRow for FlightOccupancy {
+    const HEADER: &'static [&'static str] = &[
+        "flight_number",
+        "flight_date",
+        "departure_city",
+        "destination_city",
+        "capacity",
+        "tickets_sold",
+        "load_factor",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.flight_number.to_string(),
+            self.flight_date.to_string(),
+            self.departure_city.clone(),
+            self.destination_city.clone(),
+            self.capacity.to_string(),
+            self.tickets_sold.to_string(),
+            format!("{:.4}", self.load_factor),
+        ]
+    }
+}
+
+impl CsvRow for FlightRevenue {
+    const HEADER: &'static [&'static str] = &[
+        "flight_number",
+        "flight_date",
+        "departure_city",
+        "destination_city",
+        "currency",
+        "tickets_sold",
+        "ticket_revenue",
+        "seat_revenue",
+        "total_revenue",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.flight_number.to_string(),
+            self.flight_date.to_string(),
+            self.departure_city.clone(),
+            self.destination_city.clone(),
+            self.currency.clone(),
+            self.tickets_sold.to_string(),
+            self.ticket_revenue.to_string(),
+            self.seat_revenue.to_string(),
+            self.total_revenue.to_string(),
+        ]
+    }
+}
@@ -0,0 +1,47 @@
+use crate::models::ticket::TicketBookingRequest;
+use chrono::NaiveDate;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// Header selecting the sandbox for a request made with an API key
+pub const SANDBOX_HEADER: &str = "X-Sandbox";
+
+// Tables copied from the live database when the sandbox is reset. The other tables
+// of the sandbox are emptied.
+pub const SANDBOX_INVENTORY_TABLES: &[&str] = &[
+    "aircraft",
+    "location",
+    "airport",
+    "flight_route",
+    "fare",
+    "codeshare",
+    "seat_block_rule",
+    "flight",
+    "seat_info",
+];
+
+// Passenger a sandbox booking is made for, a new sandbox customer every time
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct SandboxPassenger {
+    pub name: String,
+    pub birth_date: NaiveDate,
+    pub gender: String,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct SandboxBookingRequest {
+    pub passenger: SandboxPassenger,
+    pub booking: TicketBookingRequest,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SandboxResetResponse {
+    // Rows copied from the live database, per table
+    pub copied: Vec<SandboxTableCopy>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SandboxTableCopy {
+    pub table: String,
+    pub rows: u64,
+}
@@ -0,0 +1,68 @@
+use crate::models::aircraft::{SeatLayout, SeatPosition};
+use chrono::{NaiveDate, NaiveDateTime};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+// Seat Block Rule Enum
+#[derive(
+    Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Display, JsonSchema, sqlx::Type,
+)]
+#[sqlx(type_name = "varchar")]
+pub enum SeatBlockRuleKind {
+    // Every seat that is neither at a window nor at the aisle
+    #[sqlx(rename = "MIDDLE_SEATS")]
+    #[strum(serialize = "MIDDLE_SEATS")]
+    MiddleSeats,
+    // Every seat of the last row of the cabin
+    #[sqlx(rename = "LAST_ROW")]
+    #[strum(serialize = "LAST_ROW")]
+    LastRow,
+}
+
+impl SeatBlockRuleKind {
+    // Whether the rule blocks the seat in a cabin of the given layout and capacity
+    pub fn blocks(&self, layout: &SeatLayout, capacity: i32, seat_number: i32) -> bool {
+        match self {
+            SeatBlockRuleKind::MiddleSeats => layout.position(seat_number) == SeatPosition::Middle,
+            SeatBlockRuleKind::LastRow => layout.row(seat_number) == layout.row(capacity),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CreateSeatBlockRuleRequest {
+    pub rule: SeatBlockRuleKind,
+    pub start_date: NaiveDate,
+    // Open-ended when absent
+    #[serde(default)]
+    pub end_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct UpdateSeatBlockRuleRequest {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SeatBlockRuleResponse {
+    pub rule_id: i32,
+    pub flight_number: i32,
+    pub rule: SeatBlockRuleKind,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    pub enabled: bool,
+    // Seats the rule keeps out of sale right now, on flights of any date
+    pub blocked_seats: i64,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SeatBlockRuleChange {
+    pub rule: SeatBlockRuleResponse,
+    // Flights that have not departed whose seats were blocked or given back
+    pub updated_flights: usize,
+    // Seats blocked by the change, or given back when the rule was disabled
+    pub seats: i64,
+}
@@ -0,0 +1,44 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+// Compliance of the service level objectives
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct SloReportResponse {
+    // Minutes the compliance and error budget cover
+    pub window_minutes: u64,
+    // Minutes the burn rate covers
+    pub alert_window_minutes: u64,
+    pub objectives: Vec<SloStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct SloStatus {
+    // booking_latency or error_rate
+    pub name: String,
+    pub description: String,
+    // Share of the requests that must be good, e.g. 0.99
+    pub target: f64,
+    pub requests: u64,
+    pub good_requests: u64,
+    // Share of the requests that were good, None without requests
+    pub compliance: Option<f64>,
+    // Share of the error budget of the window left, below 0 once it is overspent
+    pub error_budget_remaining: f64,
+    // How many times faster than allowed the last minutes spend the error budget
+    pub burn_rate: f64,
+    // The burn rate is over the alert threshold
+    pub alerting: bool,
+}
+
+// Sent to the alerter when an objective starts burning its error budget too fast
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct SloAlert {
+    pub objective: String,
+    pub description: String,
+    pub burn_rate: f64,
+    pub alert_burn_rate: f64,
+    // Requests and good requests of the alert window
+    pub requests: u64,
+    pub good_requests: u64,
+    pub alert_window_minutes: u64,
+}
@@ -0,0 +1,109 @@
+use crate::models::db_enum::DbEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+// Special service request (SSR) codes a passenger can ask for on a ticket, with the
+// IATA code each one is sent and stored as
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Display, JsonSchema)]
+pub enum SsrCode {
+    // Wheelchair to the aircraft door, the passenger can use the stairs
+    #[serde(rename = "WCHR")]
+    #[strum(serialize = "WCHR")]
+    WheelchairRamp,
+    // Wheelchair to the seat, the passenger cannot use the stairs
+    #[serde(rename = "WCHS")]
+    #[strum(serialize = "WCHS")]
+    WheelchairSteps,
+    // Fully immobile passenger, carried to the seat
+    #[serde(rename = "WCHC")]
+    #[strum(serialize = "WCHC")]
+    WheelchairCabin,
+    // Infant travelling on the passenger's lap
+    #[serde(rename = "INFT")]
+    #[strum(serialize = "INFT")]
+    Infant,
+    #[serde(rename = "BLND")]
+    #[strum(serialize = "BLND")]
+    Blind,
+    #[serde(rename = "DEAF")]
+    #[strum(serialize = "DEAF")]
+    Deaf,
+    #[serde(rename = "VGML")]
+    #[strum(serialize = "VGML")]
+    VegetarianMeal,
+    #[serde(rename = "KSML")]
+    #[strum(serialize = "KSML")]
+    KosherMeal,
+    #[serde(rename = "MOML")]
+    #[strum(serialize = "MOML")]
+    MuslimMeal,
+    #[serde(rename = "HNML")]
+    #[strum(serialize = "HNML")]
+    HinduMeal,
+    #[serde(rename = "GFML")]
+    #[strum(serialize = "GFML")]
+    GlutenFreeMeal,
+    #[serde(rename = "DBML")]
+    #[strum(serialize = "DBML")]
+    DiabeticMeal,
+    #[serde(rename = "CHML")]
+    #[strum(serialize = "CHML")]
+    ChildMeal,
+}
+
+impl SsrCode {
+    pub fn is_meal(&self) -> bool {
+        matches!(
+            self,
+            SsrCode::VegetarianMeal
+                | SsrCode::KosherMeal
+                | SsrCode::MuslimMeal
+                | SsrCode::HinduMeal
+                | SsrCode::GlutenFreeMeal
+                | SsrCode::DiabeticMeal
+                | SsrCode::ChildMeal
+        )
+    }
+
+    pub fn is_wheelchair(&self) -> bool {
+        matches!(
+            self,
+            SsrCode::WheelchairRamp | SsrCode::WheelchairSteps | SsrCode::WheelchairCabin
+        )
+    }
+}
+
+// Codes requested for one ticket: each code at most once, and a single meal and a
+// single kind of wheelchair
+pub fn validate_ssr_codes(codes: &[SsrCode]) -> Result<(), String> {
+    for (index, code) in codes.iter().enumerate() {
+        if codes[..index].contains(code) {
+            return Err(format!("Special request {} is given more than once", code));
+        }
+    }
+    if codes.iter().filter(|code| code.is_meal()).count() > 1 {
+        return Err("Only one special meal can be requested per flight".to_string());
+    }
+    if codes.iter().filter(|code| code.is_wheelchair()).count() > 1 {
+        return Err("Only one kind of wheelchair assistance can be requested".to_string());
+    }
+    Ok(())
+}
+
+// Comma separated, as stored in ticket.ssr_codes
+pub fn ssr_codes_to_db(codes: &[SsrCode]) -> String {
+    codes
+        .iter()
+        .map(|code| code.as_db_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// Codes no longer known are left out
+pub fn ssr_codes_from_db(value: &str) -> Vec<SsrCode> {
+    value
+        .split(',')
+        .filter_map(|code| SsrCode::from_db_str(code.trim()))
+        .collect()
+}
@@ -1,4 +1,11 @@
-use chrono::{NaiveDate, NaiveTime};
+use crate::models::fare::{FareClass, FarePrice};
+use crate::models::flight::FlightStatus;
+use crate::models::payment::PaymentSummary;
+use crate::models::promo::AppliedPromoCode;
+use crate::models::ssr::SsrCode;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime};
+use rand::Rng;
+use rust_decimal::Decimal;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -11,42 +18,269 @@ pub struct Ticket {
     pub seat_number: Option<i32>,
     pub flight_date: NaiveDate,
     pub flight_number: i32,
+    pub unaccompanied_minor: bool,
+    pub booking_id: Option<i32>,
+    pub fare_class: FareClass,
+    pub price: Decimal,
+    pub currency: String,
+    pub overbooked: bool,
+    pub booking_reference: Option<String>,
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+// Length of a booking reference
+pub const BOOKING_REFERENCE_LENGTH: usize = 6;
+
+// Characters of booking references, without 0, O, 1 and I which are easily confused
+// when read out over the phone
+const BOOKING_REFERENCE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+// Random booking reference (PNR) such as "K7QXR2"
+pub fn new_booking_reference() -> String {
+    let mut rng = rand::thread_rng();
+    (0..BOOKING_REFERENCE_LENGTH)
+        .map(|_| {
+            BOOKING_REFERENCE_ALPHABET[rng.gen_range(0..BOOKING_REFERENCE_ALPHABET.len())] as char
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
 pub struct TicketBookingRequest {
     pub flights: Vec<FlightBookingRequest>,
+    // Required when the customer is an unaccompanied minor
+    #[serde(default)]
+    pub guardian: Option<GuardianContact>,
+    // Keep the legs that succeeded instead of reverting the whole itinerary when some fail
+    #[serde(default)]
+    pub allow_partial: bool,
+    // Discount off the fare of the booking
+    #[serde(default)]
+    pub promo_code: Option<String>,
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+// Contact of the guardian responsible for an unaccompanied minor
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct GuardianContact {
+    pub name: String,
+    pub phone: String,
+    pub relationship: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
 pub struct FlightBookingRequest {
     pub flight_number: i32,
     pub flight_date: NaiveDate,
     pub preferred_seat: Option<i32>,
+    // Preferred seat must be in the section of this fare class
+    #[serde(default)]
+    pub fare_class: FareClass,
+    // Special assistance and meal requests of the passenger on this flight
+    // api-change 2026-10-16 added: Special service request codes, e.g. WCHR or VGML
+    #[serde(default)]
+    pub ssr_codes: Vec<SsrCode>,
+}
+
+// Overall status of a booking request
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+pub enum BookingStatus {
+    Confirmed,
+    ConfirmedSeatUnavailable,
+    PendingPayment,
+    PartiallyFailed,
+}
+
+// Status of a single flight (leg) in a booking request
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+pub enum LegStatus {
+    Confirmed,
+    ConfirmedSeatUnavailable,
+    PendingPayment,
+    Failed,
 }
 
 #[derive(Debug, Serialize, JsonSchema)]
 pub struct TicketBookingResponse {
+    pub booking_id: i32,
+    // Payment to confirm before it expires, absent when nothing is due
+    pub payment: Option<PaymentSummary>,
+    // Discount of the promo code of the request, already taken off the ticket prices
+    pub promo_code: Option<AppliedPromoCode>,
     pub flight_bookings: Vec<FlightBookingResponse>,
-    pub booking_status: String,
+    // Legs that could not be booked, only populated when allow_partial is set
+    pub failed_legs: Vec<FailedLegResponse>,
+    pub booking_status: BookingStatus,
+    // Whether the passenger travels as an unaccompanied minor, also while payment is due
+    // api-change 2026-10-16 added: Unaccompanied minor flag of the booking
+    pub unaccompanied_minor: bool,
+    // Deprecated: free-text status kept for legacy clients, use booking_status instead
+    // api-change 2026-10-16 deprecated: Use booking_status instead
+    pub legacy_booking_status: String,
+    // Soft warnings, returned in the response envelope when requested
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+}
+
+impl TicketBookingResponse {
+    // Clients without the response envelope expect warnings folded into the legacy status
+    pub fn fold_warnings_into_status(&mut self) {
+        if self.warnings.is_empty() {
+            return;
+        }
+        self.legacy_booking_status = if self.warnings.len() == 1
+            && self.warnings[0] == PREFERRED_SEAT_UNAVAILABLE_WARNING
+        {
+            LEGACY_PREFERRED_SEAT_UNAVAILABLE_STATUS.to_string()
+        } else {
+            format!("{} ({})", self.legacy_booking_status, self.warnings.join(" "))
+        };
+    }
+}
+
+// A booking queued to be applied in the background
+// api-change 2026-10-16 added: Queued bookings answered with 202 Accepted
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct QueuedBookingResponse {
+    pub request_id: String,
+    // Where the outcome of the booking is reported
+    pub status_url: String,
+}
+
+// Where a queued booking stands
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+pub enum BookingRequestStatus {
+    Pending,
+    Confirmed,
+    Failed,
+}
+
+// Outcome of a queued booking, reported at its status URL
+// api-change 2026-10-16 added: Status of a queued booking
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BookingRequestStatusResponse {
+    pub request_id: String,
+    pub status: BookingRequestStatus,
+    // Booking made for the request once it is confirmed
+    pub booking_id: Option<i32>,
+    // Tickets of the booking, empty until it is confirmed
+    pub tickets: Vec<BookedTicket>,
+    // Why the booking failed
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BookedTicket {
+    pub ticket_id: i32,
+    pub public_id: String,
+    pub booking_reference: String,
+    pub flight_number: i32,
+    pub flight_date: NaiveDate,
+    pub seat_number: Option<i32>,
 }
 
+pub const PREFERRED_SEAT_UNAVAILABLE_WARNING: &str =
+    "The preferred seat is currently unavailable, please try again later.";
+
+const LEGACY_PREFERRED_SEAT_UNAVAILABLE_STATUS: &str =
+    "Confirmed booking, however the preferred seat is currently unavaiable, please try again later.";
+
 #[derive(Debug, Serialize, JsonSchema)]
 pub struct FlightBookingResponse {
+    // Replaced by public_id, still accepted where tickets are looked up
     pub ticket_id: i32,
+    pub public_id: String,
+    // Booking reference (PNR) to quote instead of the ticket id
+    pub booking_reference: String,
     pub flight_details: String,
     pub seat_number: Option<i32>,
+    pub unaccompanied_minor: bool,
+    pub fare_class: FareClass,
+    pub price: Decimal,
+    pub currency: String,
+    // Sold beyond the physical seats of the aircraft, the passenger may be bumped
+    pub overbooked: bool,
+    pub status: LegStatus,
+    // Fare class booked when its cabin was sold out and the passenger was moved up to
+    // the cabin of fare_class at that fare (an op-up)
+    // api-change 2026-10-16 added: Fare class booked by an op-up passenger
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upgraded_from: Option<FareClass>,
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct FailedLegResponse {
+    pub flight_number: i32,
+    pub flight_date: NaiveDate,
+    pub status: LegStatus,
+    pub reason: String,
+}
+
+// Result of a dry-run validation of a booking request
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BookingValidationResponse {
+    pub valid: bool,
+    // Issues concerning the itinerary as a whole (e.g. minor policy)
+    pub issues: Vec<String>,
+    pub legs: Vec<LegValidationResult>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct LegValidationResult {
+    pub flight_number: i32,
+    pub flight_date: NaiveDate,
+    pub valid: bool,
+    pub issues: Vec<String>,
+    // Problems with the preferred seat. They do not fail the booking, which goes
+    // through without the seat.
+    // api-change 2026-10-16 added: Preferred seat warnings of a validated leg
+    pub warnings: Vec<String>,
+    // Price quote for the requested fare class, when the flight offers it
+    pub price: Option<FarePrice>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct SeatBookingRequest {
     pub flight_number: i32,
     pub flight_date: chrono::NaiveDate,
     pub seat_number: i32,
 }
 
+// Flight and seat of a ticket
+#[derive(Debug, Clone)]
+pub struct TicketSeat {
+    pub ticket_id: i32,
+    pub flight_id: i32,
+    pub flight_number: i32,
+    pub flight_date: NaiveDate,
+    pub seat_number: Option<i32>,
+}
+
+// Default time a seat stays held while the customer completes payment
+pub const SEAT_HOLD_MINUTES: i64 = 10;
+
+// Longest time a seat can be held
+pub const MAX_SEAT_HOLD_MINUTES: i64 = 15;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+pub struct SeatHoldRequest {
+    pub flight_number: i32,
+    pub flight_date: NaiveDate,
+    pub seat_number: i32,
+    // How long to hold the seat, defaults to SEAT_HOLD_MINUTES
+    #[serde(default)]
+    pub minutes: Option<i64>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SeatHoldResponse {
+    pub flight_number: i32,
+    pub flight_date: NaiveDate,
+    pub seat_number: i32,
+    pub held_until: NaiveDateTime,
+}
+
 #[derive(Debug, Serialize, JsonSchema)]
 pub struct BookingHistoryDetail {
+    pub booking_reference: Option<String>,
     pub flight_number: i32,
     pub seat_number: String,
     pub departure_city: String,
@@ -54,9 +288,137 @@ pub struct BookingHistoryDetail {
     pub flight_date: NaiveDate,
     pub departure_time: NaiveTime,
     pub arrival_time: NaiveTime,
+    // Departure and arrival with the UTC offsets of their airports, and the minutes
+    // in between, when the timezones of both airports are known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub departure_at: Option<DateTime<FixedOffset>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arrival_at: Option<DateTime<FixedOffset>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_minutes: Option<i64>,
+    pub flight_status: FlightStatus,
+    pub delay_minutes: i32,
+    // The flight was cancelled and the passenger has to be moved to another flight
+    pub needs_rebooking: bool,
+    pub booking_id: Option<i32>,
+    // End of the fare hold while the booking is held and unpaid
+    pub fare_held_until: Option<NaiveDateTime>,
+    pub ssr_codes: Vec<SsrCode>,
 }
 
 #[derive(Debug, Serialize, JsonSchema)]
 pub struct BookingHistoryResponse {
     pub flights: Vec<BookingHistoryDetail>,
 }
+
+// Outcome of moving a passenger off a cancelled flight
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+pub enum RebookingStatus {
+    Rebooked,
+    Failed,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RebookedPassenger {
+    pub customer_id: i32,
+    // Ticket on the cancelled flight, replaced by the new one when rebooked
+    pub ticket_id: i32,
+    pub status: RebookingStatus,
+    pub rebooked_ticket_id: Option<i32>,
+    pub rebooked_flight_date: Option<NaiveDate>,
+    pub reason: Option<String>,
+}
+
+// Flight of the route a passenger of a cancelled or long delayed flight may move to
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RebookingOption {
+    pub flight_id: i32,
+    pub flight_number: i32,
+    pub flight_date: NaiveDate,
+    pub departure_time: NaiveTime,
+    pub arrival_time: NaiveTime,
+    pub flight_status: FlightStatus,
+    pub delay_minutes: i32,
+    pub available_tickets: i32,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RebookingOptionsResponse {
+    pub ticket_id: i32,
+    // Status and delay of the disrupted flight
+    pub flight_status: FlightStatus,
+    pub delay_minutes: i32,
+    // By date
+    pub options: Vec<RebookingOption>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SelfRebookingRequest {
+    // One of the rebooking options of the ticket
+    pub flight_id: i32,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RebookingSummary {
+    pub flight_id: i32,
+    pub rebooked: usize,
+    // Passengers still waiting for a flight, retried on the next run
+    pub failed: usize,
+    pub passengers: Vec<RebookedPassenger>,
+}
+
+// Why an admin changed a ticket of a closed flight
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum CorrectionReason {
+    SeatChangedOnboard,
+    DataEntryError,
+    Operational,
+}
+
+// Seat correction of a ticket, allowed even after the flight was closed
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct TicketCorrectionRequest {
+    pub reason: CorrectionReason,
+    // Seat the passenger actually had, none when they had no seat
+    pub seat_number: Option<i32>,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TicketCorrectionResponse {
+    pub ticket_id: i32,
+    pub flight_id: i32,
+    pub reason: CorrectionReason,
+    pub old_seat_number: Option<i32>,
+    pub new_seat_number: Option<i32>,
+}
+
+// Customers younger than this at the flight date travel as unaccompanied minors,
+// unless the booking rules set another age
+pub const UNACCOMPANIED_MINOR_AGE: i32 = 12;
+
+// Children younger than this cannot travel alone at all, unless the booking rules
+// set another age
+pub const MIN_UNACCOMPANIED_AGE: i32 = 5;
+
+// Ticket found by its booking reference
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TicketByReferenceResponse {
+    pub ticket_id: i32,
+    // None until the server has given the tickets sold before public ids theirs
+    pub public_id: Option<String>,
+    pub booking_reference: String,
+    pub flight_number: i32,
+    pub flight_date: NaiveDate,
+    pub departure_city: String,
+    pub destination_city: String,
+    pub departure_time: NaiveTime,
+    pub arrival_time: NaiveTime,
+    pub flight_status: FlightStatus,
+    pub seat_number: Option<i32>,
+    pub fare_class: FareClass,
+    pub price: Decimal,
+    pub currency: String,
+    pub ssr_codes: Vec<SsrCode>,
+}
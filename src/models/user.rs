@@ -1,4 +1,4 @@
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use validator::{Validate, ValidationError};
@@ -12,7 +12,7 @@ pub struct User {
     pub role: String,
 }
 
-#[derive(Debug, JsonSchema, PartialEq)]
+#[derive(Debug, Clone, Copy, JsonSchema, PartialEq)]
 pub enum Role {
     User,
     Admin,
@@ -48,6 +48,10 @@ pub struct UserRegistrationRequest {
     pub gender: String,
     #[serde(default)]
     pub role: Role,
+    // A verification token is sent to the address when given
+    #[serde(default)]
+    #[validate(email)]
+    pub email: Option<String>,
 }
 
 fn validate_gender(gender: &str) -> Result<(), ValidationError> {
@@ -60,6 +64,72 @@ fn validate_gender(gender: &str) -> Result<(), ValidationError> {
     }
 }
 
+// Account and customer details of a user
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct UserProfile {
+    pub user_id: i32,
+    pub username: String,
+    pub name: String,
+    pub birth_date: NaiveDate,
+    pub gender: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub phone: Option<String>,
+}
+
+// Fields left out are not changed
+#[derive(Debug, Validate, Deserialize, JsonSchema, Default)]
+pub struct UpdateProfileRequest {
+    #[validate(length(min = 1, max = 255))]
+    pub name: Option<String>,
+    pub birth_date: Option<NaiveDate>,
+    #[validate(custom(function = "validate_gender"))]
+    pub gender: Option<String>,
+    #[validate(email)]
+    pub email: Option<String>,
+    #[validate(length(min = 3, max = 32))]
+    pub phone: Option<String>,
+}
+
+// Shortest password accepted when changing or resetting it
+pub const MIN_PASSWORD_LENGTH: u64 = 8;
+
+// Time a password reset token stays valid
+pub const PASSWORD_RESET_TOKEN_MINUTES: i64 = 30;
+
+// Time an email verification token stays valid
+pub const EMAIL_VERIFICATION_TOKEN_HOURS: i64 = 48;
+
+// Time a support code stays valid
+pub const SUPPORT_TOKEN_MINUTES: i64 = 15;
+
+// Code a user reads out to a support agent so they can look at the user's bookings
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SupportTokenResponse {
+    pub token: String,
+    pub scope: String,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, Validate, Deserialize, JsonSchema)]
+pub struct ChangePasswordRequest {
+    pub old_password: String,
+    #[validate(length(min = MIN_PASSWORD_LENGTH))]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ForgotPasswordRequest {
+    pub username: String,
+}
+
+#[derive(Debug, Validate, Deserialize, JsonSchema)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    #[validate(length(min = MIN_PASSWORD_LENGTH))]
+    pub new_password: String,
+}
+
 #[derive(Debug, Deserialize, JsonSchema)]
 pub struct UserLoginRequest {
     pub username: String,
@@ -90,3 +160,48 @@ impl RegisterResponse {
         }
     }
 }
+
+// A set of accounts that likely belong to the same person
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DuplicateUserGroup {
+    pub reason: String,
+    pub users: Vec<DuplicateUserCandidate>,
+}
+
+#[derive(Debug, Serialize, JsonSchema, Clone)]
+pub struct DuplicateUserCandidate {
+    pub user_id: i32,
+    pub username: String,
+    pub name: String,
+    pub birth_date: NaiveDate,
+    pub data_region: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DuplicateUsersResponse {
+    pub groups: Vec<DuplicateUserGroup>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct MergeUsersRequest {
+    // Account that is kept
+    pub surviving_user_id: i32,
+    // Account whose data is moved to the surviving one, then deleted
+    pub duplicate_user_id: i32,
+    // Only report what would change
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct MergeUsersResponse {
+    pub dry_run: bool,
+    pub merged: bool,
+    pub surviving_user_id: i32,
+    pub duplicate_user_id: i32,
+    // Tickets moved to the surviving account
+    pub reassigned_tickets: Vec<i32>,
+    // Tickets for flights both accounts hold, which block the merge
+    pub conflicting_tickets: Vec<i32>,
+    pub reassigned_flight_views: u64,
+}
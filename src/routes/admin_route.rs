@@ -0,0 +1,568 @@
+use crate::models::aircraft::{
+    AircraftLayoutRequest, AircraftResponse, CreateAircraftRequest, SwapAircraftRequest,
+    SwapAircraftResponse,
+};
+use crate::models::flight::{
+    BumpRequest, BumpResponse, FlightCloseOutResponse, FlightManifestResponse, FlightStatus,
+    RouteAuditEntry, UpdateFlightStatusRequest, UpdateFlightStatusResponse,
+    UpdateOverbookingRequest, UpdateOverbookingResponse,
+};
+use crate::models::forecast::RouteForecastResponse;
+use crate::models::funnel::FunnelReport;
+use crate::models::config::ConfigReloadResponse;
+use crate::models::health::DiagnosticsResponse;
+use crate::models::partner::{CreatePartnerKeyRequest, PartnerKeyResponse};
+use crate::models::promo::{CreatePromoCodeRequest, PromoCodeResponse};
+use crate::models::report::{
+    OccupancyReportResponse, ReportFormat, ReportQuery, RevenueReportResponse,
+};
+use crate::models::sandbox::SandboxResetResponse;
+use crate::models::seat_block::{
+    CreateSeatBlockRuleRequest, SeatBlockRuleChange, SeatBlockRuleResponse,
+    UpdateSeatBlockRuleRequest,
+};
+use crate::models::slo::SloReportResponse;
+use crate::models::ticket::{
+    BookingHistoryResponse, RebookingSummary, TicketCorrectionRequest, TicketCorrectionResponse,
+};
+use crate::models::user::{DuplicateUsersResponse, MergeUsersRequest, MergeUsersResponse};
+use crate::services::admin_service::AdminService;
+use crate::services::aircraft_service::AircraftService;
+use crate::services::funnel_service::FunnelService;
+use crate::services::health_service::HealthService;
+use crate::services::partner_service::PartnerService;
+use crate::services::promo_code_service::PromoCodeService;
+use crate::services::report_service::{check_range, ReportService};
+use crate::services::route_stats_service::RouteStatsService;
+use crate::services::sandbox_service::SandboxService;
+use crate::services::seat_block_service::SeatBlockService;
+use crate::services::slo_service::SloService;
+use crate::services::ticket_service::TicketService;
+use crate::utils::csv::{self, JsonOrCsv};
+use crate::utils::error::AppError;
+use crate::utils::flight_ref::FlightRef;
+use crate::utils::jwt::{AdminUser, SupportAccess};
+use crate::utils::ndjson::{collect_rows, JsonOrNdjson, NdjsonRequested, NdjsonStream};
+use crate::utils::region::RegionFilter;
+use crate::utils::tunables::ConfigReloader;
+use chrono::NaiveDate;
+use rocket::serde::json::{json, Json, Value};
+use rocket::State;
+use rocket_okapi::openapi;
+
+/// Update the overbooking percentage of a route
+#[openapi(tag = "Admin")]
+#[patch("/admin/routes/<flight_number>/overbooking", format = "json", data = "<request>")]
+pub async fn update_route_overbooking(
+    flight_number: i32,
+    request: Json<UpdateOverbookingRequest>,
+    admin: AdminUser,
+    admin_service: &State<AdminService>,
+) -> Result<Json<UpdateOverbookingResponse>, AppError> {
+    let response = admin_service
+        .update_route_overbooking(admin.user_id, flight_number, request.into_inner())
+        .await?;
+    Ok(Json(response))
+}
+
+/// Find likely duplicate user accounts. `regions` and `exclude_regions` are comma
+/// separated data regions to restrict the report to or leave out.
+#[openapi(tag = "Admin")]
+#[get("/admin/users/duplicates?<regions>&<exclude_regions>")]
+pub async fn find_duplicate_users(
+    regions: Option<String>,
+    exclude_regions: Option<String>,
+    _admin: AdminUser,
+    admin_service: &State<AdminService>,
+) -> Result<Json<DuplicateUsersResponse>, AppError> {
+    let regions = RegionFilter::parse(regions.as_deref(), exclude_regions.as_deref())?;
+    let response = admin_service.find_duplicate_users(&regions).await?;
+    Ok(Json(response))
+}
+
+/// Merge a duplicate user account into another one
+#[openapi(tag = "Admin")]
+#[post("/admin/users/merge", format = "json", data = "<request>")]
+pub async fn merge_users(
+    request: Json<MergeUsersRequest>,
+    _admin: AdminUser,
+    admin_service: &State<AdminService>,
+) -> Result<Json<MergeUsersResponse>, AppError> {
+    let response = admin_service.merge_users(request.into_inner()).await?;
+    Ok(Json(response))
+}
+
+/// Unlock an account locked after too many failed logins
+#[openapi(tag = "Admin")]
+#[post("/admin/users/<user_id>/unlock")]
+pub async fn unlock_user(
+    user_id: i32,
+    admin: AdminUser,
+    admin_service: &State<AdminService>,
+) -> Result<Json<Value>, AppError> {
+    admin_service.unlock_user(user_id).await?;
+    tracing::info!(admin_id = admin.user_id, user_id, "account unlocked");
+    Ok(Json(json!({ "success": true })))
+}
+
+/// Bump the passengers of an oversold flight and rebook them on a later flight
+#[openapi(tag = "Admin")]
+#[post("/admin/flights/bump?<flight..>", format = "json", data = "<request>")]
+pub async fn bump_overbooked_passengers(
+    flight: FlightRef,
+    request: Json<BumpRequest>,
+    admin: AdminUser,
+    admin_service: &State<AdminService>,
+) -> Result<Json<BumpResponse>, AppError> {
+    let response = admin_service
+        .bump_overbooked_passengers(
+            admin.user_id,
+            flight.flight_number,
+            flight.date()?,
+            request.into_inner(),
+        )
+        .await?;
+    Ok(Json(response))
+}
+
+/// Update the status and delay of a flight, cancelling it rebooks its passengers
+#[openapi(tag = "Admin")]
+#[patch("/admin/flights/<flight_id>/status", format = "json", data = "<request>")]
+pub async fn update_flight_status(
+    flight_id: i32,
+    request: Json<UpdateFlightStatusRequest>,
+    _admin: AdminUser,
+    admin_service: &State<AdminService>,
+    ticket_service: &State<TicketService>,
+) -> Result<Json<UpdateFlightStatusResponse>, AppError> {
+    let mut response = admin_service
+        .update_flight_status(flight_id, request.into_inner())
+        .await?;
+    if response.status == FlightStatus::Cancelled {
+        response.rebooking = Some(ticket_service.rebook_cancelled_flight(flight_id).await?);
+    }
+    Ok(Json(response))
+}
+
+/// Retry rebooking the passengers of a cancelled flight
+#[openapi(tag = "Admin")]
+#[post("/admin/flights/<flight_id>/rebook")]
+pub async fn rebook_cancelled_flight(
+    flight_id: i32,
+    _admin: AdminUser,
+    ticket_service: &State<TicketService>,
+) -> Result<Json<RebookingSummary>, AppError> {
+    let summary = ticket_service.rebook_cancelled_flight(flight_id).await?;
+    Ok(Json(summary))
+}
+
+/// Add an aircraft with its cabin layout, for routes and aircraft swaps to use
+// api-change 2026-10-16 added: Admins can create aircraft
+#[openapi(tag = "Admin")]
+#[post("/admin/aircraft", format = "json", data = "<request>")]
+pub async fn create_aircraft(
+    request: Json<CreateAircraftRequest>,
+    _admin: AdminUser,
+    aircraft_service: &State<AircraftService>,
+) -> Result<Json<AircraftResponse>, AppError> {
+    let response = aircraft_service
+        .create_aircraft(request.into_inner())
+        .await?;
+    Ok(Json(response))
+}
+
+/// Replace the cabin layout of an aircraft. A new capacity adds or removes seats on
+/// the flights it operates that have not departed, and is refused when it is below
+/// the seats already sold on one of them.
+// api-change 2026-10-16 added: Admins can update aircraft
+#[openapi(tag = "Admin")]
+#[put("/admin/aircraft/<aircraft_id>", format = "json", data = "<request>")]
+pub async fn update_aircraft(
+    aircraft_id: i32,
+    request: Json<AircraftLayoutRequest>,
+    _admin: AdminUser,
+    aircraft_service: &State<AircraftService>,
+) -> Result<Json<AircraftResponse>, AppError> {
+    let response = aircraft_service
+        .update_aircraft(aircraft_id, request.into_inner())
+        .await?;
+    Ok(Json(response))
+}
+
+/// Operate a flight with another aircraft and move its passengers to the new seat map
+#[openapi(tag = "Admin")]
+#[put("/admin/flights/<flight_id>/aircraft", format = "json", data = "<request>")]
+pub async fn swap_aircraft(
+    flight_id: i32,
+    request: Json<SwapAircraftRequest>,
+    admin: AdminUser,
+    admin_service: &State<AdminService>,
+) -> Result<Json<SwapAircraftResponse>, AppError> {
+    let response = admin_service
+        .swap_aircraft(admin.user_id, flight_id, request.into_inner())
+        .await?;
+    Ok(Json(response))
+}
+
+/// Passenger manifest of a flight for ground staff: every ticketed passenger with their
+/// seat, check-in status and special service requests. With `Accept: application/x-ndjson`
+/// the passengers are streamed one per line.
+// api-change 2026-10-16 added: Passenger manifest of a flight, also streamed as NDJSON
+#[openapi(tag = "Admin")]
+#[get("/admin/flights/<flight_id>/manifest")]
+pub async fn flight_manifest(
+    flight_id: i32,
+    ndjson: NdjsonRequested,
+    _admin: AdminUser,
+    admin_service: &State<AdminService>,
+) -> Result<JsonOrNdjson<FlightManifestResponse>, AppError> {
+    if ndjson.0 {
+        // An unknown flight is reported before the stream starts
+        admin_service.ensure_flight_exists(flight_id).await?;
+        let admin_service = admin_service.inner().clone();
+        return Ok(JsonOrNdjson::Ndjson(NdjsonStream::spawn(
+            move |sink| async move {
+                admin_service
+                    .export_manifest_passengers(flight_id, sink)
+                    .await
+            },
+        )));
+    }
+
+    let response = admin_service.flight_manifest(flight_id).await?;
+    Ok(JsonOrNdjson::Json(Json(response)))
+}
+
+/// Close out a flight, after which its tickets and seats only change through corrections
+#[openapi(tag = "Admin")]
+#[post("/admin/flights/<flight_id>/close")]
+pub async fn close_flight(
+    flight_id: i32,
+    _admin: AdminUser,
+    admin_service: &State<AdminService>,
+) -> Result<Json<FlightCloseOutResponse>, AppError> {
+    let response = admin_service.close_flight(flight_id).await?;
+    Ok(Json(response))
+}
+
+/// Correct the seat of a ticket, also on closed flights. The ticket is named by its
+/// public id, or by its numeric id while those are still accepted.
+#[openapi(tag = "Admin")]
+#[patch("/admin/tickets/<ticket_id>/seat", format = "json", data = "<request>")]
+pub async fn correct_ticket(
+    ticket_id: &str,
+    request: Json<TicketCorrectionRequest>,
+    admin: AdminUser,
+    ticket_service: &State<TicketService>,
+) -> Result<Json<TicketCorrectionResponse>, AppError> {
+    let ticket_id = ticket_service.ticket_id_for(ticket_id).await?;
+    let response = ticket_service
+        .correct_ticket(admin.user_id, ticket_id, request.into_inner())
+        .await?;
+    Ok(Json(response))
+}
+
+/// List the changes made to flight routes, streamed as NDJSON with `Accept: application/x-ndjson`
+#[openapi(tag = "Admin")]
+#[get("/admin/routes/audit?<flight_number>")]
+pub async fn route_audit(
+    flight_number: Option<i32>,
+    ndjson: NdjsonRequested,
+    _admin: AdminUser,
+    admin_service: &State<AdminService>,
+) -> Result<JsonOrNdjson<Vec<RouteAuditEntry>>, AppError> {
+    if ndjson.0 {
+        let admin_service = admin_service.inner().clone();
+        return Ok(JsonOrNdjson::Ndjson(NdjsonStream::spawn(
+            move |sink| async move { admin_service.export_route_audit(flight_number, sink).await },
+        )));
+    }
+
+    let entries =
+        collect_rows(|sink| admin_service.export_route_audit(flight_number, sink)).await?;
+    Ok(JsonOrNdjson::Json(Json(entries)))
+}
+
+/// Report how many sessions reach each step of the booking funnel
+#[openapi(tag = "Admin")]
+#[get("/admin/analytics/funnel?<days>")]
+pub async fn funnel_report(
+    days: Option<i64>,
+    _admin: AdminUser,
+    funnel_service: &State<FunnelService>,
+) -> Result<Json<FunnelReport>, AppError> {
+    let days = days.unwrap_or(7);
+    if !(1..=90).contains(&days) {
+        return Err(AppError::BadRequest("days must be between 1 and 90".into()));
+    }
+
+    let report = funnel_service.conversion_report(days).await?;
+    Ok(Json(report))
+}
+
+/// Load factor of every flight departing between `from` and `to` (YYYY-MM-DD), and of
+/// each route over the range, of one route with `flight_number`. `format=csv`
+/// downloads the flights as a CSV file, `Accept: application/x-ndjson` streams them
+/// one per line.
+// api-change 2026-10-16 added: Occupancy reports for admins
+#[openapi(tag = "Admin")]
+#[get("/admin/reports/occupancy?<from>&<to>&<flight_number>&<format>")]
+pub async fn occupancy_report(
+    from: String,
+    to: String,
+    flight_number: Option<i32>,
+    format: Option<ReportFormat>,
+    ndjson: NdjsonRequested,
+    _admin: AdminUser,
+    report_service: &State<ReportService>,
+) -> Result<JsonOrCsv<OccupancyReportResponse>, AppError> {
+    let query = report_query(&from, &to, flight_number)?;
+    if ndjson.0 && format.is_none() {
+        check_range(&query)?;
+        let report_service = report_service.inner().clone();
+        return Ok(JsonOrCsv::Ndjson(NdjsonStream::spawn(
+            move |sink| async move { report_service.export_occupancy(query, sink).await },
+        )));
+    }
+
+    let report = report_service.occupancy_report(query).await?;
+    Ok(match format.unwrap_or(ReportFormat::Json) {
+        ReportFormat::Json => JsonOrCsv::Json(Json(report)),
+        ReportFormat::Csv => JsonOrCsv::Csv {
+            file_name: format!("occupancy-{}-{}", report.from, report.to),
+            csv: csv::to_csv(&report.flights),
+        },
+    })
+}
+
+/// Paid ticket and seat sales of every flight departing between `from` and `to`
+/// (YYYY-MM-DD), and of each route over the range, per currency. `format=csv`
+/// downloads the flights as a CSV file, `Accept: application/x-ndjson` streams them
+/// one per line.
+// api-change 2026-10-16 added: Revenue reports for admins
+#[openapi(tag = "Admin")]
+#[get("/admin/reports/revenue?<from>&<to>&<flight_number>&<format>")]
+pub async fn revenue_report(
+    from: String,
+    to: String,
+    flight_number: Option<i32>,
+    format: Option<ReportFormat>,
+    ndjson: NdjsonRequested,
+    _admin: AdminUser,
+    report_service: &State<ReportService>,
+) -> Result<JsonOrCsv<RevenueReportResponse>, AppError> {
+    let query = report_query(&from, &to, flight_number)?;
+    if ndjson.0 && format.is_none() {
+        check_range(&query)?;
+        let report_service = report_service.inner().clone();
+        return Ok(JsonOrCsv::Ndjson(NdjsonStream::spawn(
+            move |sink| async move { report_service.export_revenue(query, sink).await },
+        )));
+    }
+
+    let report = report_service.revenue_report(query).await?;
+    Ok(match format.unwrap_or(ReportFormat::Json) {
+        ReportFormat::Json => JsonOrCsv::Json(Json(report)),
+        ReportFormat::Csv => JsonOrCsv::Csv {
+            file_name: format!("revenue-{}-{}", report.from, report.to),
+            csv: csv::to_csv(&report.flights),
+        },
+    })
+}
+
+fn report_query(from: &str, to: &str, flight_number: Option<i32>) -> Result<ReportQuery, AppError> {
+    let from = NaiveDate::parse_from_str(from, "%Y-%m-%d")
+        .map_err(|_| AppError::BadRequest("Invalid from date format".into()))?;
+    let to = NaiveDate::parse_from_str(to, "%Y-%m-%d")
+        .map_err(|_| AppError::BadRequest("Invalid to date format".into()))?;
+    Ok(ReportQuery {
+        from,
+        to,
+        flight_number,
+    })
+}
+
+/// Forecast the bookings of the upcoming flights of a route from the booking curves of
+/// its recent flights, flagging the flights that book unusually fast or slow
+// api-change 2026-10-16 added: Route demand forecasts for admins
+#[openapi(tag = "Admin")]
+#[get("/admin/routes/<flight_number>/forecast")]
+pub async fn route_forecast(
+    flight_number: i32,
+    _admin: AdminUser,
+    route_stats_service: &State<RouteStatsService>,
+) -> Result<Json<RouteForecastResponse>, AppError> {
+    let forecast = route_stats_service.route_forecast(flight_number).await?;
+    Ok(Json(forecast))
+}
+
+/// View the bookings of a customer with the support code they generated, sent in
+/// the X-Support-Token header
+#[openapi(tag = "Admin")]
+#[get("/admin/support/bookings")]
+pub async fn support_view_bookings(
+    access: SupportAccess,
+    ticket_service: &State<TicketService>,
+) -> Result<Json<BookingHistoryResponse>, AppError> {
+    tracing::info!(
+        agent_id = access.agent_id,
+        user_id = access.user_id,
+        "support agent viewed bookings"
+    );
+    let response = ticket_service.get_history(access.user_id).await?;
+    Ok(Json(response))
+}
+
+/// Create an API key for a travel agency partner. The key is only shown once.
+#[openapi(tag = "Admin")]
+#[post("/admin/partners/keys", format = "json", data = "<request>")]
+pub async fn create_partner_key(
+    request: Json<CreatePartnerKeyRequest>,
+    _admin: AdminUser,
+    partner_service: &State<PartnerService>,
+) -> Result<Json<PartnerKeyResponse>, AppError> {
+    let response = partner_service.create_api_key(request.into_inner()).await?;
+    Ok(Json(response))
+}
+
+/// Revoke the API key of a partner
+#[openapi(tag = "Admin")]
+#[delete("/admin/partners/keys/<key_id>")]
+pub async fn revoke_partner_key(
+    key_id: i32,
+    _admin: AdminUser,
+    partner_service: &State<PartnerService>,
+) -> Result<Json<Value>, AppError> {
+    partner_service.revoke_api_key(key_id).await?;
+    Ok(Json(json!({ "revoked": true })))
+}
+
+/// Create a promo code giving a percentage or a fixed amount off the fare of a booking
+#[openapi(tag = "Admin")]
+#[post("/admin/promo-codes", format = "json", data = "<request>")]
+pub async fn create_promo_code(
+    request: Json<CreatePromoCodeRequest>,
+    admin: AdminUser,
+    promo_code_service: &State<PromoCodeService>,
+) -> Result<Json<PromoCodeResponse>, AppError> {
+    let response = promo_code_service
+        .create_promo_code(admin.user_id, request.into_inner())
+        .await?;
+    Ok(Json(response))
+}
+
+/// List the promo codes with the number of bookings that used them
+#[openapi(tag = "Admin")]
+#[get("/admin/promo-codes")]
+pub async fn list_promo_codes(
+    _admin: AdminUser,
+    promo_code_service: &State<PromoCodeService>,
+) -> Result<Json<Vec<PromoCodeResponse>>, AppError> {
+    let response = promo_code_service.list_promo_codes().await?;
+    Ok(Json(response))
+}
+
+/// Keep seats of a route out of sale between two dates, e.g. every middle seat. The
+/// seats are blocked on the open flights of the route right away, and on the flights
+/// generated later.
+// api-change 2026-10-16 added: Seat block rules keep seats of a route out of sale
+#[openapi(tag = "Admin")]
+#[post(
+    "/admin/routes/<flight_number>/seat-block-rules",
+    format = "json",
+    data = "<request>"
+)]
+pub async fn create_seat_block_rule(
+    flight_number: i32,
+    request: Json<CreateSeatBlockRuleRequest>,
+    admin: AdminUser,
+    seat_block_service: &State<SeatBlockService>,
+) -> Result<Json<SeatBlockRuleChange>, AppError> {
+    let response = seat_block_service
+        .create_rule(admin.user_id, flight_number, request.into_inner())
+        .await?;
+    Ok(Json(response))
+}
+
+/// List the seat block rules of a route with the seats each keeps out of sale
+#[openapi(tag = "Admin")]
+#[get("/admin/routes/<flight_number>/seat-block-rules")]
+pub async fn list_seat_block_rules(
+    flight_number: i32,
+    _admin: AdminUser,
+    seat_block_service: &State<SeatBlockService>,
+) -> Result<Json<Vec<SeatBlockRuleResponse>>, AppError> {
+    let response = seat_block_service.list_rules(flight_number).await?;
+    Ok(Json(response))
+}
+
+/// Turn a seat block rule on or off. Turning it off gives the seats it blocked on
+/// flights that have not departed back to sale.
+#[openapi(tag = "Admin")]
+#[patch(
+    "/admin/seat-block-rules/<rule_id>",
+    format = "json",
+    data = "<request>"
+)]
+pub async fn update_seat_block_rule(
+    rule_id: i32,
+    request: Json<UpdateSeatBlockRuleRequest>,
+    _admin: AdminUser,
+    seat_block_service: &State<SeatBlockService>,
+) -> Result<Json<SeatBlockRuleChange>, AppError> {
+    let response = seat_block_service
+        .set_enabled(rule_id, request.enabled)
+        .await?;
+    Ok(Json(response))
+}
+
+/// Empty the sandbox and copy the current flights, seats and fares of the live
+/// database into it
+#[openapi(tag = "Admin")]
+#[post("/admin/sandbox/reset")]
+pub async fn reset_sandbox(
+    _admin: AdminUser,
+    sandbox_service: &State<Option<SandboxService>>,
+) -> Result<Json<SandboxResetResponse>, AppError> {
+    let sandbox_service = sandbox_service
+        .inner()
+        .as_ref()
+        .ok_or_else(|| AppError::NotFound("No sandbox database is configured".into()))?;
+    let response = sandbox_service.reset().await?;
+    Ok(Json(response))
+}
+
+/// Run live checks of the database, replica, background jobs and event bus for
+/// on-call debugging. Failed checks do not fail the request.
+#[openapi(tag = "Admin")]
+#[get("/admin/diagnostics")]
+pub async fn diagnostics(
+    _admin: AdminUser,
+    health_service: &State<HealthService>,
+) -> Json<DiagnosticsResponse> {
+    Json(health_service.diagnostics().await)
+}
+
+/// Compliance of the booking latency and error rate objectives over the requests this
+/// server answered recently, with how fast each burns its error budget.
+// api-change 2026-10-16 added: Service level objective compliance for admins
+#[openapi(tag = "Admin")]
+#[get("/admin/slo")]
+pub async fn slo_report(
+    _admin: AdminUser,
+    slo_service: &State<SloService>,
+) -> Json<SloReportResponse> {
+    Json(slo_service.report())
+}
+
+/// Read the configuration file and environment again and apply the limits and cache
+/// TTLs. Other settings only change on restart and are listed in the response.
+#[openapi(tag = "Admin")]
+#[post("/admin/config/reload")]
+pub async fn reload_config(
+    _admin: AdminUser,
+    config_reloader: &State<ConfigReloader>,
+) -> Result<Json<ConfigReloadResponse>, AppError> {
+    let response = config_reloader.reload()?;
+    Ok(Json(response))
+}
@@ -0,0 +1,22 @@
+use crate::models::changelog::{self, ApiChangeKind, ApiChangelogResponse};
+use crate::utils::error::AppError;
+use chrono::NaiveDate;
+use rocket::serde::json::Json;
+use rocket_okapi::openapi;
+
+/// API-visible changes such as new fields and deprecations, newest first, so
+/// integrators can follow the API without reading the commit history. `since`
+/// (YYYY-MM-DD) and `kind` narrow the list. No login is needed.
+// api-change 2026-10-16 added: This changelog
+#[openapi(tag = "Changelog")]
+#[get("/changelog?<since>&<kind>")]
+pub async fn get_changelog(
+    since: Option<String>,
+    kind: Option<ApiChangeKind>,
+) -> Result<Json<ApiChangelogResponse>, AppError> {
+    let since = since
+        .map(|date| NaiveDate::parse_from_str(&date, "%Y-%m-%d"))
+        .transpose()
+        .map_err(|_| AppError::BadRequest("Invalid since date format".into()))?;
+    Ok(Json(changelog::changelog(since, kind)))
+}
@@ -0,0 +1,16 @@
+use crate::services::file_service::FileService;
+use crate::utils::document::Document;
+use crate::utils::error::AppError;
+use rocket::State;
+use rocket_okapi::openapi;
+
+/// Download a stored document, such as an e-ticket, invoice or export. The token
+/// comes from a download link and is the only credential needed until it expires.
+#[openapi(tag = "Files")]
+#[get("/files/<token>")]
+pub async fn download_file(
+    token: String,
+    file_service: &State<FileService>,
+) -> Result<Document, AppError> {
+    file_service.open(&token).await
+}
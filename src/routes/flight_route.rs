@@ -1,23 +1,55 @@
-use crate::models::flight::{AvailableSeatsResponse, FlightSearchQuery, FlightSearchResponse};
+use crate::models::flight::{
+    AvailableSeatsResponse, FlightSearchQuery, FlightSearchResponse, PriceCalendarQuery,
+    PriceCalendarResponse, RecentFlightsResponse, SearchSort, TrendingDestinationsResponse,
+};
+use crate::models::funnel::FunnelStep;
 use crate::services::flight_service::FlightService;
+use crate::services::route_stats_service::RouteStatsService;
+use crate::utils::config::SeatMapView;
+use crate::utils::document::Document;
 use crate::utils::error::AppError;
+use crate::utils::flight_ref::FlightRef;
+use crate::utils::funnel::FunnelTracker;
 use crate::utils::jwt::AuthenticatedUser;
-use chrono::NaiveDate;
+use crate::utils::locale::AcceptLanguage;
+use crate::utils::ndjson::{JsonOrNdjson, NdjsonRequested, NdjsonStream};
+use crate::utils::telemetry::RequestSpan;
+use chrono::{NaiveDate, NaiveTime};
 use rocket::serde::json::Json;
 use rocket::State;
 use rocket_okapi::openapi;
+use tracing::Instrument;
 
-/// Search flights
+/// Search flights. Long date ranges can be streamed as NDJSON, one flight per line as it
+/// is read, with `Accept: application/x-ndjson`. The fares of a route come with its
+/// first flight. A flight also sold under partner flight numbers is listed once with
+/// its codeshares, or once per flight number with `collapse_codeshares=false`.
+/// `sort_by` is `departure_time` (the default), `price` or `duration`.
+/// `departure_after` and `departure_before` (HH:MM) narrow the time of day flights
+/// leave at. `limit` and `offset` return a page of the flights, `total` counts them all.
 #[openapi(tag = "Flights")]
-#[get("/flights/search?<departure_city>&<destination_city>&<departure_date>&<end_date>")]
+#[get(
+    "/flights/search?<departure_city>&<destination_city>&<departure_date>&<end_date>&<collapse_codeshares>&<sort_by>&<departure_after>&<departure_before>&<max_stops>&<limit>&<offset>"
+)]
 pub async fn search_flights(
     departure_city: String,
     destination_city: String,
     departure_date: String,
     end_date: Option<String>,
+    collapse_codeshares: Option<bool>,
+    sort_by: Option<SearchSort>,
+    departure_after: Option<String>,
+    departure_before: Option<String>,
+    max_stops: Option<u32>,
+    limit: Option<u32>,
+    offset: Option<u32>,
     _auth: AuthenticatedUser,
+    language: AcceptLanguage,
+    funnel: FunnelTracker,
+    ndjson: NdjsonRequested,
+    span: RequestSpan,
     flight_service: &State<FlightService>,
-) -> Result<Json<FlightSearchResponse>, AppError> {
+) -> Result<JsonOrNdjson<FlightSearchResponse>, AppError> {
     let departure_date = NaiveDate::parse_from_str(&departure_date, "%Y-%m-%d")
         .map_err(|_| AppError::BadRequest("Invalid departure date format".into()))?;
 
@@ -30,30 +62,163 @@ pub async fn search_flights(
         None
     };
 
+    let time_of_day = |time: Option<String>, name: &str| {
+        time.map(|time| {
+            NaiveTime::parse_from_str(&time, "%H:%M")
+                .map_err(|_| AppError::BadRequest(format!("Invalid {} format", name)))
+        })
+        .transpose()
+    };
+
     let query = FlightSearchQuery {
         departure_city,
         destination_city,
         departure_date,
         end_date,
+        language: language.0,
+        collapse_codeshares,
+        sort_by,
+        departure_after: time_of_day(departure_after, "departure_after")?,
+        departure_before: time_of_day(departure_before, "departure_before")?,
+        max_stops,
+        limit,
+        offset,
     };
-    let flights = flight_service.search_flights(query).await?;
-    Ok(Json(flights))
+
+    if ndjson.0 {
+        let flight_service = flight_service.inner().clone();
+        funnel.track(FunnelStep::Search, None);
+        return Ok(JsonOrNdjson::Ndjson(NdjsonStream::spawn(move |sink| {
+            async move { flight_service.export_search(query, sink).await }.instrument(span.0)
+        })));
+    }
+
+    let flights = flight_service
+        .search_flights(query)
+        .instrument(span.0)
+        .await?;
+    funnel.track(FunnelStep::Search, None);
+    Ok(JsonOrNdjson::Json(Json(flights)))
 }
 
-/// Get available seats for a flight
+/// Price calendar of a month (YYYY-MM): every day with the cheapest flight and its
+/// lowest fare, or with `sort_by=departure_time` the first flight of the day. Days
+/// without flights have none.
+// api-change 2026-10-16 added: Price calendar of a month of flights
 #[openapi(tag = "Flights")]
-#[get("/flights/availableSeats?<flight_number>&<flight_date>")]
-pub async fn get_available_seats(
-    flight_number: i32,
-    flight_date: String,
+#[get("/flights/calendar?<departure_city>&<destination_city>&<month>&<sort_by>")]
+pub async fn get_price_calendar(
+    departure_city: String,
+    destination_city: String,
+    month: String,
+    sort_by: Option<SearchSort>,
     _auth: AuthenticatedUser,
+    language: AcceptLanguage,
+    span: RequestSpan,
+    flight_service: &State<FlightService>,
+) -> Result<Json<PriceCalendarResponse>, AppError> {
+    let month = NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+        .map_err(|_| AppError::BadRequest("Invalid month format".into()))?;
+
+    let calendar = flight_service
+        .price_calendar(PriceCalendarQuery {
+            departure_city,
+            destination_city,
+            month,
+            language: language.0,
+            sort_by,
+        })
+        .instrument(span.0)
+        .await?;
+    Ok(Json(calendar))
+}
+
+/// Get available seats for a flight. Depending on the configuration the occupied seats
+/// are listed too, admins always get them.
+#[openapi(tag = "Flights")]
+#[get("/flights/availableSeats?<flight..>")]
+pub async fn get_available_seats(
+    flight: FlightRef,
+    auth: AuthenticatedUser,
+    funnel: FunnelTracker,
+    span: RequestSpan,
     flight_service: &State<FlightService>,
 ) -> Result<Json<AvailableSeatsResponse>, AppError> {
-    let flight_date = NaiveDate::parse_from_str(&flight_date, "%Y-%m-%d")
-        .map_err(|_| AppError::BadRequest("Invalid flight date format".into()))?;
+    let flight_number = flight.flight_number;
+    let flight_date = flight.date()?;
+
+    let available_seats = if auth.is_admin {
+        flight_service
+            .get_seat_map(flight_number, flight_date, SeatMapView::Full)
+            .instrument(span.0.clone())
+            .await?
+    } else {
+        flight_service
+            .get_available_seats(flight_number, flight_date)
+            .instrument(span.0.clone())
+            .await?
+    };
 
-    let available_seats = flight_service
-        .get_available_seats(flight_number, flight_date)
+    // Viewing the seats of a flight counts as viewing the flight
+    flight_service
+        .record_flight_view(auth.user_id, flight_number, flight_date)
+        .instrument(span.0)
         .await?;
+    funnel.track(FunnelStep::SeatMapViewed, Some(flight_number));
+
     Ok(Json(available_seats))
 }
+
+/// Get the seat map of a flight as an SVG image, with free seats colored by kind and
+/// taken seats greyed out. Meant for emails and clients without a seat picker, so it
+/// needs no login.
+// api-change 2026-10-16 added: Seat maps as SVG images
+#[openapi(tag = "Flights")]
+#[get("/flights/<flight_id>/seatmap.svg")]
+pub async fn get_seat_map_svg(
+    flight_id: i32,
+    span: RequestSpan,
+    flight_service: &State<FlightService>,
+) -> Result<Document, AppError> {
+    let document = flight_service
+        .seat_map_svg(flight_id)
+        .instrument(span.0)
+        .await?;
+    Ok(document)
+}
+
+/// Get trending destinations over a recent time window
+#[openapi(tag = "Flights")]
+#[get("/destinations/trending?<days>&<departure_city>&<limit>")]
+pub async fn get_trending_destinations(
+    days: Option<i64>,
+    departure_city: Option<String>,
+    limit: Option<i64>,
+    route_stats_service: &State<RouteStatsService>,
+) -> Result<Json<TrendingDestinationsResponse>, AppError> {
+    let days = days.unwrap_or(7);
+    if !(1..=90).contains(&days) {
+        return Err(AppError::BadRequest("days must be between 1 and 90".into()));
+    }
+    let limit = limit.unwrap_or(10).clamp(1, 50);
+
+    let trending = route_stats_service
+        .trending_destinations(days, departure_city, limit)
+        .await?;
+    Ok(Json(trending))
+}
+
+/// Get the flights recently viewed by the current user
+#[openapi(tag = "Flights")]
+#[get("/profile/recent-flights")]
+pub async fn get_recent_flights(
+    auth: AuthenticatedUser,
+    span: RequestSpan,
+    flight_service: &State<FlightService>,
+) -> Result<Json<RecentFlightsResponse>, AppError> {
+    let recent_flights = flight_service
+        .get_recent_flights(auth.user_id)
+        .instrument(span.0)
+        .await?;
+    Ok(Json(recent_flights))
+}
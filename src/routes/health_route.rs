@@ -0,0 +1,27 @@
+use crate::models::health::HealthResponse;
+use crate::services::health_service::HealthService;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+
+// Probes are mounted at the root, outside /api and the OpenAPI document
+
+/// Liveness: the process answers. The database state is reported but never fails
+/// the probe, so a database outage does not get the service restarted.
+#[get("/health")]
+pub async fn health(health_service: &State<HealthService>) -> Json<HealthResponse> {
+    Json(health_service.check().await)
+}
+
+/// Readiness: 503 while the database does not answer, so load balancers stop
+/// sending traffic to this instance
+#[get("/ready")]
+pub async fn ready(health_service: &State<HealthService>) -> (Status, Json<HealthResponse>) {
+    let response = health_service.check().await;
+    let status = if response.database {
+        Status::Ok
+    } else {
+        Status::ServiceUnavailable
+    };
+    (status, Json(response))
+}
@@ -0,0 +1,19 @@
+use crate::models::loyalty::LoyaltyBalanceResponse;
+use crate::services::loyalty_service::LoyaltyService;
+use crate::utils::error::AppError;
+use crate::utils::jwt::AuthenticatedUser;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_okapi::openapi;
+
+/// Get your loyalty points balance and its latest changes
+// api-change 2026-10-16 added: Loyalty points earned on flown tickets
+#[openapi(tag = "Loyalty")]
+#[get("/loyalty/balance")]
+pub async fn get_loyalty_balance(
+    auth: AuthenticatedUser,
+    loyalty_service: &State<LoyaltyService>,
+) -> Result<Json<LoyaltyBalanceResponse>, AppError> {
+    let response = loyalty_service.balance(auth.user_id).await?;
+    Ok(Json(response))
+}
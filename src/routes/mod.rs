@@ -1,3 +1,11 @@
+pub mod admin_route;
+pub mod changelog_route;
+pub mod file_route;
 pub mod flight_route;
+pub mod health_route;
+pub mod loyalty_route;
+pub mod operation_route;
+pub mod partner_route;
+pub mod payment_route;
 pub mod ticket_route;
 pub mod user_route;
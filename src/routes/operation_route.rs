@@ -0,0 +1,25 @@
+use crate::models::operation::{OperationStatusResponse, MAX_WAIT_SECONDS};
+use crate::services::operation_service::OperationService;
+use crate::utils::error::AppError;
+use crate::utils::jwt::AuthenticatedUser;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_okapi::openapi;
+use std::time::Duration;
+
+/// Wait up to `timeout` seconds (at most 30, the default) for a booking payment or
+/// a refund to finish, and return its status. `done` is false when it timed out.
+#[openapi(tag = "Operations")]
+#[get("/operations/<operation_id>/wait?<timeout>")]
+pub async fn wait_for_operation(
+    operation_id: &str,
+    timeout: Option<u64>,
+    auth: AuthenticatedUser,
+    operation_service: &State<OperationService>,
+) -> Result<Json<OperationStatusResponse>, AppError> {
+    let timeout = Duration::from_secs(timeout.unwrap_or(MAX_WAIT_SECONDS));
+    let response = operation_service
+        .wait(auth.user_id, operation_id, timeout)
+        .await?;
+    Ok(Json(response))
+}
@@ -0,0 +1,105 @@
+use crate::models::partner::{
+    ChangeCursor, FlightChange, RouteAvailabilityQuery, RouteAvailabilityResponse,
+    DEFAULT_AVAILABILITY_DAYS, DEFAULT_CHANGES_PER_PAGE,
+};
+use crate::models::sandbox::{SandboxBookingRequest, SANDBOX_HEADER};
+use crate::models::ticket::TicketBookingResponse;
+use crate::services::partner_service::PartnerService;
+use crate::utils::api_key::{PartnerKey, Sandbox};
+use crate::utils::error::AppError;
+use crate::utils::ndjson::{collect_rows, JsonOrNdjson, NdjsonRequested, NdjsonStream};
+use crate::utils::telemetry::RequestSpan;
+use chrono::NaiveDate;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_okapi::openapi;
+use tracing::Instrument;
+
+/// Seats left per day on a route over up to 90 days, for travel agency partners.
+/// Authenticated with the X-API-Key header, answered from the sandbox with X-Sandbox: true.
+#[openapi(tag = "Partners")]
+#[get("/partners/availability?<departure_city>&<destination_city>&<start_date>&<days>")]
+pub async fn route_availability(
+    departure_city: String,
+    destination_city: String,
+    start_date: String,
+    days: Option<u32>,
+    _partner: PartnerKey,
+    sandbox: Sandbox,
+    span: RequestSpan,
+    partner_service: &State<PartnerService>,
+) -> Result<Json<RouteAvailabilityResponse>, AppError> {
+    let partner_service = match &sandbox.0 {
+        Some(sandbox_service) => sandbox_service.partner_service(),
+        None => partner_service.inner(),
+    };
+    let start_date = NaiveDate::parse_from_str(&start_date, "%Y-%m-%d")
+        .map_err(|_| AppError::BadRequest("Invalid start date format".into()))?;
+
+    let query = RouteAvailabilityQuery {
+        departure_city,
+        destination_city,
+        start_date,
+        days: days.unwrap_or(DEFAULT_AVAILABILITY_DAYS),
+    };
+    let response = partner_service
+        .route_availability(query)
+        .instrument(span.0)
+        .await?;
+    Ok(Json(response))
+}
+
+/// Flights whose inventory changed since the cursor, oldest change first, to keep a
+/// mirror of availability up to date. Every change carries the cursor to resume from;
+/// leave `since` out for a full sync. Streamed as NDJSON with `Accept: application/x-ndjson`.
+#[openapi(tag = "Partners")]
+#[get("/partner/changes?<since>&<limit>")]
+pub async fn partner_changes(
+    since: Option<String>,
+    limit: Option<u32>,
+    ndjson: NdjsonRequested,
+    _partner: PartnerKey,
+    sandbox: Sandbox,
+    partner_service: &State<PartnerService>,
+) -> Result<JsonOrNdjson<Vec<FlightChange>>, AppError> {
+    let partner_service = match &sandbox.0 {
+        Some(sandbox_service) => sandbox_service.partner_service(),
+        None => partner_service.inner(),
+    };
+    let since = since.as_deref().map(ChangeCursor::parse).transpose()?;
+    let limit = limit.unwrap_or(DEFAULT_CHANGES_PER_PAGE);
+
+    if ndjson.0 {
+        let partner_service = partner_service.clone();
+        return Ok(JsonOrNdjson::Ndjson(NdjsonStream::spawn(
+            move |sink| async move { partner_service.export_changes(since, limit, sink).await },
+        )));
+    }
+
+    let changes = collect_rows(|sink| partner_service.export_changes(since, limit, sink)).await?;
+    Ok(JsonOrNdjson::Json(Json(changes)))
+}
+
+/// Book flights in the sandbox, against a copy of the inventory that never touches
+/// real flights. Needs the X-Sandbox: true header; every booking is made for a new
+/// sandbox customer with the details of the passenger.
+#[openapi(tag = "Partners")]
+#[post("/partners/bookings", format = "json", data = "<request>")]
+pub async fn create_sandbox_booking(
+    request: Json<SandboxBookingRequest>,
+    partner: PartnerKey,
+    sandbox: Sandbox,
+    span: RequestSpan,
+) -> Result<Json<TicketBookingResponse>, AppError> {
+    let sandbox_service = sandbox.0.ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "Partners can only book in the sandbox, send {}: true",
+            SANDBOX_HEADER
+        ))
+    })?;
+    let response = sandbox_service
+        .book(&partner.0, request.into_inner())
+        .instrument(span.0)
+        .await?;
+    Ok(Json(response))
+}
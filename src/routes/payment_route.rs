@@ -0,0 +1,84 @@
+use crate::models::payment::{
+    ConfirmPaymentRequest, FareHoldRequest, FareHoldResponse, PaymentResponse, RefundResponse,
+    TicketCancellationResponse,
+};
+use crate::services::payment_service::PaymentService;
+use crate::services::ticket_service::TicketService;
+use crate::utils::error::AppError;
+use crate::utils::jwt::AuthenticatedUser;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_okapi::openapi;
+
+/// Pay for a booking
+#[openapi(tag = "Payments")]
+#[post("/payments/<booking_id>/confirm", format = "json", data = "<request>")]
+pub async fn confirm_payment(
+    booking_id: i32,
+    request: Json<ConfirmPaymentRequest>,
+    auth: AuthenticatedUser,
+    payment_service: &State<PaymentService>,
+) -> Result<Json<PaymentResponse>, AppError> {
+    let response = payment_service
+        .confirm_payment(auth.user_id, booking_id, request.into_inner())
+        .await?;
+    Ok(Json(response))
+}
+
+/// Pay for a booking with loyalty points, 100 points per 1.00 of the fare
+// api-change 2026-10-16 added: Bookings can be paid with loyalty points
+#[openapi(tag = "Payments")]
+#[post("/payments/<booking_id>/points")]
+pub async fn pay_with_points(
+    booking_id: i32,
+    auth: AuthenticatedUser,
+    payment_service: &State<PaymentService>,
+) -> Result<Json<PaymentResponse>, AppError> {
+    let response = payment_service
+        .pay_with_points(auth.user_id, booking_id)
+        .await?;
+    Ok(Json(response))
+}
+
+/// Pay a fee to keep an unpaid booking for 24 or 48 hours.
+/// Paying for the booking afterwards confirms it as usual.
+#[openapi(tag = "Payments")]
+#[post("/bookings/<booking_id>/hold", format = "json", data = "<request>")]
+pub async fn hold_fare(
+    booking_id: i32,
+    request: Json<FareHoldRequest>,
+    auth: AuthenticatedUser,
+    payment_service: &State<PaymentService>,
+) -> Result<Json<FareHoldResponse>, AppError> {
+    let response = payment_service
+        .hold_fare(auth.user_id, booking_id, request.into_inner())
+        .await?;
+    Ok(Json(response))
+}
+
+/// Cancel one of your tickets by its booking reference (PNR).
+/// A paid ticket is refunded by the rules of its fare class and the time left before departure.
+#[openapi(tag = "Payments")]
+#[post("/tickets/by-reference/<pnr>/cancel")]
+pub async fn cancel_ticket(
+    pnr: String,
+    auth: AuthenticatedUser,
+    payment_service: &State<PaymentService>,
+    ticket_service: &State<TicketService>,
+) -> Result<Json<TicketCancellationResponse>, AppError> {
+    let response = payment_service
+        .cancel_ticket(ticket_service, auth.user_id, &pnr)
+        .await?;
+    Ok(Json(response))
+}
+
+/// Track the refunds of your cancelled tickets, newest first
+#[openapi(tag = "Payments")]
+#[get("/refunds")]
+pub async fn list_refunds(
+    auth: AuthenticatedUser,
+    payment_service: &State<PaymentService>,
+) -> Result<Json<Vec<RefundResponse>>, AppError> {
+    let refunds = payment_service.list_refunds(auth.user_id).await?;
+    Ok(Json(refunds))
+}
@@ -1,49 +1,314 @@
-use crate::models::ticket::{BookingHistoryResponse, TicketBookingRequest, SeatBookingRequest};
+use crate::models::checkin::{BoardingPass, CheckinRequest};
+use crate::models::ticket::{
+    BookingHistoryResponse, BookingRequestStatusResponse, BookingValidationResponse, RebookedPassenger, RebookingOptionsResponse,
+    SeatHoldRequest, SeatHoldResponse, SelfRebookingRequest, TicketBookingRequest,
+    TicketByReferenceResponse,
+};
+use crate::models::file::FileLink;
+use crate::models::funnel::FunnelStep;
+use crate::models::payment::{
+    SeatChangeRequest, SeatChangeResponse, SeatSelectionRequest, SeatSelectionResponse,
+};
+use crate::services::booking_queue::{BookingQueue, BookingRequests, QueueCaller};
+use crate::services::file_service::FileService;
+use crate::services::payment_service::PaymentService;
 use crate::services::ticket_service::TicketService;
+use crate::utils::api_key::PartnerKey;
+use crate::utils::concurrency_limiter::BookingSlot;
+use crate::utils::document::{Document, DocumentFormat};
+use crate::utils::envelope::{Envelope, EnvelopeRequested};
 use crate::utils::error::AppError;
+use crate::utils::funnel::FunnelTracker;
 use crate::utils::jwt::AuthenticatedUser;
+use crate::utils::locale::{AcceptLanguage, DocumentLocale};
+use crate::utils::rate_limiter::BookingRateLimit;
+use crate::utils::telemetry::RequestSpan;
+use rocket::http::Status;
+use rocket::response::status::Custom;
 use rocket::serde::json::Json;
 use rocket::serde::json::{json, Value};
 use rocket::State;
 use rocket_okapi::openapi;
+use tracing::Instrument;
 
+/// Book tickets. In the queued booking mode the booking is only queued, and the answer
+/// is 202 Accepted with the URL its outcome is reported at.
+// api-change 2026-10-16 changed: Answers 202 Accepted with a status URL in the queued booking mode
 #[openapi(tag = "Book")]
 #[post("/tickets/book", format = "json", data = "<request>")]
 pub async fn book_ticket(
     request: Json<TicketBookingRequest>,
     auth: AuthenticatedUser,
+    partner: Option<PartnerKey>,
+    _rate_limit: BookingRateLimit,
+    _slot: BookingSlot,
+    envelope: EnvelopeRequested,
+    funnel: FunnelTracker,
+    span: RequestSpan,
     ticket_service: &State<TicketService>,
-) -> Result<Json<Value>, AppError> {
-    let response = ticket_service
-        .book_ticket(auth.user_id, request.into_inner())
+    booking_queue: &State<Option<BookingQueue>>,
+) -> Result<Custom<Json<Value>>, AppError> {
+    let request = request.into_inner();
+    let flight_number = request.flights.first().map(|flight| flight.flight_number);
+
+    funnel.track(FunnelStep::BookingAttempted, flight_number);
+    if let Some(booking_queue) = booking_queue.inner() {
+        // Agents booking with a partner API key share the key's part of the queue
+        let caller = match partner {
+            Some(PartnerKey(partner)) => QueueCaller::ApiKey(partner.key_id),
+            None => QueueCaller::User(auth.user_id),
+        };
+        let queued = booking_queue
+            .enqueue(auth.user_id, caller, request, funnel)
+            .await?;
+        return Ok(Custom(Status::Accepted, Json(json!(queued))));
+    }
+
+    let mut response = ticket_service
+        .book_ticket(auth.user_id, request)
+        .instrument(span.0)
         .await?;
+    funnel.track(FunnelStep::BookingConfirmed, flight_number);
 
-    Ok(Json(json!(response)))
+    if envelope.0 {
+        let warnings = std::mem::take(&mut response.warnings);
+        return Ok(Custom(
+            Status::Ok,
+            Json(json!(Envelope::new(response, warnings))),
+        ));
+    }
+
+    response.fold_warnings_into_status();
+    Ok(Custom(Status::Ok, Json(json!(response))))
 }
 
+/// Status of a booking queued in the queued booking mode: Pending until it is applied,
+/// then Confirmed with its tickets or Failed with the reason
+// api-change 2026-10-16 added: Status of a queued booking
+#[openapi(tag = "Book")]
+#[get("/bookings/<request_id>/status")]
+pub async fn get_booking_status(
+    request_id: &str,
+    auth: AuthenticatedUser,
+    booking_requests: &State<BookingRequests>,
+) -> Result<Json<BookingRequestStatusResponse>, AppError> {
+    let response = booking_requests.status(auth.user_id, request_id).await?;
+    Ok(Json(response))
+}
+
+/// Validate a booking request without booking anything
+#[openapi(tag = "Book")]
+#[post("/tickets/validate", format = "json", data = "<request>")]
+pub async fn validate_booking(
+    request: Json<TicketBookingRequest>,
+    auth: AuthenticatedUser,
+    span: RequestSpan,
+    ticket_service: &State<TicketService>,
+) -> Result<Json<BookingValidationResponse>, AppError> {
+    let response = ticket_service
+        .validate_booking(auth.user_id, &request.into_inner())
+        .instrument(span.0)
+        .await?;
+    Ok(Json(response))
+}
+
+/// Pick a seat for a ticket. Exit row and extra legroom seats have a price, charged
+/// with the payment token.
+// api-change 2026-10-16 changed: Takes a payment_token for priced seats, returns the amount charged
 #[openapi(tag = "Book")]
 #[post("/tickets/seat/book", format = "json", data = "<request>")]
 pub async fn book_seat_for_ticket(
-    request: Json<SeatBookingRequest>,
+    request: Json<SeatSelectionRequest>,
     auth: AuthenticatedUser,
+    _rate_limit: BookingRateLimit,
+    _slot: BookingSlot,
+    span: RequestSpan,
     ticket_service: &State<TicketService>,
-) -> Result<Json<Value>, AppError> {
-    let success = ticket_service
-        .book_seat_for_ticket(
+    payment_service: &State<PaymentService>,
+) -> Result<Json<SeatSelectionResponse>, AppError> {
+    let response = payment_service
+        .select_seat(ticket_service, auth.user_id, request.into_inner())
+        .instrument(span.0)
+        .await?;
+
+    Ok(Json(response))
+}
+
+/// Change the seat of a ticket, or give it up with a null seat_number. Returns the
+/// old and new seat.
+// api-change 2026-10-16 added: Seat change by ticket id
+#[openapi(tag = "Book")]
+#[put("/tickets/<ticket_id>/seat", format = "json", data = "<request>")]
+pub async fn change_seat(
+    ticket_id: &str,
+    request: Json<SeatChangeRequest>,
+    auth: AuthenticatedUser,
+    _rate_limit: BookingRateLimit,
+    _slot: BookingSlot,
+    span: RequestSpan,
+    ticket_service: &State<TicketService>,
+    payment_service: &State<PaymentService>,
+) -> Result<Json<SeatChangeResponse>, AppError> {
+    let ticket_id = ticket_service.ticket_id_for(ticket_id).await?;
+    let response = payment_service
+        .change_seat(
+            ticket_service,
             auth.user_id,
-            request.into_inner()
+            ticket_id,
+            request.into_inner(),
         )
+        .instrument(span.0)
         .await?;
 
-    Ok(Json(json!({ "success": success })))
+    Ok(Json(response))
+}
+
+/// Hold a seat for a few minutes while completing payment
+#[openapi(tag = "Book")]
+#[post("/tickets/seat/hold", format = "json", data = "<request>")]
+pub async fn hold_seat(
+    request: Json<SeatHoldRequest>,
+    auth: AuthenticatedUser,
+    _rate_limit: BookingRateLimit,
+    _slot: BookingSlot,
+    span: RequestSpan,
+    ticket_service: &State<TicketService>,
+) -> Result<Json<SeatHoldResponse>, AppError> {
+    let response = ticket_service
+        .hold_seat(auth.user_id, request.into_inner())
+        .instrument(span.0)
+        .await?;
+    Ok(Json(response))
 }
 
 #[openapi(tag = "Book")]
 #[get("/history")]
 pub async fn get_history(
     _auth: AuthenticatedUser,
+    span: RequestSpan,
     ticket_service: &State<TicketService>,
 ) -> Result<Json<BookingHistoryResponse>, AppError> {
-    let response = ticket_service.get_history(_auth.user_id).await?;
+    let response = ticket_service
+        .get_history(_auth.user_id)
+        .instrument(span.0)
+        .await?;
+    Ok(Json(response))
+}
+
+/// Look up one of your tickets by its booking reference (PNR)
+#[openapi(tag = "Book")]
+#[get("/tickets/by-reference/<pnr>")]
+pub async fn get_ticket_by_reference(
+    pnr: String,
+    auth: AuthenticatedUser,
+    span: RequestSpan,
+    ticket_service: &State<TicketService>,
+) -> Result<Json<TicketByReferenceResponse>, AppError> {
+    let response = ticket_service
+        .get_ticket_by_reference(auth.user_id, &pnr)
+        .instrument(span.0)
+        .await?;
     Ok(Json(response))
 }
+
+/// Flights of the route, up to two days before or after, that a passenger of a
+/// cancelled or long delayed flight can move their ticket to
+// api-change 2026-10-16 added: Rebooking options of a disrupted ticket
+#[openapi(tag = "Book")]
+#[get("/tickets/<ticket_id>/rebooking-options")]
+pub async fn get_rebooking_options(
+    ticket_id: i32,
+    auth: AuthenticatedUser,
+    span: RequestSpan,
+    ticket_service: &State<TicketService>,
+) -> Result<Json<RebookingOptionsResponse>, AppError> {
+    let response = ticket_service
+        .get_rebooking_options(auth.user_id, ticket_id)
+        .instrument(span.0)
+        .await?;
+    Ok(Json(response))
+}
+
+/// Move a ticket of a cancelled or long delayed flight to one of its rebooking options
+// api-change 2026-10-16 added: Passengers can rebook a disrupted ticket
+#[openapi(tag = "Book")]
+#[post("/tickets/<ticket_id>/rebook", format = "json", data = "<request>")]
+pub async fn rebook_ticket(
+    ticket_id: i32,
+    request: Json<SelfRebookingRequest>,
+    auth: AuthenticatedUser,
+    _rate_limit: BookingRateLimit,
+    span: RequestSpan,
+    ticket_service: &State<TicketService>,
+) -> Result<Json<RebookedPassenger>, AppError> {
+    let response = ticket_service
+        .self_rebook(auth.user_id, ticket_id, request.into_inner())
+        .instrument(span.0)
+        .await?;
+    Ok(Json(response))
+}
+
+/// Check in for a flight and get the boarding pass, from 24 hours before departure
+#[openapi(tag = "Book")]
+#[post("/checkin", format = "json", data = "<request>")]
+pub async fn check_in(
+    request: Json<CheckinRequest>,
+    auth: AuthenticatedUser,
+    span: RequestSpan,
+    ticket_service: &State<TicketService>,
+) -> Result<Json<BoardingPass>, AppError> {
+    let boarding_pass = ticket_service
+        .check_in(auth.user_id, request.into_inner())
+        .instrument(span.0)
+        .await?;
+    Ok(Json(boarding_pass))
+}
+
+/// Boarding pass of a checked-in ticket as a printable PDF (default) or as the PNG of
+/// its QR code for the gate scanner. Dates follow the Accept-Language of the request.
+#[openapi(tag = "Book")]
+#[get("/checkin/<booking_reference>/boarding-pass?<format>")]
+pub async fn get_boarding_pass(
+    booking_reference: String,
+    format: Option<DocumentFormat>,
+    auth: AuthenticatedUser,
+    language: AcceptLanguage,
+    span: RequestSpan,
+    ticket_service: &State<TicketService>,
+) -> Result<Document, AppError> {
+    ticket_service
+        .boarding_pass_document(
+            auth.user_id,
+            &booking_reference,
+            format.unwrap_or(DocumentFormat::Pdf),
+            DocumentLocale::from_language(language.0.as_deref()),
+        )
+        .instrument(span.0)
+        .await
+}
+
+/// Link to download the boarding pass without logging in, e.g. from another device.
+/// The link expires after a few minutes.
+#[openapi(tag = "Book")]
+#[post("/checkin/<booking_reference>/boarding-pass/link?<format>")]
+pub async fn create_boarding_pass_link(
+    booking_reference: String,
+    format: Option<DocumentFormat>,
+    auth: AuthenticatedUser,
+    language: AcceptLanguage,
+    span: RequestSpan,
+    ticket_service: &State<TicketService>,
+    file_service: &State<FileService>,
+) -> Result<Json<FileLink>, AppError> {
+    let document = ticket_service
+        .boarding_pass_document(
+            auth.user_id,
+            &booking_reference,
+            format.unwrap_or(DocumentFormat::Pdf),
+            DocumentLocale::from_language(language.0.as_deref()),
+        )
+        .instrument(span.0)
+        .await?;
+    let link = file_service.store("boarding-passes", &document).await?;
+    Ok(Json(link))
+}
@@ -1,20 +1,34 @@
 use crate::models::user::{
-    RegisterResponse, UserLoginRequest, UserLoginResponse, UserRegistrationRequest,
+    ChangePasswordRequest, ForgotPasswordRequest, RegisterResponse, ResetPasswordRequest,
+    SupportTokenResponse, UpdateProfileRequest, UserLoginRequest, UserLoginResponse, UserProfile,
+    UserRegistrationRequest,
 };
 use crate::services::user_service::UserService;
 use crate::utils::error::AppError;
+use crate::utils::experiment::{self, ExperimentAssignment};
+use crate::utils::jwt::AuthenticatedUser;
+use crate::utils::rate_limiter::{LoginRateLimit, RegistrationRateLimit};
+use crate::utils::telemetry::RequestSpan;
 use rocket::serde::json::Json;
+use rocket::serde::json::{json, Value};
 use rocket::State;
 use rocket_okapi::openapi;
+use std::net::IpAddr;
+use tracing::Instrument;
 
 /// Register a new user
 #[openapi(tag = "Users")]
 #[post("/register", format = "json", data = "<request>")]
 pub async fn register(
     request: Json<UserRegistrationRequest>,
+    _rate_limit: RegistrationRateLimit,
+    span: RequestSpan,
     user_service: &State<UserService>,
 ) -> Result<Json<RegisterResponse>, AppError> {
-    let user_id = user_service.register_user(request.into_inner()).await?;
+    let user_id = user_service
+        .register_user(request.into_inner())
+        .instrument(span.0)
+        .await?;
     Ok(Json(RegisterResponse {
         user_id,
         status: "success".to_string(),
@@ -26,8 +40,145 @@ pub async fn register(
 #[post("/login", format = "json", data = "<request>")]
 pub async fn login(
     request: Json<UserLoginRequest>,
+    _rate_limit: LoginRateLimit,
+    client_ip: Option<IpAddr>,
+    span: RequestSpan,
     user_service: &State<UserService>,
 ) -> Result<Json<UserLoginResponse>, AppError> {
-    let response = user_service.login_user(request.into_inner()).await?;
+    let client_ip = client_ip.map(|ip| ip.to_string());
+    let response = user_service
+        .login_user_from(request.into_inner(), client_ip.as_deref())
+        .instrument(span.0)
+        .await?;
     Ok(Json(response))
 }
+
+/// Change the password of the current user
+#[openapi(tag = "Users")]
+#[post("/users/password/change", format = "json", data = "<request>")]
+pub async fn change_password(
+    request: Json<ChangePasswordRequest>,
+    auth: AuthenticatedUser,
+    span: RequestSpan,
+    user_service: &State<UserService>,
+) -> Result<Json<Value>, AppError> {
+    user_service
+        .change_password(auth.user_id, request.into_inner())
+        .instrument(span.0)
+        .await?;
+    Ok(Json(json!({ "success": true })))
+}
+
+/// Request a password reset token, delivered to the user out of band
+#[openapi(tag = "Users")]
+#[post("/users/password/forgot", format = "json", data = "<request>")]
+pub async fn forgot_password(
+    request: Json<ForgotPasswordRequest>,
+    span: RequestSpan,
+    user_service: &State<UserService>,
+) -> Result<Json<Value>, AppError> {
+    // Same answer whether the account exists or not
+    user_service
+        .request_password_reset(&request.username)
+        .instrument(span.0)
+        .await?;
+    Ok(Json(json!({
+        "status": "If the account exists, a password reset token has been sent"
+    })))
+}
+
+/// Set a new password with a reset token
+#[openapi(tag = "Users")]
+#[post("/users/password/reset", format = "json", data = "<request>")]
+pub async fn reset_password(
+    request: Json<ResetPasswordRequest>,
+    span: RequestSpan,
+    user_service: &State<UserService>,
+) -> Result<Json<Value>, AppError> {
+    user_service
+        .reset_password(request.into_inner())
+        .instrument(span.0)
+        .await?;
+    Ok(Json(json!({ "success": true })))
+}
+
+/// Verify the email of a user with the token sent to the address
+#[openapi(tag = "Users")]
+#[get("/users/verify?<token>")]
+pub async fn verify_email(
+    token: &str,
+    span: RequestSpan,
+    user_service: &State<UserService>,
+) -> Result<Json<Value>, AppError> {
+    user_service.verify_email(token).instrument(span.0).await?;
+    Ok(Json(json!({ "email_verified": true })))
+}
+
+/// Send a new verification token to the email of the current user
+#[openapi(tag = "Users")]
+#[post("/users/verify/resend")]
+pub async fn resend_email_verification(
+    auth: AuthenticatedUser,
+    span: RequestSpan,
+    user_service: &State<UserService>,
+) -> Result<Json<Value>, AppError> {
+    let sent = user_service
+        .resend_email_verification(auth.user_id)
+        .instrument(span.0)
+        .await?
+        .is_some();
+    Ok(Json(json!({ "sent": sent })))
+}
+
+/// Create a short-lived code that lets a support agent view the bookings of the current user
+#[openapi(tag = "Users")]
+#[post("/users/support-token")]
+pub async fn create_support_token(
+    auth: AuthenticatedUser,
+    span: RequestSpan,
+    user_service: &State<UserService>,
+) -> Result<Json<SupportTokenResponse>, AppError> {
+    let response = user_service
+        .create_support_token(auth.user_id)
+        .instrument(span.0)
+        .await?;
+    Ok(Json(response))
+}
+
+/// Get the experiment variants of the current user
+#[openapi(tag = "Users")]
+#[get("/experiments")]
+pub async fn get_experiments(auth: AuthenticatedUser) -> Json<Vec<ExperimentAssignment>> {
+    Json(experiment::assignments_for(auth.user_id))
+}
+
+/// Get the profile of the current user
+#[openapi(tag = "Users")]
+#[get("/users/me")]
+pub async fn get_profile(
+    auth: AuthenticatedUser,
+    span: RequestSpan,
+    user_service: &State<UserService>,
+) -> Result<Json<UserProfile>, AppError> {
+    let profile = user_service
+        .get_profile(auth.user_id)
+        .instrument(span.0)
+        .await?;
+    Ok(Json(profile))
+}
+
+/// Update the profile of the current user
+#[openapi(tag = "Users")]
+#[patch("/users/me", format = "json", data = "<request>")]
+pub async fn update_profile(
+    request: Json<UpdateProfileRequest>,
+    auth: AuthenticatedUser,
+    span: RequestSpan,
+    user_service: &State<UserService>,
+) -> Result<Json<UserProfile>, AppError> {
+    let profile = user_service
+        .update_profile(auth.user_id, request.into_inner())
+        .instrument(span.0)
+        .await?;
+    Ok(Json(profile))
+}
@@ -0,0 +1,1228 @@
+use crate::models::aircraft::{
+    Aircraft, SeatLayout, SeatReassignment, SeatReassignmentStatus, SwapAircraftRequest,
+    SwapAircraftResponse,
+};
+use crate::models::flight::{
+    overbooked_capacity, BumpRequest, BumpResponse, BumpedPassenger, FlightCloseOutResponse,
+    FlightManifestResponse, FlightStatus, ManifestPassenger,
+    RouteAuditEntry, UpdateFlightStatusRequest, UpdateFlightStatusResponse,
+    UpdateOverbookingRequest, UpdateOverbookingResponse, MAX_OVERBOOKING,
+};
+use crate::models::db_enum::DbEnum;
+use crate::models::fare::FareClass;
+use crate::models::ssr;
+use crate::models::user::{
+    DuplicateUserCandidate, DuplicateUserGroup, DuplicateUsersResponse, MergeUsersRequest,
+    MergeUsersResponse,
+};
+use crate::services::event_bus::{DomainEvent, EventBus};
+use crate::services::outbox;
+use crate::services::schedule_service::insert_seats;
+use crate::utils::error::{AppError, AppResult};
+use crate::utils::ndjson::{collect_rows, RowSink};
+use crate::utils::public_id::new_public_id;
+use crate::utils::region::RegionFilter;
+use chrono::NaiveDate;
+use rocket::futures::TryStreamExt;
+use rust_decimal::Decimal;
+use sqlx::MySqlPool;
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Clone)]
+pub struct AdminService {
+    pool: MySqlPool,
+    event_bus: Option<EventBus>,
+}
+
+impl AdminService {
+    pub fn new(pool: MySqlPool) -> Self {
+        AdminService {
+            pool,
+            event_bus: None,
+        }
+    }
+
+    // Publish domain events (seat reassignments) to the given event bus
+    pub fn with_event_bus(mut self, event_bus: EventBus) -> Self {
+        self.event_bus = Some(event_bus);
+        self
+    }
+
+    fn publish(&self, event: DomainEvent) {
+        if let Some(event_bus) = &self.event_bus {
+            event_bus.publish(event);
+        }
+    }
+
+    // Update the overbooking ratio of a route. It is used for flights generated from now on,
+    // and optionally applied to the flights of the route that have not departed yet.
+    pub async fn update_route_overbooking(
+        &self,
+        admin_id: i32,
+        flight_number: i32,
+        request: UpdateOverbookingRequest,
+    ) -> AppResult<UpdateOverbookingResponse> {
+        if request.overbooking < Decimal::ZERO || request.overbooking > MAX_OVERBOOKING {
+            return Err(AppError::ValidationError(format!(
+                "Overbooking must be between 0 and {}",
+                MAX_OVERBOOKING
+            )));
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let route = sqlx::query!(
+            r#"
+            SELECT fr.overbooking, a.capacity
+            FROM flight_route fr
+            JOIN aircraft a ON fr.aircraft_id = a.aircraft_id
+            WHERE fr.flight_number = ?
+            FOR UPDATE
+            "#,
+            flight_number
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Flight route {} not found", flight_number)))?;
+
+        sqlx::query!(
+            r#"
+            UPDATE flight_route
+            SET overbooking = ?
+            WHERE flight_number = ?
+            "#,
+            request.overbooking,
+            flight_number
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let mut updated_flights = 0;
+        if request.apply_to_open_flights {
+            let delta = overbooked_capacity(route.capacity, request.overbooking)
+                - overbooked_capacity(route.capacity, route.overbooking);
+            updated_flights = sqlx::query!(
+                r#"
+                UPDATE flight
+                SET available_tickets = GREATEST(available_tickets + ?, 0),
+                    version = version + 1
+                WHERE flight_number = ? AND flight_date >= CURDATE()
+                "#,
+                delta,
+                flight_number
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO route_audit (flight_number, admin_id, field, old_value, new_value, changed_at)
+            VALUES (?, ?, 'overbooking', ?, ?, NOW())
+            "#,
+            flight_number,
+            admin_id,
+            route.overbooking.to_string(),
+            request.overbooking.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(UpdateOverbookingResponse {
+            flight_number,
+            previous_overbooking: route.overbooking,
+            overbooking: request.overbooking,
+            updated_flights,
+        })
+    }
+
+    // Find accounts that likely belong to the same person:
+    // same name and birth date, or usernames that only differ by case, punctuation or digits.
+    // Only accounts of the regions the filter allows are compared and listed.
+    pub async fn find_duplicate_users(
+        &self,
+        regions: &RegionFilter,
+    ) -> AppResult<DuplicateUsersResponse> {
+        let users: Vec<DuplicateUserCandidate> = sqlx::query!(
+            r#"
+            SELECT
+                u.id,
+                u.username,
+                c.name,
+                c.birth_date as "birth_date: NaiveDate",
+                c.data_region
+            FROM user u
+            JOIN customer_info c ON u.id = c.id
+            WHERE (? = '' OR FIND_IN_SET(c.data_region, ?) > 0)
+            AND FIND_IN_SET(c.data_region, ?) = 0
+            ORDER BY u.id
+            "#,
+            regions.include_list(),
+            regions.include_list(),
+            regions.exclude_list()
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| DuplicateUserCandidate {
+            user_id: row.id,
+            username: row.username,
+            name: row.name,
+            birth_date: row.birth_date,
+            data_region: row.data_region,
+        })
+        .collect();
+
+        let mut by_identity: HashMap<(String, NaiveDate), Vec<DuplicateUserCandidate>> =
+            HashMap::new();
+        let mut by_username: HashMap<String, Vec<DuplicateUserCandidate>> = HashMap::new();
+        for user in &users {
+            by_identity
+                .entry((user.name.trim().to_lowercase(), user.birth_date))
+                .or_default()
+                .push(user.clone());
+            // Usernames of only digits and punctuation have nothing left to compare
+            let username = normalize_username(&user.username);
+            if !username.is_empty() {
+                by_username.entry(username).or_default().push(user.clone());
+            }
+        }
+
+        let mut groups: Vec<DuplicateUserGroup> = by_identity
+            .into_values()
+            .filter(|users| users.len() > 1)
+            .map(|users| DuplicateUserGroup {
+                reason: "Same name and birth date".to_string(),
+                users,
+            })
+            .collect();
+        groups.extend(
+            by_username
+                .into_values()
+                .filter(|users| users.len() > 1)
+                .map(|users| DuplicateUserGroup {
+                    reason: "Similar usernames".to_string(),
+                    users,
+                }),
+        );
+        groups.sort_by_key(|group| group.users[0].user_id);
+
+        Ok(DuplicateUsersResponse { groups })
+    }
+
+    // Let a locked account log in again, with a fresh count of failed logins
+    pub async fn unlock_user(&self, user_id: i32) -> AppResult<()> {
+        let result = sqlx::query!(
+            "UPDATE user SET failed_login_count = 0, locked_until = NULL WHERE id = ?",
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            // No change when the account was not locked, so look the user up
+            let exists = sqlx::query_scalar!("SELECT COUNT(*) FROM user WHERE id = ?", user_id)
+                .fetch_one(&self.pool)
+                .await?;
+            if exists == 0 {
+                return Err(AppError::NotFound("User not found".into()));
+            }
+        }
+        Ok(())
+    }
+
+    // Move the tickets and history of the duplicate account to the surviving one and delete
+    // the duplicate, all in one transaction. With dry_run the transaction is rolled back.
+    pub async fn merge_users(&self, request: MergeUsersRequest) -> AppResult<MergeUsersResponse> {
+        if request.surviving_user_id == request.duplicate_user_id {
+            return Err(AppError::BadRequest("Cannot merge a user into itself".into()));
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        for user_id in [request.surviving_user_id, request.duplicate_user_id] {
+            sqlx::query!("SELECT id FROM user WHERE id = ? FOR UPDATE", user_id)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or_else(|| AppError::NotFound(format!("User {} not found", user_id)))?;
+        }
+
+        let tickets = sqlx::query!(
+            r#"
+            SELECT t.id, EXISTS(
+                SELECT 1 FROM ticket s
+                WHERE s.customer_id = ? AND s.flight_id = t.flight_id
+            ) as "conflict: i64"
+            FROM ticket t
+            WHERE t.customer_id = ?
+            "#,
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let (conflicting, reassigned): (Vec<_>, Vec<_>) =
+            tickets.into_iter().partition(|ticket| ticket.conflict != 0);
+        let conflicting_tickets: Vec<i32> = conflicting.into_iter().map(|t| t.id).collect();
+        let reassigned_tickets: Vec<i32> = reassigned.into_iter().map(|t| t.id).collect();
+
+        if !conflicting_tickets.is_empty() && !request.dry_run {
+            tx.rollback().await?;
+            return Err(AppError::Conflict(format!(
+                "Both accounts hold tickets for the same flights: {:?}",
+                conflicting_tickets
+            )));
+        }
+
+        sqlx::query!(
+            "UPDATE ticket SET customer_id = ? WHERE customer_id = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE booking SET customer_id = ? WHERE customer_id = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Views of flights both accounts looked at are dropped with the duplicate account
+        let reassigned_flight_views = sqlx::query!(
+            "UPDATE IGNORE flight_view SET user_id = ? WHERE user_id = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        sqlx::query!(
+            "UPDATE route_audit SET admin_id = ? WHERE admin_id = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE bump SET customer_id = ? WHERE customer_id = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE rebooking SET customer_id = ? WHERE customer_id = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE bump SET admin_id = ? WHERE admin_id = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE seat_reassignment SET customer_id = ? WHERE customer_id = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE seat_reassignment SET admin_id = ? WHERE admin_id = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE ticket_correction SET admin_id = ? WHERE admin_id = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE refund SET customer_id = ? WHERE customer_id = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE promo_redemption SET customer_id = ? WHERE customer_id = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE promo_code SET created_by = ? WHERE created_by = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE seat_block_rule SET created_by = ? WHERE created_by = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE seat_charge SET customer_id = ? WHERE customer_id = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Queued bookings of the duplicate account stay pollable by their request id
+        sqlx::query!(
+            "UPDATE booking_request SET customer_id = ? WHERE customer_id = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Bookings still being placed keep their claims, so the recovery task can give
+        // the inventory back
+        sqlx::query!(
+            "UPDATE booking_saga SET customer_id = ? WHERE customer_id = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // The loyalty points of both accounts add up
+        sqlx::query!(
+            "UPDATE loyalty_ledger SET customer_id = ? WHERE customer_id = ?",
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO loyalty_account (customer_id, balance, updated_at)
+            SELECT ?, balance, UTC_TIMESTAMP()
+            FROM loyalty_account
+            WHERE customer_id = ?
+            ON DUPLICATE KEY UPDATE
+                balance = loyalty_account.balance + VALUES(balance),
+                updated_at = VALUES(updated_at)
+            "#,
+            request.surviving_user_id,
+            request.duplicate_user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!("DELETE FROM user WHERE id = ?", request.duplicate_user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        if request.dry_run {
+            tx.rollback().await?;
+        } else {
+            tx.commit().await?;
+        }
+
+        Ok(MergeUsersResponse {
+            dry_run: request.dry_run,
+            merged: !request.dry_run,
+            surviving_user_id: request.surviving_user_id,
+            duplicate_user_id: request.duplicate_user_id,
+            reassigned_tickets,
+            conflicting_tickets,
+            reassigned_flight_views,
+        })
+    }
+
+    // When more tickets were sold than the aircraft has seats, bump the excess passengers
+    // without a seat, overbooked tickets first, and rebook them on a later flight of the
+    // same route. Passengers are bumped without rebooking when no flight has tickets left.
+    pub async fn bump_overbooked_passengers(
+        &self,
+        admin_id: i32,
+        flight_number: i32,
+        flight_date: NaiveDate,
+        request: BumpRequest,
+    ) -> AppResult<BumpResponse> {
+        if let Some(rebook_flight_date) = request.rebook_flight_date {
+            if rebook_flight_date <= flight_date {
+                return Err(AppError::BadRequest(
+                    "Passengers can only be rebooked on a later flight".into(),
+                ));
+            }
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let flight = sqlx::query!(
+            r#"
+            SELECT
+                f.flight_id,
+                a.capacity,
+                (SELECT COUNT(*) FROM ticket t WHERE t.flight_id = f.flight_id) as "sold!: i64"
+            FROM flight f
+            JOIN flight_route fr ON f.flight_number = fr.flight_number
+            JOIN aircraft a ON COALESCE(f.aircraft_id, fr.aircraft_id) = a.aircraft_id
+            WHERE f.flight_number = ? AND f.flight_date = ?
+            FOR UPDATE
+            "#,
+            flight_number,
+            flight_date
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Flight not found".into()))?;
+
+        let excess = flight.sold - flight.capacity as i64;
+        let candidates = if excess > 0 {
+            sqlx::query!(
+                r#"
+                SELECT id, customer_id, booking_reference
+                FROM ticket
+                WHERE flight_id = ? AND seat_number IS NULL
+                ORDER BY overbooked DESC, id DESC
+                LIMIT ?
+                "#,
+                flight.flight_id,
+                excess
+            )
+            .fetch_all(&mut *tx)
+            .await?
+        } else {
+            Vec::new()
+        };
+
+        let mut bumped = Vec::new();
+        for ticket in candidates {
+            // Flight to rebook on, skipping flights the passenger already holds a ticket for
+            let target = sqlx::query!(
+                r#"
+                SELECT f.flight_id, f.flight_date as "flight_date: NaiveDate"
+                FROM flight f
+                WHERE f.flight_number = ?
+                AND f.flight_date > ?
+                AND (? IS NULL OR f.flight_date = ?)
+                AND f.available_tickets > 0
+                AND NOT EXISTS (
+                    SELECT 1 FROM ticket t
+                    WHERE t.flight_id = f.flight_id AND t.customer_id = ?
+                )
+                ORDER BY f.flight_date
+                LIMIT 1
+                FOR UPDATE
+                "#,
+                flight_number,
+                flight_date,
+                request.rebook_flight_date,
+                request.rebook_flight_date,
+                ticket.customer_id
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            let mut rebooked_ticket_id = None;
+            if let Some(target) = &target {
+                sqlx::query!(
+                    r#"
+                    UPDATE flight
+                    SET available_tickets = available_tickets - 1,
+                        version = version + 1
+                    WHERE flight_id = ?
+                    "#,
+                    target.flight_id
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                // The new ticket keeps the fare and booking of the bumped one
+                let new_ticket_id = sqlx::query!(
+                    r#"
+                    INSERT INTO ticket (
+                        customer_id, flight_id, flight_date, flight_number, unaccompanied_minor,
+                        booking_id, fare_class, price, currency, public_id, ssr_codes
+                    )
+                    SELECT customer_id, ?, ?, flight_number, unaccompanied_minor,
+                        booking_id, fare_class, price, currency, ?, ssr_codes
+                    FROM ticket
+                    WHERE id = ?
+                    "#,
+                    target.flight_id,
+                    target.flight_date,
+                    new_public_id(),
+                    ticket.id
+                )
+                .execute(&mut *tx)
+                .await?
+                .last_insert_id() as i32;
+
+                sqlx::query!(
+                    "UPDATE unaccompanied_minor SET ticket_id = ? WHERE ticket_id = ?",
+                    new_ticket_id,
+                    ticket.id
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                rebooked_ticket_id = Some(new_ticket_id);
+            }
+
+            sqlx::query!(
+                r#"
+                INSERT INTO bump (ticket_id, customer_id, flight_id, rebooked_ticket_id, admin_id, bumped_at)
+                VALUES (?, ?, ?, ?, ?, UTC_TIMESTAMP())
+                "#,
+                ticket.id,
+                ticket.customer_id,
+                flight.flight_id,
+                rebooked_ticket_id,
+                admin_id
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            // The bumped ticket is not given back to the inventory, the flight is oversold
+            sqlx::query!("DELETE FROM ticket WHERE id = ?", ticket.id)
+                .execute(&mut *tx)
+                .await?;
+            // A rebooked passenger keeps their booking reference
+            if let Some(new_ticket_id) = rebooked_ticket_id {
+                sqlx::query!(
+                    "UPDATE ticket SET booking_reference = ? WHERE id = ?",
+                    ticket.booking_reference,
+                    new_ticket_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            bumped.push(BumpedPassenger {
+                customer_id: ticket.customer_id,
+                bumped_ticket_id: ticket.id,
+                rebooked_ticket_id,
+                rebooked_flight_date: target.map(|target| target.flight_date),
+            });
+        }
+
+        tx.commit().await?;
+
+        Ok(BumpResponse {
+            flight_number,
+            flight_date,
+            capacity: flight.capacity,
+            tickets_sold: flight.sold,
+            bumped,
+        })
+    }
+
+    // Change the operational status of a flight. Cancelling a flight stops its sales and
+    // marks its tickets for rebooking.
+    pub async fn update_flight_status(
+        &self,
+        flight_id: i32,
+        request: UpdateFlightStatusRequest,
+    ) -> AppResult<UpdateFlightStatusResponse> {
+        if request.delay_minutes.map_or(false, |minutes| minutes < 0) {
+            return Err(AppError::ValidationError(
+                "Delay cannot be negative".to_string(),
+            ));
+        }
+        if request.status == FlightStatus::Delayed
+            && request.delay_minutes.map_or(true, |minutes| minutes == 0)
+        {
+            return Err(AppError::ValidationError(
+                "A delayed flight needs a delay in minutes".to_string(),
+            ));
+        }
+
+        if request
+            .gate
+            .as_ref()
+            .map_or(false, |gate| gate.trim().is_empty() || gate.trim().len() > 8)
+        {
+            return Err(AppError::ValidationError(
+                "A gate must have 1 to 8 characters".to_string(),
+            ));
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let flight = sqlx::query!(
+            r#"
+            SELECT
+                flight_number,
+                flight_date as "flight_date: NaiveDate",
+                status as "status: FlightStatus",
+                delay_minutes,
+                delay_reason,
+                gate
+            FROM flight
+            WHERE flight_id = ?
+            FOR UPDATE
+            "#,
+            flight_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Flight {} not found", flight_id)))?;
+
+        if flight.status.is_final() && flight.status != request.status {
+            return Err(AppError::Conflict(format!(
+                "Flight {} is already {}",
+                flight_id,
+                flight.status.as_db_str().to_lowercase()
+            )));
+        }
+
+        let delay_minutes = request.delay_minutes.unwrap_or(flight.delay_minutes);
+        let delay_reason = request.reason.or(flight.delay_reason);
+        let gate = request
+            .gate
+            .map(|gate| gate.trim().to_uppercase())
+            .or(flight.gate);
+
+        sqlx::query!(
+            r#"
+            UPDATE flight
+            SET status = ?,
+                delay_minutes = ?,
+                delay_reason = ?,
+                gate = ?,
+                status_updated_at = NOW(),
+                version = version + 1
+            WHERE flight_id = ?
+            "#,
+            request.status.as_db_str(),
+            delay_minutes,
+            delay_reason,
+            gate,
+            flight_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let mut tickets_to_rebook = 0;
+        if request.status == FlightStatus::Cancelled {
+            sqlx::query!(
+                r#"
+                UPDATE flight
+                SET available_tickets = 0
+                WHERE flight_id = ?
+                "#,
+                flight_id
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            tickets_to_rebook = sqlx::query!(
+                r#"
+                UPDATE ticket
+                SET needs_rebooking = TRUE
+                WHERE flight_id = ?
+                "#,
+                flight_id
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+
+        tx.commit().await?;
+
+        if flight.status != request.status || flight.delay_minutes != delay_minutes {
+            self.publish(DomainEvent::FlightStatusChanged {
+                flight_id,
+                flight_number: flight.flight_number,
+                flight_date: flight.flight_date,
+                previous_status: flight.status,
+                status: request.status,
+                previous_delay_minutes: flight.delay_minutes,
+                delay_minutes,
+                reason: delay_reason.clone(),
+            });
+        }
+
+        Ok(UpdateFlightStatusResponse {
+            flight_id,
+            flight_number: flight.flight_number,
+            flight_date: flight.flight_date,
+            previous_status: flight.status,
+            status: request.status,
+            delay_minutes,
+            delay_reason,
+            gate,
+            tickets_to_rebook,
+            rebooking: None,
+        })
+    }
+
+    // Operate a flight with another aircraft. The seats of the flight are recreated for the
+    // new layout and every seated passenger keeps the same seat when it exists, or gets the
+    // nearest equivalent one. Passengers left without a seat are flagged for an agent.
+    // Seat holds and blocked seats of the old layout are dropped.
+    pub async fn swap_aircraft(
+        &self,
+        admin_id: i32,
+        flight_id: i32,
+        request: SwapAircraftRequest,
+    ) -> AppResult<SwapAircraftResponse> {
+        let mut tx = self.pool.begin().await?;
+
+        let flight = sqlx::query!(
+            r#"
+            SELECT
+                f.status as "status: FlightStatus",
+                f.closed_at IS NOT NULL as "closed!: bool",
+                COALESCE(f.aircraft_id, fr.aircraft_id) as "aircraft_id!: i32",
+                fr.overbooking,
+                (SELECT COUNT(*) FROM ticket t WHERE t.flight_id = f.flight_id) as "sold!: i64"
+            FROM flight f
+            JOIN flight_route fr ON f.flight_number = fr.flight_number
+            WHERE f.flight_id = ?
+            FOR UPDATE
+            "#,
+            flight_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Flight {} not found", flight_id)))?;
+
+        if flight.status.is_final() {
+            return Err(AppError::Conflict(format!(
+                "Flight {} is already {}",
+                flight_id,
+                flight.status.as_db_str().to_lowercase()
+            )));
+        }
+        if flight.closed {
+            return Err(AppError::Conflict(format!("Flight {} is closed", flight_id)));
+        }
+        if flight.aircraft_id == request.aircraft_id {
+            return Err(AppError::ValidationError(format!(
+                "Flight {} is already operated with aircraft {}",
+                flight_id, request.aircraft_id
+            )));
+        }
+
+        let mut layouts = Vec::new();
+        for aircraft_id in [flight.aircraft_id, request.aircraft_id] {
+            let aircraft = sqlx::query_as!(
+                Aircraft,
+                r#"
+                SELECT
+                    aircraft_id,
+                    capacity,
+                    seats_per_row,
+                    exit_rows,
+                    accessible_rows,
+                    extra_legroom_rows
+                FROM aircraft
+                WHERE aircraft_id = ?
+                "#,
+                aircraft_id
+            )
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Aircraft {} not found", aircraft_id)))?;
+            layouts.push((SeatLayout::from_aircraft(&aircraft), aircraft.capacity));
+        }
+        let (old_layout, _) = &layouts[0];
+        let (new_layout, capacity) = &layouts[1];
+
+        let tickets = sqlx::query!(
+            r#"
+            SELECT id, customer_id, seat_number as "seat_number!: i32"
+            FROM ticket
+            WHERE flight_id = ? AND seat_number IS NOT NULL
+            ORDER BY id
+            FOR UPDATE
+            "#,
+            flight_id
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        // Passengers whose seat exists on the new aircraft keep it, the others are
+        // moved to the remaining seats in booking order
+        let mut free_seats: BTreeSet<i32> = (1..=*capacity).collect();
+        let mut new_seats: Vec<Option<i32>> = tickets
+            .iter()
+            .map(|ticket| free_seats.take(&ticket.seat_number))
+            .collect();
+        for (ticket, new_seat) in tickets.iter().zip(new_seats.iter_mut()) {
+            if new_seat.is_none() {
+                let seat = old_layout.attributes(ticket.seat_number);
+                *new_seat = new_layout.nearest_equivalent(&seat, free_seats.iter().copied());
+                if let Some(seat_number) = *new_seat {
+                    free_seats.remove(&seat_number);
+                }
+            }
+        }
+
+        // Recreate the seats of the flight for the new layout
+        sqlx::query!(
+            "UPDATE ticket SET seat_number = NULL WHERE flight_id = ?",
+            flight_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!("DELETE FROM seat_info WHERE flight_id = ?", flight_id)
+            .execute(&mut *tx)
+            .await?;
+        insert_seats(&mut *tx, flight_id, *capacity).await?;
+
+        let mut reassignments = Vec::new();
+        for (ticket, new_seat_number) in tickets.into_iter().zip(new_seats) {
+            let status = match new_seat_number {
+                Some(seat_number) if seat_number == ticket.seat_number => {
+                    SeatReassignmentStatus::Same
+                }
+                Some(_) => SeatReassignmentStatus::Moved,
+                None => SeatReassignmentStatus::Unmapped,
+            };
+
+            if let Some(seat_number) = new_seat_number {
+                sqlx::query!(
+                    r#"
+                    UPDATE seat_info
+                    SET seat_status = 'BOOKED', version = version + 1
+                    WHERE flight_id = ? AND seat_number = ?
+                    "#,
+                    flight_id,
+                    seat_number
+                )
+                .execute(&mut *tx)
+                .await?;
+                sqlx::query!(
+                    "UPDATE ticket SET seat_number = ? WHERE id = ?",
+                    seat_number,
+                    ticket.id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            sqlx::query!(
+                r#"
+                INSERT INTO seat_reassignment (
+                    flight_id, ticket_id, customer_id, old_seat_number, new_seat_number,
+                    status, admin_id, reassigned_at
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, UTC_TIMESTAMP())
+                "#,
+                flight_id,
+                ticket.id,
+                ticket.customer_id,
+                ticket.seat_number,
+                new_seat_number,
+                status.as_db_str(),
+                admin_id
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            reassignments.push(SeatReassignment {
+                ticket_id: ticket.id,
+                customer_id: ticket.customer_id,
+                old_seat_number: ticket.seat_number,
+                new_seat_number,
+                status,
+            });
+        }
+
+        // Tickets left to sell on the new aircraft, none when it is already oversold
+        let sellable = overbooked_capacity(*capacity, flight.overbooking) as i64;
+        let available_tickets = (sellable - flight.sold).max(0) as i32;
+        sqlx::query!(
+            r#"
+            UPDATE flight
+            SET aircraft_id = ?,
+                available_tickets = ?,
+                version = version + 1
+            WHERE flight_id = ?
+            "#,
+            request.aircraft_id,
+            available_tickets,
+            flight_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for reassignment in &reassignments {
+            if reassignment.status != SeatReassignmentStatus::Same {
+                outbox::enqueue(
+                    &mut tx,
+                    &DomainEvent::SeatReassigned {
+                        ticket_id: reassignment.ticket_id,
+                        customer_id: reassignment.customer_id,
+                        flight_id,
+                        old_seat_number: reassignment.old_seat_number,
+                        new_seat_number: reassignment.new_seat_number,
+                    },
+                )
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(SwapAircraftResponse {
+            flight_id,
+            previous_aircraft_id: flight.aircraft_id,
+            aircraft_id: request.aircraft_id,
+            capacity: *capacity,
+            available_tickets,
+            unmapped: reassignments
+                .iter()
+                .filter(|reassignment| reassignment.status == SeatReassignmentStatus::Unmapped)
+                .count(),
+            reassignments,
+        })
+    }
+
+    // Close out a flight, freezing its tickets and seats. Outstanding seat holds are
+    // given up since nobody can book them anymore.
+    pub async fn close_flight(&self, flight_id: i32) -> AppResult<FlightCloseOutResponse> {
+        let mut tx = self.pool.begin().await?;
+
+        let flight = sqlx::query!(
+            r#"
+            SELECT
+                flight_number,
+                flight_date as "flight_date: NaiveDate",
+                status as "status: FlightStatus",
+                closed_at,
+                (SELECT COUNT(*) FROM ticket t WHERE t.flight_id = f.flight_id) as "tickets!: i64"
+            FROM flight f
+            WHERE flight_id = ?
+            FOR UPDATE
+            "#,
+            flight_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Flight {} not found", flight_id)))?;
+
+        if flight.status == FlightStatus::Cancelled {
+            return Err(AppError::Conflict(format!("Flight {} is cancelled", flight_id)));
+        }
+        if flight.closed_at.is_some() {
+            return Err(AppError::Conflict(format!("Flight {} is already closed", flight_id)));
+        }
+
+        let closed_at = chrono::Utc::now().naive_utc();
+        sqlx::query!(
+            r#"
+            UPDATE flight
+            SET closed_at = ?,
+                version = version + 1
+            WHERE flight_id = ?
+            "#,
+            closed_at,
+            flight_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let released_holds = sqlx::query!(
+            r#"
+            UPDATE seat_info
+            SET seat_status = 'AVAILABLE',
+                held_by = NULL,
+                held_until = NULL,
+                version = version + 1
+            WHERE flight_id = ? AND seat_status = 'HELD'
+            "#,
+            flight_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+
+        Ok(FlightCloseOutResponse {
+            flight_id,
+            flight_number: flight.flight_number,
+            flight_date: flight.flight_date,
+            closed_at,
+            tickets: flight.tickets,
+            released_holds,
+        })
+    }
+
+    // Everyone holding a ticket for the flight, with their seat, check-in and special
+    // service requests
+    pub async fn flight_manifest(&self, flight_id: i32) -> AppResult<FlightManifestResponse> {
+        let flight = sqlx::query!(
+            r#"
+            SELECT
+                flight_number,
+                flight_date as "flight_date: NaiveDate",
+                status as "status: FlightStatus",
+                gate
+            FROM flight
+            WHERE flight_id = ?
+            "#,
+            flight_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Flight {} not found", flight_id)))?;
+
+        let passengers =
+            collect_rows(|sink| self.export_manifest_passengers(flight_id, sink)).await?;
+
+        Ok(FlightManifestResponse {
+            flight_id,
+            flight_number: flight.flight_number,
+            flight_date: flight.flight_date,
+            status: flight.status,
+            gate: flight.gate,
+            checked_in: passengers
+                .iter()
+                .filter(|passenger| passenger.checked_in)
+                .count(),
+            passengers,
+        })
+    }
+
+    // NotFound when there is no such flight, checked before its manifest is streamed
+    pub async fn ensure_flight_exists(&self, flight_id: i32) -> AppResult<()> {
+        sqlx::query_scalar!(
+            "SELECT flight_id FROM flight WHERE flight_id = ?",
+            flight_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Flight {} not found", flight_id)))?;
+        Ok(())
+    }
+
+    // Stream the passengers of a flight's manifest, seated passengers by seat number
+    // first. Rows are fetched one by one, as for the route audit log.
+    pub async fn export_manifest_passengers(
+        &self,
+        flight_id: i32,
+        sink: RowSink<ManifestPassenger>,
+    ) -> AppResult<()> {
+        let mut rows = sqlx::query!(
+            r#"
+            SELECT
+                t.id,
+                t.booking_reference,
+                t.customer_id,
+                c.name,
+                c.birth_date as "birth_date: NaiveDate",
+                t.seat_number,
+                t.fare_class as "fare_class: FareClass",
+                t.checked_in_at,
+                t.boarding_sequence,
+                t.ssr_codes,
+                t.unaccompanied_minor as "unaccompanied_minor: bool",
+                t.overbooked as "overbooked: bool"
+            FROM ticket t
+            JOIN customer_info c ON t.customer_id = c.id
+            WHERE t.flight_id = ?
+            ORDER BY t.seat_number IS NULL, t.seat_number, c.name, t.id
+            "#,
+            flight_id
+        )
+        .fetch(&self.pool);
+
+        while let Some(row) = rows.try_next().await? {
+            let passenger = ManifestPassenger {
+                ticket_id: row.id,
+                booking_reference: row.booking_reference,
+                customer_id: row.customer_id,
+                name: row.name,
+                birth_date: row.birth_date,
+                seat_number: row.seat_number,
+                fare_class: row.fare_class,
+                checked_in: row.checked_in_at.is_some(),
+                checked_in_at: row.checked_in_at,
+                boarding_sequence: row.boarding_sequence,
+                ssr_codes: ssr::ssr_codes_from_db(&row.ssr_codes),
+                unaccompanied_minor: row.unaccompanied_minor,
+                overbooked: row.overbooked,
+            };
+            if !sink.send(passenger).await {
+                // The client went away
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Stream the route audit log oldest first, optionally for one route. Rows are
+    // fetched one by one so the whole log never has to be held in memory.
+    pub async fn export_route_audit(
+        &self,
+        flight_number: Option<i32>,
+        sink: RowSink<RouteAuditEntry>,
+    ) -> AppResult<()> {
+        let mut rows = sqlx::query_as!(
+            RouteAuditEntry,
+            r#"
+            SELECT id, flight_number, admin_id, field, old_value, new_value, changed_at
+            FROM route_audit
+            WHERE ? IS NULL OR flight_number = ?
+            ORDER BY id
+            "#,
+            flight_number,
+            flight_number
+        )
+        .fetch(&self.pool);
+
+        while let Some(entry) = rows.try_next().await? {
+            if !sink.send(entry).await {
+                // The client went away
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Lowercase the username and drop punctuation and trailing digits, so that
+// "John.Doe", "john_doe" and "johndoe2" compare equal
+fn normalize_username(username: &str) -> String {
+    username
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .trim_end_matches(|c: char| c.is_ascii_digit())
+        .to_string()
+}
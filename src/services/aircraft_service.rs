@@ -0,0 +1,235 @@
+use crate::models::aircraft::{
+    format_rows, Aircraft, AircraftLayoutRequest, AircraftResponse, CreateAircraftRequest,
+};
+use crate::models::flight::overbooked_capacity;
+use crate::services::schedule_service::insert_seat_range;
+use crate::services::seat_block_service::apply_block_rules;
+use crate::utils::error::{is_unique_violation, AppError, AppResult};
+use chrono::NaiveDate;
+use sqlx::{MySql, MySqlPool, Transaction};
+use validator::Validate;
+
+#[derive(Clone)]
+pub struct AircraftService {
+    pool: MySqlPool,
+}
+
+impl AircraftService {
+    pub fn new(pool: MySqlPool) -> Self {
+        AircraftService { pool }
+    }
+
+    pub async fn create_aircraft(
+        &self,
+        request: CreateAircraftRequest,
+    ) -> AppResult<AircraftResponse> {
+        if request.aircraft_id < 1 {
+            return Err(AppError::ValidationError(
+                "The aircraft id must be positive".into(),
+            ));
+        }
+        check_layout(&request.layout)?;
+
+        let layout = &request.layout;
+        let aircraft = Aircraft {
+            aircraft_id: request.aircraft_id,
+            capacity: layout.capacity,
+            seats_per_row: layout.seats_per_row,
+            exit_rows: format_rows(&layout.exit_rows),
+            accessible_rows: format_rows(&layout.accessible_rows),
+            extra_legroom_rows: format_rows(&layout.extra_legroom_rows),
+        };
+        sqlx::query!(
+            r#"
+            INSERT INTO aircraft (
+                aircraft_id, capacity, seats_per_row, exit_rows, accessible_rows,
+                extra_legroom_rows
+            )
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+            aircraft.aircraft_id,
+            aircraft.capacity,
+            aircraft.seats_per_row,
+            aircraft.exit_rows,
+            aircraft.accessible_rows,
+            aircraft.extra_legroom_rows
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            if is_unique_violation(&e) {
+                AppError::Conflict(format!("Aircraft {} already exists", request.aircraft_id))
+            } else {
+                e.into()
+            }
+        })?;
+
+        Ok(aircraft.response(0))
+    }
+
+    // Replace the layout of an aircraft. A new capacity adds or removes seats on the
+    // flights it operates that have not departed, which fails when they sold more
+    // tickets than the new capacity or have a booked or held seat past it.
+    pub async fn update_aircraft(
+        &self,
+        aircraft_id: i32,
+        layout: AircraftLayoutRequest,
+    ) -> AppResult<AircraftResponse> {
+        check_layout(&layout)?;
+
+        let mut tx = self.pool.begin().await?;
+        let previous = lock_aircraft(&mut tx, aircraft_id).await?;
+
+        let flights = sqlx::query!(
+            r#"
+            SELECT
+                f.flight_id,
+                f.flight_number,
+                f.flight_date as "flight_date: NaiveDate",
+                fr.overbooking,
+                (SELECT COUNT(*) FROM ticket t WHERE t.flight_id = f.flight_id) as "sold!: i64",
+                (SELECT COALESCE(MAX(s.seat_number), 0) FROM seat_info s
+                 WHERE s.flight_id = f.flight_id) as "last_seat!: i32",
+                (SELECT COALESCE(MAX(s.seat_number), 0) FROM seat_info s
+                 WHERE s.flight_id = f.flight_id AND s.seat_status IN ('BOOKED', 'HELD'))
+                    as "last_taken_seat!: i32"
+            FROM flight f
+            JOIN flight_route fr ON f.flight_number = fr.flight_number
+            WHERE COALESCE(f.aircraft_id, fr.aircraft_id) = ?
+            AND f.status NOT IN ('DEPARTED', 'CANCELLED')
+            AND f.closed_at IS NULL
+            ORDER BY f.flight_id
+            FOR UPDATE
+            "#,
+            aircraft_id
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let too_small: Vec<String> = flights
+            .iter()
+            .filter(|flight| {
+                flight.sold > i64::from(layout.capacity) || flight.last_taken_seat > layout.capacity
+            })
+            .map(|flight| format!("{} on {}", flight.flight_number, flight.flight_date))
+            .collect();
+        if !too_small.is_empty() {
+            return Err(AppError::Conflict(format!(
+                "Capacity {} is below the seats already sold on flights {}",
+                layout.capacity,
+                too_small.join(", ")
+            )));
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE aircraft
+            SET capacity = ?,
+                seats_per_row = ?,
+                exit_rows = ?,
+                accessible_rows = ?,
+                extra_legroom_rows = ?
+            WHERE aircraft_id = ?
+            "#,
+            layout.capacity,
+            layout.seats_per_row,
+            format_rows(&layout.exit_rows),
+            format_rows(&layout.accessible_rows),
+            format_rows(&layout.extra_legroom_rows),
+            aircraft_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let mut updated_flights = 0;
+        for flight in flights
+            .iter()
+            .filter(|flight| flight.last_seat != layout.capacity)
+        {
+            insert_seat_range(
+                &mut *tx,
+                flight.flight_id,
+                flight.last_seat + 1,
+                layout.capacity,
+            )
+            .await?;
+            // Blocked seats took a ticket off sale, removing them gives it back
+            let removed_blocked = sqlx::query!(
+                r#"
+                DELETE FROM seat_info
+                WHERE flight_id = ? AND seat_number > ? AND block_rule_id IS NOT NULL
+                "#,
+                flight.flight_id,
+                layout.capacity
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected() as i32;
+            sqlx::query!(
+                "DELETE FROM seat_info WHERE flight_id = ? AND seat_number > ?",
+                flight.flight_id,
+                layout.capacity
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            let sellable_change = overbooked_capacity(layout.capacity, flight.overbooking)
+                - overbooked_capacity(previous.capacity, flight.overbooking);
+            sqlx::query!(
+                r#"
+                UPDATE flight
+                SET available_tickets = GREATEST(available_tickets + ?, 0),
+                    version = version + 1
+                WHERE flight_id = ?
+                "#,
+                sellable_change + removed_blocked,
+                flight.flight_id
+            )
+            .execute(&mut *tx)
+            .await?;
+            // New seats get the blocking rules of the route
+            apply_block_rules(&mut tx, flight.flight_id).await?;
+            updated_flights += 1;
+        }
+
+        let aircraft = lock_aircraft(&mut tx, aircraft_id).await?;
+        tx.commit().await?;
+        Ok(aircraft.response(updated_flights))
+    }
+}
+
+fn check_layout(layout: &AircraftLayoutRequest) -> AppResult<()> {
+    layout
+        .validate()
+        .map_err(|e| AppError::ValidationError(format!("{:?}", e)))?;
+    let unknown_rows = layout.unknown_rows();
+    if !unknown_rows.is_empty() {
+        return Err(AppError::ValidationError(format!(
+            "Rows {:?} are not in the cabin of {} seats in rows of {}",
+            unknown_rows, layout.capacity, layout.seats_per_row
+        )));
+    }
+    Ok(())
+}
+
+async fn lock_aircraft(tx: &mut Transaction<'_, MySql>, aircraft_id: i32) -> AppResult<Aircraft> {
+    sqlx::query_as!(
+        Aircraft,
+        r#"
+        SELECT
+            aircraft_id,
+            capacity,
+            seats_per_row,
+            exit_rows,
+            accessible_rows,
+            extra_legroom_rows
+        FROM aircraft
+        WHERE aircraft_id = ?
+        FOR UPDATE
+        "#,
+        aircraft_id
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Aircraft {} not found", aircraft_id)))
+}
@@ -0,0 +1,477 @@
+use crate::models::db_enum::DbEnum;
+use crate::models::funnel::FunnelStep;
+use crate::models::ticket::{
+    BookedTicket, BookingRequestStatus, BookingRequestStatusResponse, QueuedBookingResponse,
+    TicketBookingRequest,
+};
+use crate::services::ticket_service::TicketService;
+use crate::utils::concurrency_limiter::ConcurrencyLimiter;
+use crate::utils::error::{AppError, AppResult, RetryHints};
+use crate::utils::funnel::FunnelTracker;
+use crate::utils::public_id::{new_public_id, parse_public_id};
+use chrono::{NaiveDateTime, Utc};
+use sqlx::MySqlPool;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, OwnedSemaphorePermit};
+
+// A server not seen for this long stopped, e.g. in a restart. The bookings it queued
+// that are still pending were lost with it and are reported as failed.
+pub const BOOKING_REQUEST_TIMEOUT_MINUTES: i64 = 10;
+
+// Error reported for a queued booking lost before it was applied
+const ABANDONED_ERROR: &str = "The booking was not applied, please book again";
+
+// Time a client turned away by a full queue is asked to wait
+const QUEUE_FULL_RETRY_AFTER_MS: u64 = 1000;
+
+// Longest error kept for a failed booking, the size of the column
+const MAX_ERROR_LENGTH: usize = 255;
+
+// The queued bookings and their outcome, kept in the database so that any server can
+// report the status of a booking queued on another
+#[derive(Clone)]
+pub struct BookingRequests {
+    pool: MySqlPool,
+    // Id of this server, recorded with the bookings it queues
+    server_id: String,
+}
+
+impl BookingRequests {
+    pub fn new(pool: MySqlPool) -> Self {
+        BookingRequests {
+            pool,
+            server_id: new_public_id(),
+        }
+    }
+
+    // Status of a queued booking of the customer, with its tickets once confirmed
+    pub async fn status(
+        &self,
+        user_id: i32,
+        request_id: &str,
+    ) -> AppResult<BookingRequestStatusResponse> {
+        let not_found = || AppError::NotFound("Booking request not found".into());
+        let request_id = parse_public_id(request_id).ok_or_else(not_found)?;
+        let request = sqlx::query!(
+            r#"
+            SELECT customer_id, status, booking_id, error
+            FROM booking_request
+            WHERE request_id = ?
+            "#,
+            request_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(not_found)?;
+        // Do not reveal bookings of other customers
+        if request.customer_id != user_id {
+            return Err(not_found());
+        }
+        let status = BookingRequestStatus::from_db_str(&request.status).ok_or_else(|| {
+            AppError::DatabaseError(format!("Unknown booking request status {}", request.status))
+        })?;
+
+        let tickets = match request.booking_id {
+            Some(booking_id) => {
+                sqlx::query_as!(
+                    BookedTicket,
+                    r#"
+                SELECT
+                    id as ticket_id,
+                    public_id as "public_id!",
+                    booking_reference as "booking_reference!",
+                    flight_number,
+                    flight_date,
+                    seat_number
+                FROM ticket
+                WHERE booking_id = ?
+                ORDER BY id
+                "#,
+                    booking_id
+                )
+                .fetch_all(&self.pool)
+                .await?
+            }
+            None => Vec::new(),
+        };
+        Ok(BookingRequestStatusResponse {
+            request_id,
+            status,
+            booking_id: request.booking_id,
+            tickets,
+            error: request.error,
+        })
+    }
+
+    // Record that this server is running, so the bookings in its queue are not taken
+    // for lost
+    pub async fn heartbeat(&self) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO booking_server (server_id, seen_at)
+            VALUES (?, ?)
+            ON DUPLICATE KEY UPDATE seen_at = VALUES(seen_at)
+            "#,
+            self.server_id,
+            Utc::now().naive_utc()
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // Fail the bookings still pending of the servers not seen since `seen_before`. Their
+    // queue lived in the memory of a server that stopped. Bookings queued since then are
+    // left alone, whichever server queued them. Returns how many were failed.
+    pub async fn fail_abandoned(&self, seen_before: NaiveDateTime) -> AppResult<u64> {
+        let failed = sqlx::query!(
+            r#"
+            UPDATE booking_request r
+            LEFT JOIN booking_server s ON r.server_id = s.server_id
+            SET r.status = ?, r.error = ?, r.completed_at = ?
+            WHERE r.status = ?
+                AND r.created_at < ?
+                AND (s.seen_at IS NULL OR s.seen_at < ?)
+            "#,
+            BookingRequestStatus::Failed.as_db_str(),
+            ABANDONED_ERROR,
+            Utc::now().naive_utc(),
+            BookingRequestStatus::Pending.as_db_str(),
+            seen_before,
+            seen_before
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        if failed > 0 {
+            tracing::warn!(failed, "failed abandoned queued bookings");
+        }
+
+        // Their bookings are all failed now
+        sqlx::query!("DELETE FROM booking_server WHERE seen_at < ?", seen_before)
+            .execute(&self.pool)
+            .await?;
+        Ok(failed)
+    }
+
+    // Periodically record this server as running, and fail the pending bookings of the
+    // servers not seen for BOOKING_REQUEST_TIMEOUT_MINUTES, in the background
+    pub fn spawn_sweeper(&self, period: Duration) {
+        let requests = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                if let Err(e) = requests.heartbeat().await {
+                    tracing::error!(error = %e, "failed to record the booking server as running");
+                    continue;
+                }
+                let seen_before = Utc::now().naive_utc()
+                    - chrono::Duration::minutes(BOOKING_REQUEST_TIMEOUT_MINUTES);
+                if let Err(e) = requests.fail_abandoned(seen_before).await {
+                    tracing::error!(error = %e, "failed to fail abandoned queued bookings");
+                }
+            }
+        });
+    }
+
+    async fn create(&self, request_id: &str, user_id: i32) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO booking_request (request_id, customer_id, status, created_at, server_id)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+            request_id,
+            user_id,
+            BookingRequestStatus::Pending.as_db_str(),
+            Utc::now().naive_utc(),
+            self.server_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // Whether the booking is still waiting to be applied, and not failed in the meantime
+    async fn is_pending(&self, request_id: &str) -> AppResult<bool> {
+        let status = sqlx::query_scalar!(
+            "SELECT status FROM booking_request WHERE request_id = ?",
+            request_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(status.as_deref() == Some(BookingRequestStatus::Pending.as_db_str()))
+    }
+
+    // Forget a booking that could not be queued
+    async fn remove(&self, request_id: &str) -> AppResult<()> {
+        sqlx::query!(
+            "DELETE FROM booking_request WHERE request_id = ?",
+            request_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // Record the outcome of a pending booking. Returns false when it was no longer
+    // pending, its client already told it failed.
+    async fn complete(
+        &self,
+        request_id: &str,
+        booking_id: Option<i32>,
+        error: Option<String>,
+    ) -> AppResult<bool> {
+        let status = if error.is_some() {
+            BookingRequestStatus::Failed
+        } else {
+            BookingRequestStatus::Confirmed
+        };
+        let error = error.map(|error| error.chars().take(MAX_ERROR_LENGTH).collect::<String>());
+        let completed = sqlx::query!(
+            r#"
+            UPDATE booking_request
+            SET status = ?, booking_id = ?, error = ?, completed_at = ?
+            WHERE request_id = ? AND status = ?
+            "#,
+            status.as_db_str(),
+            booking_id,
+            error,
+            Utc::now().naive_utc(),
+            request_id,
+            BookingRequestStatus::Pending.as_db_str()
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(completed > 0)
+    }
+}
+
+// Whose share of the queue a booking takes: the partner API key it was sent with, so
+// the users of one key share it, otherwise the user booking
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueCaller {
+    User(i32),
+    ApiKey(i32),
+}
+
+impl QueueCaller {
+    fn slot_key(&self) -> String {
+        match self {
+            QueueCaller::User(user_id) => format!("user:{}", user_id),
+            QueueCaller::ApiKey(key_id) => format!("key:{}", key_id),
+        }
+    }
+}
+
+struct QueuedBooking {
+    request_id: String,
+    user_id: i32,
+    request: TicketBookingRequest,
+    // Reports the booking confirmed in the funnel of the session that queued it
+    funnel: FunnelTracker,
+    // Place of the booking in the caller's share of the queue, given back once applied
+    _user_slot: OwnedSemaphorePermit,
+}
+
+// Bookings applied in the background by a pool of workers. The bookings of a flight
+// always go to the same worker, which applies them one after the other, so a rush on
+// a popular flight waits in line instead of fighting over its rows in MySQL. A booking
+// of several flights goes to the worker of its first flight.
+#[derive(Clone)]
+pub struct BookingQueue {
+    workers: Vec<mpsc::Sender<QueuedBooking>>,
+    // Bookings of each caller waiting at each worker
+    user_slots: Arc<Vec<ConcurrencyLimiter>>,
+    capacity: usize,
+    // Bookings a single caller may have waiting at a worker, the whole queue by default
+    per_user_limit: usize,
+    requests: BookingRequests,
+}
+
+impl BookingQueue {
+    // Start the workers, each queuing up to `capacity` bookings
+    pub fn start(
+        ticket_service: TicketService,
+        requests: BookingRequests,
+        workers: usize,
+        capacity: usize,
+    ) -> Self {
+        let capacity = capacity.max(1);
+        let workers: Vec<_> = (0..workers.max(1))
+            .map(|_| {
+                let (sender, mut receiver) = mpsc::channel::<QueuedBooking>(capacity);
+                let ticket_service = ticket_service.clone();
+                let requests = requests.clone();
+                tokio::spawn(async move {
+                    while let Some(booking) = receiver.recv().await {
+                        apply(&ticket_service, &requests, booking).await;
+                    }
+                });
+                sender
+            })
+            .collect();
+        let user_slots = workers.iter().map(|_| ConcurrencyLimiter::new()).collect();
+        BookingQueue {
+            workers,
+            user_slots: Arc::new(user_slots),
+            capacity,
+            per_user_limit: capacity,
+            requests,
+        }
+    }
+
+    // Let a single user or API key take only this percentage of a worker's queue, so
+    // the bulk bookings of an agent during a sale do not crowd out other customers
+    pub fn with_user_share(mut self, percent: usize) -> Self {
+        self.per_user_limit = (self.capacity * percent / 100).max(1);
+        self
+    }
+
+    // Queue the booking, or turn it away when the queue of its flight is full or the
+    // caller already takes their share of it. The booking is recorded as pending before
+    // the worker can see it.
+    pub async fn enqueue(
+        &self,
+        user_id: i32,
+        caller: QueueCaller,
+        request: TicketBookingRequest,
+        funnel: FunnelTracker,
+    ) -> AppResult<QueuedBookingResponse> {
+        let worker = self.worker_for(&request);
+        let user_slot = self.user_slots[worker]
+            .try_acquire(&caller.slot_key(), self.per_user_limit)
+            .ok_or_else(|| queue_full("You have too many bookings waiting, please try again"))?;
+        let request_id = new_public_id();
+        self.requests.create(&request_id, user_id).await?;
+        let queued = self.workers[worker].try_send(QueuedBooking {
+            request_id: request_id.clone(),
+            user_id,
+            request,
+            funnel,
+            _user_slot: user_slot,
+        });
+        if queued.is_err() {
+            self.requests.remove(&request_id).await?;
+            return Err(queue_full(
+                "Too many bookings are waiting, please try again",
+            ));
+        }
+        Ok(QueuedBookingResponse {
+            status_url: format!("/api/bookings/{}/status", request_id),
+            request_id,
+        })
+    }
+
+    fn worker_for(&self, request: &TicketBookingRequest) -> usize {
+        let mut hasher = DefaultHasher::new();
+        if let Some(flight) = request.flights.first() {
+            (flight.flight_number, flight.flight_date).hash(&mut hasher);
+        }
+        (hasher.finish() % self.workers.len() as u64) as usize
+    }
+}
+
+fn queue_full(message: &str) -> AppError {
+    AppError::ConflictWithHints(
+        message.into(),
+        RetryHints {
+            retry_after_ms: Some(QUEUE_FULL_RETRY_AFTER_MS),
+            ..Default::default()
+        },
+    )
+}
+
+async fn apply(ticket_service: &TicketService, requests: &BookingRequests, booking: QueuedBooking) {
+    // A booking failed while it waited, e.g. taken for lost, is not applied any more: its
+    // client was told to book again
+    match requests.is_pending(&booking.request_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            tracing::warn!(
+                request_id = %booking.request_id,
+                "skipped queued booking no longer pending"
+            );
+            return;
+        }
+        Err(e) => {
+            complete(requests, &booking.request_id, None, Some(e.to_string())).await;
+            return;
+        }
+    }
+
+    let flight_number = booking
+        .request
+        .flights
+        .first()
+        .map(|flight| flight.flight_number);
+    match ticket_service
+        .book_ticket(booking.user_id, booking.request)
+        .await
+    {
+        Ok(response) => {
+            tracing::info!(
+                request_id = %booking.request_id,
+                booking_id = response.booking_id,
+                "queued booking applied"
+            );
+            let booking_id = Some(response.booking_id);
+            if complete(requests, &booking.request_id, booking_id, None).await {
+                booking
+                    .funnel
+                    .track(FunnelStep::BookingConfirmed, flight_number);
+                return;
+            }
+            // Failed while it was being applied, so its client may book again: give the
+            // tickets back rather than book the customer twice
+            tracing::warn!(
+                request_id = %booking.request_id,
+                booking_id = response.booking_id,
+                "reverting queued booking failed while applied"
+            );
+            for leg in &response.flight_bookings {
+                if let Err(e) = ticket_service.release_ticket(leg.ticket_id).await {
+                    tracing::error!(
+                        request_id = %booking.request_id,
+                        ticket_id = leg.ticket_id,
+                        error = %e,
+                        "failed to revert a ticket of a failed queued booking"
+                    );
+                }
+            }
+        }
+        Err(e) => {
+            tracing::warn!(
+                request_id = %booking.request_id,
+                error = %e,
+                "queued booking failed"
+            );
+            complete(requests, &booking.request_id, None, Some(e.to_string())).await;
+        }
+    }
+}
+
+// Record the outcome of a queued booking. Returns false when it was not recorded,
+// because the booking was no longer pending or the database failed.
+async fn complete(
+    requests: &BookingRequests,
+    request_id: &str,
+    booking_id: Option<i32>,
+    error: Option<String>,
+) -> bool {
+    match requests.complete(request_id, booking_id, error).await {
+        Ok(completed) => completed,
+        Err(e) => {
+            tracing::error!(
+                request_id = %request_id,
+                error = %e,
+                "failed to record the outcome of a queued booking"
+            );
+            false
+        }
+    }
+}
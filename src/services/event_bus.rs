@@ -0,0 +1,143 @@
+use crate::models::flight::FlightStatus;
+use crate::models::funnel::FunnelStep;
+use crate::utils::experiment::ExperimentAssignment;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+// Number of events buffered for slow subscribers before they start lagging
+const EVENT_BUS_CAPACITY: usize = 1024;
+
+// Domain events published by the services
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DomainEvent {
+    FlightSearched {
+        departure_city: String,
+        destination_city: String,
+    },
+    TicketBooked {
+        ticket_id: i32,
+        customer_id: i32,
+        flight_id: i32,
+        flight_number: i32,
+        flight_date: NaiveDate,
+        // Experiment variants of the customer, to compare booking metrics between them
+        experiments: Vec<ExperimentAssignment>,
+    },
+    // A ticket was cancelled and its seat given back
+    TicketCancelled {
+        ticket_id: i32,
+        customer_id: i32,
+        flight_number: i32,
+        flight_date: NaiveDate,
+        seat_number: Option<i32>,
+    },
+    // A passenger was moved to another flight of the route on a new ticket, e.g. from a
+    // cancelled flight. The old ticket is gone, its seat is not given back here.
+    TicketRebooked {
+        ticket_id: i32,
+        rebooked_ticket_id: i32,
+        customer_id: i32,
+        flight_number: i32,
+        flight_date: NaiveDate,
+        seat_number: Option<i32>,
+        rebooked_flight_id: i32,
+        rebooked_flight_date: NaiveDate,
+    },
+    // The status or the delay of a flight changed
+    FlightStatusChanged {
+        flight_id: i32,
+        flight_number: i32,
+        flight_date: NaiveDate,
+        previous_status: FlightStatus,
+        status: FlightStatus,
+        previous_delay_minutes: i32,
+        delay_minutes: i32,
+        reason: Option<String>,
+    },
+    // A password reset token to deliver to the user, never returned by the API
+    PasswordResetRequested {
+        user_id: i32,
+        token: String,
+    },
+    // An email verification token to deliver to the given address
+    EmailVerificationRequested {
+        user_id: i32,
+        email: String,
+        token: String,
+    },
+    // The seat of a passenger changed because the aircraft of the flight was swapped.
+    // No new seat means the passenger has to be seated by an agent.
+    SeatReassigned {
+        ticket_id: i32,
+        customer_id: i32,
+        flight_id: i32,
+        old_seat_number: i32,
+        new_seat_number: Option<i32>,
+    },
+    // A seat booking gave up after losing the version check of the seat on every attempt
+    SeatBookingRetriesExhausted {
+        customer_id: i32,
+        flight_id: i32,
+        seat_number: i32,
+        attempts: u32,
+    },
+    // A step of the booking funnel reached by an anonymous client session
+    FunnelStepReached {
+        session_id: String,
+        step: FunnelStep,
+        flight_number: Option<i32>,
+    },
+}
+
+impl DomainEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DomainEvent::FlightSearched { .. } => "FlightSearched",
+            DomainEvent::TicketBooked { .. } => "TicketBooked",
+            DomainEvent::TicketCancelled { .. } => "TicketCancelled",
+            DomainEvent::TicketRebooked { .. } => "TicketRebooked",
+            DomainEvent::FlightStatusChanged { .. } => "FlightStatusChanged",
+            DomainEvent::PasswordResetRequested { .. } => "PasswordResetRequested",
+            DomainEvent::EmailVerificationRequested { .. } => "EmailVerificationRequested",
+            DomainEvent::SeatReassigned { .. } => "SeatReassigned",
+            DomainEvent::SeatBookingRetriesExhausted { .. } => "SeatBookingRetriesExhausted",
+            DomainEvent::FunnelStepReached { .. } => "FunnelStepReached",
+        }
+    }
+}
+
+// In-process publish/subscribe bus, subscribers process events asynchronously
+// so publishing never adds latency to the request
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<DomainEvent>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUS_CAPACITY);
+        EventBus { sender }
+    }
+
+    pub fn publish(&self, event: DomainEvent) {
+        // Sending only fails when nobody is subscribed, which is fine
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DomainEvent> {
+        self.sender.subscribe()
+    }
+
+    // Events not yet received by every subscriber, and the number the bus can hold
+    // before the slowest subscriber starts missing events
+    pub fn backlog(&self) -> (usize, usize) {
+        (self.sender.len(), EVENT_BUS_CAPACITY)
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,75 @@
+use crate::models::fare::{Fare, FareClass};
+use crate::models::payment::DEFAULT_CURRENCY;
+use crate::utils::error::{AppError, AppResult};
+use sqlx::MySqlPool;
+
+#[derive(Clone)]
+pub struct FareService {
+    pool: MySqlPool,
+}
+
+impl FareService {
+    pub fn new(pool: MySqlPool) -> Self {
+        FareService { pool }
+    }
+
+    // Fares offered on a route, cheapest first. Routes without configured fares
+    // sell a single economy fare at the route's base fare.
+    pub async fn route_fares(&self, flight_number: i32) -> AppResult<Vec<Fare>> {
+        let fares = sqlx::query_as!(
+            Fare,
+            r#"
+            SELECT
+                flight_number,
+                fare_class as "fare_class: FareClass",
+                base_price,
+                currency,
+                first_row,
+                last_row
+            FROM fare
+            WHERE flight_number = ?
+            ORDER BY base_price
+            "#,
+            flight_number
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        if !fares.is_empty() {
+            return Ok(fares);
+        }
+
+        let route = sqlx::query!(
+            "SELECT base_fare FROM flight_route WHERE flight_number = ?",
+            flight_number
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(match route {
+            Some(route) => vec![Fare {
+                flight_number,
+                fare_class: FareClass::Economy,
+                base_price: route.base_fare,
+                currency: DEFAULT_CURRENCY.to_string(),
+                first_row: None,
+                last_row: None,
+            }],
+            None => Vec::new(),
+        })
+    }
+
+    // Fare of the given class on a route
+    pub async fn fare(&self, flight_number: i32, fare_class: FareClass) -> AppResult<Fare> {
+        self.route_fares(flight_number)
+            .await?
+            .into_iter()
+            .find(|fare| fare.fare_class == fare_class)
+            .ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "Fare class {} is not offered on flight {}",
+                    fare_class, flight_number
+                ))
+            })
+    }
+}
@@ -0,0 +1,307 @@
+use crate::models::file::{FileLink, FILE_ROUTE_PREFIX};
+use crate::utils::config::StorageConfig;
+use crate::utils::document::Document;
+use crate::utils::error::{AppError, AppResult};
+use crate::utils::jwt;
+use chrono::SubsecRound;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+// How long a download link works when not configured
+const DEFAULT_URL_TTL: Duration = Duration::from_secs(15 * 60);
+
+// A place generated documents are kept in, by key
+#[rocket::async_trait]
+pub trait FileStorage: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn put(&self, key: &str, bytes: &[u8]) -> Result<(), String>;
+
+    // Contents of the file, None when there is no file with this key
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, String>;
+}
+
+// Keeps the files in a directory of the server, for development and single servers
+pub struct LocalFileStorage {
+    root: PathBuf,
+}
+
+impl LocalFileStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LocalFileStorage { root: root.into() }
+    }
+
+    // Path of the file, refusing keys that would leave the directory
+    fn path(&self, key: &str) -> Result<PathBuf, String> {
+        let relative = Path::new(key);
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(format!("Invalid file key {}", key));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+#[rocket::async_trait]
+impl FileStorage for LocalFileStorage {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn put(&self, key: &str, bytes: &[u8]) -> Result<(), String> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        tokio::fs::write(&path, bytes)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+// Keeps the files in a bucket of Amazon S3 or a compatible service such as MinIO,
+// addressed by path and signed with AWS Signature Version 4
+pub struct S3FileStorage {
+    client: reqwest::Client,
+    endpoint: String,
+    bucket: String,
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+}
+
+impl S3FileStorage {
+    pub fn new(
+        endpoint: &str,
+        bucket: &str,
+        region: &str,
+        access_key_id: &str,
+        secret_access_key: &str,
+    ) -> Self {
+        S3FileStorage {
+            client: reqwest::Client::new(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            bucket: bucket.to_string(),
+            region: region.to_string(),
+            access_key_id: access_key_id.to_string(),
+            secret_access_key: secret_access_key.to_string(),
+        }
+    }
+
+    // Request for the object with the signature headers set
+    fn signed_request(
+        &self,
+        method: reqwest::Method,
+        key: &str,
+        body: &[u8],
+    ) -> Result<reqwest::RequestBuilder, String> {
+        let path = format!("/{}/{}", uri_encode(&self.bucket), uri_encode(key));
+        let url = reqwest::Url::parse(&format!("{}{}", self.endpoint, path))
+            .map_err(|e| e.to_string())?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(format!("Invalid S3 endpoint {}", self.endpoint)),
+        };
+
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex(&Sha256::digest(body));
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, path, host, payload_hash, amz_date, signed_headers, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let mut signing_key = hmac_sha256(
+            format!("AWS4{}", self.secret_access_key).as_bytes(),
+            date.as_bytes(),
+        );
+        for part in [self.region.as_str(), "s3", "aws4_request"] {
+            signing_key = hmac_sha256(&signing_key, part.as_bytes());
+        }
+        let signature = hex(&hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key_id, scope, signed_headers, signature
+        );
+
+        Ok(self
+            .client
+            .request(method, url)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", amz_date)
+            .header("Authorization", authorization))
+    }
+}
+
+#[rocket::async_trait]
+impl FileStorage for S3FileStorage {
+    fn name(&self) -> &'static str {
+        "s3"
+    }
+
+    async fn put(&self, key: &str, bytes: &[u8]) -> Result<(), String> {
+        let response = self
+            .signed_request(reqwest::Method::PUT, key, bytes)?
+            .body(bytes.to_vec())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "S3 answered {} to storing {}",
+                response.status(),
+                key
+            ));
+        }
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        let response = self
+            .signed_request(reqwest::Method::GET, key, &[])?
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(format!(
+                "S3 answered {} to reading {}",
+                response.status(),
+                key
+            ));
+        }
+        let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+        Ok(Some(bytes.to_vec()))
+    }
+}
+
+// Storage for the configuration, the S3 bucket when one is set and the local
+// directory otherwise
+pub fn storage_for(config: &StorageConfig) -> Arc<dyn FileStorage> {
+    match &config.s3_bucket {
+        Some(bucket) => Arc::new(S3FileStorage::new(
+            &config.s3_endpoint,
+            bucket,
+            &config.s3_region,
+            &config.s3_access_key_id,
+            &config.s3_secret_access_key,
+        )),
+        None => Arc::new(LocalFileStorage::new(&config.local_path)),
+    }
+}
+
+// Stores generated documents such as e-tickets, invoices and exports, and hands out
+// time-limited links to download them
+#[derive(Clone)]
+pub struct FileService {
+    storage: Arc<dyn FileStorage>,
+    url_ttl: Duration,
+}
+
+impl FileService {
+    pub fn new(storage: Arc<dyn FileStorage>) -> Self {
+        FileService {
+            storage,
+            url_ttl: DEFAULT_URL_TTL,
+        }
+    }
+
+    pub fn with_url_ttl(mut self, url_ttl: Duration) -> Self {
+        self.url_ttl = url_ttl;
+        self
+    }
+
+    // Keep the document under the folder and return a link to it. Every call stores a
+    // new file, so a link never shows a later version of the document.
+    pub async fn store(&self, folder: &str, document: &Document) -> AppResult<FileLink> {
+        let key = format!("{}/{}/{}", folder, Uuid::new_v4(), document.file_name());
+        self.storage
+            .put(&key, document.bytes())
+            .await
+            .map_err(|e| storage_error(self.storage.name(), "store", e))?;
+        self.link(&key)
+    }
+
+    // Signed link to the stored file, valid for the configured time
+    pub fn link(&self, key: &str) -> AppResult<FileLink> {
+        // Whole seconds, like the expiry of the token
+        let expires_at = chrono::Utc::now().naive_utc().trunc_subsecs(0)
+            + chrono::Duration::seconds(self.url_ttl.as_secs() as i64);
+        let token = jwt::generate_file_token(key, expires_at)
+            .map_err(|e| AppError::AuthError(format!("Cannot sign download link: {}", e)))?;
+        Ok(FileLink {
+            url: format!("{}{}", FILE_ROUTE_PREFIX, token),
+            expires_at,
+        })
+    }
+
+    // The file a download link is for
+    pub async fn open(&self, token: &str) -> AppResult<Document> {
+        let key = jwt::decode_file_token(token)
+            .ok_or_else(|| AppError::AuthError("The download link is invalid or expired".into()))?;
+        let bytes = self
+            .storage
+            .get(&key)
+            .await
+            .map_err(|e| storage_error(self.storage.name(), "read", e))?
+            .ok_or_else(|| AppError::NotFound("The file no longer exists".into()))?;
+        let file_name = key.rsplit('/').next().unwrap_or(&key);
+        Ok(Document::from_file(file_name, bytes))
+    }
+}
+
+// Storage failures are the server's, reported like a database failure
+fn storage_error(storage: &str, action: &str, cause: String) -> AppError {
+    tracing::error!(storage, action, cause = %cause, "file storage error");
+    AppError::DatabaseError(format!("Cannot {} file: {}", action, cause))
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Percent-encode a key as S3 expects in the request path, keeping the slashes
+fn uri_encode(key: &str) -> String {
+    key.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
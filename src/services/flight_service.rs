@@ -1,95 +1,472 @@
+use crate::models::aircraft::{Aircraft, SeatLayout};
 use crate::models::flight::{
-    AvailableSeatsResponse, FlightDetail, FlightSearchQuery, FlightSearchResponse,
+    codeshare_entries, AvailableSeatsResponse, CalendarDay, FlightDetail, FlightSearchQuery,
+    FlightSearchResponse, FlightSearchRow, FlightStatus, FlightTimes, MarketingFlight,
+    PriceCalendarQuery, PriceCalendarResponse, RecentFlightsResponse, SearchSort, SeatStatus,
 };
+use crate::models::fare::{self, FarePrice, RouteFares};
+use crate::services::event_bus::{DomainEvent, EventBus};
+use crate::services::fare_service::FareService;
+use crate::services::search_cache::{CachedSearch, SearchCache};
+use crate::utils::config::SeatMapView;
+use crate::utils::document::{self, Document};
 use crate::utils::error::AppError;
 use crate::utils::error::AppResult;
+use crate::utils::ndjson::{collect_rows, RowSink};
+use crate::utils::tunables::SharedTunables;
+use rocket::futures::TryStreamExt;
 use sqlx::types::chrono::{NaiveDate, NaiveTime};
 use sqlx::MySqlPool;
+use tracing::instrument;
+use std::collections::{HashMap, HashSet};
 
+// Number of recently viewed flights kept per user
+pub const RECENT_FLIGHTS_LIMIT: i64 = 10;
+
+// Most flights on one page of search results
+pub const MAX_SEARCH_LIMIT: u32 = 200;
+
+#[derive(Clone)]
 pub struct FlightService {
     pool: MySqlPool,
+    fare_service: FareService,
+    event_bus: Option<EventBus>,
+    tunables: SharedTunables,
+    search_cache: Option<SearchCache>,
 }
 
 impl FlightService {
     pub fn new(pool: MySqlPool) -> Self {
-        FlightService { pool }
+        FlightService {
+            fare_service: FareService::new(pool.clone()),
+            pool,
+            event_bus: None,
+            tunables: SharedTunables::default(),
+            search_cache: None,
+        }
+    }
+
+    // Follow the tunables, which the configuration reload may change while running
+    pub fn with_tunables(mut self, tunables: SharedTunables) -> Self {
+        self.tunables = tunables;
+        self
+    }
+
+    // Publish domain events (searches) to the given event bus
+    pub fn with_event_bus(mut self, event_bus: EventBus) -> Self {
+        self.event_bus = Some(event_bus);
+        self
     }
 
-    // Search available flights
+    // Answer repeated searches and seat maps from the given cache
+    pub fn with_search_cache(mut self, search_cache: SearchCache) -> Self {
+        self.search_cache = Some(search_cache);
+        self
+    }
+
+    fn publish(&self, event: DomainEvent) {
+        if let Some(event_bus) = &self.event_bus {
+            event_bus.publish(event);
+        }
+    }
+    // Search available flights on dates their route operates, like the schedule service
+    #[instrument(skip(self))]
     pub async fn search_flights(
         &self,
         search_query: FlightSearchQuery,
     ) -> AppResult<FlightSearchResponse> {
-        let flights = match search_query.end_date {
-            // If end date is provided, search by date range
-            Some(end_date) => {
-                // Search by date range
-                sqlx::query_as!(
-                    FlightDetail,
-                    r#"
-                    SELECT 
-                        f.flight_id,
-                        f.flight_number,
-                        fr.departure_city,
-                        fr.destination_city,
-                        fr.departure_time as "departure_time: NaiveTime",
-                        fr.arrival_time as "arrival_time: NaiveTime",
-                        f.available_tickets,
-                        f.flight_date as "flight_date: NaiveDate"
-                    FROM flight f
-                    JOIN flight_route fr ON f.flight_number = fr.flight_number
-                    WHERE fr.departure_city = ?
-                    AND fr.destination_city = ?
-                    AND f.flight_date BETWEEN ? AND ?
-                    AND f.available_tickets > 0
-                    "#,
-                    search_query.departure_city,
-                    search_query.destination_city,
-                    search_query.departure_date,
-                    end_date
-                )
-                .fetch_all(&self.pool)
-                .await?
+        let cached = self
+            .search_cache
+            .as_ref()
+            .and_then(|search_cache| search_cache.search(&search_query));
+        if let Some(cached) = cached {
+            self.publish(DomainEvent::FlightSearched {
+                departure_city: cached.departure_city,
+                destination_city: cached.destination_city,
+            });
+            return Ok(cached.response);
+        }
+
+        check_search(&search_query)?;
+        let departure = self.resolve_place(&search_query.departure_city).await?;
+        let destination = self.resolve_place(&search_query.destination_city).await?;
+        let total = self
+            .count_search(&search_query, &departure, &destination)
+            .await?;
+        let cache_key = self.search_cache.is_some().then(|| search_query.clone());
+        let rows = collect_rows(|sink| self.export_search(search_query, sink)).await?;
+
+        let mut response = FlightSearchResponse {
+            flights: Vec::with_capacity(rows.len()),
+            fares: Vec::new(),
+            total,
+        };
+        for row in rows {
+            if let Some(fares) = row.fares {
+                response.fares.push(RouteFares {
+                    flight_number: row.flight.flight_number,
+                    fares,
+                });
             }
-            // If end date is not provided, search by single date
-            None => {
-                sqlx::query_as!(
-                    FlightDetail,
-                    r#"
-                    SELECT 
-                        f.flight_id,
-                        f.flight_number,
-                        fr.departure_city,
-                        fr.destination_city,
-                        fr.departure_time as "departure_time: NaiveTime",
-                        fr.arrival_time as "arrival_time: NaiveTime",
-                        f.available_tickets,
-                        f.flight_date as "flight_date: NaiveDate"
-                    FROM flight f
-                    JOIN flight_route fr ON f.flight_number = fr.flight_number
-                    WHERE fr.departure_city = ?
-                    AND fr.destination_city = ?
-                    AND f.flight_date = ?
-                    AND f.available_tickets > 0
-                    "#,
-                    search_query.departure_city,
-                    search_query.destination_city,
-                    search_query.departure_date
-                )
-                .fetch_all(&self.pool)
-                .await?
+            response.flights.push(row.flight);
+        }
+
+        if let (Some(search_cache), Some(cache_key)) = (&self.search_cache, cache_key) {
+            search_cache.store_search(
+                cache_key,
+                CachedSearch {
+                    departure_city: departure.city,
+                    destination_city: destination.city,
+                    response: response.clone(),
+                },
+            );
+        }
+        Ok(response)
+    }
+
+    // Stream the flights of a search as they are read from the database, so long date
+    // ranges never have to fit in memory. The fares of a route come with its first flight.
+    #[instrument(skip(self, sink))]
+    pub async fn export_search(
+        &self,
+        search_query: FlightSearchQuery,
+        sink: RowSink<FlightSearchRow>,
+    ) -> AppResult<()> {
+        check_search(&search_query)?;
+        // City names may be given in any language or as an airport code, search by the
+        // canonical name
+        let departure = self.resolve_place(&search_query.departure_city).await?;
+        let destination = self.resolve_place(&search_query.destination_city).await?;
+
+        self.publish(DomainEvent::FlightSearched {
+            departure_city: departure.city.clone(),
+            destination_city: destination.city.clone(),
+        });
+
+        // Translate city names to the caller's language when available
+        let names = match &search_query.language {
+            Some(language) => self.localized_city_names(language).await?,
+            None => HashMap::new(),
+        };
+
+        // Without an end date, search the departure date only
+        let end_date = search_query.end_date.unwrap_or(search_query.departure_date);
+        let collapse_codeshares = search_query.collapse_codeshares.unwrap_or(true);
+        let sort_by = search_query.sort_by.unwrap_or_default();
+        let mut flights = sqlx::query_as!(
+            FlightRow,
+            r#"
+            SELECT 
+                f.flight_id,
+                f.flight_number,
+                fr.departure_city,
+                fr.destination_city,
+                fr.departure_airport,
+                fr.destination_airport,
+                da.timezone as "departure_timezone?",
+                aa.timezone as "destination_timezone?",
+                fr.departure_time as "departure_time: NaiveTime",
+                fr.arrival_time as "arrival_time: NaiveTime",
+                f.available_tickets,
+                f.flight_date as "flight_date: NaiveDate",
+                f.status as "status: FlightStatus",
+                f.delay_minutes
+            FROM flight f
+            JOIN flight_route fr ON f.flight_number = fr.flight_number
+            LEFT JOIN airport da ON fr.departure_airport = da.code
+            LEFT JOIN airport aa ON fr.destination_airport = aa.code
+            WHERE LOWER(fr.departure_city) = LOWER(?)
+            AND LOWER(fr.destination_city) = LOWER(?)
+            -- An airport code matches the routes of the airport, and the routes of its
+            -- city without a known airport
+            AND (? IS NULL OR COALESCE(fr.departure_airport, ?) = ?)
+            AND (? IS NULL OR COALESCE(fr.destination_airport, ?) = ?)
+            AND f.flight_date BETWEEN ? AND ?
+            AND f.available_tickets > 0
+            AND f.status <> 'CANCELLED'
+            AND f.flight_date >= fr.start_date
+            AND (fr.end_date IS NULL OR f.flight_date <= fr.end_date)
+            AND INSTR(fr.operating_days, WEEKDAY(f.flight_date) + 1) > 0
+            AND (? IS NULL OR fr.departure_time >= ?)
+            AND (? IS NULL OR fr.departure_time <= ?)
+            ORDER BY
+                -- Cheapest fare of the route
+                IF(?, COALESCE(
+                    (SELECT MIN(fa.base_price) FROM fare fa WHERE fa.flight_number = f.flight_number),
+                    fr.base_fare
+                ), NULL),
+                -- Seconds in the air, arriving the next day when arriving earlier in the day
+                IF(?, MOD(
+                    TIME_TO_SEC(fr.arrival_time) - TIME_TO_SEC(fr.departure_time) + 86400,
+                    86400
+                ), NULL),
+                f.flight_date, fr.departure_time, f.flight_id
+            LIMIT ? OFFSET ?
+            "#,
+            departure.city,
+            destination.city,
+            departure.airport,
+            departure.airport,
+            departure.airport,
+            destination.airport,
+            destination.airport,
+            destination.airport,
+            search_query.departure_date,
+            end_date,
+            search_query.departure_after,
+            search_query.departure_after,
+            search_query.departure_before,
+            search_query.departure_before,
+            sort_by == SearchSort::Price,
+            sort_by == SearchSort::Duration,
+            search_query.limit.map_or(i64::MAX, i64::from),
+            search_query.offset.unwrap_or(0)
+        )
+        .fetch(&self.pool);
+
+        // Price every route in the results once, and look up its codeshares once
+        let mut priced_routes = HashSet::new();
+        let mut route_codeshares: HashMap<i32, Vec<MarketingFlight>> = HashMap::new();
+        while let Some(row) = flights.try_next().await? {
+            let mut flight = FlightDetail::from(row);
+            if let Some(name) = names.get(&flight.departure_city) {
+                flight.departure_city = name.clone();
             }
+            if let Some(name) = names.get(&flight.destination_city) {
+                flight.destination_city = name.clone();
+            }
+
+            let mut fares = if priced_routes.insert(flight.flight_number) {
+                let route_fares = self.fare_service.route_fares(flight.flight_number).await?;
+                Some(route_fares.iter().map(FarePrice::from).collect())
+            } else {
+                None
+            };
+
+            let flight_number = flight.flight_number;
+            if !route_codeshares.contains_key(&flight_number) {
+                let codeshares = self.codeshares(flight_number).await?;
+                route_codeshares.insert(flight_number, codeshares);
+            }
+            let entries = codeshare_entries(
+                flight,
+                &route_codeshares[&flight_number],
+                collapse_codeshares,
+            );
+
+            // The fares come with the first entry of the flight
+            for flight in entries {
+                let fares = fares.take();
+                // Sending waits while the client is behind, which pauses the query
+                if !sink.send(FlightSearchRow { flight, fares }).await {
+                    // The client went away
+                    return Ok(());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Flights a search finds over all its pages
+    async fn count_search(
+        &self,
+        search_query: &FlightSearchQuery,
+        departure: &SearchPlace,
+        destination: &SearchPlace,
+    ) -> AppResult<i64> {
+        let end_date = search_query.end_date.unwrap_or(search_query.departure_date);
+
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*)
+            FROM flight f
+            JOIN flight_route fr ON f.flight_number = fr.flight_number
+            WHERE LOWER(fr.departure_city) = LOWER(?)
+            AND LOWER(fr.destination_city) = LOWER(?)
+            -- An airport code matches the routes of the airport, and the routes of its
+            -- city without a known airport
+            AND (? IS NULL OR COALESCE(fr.departure_airport, ?) = ?)
+            AND (? IS NULL OR COALESCE(fr.destination_airport, ?) = ?)
+            AND f.flight_date BETWEEN ? AND ?
+            AND f.available_tickets > 0
+            AND f.status <> 'CANCELLED'
+            AND f.flight_date >= fr.start_date
+            AND (fr.end_date IS NULL OR f.flight_date <= fr.end_date)
+            AND INSTR(fr.operating_days, WEEKDAY(f.flight_date) + 1) > 0
+            AND (? IS NULL OR fr.departure_time >= ?)
+            AND (? IS NULL OR fr.departure_time <= ?)
+            "#,
+            departure.city,
+            destination.city,
+            departure.airport,
+            departure.airport,
+            departure.airport,
+            destination.airport,
+            destination.airport,
+            destination.airport,
+            search_query.departure_date,
+            end_date,
+            search_query.departure_after,
+            search_query.departure_after,
+            search_query.departure_before,
+            search_query.departure_before
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(total)
+    }
+
+    // One flight for each day of a month, so flexible date searches take one request.
+    // The flights are those a search over the month finds, the first of each day in
+    // the sort order, which is by price unless asked otherwise.
+    #[instrument(skip(self))]
+    pub async fn price_calendar(
+        &self,
+        query: PriceCalendarQuery,
+    ) -> AppResult<PriceCalendarResponse> {
+        let month_end = query.month_end();
+        let mut days: Vec<CalendarDay> = query
+            .month
+            .iter_days()
+            .take_while(|date| *date <= month_end)
+            .map(|date| CalendarDay {
+                date,
+                flights: 0,
+                flight: None,
+                lowest_fare: None,
+            })
+            .collect();
+
+        let search_query = FlightSearchQuery {
+            departure_city: query.departure_city,
+            destination_city: query.destination_city,
+            departure_date: query.month,
+            end_date: Some(month_end),
+            language: query.language,
+            sort_by: Some(query.sort_by.unwrap_or(SearchSort::Price)),
+            ..Default::default()
         };
+        let rows = collect_rows(|sink| self.export_search(search_query, sink)).await?;
+
+        // The fares of a route come with its first flight, before any other of its flights
+        let mut lowest_fares: HashMap<i32, FarePrice> = HashMap::new();
+        for row in rows {
+            let flight = row.flight;
+            if let Some(lowest) = row
+                .fares
+                .and_then(|fares| fares.into_iter().min_by_key(|fare| fare.price))
+            {
+                lowest_fares.insert(flight.flight_number, lowest);
+            }
+            let Some(day) = days.get_mut((flight.flight_date - query.month).num_days() as usize)
+            else {
+                continue;
+            };
+            day.flights += 1;
+            if day.flight.is_none() {
+                day.lowest_fare = lowest_fares.get(&flight.flight_number).cloned();
+                day.flight = Some(flight);
+            }
+        }
 
-        Ok(FlightSearchResponse { flights })
+        Ok(PriceCalendarResponse {
+            month: query.month.format("%Y-%m").to_string(),
+            days,
+        })
     }
 
-    pub async fn get_available_seats(
+    // Partner flight numbers marketing the flights of a route
+    async fn codeshares(&self, flight_number: i32) -> AppResult<Vec<MarketingFlight>> {
+        let codeshares = sqlx::query_as!(
+            MarketingFlight,
+            r#"
+            SELECT
+                marketing_carrier as carrier_code,
+                marketing_flight_number as flight_number
+            FROM codeshare
+            WHERE flight_number = ?
+            ORDER BY marketing_carrier, marketing_flight_number
+            "#,
+            flight_number
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(codeshares)
+    }
+
+    // Map a city name in any language to its canonical name
+    // City of an airport code, or of a city name in any language
+    async fn resolve_place(&self, name: &str) -> AppResult<SearchPlace> {
+        if name.len() == 3 && name.chars().all(|c| c.is_ascii_alphabetic()) {
+            let airport = sqlx::query!(
+                "SELECT code, city FROM airport WHERE code = ?",
+                name.to_uppercase()
+            )
+            .fetch_optional(&self.pool)
+            .await?;
+            if let Some(airport) = airport {
+                return Ok(SearchPlace {
+                    city: airport.city,
+                    airport: Some(airport.code),
+                });
+            }
+        }
+        Ok(SearchPlace {
+            city: self.resolve_city(name).await?,
+            airport: None,
+        })
+    }
+
+    async fn resolve_city(&self, name: &str) -> AppResult<String> {
+        let location = sqlx::query!(
+            r#"
+            SELECT city
+            FROM location
+            WHERE LOWER(name) = LOWER(?)
+            LIMIT 1
+            "#,
+            name
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(location) = location {
+            return Ok(location.city);
+        }
+        // Spelled as the city of an airport
+        let airport_city = sqlx::query_scalar!(
+            "SELECT city FROM airport WHERE LOWER(city) = LOWER(?) LIMIT 1",
+            name
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(airport_city.unwrap_or_else(|| name.to_string()))
+    }
+
+    // Canonical city name to localized name for the given language
+    async fn localized_city_names(&self, language: &str) -> AppResult<HashMap<String, String>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT city, name
+            FROM location
+            WHERE language = ?
+            "#,
+            language
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| (row.city, row.name)).collect())
+    }
+
+    // Id of the flight with the given number and date, or NotFound
+    #[instrument(skip(self))]
+    pub async fn resolve_flight(
         &self,
         flight_number: i32,
         flight_date: NaiveDate,
-    ) -> AppResult<AvailableSeatsResponse> {
-        // Get flight id by flight number and flight date
+    ) -> AppResult<i32> {
         let flight = sqlx::query!(
             r#"
             SELECT flight_id 
@@ -102,25 +479,347 @@ impl FlightService {
         .fetch_optional(&self.pool)
         .await?
         .ok_or_else(|| AppError::NotFound("Flight not found".into()))?;
+        Ok(flight.flight_id)
+    }
 
-        // Get all available seats
-        let available_seats = sqlx::query!(
+    // Seat map as passengers see it, in the view the configuration sets
+    #[instrument(skip(self))]
+    pub async fn get_available_seats(
+        &self,
+        flight_number: i32,
+        flight_date: NaiveDate,
+    ) -> AppResult<AvailableSeatsResponse> {
+        let view = self.tunables.current().seat_map_view;
+        let Some(search_cache) = &self.search_cache else {
+            return self.get_seat_map(flight_number, flight_date, view).await;
+        };
+        if let Some(seat_map) = search_cache.seat_map(flight_number, flight_date, view) {
+            return Ok(seat_map);
+        }
+
+        let flight_id = self.resolve_flight(flight_number, flight_date).await?;
+        let seat_map = self.flight_seat_map(flight_id, flight_number, view).await?;
+        search_cache.store_seat_map(
+            flight_id,
+            flight_number,
+            flight_date,
+            view,
+            seat_map.clone(),
+        );
+        Ok(seat_map)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn get_seat_map(
+        &self,
+        flight_number: i32,
+        flight_date: NaiveDate,
+        view: SeatMapView,
+    ) -> AppResult<AvailableSeatsResponse> {
+        let flight_id = self.resolve_flight(flight_number, flight_date).await?;
+        self.flight_seat_map(flight_id, flight_number, view).await
+    }
+
+    async fn flight_seat_map(
+        &self,
+        flight_id: i32,
+        flight_number: i32,
+        view: SeatMapView,
+    ) -> AppResult<AvailableSeatsResponse> {
+        let seat_rows = sqlx::query!(
+            r#"
+            SELECT seat_number, seat_status as "seat_status: SeatStatus"
+            FROM seat_info
+            WHERE flight_id = ?
+            ORDER BY seat_number
+            "#,
+            flight_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut available_seats = Vec::new();
+        let mut occupied_seats = Vec::new();
+        for row in seat_rows {
+            match row.seat_status {
+                SeatStatus::Available => available_seats.push(row.seat_number),
+                SeatStatus::Booked | SeatStatus::Held => occupied_seats.push(row.seat_number),
+                // Seats taken out of service are simply not offered
+                SeatStatus::Unavailable => {}
+            }
+        }
+
+        // Derive seat attributes from the aircraft layout
+        let aircraft = sqlx::query_as!(
+            Aircraft,
+            r#"
+            SELECT
+                a.aircraft_id,
+                a.capacity,
+                a.seats_per_row,
+                a.exit_rows,
+                a.accessible_rows,
+                a.extra_legroom_rows
+            FROM flight f
+            JOIN flight_route fr ON f.flight_number = fr.flight_number
+            JOIN aircraft a ON COALESCE(f.aircraft_id, fr.aircraft_id) = a.aircraft_id
+            WHERE f.flight_id = ?
+            "#,
+            flight_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        let layout = SeatLayout::from_aircraft(&aircraft);
+        // The cabin is split into fare class sections by the rows of the route's fares
+        let fares = self.fare_service.route_fares(flight_number).await?;
+
+        let seats = available_seats
+            .iter()
+            .map(|seat_number| {
+                let mut seat = layout.attributes(*seat_number);
+                seat.fare_class = fare::seat_class(&fares, seat.row);
+                seat
+            })
+            .collect();
+
+        Ok(AvailableSeatsResponse {
+            available_seats,
+            seats,
+            occupied_seats: (view == SeatMapView::Full).then_some(occupied_seats),
+        })
+    }
+
+    // Seat map of a flight as an SVG image, showing which seats are free like the
+    // availability-only view does
+    #[instrument(skip(self))]
+    pub async fn seat_map_svg(&self, flight_id: i32) -> AppResult<Document> {
+        let flight = sqlx::query!(
+            r#"
+            SELECT
+                f.flight_number,
+                f.flight_date as "flight_date: NaiveDate",
+                a.aircraft_id,
+                a.capacity,
+                a.seats_per_row,
+                a.exit_rows,
+                a.accessible_rows,
+                a.extra_legroom_rows
+            FROM flight f
+            JOIN flight_route fr ON f.flight_number = fr.flight_number
+            JOIN aircraft a ON COALESCE(f.aircraft_id, fr.aircraft_id) = a.aircraft_id
+            WHERE f.flight_id = ?
+            "#,
+            flight_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Flight not found".into()))?;
+
+        let available_seats = sqlx::query_scalar!(
             r#"
             SELECT seat_number
             FROM seat_info
             WHERE flight_id = ? AND seat_status = 'AVAILABLE'
+            ORDER BY seat_number
             "#,
+            flight_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let layout = SeatLayout::from_aircraft(&Aircraft {
+            aircraft_id: flight.aircraft_id,
+            capacity: flight.capacity,
+            seats_per_row: flight.seats_per_row,
+            exit_rows: flight.exit_rows,
+            accessible_rows: flight.accessible_rows,
+            extra_legroom_rows: flight.extra_legroom_rows,
+        });
+        let title = format!("Flight {} on {}", flight.flight_number, flight.flight_date);
+        let svg = document::seat_map_svg(&title, &layout, flight.capacity, &available_seats);
+        Ok(Document::svg(
+            &format!("seatmap-{}-{}", flight.flight_number, flight.flight_date),
+            svg,
+        ))
+    }
+
+    // Remember that the user looked at this flight, keeping only the most recent views
+    #[instrument(skip(self))]
+    pub async fn record_flight_view(
+        &self,
+        user_id: i32,
+        flight_number: i32,
+        flight_date: NaiveDate,
+    ) -> AppResult<()> {
+        let flight = sqlx::query!(
+            r#"
+            SELECT flight_id
+            FROM flight
+            WHERE flight_number = ? AND flight_date = ?
+            "#,
+            flight_number,
+            flight_date
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let flight = match flight {
+            Some(flight) => flight,
+            None => return Ok(()),
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO flight_view (user_id, flight_id, viewed_at)
+            VALUES (?, ?, NOW(6))
+            ON DUPLICATE KEY UPDATE viewed_at = NOW(6)
+            "#,
+            user_id,
             flight.flight_id
         )
+        .execute(&self.pool)
+        .await?;
+
+        // Drop everything older than the last kept view
+        sqlx::query!(
+            r#"
+            DELETE FROM flight_view
+            WHERE user_id = ?
+            AND viewed_at < (
+                SELECT viewed_at FROM (
+                    SELECT viewed_at
+                    FROM flight_view
+                    WHERE user_id = ?
+                    ORDER BY viewed_at DESC
+                    LIMIT 1 OFFSET ?
+                ) AS oldest_kept
+            )
+            "#,
+            user_id,
+            user_id,
+            RECENT_FLIGHTS_LIMIT - 1
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Flights recently viewed by the user, most recent first
+    #[instrument(skip(self))]
+    pub async fn get_recent_flights(&self, user_id: i32) -> AppResult<RecentFlightsResponse> {
+        let flights = sqlx::query_as!(
+            FlightRow,
+            r#"
+            SELECT
+                f.flight_id,
+                f.flight_number,
+                fr.departure_city,
+                fr.destination_city,
+                fr.departure_airport,
+                fr.destination_airport,
+                da.timezone as "departure_timezone?",
+                aa.timezone as "destination_timezone?",
+                fr.departure_time as "departure_time: NaiveTime",
+                fr.arrival_time as "arrival_time: NaiveTime",
+                f.available_tickets,
+                f.flight_date as "flight_date: NaiveDate",
+                f.status as "status: FlightStatus",
+                f.delay_minutes
+            FROM flight_view v
+            JOIN flight f ON v.flight_id = f.flight_id
+            JOIN flight_route fr ON f.flight_number = fr.flight_number
+            LEFT JOIN airport da ON fr.departure_airport = da.code
+            LEFT JOIN airport aa ON fr.destination_airport = aa.code
+            WHERE v.user_id = ?
+            ORDER BY v.viewed_at DESC
+            LIMIT ?
+            "#,
+            user_id,
+            RECENT_FLIGHTS_LIMIT
+        )
         .fetch_all(&self.pool)
         .await?;
 
-        // Convert query result to Vec<i32>
-        let available_seats: Vec<i32> = available_seats
-            .into_iter()
-            .map(|row| row.seat_number)
-            .collect();
+        Ok(RecentFlightsResponse {
+            flights: flights.into_iter().map(FlightDetail::from).collect(),
+        })
+    }
+}
+
+// Where a search leaves from or goes to: a city, and an airport of it when searched
+// by code
+struct SearchPlace {
+    city: String,
+    airport: Option<String>,
+}
+
+struct FlightRow {
+    flight_id: i32,
+    flight_number: i32,
+    departure_city: String,
+    destination_city: String,
+    departure_airport: Option<String>,
+    destination_airport: Option<String>,
+    departure_timezone: Option<String>,
+    destination_timezone: Option<String>,
+    departure_time: NaiveTime,
+    arrival_time: NaiveTime,
+    available_tickets: i32,
+    flight_date: NaiveDate,
+    status: FlightStatus,
+    delay_minutes: i32,
+}
 
-        Ok(AvailableSeatsResponse { available_seats })
+impl From<FlightRow> for FlightDetail {
+    fn from(row: FlightRow) -> Self {
+        let times = FlightTimes::new(
+            row.flight_date,
+            row.departure_time,
+            row.arrival_time,
+            row.departure_timezone.as_deref(),
+            row.destination_timezone.as_deref(),
+        );
+        FlightDetail {
+            flight_id: row.flight_id,
+            flight_number: row.flight_number,
+            departure_city: row.departure_city,
+            destination_city: row.destination_city,
+            departure_airport: row.departure_airport,
+            destination_airport: row.destination_airport,
+            departure_time: row.departure_time,
+            arrival_time: row.arrival_time,
+            departure_at: times.map(|times| times.departure_at),
+            arrival_at: times.map(|times| times.arrival_at),
+            duration_minutes: times.map(|times| times.duration_minutes),
+            available_tickets: row.available_tickets,
+            flight_date: row.flight_date,
+            status: row.status,
+            delay_minutes: row.delay_minutes,
+            marketed_as: None,
+            codeshares: Vec::new(),
+        }
+    }
+}
+
+fn check_search(search_query: &FlightSearchQuery) -> AppResult<()> {
+    if let (Some(after), Some(before)) =
+        (search_query.departure_after, search_query.departure_before)
+    {
+        if after > before {
+            return Err(AppError::BadRequest(
+                "departure_after must not be later than departure_before".into(),
+            ));
+        }
+    }
+    if search_query
+        .limit
+        .map_or(false, |limit| limit == 0 || limit > MAX_SEARCH_LIMIT)
+    {
+        return Err(AppError::BadRequest(format!(
+            "limit must be between 1 and {}",
+            MAX_SEARCH_LIMIT
+        )));
     }
+    Ok(())
 }
@@ -0,0 +1,116 @@
+use crate::models::db_enum::DbEnum;
+use crate::models::funnel::{FunnelReport, FunnelStep, FunnelStepReport};
+use crate::services::event_bus::{DomainEvent, EventBus};
+use crate::utils::error::AppResult;
+use sqlx::MySqlPool;
+use std::collections::HashMap;
+use tokio::sync::broadcast::error::RecvError;
+
+#[derive(Clone)]
+pub struct FunnelService {
+    pool: MySqlPool,
+}
+
+impl FunnelService {
+    pub fn new(pool: MySqlPool) -> Self {
+        FunnelService { pool }
+    }
+
+    // Store the funnel events published on the bus in the background
+    pub fn spawn_recorder(&self, event_bus: &EventBus) {
+        let service = self.clone();
+        let mut receiver = event_bus.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(DomainEvent::FunnelStepReached {
+                        session_id,
+                        step,
+                        flight_number,
+                    }) => {
+                        if let Err(e) = service.record(&session_id, step, flight_number).await {
+                            tracing::error!(error = %e, "failed to record funnel event");
+                        }
+                    }
+                    Ok(_) => continue,
+                    // Analytics can afford to miss a few events under heavy load
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    pub async fn record(
+        &self,
+        session_id: &str,
+        step: FunnelStep,
+        flight_number: Option<i32>,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO funnel_event (session_id, step, flight_number, created_at)
+            VALUES (?, ?, ?, NOW())
+            "#,
+            session_id,
+            step.as_db_str(),
+            flight_number
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // Sessions reaching each funnel step over the last `days` days, and the conversion between steps
+    pub async fn conversion_report(&self, days: i64) -> AppResult<FunnelReport> {
+        let since = chrono::Utc::now().naive_utc() - chrono::Duration::days(days);
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT step, COUNT(DISTINCT session_id) as "sessions!: i64"
+            FROM funnel_event
+            WHERE created_at >= ?
+            GROUP BY step
+            "#,
+            since
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let sessions: HashMap<FunnelStep, i64> = rows
+            .into_iter()
+            .filter_map(|row| FunnelStep::from_db_str(&row.step).map(|step| (step, row.sessions)))
+            .collect();
+
+        let mut steps: Vec<FunnelStepReport> = Vec::new();
+        for step in FunnelStep::VARIANTS {
+            let count = sessions.get(step).copied().unwrap_or(0);
+            let conversion_rate = steps
+                .last()
+                .and_then(|previous| conversion_rate(count, previous.sessions));
+            steps.push(FunnelStepReport {
+                step: *step,
+                sessions: count,
+                conversion_rate,
+            });
+        }
+
+        let overall_conversion_rate = match (steps.first(), steps.last()) {
+            (Some(first), Some(last)) => conversion_rate(last.sessions, first.sessions),
+            _ => None,
+        };
+
+        Ok(FunnelReport {
+            days,
+            steps,
+            overall_conversion_rate,
+        })
+    }
+}
+
+fn conversion_rate(sessions: i64, previous_sessions: i64) -> Option<f64> {
+    if previous_sessions == 0 {
+        return None;
+    }
+    Some(sessions as f64 / previous_sessions as f64)
+}
@@ -0,0 +1,241 @@
+use crate::models::health::{
+    BuildInfo, CheckStatus, DiagnosticCheck, DiagnosticsResponse, HealthResponse,
+};
+use crate::services::event_bus::EventBus;
+use sqlx::{MySqlPool, Row};
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+// Longest a ping may take before the database counts as unavailable
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Replicas further behind than this fail the diagnostics
+const MAX_REPLICATION_LAG_SECONDS: i64 = 30;
+
+// Overdue work younger than this is left to the next run of the background jobs
+const JOB_BACKLOG_GRACE: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Clone)]
+pub struct HealthService {
+    pool: MySqlPool,
+    started: Instant,
+    replica: Option<MySqlPool>,
+    event_bus: Option<EventBus>,
+}
+
+impl HealthService {
+    pub fn new(pool: MySqlPool) -> Self {
+        HealthService {
+            pool,
+            started: Instant::now(),
+            replica: None,
+            event_bus: None,
+        }
+    }
+
+    pub fn build_info() -> BuildInfo {
+        BuildInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_sha: option_env!("GIT_SHA").map(str::to_string),
+        }
+    }
+
+    // Ping the database and report it along with the build of the service
+    pub async fn check(&self) -> HealthResponse {
+        let ping_started = Instant::now();
+        let ping = tokio::time::timeout(PING_TIMEOUT, sqlx::query("SELECT 1").execute(&self.pool));
+        let database_latency_ms = match ping.await {
+            Ok(Ok(_)) => Some(ping_started.elapsed().as_millis() as u64),
+            Ok(Err(e)) => {
+                tracing::warn!(error = %e, "database ping failed");
+                None
+            }
+            Err(_) => {
+                tracing::warn!(
+                    timeout_ms = PING_TIMEOUT.as_millis() as u64,
+                    "database ping timed out"
+                );
+                None
+            }
+        };
+
+        let database = database_latency_ms.is_some();
+        HealthResponse {
+            status: if database { "ok" } else { "unavailable" }.to_string(),
+            database,
+            database_latency_ms,
+            uptime_seconds: self.started.elapsed().as_secs(),
+            build: Self::build_info(),
+        }
+    }
+
+    pub fn with_event_bus(mut self, event_bus: EventBus) -> Self {
+        self.event_bus = Some(event_bus);
+        self
+    }
+
+    // Read replica whose replication lag the diagnostics report
+    pub fn with_replica(mut self, replica: MySqlPool) -> Self {
+        self.replica = Some(replica);
+        self
+    }
+
+    // Live checks for on-call debugging, each reported as pass, fail or skipped.
+    // Checks run one after the other so a slow one shows in its own duration.
+    pub async fn diagnostics(&self) -> DiagnosticsResponse {
+        let checks = vec![
+            timed("database_pool", self.check_pool()).await,
+            timed("replication_lag", self.check_replication_lag()).await,
+            timed("job_backlog", self.check_job_backlog()).await,
+            timed("event_backlog", async { self.check_event_backlog() }).await,
+            timed("cache", async {
+                (
+                    CheckStatus::Skipped,
+                    "No external cache configured, caches are in process".to_string(),
+                )
+            })
+            .await,
+        ];
+
+        let status = if checks.iter().any(|check| check.status == CheckStatus::Fail) {
+            CheckStatus::Fail
+        } else {
+            CheckStatus::Pass
+        };
+        DiagnosticsResponse {
+            status,
+            checks,
+            uptime_seconds: self.started.elapsed().as_secs(),
+            build: Self::build_info(),
+        }
+    }
+
+    async fn check_pool(&self) -> (CheckStatus, String) {
+        let connections = format!(
+            "{} connections open, {} idle",
+            self.pool.size(),
+            self.pool.num_idle()
+        );
+        let ping = tokio::time::timeout(PING_TIMEOUT, sqlx::query("SELECT 1").execute(&self.pool));
+        match ping.await {
+            Ok(Ok(_)) => (CheckStatus::Pass, connections),
+            Ok(Err(e)) => (
+                CheckStatus::Fail,
+                format!("Ping failed: {}, {}", e, connections),
+            ),
+            Err(_) => (
+                CheckStatus::Fail,
+                format!("Ping timed out after {:?}, {}", PING_TIMEOUT, connections),
+            ),
+        }
+    }
+
+    async fn check_replication_lag(&self) -> (CheckStatus, String) {
+        let Some(replica) = &self.replica else {
+            return (
+                CheckStatus::Skipped,
+                "No read replica configured".to_string(),
+            );
+        };
+        let status = tokio::time::timeout(
+            PING_TIMEOUT,
+            sqlx::query("SHOW REPLICA STATUS").fetch_optional(replica),
+        );
+        let row = match status.await {
+            Ok(Ok(Some(row))) => row,
+            Ok(Ok(None)) => {
+                return (CheckStatus::Fail, "Replica is not replicating".to_string());
+            }
+            Ok(Err(e)) => return (CheckStatus::Fail, format!("Replica query failed: {}", e)),
+            Err(_) => {
+                return (
+                    CheckStatus::Fail,
+                    format!("Replica did not answer within {:?}", PING_TIMEOUT),
+                )
+            }
+        };
+
+        // NULL while the replication threads are stopped. Unchecked as servers differ
+        // on whether the column is signed.
+        match row.try_get_unchecked::<Option<i64>, _>("Seconds_Behind_Source") {
+            Ok(Some(lag)) if lag <= MAX_REPLICATION_LAG_SECONDS => {
+                (CheckStatus::Pass, format!("{} seconds behind", lag))
+            }
+            Ok(Some(lag)) => (
+                CheckStatus::Fail,
+                format!(
+                    "{} seconds behind, more than {}",
+                    lag, MAX_REPLICATION_LAG_SECONDS
+                ),
+            ),
+            Ok(None) => (CheckStatus::Fail, "Replication is stopped".to_string()),
+            Err(e) => (CheckStatus::Fail, format!("Cannot read the lag: {}", e)),
+        }
+    }
+
+    // Work the background jobs should have done by now: unpaid bookings past their
+    // payment deadline, seat holds past their expiry and committed events not published
+    async fn check_job_backlog(&self) -> (CheckStatus, String) {
+        let grace_seconds = JOB_BACKLOG_GRACE.as_secs() as i64;
+        let backlog = sqlx::query!(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM payment
+                 WHERE status = 'PENDING'
+                 AND expires_at < UTC_TIMESTAMP() - INTERVAL ? SECOND) as "unpaid!: i64",
+                (SELECT COUNT(*) FROM seat_info
+                 WHERE seat_status = 'HELD'
+                 AND held_until < UTC_TIMESTAMP() - INTERVAL ? SECOND) as "holds!: i64",
+                (SELECT COUNT(*) FROM outbox_event
+                 WHERE published_at IS NULL
+                 AND created_at < UTC_TIMESTAMP() - INTERVAL ? SECOND) as "events!: i64"
+            "#,
+            grace_seconds,
+            grace_seconds,
+            grace_seconds
+        )
+        .fetch_one(&self.pool)
+        .await;
+
+        match backlog {
+            Ok(backlog) => {
+                let detail = format!(
+                    "{} unpaid bookings, {} seat holds and {} outbox events overdue by more \
+                     than {} seconds",
+                    backlog.unpaid, backlog.holds, backlog.events, grace_seconds
+                );
+                if backlog.unpaid == 0 && backlog.holds == 0 && backlog.events == 0 {
+                    (CheckStatus::Pass, detail)
+                } else {
+                    (CheckStatus::Fail, detail)
+                }
+            }
+            Err(e) => (CheckStatus::Fail, format!("Backlog query failed: {}", e)),
+        }
+    }
+
+    fn check_event_backlog(&self) -> (CheckStatus, String) {
+        let Some(event_bus) = &self.event_bus else {
+            return (CheckStatus::Skipped, "No event bus attached".to_string());
+        };
+        let (pending, capacity) = event_bus.backlog();
+        let detail = format!("{} of {} events waiting for subscribers", pending, capacity);
+        // Past this the slowest subscriber is about to miss events
+        if pending * 10 >= capacity * 9 {
+            (CheckStatus::Fail, detail)
+        } else {
+            (CheckStatus::Pass, detail)
+        }
+    }
+}
+
+async fn timed(name: &str, check: impl Future<Output = (CheckStatus, String)>) -> DiagnosticCheck {
+    let started = Instant::now();
+    let (status, detail) = check.await;
+    DiagnosticCheck {
+        name: name.to_string(),
+        status,
+        detail,
+        duration_ms: started.elapsed().as_millis() as u64,
+    }
+}
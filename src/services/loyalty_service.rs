@@ -0,0 +1,230 @@
+use crate::models::fare::FareClass;
+use crate::models::loyalty::{
+    earned_points, points_value, LoyaltyBalanceResponse, LoyaltyEntryType, LoyaltyLedgerEntry,
+    LOYALTY_PROVIDER, RECENT_LEDGER_ENTRIES,
+};
+use crate::models::payment::DEFAULT_CURRENCY;
+use crate::utils::error::{is_unique_violation, AppError, AppResult};
+use chrono::{NaiveDate, NaiveDateTime};
+use sqlx::{MySql, MySqlPool, Transaction};
+use std::time::Duration;
+
+// Tickets credited per accrual run, the rest wait for the next one
+const ACCRUAL_BATCH_SIZE: i64 = 500;
+
+#[derive(Clone)]
+pub struct LoyaltyService {
+    pool: MySqlPool,
+}
+
+impl LoyaltyService {
+    pub fn new(pool: MySqlPool) -> Self {
+        LoyaltyService { pool }
+    }
+
+    // Points of the customer with the latest changes to them
+    pub async fn balance(&self, user_id: i32) -> AppResult<LoyaltyBalanceResponse> {
+        let balance = sqlx::query_scalar!(
+            "SELECT balance FROM loyalty_account WHERE customer_id = ?",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .unwrap_or(0);
+
+        let recent_entries = sqlx::query_as!(
+            LoyaltyLedgerEntry,
+            r#"
+            SELECT
+                id as entry_id,
+                entry_type as "entry_type: LoyaltyEntryType",
+                points,
+                ticket_id,
+                booking_id,
+                description,
+                created_at as "created_at: NaiveDateTime"
+            FROM loyalty_ledger
+            WHERE customer_id = ?
+            ORDER BY id DESC
+            LIMIT ?
+            "#,
+            user_id,
+            RECENT_LEDGER_ENTRIES
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(LoyaltyBalanceResponse {
+            balance,
+            value: points_value(balance),
+            currency: DEFAULT_CURRENCY.to_string(),
+            recent_entries,
+        })
+    }
+
+    // Credit the points of the tickets of departed flights that have not earned them
+    // yet. Only fares paid with money earn points. Returns the number of tickets
+    // credited.
+    pub async fn accrue_completed_flights(&self) -> AppResult<usize> {
+        let tickets = sqlx::query!(
+            r#"
+            SELECT
+                t.id,
+                t.customer_id,
+                t.booking_id as "booking_id!: i32",
+                t.flight_number,
+                t.flight_date as "flight_date: NaiveDate",
+                t.fare_class as "fare_class: FareClass",
+                t.price
+            FROM ticket t
+            JOIN flight f ON t.flight_id = f.flight_id
+            JOIN booking b ON t.booking_id = b.id
+            WHERE f.status = 'DEPARTED'
+                AND b.status = 'CONFIRMED'
+                AND t.price > 0
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM loyalty_ledger l
+                    WHERE l.ticket_id = t.id AND l.entry_type = 'EARN'
+                )
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM payment p
+                    WHERE p.booking_id = b.id AND p.provider = ?
+                )
+            ORDER BY t.id
+            LIMIT ?
+            "#,
+            LOYALTY_PROVIDER,
+            ACCRUAL_BATCH_SIZE
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut credited = 0;
+        for ticket in tickets {
+            let points = earned_points(ticket.fare_class, ticket.price);
+            if points == 0 {
+                continue;
+            }
+            let mut tx = self.pool.begin().await?;
+            let result = add_entry(
+                &mut tx,
+                LedgerChange {
+                    customer_id: ticket.customer_id,
+                    entry_type: LoyaltyEntryType::Earn,
+                    points,
+                    ticket_id: Some(ticket.id),
+                    booking_id: Some(ticket.booking_id),
+                    description: format!(
+                        "Flight {} on {}",
+                        ticket.flight_number, ticket.flight_date
+                    ),
+                },
+            )
+            .await;
+            match result {
+                Ok(_) => {
+                    tx.commit().await?;
+                    credited += 1;
+                }
+                // Credited by a concurrent run
+                Err(AppError::Conflict(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(credited)
+    }
+
+    // Periodically credit the points of departed flights in the background
+    pub fn spawn_accrual_task(&self, period: Duration) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                if let Err(e) = service.accrue_completed_flights().await {
+                    tracing::error!(error = %e, "failed to accrue loyalty points");
+                }
+            }
+        });
+    }
+}
+
+// A change to the points of a customer, negative when points are spent
+pub struct LedgerChange {
+    pub customer_id: i32,
+    pub entry_type: LoyaltyEntryType,
+    pub points: i32,
+    pub ticket_id: Option<i32>,
+    pub booking_id: Option<i32>,
+    pub description: String,
+}
+
+// Record a change of points in the transaction of what caused it. The account is
+// locked until the transaction ends, so concurrent redemptions cannot spend the same
+// points. Returns the id of the ledger entry.
+pub async fn add_entry(tx: &mut Transaction<'_, MySql>, change: LedgerChange) -> AppResult<i32> {
+    sqlx::query!(
+        r#"
+        INSERT INTO loyalty_account (customer_id, balance, updated_at)
+        VALUES (?, 0, UTC_TIMESTAMP())
+        ON DUPLICATE KEY UPDATE customer_id = customer_id
+        "#,
+        change.customer_id
+    )
+    .execute(&mut **tx)
+    .await?;
+    let balance = sqlx::query_scalar!(
+        "SELECT balance FROM loyalty_account WHERE customer_id = ? FOR UPDATE",
+        change.customer_id
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+    if balance + change.points < 0 {
+        return Err(AppError::Unprocessable(format!(
+            "Not enough loyalty points: {} needed, {} available",
+            -change.points, balance
+        )));
+    }
+
+    let entry_id = sqlx::query!(
+        r#"
+        INSERT INTO loyalty_ledger
+        (customer_id, entry_type, points, ticket_id, booking_id, description, created_at)
+        VALUES (?, ?, ?, ?, ?, ?, UTC_TIMESTAMP())
+        "#,
+        change.customer_id,
+        change.entry_type,
+        change.points,
+        change.ticket_id,
+        change.booking_id,
+        change.description
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| {
+        // Each ticket earns and is refunded once
+        if is_unique_violation(&e) {
+            AppError::Conflict("The points of this ticket are already recorded".into())
+        } else {
+            e.into()
+        }
+    })?
+    .last_insert_id() as i32;
+    sqlx::query!(
+        r#"
+        UPDATE loyalty_account
+        SET balance = balance + ?,
+            updated_at = UTC_TIMESTAMP()
+        WHERE customer_id = ?
+        "#,
+        change.points,
+        change.customer_id
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(entry_id)
+}
@@ -1,3 +1,26 @@
+pub mod admin_service;
+pub mod aircraft_service;
+pub mod booking_queue;
+pub mod event_bus;
+pub mod fare_service;
+pub mod file_service;
 pub mod flight_service;
+pub mod funnel_service;
+pub mod health_service;
+pub mod loyalty_service;
+pub mod notification_service;
+pub mod operation_log;
+pub mod operation_service;
+pub mod outbox;
+pub mod partner_service;
+pub mod payment_service;
+pub mod promo_code_service;
+pub mod report_service;
+pub mod route_stats_service;
+pub mod sandbox_service;
+pub mod schedule_service;
+pub mod search_cache;
+pub mod seat_block_service;
+pub mod slo_service;
 pub mod ticket_service;
 pub mod user_service;
@@ -0,0 +1,384 @@
+use crate::models::flight::FlightStatus;
+use crate::services::event_bus::{DomainEvent, EventBus};
+use crate::utils::config::NotificationConfig;
+use crate::utils::error::{AppError, AppResult};
+use chrono::{NaiveDate, NaiveTime};
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use sqlx::MySqlPool;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+// An email to a passenger
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailMessage {
+    pub to_name: String,
+    pub to_address: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// A way of delivering emails
+#[rocket::async_trait]
+pub trait EmailTransport: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn send(&self, message: &EmailMessage) -> Result<(), String>;
+}
+
+// Sends emails through an SMTP server
+pub struct SmtpTransport {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpTransport {
+    pub fn new(smtp_url: &str, from: &str) -> AppResult<Self> {
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::from_url(smtp_url)
+            .map_err(|e| AppError::ValidationError(format!("Invalid SMTP URL: {}", e)))?
+            .build();
+        let from = from
+            .parse()
+            .map_err(|e| AppError::ValidationError(format!("Invalid sender address: {}", e)))?;
+        Ok(SmtpTransport { transport, from })
+    }
+}
+
+#[rocket::async_trait]
+impl EmailTransport for SmtpTransport {
+    fn name(&self) -> &'static str {
+        "smtp"
+    }
+
+    async fn send(&self, message: &EmailMessage) -> Result<(), String> {
+        let to = Mailbox::new(
+            Some(message.to_name.clone()),
+            message.to_address.parse().map_err(|e| format!("{}", e))?,
+        );
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(message.subject.clone())
+            .header(ContentType::TEXT_PLAIN)
+            .body(message.body.clone())
+            .map_err(|e| e.to_string())?;
+        self.transport
+            .send(email)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+// Only logs the emails, for development without an SMTP server
+pub struct LogTransport;
+
+#[rocket::async_trait]
+impl EmailTransport for LogTransport {
+    fn name(&self) -> &'static str {
+        "log"
+    }
+
+    async fn send(&self, message: &EmailMessage) -> Result<(), String> {
+        tracing::info!(
+            to = %message.to_address,
+            subject = %message.subject,
+            "email not sent, no SMTP server configured"
+        );
+        Ok(())
+    }
+}
+
+// Transport for the configuration, SMTP when a server is set and logging otherwise
+pub fn transport_for(config: &NotificationConfig) -> AppResult<Arc<dyn EmailTransport>> {
+    Ok(match &config.smtp_url {
+        Some(smtp_url) => Arc::new(SmtpTransport::new(smtp_url, &config.from)?),
+        None => Arc::new(LogTransport),
+    })
+}
+
+// Emails passengers about their bookings and flights. Emails only go to verified
+// addresses.
+#[derive(Clone)]
+pub struct NotificationService {
+    pool: MySqlPool,
+    transport: Arc<dyn EmailTransport>,
+}
+
+impl NotificationService {
+    pub fn new(pool: MySqlPool, transport: Arc<dyn EmailTransport>) -> Self {
+        NotificationService { pool, transport }
+    }
+
+    // Send the emails for the events published on the bus in the background, so
+    // requests never wait for the mail server
+    pub fn spawn_sender(&self, event_bus: &EventBus) {
+        let service = self.clone();
+        let mut receiver = event_bus.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => {
+                        if let Err(e) = service.notify(&event).await {
+                            tracing::error!(error = %e, "failed to send notifications");
+                        }
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        tracing::warn!(missed, "notification sender lagging, events dropped");
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    // Send the emails an event calls for, returning how many were sent
+    pub async fn notify(&self, event: &DomainEvent) -> AppResult<usize> {
+        let messages = match event {
+            DomainEvent::TicketBooked { ticket_id, .. } => {
+                self.booking_confirmation(*ticket_id).await?
+            }
+            DomainEvent::TicketCancelled {
+                customer_id,
+                flight_number,
+                flight_date,
+                ..
+            } => {
+                self.cancellation_notice(*customer_id, *flight_number, *flight_date)
+                    .await?
+            }
+            DomainEvent::FlightStatusChanged {
+                flight_id,
+                status: FlightStatus::Cancelled,
+                previous_status,
+                ..
+            } if *previous_status != FlightStatus::Cancelled => {
+                self.flight_cancellation_alerts(*flight_id).await?
+            }
+            // Only a longer delay is worth an alert, not a shorter one
+            DomainEvent::FlightStatusChanged {
+                flight_id,
+                status: FlightStatus::Delayed,
+                previous_delay_minutes,
+                delay_minutes,
+                reason,
+                ..
+            } if delay_minutes > previous_delay_minutes => {
+                self.delay_alerts(*flight_id, *delay_minutes, reason.as_deref())
+                    .await?
+            }
+            _ => Vec::new(),
+        };
+
+        let mut sent = 0;
+        for message in &messages {
+            match self.transport.send(message).await {
+                Ok(()) => sent += 1,
+                // One bad address must not keep the other passengers uninformed
+                Err(e) => tracing::warn!(
+                    transport = self.transport.name(),
+                    to = %message.to_address,
+                    error = %e,
+                    "failed to send email"
+                ),
+            }
+        }
+        Ok(sent)
+    }
+
+    async fn booking_confirmation(&self, ticket_id: i32) -> AppResult<Vec<EmailMessage>> {
+        let ticket = sqlx::query!(
+            r#"
+            SELECT
+                c.name,
+                c.email as "email!",
+                t.booking_reference,
+                t.flight_number,
+                t.flight_date as "flight_date: NaiveDate",
+                t.seat_number,
+                fr.departure_city,
+                fr.destination_city,
+                fr.departure_time as "departure_time: NaiveTime"
+            FROM ticket t
+            JOIN customer_info c ON t.customer_id = c.id
+            JOIN flight_route fr ON t.flight_number = fr.flight_number
+            WHERE t.id = ? AND c.email IS NOT NULL AND c.email_verified
+            "#,
+            ticket_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(ticket
+            .map(|ticket| {
+                let seat = ticket
+                    .seat_number
+                    .map_or("assigned at check-in".to_string(), |seat| seat.to_string());
+                EmailMessage {
+                    to_name: ticket.name.clone(),
+                    to_address: ticket.email,
+                    subject: format!(
+                        "Booking confirmed: flight {} on {}",
+                        ticket.flight_number, ticket.flight_date
+                    ),
+                    body: format!(
+                        "Dear {},\n\n\
+                         your booking is confirmed.\n\n\
+                         Booking reference: {}\n\
+                         Flight: {} from {} to {}\n\
+                         Departure: {} at {} local time\n\
+                         Seat: {}\n",
+                        ticket.name,
+                        ticket.booking_reference.as_deref().unwrap_or("-"),
+                        ticket.flight_number,
+                        ticket.departure_city,
+                        ticket.destination_city,
+                        ticket.flight_date,
+                        ticket.departure_time.format("%H:%M"),
+                        seat
+                    ),
+                }
+            })
+            .into_iter()
+            .collect())
+    }
+
+    async fn cancellation_notice(
+        &self,
+        customer_id: i32,
+        flight_number: i32,
+        flight_date: NaiveDate,
+    ) -> AppResult<Vec<EmailMessage>> {
+        let customer = sqlx::query!(
+            r#"
+            SELECT name, email as "email!"
+            FROM customer_info
+            WHERE id = ? AND email IS NOT NULL AND email_verified
+            "#,
+            customer_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(customer
+            .map(|customer| EmailMessage {
+                to_name: customer.name.clone(),
+                to_address: customer.email,
+                subject: format!(
+                    "Booking cancelled: flight {} on {}",
+                    flight_number, flight_date
+                ),
+                body: format!(
+                    "Dear {},\n\n\
+                     your ticket for flight {} on {} has been cancelled and its seat \
+                     released.\n",
+                    customer.name, flight_number, flight_date
+                ),
+            })
+            .into_iter()
+            .collect())
+    }
+
+    async fn flight_cancellation_alerts(&self, flight_id: i32) -> AppResult<Vec<EmailMessage>> {
+        let passengers = self.passengers(flight_id).await?;
+        Ok(passengers
+            .into_iter()
+            .map(|passenger| EmailMessage {
+                subject: format!(
+                    "Flight {} on {} is cancelled",
+                    passenger.flight_number, passenger.flight_date
+                ),
+                body: format!(
+                    "Dear {},\n\n\
+                     we are sorry to tell you that flight {} from {} to {} on {} is \
+                     cancelled. We will rebook you on the next available flight and \
+                     send you the new details.\n",
+                    passenger.name,
+                    passenger.flight_number,
+                    passenger.departure_city,
+                    passenger.destination_city,
+                    passenger.flight_date
+                ),
+                to_name: passenger.name,
+                to_address: passenger.email,
+            })
+            .collect())
+    }
+
+    async fn delay_alerts(
+        &self,
+        flight_id: i32,
+        delay_minutes: i32,
+        reason: Option<&str>,
+    ) -> AppResult<Vec<EmailMessage>> {
+        let passengers = self.passengers(flight_id).await?;
+        Ok(passengers
+            .into_iter()
+            .map(|passenger| {
+                let departure =
+                    passenger.departure_time + chrono::Duration::minutes(delay_minutes as i64);
+                let reason = reason
+                    .map(|reason| format!("Reason: {}\n", reason))
+                    .unwrap_or_default();
+                EmailMessage {
+                    subject: format!(
+                        "Flight {} on {} is delayed",
+                        passenger.flight_number, passenger.flight_date
+                    ),
+                    body: format!(
+                        "Dear {},\n\n\
+                         flight {} from {} to {} on {} is delayed by {} minutes. The new \
+                         departure time is {} local time.\n{}",
+                        passenger.name,
+                        passenger.flight_number,
+                        passenger.departure_city,
+                        passenger.destination_city,
+                        passenger.flight_date,
+                        delay_minutes,
+                        departure.format("%H:%M"),
+                        reason
+                    ),
+                    to_name: passenger.name,
+                    to_address: passenger.email,
+                }
+            })
+            .collect())
+    }
+
+    // Passengers of a flight with a verified email address
+    async fn passengers(&self, flight_id: i32) -> AppResult<Vec<Passenger>> {
+        let passengers = sqlx::query_as!(
+            Passenger,
+            r#"
+            SELECT
+                c.name,
+                c.email as "email!",
+                t.flight_number,
+                t.flight_date as "flight_date: NaiveDate",
+                fr.departure_city,
+                fr.destination_city,
+                fr.departure_time as "departure_time: NaiveTime"
+            FROM ticket t
+            JOIN customer_info c ON t.customer_id = c.id
+            JOIN flight_route fr ON t.flight_number = fr.flight_number
+            WHERE t.flight_id = ? AND c.email IS NOT NULL AND c.email_verified
+            ORDER BY t.id
+            "#,
+            flight_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(passengers)
+    }
+}
+
+struct Passenger {
+    name: String,
+    email: String,
+    flight_number: i32,
+    flight_date: NaiveDate,
+    departure_city: String,
+    destination_city: String,
+    departure_time: NaiveTime,
+}
@@ -0,0 +1,218 @@
+use crate::models::ticket::{SeatBookingRequest, TicketBookingRequest};
+use crate::services::payment_service::PaymentService;
+use crate::services::ticket_service::TicketService;
+use crate::utils::error::AppResult;
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+// Default location of the operation log
+pub const DEFAULT_OPERATION_LOG_PATH: &str = "operation_log.jsonl";
+
+// Booking operation with the parameters needed to perform it again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Operation {
+    BookTicket {
+        user_id: i32,
+        request: TicketBookingRequest,
+    },
+    BookSeat {
+        user_id: i32,
+        request: SeatBookingRequest,
+    },
+    // Ticket ids are not stable across a restore, so released tickets are
+    // identified by their customer and flight
+    ReleaseTicket {
+        customer_id: i32,
+        flight_number: i32,
+        flight_date: NaiveDate,
+    },
+    // Booking ids are not stable either, so paid bookings are identified by their
+    // customer and first flight. The provider is not charged again on replay.
+    ConfirmPayment {
+        customer_id: i32,
+        flight_number: i32,
+        flight_date: NaiveDate,
+        provider: String,
+        provider_reference: Option<String>,
+    },
+    PayWithPoints {
+        customer_id: i32,
+        flight_number: i32,
+        flight_date: NaiveDate,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum OperationOutcome {
+    Succeeded,
+    Failed { error: String },
+}
+
+// One line of the operation log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationRecord {
+    pub recorded_at: NaiveDateTime,
+    pub operation: Operation,
+    pub outcome: OperationOutcome,
+}
+
+// Append-only log of booking operations, one JSON record per line. Replaying the log
+// against a restored database snapshot recovers the bookings made after the snapshot.
+#[derive(Clone)]
+pub struct OperationLog {
+    file: Arc<Mutex<File>>,
+    // Second handle of the file, synced without holding the lock so bookings only
+    // wait for each other while their line is written
+    sync_file: Arc<File>,
+}
+
+impl OperationLog {
+    pub async fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        let sync_file = file.try_clone().await?;
+        Ok(OperationLog {
+            file: Arc::new(Mutex::new(file)),
+            sync_file: Arc::new(sync_file),
+        })
+    }
+
+    pub async fn append(
+        &self,
+        operation: Operation,
+        outcome: OperationOutcome,
+    ) -> std::io::Result<()> {
+        let record = OperationRecord {
+            recorded_at: chrono::Utc::now().naive_utc(),
+            operation,
+            outcome,
+        };
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+
+        {
+            let mut file = self.file.lock().await;
+            file.write_all(line.as_bytes()).await?;
+            file.flush().await?;
+        }
+        // The log is only useful for recovery if it survives a crash. Syncing also
+        // writes out the lines appended meanwhile, so concurrent bookings share syncs.
+        self.sync_file.sync_data().await
+    }
+
+    // Read all records of a log. A truncated last line, left by a crash while
+    // writing, is ignored.
+    pub async fn read(path: impl AsRef<Path>) -> std::io::Result<Vec<OperationRecord>> {
+        let content = tokio::fs::read_to_string(path).await?;
+        let lines: Vec<&str> = content.lines().filter(|line| !line.is_empty()).collect();
+
+        let mut records = Vec::new();
+        for (index, line) in lines.iter().enumerate() {
+            match serde_json::from_str(line) {
+                Ok(record) => records.push(record),
+                Err(_) if index == lines.len() - 1 => break,
+                Err(e) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("Invalid record on line {}: {}", index + 1, e),
+                    ))
+                }
+            }
+        }
+        Ok(records)
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReplaySummary {
+    pub replayed: usize,
+    // Operations that failed originally or were recorded before the snapshot
+    pub skipped: usize,
+    pub failed: Vec<String>,
+}
+
+// Perform the successful operations recorded at or after `since` again, in log order.
+// The services should not write to an operation log themselves.
+pub async fn replay(
+    ticket_service: &TicketService,
+    payment_service: &PaymentService,
+    records: &[OperationRecord],
+    since: Option<NaiveDateTime>,
+) -> AppResult<ReplaySummary> {
+    let mut summary = ReplaySummary::default();
+
+    for record in records {
+        if record.outcome != OperationOutcome::Succeeded
+            || since.map_or(false, |since| record.recorded_at < since)
+        {
+            summary.skipped += 1;
+            continue;
+        }
+
+        let result = match &record.operation {
+            Operation::BookTicket { user_id, request } => ticket_service
+                .book_ticket(*user_id, request.clone())
+                .await
+                .map(|_| ()),
+            Operation::BookSeat { user_id, request } => ticket_service
+                .book_seat_for_ticket(*user_id, request.clone())
+                .await
+                .map(|_| ()),
+            Operation::ReleaseTicket {
+                customer_id,
+                flight_number,
+                flight_date,
+            } => {
+                ticket_service
+                    .release_ticket_for_flight(*customer_id, *flight_number, *flight_date)
+                    .await
+            }
+            Operation::ConfirmPayment {
+                customer_id,
+                flight_number,
+                flight_date,
+                provider,
+                provider_reference,
+            } => {
+                payment_service
+                    .restore_payment(
+                        *customer_id,
+                        *flight_number,
+                        *flight_date,
+                        provider,
+                        provider_reference.as_deref(),
+                    )
+                    .await
+            }
+            Operation::PayWithPoints {
+                customer_id,
+                flight_number,
+                flight_date,
+            } => {
+                payment_service
+                    .restore_points_payment(*customer_id, *flight_number, *flight_date)
+                    .await
+            }
+        };
+
+        match result {
+            Ok(()) => summary.replayed += 1,
+            Err(e) => summary.failed.push(format!(
+                "{} {:?}: {}",
+                record.recorded_at, record.operation, e
+            )),
+        }
+    }
+
+    Ok(summary)
+}
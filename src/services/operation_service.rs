@@ -0,0 +1,97 @@
+use crate::models::operation::{
+    OperationId, OperationKind, OperationStatusResponse, MAX_WAIT_SECONDS,
+};
+use crate::utils::error::{AppError, AppResult};
+use sqlx::MySqlPool;
+use std::time::Duration;
+use tokio::time::Instant;
+
+// How often a waiting request looks at the operation again
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone)]
+pub struct OperationService {
+    pool: MySqlPool,
+}
+
+impl OperationService {
+    pub fn new(pool: MySqlPool) -> Self {
+        OperationService { pool }
+    }
+
+    // Current status of an operation of the customer
+    pub async fn status(
+        &self,
+        user_id: i32,
+        operation_id: &str,
+    ) -> AppResult<OperationStatusResponse> {
+        let operation_id = OperationId::parse(operation_id).ok_or_else(|| {
+            AppError::ValidationError(
+                "Operation ids look like booking-<booking_id> or refund-<refund_id>".into(),
+            )
+        })?;
+
+        let (customer_id, status, done) = match operation_id.kind {
+            OperationKind::Booking => {
+                let booking = sqlx::query!(
+                    "SELECT customer_id, status FROM booking WHERE id = ?",
+                    operation_id.id
+                )
+                .fetch_optional(&self.pool)
+                .await?;
+                match booking {
+                    Some(booking) => {
+                        let done = booking.status != "PENDING_PAYMENT";
+                        (booking.customer_id, booking.status, done)
+                    }
+                    None => return Err(AppError::NotFound("Operation not found".into())),
+                }
+            }
+            OperationKind::Refund => {
+                let refund = sqlx::query!(
+                    "SELECT customer_id, status FROM refund WHERE id = ?",
+                    operation_id.id
+                )
+                .fetch_optional(&self.pool)
+                .await?;
+                match refund {
+                    Some(refund) => {
+                        let done = refund.status == "REFUNDED" || refund.status == "FAILED";
+                        (refund.customer_id, refund.status, done)
+                    }
+                    None => return Err(AppError::NotFound("Operation not found".into())),
+                }
+            }
+        };
+        // Do not reveal operations of other customers
+        if customer_id != user_id {
+            return Err(AppError::NotFound("Operation not found".into()));
+        }
+
+        Ok(OperationStatusResponse {
+            operation_id: operation_id.to_string(),
+            kind: operation_id.kind,
+            status,
+            done,
+        })
+    }
+
+    // Wait until the operation is done or the timeout passes, whichever comes first,
+    // and return its status then. The timeout is capped at MAX_WAIT_SECONDS.
+    pub async fn wait(
+        &self,
+        user_id: i32,
+        operation_id: &str,
+        timeout: Duration,
+    ) -> AppResult<OperationStatusResponse> {
+        let deadline = Instant::now() + timeout.min(Duration::from_secs(MAX_WAIT_SECONDS));
+        loop {
+            let response = self.status(user_id, operation_id).await?;
+            let now = Instant::now();
+            if response.done || now >= deadline {
+                return Ok(response);
+            }
+            tokio::time::sleep(POLL_INTERVAL.min(deadline - now)).await;
+        }
+    }
+}
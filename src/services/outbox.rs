@@ -0,0 +1,125 @@
+use crate::services::event_bus::{DomainEvent, EventBus};
+use crate::utils::error::AppResult;
+use sqlx::types::Json;
+use sqlx::{MySql, MySqlPool, Transaction};
+use std::time::Duration;
+
+// Events published per dispatch, the rest wait for the next one
+const DISPATCH_BATCH_SIZE: i64 = 100;
+
+// Days published events are kept for troubleshooting before they are deleted
+const PUBLISHED_RETENTION_DAYS: i64 = 7;
+
+// Record an event in the transaction of the change it describes. It is published
+// when the transaction commits, and never when it is rolled back.
+pub async fn enqueue(tx: &mut Transaction<'_, MySql>, event: &DomainEvent) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO outbox_event (event_type, payload, created_at)
+        VALUES (?, ?, UTC_TIMESTAMP())
+        "#,
+        event.name(),
+        Json(event)
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+// Publishes the committed outbox events to the event bus, in the order they were written.
+// An event is published at least once: when marking it as published fails, it is
+// published again on the next dispatch.
+#[derive(Clone)]
+pub struct OutboxDispatcher {
+    pool: MySqlPool,
+    event_bus: EventBus,
+}
+
+impl OutboxDispatcher {
+    pub fn new(pool: MySqlPool, event_bus: EventBus) -> Self {
+        OutboxDispatcher { pool, event_bus }
+    }
+
+    // Publish the pending events, returning how many were read from the outbox
+    pub async fn dispatch_pending(&self) -> AppResult<usize> {
+        let mut tx = self.pool.begin().await?;
+        // Other instances skip the locked rows instead of publishing them twice
+        let events = sqlx::query!(
+            r#"
+            SELECT id, event_type, payload as "payload: Json<serde_json::Value>"
+            FROM outbox_event
+            WHERE published_at IS NULL
+            ORDER BY id
+            LIMIT ?
+            FOR UPDATE SKIP LOCKED
+            "#,
+            DISPATCH_BATCH_SIZE
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for event in &events {
+            match serde_json::from_value::<DomainEvent>(event.payload.0.clone()) {
+                Ok(payload) => self.event_bus.publish(payload),
+                // Left unpublished it would be retried forever, so it is only logged
+                Err(e) => tracing::error!(
+                    id = event.id,
+                    event_type = %event.event_type,
+                    error = %e,
+                    "dropping unreadable outbox event"
+                ),
+            }
+            sqlx::query!(
+                "UPDATE outbox_event SET published_at = UTC_TIMESTAMP() WHERE id = ?",
+                event.id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(events.len())
+    }
+
+    // Delete the events published more than the retention period ago
+    pub async fn purge_published(&self) -> AppResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM outbox_event
+            WHERE published_at < UTC_TIMESTAMP() - INTERVAL ? DAY
+            "#,
+            PUBLISHED_RETENTION_DAYS
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    // Poll the outbox in the background. A full batch is followed by the next one right
+    // away so a burst of events does not wait for several periods.
+    pub fn spawn_dispatcher(&self, period: Duration) {
+        let dispatcher = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            let mut purged_at = tokio::time::Instant::now();
+            loop {
+                interval.tick().await;
+                loop {
+                    match dispatcher.dispatch_pending().await {
+                        Ok(published) if published as i64 == DISPATCH_BATCH_SIZE => continue,
+                        Ok(_) => break,
+                        Err(e) => {
+                            tracing::error!(error = %e, "failed to dispatch outbox events");
+                            break;
+                        }
+                    }
+                }
+                if purged_at.elapsed() >= Duration::from_secs(60 * 60) {
+                    purged_at = tokio::time::Instant::now();
+                    if let Err(e) = dispatcher.purge_published().await {
+                        tracing::error!(error = %e, "failed to purge published outbox events");
+                    }
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,357 @@
+use crate::models::flight::FlightStatus;
+use crate::models::partner::{
+    ChangeCursor, CreatePartnerKeyRequest, DayAvailability, FlightChange, PartnerKeyResponse,
+    RouteAvailabilityQuery, RouteAvailabilityResponse, MAX_AVAILABILITY_DAYS,
+    MAX_CHANGES_PER_PAGE,
+};
+use crate::utils::error::{AppError, AppResult};
+use crate::utils::ndjson::RowSink;
+use crate::utils::region::{RegionFilter, DEFAULT_DATA_REGION};
+use crate::utils::tunables::{SharedTunables, Tunables};
+use chrono::{NaiveDate, NaiveDateTime};
+use rocket::futures::TryStreamExt;
+use sha2::{Digest, Sha256};
+use sqlx::MySqlPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::instrument;
+use validator::Validate;
+
+// Time an availability answer is served from the cache
+pub const DEFAULT_AVAILABILITY_CACHE_TTL: Duration = Duration::from_secs(60);
+
+// Changes younger than this are left for the next sync. A transaction still running
+// when the feed is read may commit a change stamped before the cursor moved past it.
+pub const DEFAULT_CHANGE_SETTLE_TIME: Duration = Duration::from_secs(2);
+
+// Partner identified by the API key of the request
+#[derive(Debug, Clone)]
+pub struct Partner {
+    pub key_id: i32,
+    pub partner_name: String,
+}
+
+type AvailabilityKey = (String, String, NaiveDate, u32);
+
+#[derive(Clone)]
+pub struct PartnerService {
+    pool: MySqlPool,
+    // Cache TTL and change settle time
+    tunables: SharedTunables,
+    cache: Arc<Mutex<HashMap<AvailabilityKey, (Instant, RouteAvailabilityResponse)>>>,
+    // Bookings of these data regions are left out of the feeds
+    excluded_regions: RegionFilter,
+}
+
+impl PartnerService {
+    pub fn new(pool: MySqlPool) -> Self {
+        PartnerService {
+            pool,
+            tunables: SharedTunables::default(),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            excluded_regions: RegionFilter::default(),
+        }
+    }
+
+    // Keep the bookings of the given data regions out of the feeds
+    pub fn with_excluded_regions(mut self, regions: &[String]) -> Self {
+        self.excluded_regions = RegionFilter::default().excluding(regions);
+        self
+    }
+
+    // Follow the tunables, which the configuration reload may change while running
+    pub fn with_tunables(mut self, tunables: SharedTunables) -> Self {
+        self.tunables = tunables;
+        self
+    }
+
+    pub fn with_cache_ttl(mut self, cache_ttl: Duration) -> Self {
+        let tunables = Tunables {
+            availability_cache_ttl: cache_ttl,
+            ..(*self.tunables.current()).clone()
+        };
+        self.tunables = SharedTunables::new(tunables);
+        self
+    }
+
+    pub fn with_change_settle_time(mut self, change_settle_time: Duration) -> Self {
+        let tunables = Tunables {
+            change_settle_time,
+            ..(*self.tunables.current()).clone()
+        };
+        self.tunables = SharedTunables::new(tunables);
+        self
+    }
+
+    // The same feeds against another database, e.g. the sandbox, with a cache of its own
+    pub fn for_database(&self, pool: MySqlPool) -> Self {
+        PartnerService {
+            tunables: self.tunables.clone(),
+            excluded_regions: self.excluded_regions.clone(),
+            ..PartnerService::new(pool)
+        }
+    }
+
+    // Create a key for a partner. Keys have the form "<id>.<secret>" and only the
+    // hash of the secret is stored.
+    pub async fn create_api_key(
+        &self,
+        request: CreatePartnerKeyRequest,
+    ) -> AppResult<PartnerKeyResponse> {
+        request
+            .validate()
+            .map_err(|e| AppError::ValidationError(format!("{:?}", e)))?;
+
+        let secret = uuid::Uuid::new_v4().simple().to_string();
+        let key_id = sqlx::query!(
+            r#"
+            INSERT INTO partner_api_key (partner_name, key_hash, created_at)
+            VALUES (?, ?, UTC_TIMESTAMP())
+            "#,
+            request.partner_name,
+            hash_secret(&secret)
+        )
+        .execute(&self.pool)
+        .await?
+        .last_insert_id() as i32;
+
+        Ok(PartnerKeyResponse {
+            key_id,
+            partner_name: request.partner_name,
+            api_key: format!("{}.{}", key_id, secret),
+        })
+    }
+
+    pub async fn revoke_api_key(&self, key_id: i32) -> AppResult<()> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE partner_api_key SET revoked_at = UTC_TIMESTAMP()
+            WHERE id = ? AND revoked_at IS NULL
+            "#,
+            key_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Active API key not found".into()));
+        }
+        Ok(())
+    }
+
+    // Partner owning the key, or None when the key is unknown or revoked
+    pub async fn authenticate(&self, api_key: &str) -> AppResult<Option<Partner>> {
+        let Some((key_id, secret)) = api_key
+            .split_once('.')
+            .and_then(|(id, secret)| Some((id.parse::<i32>().ok()?, secret)))
+        else {
+            return Ok(None);
+        };
+
+        let key = sqlx::query!(
+            r#"
+            SELECT partner_name, key_hash FROM partner_api_key
+            WHERE id = ? AND revoked_at IS NULL
+            "#,
+            key_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(key
+            .filter(|key| key.key_hash == hash_secret(secret))
+            .map(|key| Partner {
+                key_id,
+                partner_name: key.partner_name,
+            }))
+    }
+
+    // Seats left per day on a route, for every day of the period. Answers are cached
+    // for a short time as partners poll the same routes over and over.
+    #[instrument(skip(self))]
+    pub async fn route_availability(
+        &self,
+        query: RouteAvailabilityQuery,
+    ) -> AppResult<RouteAvailabilityResponse> {
+        if query.days == 0 || query.days > MAX_AVAILABILITY_DAYS {
+            return Err(AppError::ValidationError(format!(
+                "days must be between 1 and {}",
+                MAX_AVAILABILITY_DAYS
+            )));
+        }
+
+        let cache_ttl = self.tunables.current().availability_cache_ttl;
+        let key = (
+            query.departure_city.clone(),
+            query.destination_city.clone(),
+            query.start_date,
+            query.days,
+        );
+        if let Some((cached_at, response)) = self.cache.lock().unwrap().get(&key) {
+            if cached_at.elapsed() < cache_ttl {
+                return Ok(response.clone());
+            }
+        }
+
+        let end_date = query.start_date + chrono::Duration::days(query.days as i64 - 1);
+        // Same flights as a search would show, counted per day in the database
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                f.flight_date as "flight_date: NaiveDate",
+                COUNT(*) as "flights!: i64",
+                CAST(COALESCE(SUM(GREATEST(f.available_tickets, 0)), 0) AS SIGNED)
+                    as "available_seats!: i64"
+            FROM flight f
+            JOIN flight_route fr ON f.flight_number = fr.flight_number
+            WHERE fr.departure_city = ?
+            AND fr.destination_city = ?
+            AND f.flight_date BETWEEN ? AND ?
+            AND f.status NOT IN ('CANCELLED', 'DEPARTED')
+            AND f.closed_at IS NULL
+            AND f.flight_date >= fr.start_date
+            AND (fr.end_date IS NULL OR f.flight_date <= fr.end_date)
+            AND INSTR(fr.operating_days, WEEKDAY(f.flight_date) + 1) > 0
+            GROUP BY f.flight_date
+            "#,
+            query.departure_city,
+            query.destination_city,
+            query.start_date,
+            end_date
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let by_date: HashMap<NaiveDate, (i64, i64)> = rows
+            .into_iter()
+            .map(|row| (row.flight_date, (row.flights, row.available_seats)))
+            .collect();
+        let days = query
+            .start_date
+            .iter_days()
+            .take(query.days as usize)
+            .map(|date| {
+                let (flights, available_seats) = by_date.get(&date).copied().unwrap_or((0, 0));
+                DayAvailability {
+                    date,
+                    flights,
+                    available_seats,
+                }
+            })
+            .collect();
+
+        let response = RouteAvailabilityResponse {
+            departure_city: query.departure_city,
+            destination_city: query.destination_city,
+            start_date: query.start_date,
+            end_date,
+            days,
+        };
+
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|_, (cached_at, _)| cached_at.elapsed() < cache_ttl);
+        cache.insert(key, (Instant::now(), response.clone()));
+        Ok(response)
+    }
+
+    // Stream the flights whose inventory changed after the cursor, oldest change first.
+    // A flight changes when its row or one of its seats is updated, both tables keep
+    // updated_at current. Without a cursor every flight is returned. Tickets of bookings
+    // in excluded data regions are not counted as sold.
+    pub async fn export_changes(
+        &self,
+        since: Option<ChangeCursor>,
+        limit: u32,
+        sink: RowSink<FlightChange>,
+    ) -> AppResult<()> {
+        if limit == 0 || limit > MAX_CHANGES_PER_PAGE {
+            return Err(AppError::ValidationError(format!(
+                "limit must be between 1 and {}",
+                MAX_CHANGES_PER_PAGE
+            )));
+        }
+
+        // Unix epoch, before any change
+        let since = since.unwrap_or(ChangeCursor {
+            changed_at: NaiveDateTime::default(),
+            flight_id: 0,
+        });
+        let settle_micros = self.tunables.current().change_settle_time.as_micros() as i64;
+        let mut rows = sqlx::query!(
+            r#"
+            SELECT
+                f.flight_id,
+                f.flight_number,
+                f.flight_date as "flight_date: NaiveDate",
+                f.status as "status: FlightStatus",
+                f.available_tickets,
+                f.version,
+                c.changed_at as "changed_at!: NaiveDateTime",
+                (SELECT COUNT(*) FROM seat_info s
+                 WHERE s.flight_id = f.flight_id AND s.seat_status = 'AVAILABLE')
+                    as "available_seats!: i64",
+                (SELECT COUNT(*) FROM ticket t
+                 LEFT JOIN booking b ON t.booking_id = b.id
+                 WHERE t.flight_id = f.flight_id
+                 AND FIND_IN_SET(COALESCE(b.data_region, ?), ?) = 0)
+                    as "tickets_sold!: i64"
+            FROM (
+                SELECT
+                    f.flight_id,
+                    GREATEST(
+                        f.updated_at,
+                        COALESCE(
+                            (SELECT MAX(s.updated_at) FROM seat_info s
+                             WHERE s.flight_id = f.flight_id),
+                            f.updated_at
+                        )
+                    ) as changed_at
+                FROM flight f
+            ) c
+            JOIN flight f ON f.flight_id = c.flight_id
+            WHERE (c.changed_at > ? OR (c.changed_at = ? AND c.flight_id > ?))
+            AND c.changed_at < NOW(6) - INTERVAL ? MICROSECOND
+            ORDER BY c.changed_at, c.flight_id
+            LIMIT ?
+            "#,
+            DEFAULT_DATA_REGION,
+            self.excluded_regions.exclude_list(),
+            since.changed_at,
+            since.changed_at,
+            since.flight_id,
+            settle_micros,
+            limit
+        )
+        .fetch(&self.pool);
+
+        while let Some(row) = rows.try_next().await? {
+            let cursor = ChangeCursor {
+                changed_at: row.changed_at,
+                flight_id: row.flight_id,
+            };
+            let change = FlightChange {
+                cursor: cursor.to_string(),
+                flight_id: row.flight_id,
+                flight_number: row.flight_number,
+                flight_date: row.flight_date,
+                status: row.status,
+                available_tickets: row.available_tickets,
+                available_seats: row.available_seats,
+                tickets_sold: row.tickets_sold,
+                version: row.version,
+                changed_at: row.changed_at,
+            };
+            if !sink.send(change).await {
+                // The partner went away
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
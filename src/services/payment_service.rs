@@ -0,0 +1,1276 @@
+use crate::models::fare::{refund_share, FareClass};
+use crate::models::flight::departure_utc;
+use crate::models::loyalty::{points_for_amount, LoyaltyEntryType, LOYALTY_PROVIDER};
+use crate::models::payment::{
+    fare_hold_fee, ConfirmPaymentRequest, FareHoldRequest, FareHoldResponse, PaymentCapture,
+    PaymentResponse, PaymentStatus, RefundExecution, RefundResponse, RefundStatus,
+    SeatChangeRequest, SeatChangeResponse, SeatSelectionRequest, SeatSelectionResponse,
+    TicketCancellationResponse, DEFAULT_CURRENCY, FARE_HOLD_OPTIONS,
+};
+use crate::models::ticket::{SeatBookingRequest, SeatHoldRequest};
+use crate::services::loyalty_service::{self, LedgerChange};
+use crate::services::operation_log::{Operation, OperationLog, OperationOutcome};
+use crate::services::promo_code_service;
+use crate::services::ticket_service::TicketService;
+use crate::utils::error::{AppError, AppResult};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, SubsecRound};
+use rust_decimal::Decimal;
+use sqlx::MySqlPool;
+use std::sync::Arc;
+use std::time::Duration;
+
+// A payment provider capturing funds for a payment
+#[rocket::async_trait]
+pub trait PaymentProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Capture the payment, returning the provider's reference on success
+    async fn capture(&self, payment: &PaymentCapture) -> Result<String, String>;
+
+    // Pay back part or all of a captured payment, returning the provider's reference
+    // of the refund on success
+    async fn refund(&self, refund: &RefundExecution) -> Result<String, String>;
+}
+
+// Payment provider accepting every token except "declined", for development and tests
+pub struct MockPaymentProvider;
+
+#[rocket::async_trait]
+impl PaymentProvider for MockPaymentProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn capture(&self, payment: &PaymentCapture) -> Result<String, String> {
+        if payment.payment_token == "declined" {
+            return Err("Card declined".to_string());
+        }
+        Ok(format!("mock-{}", payment.payment_id))
+    }
+
+    async fn refund(&self, refund: &RefundExecution) -> Result<String, String> {
+        Ok(format!("mock-refund-{}", refund.refund_id))
+    }
+}
+
+#[derive(Clone)]
+pub struct PaymentService {
+    pool: MySqlPool,
+    provider: Arc<dyn PaymentProvider>,
+    operation_log: Option<OperationLog>,
+}
+
+impl PaymentService {
+    pub fn new(pool: MySqlPool, provider: Arc<dyn PaymentProvider>) -> Self {
+        PaymentService {
+            pool,
+            provider,
+            operation_log: None,
+        }
+    }
+
+    // Log payment confirmations for replay, next to the bookings they pay
+    pub fn with_operation_log(mut self, operation_log: OperationLog) -> Self {
+        self.operation_log = Some(operation_log);
+        self
+    }
+
+    // Capture the pending payment of a booking and confirm the booking
+    pub async fn confirm_payment(
+        &self,
+        user_id: i32,
+        booking_id: i32,
+        request: ConfirmPaymentRequest,
+    ) -> AppResult<PaymentResponse> {
+        let result = self
+            .capture_booking_payment(user_id, booking_id, request)
+            .await;
+        self.record(
+            user_id,
+            booking_id,
+            &result,
+            |flight_number, flight_date, payment| Operation::ConfirmPayment {
+                customer_id: user_id,
+                flight_number,
+                flight_date,
+                provider: self.provider.name().to_string(),
+                provider_reference: payment.and_then(|p| p.provider_reference.clone()),
+            },
+        )
+        .await;
+        result
+    }
+
+    async fn capture_booking_payment(
+        &self,
+        user_id: i32,
+        booking_id: i32,
+        request: ConfirmPaymentRequest,
+    ) -> AppResult<PaymentResponse> {
+        let payment = sqlx::query!(
+            r#"
+            SELECT
+                p.id,
+                p.amount,
+                p.currency,
+                p.status as "status: PaymentStatus",
+                p.expires_at as "expires_at: NaiveDateTime",
+                b.customer_id
+            FROM payment p
+            JOIN booking b ON p.booking_id = b.id
+            WHERE p.booking_id = ?
+            ORDER BY p.id DESC
+            LIMIT 1
+            "#,
+            booking_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let payment = match payment {
+            // Do not reveal bookings of other customers
+            Some(payment) if payment.customer_id == user_id => payment,
+            _ => return Err(AppError::NotFound("Booking not found".into())),
+        };
+
+        if payment.status != PaymentStatus::Pending {
+            return Err(AppError::Conflict(format!(
+                "Payment is already {}",
+                payment.status
+            )));
+        }
+        if payment.expires_at < chrono::Utc::now().naive_utc() {
+            return Err(AppError::Conflict("The payment window has expired".into()));
+        }
+
+        // Claim the payment so that it cannot be captured twice or expire meanwhile
+        let claimed = sqlx::query!(
+            r#"
+            UPDATE payment
+            SET status = 'PROCESSING'
+            WHERE id = ? AND status = 'PENDING'
+            "#,
+            payment.id
+        )
+        .execute(&self.pool)
+        .await?;
+        if claimed.rows_affected() == 0 {
+            return Err(AppError::Conflict("Payment is already being processed".into()));
+        }
+
+        let capture = PaymentCapture {
+            payment_id: payment.id,
+            amount: payment.amount,
+            currency: payment.currency.clone(),
+            payment_token: request.payment_token,
+        };
+
+        let provider_reference = match self.provider.capture(&capture).await {
+            Ok(reference) => reference,
+            Err(reason) => {
+                // Let the customer retry with another payment method
+                sqlx::query!(
+                    "UPDATE payment SET status = 'PENDING' WHERE id = ?",
+                    payment.id
+                )
+                .execute(&self.pool)
+                .await?;
+                return Err(AppError::Unprocessable(format!("Payment failed: {}", reason)));
+            }
+        };
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            UPDATE payment
+            SET status = 'CAPTURED',
+                provider = ?,
+                provider_reference = ?,
+                captured_at = UTC_TIMESTAMP()
+            WHERE id = ?
+            "#,
+            self.provider.name(),
+            provider_reference,
+            payment.id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE booking SET status = 'CONFIRMED' WHERE id = ?",
+            booking_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(PaymentResponse {
+            booking_id,
+            payment_id: payment.id,
+            amount: payment.amount,
+            currency: payment.currency,
+            status: PaymentStatus::Captured,
+            provider_reference: Some(provider_reference),
+        })
+    }
+
+    // Pay the pending payment of a booking with loyalty points and confirm the booking.
+    // The whole amount is paid with points, in the default currency only.
+    pub async fn pay_with_points(
+        &self,
+        user_id: i32,
+        booking_id: i32,
+    ) -> AppResult<PaymentResponse> {
+        let result = self.redeem_points_for_booking(user_id, booking_id).await;
+        self.record(
+            user_id,
+            booking_id,
+            &result,
+            |flight_number, flight_date, _| Operation::PayWithPoints {
+                customer_id: user_id,
+                flight_number,
+                flight_date,
+            },
+        )
+        .await;
+        result
+    }
+
+    async fn redeem_points_for_booking(
+        &self,
+        user_id: i32,
+        booking_id: i32,
+    ) -> AppResult<PaymentResponse> {
+        let mut tx = self.pool.begin().await?;
+        let payment = sqlx::query!(
+            r#"
+            SELECT
+                p.id,
+                p.amount,
+                p.currency,
+                p.status as "status: PaymentStatus",
+                p.expires_at as "expires_at: NaiveDateTime",
+                b.customer_id
+            FROM payment p
+            JOIN booking b ON p.booking_id = b.id
+            WHERE p.booking_id = ?
+            ORDER BY p.id DESC
+            LIMIT 1
+            FOR UPDATE
+            "#,
+            booking_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let payment = match payment {
+            // Do not reveal bookings of other customers
+            Some(payment) if payment.customer_id == user_id => payment,
+            _ => return Err(AppError::NotFound("Booking not found".into())),
+        };
+
+        if payment.status != PaymentStatus::Pending {
+            return Err(AppError::Conflict(format!(
+                "Payment is already {}",
+                payment.status
+            )));
+        }
+        if payment.expires_at < chrono::Utc::now().naive_utc() {
+            return Err(AppError::Conflict("The payment window has expired".into()));
+        }
+        if payment.currency != DEFAULT_CURRENCY {
+            return Err(AppError::ValidationError(format!(
+                "Loyalty points only pay for fares in {}",
+                DEFAULT_CURRENCY
+            )));
+        }
+
+        let entry_id = loyalty_service::add_entry(
+            &mut tx,
+            LedgerChange {
+                customer_id: user_id,
+                entry_type: LoyaltyEntryType::Redeem,
+                points: -points_for_amount(payment.amount),
+                ticket_id: None,
+                booking_id: Some(booking_id),
+                description: format!("Booking {}", booking_id),
+            },
+        )
+        .await?;
+        let provider_reference = entry_id.to_string();
+
+        sqlx::query!(
+            r#"
+            UPDATE payment
+            SET status = 'CAPTURED',
+                provider = ?,
+                provider_reference = ?,
+                captured_at = UTC_TIMESTAMP()
+            WHERE id = ?
+            "#,
+            LOYALTY_PROVIDER,
+            provider_reference,
+            payment.id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE booking SET status = 'CONFIRMED' WHERE id = ?",
+            booking_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(PaymentResponse {
+            booking_id,
+            payment_id: payment.id,
+            amount: payment.amount,
+            currency: payment.currency,
+            status: PaymentStatus::Captured,
+            provider_reference: Some(provider_reference),
+        })
+    }
+
+    // Log a payment of a booking of the customer. Booking ids are not stable across a
+    // restore, so the booking is named by its first flight in the log.
+    async fn record(
+        &self,
+        user_id: i32,
+        booking_id: i32,
+        result: &AppResult<PaymentResponse>,
+        operation: impl FnOnce(i32, NaiveDate, Option<&PaymentResponse>) -> Operation,
+    ) {
+        let Some(operation_log) = &self.operation_log else {
+            return;
+        };
+        let first_flight = sqlx::query!(
+            r#"
+            SELECT t.flight_number, t.flight_date as "flight_date: NaiveDate"
+            FROM ticket t
+            JOIN booking b ON b.id = t.booking_id
+            WHERE b.id = ? AND b.customer_id = ?
+            ORDER BY t.id
+            LIMIT 1
+            "#,
+            booking_id,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await;
+        // Payments of bookings that cannot be named could not be replayed anyway
+        let flight = match first_flight {
+            Ok(Some(flight)) => flight,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::error!(error = %e, booking_id, "failed to log payment");
+                return;
+            }
+        };
+        let outcome = match result {
+            Ok(_) => OperationOutcome::Succeeded,
+            Err(e) => OperationOutcome::Failed {
+                error: e.to_string(),
+            },
+        };
+        let operation = operation(
+            flight.flight_number,
+            flight.flight_date,
+            result.as_ref().ok(),
+        );
+        if let Err(e) = operation_log.append(operation, outcome).await {
+            tracing::error!(error = %e, "failed to write operation log");
+        }
+    }
+
+    // Booking of the customer with a ticket for the flight whose payment is still
+    // pending, the latest one when there are several
+    async fn pending_booking_on(
+        &self,
+        customer_id: i32,
+        flight_number: i32,
+        flight_date: NaiveDate,
+    ) -> AppResult<i32> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT b.id
+            FROM booking b
+            JOIN ticket t ON t.booking_id = b.id
+            JOIN payment p ON p.booking_id = b.id
+            WHERE b.customer_id = ?
+            AND t.flight_number = ?
+            AND t.flight_date = ?
+            AND p.status = 'PENDING'
+            ORDER BY b.id DESC
+            LIMIT 1
+            "#,
+            customer_id,
+            flight_number,
+            flight_date
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "No unpaid booking of customer {} for flight {} on {}",
+                customer_id, flight_number, flight_date
+            ))
+        })
+    }
+
+    // Confirm again a payment captured before a database snapshot was restored. The
+    // provider already holds the funds, so nothing is captured.
+    pub async fn restore_payment(
+        &self,
+        customer_id: i32,
+        flight_number: i32,
+        flight_date: NaiveDate,
+        provider: &str,
+        provider_reference: Option<&str>,
+    ) -> AppResult<()> {
+        let booking_id = self
+            .pending_booking_on(customer_id, flight_number, flight_date)
+            .await?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            UPDATE payment
+            SET status = 'CAPTURED',
+                provider = ?,
+                provider_reference = ?,
+                captured_at = UTC_TIMESTAMP()
+            WHERE booking_id = ? AND status = 'PENDING'
+            "#,
+            provider,
+            provider_reference,
+            booking_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE booking SET status = 'CONFIRMED' WHERE id = ?",
+            booking_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Pay again with points for a booking paid with points before a database snapshot
+    // was restored
+    pub async fn restore_points_payment(
+        &self,
+        customer_id: i32,
+        flight_number: i32,
+        flight_date: NaiveDate,
+    ) -> AppResult<()> {
+        let booking_id = self
+            .pending_booking_on(customer_id, flight_number, flight_date)
+            .await?;
+        self.redeem_points_for_booking(customer_id, booking_id)
+            .await
+            .map(|_| ())
+    }
+
+    // Charge a fee to keep an unpaid booking for the given hours instead of the normal
+    // payment window. Its price and tickets stay as they are, and confirming the payment
+    // turns it into a confirmed booking as usual.
+    pub async fn hold_fare(
+        &self,
+        user_id: i32,
+        booking_id: i32,
+        request: FareHoldRequest,
+    ) -> AppResult<FareHoldResponse> {
+        let fee = fare_hold_fee(request.hours).ok_or_else(|| {
+            AppError::ValidationError(format!(
+                "A fare can be held for {} hours",
+                FARE_HOLD_OPTIONS
+                    .iter()
+                    .map(|(hours, _)| hours.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" or ")
+            ))
+        })?;
+
+        let payment = sqlx::query!(
+            r#"
+            SELECT
+                p.id,
+                p.currency,
+                p.status as "status: PaymentStatus",
+                p.expires_at as "expires_at: NaiveDateTime",
+                b.customer_id,
+                (SELECT COUNT(*) FROM fare_hold h WHERE h.booking_id = b.id) as "holds!: i64"
+            FROM payment p
+            JOIN booking b ON p.booking_id = b.id
+            WHERE p.booking_id = ?
+            ORDER BY p.id DESC
+            LIMIT 1
+            "#,
+            booking_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let payment = match payment {
+            // Do not reveal bookings of other customers
+            Some(payment) if payment.customer_id == user_id => payment,
+            _ => return Err(AppError::NotFound("Booking not found".into())),
+        };
+
+        if payment.status != PaymentStatus::Pending {
+            return Err(AppError::Conflict(format!(
+                "Payment is already {}",
+                payment.status
+            )));
+        }
+        // Whole seconds, as stored in the database
+        let now = chrono::Utc::now().naive_utc().trunc_subsecs(0);
+        if payment.expires_at < now {
+            return Err(AppError::Conflict("The payment window has expired".into()));
+        }
+        if payment.holds > 0 {
+            return Err(AppError::Conflict("The fare of this booking is already held".into()));
+        }
+        let expires_at = now + chrono::Duration::hours(request.hours);
+        let first_departure = sqlx::query!(
+            r#"
+            SELECT
+                t.flight_date as "flight_date: NaiveDate",
+                fr.departure_time as "departure_time: NaiveTime",
+                da.timezone as "departure_timezone?"
+            FROM ticket t
+            JOIN flight_route fr ON t.flight_number = fr.flight_number
+            LEFT JOIN airport da ON fr.departure_airport = da.code
+            WHERE t.booking_id = ?
+            "#,
+            booking_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|leg| {
+            departure_utc(
+                leg.flight_date,
+                leg.departure_time,
+                leg.departure_timezone.as_deref(),
+            )
+        })
+        .min();
+        if first_departure.map_or(false, |departure| expires_at >= departure) {
+            return Err(AppError::BadRequest(
+                "The hold would end after the first flight departs".into(),
+            ));
+        }
+
+        // Claim the payment like a confirmation does, so it cannot expire while the fee
+        // is charged
+        let claimed = sqlx::query!(
+            r#"
+            UPDATE payment
+            SET status = 'PROCESSING'
+            WHERE id = ? AND status = 'PENDING'
+            "#,
+            payment.id
+        )
+        .execute(&self.pool)
+        .await?;
+        if claimed.rows_affected() == 0 {
+            return Err(AppError::Conflict("Payment is already being processed".into()));
+        }
+
+        let capture = PaymentCapture {
+            payment_id: payment.id,
+            amount: fee,
+            currency: payment.currency.clone(),
+            payment_token: request.payment_token,
+        };
+        let provider_reference = match self.provider.capture(&capture).await {
+            Ok(reference) => reference,
+            Err(reason) => {
+                // The booking keeps its normal payment window
+                sqlx::query!(
+                    "UPDATE payment SET status = 'PENDING' WHERE id = ?",
+                    payment.id
+                )
+                .execute(&self.pool)
+                .await?;
+                return Err(AppError::Unprocessable(format!("Payment failed: {}", reason)));
+            }
+        };
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO fare_hold
+            (booking_id, hours, fee, currency, provider, provider_reference, created_at,
+                expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+            booking_id,
+            request.hours,
+            fee,
+            payment.currency,
+            self.provider.name(),
+            provider_reference,
+            now,
+            expires_at
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE payment SET status = 'PENDING', expires_at = ? WHERE id = ?",
+            expires_at,
+            payment.id
+        )
+        .execute(&mut *tx)
+        .await?;
+        // Seats the customer holds on the booked flights are kept as long as the fare
+        sqlx::query!(
+            r#"
+            UPDATE seat_info s
+            JOIN ticket t ON s.flight_id = t.flight_id AND s.held_by = t.customer_id
+            SET s.held_until = ?, s.version = s.version + 1
+            WHERE t.booking_id = ? AND s.seat_status = 'HELD'
+            "#,
+            expires_at,
+            booking_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(FareHoldResponse {
+            booking_id,
+            hours: request.hours,
+            fee,
+            currency: payment.currency,
+            expires_at,
+            provider_reference,
+        })
+    }
+
+    // Pick a seat for the customer's ticket, charging the price of the seat less what
+    // the ticket already paid for seats. The seat is held while the price is charged,
+    // and the charge is refunded when the seat cannot be taken after all.
+    pub async fn select_seat(
+        &self,
+        ticket_service: &TicketService,
+        user_id: i32,
+        request: SeatSelectionRequest,
+    ) -> AppResult<SeatSelectionResponse> {
+        let ticket_id = ticket_service
+            .ticket_on_flight(
+                user_id,
+                request.seat.flight_number,
+                request.seat.flight_date,
+            )
+            .await?;
+        self.select_ticket_seat(ticket_service, user_id, ticket_id, request)
+            .await
+    }
+
+    // Seat one ticket of the customer, the request naming the ticket's flight
+    async fn select_ticket_seat(
+        &self,
+        ticket_service: &TicketService,
+        user_id: i32,
+        ticket_id: i32,
+        request: SeatSelectionRequest,
+    ) -> AppResult<SeatSelectionResponse> {
+        let seat = request.seat;
+        let quote = ticket_service
+            .seat_price_quote(user_id, ticket_id, seat.seat_number)
+            .await?;
+        let due = (quote.seat_price - quote.paid).max(Decimal::ZERO);
+        let seat_number = seat.seat_number;
+
+        if due.is_zero() {
+            let success = ticket_service
+                .book_ticket_seat(user_id, ticket_id, seat)
+                .await?;
+            return Ok(SeatSelectionResponse {
+                success,
+                seat_number,
+                seat_price: quote.seat_price,
+                amount_charged: Decimal::ZERO,
+                currency: DEFAULT_CURRENCY.to_string(),
+                provider_reference: None,
+            });
+        }
+        let payment_token = request.payment_token.ok_or_else(|| {
+            AppError::ValidationError(format!(
+                "Seat {} costs {} {}, a payment token is required",
+                seat_number, due, DEFAULT_CURRENCY
+            ))
+        })?;
+
+        // Nobody else can take the seat while it is paid for
+        ticket_service
+            .hold_seat(
+                user_id,
+                SeatHoldRequest {
+                    flight_number: seat.flight_number,
+                    flight_date: seat.flight_date,
+                    seat_number,
+                    minutes: None,
+                },
+            )
+            .await?;
+
+        let charge_id = sqlx::query!(
+            r#"
+            INSERT INTO seat_charge
+            (ticket_id, customer_id, flight_id, seat_number, amount, currency, status,
+                created_at)
+            VALUES (?, ?, ?, ?, ?, ?, 'PENDING', UTC_TIMESTAMP())
+            "#,
+            quote.ticket_id,
+            user_id,
+            quote.flight_id,
+            seat_number,
+            due,
+            DEFAULT_CURRENCY
+        )
+        .execute(&self.pool)
+        .await?
+        .last_insert_id() as i32;
+
+        let capture = PaymentCapture {
+            payment_id: charge_id,
+            amount: due,
+            currency: DEFAULT_CURRENCY.to_string(),
+            payment_token,
+        };
+        let provider_reference = match self.provider.capture(&capture).await {
+            Ok(reference) => reference,
+            Err(reason) => {
+                let failure_reason: String = reason.chars().take(255).collect();
+                sqlx::query!(
+                    r#"
+                    UPDATE seat_charge
+                    SET status = 'FAILED', provider = ?, failure_reason = ?
+                    WHERE id = ?
+                    "#,
+                    self.provider.name(),
+                    failure_reason,
+                    charge_id
+                )
+                .execute(&self.pool)
+                .await?;
+                ticket_service
+                    .release_seat_hold(user_id, quote.flight_id, seat_number)
+                    .await?;
+                return Err(AppError::Unprocessable(format!("Payment failed: {}", reason)));
+            }
+        };
+        sqlx::query!(
+            r#"
+            UPDATE seat_charge
+            SET status = 'CAPTURED',
+                provider = ?,
+                provider_reference = ?,
+                captured_at = UTC_TIMESTAMP()
+            WHERE id = ?
+            "#,
+            self.provider.name(),
+            provider_reference,
+            charge_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        match ticket_service
+            .book_ticket_seat(user_id, ticket_id, seat)
+            .await
+        {
+            Ok(success) => Ok(SeatSelectionResponse {
+                success,
+                seat_number,
+                seat_price: quote.seat_price,
+                amount_charged: due,
+                currency: DEFAULT_CURRENCY.to_string(),
+                provider_reference: Some(provider_reference),
+            }),
+            Err(e) => {
+                self.refund_seat_charge(charge_id, due, provider_reference)
+                    .await?;
+                Err(e)
+            }
+        }
+    }
+
+    // Move a ticket of the customer to another seat, or give its seat up. Asking for
+    // the seat the ticket already has changes nothing.
+    pub async fn change_seat(
+        &self,
+        ticket_service: &TicketService,
+        user_id: i32,
+        ticket_id: i32,
+        request: SeatChangeRequest,
+    ) -> AppResult<SeatChangeResponse> {
+        let ticket = ticket_service.ticket_seat(user_id, ticket_id).await?;
+        let unchanged = SeatChangeResponse {
+            ticket_id,
+            old_seat_number: ticket.seat_number,
+            new_seat_number: ticket.seat_number,
+            amount_charged: Decimal::ZERO,
+            currency: DEFAULT_CURRENCY.to_string(),
+            provider_reference: None,
+        };
+
+        let Some(seat_number) = request.seat_number else {
+            let released = ticket_service
+                .release_ticket_seat(user_id, ticket_id)
+                .await?;
+            return Ok(SeatChangeResponse {
+                old_seat_number: released,
+                new_seat_number: None,
+                ..unchanged
+            });
+        };
+        if ticket.seat_number == Some(seat_number) {
+            return Ok(unchanged);
+        }
+
+        let selection = self
+            .select_ticket_seat(
+                ticket_service,
+                user_id,
+                ticket_id,
+                SeatSelectionRequest {
+                    seat: SeatBookingRequest {
+                        flight_number: ticket.flight_number,
+                        flight_date: ticket.flight_date,
+                        seat_number,
+                    },
+                    payment_token: request.payment_token,
+                },
+            )
+            .await?;
+        Ok(SeatChangeResponse {
+            new_seat_number: Some(selection.seat_number),
+            amount_charged: selection.amount_charged,
+            currency: selection.currency,
+            provider_reference: selection.provider_reference,
+            ..unchanged
+        })
+    }
+
+    // Give back the price of a seat that could not be taken after it was charged
+    async fn refund_seat_charge(
+        &self,
+        charge_id: i32,
+        amount: Decimal,
+        capture_reference: String,
+    ) -> AppResult<()> {
+        let execution = RefundExecution {
+            refund_id: charge_id,
+            amount,
+            currency: DEFAULT_CURRENCY.to_string(),
+            capture_reference,
+        };
+        match self.provider.refund(&execution).await {
+            Ok(_) => {
+                sqlx::query!(
+                    "UPDATE seat_charge SET status = 'REFUNDED' WHERE id = ?",
+                    charge_id
+                )
+                .execute(&self.pool)
+                .await?;
+            }
+            // The charge stays captured, so it still counts towards the next seat
+            Err(reason) => {
+                tracing::error!(charge_id, reason = %reason, "failed to refund seat charge")
+            }
+        }
+        Ok(())
+    }
+
+    // Expire bookings whose payment window has passed and release their tickets.
+    // Returns the number of expired bookings.
+    pub async fn expire_unpaid_bookings(&self, ticket_service: &TicketService) -> AppResult<usize> {
+        let expired = sqlx::query!(
+            r#"
+            SELECT id, booking_id
+            FROM payment
+            WHERE status = 'PENDING' AND expires_at < UTC_TIMESTAMP()
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut count = 0;
+        for payment in expired {
+            // Skip payments confirmed in the meantime
+            let updated = sqlx::query!(
+                "UPDATE payment SET status = 'EXPIRED' WHERE id = ? AND status = 'PENDING'",
+                payment.id
+            )
+            .execute(&self.pool)
+            .await?;
+            if updated.rows_affected() == 0 {
+                continue;
+            }
+
+            // The promo code of the booking can be used again
+            let mut tx = self.pool.begin().await?;
+            sqlx::query!(
+                "UPDATE booking SET status = 'EXPIRED' WHERE id = ?",
+                payment.booking_id
+            )
+            .execute(&mut *tx)
+            .await?;
+            promo_code_service::release_redemption(&mut tx, payment.booking_id).await?;
+            tx.commit().await?;
+
+            let tickets = sqlx::query!(
+                "SELECT id FROM ticket WHERE booking_id = ?",
+                payment.booking_id
+            )
+            .fetch_all(&self.pool)
+            .await?;
+            for ticket in tickets {
+                match ticket_service.release_ticket(ticket.id).await {
+                    // Tickets of closed flights are kept as flown
+                    Err(AppError::Conflict(e)) => tracing::warn!(
+                        ticket_id = ticket.id,
+                        reason = %e,
+                        "kept ticket of expired booking"
+                    ),
+                    result => {
+                        result?;
+                    }
+                }
+            }
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    // Cancel a ticket of the customer and refund the share of its paid price the
+    // refund rules of its fare class allow this long before departure
+    pub async fn cancel_ticket(
+        &self,
+        ticket_service: &TicketService,
+        user_id: i32,
+        booking_reference: &str,
+    ) -> AppResult<TicketCancellationResponse> {
+        let booking_reference = booking_reference.trim().to_ascii_uppercase();
+        let ticket = sqlx::query!(
+            r#"
+            SELECT
+                t.id,
+                t.customer_id,
+                t.flight_number,
+                t.flight_date as "flight_date: NaiveDate",
+                t.fare_class as "fare_class: FareClass",
+                t.price,
+                t.currency,
+                fr.departure_time as "departure_time: NaiveTime",
+                da.timezone as "departure_timezone?",
+                (
+                    SELECT p.id
+                    FROM payment p
+                    WHERE p.booking_id = t.booking_id AND p.status = 'CAPTURED'
+                    ORDER BY p.id DESC
+                    LIMIT 1
+                ) as "payment_id?: i32"
+            FROM ticket t
+            JOIN flight_route fr ON t.flight_number = fr.flight_number
+            LEFT JOIN airport da ON fr.departure_airport = da.code
+            WHERE t.booking_reference = ?
+            "#,
+            booking_reference
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let ticket = match ticket {
+            // Do not reveal tickets of other customers
+            Some(ticket) if ticket.customer_id == user_id => ticket,
+            _ => return Err(AppError::NotFound("Ticket not found".into())),
+        };
+
+        // Whole seconds, as stored in the database
+        let now = chrono::Utc::now().naive_utc().trunc_subsecs(0);
+        let departure = departure_utc(
+            ticket.flight_date,
+            ticket.departure_time,
+            ticket.departure_timezone.as_deref(),
+        );
+        if departure <= now {
+            return Err(AppError::Conflict("The flight has already departed".into()));
+        }
+
+        // The refund is recorded with the release, so a released ticket is never left
+        // without its refund. Only the request that released the ticket refunds it.
+        let mut tx = self.pool.begin().await?;
+        let released = ticket_service
+            .release_ticket_in(&mut tx, ticket.id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Ticket not found".into()))?;
+
+        let share = refund_share(ticket.fare_class, (departure - now).num_hours());
+        let amount = (ticket.price * share).round_dp(2);
+        let refund_id = match ticket.payment_id {
+            Some(payment_id) if amount > Decimal::ZERO => {
+                let result = sqlx::query!(
+                    r#"
+                    INSERT INTO refund
+                    (payment_id, customer_id, ticket_id, booking_reference, flight_number,
+                        flight_date, fare_class, ticket_price, amount, currency, status,
+                        created_at)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 'PENDING', ?)
+                    "#,
+                    payment_id,
+                    user_id,
+                    ticket.id,
+                    booking_reference,
+                    ticket.flight_number,
+                    ticket.flight_date,
+                    ticket.fare_class,
+                    ticket.price,
+                    amount,
+                    ticket.currency,
+                    now
+                )
+                .execute(&mut *tx)
+                .await?;
+                Some(result.last_insert_id() as i32)
+            }
+            _ => None,
+        };
+        tx.commit().await?;
+        ticket_service.ticket_released(released).await;
+
+        let refund = match refund_id {
+            Some(refund_id) => {
+                // Left pending for the background task if this fails
+                if let Err(e) = self.process_refund(refund_id).await {
+                    tracing::error!(refund_id, error = %e, "failed to process refund");
+                }
+                Some(self.get_refund(refund_id).await?)
+            }
+            None => None,
+        };
+
+        Ok(TicketCancellationResponse {
+            ticket_id: ticket.id,
+            booking_reference,
+            refund,
+        })
+    }
+
+    // Pay a pending refund back through the provider. A refund the provider rejects is
+    // kept as failed with its reason, for support to follow up.
+    async fn process_refund(&self, refund_id: i32) -> AppResult<()> {
+        // Claim the refund so that it cannot be paid twice
+        let claimed = sqlx::query!(
+            r#"
+            UPDATE refund
+            SET status = 'PROCESSING'
+            WHERE id = ? AND status = 'PENDING'
+            "#,
+            refund_id
+        )
+        .execute(&self.pool)
+        .await?;
+        if claimed.rows_affected() == 0 {
+            return Ok(());
+        }
+
+        let refund = sqlx::query!(
+            r#"
+            SELECT
+                r.customer_id,
+                r.ticket_id,
+                r.booking_reference,
+                r.amount,
+                r.currency,
+                p.booking_id,
+                p.provider,
+                p.provider_reference
+            FROM refund r
+            JOIN payment p ON r.payment_id = p.id
+            WHERE r.id = ?
+            "#,
+            refund_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        // Points paid for the ticket go back to the loyalty account
+        if refund.provider.as_deref() == Some(LOYALTY_PROVIDER) {
+            let mut tx = self.pool.begin().await?;
+            let entry_id = loyalty_service::add_entry(
+                &mut tx,
+                LedgerChange {
+                    customer_id: refund.customer_id,
+                    entry_type: LoyaltyEntryType::Refund,
+                    points: points_for_amount(refund.amount),
+                    ticket_id: Some(refund.ticket_id),
+                    booking_id: Some(refund.booking_id),
+                    description: format!(
+                        "Refund of {}",
+                        refund.booking_reference.as_deref().unwrap_or("ticket")
+                    ),
+                },
+            )
+            .await?;
+            sqlx::query!(
+                r#"
+                UPDATE refund
+                SET status = 'REFUNDED',
+                    provider = ?,
+                    provider_reference = ?,
+                    processed_at = UTC_TIMESTAMP()
+                WHERE id = ?
+                "#,
+                LOYALTY_PROVIDER,
+                entry_id.to_string(),
+                refund_id
+            )
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            return Ok(());
+        }
+
+        let execution = RefundExecution {
+            refund_id,
+            amount: refund.amount,
+            currency: refund.currency,
+            capture_reference: refund.provider_reference.unwrap_or_default(),
+        };
+        match self.provider.refund(&execution).await {
+            Ok(provider_reference) => {
+                sqlx::query!(
+                    r#"
+                    UPDATE refund
+                    SET status = 'REFUNDED',
+                        provider = ?,
+                        provider_reference = ?,
+                        processed_at = UTC_TIMESTAMP()
+                    WHERE id = ?
+                    "#,
+                    self.provider.name(),
+                    provider_reference,
+                    refund_id
+                )
+                .execute(&self.pool)
+                .await?;
+            }
+            Err(reason) => {
+                tracing::warn!(refund_id, reason = %reason, "refund failed");
+                let reason: String = reason.chars().take(255).collect();
+                sqlx::query!(
+                    r#"
+                    UPDATE refund
+                    SET status = 'FAILED',
+                        provider = ?,
+                        failure_reason = ?,
+                        processed_at = UTC_TIMESTAMP()
+                    WHERE id = ?
+                    "#,
+                    self.provider.name(),
+                    reason,
+                    refund_id
+                )
+                .execute(&self.pool)
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    // Process refunds left pending, e.g. when the server stopped before paying them.
+    // Returns the number of refunds processed.
+    pub async fn process_pending_refunds(&self) -> AppResult<usize> {
+        let pending = sqlx::query_scalar!("SELECT id FROM refund WHERE status = 'PENDING'")
+            .fetch_all(&self.pool)
+            .await?;
+        for refund_id in &pending {
+            self.process_refund(*refund_id).await?;
+        }
+        Ok(pending.len())
+    }
+
+    async fn get_refund(&self, refund_id: i32) -> AppResult<RefundResponse> {
+        let refund = sqlx::query_as!(
+            RefundResponse,
+            r#"
+            SELECT
+                id as refund_id,
+                ticket_id,
+                booking_reference,
+                flight_number,
+                flight_date as "flight_date: NaiveDate",
+                fare_class as "fare_class: FareClass",
+                ticket_price,
+                amount,
+                currency,
+                status as "status: RefundStatus",
+                provider_reference,
+                failure_reason,
+                created_at as "created_at: NaiveDateTime",
+                processed_at as "processed_at: NaiveDateTime"
+            FROM refund
+            WHERE id = ?
+            "#,
+            refund_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(refund)
+    }
+
+    // Refunds of the customer, newest first
+    pub async fn list_refunds(&self, user_id: i32) -> AppResult<Vec<RefundResponse>> {
+        let refunds = sqlx::query_as!(
+            RefundResponse,
+            r#"
+            SELECT
+                id as refund_id,
+                ticket_id,
+                booking_reference,
+                flight_number,
+                flight_date as "flight_date: NaiveDate",
+                fare_class as "fare_class: FareClass",
+                ticket_price,
+                amount,
+                currency,
+                status as "status: RefundStatus",
+                provider_reference,
+                failure_reason,
+                created_at as "created_at: NaiveDateTime",
+                processed_at as "processed_at: NaiveDateTime"
+            FROM refund
+            WHERE customer_id = ?
+            ORDER BY created_at DESC, id DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(refunds)
+    }
+
+    // Periodically expire unpaid bookings and retry pending refunds in the background
+    pub fn spawn_expiry_task(&self, ticket_service: TicketService, period: Duration) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                if let Err(e) = service.expire_unpaid_bookings(&ticket_service).await {
+                    tracing::error!(error = %e, "failed to expire unpaid bookings");
+                }
+                if let Err(e) = service.process_pending_refunds().await {
+                    tracing::error!(error = %e, "failed to process pending refunds");
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,290 @@
+use crate::models::promo::{
+    discounted_prices, normalize_code, AppliedPromoCode, CreatePromoCodeRequest, DiscountType,
+    PromoCodeResponse,
+};
+use crate::utils::error::{is_unique_violation, AppError, AppResult};
+use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
+use sqlx::{MySql, MySqlPool, Transaction};
+use validator::Validate;
+
+#[derive(Clone)]
+pub struct PromoCodeService {
+    pool: MySqlPool,
+}
+
+impl PromoCodeService {
+    pub fn new(pool: MySqlPool) -> Self {
+        PromoCodeService { pool }
+    }
+
+    pub async fn create_promo_code(
+        &self,
+        admin_id: i32,
+        request: CreatePromoCodeRequest,
+    ) -> AppResult<PromoCodeResponse> {
+        request
+            .validate()
+            .map_err(|e| AppError::ValidationError(format!("{:?}", e)))?;
+
+        let code = normalize_code(&request.code);
+        if code.is_empty()
+            || !code
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(AppError::ValidationError(
+                "Promo codes are made of letters, digits, '-' and '_'".into(),
+            ));
+        }
+        if request.discount_value <= Decimal::ZERO {
+            return Err(AppError::ValidationError(
+                "The discount must be more than zero".into(),
+            ));
+        }
+        let currency = request
+            .currency
+            .as_deref()
+            .map(|currency| currency.trim().to_ascii_uppercase());
+        match request.discount_type {
+            DiscountType::Percentage => {
+                if request.discount_value > Decimal::ONE_HUNDRED {
+                    return Err(AppError::ValidationError(
+                        "A percentage discount is at most 100".into(),
+                    ));
+                }
+            }
+            DiscountType::Fixed => {
+                if !currency.as_deref().map_or(false, |currency| {
+                    currency.len() == 3 && currency.chars().all(|c| c.is_ascii_alphabetic())
+                }) {
+                    return Err(AppError::ValidationError(
+                        "A fixed discount needs the three-letter currency it is in".into(),
+                    ));
+                }
+            }
+        }
+        // Percentages apply to fares in any currency
+        let currency = match request.discount_type {
+            DiscountType::Percentage => None,
+            DiscountType::Fixed => currency,
+        };
+        let discount_value = request.discount_value.round_dp(2);
+
+        let promo_code_id = sqlx::query!(
+            r#"
+            INSERT INTO promo_code
+            (code, discount_type, discount_value, currency, expires_at, max_redemptions,
+                max_redemptions_per_customer, created_by, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, UTC_TIMESTAMP())
+            "#,
+            code,
+            request.discount_type,
+            discount_value,
+            currency,
+            request.expires_at,
+            request.max_redemptions,
+            request.max_redemptions_per_customer,
+            admin_id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            if is_unique_violation(&e) {
+                AppError::Conflict(format!("Promo code {} already exists", code))
+            } else {
+                e.into()
+            }
+        })?
+        .last_insert_id() as i32;
+
+        self.get_promo_code(promo_code_id).await
+    }
+
+    async fn get_promo_code(&self, promo_code_id: i32) -> AppResult<PromoCodeResponse> {
+        let promo_code = sqlx::query_as!(
+            PromoCodeResponse,
+            r#"
+            SELECT
+                id as promo_code_id,
+                code,
+                discount_type as "discount_type: DiscountType",
+                discount_value,
+                currency,
+                expires_at as "expires_at: NaiveDateTime",
+                max_redemptions,
+                max_redemptions_per_customer,
+                redemptions,
+                created_at as "created_at: NaiveDateTime"
+            FROM promo_code
+            WHERE id = ?
+            "#,
+            promo_code_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(promo_code)
+    }
+
+    // All promo codes with their redemptions so far, newest first
+    pub async fn list_promo_codes(&self) -> AppResult<Vec<PromoCodeResponse>> {
+        let promo_codes = sqlx::query_as!(
+            PromoCodeResponse,
+            r#"
+            SELECT
+                id as promo_code_id,
+                code,
+                discount_type as "discount_type: DiscountType",
+                discount_value,
+                currency,
+                expires_at as "expires_at: NaiveDateTime",
+                max_redemptions,
+                max_redemptions_per_customer,
+                redemptions,
+                created_at as "created_at: NaiveDateTime"
+            FROM promo_code
+            ORDER BY id DESC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(promo_codes)
+    }
+}
+
+// Redeem a promo code for a new booking in the transaction creating it. The code is
+// locked until the transaction ends, so concurrent bookings cannot use it beyond its
+// limits. Returns the ticket prices after the discount and the discount given.
+pub async fn redeem(
+    tx: &mut Transaction<'_, MySql>,
+    code: &str,
+    customer_id: i32,
+    booking_id: i32,
+    prices: &[Decimal],
+    currency: &str,
+) -> AppResult<(Vec<Decimal>, AppliedPromoCode)> {
+    let code = normalize_code(code);
+    let promo_code = sqlx::query!(
+        r#"
+        SELECT
+            id,
+            discount_type as "discount_type: DiscountType",
+            discount_value,
+            currency,
+            expires_at as "expires_at: NaiveDateTime",
+            max_redemptions,
+            max_redemptions_per_customer,
+            redemptions,
+            UTC_TIMESTAMP() as "now!: NaiveDateTime"
+        FROM promo_code
+        WHERE code = ?
+        FOR UPDATE
+        "#,
+        code
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or_else(|| AppError::ValidationError(format!("Unknown promo code {}", code)))?;
+
+    if promo_code
+        .expires_at
+        .map_or(false, |expires_at| expires_at <= promo_code.now)
+    {
+        return Err(AppError::ValidationError(format!(
+            "Promo code {} has expired",
+            code
+        )));
+    }
+    if let Some(promo_currency) = &promo_code.currency {
+        if promo_currency != currency {
+            return Err(AppError::ValidationError(format!(
+                "Promo code {} only applies to fares in {}",
+                code, promo_currency
+            )));
+        }
+    }
+    if promo_code
+        .max_redemptions
+        .map_or(false, |max| promo_code.redemptions >= max)
+    {
+        return Err(AppError::Conflict(format!(
+            "Promo code {} has been used up",
+            code
+        )));
+    }
+    if let Some(max_per_customer) = promo_code.max_redemptions_per_customer {
+        let used = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*)
+            FROM promo_redemption
+            WHERE promo_code_id = ? AND customer_id = ?
+            "#,
+            promo_code.id,
+            customer_id
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+        if used >= max_per_customer as i64 {
+            return Err(AppError::Conflict(format!(
+                "You have already used promo code {}",
+                code
+            )));
+        }
+    }
+
+    let discounted = discounted_prices(prices, promo_code.discount_type, promo_code.discount_value);
+    let discount = prices.iter().sum::<Decimal>() - discounted.iter().sum::<Decimal>();
+
+    sqlx::query!(
+        "UPDATE promo_code SET redemptions = redemptions + 1 WHERE id = ?",
+        promo_code.id
+    )
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO promo_redemption
+        (promo_code_id, booking_id, customer_id, discount, currency, redeemed_at)
+        VALUES (?, ?, ?, ?, ?, UTC_TIMESTAMP())
+        "#,
+        promo_code.id,
+        booking_id,
+        customer_id,
+        discount,
+        currency
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok((
+        discounted,
+        AppliedPromoCode {
+            code,
+            discount,
+            currency: currency.to_string(),
+        },
+    ))
+}
+
+// Give back the promo code redemption of a booking that expired unpaid, so the code
+// can be used again
+pub async fn release_redemption(tx: &mut Transaction<'_, MySql>, booking_id: i32) -> AppResult<()> {
+    sqlx::query!(
+        r#"
+        UPDATE promo_code p
+        JOIN promo_redemption r ON r.promo_code_id = p.id
+        SET p.redemptions = p.redemptions - 1
+        WHERE r.booking_id = ?
+        "#,
+        booking_id
+    )
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM promo_redemption WHERE booking_id = ?",
+        booking_id
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
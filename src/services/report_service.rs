@@ -0,0 +1,178 @@
+use crate::models::report::{
+    self, FlightOccupancy, FlightRevenue, OccupancyReportResponse, ReportQuery,
+    RevenueReportResponse, MAX_REPORT_DAYS,
+};
+use crate::utils::error::{AppError, AppResult};
+use crate::utils::ndjson::{collect_rows, RowSink};
+use chrono::NaiveDate;
+use rocket::futures::TryStreamExt;
+use rust_decimal::Decimal;
+use sqlx::MySqlPool;
+
+#[derive(Clone)]
+pub struct ReportService {
+    pool: MySqlPool,
+}
+
+impl ReportService {
+    pub fn new(pool: MySqlPool) -> Self {
+        ReportService { pool }
+    }
+
+    // Seats and tickets sold of each flight departing in the range. Every ticket
+    // counts, paid or not, since it takes a seat.
+    pub async fn occupancy_report(&self, query: ReportQuery) -> AppResult<OccupancyReportResponse> {
+        let flights = collect_rows(|sink| self.export_occupancy(query.clone(), sink)).await?;
+        Ok(OccupancyReportResponse {
+            from: query.from,
+            to: query.to,
+            routes: report::route_occupancy(&flights),
+            flights,
+        })
+    }
+
+    // Stream the flights of the occupancy report as they are fetched
+    pub async fn export_occupancy(
+        &self,
+        query: ReportQuery,
+        sink: RowSink<FlightOccupancy>,
+    ) -> AppResult<()> {
+        check_range(&query)?;
+
+        let mut rows = sqlx::query!(
+            r#"
+            SELECT
+                f.flight_number,
+                f.flight_date as "flight_date: NaiveDate",
+                fr.departure_city,
+                fr.destination_city,
+                a.capacity,
+                (SELECT COUNT(*) FROM ticket t WHERE t.flight_id = f.flight_id)
+                    as "tickets_sold!: i64"
+            FROM flight f
+            JOIN flight_route fr ON f.flight_number = fr.flight_number
+            JOIN aircraft a ON COALESCE(f.aircraft_id, fr.aircraft_id) = a.aircraft_id
+            WHERE f.flight_date BETWEEN ? AND ?
+            AND (? IS NULL OR f.flight_number = ?)
+            AND f.status != 'CANCELLED'
+            ORDER BY f.flight_date, f.flight_number
+            "#,
+            query.from,
+            query.to,
+            query.flight_number,
+            query.flight_number
+        )
+        .fetch(&self.pool);
+
+        while let Some(row) = rows.try_next().await? {
+            let flight = FlightOccupancy {
+                flight_number: row.flight_number,
+                flight_date: row.flight_date,
+                departure_city: row.departure_city,
+                destination_city: row.destination_city,
+                capacity: row.capacity,
+                tickets_sold: row.tickets_sold,
+                load_factor: report::load_factor(row.tickets_sold, i64::from(row.capacity)),
+            };
+            if !sink.send(flight).await {
+                // The client went away
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Paid sales of each flight departing in the range, per currency. A ticket is paid
+    // when its booking is confirmed or it was booked without one; seat charges count
+    // once captured.
+    pub async fn revenue_report(&self, query: ReportQuery) -> AppResult<RevenueReportResponse> {
+        let flights = collect_rows(|sink| self.export_revenue(query.clone(), sink)).await?;
+        Ok(RevenueReportResponse {
+            from: query.from,
+            to: query.to,
+            routes: report::route_revenue(&flights),
+            flights,
+        })
+    }
+
+    // Stream the flights of the revenue report as they are fetched
+    pub async fn export_revenue(
+        &self,
+        query: ReportQuery,
+        sink: RowSink<FlightRevenue>,
+    ) -> AppResult<()> {
+        check_range(&query)?;
+
+        let mut rows = sqlx::query!(
+            r#"
+            SELECT
+                f.flight_number,
+                f.flight_date as "flight_date: NaiveDate",
+                fr.departure_city,
+                fr.destination_city,
+                sales.currency as "currency!: String",
+                CAST(SUM(sales.tickets) AS SIGNED) as "tickets_sold!: i64",
+                SUM(sales.ticket_revenue) as "ticket_revenue!: Decimal",
+                SUM(sales.seat_revenue) as "seat_revenue!: Decimal"
+            FROM (
+                SELECT t.flight_id, t.currency, 1 as tickets,
+                    t.price as ticket_revenue, 0.00 as seat_revenue
+                FROM ticket t
+                LEFT JOIN booking b ON t.booking_id = b.id
+                WHERE b.id IS NULL OR b.status = 'CONFIRMED'
+                UNION ALL
+                SELECT sc.flight_id, sc.currency, 0, 0.00, sc.amount
+                FROM seat_charge sc
+                WHERE sc.status = 'CAPTURED'
+            ) sales
+            JOIN flight f ON sales.flight_id = f.flight_id
+            JOIN flight_route fr ON f.flight_number = fr.flight_number
+            WHERE f.flight_date BETWEEN ? AND ?
+            AND (? IS NULL OR f.flight_number = ?)
+            GROUP BY f.flight_id, sales.currency
+            ORDER BY f.flight_date, f.flight_number, sales.currency
+            "#,
+            query.from,
+            query.to,
+            query.flight_number,
+            query.flight_number
+        )
+        .fetch(&self.pool);
+
+        while let Some(row) = rows.try_next().await? {
+            let flight = FlightRevenue {
+                flight_number: row.flight_number,
+                flight_date: row.flight_date,
+                departure_city: row.departure_city,
+                destination_city: row.destination_city,
+                currency: row.currency,
+                tickets_sold: row.tickets_sold,
+                ticket_revenue: row.ticket_revenue,
+                seat_revenue: row.seat_revenue,
+                total_revenue: row.ticket_revenue + row.seat_revenue,
+            };
+            if !sink.send(flight).await {
+                // The client went away
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Reports cover a date range of at most MAX_REPORT_DAYS, checked before a report is
+// streamed
+pub fn check_range(query: &ReportQuery) -> AppResult<()> {
+    if query.to < query.from {
+        return Err(AppError::BadRequest("to must not be before from".into()));
+    }
+    if (query.to - query.from).num_days() >= MAX_REPORT_DAYS {
+        return Err(AppError::BadRequest(format!(
+            "Reports cover at most {} days",
+            MAX_REPORT_DAYS
+        )));
+    }
+    Ok(())
+}
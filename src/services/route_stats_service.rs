@@ -0,0 +1,240 @@
+use crate::models::flight::{TrendingDestination, TrendingDestinationsResponse};
+use crate::models::forecast::{
+    self, BookingCurve, FlightForecast, RouteForecastResponse, FORECAST_HISTORY_FLIGHTS,
+};
+use crate::services::event_bus::{DomainEvent, EventBus};
+use crate::utils::error::{AppError, AppResult};
+use chrono::NaiveDate;
+use sqlx::MySqlPool;
+use std::collections::BTreeMap;
+use tokio::sync::broadcast::error::RecvError;
+
+#[derive(Clone)]
+pub struct RouteStatsService {
+    pool: MySqlPool,
+}
+
+impl RouteStatsService {
+    pub fn new(pool: MySqlPool) -> Self {
+        RouteStatsService { pool }
+    }
+
+    // Aggregate search and booking events into per-route daily counters in the background
+    pub fn spawn_aggregator(&self, event_bus: &EventBus) {
+        let service = self.clone();
+        let mut receiver = event_bus.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => {
+                        if let Err(e) = service.record_event(&event).await {
+                            tracing::error!(error = %e, "failed to record route stats");
+                        }
+                    }
+                    // Dropping a few counts under heavy load is acceptable
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    async fn record_event(&self, event: &DomainEvent) -> AppResult<()> {
+        match event {
+            DomainEvent::FlightSearched {
+                departure_city,
+                destination_city,
+            } => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO route_stats (departure_city, destination_city, stat_date, searches, bookings)
+                    VALUES (?, ?, CURDATE(), 1, 0)
+                    ON DUPLICATE KEY UPDATE searches = searches + 1
+                    "#,
+                    departure_city,
+                    destination_city
+                )
+                .execute(&self.pool)
+                .await?;
+            }
+            DomainEvent::TicketBooked {
+                flight_id,
+                flight_number,
+                flight_date,
+                ..
+            } => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO route_stats (departure_city, destination_city, stat_date, searches, bookings)
+                    SELECT departure_city, destination_city, CURDATE(), 0, 1
+                    FROM flight_route
+                    WHERE flight_number = ?
+                    ON DUPLICATE KEY UPDATE bookings = bookings + 1
+                    "#,
+                    flight_number
+                )
+                .execute(&self.pool)
+                .await?;
+                sqlx::query!(
+                    r#"
+                    INSERT INTO booking_curve (flight_id, days_before_departure, bookings)
+                    VALUES (?, GREATEST(DATEDIFF(?, CURDATE()), 0), 1)
+                    ON DUPLICATE KEY UPDATE bookings = bookings + 1
+                    "#,
+                    flight_id,
+                    flight_date
+                )
+                .execute(&self.pool)
+                .await?;
+            }
+            // Other events do not count towards route stats
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Most booked (then most searched) routes over the last `days` days
+    pub async fn trending_destinations(
+        &self,
+        days: i64,
+        departure_city: Option<String>,
+        limit: i64,
+    ) -> AppResult<TrendingDestinationsResponse> {
+        let since = chrono::Utc::now().date_naive() - chrono::Duration::days(days);
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                departure_city,
+                destination_city,
+                CAST(SUM(searches) AS SIGNED) as "searches!: i64",
+                CAST(SUM(bookings) AS SIGNED) as "bookings!: i64"
+            FROM route_stats
+            WHERE stat_date >= ?
+            AND (? IS NULL OR departure_city = ?)
+            GROUP BY departure_city, destination_city
+            ORDER BY 4 DESC, 3 DESC
+            LIMIT ?
+            "#,
+            since,
+            departure_city,
+            departure_city,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let destinations = rows
+            .into_iter()
+            .map(|row| TrendingDestination {
+                departure_city: row.departure_city,
+                destination_city: row.destination_city,
+                searches: row.searches,
+                bookings: row.bookings,
+            })
+            .collect();
+
+        Ok(TrendingDestinationsResponse { days, destinations })
+    }
+
+    // Booking pace of the upcoming flights of a route against the booking curves of its
+    // recent departed flights, with the bookings each flight is projected to end with
+    pub async fn route_forecast(&self, flight_number: i32) -> AppResult<RouteForecastResponse> {
+        let today = chrono::Utc::now().date_naive();
+
+        let route = sqlx::query!(
+            r#"
+            SELECT departure_city, destination_city
+            FROM flight_route
+            WHERE flight_number = ?
+            "#,
+            flight_number
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Route not found".into()))?;
+
+        // Past flights without bookings still count, with an empty curve
+        let history_rows = sqlx::query!(
+            r#"
+            SELECT
+                h.flight_id,
+                bc.days_before_departure as "days_before_departure?: i32",
+                bc.bookings as "bookings?: i32"
+            FROM (
+                SELECT flight_id
+                FROM flight
+                WHERE flight_number = ? AND flight_date < ? AND status <> 'CANCELLED'
+                ORDER BY flight_date DESC
+                LIMIT ?
+            ) h
+            LEFT JOIN booking_curve bc ON bc.flight_id = h.flight_id
+            "#,
+            flight_number,
+            today,
+            FORECAST_HISTORY_FLIGHTS
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut curves: BTreeMap<i32, BookingCurve> = BTreeMap::new();
+        for row in history_rows {
+            let curve = curves.entry(row.flight_id).or_default();
+            if let (Some(days), Some(bookings)) = (row.days_before_departure, row.bookings) {
+                curve.points.push((days as i64, bookings as i64));
+            }
+        }
+        let history: Vec<BookingCurve> = curves.into_values().collect();
+
+        let upcoming = sqlx::query!(
+            r#"
+            SELECT
+                f.flight_id,
+                f.flight_date as "flight_date: NaiveDate",
+                a.capacity,
+                CAST(COALESCE(SUM(bc.bookings), 0) AS SIGNED) as "booked!: i64"
+            FROM flight f
+            JOIN flight_route fr ON f.flight_number = fr.flight_number
+            JOIN aircraft a ON COALESCE(f.aircraft_id, fr.aircraft_id) = a.aircraft_id
+            LEFT JOIN booking_curve bc ON bc.flight_id = f.flight_id
+            WHERE f.flight_number = ? AND f.flight_date >= ? AND f.status <> 'CANCELLED'
+            GROUP BY f.flight_id, f.flight_date, a.capacity
+            ORDER BY f.flight_date
+            "#,
+            flight_number,
+            today
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let flights = upcoming
+            .into_iter()
+            .map(|row| {
+                let days_to_departure = (row.flight_date - today).num_days();
+                let projection = forecast::project(&history, row.booked, days_to_departure);
+                let projected_load_factor = if row.capacity > 0 {
+                    projection.projected_bookings as f64 / row.capacity as f64
+                } else {
+                    0.0
+                };
+                FlightForecast {
+                    flight_id: row.flight_id,
+                    flight_date: row.flight_date,
+                    days_to_departure,
+                    capacity: row.capacity,
+                    booked: row.booked,
+                    projection,
+                    projected_load_factor,
+                }
+            })
+            .collect();
+
+        Ok(RouteForecastResponse {
+            flight_number,
+            departure_city: route.departure_city,
+            destination_city: route.destination_city,
+            history_flights: history.len() as i64,
+            flights,
+        })
+    }
+}
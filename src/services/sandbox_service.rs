@@ -0,0 +1,157 @@
+use crate::models::sandbox::{
+    SandboxBookingRequest, SandboxResetResponse, SandboxTableCopy, SANDBOX_INVENTORY_TABLES,
+};
+use crate::models::ticket::TicketBookingResponse;
+use crate::models::user::{Role, UserRegistrationRequest};
+use crate::services::partner_service::{Partner, PartnerService};
+use crate::services::ticket_service::TicketService;
+use crate::services::user_service::UserService;
+use crate::utils::error::{AppError, AppResult};
+use sqlx::{MySqlConnection, MySqlPool};
+use uuid::Uuid;
+
+// Sandbox customers never log in, their passwords need no strong hash
+const SANDBOX_BCRYPT_COST: u32 = 4;
+
+// Bookings against a copy of the inventory kept in a database of its own, where
+// partners and tests can try booking flows without touching real flights. The
+// services are the same as for the live database, only their pool differs.
+// They have the default configuration until with_live_configuration copies that of
+// the live services.
+#[derive(Clone)]
+pub struct SandboxService {
+    live_pool: MySqlPool,
+    pool: MySqlPool,
+    ticket_service: TicketService,
+    user_service: UserService,
+    partner_service: PartnerService,
+}
+
+impl SandboxService {
+    // Sandbox in the database of `pool`, copying its inventory from `live_pool`
+    pub fn new(live_pool: MySqlPool, pool: MySqlPool) -> Self {
+        SandboxService {
+            ticket_service: TicketService::new(pool.clone()),
+            user_service: UserService::new(pool.clone()).with_bcrypt_cost(SANDBOX_BCRYPT_COST),
+            partner_service: PartnerService::new(pool.clone()),
+            live_pool,
+            pool,
+        }
+    }
+
+    // Book with the configuration of the live services, e.g. their booking rules, seat
+    // booking attempts and data region, so sandbox bookings behave like live ones
+    pub fn with_live_configuration(
+        mut self,
+        ticket_service: &TicketService,
+        user_service: &UserService,
+        partner_service: &PartnerService,
+    ) -> Self {
+        self.ticket_service = ticket_service.for_database(self.pool.clone());
+        self.user_service = user_service
+            .for_database(self.pool.clone())
+            .with_bcrypt_cost(SANDBOX_BCRYPT_COST);
+        self.partner_service = partner_service.for_database(self.pool.clone());
+        self
+    }
+
+    pub fn ticket_service(&self) -> &TicketService {
+        &self.ticket_service
+    }
+
+    pub fn partner_service(&self) -> &PartnerService {
+        &self.partner_service
+    }
+
+    // Book the flights for a new sandbox customer, as the partner would for their client
+    pub async fn book(
+        &self,
+        partner: &Partner,
+        request: SandboxBookingRequest,
+    ) -> AppResult<TicketBookingResponse> {
+        let passenger = request.passenger;
+        let customer_id = self
+            .user_service
+            .register_user(UserRegistrationRequest {
+                username: format!("sandbox_{}_{}", partner.key_id, Uuid::new_v4().simple()),
+                password: Uuid::new_v4().to_string(),
+                name: passenger.name,
+                birth_date: passenger.birth_date,
+                gender: passenger.gender,
+                role: Role::User,
+                email: None,
+            })
+            .await?;
+        self.ticket_service
+            .book_ticket(customer_id, request.booking)
+            .await
+    }
+
+    // Empty the sandbox and copy the current inventory of the live database into it.
+    // Both databases have to be on the same server.
+    pub async fn reset(&self) -> AppResult<SandboxResetResponse> {
+        let live_schema: Option<String> = sqlx::query_scalar("SELECT DATABASE()")
+            .fetch_one(&self.live_pool)
+            .await?;
+        let live_schema = live_schema
+            .ok_or_else(|| AppError::DatabaseError("The live pool has no database".into()))?;
+
+        // Foreign key checks are per session, the tables are refilled on one connection
+        let mut conn = self.pool.acquire().await?;
+        let sandbox_schema: Option<String> = sqlx::query_scalar("SELECT DATABASE()")
+            .fetch_one(&mut *conn)
+            .await?;
+        if sandbox_schema.as_deref() == Some(live_schema.as_str()) {
+            return Err(AppError::Conflict(
+                "The sandbox database is the live database, refusing to reset it".into(),
+            ));
+        }
+
+        // The migration history is not sandbox data
+        let tables: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT CAST(table_name AS CHAR) FROM information_schema.tables
+            WHERE table_schema = DATABASE() AND table_type = 'BASE TABLE'
+            AND table_name <> '_sqlx_migrations'
+            "#,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        sqlx::query("SET FOREIGN_KEY_CHECKS = 0")
+            .execute(&mut *conn)
+            .await?;
+        let result = refill(&mut conn, &live_schema, &tables).await;
+        sqlx::query("SET FOREIGN_KEY_CHECKS = 1")
+            .execute(&mut *conn)
+            .await?;
+        result
+    }
+}
+
+async fn refill(
+    conn: &mut MySqlConnection,
+    live_schema: &str,
+    tables: &[String],
+) -> AppResult<SandboxResetResponse> {
+    let mut copied = Vec::new();
+    for table in tables {
+        sqlx::query(&format!("TRUNCATE TABLE `{}`", table))
+            .execute(&mut *conn)
+            .await?;
+        if SANDBOX_INVENTORY_TABLES.contains(&table.as_str()) {
+            let rows = sqlx::query(&format!(
+                "INSERT INTO `{}` SELECT * FROM `{}`.`{}`",
+                table, live_schema, table
+            ))
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+            copied.push(SandboxTableCopy {
+                table: table.clone(),
+                rows,
+            });
+        }
+    }
+    Ok(SandboxResetResponse { copied })
+}
@@ -0,0 +1,195 @@
+use crate::models::flight::{overbooked_capacity, RouteSchedule};
+use crate::services::seat_block_service::apply_block_rules;
+use crate::utils::error::{AppError, AppResult};
+use chrono::NaiveDate;
+use sqlx::{MySqlConnection, MySqlPool};
+use std::collections::HashSet;
+use std::time::Duration;
+
+// How far ahead flights are materialized by the background scheduler
+pub const DEFAULT_HORIZON_DAYS: i64 = 90;
+
+#[derive(Clone)]
+pub struct ScheduleService {
+    pool: MySqlPool,
+}
+
+impl ScheduleService {
+    pub fn new(pool: MySqlPool) -> Self {
+        ScheduleService { pool }
+    }
+
+    // Periodically materialize upcoming flights for all routes in the background
+    pub fn spawn_scheduler(&self, period: Duration, horizon_days: i64) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                if let Err(e) = service.generate_upcoming_flights(horizon_days).await {
+                    tracing::error!(error = %e, "failed to generate scheduled flights");
+                }
+            }
+        });
+    }
+
+    // Materialize flights of every route from today up to the horizon
+    pub async fn generate_upcoming_flights(&self, horizon_days: i64) -> AppResult<usize> {
+        let today = chrono::Utc::now().date_naive();
+        let until = today + chrono::Duration::days(horizon_days);
+
+        let routes = sqlx::query!(r#"SELECT flight_number FROM flight_route"#)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut generated = 0;
+        for route in routes {
+            generated += self
+                .generate_flights_for_route(route.flight_number, today, until)
+                .await?;
+        }
+        Ok(generated)
+    }
+
+    // Create the flight and its seats for each date between `from` and `until` (inclusive)
+    // on which the route operates, within its start and end dates and on its operating days. Dates that already have a flight are skipped.
+    // The seat block rules of the route are applied to the new flights.
+    // Returns the number of flights created.
+    pub async fn generate_flights_for_route(
+        &self,
+        flight_number: i32,
+        from: NaiveDate,
+        until: NaiveDate,
+    ) -> AppResult<usize> {
+        let route = sqlx::query!(
+            r#"
+            SELECT
+                fr.start_date as "start_date: NaiveDate",
+                fr.end_date as "end_date: NaiveDate",
+                fr.operating_days,
+                fr.overbooking,
+                a.capacity
+            FROM flight_route fr
+            JOIN aircraft a ON fr.aircraft_id = a.aircraft_id
+            WHERE fr.flight_number = ?
+            "#,
+            flight_number
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Flight route {} not found", flight_number)))?;
+        let schedule = RouteSchedule {
+            start_date: route.start_date,
+            end_date: route.end_date,
+            operating_days: route.operating_days,
+        };
+
+        // Clamp the requested window to the dates the route is active
+        let from = from.max(route.start_date);
+        let until = match route.end_date {
+            Some(end_date) => until.min(end_date),
+            None => until,
+        };
+        if from > until {
+            return Ok(0);
+        }
+
+        let existing_dates: HashSet<NaiveDate> = sqlx::query!(
+            r#"
+            SELECT flight_date as "flight_date: NaiveDate"
+            FROM flight
+            WHERE flight_number = ? AND flight_date BETWEEN ? AND ?
+            "#,
+            flight_number,
+            from,
+            until
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| row.flight_date)
+        .collect();
+
+        let mut generated = 0;
+        let mut flight_date = from;
+        while flight_date <= until {
+            if schedule.operates_on(flight_date) && !existing_dates.contains(&flight_date) {
+                self.create_flight(
+                    flight_number,
+                    flight_date,
+                    route.capacity,
+                    overbooked_capacity(route.capacity, route.overbooking),
+                )
+                .await?;
+                generated += 1;
+            }
+            flight_date = flight_date.succ_opt().unwrap();
+        }
+
+        Ok(generated)
+    }
+
+    async fn create_flight(
+        &self,
+        flight_number: i32,
+        flight_date: NaiveDate,
+        capacity: i32,
+        available_tickets: i32,
+    ) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+            VALUES (?, ?, ?, 1)
+            "#,
+            flight_number,
+            flight_date,
+            available_tickets
+        )
+        .execute(&mut *tx)
+        .await?;
+        let flight_id = result.last_insert_id() as i32;
+        insert_seats(&mut *tx, flight_id, capacity).await?;
+        apply_block_rules(&mut tx, flight_id).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+// Create the available seats 1 to capacity of a flight in a single query
+pub async fn insert_seats(
+    conn: &mut MySqlConnection,
+    flight_id: i32,
+    capacity: i32,
+) -> AppResult<()> {
+    insert_seat_range(conn, flight_id, 1, capacity).await
+}
+
+// Create the available seats first to last of a flight in a single query
+pub async fn insert_seat_range(
+    conn: &mut MySqlConnection,
+    flight_id: i32,
+    first: i32,
+    last: i32,
+) -> AppResult<()> {
+    if last < first {
+        return Ok(());
+    }
+
+    let values = vec!["(?, ?, 'AVAILABLE', 0)"; (last - first + 1) as usize].join(",");
+    let query = format!(
+        r#"
+        INSERT INTO seat_info (flight_id, seat_number, seat_status, version)
+        VALUES {}
+        "#,
+        values
+    );
+    let mut query_builder = sqlx::query(&query);
+    for seat_number in first..=last {
+        query_builder = query_builder.bind(flight_id).bind(seat_number);
+    }
+    query_builder.execute(conn).await?;
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use crate::models::flight::{AvailableSeatsResponse, FlightSearchQuery, FlightSearchResponse};
+use crate::utils::config::SeatMapView;
+use crate::utils::tunables::SharedTunables;
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Time a search answer is served from the cache
+pub const DEFAULT_SEARCH_CACHE_TTL: Duration = Duration::from_secs(5);
+
+// Time a seat map is served from the cache, shorter as seats are picked one by one
+pub const DEFAULT_SEAT_MAP_CACHE_TTL: Duration = Duration::from_secs(2);
+
+// Most answers of each kind kept, further ones are not cached until some expire
+const MAX_CACHED_ANSWERS: usize = 10_000;
+
+type SeatMapKey = (i32, NaiveDate, SeatMapView);
+
+// A search answered from the database
+#[derive(Debug, Clone)]
+pub struct CachedSearch {
+    // Canonical cities searched, a search served from the cache still counts for them
+    pub departure_city: String,
+    pub destination_city: String,
+    pub response: FlightSearchResponse,
+}
+
+#[derive(Debug, Clone)]
+struct CachedSeatMap {
+    flight_id: i32,
+    response: AvailableSeatsResponse,
+}
+
+// Answers of flight searches and seat maps kept for a few seconds, so the same popular
+// searches do not each take a database connection bookings are waiting for. Each server
+// keeps its own. The booking path drops the answers showing a flight whose tickets or
+// seats it changed. Other changes, such as a flight getting tickets back that a cached
+// search left out, show once the answer expires.
+#[derive(Clone)]
+pub struct SearchCache {
+    // Cache TTLs, 0 to cache nothing
+    tunables: SharedTunables,
+    searches: Arc<Mutex<HashMap<FlightSearchQuery, (Instant, CachedSearch)>>>,
+    seat_maps: Arc<Mutex<HashMap<SeatMapKey, (Instant, CachedSeatMap)>>>,
+}
+
+impl SearchCache {
+    pub fn new(tunables: SharedTunables) -> Self {
+        SearchCache {
+            tunables,
+            searches: Arc::new(Mutex::new(HashMap::new())),
+            seat_maps: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn search(&self, query: &FlightSearchQuery) -> Option<CachedSearch> {
+        let ttl = self.tunables.current().search_cache_ttl;
+        let searches = self.searches.lock().unwrap();
+        let (cached_at, search) = searches.get(query)?;
+        (cached_at.elapsed() < ttl).then(|| search.clone())
+    }
+
+    pub fn store_search(&self, query: FlightSearchQuery, search: CachedSearch) {
+        let ttl = self.tunables.current().search_cache_ttl;
+        store(&self.searches, ttl, query, search);
+    }
+
+    pub fn seat_map(
+        &self,
+        flight_number: i32,
+        flight_date: NaiveDate,
+        view: SeatMapView,
+    ) -> Option<AvailableSeatsResponse> {
+        let ttl = self.tunables.current().seat_map_cache_ttl;
+        let seat_maps = self.seat_maps.lock().unwrap();
+        let (cached_at, seat_map) = seat_maps.get(&(flight_number, flight_date, view))?;
+        (cached_at.elapsed() < ttl).then(|| seat_map.response.clone())
+    }
+
+    pub fn store_seat_map(
+        &self,
+        flight_id: i32,
+        flight_number: i32,
+        flight_date: NaiveDate,
+        view: SeatMapView,
+        response: AvailableSeatsResponse,
+    ) {
+        let ttl = self.tunables.current().seat_map_cache_ttl;
+        store(
+            &self.seat_maps,
+            ttl,
+            (flight_number, flight_date, view),
+            CachedSeatMap {
+                flight_id,
+                response,
+            },
+        );
+    }
+
+    // Drop the answers showing the flight, after its tickets or seats changed
+    pub fn invalidate_flight(&self, flight_id: i32) {
+        self.searches.lock().unwrap().retain(|_, (_, search)| {
+            !search
+                .response
+                .flights
+                .iter()
+                .any(|flight| flight.flight_id == flight_id)
+        });
+        self.seat_maps
+            .lock()
+            .unwrap()
+            .retain(|_, (_, seat_map)| seat_map.flight_id != flight_id);
+    }
+
+    // Drop every seat map, after seats of any number of flights changed
+    pub fn invalidate_seat_maps(&self) {
+        self.seat_maps.lock().unwrap().clear();
+    }
+}
+
+fn store<K: std::hash::Hash + Eq, V>(
+    answers: &Mutex<HashMap<K, (Instant, V)>>,
+    ttl: Duration,
+    key: K,
+    answer: V,
+) {
+    if ttl.is_zero() {
+        return;
+    }
+    let mut answers = answers.lock().unwrap();
+    answers.retain(|_, (cached_at, _)| cached_at.elapsed() < ttl);
+    if answers.len() < MAX_CACHED_ANSWERS {
+        answers.insert(key, (Instant::now(), answer));
+    }
+}
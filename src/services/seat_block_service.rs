@@ -0,0 +1,375 @@
+use crate::models::aircraft::{Aircraft, SeatLayout};
+use crate::models::seat_block::{
+    CreateSeatBlockRuleRequest, SeatBlockRuleChange, SeatBlockRuleKind, SeatBlockRuleResponse,
+};
+use crate::utils::error::{AppError, AppResult};
+use chrono::{NaiveDate, NaiveDateTime};
+use sqlx::{MySql, MySqlPool, Transaction};
+use std::collections::HashSet;
+
+#[derive(Clone)]
+pub struct SeatBlockService {
+    pool: MySqlPool,
+}
+
+impl SeatBlockService {
+    pub fn new(pool: MySqlPool) -> Self {
+        SeatBlockService { pool }
+    }
+
+    // Add a blocking rule to a route and block its seats on the open flights of the route
+    // in the rule's date range. Flights generated later get it when they are created.
+    pub async fn create_rule(
+        &self,
+        admin_id: i32,
+        flight_number: i32,
+        request: CreateSeatBlockRuleRequest,
+    ) -> AppResult<SeatBlockRuleChange> {
+        if request
+            .end_date
+            .map_or(false, |end_date| end_date < request.start_date)
+        {
+            return Err(AppError::ValidationError(
+                "The end date of the rule is before its start date".into(),
+            ));
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            "SELECT flight_number FROM flight_route WHERE flight_number = ?",
+            flight_number
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Flight route {} not found", flight_number)))?;
+
+        let rule_id = sqlx::query!(
+            r#"
+            INSERT INTO seat_block_rule
+            (flight_number, rule, start_date, end_date, enabled, created_by, created_at, updated_at)
+            VALUES (?, ?, ?, ?, TRUE, ?, UTC_TIMESTAMP(), UTC_TIMESTAMP())
+            "#,
+            flight_number,
+            request.rule,
+            request.start_date,
+            request.end_date,
+            admin_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .last_insert_id() as i32;
+
+        let (updated_flights, seats) = block_open_flights(&mut tx, rule_id).await?;
+        tx.commit().await?;
+
+        Ok(SeatBlockRuleChange {
+            rule: self.get_rule(rule_id).await?,
+            updated_flights,
+            seats,
+        })
+    }
+
+    pub async fn list_rules(&self, flight_number: i32) -> AppResult<Vec<SeatBlockRuleResponse>> {
+        let rules = sqlx::query_as!(
+            SeatBlockRuleResponse,
+            r#"
+            SELECT
+                r.id as rule_id,
+                r.flight_number,
+                r.rule as "rule: SeatBlockRuleKind",
+                r.start_date as "start_date: NaiveDate",
+                r.end_date as "end_date: NaiveDate",
+                r.enabled as "enabled: bool",
+                (
+                    SELECT COUNT(*)
+                    FROM seat_info s
+                    WHERE s.block_rule_id = r.id AND s.seat_status = 'UNAVAILABLE'
+                ) as "blocked_seats!: i64",
+                r.created_at as "created_at: NaiveDateTime",
+                r.updated_at as "updated_at: NaiveDateTime"
+            FROM seat_block_rule r
+            WHERE r.flight_number = ?
+            ORDER BY r.id
+            "#,
+            flight_number
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rules)
+    }
+
+    // Turn a rule on or off. Turning it on blocks its seats on the open flights in its
+    // date range, turning it off gives the seats it blocked on open flights back to sale.
+    // Seats of departed and closed flights stay as they were.
+    pub async fn set_enabled(&self, rule_id: i32, enabled: bool) -> AppResult<SeatBlockRuleChange> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            "SELECT id FROM seat_block_rule WHERE id = ? FOR UPDATE",
+            rule_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Seat block rule {} not found", rule_id)))?;
+
+        sqlx::query!(
+            r#"
+            UPDATE seat_block_rule
+            SET enabled = ?, updated_at = UTC_TIMESTAMP()
+            WHERE id = ?
+            "#,
+            enabled,
+            rule_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let (updated_flights, seats) = if enabled {
+            block_open_flights(&mut tx, rule_id).await?
+        } else {
+            release_open_flights(&mut tx, rule_id).await?
+        };
+        tx.commit().await?;
+
+        Ok(SeatBlockRuleChange {
+            rule: self.get_rule(rule_id).await?,
+            updated_flights,
+            seats,
+        })
+    }
+
+    async fn get_rule(&self, rule_id: i32) -> AppResult<SeatBlockRuleResponse> {
+        let rule = sqlx::query_as!(
+            SeatBlockRuleResponse,
+            r#"
+            SELECT
+                r.id as rule_id,
+                r.flight_number,
+                r.rule as "rule: SeatBlockRuleKind",
+                r.start_date as "start_date: NaiveDate",
+                r.end_date as "end_date: NaiveDate",
+                r.enabled as "enabled: bool",
+                (
+                    SELECT COUNT(*)
+                    FROM seat_info s
+                    WHERE s.block_rule_id = r.id AND s.seat_status = 'UNAVAILABLE'
+                ) as "blocked_seats!: i64",
+                r.created_at as "created_at: NaiveDateTime",
+                r.updated_at as "updated_at: NaiveDateTime"
+            FROM seat_block_rule r
+            WHERE r.id = ?
+            "#,
+            rule_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Seat block rule {} not found", rule_id)))?;
+        Ok(rule)
+    }
+}
+
+// Block the seats the enabled rules of the flight's route cover on its date. Only
+// available seats are blocked, and no more than the tickets left for sale, so every
+// blocked seat takes one ticket off available_tickets. Returns the seats blocked.
+pub async fn apply_block_rules(tx: &mut Transaction<'_, MySql>, flight_id: i32) -> AppResult<i64> {
+    let flight = sqlx::query!(
+        r#"
+        SELECT
+            f.flight_number,
+            f.flight_date as "flight_date: NaiveDate",
+            f.available_tickets,
+            a.aircraft_id,
+            a.capacity,
+            a.seats_per_row,
+            a.exit_rows,
+            a.accessible_rows,
+            a.extra_legroom_rows
+        FROM flight f
+        JOIN flight_route fr ON f.flight_number = fr.flight_number
+        JOIN aircraft a ON COALESCE(f.aircraft_id, fr.aircraft_id) = a.aircraft_id
+        WHERE f.flight_id = ?
+        FOR UPDATE
+        "#,
+        flight_id
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Flight {} not found", flight_id)))?;
+
+    let rules = sqlx::query!(
+        r#"
+        SELECT id, rule as "rule: SeatBlockRuleKind"
+        FROM seat_block_rule
+        WHERE flight_number = ?
+        AND enabled
+        AND start_date <= ?
+        AND (end_date IS NULL OR end_date >= ?)
+        ORDER BY id
+        "#,
+        flight.flight_number,
+        flight.flight_date,
+        flight.flight_date
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    if rules.is_empty() {
+        return Ok(0);
+    }
+
+    let available_seats = sqlx::query_scalar!(
+        r#"
+        SELECT seat_number
+        FROM seat_info
+        WHERE flight_id = ? AND seat_status = 'AVAILABLE'
+        ORDER BY seat_number
+        FOR UPDATE
+        "#,
+        flight_id
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let layout = SeatLayout::from_aircraft(&Aircraft {
+        aircraft_id: flight.aircraft_id,
+        capacity: flight.capacity,
+        seats_per_row: flight.seats_per_row,
+        exit_rows: flight.exit_rows,
+        accessible_rows: flight.accessible_rows,
+        extra_legroom_rows: flight.extra_legroom_rows,
+    });
+    let mut remaining = flight.available_tickets.max(0) as usize;
+    let mut taken = HashSet::new();
+    let mut blocked = 0;
+    for rule in rules {
+        let seats: Vec<i32> = available_seats
+            .iter()
+            .copied()
+            .filter(|seat_number| {
+                !taken.contains(seat_number)
+                    && rule.rule.blocks(&layout, flight.capacity, *seat_number)
+            })
+            .take(remaining)
+            .collect();
+        if seats.is_empty() {
+            continue;
+        }
+
+        let query = format!(
+            r#"
+            UPDATE seat_info
+            SET seat_status = 'UNAVAILABLE', block_rule_id = ?, version = version + 1
+            WHERE flight_id = ? AND seat_status = 'AVAILABLE' AND seat_number IN ({})
+            "#,
+            vec!["?"; seats.len()].join(",")
+        );
+        let mut query_builder = sqlx::query(&query).bind(rule.id).bind(flight_id);
+        for seat_number in &seats {
+            query_builder = query_builder.bind(seat_number);
+        }
+        let rows = query_builder.execute(&mut **tx).await?.rows_affected() as usize;
+
+        remaining -= rows;
+        blocked += rows as i64;
+        taken.extend(seats);
+    }
+
+    if blocked > 0 {
+        sqlx::query!(
+            r#"
+            UPDATE flight
+            SET available_tickets = available_tickets - ?, version = version + 1
+            WHERE flight_id = ?
+            "#,
+            blocked,
+            flight_id
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(blocked)
+}
+
+// Flights of the rule's route in its date range that have not departed and are not closed
+async fn open_flights(tx: &mut Transaction<'_, MySql>, rule_id: i32) -> AppResult<Vec<i32>> {
+    let flights = sqlx::query_scalar!(
+        r#"
+        SELECT f.flight_id
+        FROM seat_block_rule r
+        JOIN flight f ON f.flight_number = r.flight_number
+        WHERE r.id = ?
+        AND f.flight_date >= GREATEST(r.start_date, CURDATE())
+        AND (r.end_date IS NULL OR f.flight_date <= r.end_date)
+        AND f.status IN ('SCHEDULED', 'DELAYED')
+        AND f.closed_at IS NULL
+        ORDER BY f.flight_date
+        "#,
+        rule_id
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    Ok(flights)
+}
+
+// Returns the flights whose seats were blocked and the seats blocked
+async fn block_open_flights(
+    tx: &mut Transaction<'_, MySql>,
+    rule_id: i32,
+) -> AppResult<(usize, i64)> {
+    let mut updated_flights = 0;
+    let mut seats = 0;
+    for flight_id in open_flights(tx, rule_id).await? {
+        let blocked = apply_block_rules(tx, flight_id).await?;
+        if blocked > 0 {
+            updated_flights += 1;
+            seats += blocked;
+        }
+    }
+    Ok((updated_flights, seats))
+}
+
+// Give the seats the rule blocked on open flights back to sale, apart from the ones
+// another enabled rule covers. Returns the flights whose seats were given back and the
+// seats given back.
+async fn release_open_flights(
+    tx: &mut Transaction<'_, MySql>,
+    rule_id: i32,
+) -> AppResult<(usize, i64)> {
+    let mut updated_flights = 0;
+    let mut seats = 0;
+    for flight_id in open_flights(tx, rule_id).await? {
+        let released = sqlx::query!(
+            r#"
+            UPDATE seat_info
+            SET seat_status = 'AVAILABLE', block_rule_id = NULL, version = version + 1
+            WHERE flight_id = ? AND block_rule_id = ? AND seat_status = 'UNAVAILABLE'
+            "#,
+            flight_id,
+            rule_id
+        )
+        .execute(&mut **tx)
+        .await?
+        .rows_affected() as i64;
+        if released == 0 {
+            continue;
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE flight
+            SET available_tickets = available_tickets + ?, version = version + 1
+            WHERE flight_id = ?
+            "#,
+            released,
+            flight_id
+        )
+        .execute(&mut **tx)
+        .await?;
+        let blocked_again = apply_block_rules(tx, flight_id).await?;
+        if released > blocked_again {
+            updated_flights += 1;
+            seats += released - blocked_again;
+        }
+    }
+    Ok((updated_flights, seats))
+}
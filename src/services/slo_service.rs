@@ -0,0 +1,240 @@
+use crate::models::slo::{SloAlert, SloReportResponse, SloStatus};
+use crate::utils::config::SloConfig;
+use crate::utils::metrics::{RequestCounts, RequestMetrics};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Share of the booking requests that must finish within the p99 latency
+const BOOKING_LATENCY_TARGET: f64 = 0.99;
+
+// A way of telling the operators an objective is in trouble
+#[rocket::async_trait]
+pub trait SloAlerter: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn alert(&self, alert: &SloAlert) -> Result<(), String>;
+}
+
+// Posts the alerts as JSON to a webhook, e.g. of a chat or paging service
+pub struct WebhookAlerter {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookAlerter {
+    pub fn new(url: &str) -> Self {
+        WebhookAlerter {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl SloAlerter for WebhookAlerter {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn alert(&self, alert: &SloAlert) -> Result<(), String> {
+        let body = serde_json::to_vec(alert).map_err(|e| e.to_string())?;
+        let response = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("webhook answered {}", response.status()));
+        }
+        Ok(())
+    }
+}
+
+// Only logs the alerts, when no webhook is configured
+pub struct LogAlerter;
+
+#[rocket::async_trait]
+impl SloAlerter for LogAlerter {
+    fn name(&self) -> &'static str {
+        "log"
+    }
+
+    async fn alert(&self, alert: &SloAlert) -> Result<(), String> {
+        tracing::warn!(
+            objective = %alert.objective,
+            burn_rate = alert.burn_rate,
+            "service level objective burning its error budget"
+        );
+        Ok(())
+    }
+}
+
+// Alerter for the configuration, the webhook when one is set and logging otherwise
+pub fn alerter_for(config: &SloConfig) -> Arc<dyn SloAlerter> {
+    match &config.alert_webhook_url {
+        Some(url) => Arc::new(WebhookAlerter::new(url)),
+        None => Arc::new(LogAlerter),
+    }
+}
+
+// Checks the requests this server answered against the objectives: 99% of the
+// bookings within the p99 latency, and at most the error rate of server errors
+#[derive(Clone)]
+pub struct SloService {
+    metrics: RequestMetrics,
+    config: SloConfig,
+    alerter: Arc<dyn SloAlerter>,
+    // Objectives alerted about that have not recovered yet
+    alerting: Arc<Mutex<HashSet<String>>>,
+}
+
+impl SloService {
+    pub fn new(metrics: RequestMetrics, config: SloConfig, alerter: Arc<dyn SloAlerter>) -> Self {
+        SloService {
+            metrics,
+            config,
+            alerter,
+            alerting: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    pub fn report(&self) -> SloReportResponse {
+        let window = self.metrics.counts(self.config.window_minutes);
+        let recent = self.metrics.counts(self.config.alert_window_minutes);
+        SloReportResponse {
+            window_minutes: self.config.window_minutes,
+            alert_window_minutes: self.config.alert_window_minutes,
+            objectives: self
+                .objectives()
+                .iter()
+                .map(|objective| self.status(objective, &window, &recent))
+                .collect(),
+        }
+    }
+
+    // Alert about the objectives that started burning their error budget too fast
+    // since the last check, returning the alerts sent
+    pub async fn check(&self) -> Vec<SloAlert> {
+        let report = self.report();
+        let recent = self.metrics.counts(self.config.alert_window_minutes);
+        let mut alerts = Vec::new();
+        for (status, objective) in report.objectives.iter().zip(self.objectives()) {
+            let newly_alerting = {
+                let mut alerting = self.alerting.lock().unwrap();
+                if status.alerting {
+                    alerting.insert(status.name.clone())
+                } else {
+                    if alerting.remove(&status.name) {
+                        tracing::info!(objective = %status.name, "service level objective recovered");
+                    }
+                    false
+                }
+            };
+            if newly_alerting {
+                let (requests, good_requests) = (objective.count)(&recent);
+                alerts.push(SloAlert {
+                    objective: status.name.clone(),
+                    description: status.description.clone(),
+                    burn_rate: status.burn_rate,
+                    alert_burn_rate: self.config.alert_burn_rate,
+                    requests,
+                    good_requests,
+                    alert_window_minutes: self.config.alert_window_minutes,
+                });
+            }
+        }
+
+        for alert in &alerts {
+            if let Err(e) = self.alerter.alert(alert).await {
+                tracing::error!(
+                    error = %e,
+                    alerter = self.alerter.name(),
+                    objective = %alert.objective,
+                    "failed to send service level objective alert"
+                );
+            }
+        }
+        alerts
+    }
+
+    // Check the objectives every period
+    pub fn spawn_monitor(&self, period: Duration) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                service.check().await;
+            }
+        });
+    }
+
+    fn objectives(&self) -> [Objective; 2] {
+        [
+            Objective {
+                name: "booking_latency",
+                description: format!(
+                    "99% of the bookings finish within {} ms",
+                    self.config.booking_p99_latency_ms
+                ),
+                target: BOOKING_LATENCY_TARGET,
+                count: |counts| (counts.bookings, counts.bookings - counts.slow_bookings),
+            },
+            Objective {
+                name: "error_rate",
+                description: format!(
+                    "At most {}% of the requests fail with a server error",
+                    self.config.max_error_rate * 100.0
+                ),
+                target: 1.0 - self.config.max_error_rate,
+                count: |counts| (counts.requests, counts.requests - counts.server_errors),
+            },
+        ]
+    }
+
+    fn status(
+        &self,
+        objective: &Objective,
+        window: &RequestCounts,
+        recent: &RequestCounts,
+    ) -> SloStatus {
+        let (requests, good_requests) = (objective.count)(window);
+        let budget = 1.0 - objective.target;
+        let compliance = (requests > 0).then(|| good_requests as f64 / requests as f64);
+        let error_budget_remaining =
+            compliance.map_or(1.0, |compliance| 1.0 - (1.0 - compliance) / budget);
+
+        let (recent_requests, recent_good) = (objective.count)(recent);
+        let burn_rate = if recent_requests > 0 {
+            (recent_requests - recent_good) as f64 / recent_requests as f64 / budget
+        } else {
+            0.0
+        };
+        SloStatus {
+            name: objective.name.to_string(),
+            description: objective.description.clone(),
+            target: objective.target,
+            requests,
+            good_requests,
+            compliance,
+            error_budget_remaining,
+            burn_rate,
+            alerting: recent_requests >= self.config.alert_min_requests
+                && burn_rate >= self.config.alert_burn_rate,
+        }
+    }
+}
+
+struct Objective {
+    name: &'static str,
+    description: String,
+    // Share of the requests that must be good
+    target: f64,
+    // Requests the objective covers and how many of them were good
+    count: fn(&RequestCounts) -> (u64, u64),
+}
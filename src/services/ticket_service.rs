@@ -1,128 +1,814 @@
+use crate::models::aircraft::{Aircraft, SeatAttributes, SeatLayout};
+use crate::models::booking_rules::{BookingRules, DuplicatePolicy, LegFacts};
+use crate::models::checkin::{
+    BoardingPass, CheckinRequest, CARRIER_CODE, CHECKIN_CLOSES_MINUTES, CHECKIN_OPENS_HOURS,
+};
+use crate::models::db_enum::DbEnum;
+use crate::models::fare::{self, CabinInventory, Fare, FareClass, FarePrice};
 use crate::models::flight::Flight;
-use crate::models::flight::SeatStatus;
+use crate::models::flight::{
+    departure_utc, overbooked_capacity, FlightStatus, FlightTimes, SeatStatus,
+};
 use crate::models::ticket::{
-    BookingHistoryDetail, BookingHistoryResponse, FlightBookingRequest, FlightBookingResponse,
-    SeatBookingRequest, TicketBookingRequest, TicketBookingResponse,
+    BookingHistoryDetail, BookingHistoryResponse, BookingStatus, BookingValidationResponse,
+    FailedLegResponse, TicketCorrectionRequest, TicketCorrectionResponse, FlightBookingRequest, FlightBookingResponse, GuardianContact,
+    LegStatus, LegValidationResult, RebookedPassenger, RebookingOption, RebookingOptionsResponse,
+    RebookingStatus, RebookingSummary, SelfRebookingRequest,
+    SeatBookingRequest, SeatHoldRequest, SeatHoldResponse, TicketSeat,
+    TicketBookingRequest, TicketBookingResponse, TicketByReferenceResponse, MAX_SEAT_HOLD_MINUTES,
+    PREFERRED_SEAT_UNAVAILABLE_WARNING, SEAT_HOLD_MINUTES, new_booking_reference,
+    BOOKING_REFERENCE_LENGTH,
 };
-use crate::utils::error::{AppError, AppResult};
-use chrono::{NaiveDate, NaiveTime};
-use rand::Rng;
-use sqlx::MySqlPool;
+use crate::models::payment::{
+    PaymentSummary, SeatPriceQuote, DEFAULT_CURRENCY, PAYMENT_TIMEOUT_MINUTES,
+};
+use crate::models::promo::AppliedPromoCode;
+use crate::models::ssr;
+use crate::services::event_bus::DomainEvent;
+use crate::services::fare_service::FareService;
+use crate::services::operation_log::{Operation, OperationLog, OperationOutcome};
+use crate::services::outbox;
+use crate::services::promo_code_service;
+use crate::services::search_cache::SearchCache;
+use crate::utils::document::{self, Document, DocumentFormat};
+use crate::utils::error::{is_unique_violation, AppError, AppResult, RetryHints};
+use crate::utils::locale::DocumentLocale;
+use crate::utils::public_id::{new_public_id, parse_public_id};
+use crate::utils::region::DEFAULT_DATA_REGION;
+use crate::utils::retry::{RetryMetrics, RetryPolicy};
+use crate::utils::seat_lock::{self, SeatLock, SEAT_LOCK_TTL};
+use crate::utils::experiment::{self, NEAREST_SEAT_VARIANT, SEAT_ASSIGNMENT};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime};
+use rust_decimal::Decimal;
+use sqlx::{MySql, MySqlConnection, MySqlPool, Transaction};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::instrument;
+
+// Suggested wait before retrying a fully booked flight
+const FULL_FLIGHT_RETRY_AFTER_MS: u64 = 60_000;
+
+// How many days around the requested date to look for alternative flights
+const ALTERNATIVE_DATE_WINDOW_DAYS: i64 = 3;
+
+// Maximum number of alternatives returned in retry hints
+const MAX_ALTERNATIVES: i64 = 5;
+
+// Booking references drawn before giving up on finding a free one
+pub const MAX_BOOKING_REFERENCE_ATTEMPTS: u32 = 5;
+
+// Tickets given a public id per query when assigning them to older tickets
+const PUBLIC_ID_BATCH_SIZE: i64 = 500;
+
+// Attempts at booking a seat whose version keeps changing before giving up
+pub const DEFAULT_SEAT_BOOKING_ATTEMPTS: u32 = 10;
+
+// Age from which a booking still being placed is taken as interrupted, far longer than
+// placing one takes
+pub const BOOKING_SAGA_TIMEOUT_MINUTES: i64 = 10;
+
+// Delay from which passengers may move to another flight of the route themselves
+pub const SIGNIFICANT_DELAY_MINUTES: i32 = 180;
+
+// How many days around a disrupted flight its passengers may rebook on
+pub const SELF_REBOOKING_WINDOW_DAYS: i64 = 2;
+
+// Seat bookings that lost their version check or seat lock, and those that gave up
+// because of it, since the service started
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeatConflictStats {
+    pub conflicts: u64,
+    pub retries_exhausted: u64,
+    // Time spent waiting to try again
+    pub backoff: Duration,
+}
 
 #[derive(Clone)]
 pub struct TicketService {
     pool: MySqlPool,
+    fare_service: FareService,
+    operation_log: Option<OperationLog>,
+    rules: Arc<BookingRules>,
+    data_region: String,
+    seat_retry: RetryPolicy,
+    seat_retries: RetryMetrics,
+    forced_seat_conflicts: Option<Arc<AtomicU32>>,
+    search_cache: Option<SearchCache>,
+    seat_lock: Option<Arc<dyn SeatLock>>,
 }
 
 impl TicketService {
     pub fn new(pool: MySqlPool) -> Self {
-        TicketService { pool }
+        TicketService {
+            fare_service: FareService::new(pool.clone()),
+            pool,
+            operation_log: None,
+            rules: Arc::new(BookingRules::default()),
+            data_region: DEFAULT_DATA_REGION.to_string(),
+            seat_retry: RetryPolicy::new(DEFAULT_SEAT_BOOKING_ATTEMPTS),
+            seat_retries: RetryMetrics::new(),
+            forced_seat_conflicts: None,
+            search_cache: None,
+            seat_lock: None,
+        }
+    }
+
+    // Give up booking a seat after this many lost version checks, at least one attempt
+    // is always made
+    pub fn with_seat_booking_attempts(mut self, attempts: u32) -> Self {
+        self.seat_retry = RetryPolicy::new(attempts);
+        self
+    }
+
+    // Make the next `conflicts` seat booking attempts lose their version check as if
+    // another booking changed the seat first. Only meant for tests, which cannot win
+    // such races on purpose.
+    pub fn with_forced_seat_conflicts(mut self, conflicts: u32) -> Self {
+        self.forced_seat_conflicts = Some(Arc::new(AtomicU32::new(conflicts)));
+        self
+    }
+
+    // Lock a seat before booking it, so customers picking the same seat wait on the lock
+    // instead of racing each other's version checks in the database
+    pub fn with_seat_lock(mut self, seat_lock: Arc<dyn SeatLock>) -> Self {
+        self.seat_lock = Some(seat_lock);
+        self
+    }
+
+    fn take_forced_seat_conflict(&self) -> bool {
+        self.forced_seat_conflicts
+            .as_ref()
+            .map_or(false, |remaining| {
+                remaining
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                    .is_ok()
+            })
+    }
+
+    pub fn seat_conflict_stats(&self) -> SeatConflictStats {
+        let stats = self.seat_retries.stats();
+        SeatConflictStats {
+            conflicts: stats.retries + stats.exhausted,
+            retries_exhausted: stats.exhausted,
+            backoff: stats.backoff,
+        }
+    }
+
+    // Data region new bookings are tagged with
+    pub fn with_data_region(mut self, data_region: String) -> Self {
+        self.data_region = data_region;
+        self
+    }
+
+    // Apply the given booking policy instead of the built-in one
+    pub fn with_rules(mut self, rules: BookingRules) -> Self {
+        self.rules = Arc::new(rules);
+        self
+    }
+
+    // The same booking policy against another database, e.g. the sandbox. The operation
+    // log, search cache and seat lock belong to this database and are left out.
+    pub fn for_database(&self, pool: MySqlPool) -> Self {
+        TicketService {
+            rules: self.rules.clone(),
+            data_region: self.data_region.clone(),
+            seat_retry: self.seat_retry,
+            ..TicketService::new(pool)
+        }
+    }
+
+    // Record booking operations in the given log so they can be replayed after a restore
+    pub fn with_operation_log(mut self, operation_log: OperationLog) -> Self {
+        self.operation_log = Some(operation_log);
+        self
+    }
+
+    // Drop the cached searches and seat maps showing the flights whose tickets or seats
+    // change
+    pub fn with_search_cache(mut self, search_cache: SearchCache) -> Self {
+        self.search_cache = Some(search_cache);
+        self
+    }
+
+    fn invalidate_cached(&self, flight_id: i32) {
+        if let Some(search_cache) = &self.search_cache {
+            search_cache.invalidate_flight(flight_id);
+        }
+    }
+
+    async fn record<T>(&self, operation: Operation, result: &AppResult<T>) {
+        if let Some(operation_log) = &self.operation_log {
+            let outcome = match result {
+                Ok(_) => OperationOutcome::Succeeded,
+                Err(e) => OperationOutcome::Failed {
+                    error: e.to_string(),
+                },
+            };
+            if let Err(e) = operation_log.append(operation, outcome).await {
+                tracing::error!(error = %e, "failed to write operation log");
+            }
+        }
     }
 
+    #[instrument(skip(self, request))]
     pub async fn book_ticket(
         &self,
         user_id: i32,
         request: TicketBookingRequest,
     ) -> AppResult<TicketBookingResponse> {
+        let result = self.place_booking(user_id, request.clone()).await;
+        self.record(Operation::BookTicket { user_id, request }, &result)
+            .await;
+        result
+    }
+
+    async fn place_booking(
+        &self,
+        user_id: i32,
+        request: TicketBookingRequest,
+    ) -> AppResult<TicketBookingResponse> {
+        let check = self.check_request(user_id, &request).await?;
+        if let Some(issue) = check.issues.into_iter().next() {
+            return Err(issue);
+        }
+        let unaccompanied_minor = check.unaccompanied_minor;
+
+        // The booking is placed as a saga recording every ticket it takes from a flight
+        // and every ticket it books, so a booking that fails halfway is reverted as a
+        // whole. One interrupted by a crash is reverted by the recovery task.
+        let saga_id = self.start_booking_saga(user_id).await?;
+        let result = self
+            .book_itinerary(saga_id, user_id, &request, unaccompanied_minor)
+            .await;
+        if result.is_err() {
+            // Left to the recovery task when it cannot be reverted now
+            if let Err(e) = self.revert_booking(saga_id).await {
+                tracing::error!(saga_id, error = %e, "failed to revert booking");
+            }
+        }
+        result
+    }
+
+    async fn book_itinerary(
+        &self,
+        saga_id: i32,
+        user_id: i32,
+        request: &TicketBookingRequest,
+        unaccompanied_minor: bool,
+    ) -> AppResult<TicketBookingResponse> {
+        let guardian = if unaccompanied_minor {
+            request.guardian.as_ref()
+        } else {
+            None
+        };
+
+        // A connecting itinerary takes a ticket from every leg before confirming any,
+        // so a full later leg fails the booking instead of stranding the passenger
+        let mut tickets_left = vec![None; request.flights.len()];
+        if !request.allow_partial && request.flights.len() > 1 {
+            match self
+                .claim_itinerary_inventory(saga_id, &request.flights)
+                .await
+            {
+                Ok(left) => tickets_left = left.into_iter().map(Some).collect(),
+                Err(e) => return Err(itinerary_error(e)),
+            }
+        }
+
         let mut flight_booking_results = Vec::new();
+        let mut failed_legs = Vec::new();
         let mut fail_to_choose_seat = false;
-        for flight_request in &request.flights {
-            let has_prefered_seat = flight_request.preferred_seat.is_some();
+        for (index, flight_request) in request.flights.iter().enumerate() {
             let flight_booking_result = self
-                .book_ticket_for_flight(user_id, flight_request.clone())
+                .book_ticket_for_flight(
+                    saga_id,
+                    user_id,
+                    flight_request.clone(),
+                    &request.flights[..index],
+                    guardian,
+                    tickets_left[index],
+                )
                 .await;
 
             match flight_booking_result {
                 Ok(r) => {
-                    if has_prefered_seat && r.seat_number.is_none() {
+                    if r.status == LegStatus::ConfirmedSeatUnavailable {
                         fail_to_choose_seat = true;
                     }
                     flight_booking_results.push(r);
                 }
-                Err(e) => {
-                    // revert existing bookings
-                    for existing_booking in &flight_booking_results {
-                        self.revert_booking(existing_booking).await?;
-                    }
-                    return Err(AppError::ValidationError(format!(
-                        "Failed to book some of your flights, please try again: {}",
-                        e.to_string()
-                    )));
+                Err(e) if request.allow_partial => {
+                    // report the failed leg and keep going with the rest of the itinerary
+                    failed_legs.push(FailedLegResponse {
+                        flight_number: flight_request.flight_number,
+                        flight_date: flight_request.flight_date,
+                        status: LegStatus::Failed,
+                        reason: e.to_string(),
+                    });
+                }
+                // the legs booked so far are reverted with the saga
+                Err(e) => return Err(itinerary_error(e)),
+            }
+        }
+        // Nothing was booked at all, so there is no partial success to report
+        if flight_booking_results.is_empty() && !failed_legs.is_empty() {
+            return Err(AppError::ValidationError(format!(
+                "Failed to book all of your flights, please try again: {}",
+                failed_legs
+                    .iter()
+                    .map(|leg| leg.reason.clone())
+                    .collect::<Vec<String>>()
+                    .join("; ")
+            )));
+        }
+
+        // Group the tickets into a booking, with a pending payment when there is a fare to pay
+        let (booking_id, payment, promo_code) = self
+            .create_booking(
+                saga_id,
+                user_id,
+                &mut flight_booking_results,
+                request.promo_code.as_deref(),
+            )
+            .await?;
+        if payment.is_some() {
+            for booking in flight_booking_results.iter_mut() {
+                if booking.status == LegStatus::Confirmed {
+                    booking.status = LegStatus::PendingPayment;
                 }
             }
         }
+
+        let mut warnings = Vec::new();
+        if fail_to_choose_seat {
+            warnings.push(PREFERRED_SEAT_UNAVAILABLE_WARNING.to_string());
+        }
+        for leg in &failed_legs {
+            warnings.push(format!(
+                "Flight {} on {} could not be booked: {}",
+                leg.flight_number, leg.flight_date, leg.reason
+            ));
+        }
+
         Ok(TicketBookingResponse {
-            booking_status: if !fail_to_choose_seat {
-                "Confirmed".to_string()
+            booking_status: if !failed_legs.is_empty() {
+                BookingStatus::PartiallyFailed
+            } else if payment.is_some() {
+                BookingStatus::PendingPayment
+            } else if fail_to_choose_seat {
+                BookingStatus::ConfirmedSeatUnavailable
+            } else {
+                BookingStatus::Confirmed
+            },
+            legacy_booking_status: if !failed_legs.is_empty() {
+                "Partially confirmed".to_string()
+            } else if payment.is_some() {
+                "Pending payment".to_string()
+            } else if unaccompanied_minor {
+                "Confirmed (Unaccompanied Minor)".to_string()
             } else {
-                "Confirmed booking, however the preferred seat is currently unavaiable, please try again later.".to_string()
+                "Confirmed".to_string()
             },
+            unaccompanied_minor,
+            booking_id,
+            payment,
+            promo_code,
             flight_bookings: flight_booking_results,
+            failed_legs,
+            warnings,
         })
     }
 
-    async fn revert_booking(&self, request: &FlightBookingResponse) -> AppResult<()> {
-        let flight = sqlx::query!(
+    // Create the booking grouping the given tickets, with the discount of the promo code
+    // taken off their prices. When the prices add up to a non-zero amount, a pending
+    // payment is created that must be confirmed before it expires.
+    async fn create_booking(
+        &self,
+        saga_id: i32,
+        user_id: i32,
+        tickets: &mut [FlightBookingResponse],
+        promo_code: Option<&str>,
+    ) -> AppResult<(i32, Option<PaymentSummary>, Option<AppliedPromoCode>)> {
+        // All tickets of a booking are paid together, in a single currency
+        let currency = tickets
+            .first()
+            .map(|ticket| ticket.currency.clone())
+            .unwrap_or_else(|| DEFAULT_CURRENCY.to_string());
+        if tickets.iter().any(|ticket| ticket.currency != currency) {
+            return Err(AppError::ValidationError(
+                "All flights of a booking must be priced in the same currency".to_string(),
+            ));
+        }
+        let fare: Decimal = tickets.iter().map(|ticket| ticket.price).sum();
+
+        let mut tx = self.pool.begin().await?;
+
+        // The saga ends with the booking, giving back the tickets of failed legs
+        let released_flights = self
+            .end_booking_saga(&mut tx, saga_id, false)
+            .await?
+            .ok_or_else(|| {
+                AppError::Conflict(
+                    "The booking took too long and was cancelled, please try again".into(),
+                )
+            })?;
+
+        let status = if fare > Decimal::ZERO {
+            "PENDING_PAYMENT"
+        } else {
+            "CONFIRMED"
+        };
+        let booking_id = sqlx::query!(
             r#"
-            SELECT flight_id
+            INSERT INTO booking (customer_id, status, created_at, data_region)
+            VALUES (?, ?, UTC_TIMESTAMP(), ?)
+            "#,
+            user_id,
+            status,
+            self.data_region
+        )
+        .execute(&mut *tx)
+        .await?
+        .last_insert_id() as i32;
+
+        let applied_promo_code = match promo_code {
+            Some(code) => {
+                let prices: Vec<Decimal> = tickets.iter().map(|ticket| ticket.price).collect();
+                let (discounted, applied) = promo_code_service::redeem(
+                    &mut tx, code, user_id, booking_id, &prices, &currency,
+                )
+                .await?;
+                for (ticket, price) in tickets.iter_mut().zip(discounted) {
+                    ticket.price = price;
+                }
+                Some(applied)
+            }
+            None => None,
+        };
+
+        for ticket in tickets.iter() {
+            sqlx::query!(
+                "UPDATE ticket SET booking_id = ?, price = ? WHERE id = ?",
+                booking_id,
+                ticket.price,
+                ticket.ticket_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        // Nothing is left to pay when the discount covers the whole fare
+        let amount: Decimal = tickets.iter().map(|ticket| ticket.price).sum();
+        if fare > Decimal::ZERO && amount == Decimal::ZERO {
+            sqlx::query!(
+                "UPDATE booking SET status = 'CONFIRMED' WHERE id = ?",
+                booking_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        // The tickets are only announced once the whole booking is in place, so a
+        // booking reverted halfway never notifies anyone
+        let booked = sqlx::query!(
+            r#"
+            SELECT id, flight_id, flight_number, flight_date as "flight_date: NaiveDate"
+            FROM ticket
+            WHERE booking_id = ?
+            ORDER BY id
+            "#,
+            booking_id
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let booked_flights: Vec<i32> = booked.iter().map(|ticket| ticket.flight_id).collect();
+        let experiments = experiment::assignments_for(user_id);
+        for ticket in booked {
+            outbox::enqueue(
+                &mut tx,
+                &DomainEvent::TicketBooked {
+                    ticket_id: ticket.id,
+                    customer_id: user_id,
+                    flight_id: ticket.flight_id,
+                    flight_number: ticket.flight_number,
+                    flight_date: ticket.flight_date,
+                    experiments: experiments.clone(),
+                },
+            )
+            .await?;
+        }
+
+        let mut payment = None;
+        if amount > Decimal::ZERO {
+            let expires_at = chrono::Utc::now().naive_utc()
+                + chrono::Duration::minutes(PAYMENT_TIMEOUT_MINUTES);
+            let payment_id = sqlx::query!(
+                r#"
+                INSERT INTO payment (booking_id, amount, currency, status, created_at, expires_at)
+                VALUES (?, ?, ?, 'PENDING', UTC_TIMESTAMP(), ?)
+                "#,
+                booking_id,
+                amount,
+                currency,
+                expires_at
+            )
+            .execute(&mut *tx)
+            .await?
+            .last_insert_id() as i32;
+
+            payment = Some(PaymentSummary {
+                payment_id,
+                amount,
+                currency,
+                expires_at,
+            });
+        }
+
+        tx.commit().await?;
+        for flight_id in booked_flights.into_iter().chain(released_flights) {
+            self.invalidate_cached(flight_id);
+        }
+        Ok((booking_id, payment, applied_promo_code))
+    }
+
+    // Cancel a ticket and give its ticket and seat back to the flight inventory.
+    // Returns false when the ticket was already released.
+    #[instrument(skip(self))]
+    pub async fn release_ticket(&self, ticket_id: i32) -> AppResult<bool> {
+        let mut tx = self.pool.begin().await?;
+        let released = self.release_ticket_in(&mut tx, ticket_id).await?;
+        tx.commit().await?;
+        match released {
+            Some(ticket) => {
+                self.ticket_released(ticket).await;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // Release the ticket within the transaction of the caller, so that what the caller
+    // records about it, e.g. a refund, commits with the release. Returns None when the
+    // ticket was already released. Pass the ticket to ticket_released once committed.
+    pub(crate) async fn release_ticket_in(
+        &self,
+        tx: &mut Transaction<'_, MySql>,
+        ticket_id: i32,
+    ) -> AppResult<Option<ReleasedTicket>> {
+        let ticket = sqlx::query!(
+            r#"
+            SELECT
+                customer_id,
+                booking_id,
+                flight_id,
+                flight_number,
+                flight_date as "flight_date: NaiveDate",
+                seat_number,
+                price
             FROM ticket
             WHERE id = ?
+            FOR UPDATE
             "#,
-            request.ticket_id
+            ticket_id
         )
-        .fetch_one(&self.pool)
+        .fetch_optional(&mut **tx)
         .await?;
 
+        let ticket = match ticket {
+            Some(ticket) => ticket,
+            // Already released
+            None => return Ok(None),
+        };
+        self.ensure_flight_open(ticket.flight_id).await?;
+
+        sqlx::query!("DELETE FROM ticket WHERE id = ?", ticket_id)
+            .execute(&mut **tx)
+            .await?;
+
         sqlx::query!(
             r#"
             UPDATE flight
-            set available_tickets = available_tickets + 1, 
+            SET available_tickets = available_tickets + 1,
                 version = version + 1
-            where flight_id = ?
+            WHERE flight_id = ?
             "#,
-            flight.flight_id,
+            ticket.flight_id
         )
-        .execute(&self.pool)
+        .execute(&mut **tx)
         .await?;
 
-        sqlx::query!(
+        if let Some(seat_number) = ticket.seat_number {
+            sqlx::query!(
+                r#"
+                UPDATE seat_info
+                SET seat_status = 'AVAILABLE',
+                    version = version + 1
+                WHERE flight_id = ? AND seat_number = ?
+                "#,
+                ticket.flight_id,
+                seat_number
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        if let Some(booking_id) = ticket.booking_id {
+            reduce_pending_payment(tx, booking_id, ticket.price).await?;
+        }
+
+        outbox::enqueue(
+            tx,
+            &DomainEvent::TicketCancelled {
+                ticket_id,
+                customer_id: ticket.customer_id,
+                flight_number: ticket.flight_number,
+                flight_date: ticket.flight_date,
+                seat_number: ticket.seat_number,
+            },
+        )
+        .await?;
+
+        Ok(Some(ReleasedTicket {
+            customer_id: ticket.customer_id,
+            flight_id: ticket.flight_id,
+            flight_number: ticket.flight_number,
+            flight_date: ticket.flight_date,
+        }))
+    }
+
+    // Refresh the cached availability of the flight of a released ticket and log the
+    // release, once its transaction has committed
+    pub(crate) async fn ticket_released(&self, ticket: ReleasedTicket) {
+        self.invalidate_cached(ticket.flight_id);
+        self.record::<()>(
+            Operation::ReleaseTicket {
+                customer_id: ticket.customer_id,
+                flight_number: ticket.flight_number,
+                flight_date: ticket.flight_date,
+            },
+            &Ok(()),
+        )
+        .await;
+    }
+
+    // Release the ticket of a customer on a flight, if any
+    #[instrument(skip(self))]
+    pub async fn release_ticket_for_flight(
+        &self,
+        customer_id: i32,
+        flight_number: i32,
+        flight_date: NaiveDate,
+    ) -> AppResult<()> {
+        let ticket = sqlx::query!(
             r#"
-            DELETE FROM ticket
-            WHERE id = ?
+            SELECT id
+            FROM ticket
+            WHERE customer_id = ? AND flight_number = ? AND flight_date = ?
             "#,
-            flight.flight_id,
+            customer_id,
+            flight_number,
+            flight_date
         )
-        .execute(&self.pool)
-        .await?;
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Ticket not found".into()))?;
 
-        match request.seat_number {
-            Some(s) => {
-                sqlx::query!(
+        self.release_ticket(ticket.id).await.map(|_| ())
+    }
+
+    // Run all booking validations without mutating anything. These are the checks
+    // the booking itself runs, so a request that validates books and one that does
+    // not validate fails to book.
+    #[instrument(skip(self, request))]
+    pub async fn validate_booking(
+        &self,
+        user_id: i32,
+        request: &TicketBookingRequest,
+    ) -> AppResult<BookingValidationResponse> {
+        let check = self.check_request(user_id, request).await?;
+        let issues: Vec<String> = check.issues.iter().map(AppError::to_string).collect();
+
+        let mut legs = Vec::new();
+        for (index, flight_request) in request.flights.iter().enumerate() {
+            let leg = self
+                .check_leg(
+                    user_id,
+                    flight_request,
+                    &request.flights[..index],
+                    check.unaccompanied_minor,
+                    false,
+                )
+                .await?;
+
+            // The booking still goes through without its preferred seat, so seat
+            // problems are only warnings
+            let mut warnings = Vec::new();
+            if let (Some(flight), Some(seat_number)) = (&leg.flight, flight_request.preferred_seat)
+            {
+                if let Some(fare) = &leg.fare {
+                    if !self
+                        .seat_in_fare_section(flight.flight_id, seat_number, fare)
+                        .await?
+                    {
+                        warnings.push(format!(
+                            "Seat {} is not part of the {} cabin",
+                            seat_number, fare.fare_class
+                        ));
+                    }
+                }
+
+                let seat = sqlx::query!(
                     r#"
-                    UPDATE seat_info
-                    SET seat_status = 'AVAILABLE',
-                        version = version + 1
+                    SELECT seat_status as "seat_status: SeatStatus"
+                    FROM seat_info
                     WHERE flight_id = ? AND seat_number = ?
                     "#,
                     flight.flight_id,
-                    s
+                    seat_number
                 )
-                .execute(&self.pool)
+                .fetch_optional(&self.pool)
                 .await?;
+                match seat {
+                    None => warnings.push(format!("Seat {} does not exist", seat_number)),
+                    Some(seat) if seat.seat_status != SeatStatus::Available => {
+                        warnings.push(format!("Seat {} is not available", seat_number))
+                    }
+                    Some(_) => {}
+                }
             }
-            None => {}
+
+            legs.push(LegValidationResult {
+                flight_number: flight_request.flight_number,
+                flight_date: flight_request.flight_date,
+                valid: leg.issues.is_empty(),
+                issues: leg.issues.iter().map(AppError::to_string).collect(),
+                warnings,
+                price: leg.fare.as_ref().map(FarePrice::from),
+            });
         }
 
-        Ok(())
+        Ok(BookingValidationResponse {
+            valid: issues.is_empty() && legs.iter().all(|leg| leg.valid),
+            issues,
+            legs,
+        })
     }
 
-    async fn book_ticket_for_flight(
+    // Check a booking request as a whole: booking rules, special service requests and
+    // the minor policy. Database errors are returned, broken checks are collected.
+    async fn check_request(
         &self,
         user_id: i32,
-        request: FlightBookingRequest,
-    ) -> AppResult<FlightBookingResponse> {
-        // get the flight information
-        // Check this flight exist
+        request: &TicketBookingRequest,
+    ) -> AppResult<RequestCheck> {
+        let mut issues = Vec::new();
+        if request.flights.is_empty() {
+            issues.push(AppError::BadRequest("No flights requested".into()));
+        }
+        let violations = self.check_booking_rules(request).await?;
+        if !violations.is_empty() {
+            issues.push(AppError::BadRequest(violations.join("; ")));
+        }
+        for flight_request in &request.flights {
+            if let Err(message) = ssr::validate_ssr_codes(&flight_request.ssr_codes) {
+                issues.push(AppError::ValidationError(format!(
+                    "Flight {} on {}: {}",
+                    flight_request.flight_number, flight_request.flight_date, message
+                )));
+            }
+        }
+        let unaccompanied_minor = collect_issue(
+            &mut issues,
+            self.check_unaccompanied_minor(user_id, request).await,
+        )?
+        .unwrap_or(false);
+
+        Ok(RequestCheck {
+            issues,
+            unaccompanied_minor,
+        })
+    }
+
+    // Check one leg of a booking request. With `inventory_claimed` the ticket was
+    // already taken from the flight, so a full flight is not an issue.
+    async fn check_leg(
+        &self,
+        user_id: i32,
+        request: &FlightBookingRequest,
+        earlier_legs: &[FlightBookingRequest],
+        unaccompanied_minor: bool,
+        inventory_claimed: bool,
+    ) -> AppResult<LegCheck> {
+        let mut issues = Vec::new();
+        if earlier_legs.iter().any(|other| {
+            other.flight_number == request.flight_number && other.flight_date == request.flight_date
+        }) {
+            issues.push(AppError::BadRequest(
+                "Flight is requested more than once".into(),
+            ));
+        }
+
         let flight = sqlx::query_as!(
             Flight,
             r#"
@@ -137,15 +823,36 @@ impl TicketService {
         .fetch_optional(&self.pool)
         .await?;
 
-        match flight {
-            Some(_) => {}
+        let flight = match flight {
+            Some(flight) => flight,
             None => {
-                return Err(AppError::BadRequest(format!(
-                    "Flight {} does not exist on {}\n",
+                issues.push(AppError::BadRequest(format!(
+                    "Flight {} does not exist on {}",
                     request.flight_number, request.flight_date
-                )))
+                )));
+                return Ok(LegCheck {
+                    flight: None,
+                    fare: None,
+                    issues,
+                });
             }
+        };
+
+        // Cancelled flights have no tickets left, say why instead of reporting them as full
+        let status = sqlx::query!(
+            r#"SELECT status as "status: FlightStatus" FROM flight WHERE flight_id = ?"#,
+            flight.flight_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        let cancelled = status.status == FlightStatus::Cancelled;
+        if cancelled {
+            issues.push(AppError::BadRequest(format!(
+                "Flight {} on {} is cancelled",
+                request.flight_number, request.flight_date
+            )));
         }
+        collect_issue(&mut issues, self.ensure_flight_open(flight.flight_id).await)?;
 
         // do not allow re-booking the same flight for now
         let existing_ticket = sqlx::query!(
@@ -159,323 +866,2184 @@ impl TicketService {
         )
         .fetch_optional(&self.pool)
         .await?;
+        if existing_ticket.is_some() && self.rules.duplicate_booking == DuplicatePolicy::Reject {
+            issues.push(AppError::BadRequest(
+                "Cannot re-book the same flight".to_string(),
+            ));
+        }
 
-        match existing_ticket {
-            Some(_) => {
-                return Err(AppError::BadRequest(
-                    "Cannot re-book the same flight".to_string(),
-                ))
-            }
-            None => {}
-        };
-
-        let mut flight: Flight;
+        // Price the ticket with the requested fare class
+        let fare = collect_issue(
+            &mut issues,
+            self.fare_service
+                .fare(request.flight_number, request.fare_class)
+                .await,
+        )?;
 
-        loop {
-            flight = sqlx::query_as!(
-                Flight,
+        // Each flight only accepts a limited number of unaccompanied minors
+        if unaccompanied_minor {
+            let quota = sqlx::query!(
                 r#"
-                SELECT flight_id, flight_number, flight_date, available_tickets, version 
-                FROM flight 
-                WHERE flight_number = ? 
-                AND flight_date = ?
+                SELECT fr.um_quota, COUNT(t.id) as "booked: i64"
+                FROM flight_route fr
+                LEFT JOIN ticket t
+                    ON t.flight_number = fr.flight_number
+                    AND t.flight_date = ?
+                    AND t.unaccompanied_minor = TRUE
+                WHERE fr.flight_number = ?
+                GROUP BY fr.um_quota
                 "#,
-                request.flight_number,
-                request.flight_date
+                request.flight_date,
+                request.flight_number
             )
             .fetch_one(&self.pool)
             .await?;
 
-            // println!("Searched flight {}!", flight.flight_id);
-
-            if flight.available_tickets == 0 {
-                return Err(AppError::ValidationError(
-                    "This flight is fully booked.".to_string(),
+            if quota.booked >= quota.um_quota as i64 {
+                issues.push(AppError::ValidationError(
+                    "This flight has no more capacity for unaccompanied minors.".to_string(),
                 ));
             }
+        }
 
-            // Create a ticket for the user first, and worry about the seat later.
-            // We book a ticket for the user regardless of whether the preferred seat is available
-            let mut tx = self.pool.begin().await?;
+        if !inventory_claimed && !cancelled && flight.available_tickets <= 0 {
+            issues.push(
+                self.fully_booked_error(request.flight_number, request.flight_date)
+                    .await?,
+            );
+        }
 
-            let update_result = sqlx::query!(
+        Ok(LegCheck {
+            flight: Some(flight),
+            fare,
+            issues,
+        })
+    }
+
+    // Evaluate the itinerary against the booking rules, returning every rule broken
+    async fn check_booking_rules(&self, request: &TicketBookingRequest) -> AppResult<Vec<String>> {
+        let mut legs = Vec::new();
+        for flight_request in &request.flights {
+            let route = sqlx::query!(
                 r#"
-                UPDATE flight
-                set available_tickets = available_tickets - 1, 
-                    version = version + 1
-                where flight_id = ?
-                AND version = ?
+                SELECT
+                    fr.departure_time as "departure_time: NaiveTime",
+                    da.timezone as "departure_timezone?"
+                FROM flight_route fr
+                LEFT JOIN airport da ON fr.departure_airport = da.code
+                WHERE fr.flight_number = ?
                 "#,
-                flight.flight_id,
-                flight.version,
+                flight_request.flight_number
             )
-            .execute(&mut *tx)
+            .fetch_optional(&self.pool)
             .await?;
 
-            if update_result.rows_affected() == 0 {
-                tx.rollback().await?;
-
-                // sleep a bit to prevent from deadlock
-                let millis = rand::thread_rng().gen_range(1..=50);
-                tokio::time::sleep(tokio::time::Duration::from_millis(millis)).await;
-            } else {
-                tx.commit().await?;
-                break;
-            }
+            legs.push(LegFacts {
+                flight_number: flight_request.flight_number,
+                departure: route.map(|route| {
+                    departure_utc(
+                        flight_request.flight_date,
+                        route.departure_time,
+                        route.departure_timezone.as_deref(),
+                    )
+                }),
+            });
         }
 
-        let result = sqlx::query!(
+        Ok(self.rules.evaluate(&legs, chrono::Utc::now().naive_utc()))
+    }
+
+    // Check whether the customer travels as an unaccompanied minor, and if so enforce
+    // the minor policy: guardian contact is required, and by default of the booking
+    // rules only direct flights are allowed
+    async fn check_unaccompanied_minor(
+        &self,
+        user_id: i32,
+        request: &TicketBookingRequest,
+    ) -> AppResult<bool> {
+        let customer = sqlx::query!(
             r#"
-            INSERT INTO ticket (customer_id, flight_id, flight_date, flight_number)
-            VALUES (?, ?, ?, ?)
+            SELECT birth_date as "birth_date: NaiveDate"
+            FROM customer_info
+            WHERE id = ?
             "#,
-            user_id,
-            flight.flight_id,
-            flight.flight_date,
-            flight.flight_number
+            user_id
         )
-        .execute(&self.pool)
+        .fetch_optional(&self.pool)
         .await?;
 
-        let ticket_id = result.last_insert_id() as i32;
-        // println!("inserted {}", ticket_id);
-
-        let response = FlightBookingResponse {
-            ticket_id,
-            flight_details: format!("Flight {} on {}", flight.flight_number, flight.flight_date),
-            seat_number: None,
-            // booking_status: "Confirmed.".to_string(),
+        let birth_date = match customer {
+            Some(c) => c.birth_date,
+            None => return Ok(false),
         };
 
-        // Successfully booked a ticket, now do the seat part.
-        match request.preferred_seat {
-            Some(prefered_seat) => {
-                let book_seat_result = self
-                    .book_seat_for_ticket(
-                        user_id,
-                        SeatBookingRequest {
-                            flight_number: flight.flight_number,
-                            flight_date: NaiveDate::from_ymd_opt(
-                                flight.flight_date.year() as i32,
-                                flight.flight_date.month() as u32,
-                                flight.flight_date.day() as u32,
-                            )
-                            .unwrap(),
-                            seat_number: prefered_seat,
-                        },
-                    )
-                    .await;
-                match book_seat_result {
-                    Ok(_) => {
-                        return Ok(FlightBookingResponse {
-                            seat_number: Some(prefered_seat),
-                            ..response
-                        })
-                    }
-                    Err(_) => {
-                        return Ok(FlightBookingResponse {
-                            // booking_status: "Confirmed booking, however the preferred seat is currently unavaiable, please try again later.".to_string(),
-                            ..response
-                        });
-                    }
-                }
+        let minors = &self.rules.minors;
+        let mut unaccompanied_minor = false;
+        for flight_request in &request.flights {
+            let age = age_on(birth_date, flight_request.flight_date);
+            if age < minors.min_unaccompanied_age {
+                return Err(AppError::BadRequest(format!(
+                    "Children under {} cannot travel alone",
+                    minors.min_unaccompanied_age
+                )));
+            }
+            if age < minors.unaccompanied_minor_age {
+                unaccompanied_minor = true;
+            }
+        }
+
+        if !unaccompanied_minor {
+            return Ok(false);
+        }
+
+        match &request.guardian {
+            Some(guardian) if is_valid_guardian(guardian) => {}
+            _ => {
+                return Err(AppError::BadRequest(
+                    "Guardian contact information is required for unaccompanied minors".into(),
+                ))
+            }
+        }
+        if !minors.direct_flights_only {
+            return Ok(true);
+        }
+
+        // Unaccompanied minors can only take direct flights, so reject connecting legs
+        let mut legs = Vec::new();
+        for flight_request in &request.flights {
+            let route = sqlx::query!(
+                r#"
+                SELECT departure_city, destination_city
+                FROM flight_route
+                WHERE flight_number = ?
+                "#,
+                flight_request.flight_number
+            )
+            .fetch_optional(&self.pool)
+            .await?;
+
+            if let Some(route) = route {
+                legs.push((
+                    flight_request.flight_date,
+                    route.departure_city,
+                    route.destination_city,
+                ));
+            }
+        }
+        legs.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for pair in legs.windows(2) {
+            let (first_date, first_departure, first_destination) = &pair[0];
+            let (second_date, second_departure, second_destination) = &pair[1];
+            // A leg leaving from where the previous one landed within a day is a connection,
+            // unless it simply flies back (round trip)
+            if first_destination == second_departure
+                && first_departure != second_destination
+                && (*second_date - *first_date).num_days() <= 1
+            {
+                return Err(AppError::BadRequest(
+                    "Unaccompanied minors can only be booked on direct flights".into(),
+                ));
             }
-            None => return Ok(response),
         }
+
+        Ok(true)
     }
 
-    pub async fn book_seat(
+    async fn start_booking_saga(&self, user_id: i32) -> AppResult<i32> {
+        let saga_id = sqlx::query!(
+            "INSERT INTO booking_saga (customer_id, created_at) VALUES (?, UTC_TIMESTAMP())",
+            user_id
+        )
+        .execute(&self.pool)
+        .await?
+        .last_insert_id() as i32;
+        Ok(saga_id)
+    }
+
+    // End a booking saga in the transaction: give back the tickets it claimed from the
+    // flights that no ticket of the saga was booked with, and with `revert` delete its
+    // tickets and give back their seats and claimed tickets too. Flights are updated in
+    // id order, like claim_itinerary_inventory locks them. Returns the flights given
+    // tickets back, or None when the saga already ended.
+    async fn end_booking_saga(
         &self,
-        customer_id: i32,
-        flight_id: i32,
-        new_seat_number: i32,
-        old_seat_number: Option<i32>,
-    ) -> AppResult<bool> {
-        loop {
-            let mut tx = self.pool.begin().await?;
+        tx: &mut Transaction<'_, MySql>,
+        saga_id: i32,
+        revert: bool,
+    ) -> AppResult<Option<Vec<i32>>> {
+        let saga = sqlx::query!(
+            "SELECT id FROM booking_saga WHERE id = ? FOR UPDATE",
+            saga_id
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+        if saga.is_none() {
+            return Ok(None);
+        }
 
-            // get the new seat information
-            let new_seat_info = sqlx::query!(
+        // The seat may have been assigned after the leg was booked, the ticket knows
+        let tickets = sqlx::query!(
+            r#"
+            SELECT id, flight_id, seat_number
+            FROM ticket
+            WHERE booking_saga_id = ?
+            ORDER BY id
+            FOR UPDATE
+            "#,
+            saga_id
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+        let mut released = sqlx::query_scalar!(
+            "SELECT flight_id FROM booking_saga_claim WHERE saga_id = ?",
+            saga_id
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+        // Each ticket kept uses up one ticket claimed from its flight
+        if !revert {
+            for ticket in &tickets {
+                if let Some(index) = released.iter().position(|id| *id == ticket.flight_id) {
+                    released.swap_remove(index);
+                }
+            }
+        }
+        released.sort();
+
+        let mut freed_seats = Vec::new();
+        if revert {
+            for ticket in &tickets {
+                sqlx::query!("DELETE FROM ticket WHERE id = ?", ticket.id)
+                    .execute(&mut **tx)
+                    .await?;
+                if let Some(seat_number) = ticket.seat_number {
+                    freed_seats.push((ticket.flight_id, seat_number));
+                }
+            }
+        }
+        // Also drops the claims, and clears the saga of the tickets kept
+        sqlx::query!("DELETE FROM booking_saga WHERE id = ?", saga_id)
+            .execute(&mut **tx)
+            .await?;
+
+        for flight_id in &released {
+            sqlx::query!(
                 r#"
-                SELECT seat_status as "seat_status: SeatStatus", version
-                FROM seat_info
+                UPDATE flight
+                SET available_tickets = available_tickets + 1,
+                    version = version + 1
+                WHERE flight_id = ?
+                "#,
+                flight_id
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        for (flight_id, seat_number) in &freed_seats {
+            sqlx::query!(
+                r#"
+                UPDATE seat_info
+                SET seat_status = 'AVAILABLE',
+                    held_by = NULL,
+                    held_until = NULL,
+                    version = version + 1
                 WHERE flight_id = ? AND seat_number = ?
                 "#,
                 flight_id,
-                new_seat_number
+                seat_number
             )
-            .fetch_optional(&mut *tx)
+            .execute(&mut **tx)
             .await?;
+        }
 
-            let new_seat_info = match new_seat_info {
-                Some(info) => info,
-                None => {
-                    return Err(AppError::NotFound(
-                        "The new seat is not found".to_string(),
-                    ))
-                }
-            };
+        released.extend(freed_seats.into_iter().map(|(flight_id, _)| flight_id));
+        released.sort();
+        released.dedup();
+        Ok(Some(released))
+    }
 
-            if new_seat_info.seat_status != SeatStatus::Available {
-                return Err(AppError::ValidationError(
-                    "The new seat is already booked or unavailable".to_string(),
-                ));
+    // Undo a booking that failed halfway, all at once or not at all. Nothing is left to
+    // do when the booking was created or reverted already.
+    async fn revert_booking(&self, saga_id: i32) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        let flights = self.end_booking_saga(&mut tx, saga_id, true).await?;
+        tx.commit().await?;
+        for flight_id in flights.unwrap_or_default() {
+            self.invalidate_cached(flight_id);
+        }
+        Ok(())
+    }
+
+    // Revert the bookings interrupted before they were created or reverted, those whose
+    // saga started more than BOOKING_SAGA_TIMEOUT_MINUTES ago. Returns how many were.
+    pub async fn revert_interrupted_bookings(&self) -> AppResult<u64> {
+        let started_before = chrono::Utc::now().naive_utc()
+            - chrono::Duration::minutes(BOOKING_SAGA_TIMEOUT_MINUTES);
+        let sagas = sqlx::query_scalar!(
+            "SELECT id FROM booking_saga WHERE created_at < ? ORDER BY id",
+            started_before
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for saga_id in &sagas {
+            tracing::warn!(saga_id, "reverting interrupted booking");
+            self.revert_booking(*saga_id).await?;
+        }
+        Ok(sagas.len() as u64)
+    }
+
+    // Periodically revert the bookings interrupted halfway in the background
+    pub fn spawn_booking_recovery_task(&self, period: std::time::Duration) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                if let Err(e) = service.revert_interrupted_bookings().await {
+                    tracing::error!(error = %e, "failed to revert interrupted bookings");
+                }
             }
+        });
+    }
 
-            // update the new seat information
-            let update_result = sqlx::query!(
+    // Take one ticket from every leg of an itinerary in a single transaction, or none when
+    // a leg is missing or full. Flights are locked in id order so concurrent itineraries
+    // sharing legs cannot deadlock. Returns the tickets left on each leg after the claim.
+    async fn claim_itinerary_inventory(
+        &self,
+        saga_id: i32,
+        legs: &[FlightBookingRequest],
+    ) -> AppResult<Vec<i32>> {
+        let mut flights = Vec::new();
+        for (index, leg) in legs.iter().enumerate() {
+            let flight = sqlx::query!(
                 r#"
-                UPDATE seat_info
-                SET seat_status = ?,
+                SELECT flight_id
+                FROM flight
+                WHERE flight_number = ? AND flight_date = ?
+                "#,
+                leg.flight_number,
+                leg.flight_date
+            )
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "Flight {} does not exist on {}",
+                    leg.flight_number, leg.flight_date
+                ))
+            })?;
+            flights.push((flight.flight_id, index, leg));
+        }
+        flights.sort_by_key(|(flight_id, _, _)| *flight_id);
+
+        let mut tickets_left = vec![0; legs.len()];
+        let mut tx = self.pool.begin().await?;
+        for (flight_id, index, leg) in flights {
+            let claimed = sqlx::query!(
+                r#"
+                UPDATE flight
+                SET available_tickets = available_tickets - 1,
                     version = version + 1
-                WHERE flight_id = ? 
-                AND seat_number = ? 
-                AND version = ?
-                AND seat_status = 'AVAILABLE'
+                WHERE flight_id = ?
+                AND available_tickets > 0
                 "#,
-                SeatStatus::Booked.to_string(),
-                flight_id,
-                new_seat_number,
-                new_seat_info.version
+                flight_id
             )
             .execute(&mut *tx)
-            .await?;
+            .await?
+            .rows_affected();
 
-            if update_result.rows_affected() == 0 {
+            if claimed == 0 {
                 tx.rollback().await?;
-
-                // sleep a bit to prevent from deadlock
-                let millis = rand::thread_rng().gen_range(1..=50);
-                tokio::time::sleep(tokio::time::Duration::from_millis(millis)).await;
-                continue;
+                return Err(self
+                    .fully_booked_error(leg.flight_number, leg.flight_date)
+                    .await?);
             }
+            claim_for_saga(&mut tx, saga_id, flight_id).await?;
+            tickets_left[index] = tickets_left_on(&mut tx, flight_id).await?;
+        }
+        tx.commit().await?;
 
-            // update the old seat information
-            if let Some(old_seat) = old_seat_number {
-                sqlx::query!(
+        Ok(tickets_left)
+    }
+
+    async fn fully_booked_error(
+        &self,
+        flight_number: i32,
+        flight_date: NaiveDate,
+    ) -> AppResult<AppError> {
+        let alternative_dates = self.alternative_dates(flight_number, flight_date).await?;
+        Ok(AppError::ConflictWithHints(
+            "This flight is fully booked.".to_string(),
+            RetryHints {
+                // tickets may be released by cancellations
+                retry_after_ms: Some(FULL_FLIGHT_RETRY_AFTER_MS),
+                alternative_dates,
+                ..Default::default()
+            },
+        ))
+    }
+
+    // Book a ticket on one flight. With `claimed_tickets_left` the ticket was already taken
+    // from the flight inventory by claim_itinerary_inventory, leaving that many tickets.
+    async fn book_ticket_for_flight(
+        &self,
+        saga_id: i32,
+        user_id: i32,
+        request: FlightBookingRequest,
+        earlier_legs: &[FlightBookingRequest],
+        unaccompanied_minor: Option<&GuardianContact>,
+        claimed_tickets_left: Option<i32>,
+    ) -> AppResult<FlightBookingResponse> {
+        let check = self
+            .check_leg(
+                user_id,
+                &request,
+                earlier_legs,
+                unaccompanied_minor.is_some(),
+                claimed_tickets_left.is_some(),
+            )
+            .await?;
+        if let Some(issue) = check.issues.into_iter().next() {
+            return Err(issue);
+        }
+        // A leg without issues always has its flight and fare
+        let (Some(flight), Some(fare)) = (check.flight, check.fare) else {
+            return Err(AppError::DatabaseError(format!(
+                "Flight {} on {} was checked without its fare",
+                request.flight_number, request.flight_date
+            )));
+        };
+
+        // Passengers of a sold out cabin may be moved up a cabin at their own fare
+        let op_up = self.op_up_cabin(flight.flight_id, &fare).await?;
+        let cabin = op_up.as_ref().unwrap_or(&fare);
+
+        // Create a ticket for the user first, and worry about the seat later.
+        // We book a ticket for the user regardless of whether the preferred seat is available.
+        // The decrement is a single atomic statement, so concurrent bookings never oversell
+        // and never have to retry. It is claimed for the saga along with it, and the
+        // ticket is inserted in the same transaction.
+        let mut tx = self.pool.begin().await?;
+        let tickets_left = match claimed_tickets_left {
+            Some(tickets_left) => tickets_left,
+            None => {
+                let update_result = sqlx::query!(
                     r#"
-                    UPDATE seat_info
-                    SET seat_status = 'AVAILABLE',
+                    UPDATE flight
+                    set available_tickets = available_tickets - 1,
                         version = version + 1
-                    WHERE flight_id = ? AND seat_number = ?
+                    where flight_id = ?
+                    AND available_tickets > 0
                     "#,
-                    flight_id,
-                    old_seat
+                    flight.flight_id,
                 )
                 .execute(&mut *tx)
                 .await?;
+
+                if update_result.rows_affected() == 0 {
+                    tx.rollback().await?;
+                    return Err(self
+                        .fully_booked_error(request.flight_number, request.flight_date)
+                        .await?);
+                }
+                claim_for_saga(&mut tx, saga_id, flight.flight_id).await?;
+                tickets_left_on(&mut tx, flight.flight_id).await?
             }
+        };
 
-            // update the ticket information
+        // Each flight only accepts a limited number of unaccompanied minors. The route is
+        // locked until the ticket is inserted, so concurrent bookings cannot both take the
+        // last place checked by check_leg.
+        if unaccompanied_minor.is_some() {
+            let um_quota = sqlx::query_scalar!(
+                "SELECT um_quota FROM flight_route WHERE flight_number = ? FOR UPDATE",
+                request.flight_number
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            let booked = sqlx::query_scalar!(
+                r#"
+                SELECT COUNT(*) as "booked: i64"
+                FROM ticket
+                WHERE flight_number = ?
+                AND flight_date = ?
+                AND unaccompanied_minor = TRUE
+                "#,
+                request.flight_number,
+                request.flight_date
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if booked >= um_quota as i64 {
+                tx.rollback().await?;
+                return Err(AppError::ValidationError(
+                    "This flight has no more capacity for unaccompanied minors.".to_string(),
+                ));
+            }
+        }
+
+        let overbooked = is_overbooked(&mut tx, flight.flight_id, tickets_left).await?;
+
+        // Draw another booking reference in the rare case the first one is taken
+        let mut attempts = 1;
+        let public_id = new_public_id();
+        let (result, booking_reference) = loop {
+            let booking_reference = new_booking_reference();
+            let result = sqlx::query!(
+                r#"
+                INSERT INTO ticket (
+                    customer_id, flight_id, flight_date, flight_number, unaccompanied_minor,
+                    overbooked, fare_class, price, currency, booking_reference, public_id,
+                    ssr_codes, booking_saga_id
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+                user_id,
+                flight.flight_id,
+                flight.flight_date,
+                flight.flight_number,
+                unaccompanied_minor.is_some(),
+                overbooked,
+                cabin.fare_class,
+                fare.base_price,
+                fare.currency,
+                booking_reference,
+                public_id,
+                ssr::ssr_codes_to_db(&request.ssr_codes),
+                saga_id
+            )
+            .execute(&mut *tx)
+            .await;
+            match result {
+                Err(e) if is_unique_violation(&e) && attempts < MAX_BOOKING_REFERENCE_ATTEMPTS => {
+                    attempts += 1;
+                }
+                result => break (result?, booking_reference),
+            }
+        };
+
+        let ticket_id = result.last_insert_id() as i32;
+        // println!("inserted {}", ticket_id);
+
+        // The op-up record and the guardian go in with the ticket, so a ticket is never
+        // left without them
+        if let Some(cabin) = &op_up {
             sqlx::query!(
                 r#"
-                UPDATE ticket
-                SET seat_number = ?
-                WHERE customer_id = ? AND flight_id = ?
+                INSERT INTO op_up
+                (ticket_id, flight_id, booked_fare_class, cabin_fare_class, reason, created_at)
+                VALUES (?, ?, ?, ?, ?, UTC_TIMESTAMP())
                 "#,
-                new_seat_number,
-                customer_id,
-                flight_id
+                ticket_id,
+                flight.flight_id,
+                fare.fare_class,
+                cabin.fare_class,
+                format!("{} cabin sold out", fare.fare_class)
             )
             .execute(&mut *tx)
             .await?;
+        }
 
-            tx.commit().await?;
-            return Ok(true);
+        if let Some(guardian) = unaccompanied_minor {
+            sqlx::query!(
+                r#"
+                INSERT INTO unaccompanied_minor (ticket_id, guardian_name, guardian_phone, guardian_relationship)
+                VALUES (?, ?, ?, ?)
+                "#,
+                ticket_id,
+                guardian.name,
+                guardian.phone,
+                guardian.relationship
+            )
+            .execute(&mut *tx)
+            .await?;
         }
-    }
+        tx.commit().await?;
 
-    pub async fn book_seat_for_ticket(
-        &self,
-        customer_id: i32,
-        request: SeatBookingRequest,
-    ) -> AppResult<bool> {
-        // Check this flight exist
-        let flight = sqlx::query_as!(
-            Flight,
+        let response = FlightBookingResponse {
+            ticket_id,
+            public_id,
+            booking_reference,
+            flight_details: format!("Flight {} on {}", flight.flight_number, flight.flight_date),
+            seat_number: None,
+            unaccompanied_minor: unaccompanied_minor.is_some(),
+            fare_class: cabin.fare_class,
+            price: fare.base_price,
+            currency: fare.currency.clone(),
+            overbooked,
+            status: LegStatus::Confirmed,
+            upgraded_from: op_up.as_ref().map(|_| fare.fare_class),
+        };
+
+        // Successfully booked a ticket, now do the seat part.
+        match request.preferred_seat {
+            // Seats outside the section of the fare class cannot be chosen
+            Some(prefered_seat)
+                if !self
+                    .seat_in_fare_section(flight.flight_id, prefered_seat, cabin)
+                    .await? =>
+            {
+                return Ok(FlightBookingResponse {
+                    status: LegStatus::ConfirmedSeatUnavailable,
+                    ..response
+                });
+            }
+            Some(prefered_seat) => {
+                let book_seat_result = self
+                    .assign_ticket_seat(user_id, ticket_id, prefered_seat)
+                    .await;
+                match book_seat_result {
+                    Ok(_) => {
+                        return Ok(FlightBookingResponse {
+                            seat_number: Some(prefered_seat),
+                            ..response
+                        })
+                    }
+                    Err(_) if SEAT_ASSIGNMENT.variant_for(user_id) == NEAREST_SEAT_VARIANT => {
+                        let seat = self
+                            .assign_nearest_seat(
+                                user_id,
+                                ticket_id,
+                                flight.flight_id,
+                                prefered_seat,
+                                cabin,
+                            )
+                            .await?;
+                        return Ok(match seat {
+                            Some(seat) => FlightBookingResponse {
+                                seat_number: Some(seat),
+                                ..response
+                            },
+                            None => FlightBookingResponse {
+                                status: LegStatus::ConfirmedSeatUnavailable,
+                                ..response
+                            },
+                        });
+                    }
+                    Err(_) => {
+                        return Ok(FlightBookingResponse {
+                            status: LegStatus::ConfirmedSeatUnavailable,
+                            ..response
+                        });
+                    }
+                }
+            }
+            None => return Ok(response),
+        }
+    }
+
+    // Assign the available seat closest to the preferred one within the fare section,
+    // or None when there is none
+    async fn assign_nearest_seat(
+        &self,
+        user_id: i32,
+        ticket_id: i32,
+        flight_id: i32,
+        preferred_seat: i32,
+        fare: &Fare,
+    ) -> AppResult<Option<i32>> {
+        for seat_number in self.alternative_seats(flight_id, preferred_seat).await? {
+            if !self.seat_in_fare_section(flight_id, seat_number, fare).await? {
+                continue;
+            }
+            let assigned = self
+                .assign_ticket_seat(user_id, ticket_id, seat_number)
+                .await;
+            if assigned.is_ok() {
+                return Ok(Some(seat_number));
+            }
+        }
+        Ok(None)
+    }
+
+    // Whether the seat is in the cabin section sold under the fare
+    async fn seat_in_fare_section(
+        &self,
+        flight_id: i32,
+        seat_number: i32,
+        fare: &Fare,
+    ) -> AppResult<bool> {
+        if !fare.has_section() {
+            return Ok(true);
+        }
+        let seat = self.seat_attributes(flight_id, seat_number).await?;
+        Ok(fare.covers_row(seat.row))
+    }
+
+    // Seats can only be taken in the cabin section of the ticket's fare class
+    async fn check_seat_section(
+        &self,
+        flight_number: i32,
+        flight_id: i32,
+        fare_class: FareClass,
+        seat_number: i32,
+    ) -> AppResult<()> {
+        let fares = self.fare_service.route_fares(flight_number).await?;
+        let Some(fare) = fares
+            .iter()
+            .find(|fare| fare.fare_class == fare_class && fare.has_section())
+        else {
+            return Ok(());
+        };
+
+        let row = self.seat_attributes(flight_id, seat_number).await?.row;
+        if fare.covers_row(row) {
+            return Ok(());
+        }
+        let seat_cabin = fare::seat_class(&fares, row)
+            .map_or("outside the fare class cabins".to_string(), |class| {
+                format!("in the {} cabin", class)
+            });
+        Err(AppError::BadRequest(format!(
+            "Seat {} is {}, a {} ticket can only take seats in {}",
+            seat_number,
+            seat_cabin,
+            fare_class,
+            fare.section_rows()
+        )))
+    }
+
+    // Layout and price of the seat in the cabin of the flight's aircraft
+    async fn seat_attributes(&self, flight_id: i32, seat_number: i32) -> AppResult<SeatAttributes> {
+        let aircraft = self.flight_aircraft(flight_id).await?;
+        Ok(SeatLayout::from_aircraft(&aircraft).attributes(seat_number))
+    }
+
+    // Seats and tickets of each cabin of the flight sold by a fare with its own rows.
+    // Op-up tickets take seats in the cabin they were moved to, so those seats are not
+    // free to sell again, as paid upgrades or otherwise.
+    pub async fn cabin_inventory(&self, flight_id: i32) -> AppResult<Vec<CabinInventory>> {
+        let flight_number = sqlx::query_scalar!(
+            "SELECT flight_number FROM flight WHERE flight_id = ?",
+            flight_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Flight {} not found", flight_id)))?;
+        let aircraft = self.flight_aircraft(flight_id).await?;
+        let layout = SeatLayout::from_aircraft(&aircraft);
+
+        let sold = sqlx::query!(
+            r#"
+            SELECT
+                t.fare_class as "fare_class: FareClass",
+                COUNT(*) as "tickets_sold!: i64",
+                CAST(SUM(o.id IS NOT NULL) AS SIGNED) as "op_ups!: i64"
+            FROM ticket t
+            LEFT JOIN op_up o ON o.ticket_id = t.id AND o.flight_id = t.flight_id
+            WHERE t.flight_id = ?
+            GROUP BY t.fare_class
+            "#,
+            flight_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let inventory = self
+            .fare_service
+            .route_fares(flight_number)
+            .await?
+            .into_iter()
+            .filter(|fare| fare.has_section())
+            .map(|fare| {
+                let seats = (1..=aircraft.capacity)
+                    .filter(|seat_number| fare.covers_row(layout.row(*seat_number)))
+                    .count() as i64;
+                let (tickets_sold, op_ups) = sold
+                    .iter()
+                    .find(|row| row.fare_class == fare.fare_class)
+                    .map_or((0, 0), |row| (row.tickets_sold, row.op_ups));
+                CabinInventory {
+                    fare_class: fare.fare_class,
+                    seats,
+                    tickets_sold,
+                    op_ups,
+                    free_seats: (seats - tickets_sold).max(0),
+                }
+            })
+            .collect();
+        Ok(inventory)
+    }
+
+    // Fare of the higher cabin to op-up a passenger of the fare into, when the op-up policy
+    // is on, the cabin of the fare is sold out and a higher one has a free seat
+    async fn op_up_cabin(&self, flight_id: i32, fare: &Fare) -> AppResult<Option<Fare>> {
+        if !self.rules.op_up.enabled || !fare.has_section() {
+            return Ok(None);
+        }
+        let cabins = self.cabin_inventory(flight_id).await?;
+        let sold_out = cabins
+            .iter()
+            .any(|cabin| cabin.fare_class == fare.fare_class && cabin.free_seats == 0);
+        if !sold_out {
+            return Ok(None);
+        }
+
+        let fares = self.fare_service.route_fares(fare.flight_number).await?;
+        Ok(fare
+            .fare_class
+            .higher_classes()
+            .iter()
+            .filter(|class| {
+                cabins
+                    .iter()
+                    .any(|cabin| cabin.fare_class == **class && cabin.free_seats > 0)
+            })
+            .find_map(|class| fares.iter().find(|fare| fare.fare_class == *class).cloned()))
+    }
+
+    async fn flight_aircraft(&self, flight_id: i32) -> AppResult<Aircraft> {
+        let aircraft = sqlx::query_as!(
+            Aircraft,
+            r#"
+            SELECT
+                a.aircraft_id,
+                a.capacity,
+                a.seats_per_row,
+                a.exit_rows,
+                a.accessible_rows,
+                a.extra_legroom_rows
+            FROM flight f
+            JOIN flight_route fr ON f.flight_number = fr.flight_number
+            JOIN aircraft a ON COALESCE(f.aircraft_id, fr.aircraft_id) = a.aircraft_id
+            WHERE f.flight_id = ?
+            "#,
+            flight_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(aircraft)
+    }
+
+    // Price of a seat for a ticket of the customer, checked like a seat selection is,
+    // and what the ticket already paid for seats
+    #[instrument(skip(self))]
+    pub async fn seat_price_quote(
+        &self,
+        customer_id: i32,
+        ticket_id: i32,
+        seat_number: i32,
+    ) -> AppResult<SeatPriceQuote> {
+        let ticket = sqlx::query!(
+            r#"
+            SELECT
+                t.flight_id,
+                t.seat_number,
+                t.fare_class as "fare_class: FareClass",
+                f.flight_number
+            FROM ticket t
+            JOIN flight f ON f.flight_id = t.flight_id
+            WHERE t.id = ? AND t.customer_id = ?
+            "#,
+            ticket_id,
+            customer_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Ticket {} not found", ticket_id)))?;
+        if ticket.seat_number == Some(seat_number) {
+            return Err(AppError::BadRequest(
+                "Cannot book the same seat you already have".into(),
+            ));
+        }
+        self.check_seat_section(
+            ticket.flight_number,
+            ticket.flight_id,
+            ticket.fare_class,
+            seat_number,
+        )
+        .await?;
+
+        let seat = self.seat_attributes(ticket.flight_id, seat_number).await?;
+        let paid = sqlx::query_scalar!(
+            r#"
+            SELECT COALESCE(SUM(amount), 0) as "paid!: Decimal"
+            FROM seat_charge
+            WHERE ticket_id = ? AND status = 'CAPTURED'
+            "#,
+            ticket_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(SeatPriceQuote {
+            ticket_id,
+            flight_id: ticket.flight_id,
+            seat_price: seat.fee,
+            paid,
+        })
+    }
+
+    // Give a seat the customer holds back to the inventory
+    pub async fn release_seat_hold(
+        &self,
+        customer_id: i32,
+        flight_id: i32,
+        seat_number: i32,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            UPDATE seat_info
+            SET seat_status = 'AVAILABLE',
+                held_by = NULL,
+                held_until = NULL,
+                version = version + 1
+            WHERE flight_id = ? AND seat_number = ? AND seat_status = 'HELD' AND held_by = ?
+            "#,
+            flight_id,
+            seat_number,
+            customer_id
+        )
+        .execute(&self.pool)
+        .await?;
+        self.invalidate_cached(flight_id);
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn book_seat(
+        &self,
+        customer_id: i32,
+        ticket_id: i32,
+        flight_id: i32,
+        new_seat_number: i32,
+        old_seat_number: Option<i32>,
+    ) -> AppResult<bool> {
+        self.ensure_flight_open(flight_id).await?;
+
+        let Some(seat_lock) = &self.seat_lock else {
+            return self
+                .book_seat_versioned(
+                    customer_id,
+                    ticket_id,
+                    flight_id,
+                    new_seat_number,
+                    old_seat_number,
+                )
+                .await;
+        };
+        let key = seat_lock::seat_lock_key(flight_id, new_seat_number);
+        let token = self
+            .lock_seat(
+                seat_lock.as_ref(),
+                &key,
+                customer_id,
+                flight_id,
+                new_seat_number,
+            )
+            .await?;
+        let result = self
+            .book_seat_versioned(
+                customer_id,
+                ticket_id,
+                flight_id,
+                new_seat_number,
+                old_seat_number,
+            )
+            .await;
+        if let Some(token) = token {
+            if let Err(e) = seat_lock.release(&key, &token).await {
+                // The lock expires on its own
+                tracing::warn!(
+                    error = %e,
+                    lock = seat_lock.name(),
+                    key = %key,
+                    "failed to release seat lock"
+                );
+            }
+        }
+        result
+    }
+
+    // Wait for the lock of the seat, as long as the version check would have retried.
+    // Returns None when the lock cannot be reached, the version check alone then keeps
+    // the booking safe.
+    async fn lock_seat(
+        &self,
+        seat_lock: &dyn SeatLock,
+        key: &str,
+        customer_id: i32,
+        flight_id: i32,
+        seat_number: i32,
+    ) -> AppResult<Option<String>> {
+        let mut retry = self.seat_retry.start(&self.seat_retries);
+        loop {
+            match seat_lock.acquire(key, SEAT_LOCK_TTL).await {
+                Ok(Some(token)) => return Ok(Some(token)),
+                Ok(None) => {
+                    if !retry.backoff().await {
+                        return Err(self
+                            .seat_retries_exhausted(
+                                customer_id,
+                                flight_id,
+                                seat_number,
+                                retry.failed_attempts(),
+                            )
+                            .await?);
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        error = %e,
+                        lock = seat_lock.name(),
+                        key,
+                        "failed to take seat lock, booking with the version check alone"
+                    );
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    // Book the seat if its version did not change since it was read, retrying when
+    // another booking changed it first
+    async fn book_seat_versioned(
+        &self,
+        customer_id: i32,
+        ticket_id: i32,
+        flight_id: i32,
+        new_seat_number: i32,
+        old_seat_number: Option<i32>,
+    ) -> AppResult<bool> {
+        let mut retry = self.seat_retry.start(&self.seat_retries);
+        loop {
+            let mut tx = self.pool.begin().await?;
+
+            // get the new seat information
+            let new_seat_info = sqlx::query!(
+                r#"
+                SELECT seat_status as "seat_status: SeatStatus", version, held_by
+                FROM seat_info
+                WHERE flight_id = ? AND seat_number = ?
+                "#,
+                flight_id,
+                new_seat_number
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            let new_seat_info = match new_seat_info {
+                Some(info) => info,
+                None => {
+                    return Err(AppError::NotFound(
+                        "The new seat is not found".to_string(),
+                    ))
+                }
+            };
+
+            // A seat held by the customer can be booked by them
+            let held_by_customer = new_seat_info.seat_status == SeatStatus::Held
+                && new_seat_info.held_by == Some(customer_id);
+            if new_seat_info.seat_status != SeatStatus::Available && !held_by_customer {
+                tx.rollback().await?;
+                let alternative_seats = self
+                    .alternative_seats(flight_id, new_seat_number)
+                    .await?;
+                return Err(AppError::SeatTaken {
+                    flight_id,
+                    seat_number: new_seat_number,
+                    alternative_seats,
+                });
+            }
+
+            // update the new seat information
+            let update_result = sqlx::query!(
+                r#"
+                UPDATE seat_info
+                SET seat_status = ?,
+                    held_by = NULL,
+                    held_until = NULL,
+                    version = version + 1
+                WHERE flight_id = ? 
+                AND seat_number = ? 
+                AND version = ?
+                AND (seat_status = 'AVAILABLE' OR (seat_status = 'HELD' AND held_by = ?))
+                "#,
+                SeatStatus::Booked.as_db_str(),
+                flight_id,
+                new_seat_number,
+                new_seat_info.version,
+                customer_id
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            if update_result.rows_affected() == 0 || self.take_forced_seat_conflict() {
+                tx.rollback().await?;
+                // Back off so the bookings that collided spread out
+                if !retry.backoff().await {
+                    return Err(self
+                        .seat_retries_exhausted(
+                            customer_id,
+                            flight_id,
+                            new_seat_number,
+                            retry.failed_attempts(),
+                        )
+                        .await?);
+                }
+                continue;
+            }
+
+            // update the old seat information
+            if let Some(old_seat) = old_seat_number {
+                sqlx::query!(
+                    r#"
+                    UPDATE seat_info
+                    SET seat_status = 'AVAILABLE',
+                        version = version + 1
+                    WHERE flight_id = ? AND seat_number = ?
+                    "#,
+                    flight_id,
+                    old_seat
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            // update the ticket information
+            sqlx::query!(
+                r#"
+                UPDATE ticket
+                SET seat_number = ?
+                WHERE id = ? AND customer_id = ?
+                "#,
+                new_seat_number,
+                ticket_id,
+                customer_id
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            tx.commit().await?;
+            self.invalidate_cached(flight_id);
+            return Ok(true);
+        }
+    }
+
+    // Record a seat booking that kept losing its version check, so contention shows up
+    // in the audit trail instead of only in the client's error
+    async fn seat_retries_exhausted(
+        &self,
+        customer_id: i32,
+        flight_id: i32,
+        seat_number: i32,
+        attempts: u32,
+    ) -> AppResult<AppError> {
+        tracing::warn!(
+            customer_id,
+            flight_id,
+            seat_number,
+            attempts,
+            "seat booking retries exhausted"
+        );
+
+        let mut tx = self.pool.begin().await?;
+        outbox::enqueue(
+            &mut tx,
+            &DomainEvent::SeatBookingRetriesExhausted {
+                customer_id,
+                flight_id,
+                seat_number,
+                attempts,
+            },
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(AppError::RetryExhausted {
+            operation: format!("Booking seat {} of flight {}", seat_number, flight_id),
+            attempts,
+        })
+    }
+
+    // Tickets and seats of a flight that departed or was closed out are frozen,
+    // only admin corrections can change them afterwards
+    async fn ensure_flight_open(&self, flight_id: i32) -> AppResult<()> {
+        let flight = sqlx::query!(
+            r#"
+            SELECT
+                flight_number,
+                flight_date as "flight_date: NaiveDate",
+                status as "status: FlightStatus",
+                closed_at IS NOT NULL as "closed!: bool"
+            FROM flight
+            WHERE flight_id = ?
+            "#,
+            flight_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Flight {} not found", flight_id)))?;
+
+        if flight.closed || flight.status == FlightStatus::Departed {
+            return Err(AppError::Conflict(format!(
+                "Flight {} on {} is closed, its tickets and seats can no longer change",
+                flight.flight_number, flight.flight_date
+            )));
+        }
+        Ok(())
+    }
+
+    // Id of the ticket named by its public id, or by its numeric id while clients
+    // still use those
+    pub async fn ticket_id_for(&self, ticket: &str) -> AppResult<i32> {
+        if let Ok(ticket_id) = ticket.trim().parse::<i32>() {
+            return Ok(ticket_id);
+        }
+        let public_id = parse_public_id(ticket)
+            .ok_or_else(|| AppError::ValidationError(format!("Invalid ticket id {}", ticket)))?;
+        sqlx::query_scalar!("SELECT id FROM ticket WHERE public_id = ?", public_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Ticket {} not found", public_id)))
+    }
+
+    // Flight and seat of a ticket of the customer. Tickets of other customers are not
+    // found, so their ids do not leak.
+    pub async fn ticket_seat(&self, customer_id: i32, ticket_id: i32) -> AppResult<TicketSeat> {
+        let ticket = sqlx::query!(
+            r#"
+            SELECT
+                t.flight_id,
+                t.seat_number,
+                f.flight_number,
+                f.flight_date as "flight_date: NaiveDate"
+            FROM ticket t
+            JOIN flight f ON f.flight_id = t.flight_id
+            WHERE t.id = ? AND t.customer_id = ?
+            "#,
+            ticket_id,
+            customer_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Ticket {} not found", ticket_id)))?;
+
+        Ok(TicketSeat {
+            ticket_id,
+            flight_id: ticket.flight_id,
+            flight_number: ticket.flight_number,
+            flight_date: ticket.flight_date,
+            seat_number: ticket.seat_number,
+        })
+    }
+
+    // Give the seat of a ticket of the customer back to the inventory, keeping the
+    // ticket. Returns the seat given up, None when the ticket had no seat.
+    #[instrument(skip(self))]
+    pub async fn release_ticket_seat(
+        &self,
+        customer_id: i32,
+        ticket_id: i32,
+    ) -> AppResult<Option<i32>> {
+        let mut tx = self.pool.begin().await?;
+        let ticket = sqlx::query!(
+            r#"
+            SELECT flight_id, seat_number
+            FROM ticket
+            WHERE id = ? AND customer_id = ?
+            FOR UPDATE
+            "#,
+            ticket_id,
+            customer_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Ticket {} not found", ticket_id)))?;
+        let Some(seat_number) = ticket.seat_number else {
+            tx.rollback().await?;
+            return Ok(None);
+        };
+        self.ensure_flight_open(ticket.flight_id).await?;
+
+        sqlx::query!(
+            "UPDATE ticket SET seat_number = NULL WHERE id = ?",
+            ticket_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            UPDATE seat_info
+            SET seat_status = 'AVAILABLE',
+                version = version + 1
+            WHERE flight_id = ? AND seat_number = ?
+            "#,
+            ticket.flight_id,
+            seat_number
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        self.invalidate_cached(ticket.flight_id);
+        Ok(Some(seat_number))
+    }
+
+    // Give the tickets sold before public ids theirs, oldest first so the ids sort
+    // like the tickets. Returns how many tickets got one.
+    pub async fn assign_public_ids(&self) -> AppResult<u64> {
+        let mut assigned = 0;
+        loop {
+            let ticket_ids = sqlx::query_scalar!(
+                "SELECT id FROM ticket WHERE public_id IS NULL ORDER BY id LIMIT ?",
+                PUBLIC_ID_BATCH_SIZE
+            )
+            .fetch_all(&self.pool)
+            .await?;
+            if ticket_ids.is_empty() {
+                return Ok(assigned);
+            }
+            for ticket_id in ticket_ids {
+                assigned += sqlx::query!(
+                    "UPDATE ticket SET public_id = ? WHERE id = ? AND public_id IS NULL",
+                    new_public_id(),
+                    ticket_id
+                )
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+            }
+        }
+    }
+
+    // Set the seat a passenger actually had, bypassing the close-out lock. Every
+    // correction is recorded with its reason.
+    #[instrument(skip(self))]
+    pub async fn correct_ticket(
+        &self,
+        admin_id: i32,
+        ticket_id: i32,
+        request: TicketCorrectionRequest,
+    ) -> AppResult<TicketCorrectionResponse> {
+        let mut tx = self.pool.begin().await?;
+
+        let ticket = sqlx::query!(
+            "SELECT flight_id, seat_number FROM ticket WHERE id = ? FOR UPDATE",
+            ticket_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Ticket {} not found", ticket_id)))?;
+
+        if request.seat_number == ticket.seat_number {
+            return Err(AppError::ValidationError(
+                "The ticket already has this seat".to_string(),
+            ));
+        }
+
+        if let Some(seat_number) = request.seat_number {
+            // Blocked and held seats can be given, seats of other passengers cannot
+            let update_result = sqlx::query!(
+                r#"
+                UPDATE seat_info
+                SET seat_status = 'BOOKED',
+                    held_by = NULL,
+                    held_until = NULL,
+                    version = version + 1
+                WHERE flight_id = ? AND seat_number = ? AND seat_status <> 'BOOKED'
+                "#,
+                ticket.flight_id,
+                seat_number
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            if update_result.rows_affected() == 0 {
+                let seat = sqlx::query!(
+                    "SELECT seat_number FROM seat_info WHERE flight_id = ? AND seat_number = ?",
+                    ticket.flight_id,
+                    seat_number
+                )
+                .fetch_optional(&mut *tx)
+                .await?;
+                return Err(match seat {
+                    Some(_) => AppError::Conflict(format!(
+                        "Seat {} is booked by another passenger",
+                        seat_number
+                    )),
+                    None => AppError::NotFound("The seat is not found".to_string()),
+                });
+            }
+        }
+
+        if let Some(old_seat) = ticket.seat_number {
+            sqlx::query!(
+                r#"
+                UPDATE seat_info
+                SET seat_status = 'AVAILABLE',
+                    version = version + 1
+                WHERE flight_id = ? AND seat_number = ?
+                "#,
+                ticket.flight_id,
+                old_seat
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query!(
+            "UPDATE ticket SET seat_number = ? WHERE id = ?",
+            request.seat_number,
+            ticket_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO ticket_correction (
+                ticket_id, flight_id, admin_id, reason, old_seat_number, new_seat_number,
+                note, corrected_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, UTC_TIMESTAMP())
+            "#,
+            ticket_id,
+            ticket.flight_id,
+            admin_id,
+            request.reason.as_db_str(),
+            ticket.seat_number,
+            request.seat_number,
+            request.note
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(TicketCorrectionResponse {
+            ticket_id,
+            flight_id: ticket.flight_id,
+            reason: request.reason,
+            old_seat_number: ticket.seat_number,
+            new_seat_number: request.seat_number,
+        })
+    }
+
+    // Reserve a seat for the customer for a few minutes while they complete payment.
+    // Holding another seat on the same flight gives up the previous hold.
+    #[instrument(skip(self))]
+    pub async fn hold_seat(
+        &self,
+        customer_id: i32,
+        request: SeatHoldRequest,
+    ) -> AppResult<SeatHoldResponse> {
+        let minutes = request.minutes.unwrap_or(SEAT_HOLD_MINUTES);
+        if !(1..=MAX_SEAT_HOLD_MINUTES).contains(&minutes) {
+            return Err(AppError::ValidationError(format!(
+                "Seats can be held for 1 to {} minutes",
+                MAX_SEAT_HOLD_MINUTES
+            )));
+        }
+
+        let ticket = sqlx::query!(
+            r#"
+            SELECT flight_id
+            FROM ticket
+            WHERE customer_id = ? AND flight_number = ? AND flight_date = ?
+            "#,
+            customer_id,
+            request.flight_number,
+            request.flight_date
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| {
+            AppError::BadRequest("Customer does not have a ticket for this flight".into())
+        })?;
+        self.ensure_flight_open(ticket.flight_id).await?;
+
+        let held_until = chrono::Utc::now().naive_utc() + chrono::Duration::minutes(minutes);
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE seat_info
+            SET seat_status = 'AVAILABLE',
+                held_by = NULL,
+                held_until = NULL,
+                version = version + 1
+            WHERE flight_id = ? AND seat_status = 'HELD' AND held_by = ? AND seat_number != ?
+            "#,
+            ticket.flight_id,
+            customer_id,
+            request.seat_number
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let update_result = sqlx::query!(
+            r#"
+            UPDATE seat_info
+            SET seat_status = 'HELD',
+                held_by = ?,
+                held_until = ?,
+                version = version + 1
+            WHERE flight_id = ?
+            AND seat_number = ?
+            AND (seat_status = 'AVAILABLE' OR (seat_status = 'HELD' AND held_by = ?))
+            "#,
+            customer_id,
+            held_until,
+            ticket.flight_id,
+            request.seat_number,
+            customer_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if update_result.rows_affected() == 0 {
+            tx.rollback().await?;
+            let seat = sqlx::query!(
+                "SELECT seat_number FROM seat_info WHERE flight_id = ? AND seat_number = ?",
+                ticket.flight_id,
+                request.seat_number
+            )
+            .fetch_optional(&self.pool)
+            .await?;
+            if seat.is_none() {
+                return Err(AppError::NotFound("The seat is not found".to_string()));
+            }
+            let alternative_seats = self
+                .alternative_seats(ticket.flight_id, request.seat_number)
+                .await?;
+            return Err(AppError::SeatTaken {
+                flight_id: ticket.flight_id,
+                seat_number: request.seat_number,
+                alternative_seats,
+            });
+        }
+
+        tx.commit().await?;
+        self.invalidate_cached(ticket.flight_id);
+        Ok(SeatHoldResponse {
+            flight_number: request.flight_number,
+            flight_date: request.flight_date,
+            seat_number: request.seat_number,
+            held_until,
+        })
+    }
+
+    // Give expired seat holds back to the inventory, returning how many were released
+    pub async fn release_expired_holds(&self) -> AppResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE seat_info
+            SET seat_status = 'AVAILABLE',
+                held_by = NULL,
+                held_until = NULL,
+                version = version + 1
+            WHERE seat_status = 'HELD' AND held_until < UTC_TIMESTAMP()
+            "#
+        )
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() > 0 {
+            if let Some(search_cache) = &self.search_cache {
+                search_cache.invalidate_seat_maps();
+            }
+        }
+        Ok(result.rows_affected())
+    }
+
+    // Periodically release expired seat holds in the background
+    pub fn spawn_hold_expiry_task(&self, period: std::time::Duration) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                if let Err(e) = service.release_expired_holds().await {
+                    tracing::error!(error = %e, "failed to release expired seat holds");
+                }
+            }
+        });
+    }
+
+    // Other dates of the same flight that still have tickets, closest first
+    async fn alternative_dates(
+        &self,
+        flight_number: i32,
+        flight_date: NaiveDate,
+    ) -> AppResult<Vec<NaiveDate>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT flight_date as "flight_date: NaiveDate"
+            FROM flight
+            WHERE flight_number = ?
+            AND flight_date BETWEEN ? AND ?
+            AND flight_date <> ?
+            AND available_tickets > 0
+            ORDER BY ABS(DATEDIFF(flight_date, ?))
+            LIMIT ?
+            "#,
+            flight_number,
+            flight_date - chrono::Duration::days(ALTERNATIVE_DATE_WINDOW_DAYS),
+            flight_date + chrono::Duration::days(ALTERNATIVE_DATE_WINDOW_DAYS),
+            flight_date,
+            flight_date,
+            MAX_ALTERNATIVES
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.flight_date).collect())
+    }
+
+    // Available seats on the same flight, closest to the requested one first
+    async fn alternative_seats(&self, flight_id: i32, seat_number: i32) -> AppResult<Vec<i32>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT seat_number
+            FROM seat_info
+            WHERE flight_id = ? AND seat_status = 'AVAILABLE'
+            ORDER BY ABS(seat_number - ?)
+            LIMIT ?
+            "#,
+            flight_id,
+            seat_number,
+            MAX_ALTERNATIVES
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.seat_number).collect())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn book_seat_for_ticket(
+        &self,
+        customer_id: i32,
+        request: SeatBookingRequest,
+    ) -> AppResult<bool> {
+        let result = self
+            .assign_seat_for_ticket(customer_id, request.clone())
+            .await;
+        self.record(
+            Operation::BookSeat {
+                user_id: customer_id,
+                request,
+            },
+            &result,
+        )
+        .await;
+        result
+    }
+
+    // Seat a ticket of the customer named by its id, for callers that know which of
+    // the customer's tickets on the flight they mean
+    #[instrument(skip(self))]
+    pub async fn book_ticket_seat(
+        &self,
+        customer_id: i32,
+        ticket_id: i32,
+        request: SeatBookingRequest,
+    ) -> AppResult<bool> {
+        let result = self
+            .assign_ticket_seat(customer_id, ticket_id, request.seat_number)
+            .await;
+        self.record(
+            Operation::BookSeat {
+                user_id: customer_id,
+                request,
+            },
+            &result,
+        )
+        .await;
+        result
+    }
+
+    async fn assign_seat_for_ticket(
+        &self,
+        customer_id: i32,
+        request: SeatBookingRequest,
+    ) -> AppResult<bool> {
+        let ticket_id = self
+            .ticket_on_flight(customer_id, request.flight_number, request.flight_date)
+            .await?;
+        self.assign_ticket_seat(customer_id, ticket_id, request.seat_number)
+            .await
+    }
+
+    // Id of a ticket of the customer on the flight. Customers allowed several tickets
+    // on a flight should name the ticket by its id instead.
+    pub async fn ticket_on_flight(
+        &self,
+        customer_id: i32,
+        flight_number: i32,
+        flight_date: NaiveDate,
+    ) -> AppResult<i32> {
+        let ticket = sqlx::query!(
+            r#"
+            SELECT t.id as "id?"
+            FROM flight f
+            LEFT JOIN ticket t ON t.flight_id = f.flight_id AND t.customer_id = ?
+            WHERE f.flight_number = ? AND f.flight_date = ?
+            ORDER BY t.id
+            LIMIT 1
+            "#,
+            customer_id,
+            flight_number,
+            flight_date
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Flight {} does not exist on {}\n",
+                flight_number, flight_date
+            ))
+        })?;
+        ticket.id.ok_or_else(|| {
+            AppError::BadRequest("Customer does not have a ticket for this flight".into())
+        })
+    }
+
+    async fn assign_ticket_seat(
+        &self,
+        customer_id: i32,
+        ticket_id: i32,
+        seat_number: i32,
+    ) -> AppResult<bool> {
+        let ticket = sqlx::query!(
+            r#"
+            SELECT
+                t.flight_id,
+                t.seat_number,
+                t.fare_class as "fare_class: FareClass",
+                f.flight_number
+            FROM ticket t
+            JOIN flight f ON f.flight_id = t.flight_id
+            WHERE t.id = ? AND t.customer_id = ?
+            "#,
+            ticket_id,
+            customer_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Ticket {} not found", ticket_id)))?;
+
+        if ticket.seat_number == Some(seat_number) {
+            return Err(AppError::BadRequest(
+                "Cannot book the same seat you already have".into(),
+            ));
+        }
+
+        self.check_seat_section(
+            ticket.flight_number,
+            ticket.flight_id,
+            ticket.fare_class,
+            seat_number,
+        )
+        .await?;
+
+        // book the seat
+        self.book_seat(
+            customer_id,
+            ticket_id,
+            ticket.flight_id,
+            seat_number,
+            ticket.seat_number,
+        )
+        .await
+    }
+
+    // Ticket of the user with the given booking reference. Tickets of other customers
+    // are reported as not found, so references cannot be probed.
+    #[instrument(skip(self))]
+    pub async fn get_ticket_by_reference(
+        &self,
+        user_id: i32,
+        booking_reference: &str,
+    ) -> AppResult<TicketByReferenceResponse> {
+        let booking_reference = normalize_booking_reference(booking_reference)?;
+
+        let ticket = sqlx::query!(
+            r#"
+            SELECT
+                t.id,
+                t.public_id,
+                t.booking_reference as "booking_reference!",
+                t.flight_number,
+                t.flight_date as "flight_date: NaiveDate",
+                fr.departure_city,
+                fr.destination_city,
+                fr.departure_time as "departure_time: NaiveTime",
+                fr.arrival_time as "arrival_time: NaiveTime",
+                f.status as "status: FlightStatus",
+                t.seat_number,
+                t.fare_class as "fare_class: FareClass",
+                t.price,
+                t.currency,
+                t.ssr_codes
+            FROM ticket t
+            JOIN flight f ON t.flight_id = f.flight_id
+            JOIN flight_route fr ON f.flight_number = fr.flight_number
+            WHERE t.booking_reference = ? AND t.customer_id = ?
+            "#,
+            booking_reference,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("No ticket with this booking reference".into()))?;
+
+        Ok(TicketByReferenceResponse {
+            ticket_id: ticket.id,
+            public_id: ticket.public_id,
+            booking_reference: ticket.booking_reference,
+            flight_number: ticket.flight_number,
+            flight_date: ticket.flight_date,
+            departure_city: ticket.departure_city,
+            destination_city: ticket.destination_city,
+            departure_time: ticket.departure_time,
+            arrival_time: ticket.arrival_time,
+            flight_status: ticket.status,
+            seat_number: ticket.seat_number,
+            fare_class: ticket.fare_class,
+            price: ticket.price,
+            currency: ticket.currency,
+            ssr_codes: ssr::ssr_codes_from_db(&ticket.ssr_codes),
+        })
+    }
+
+    // Check in the passenger of a ticket and issue the boarding pass. Check-in opens
+    // CHECKIN_OPENS_HOURS before departure and passengers without a seat get one of
+    // their fare section. Checking in again returns the same boarding pass.
+    #[instrument(skip(self))]
+    pub async fn check_in(&self, user_id: i32, request: CheckinRequest) -> AppResult<BoardingPass> {
+        self.check_in_at(user_id, request, chrono::Utc::now().naive_utc())
+            .await
+    }
+
+    // Check in as if the current UTC time were `now`
+    pub async fn check_in_at(
+        &self,
+        user_id: i32,
+        request: CheckinRequest,
+        now: NaiveDateTime,
+    ) -> AppResult<BoardingPass> {
+        let booking_reference = normalize_booking_reference(&request.booking_reference)?;
+
+        let ticket = sqlx::query!(
+            r#"
+            SELECT
+                t.id,
+                t.flight_id,
+                t.flight_number,
+                t.flight_date as "flight_date: NaiveDate",
+                t.seat_number,
+                t.fare_class as "fare_class: FareClass",
+                t.checked_in_at as "checked_in_at: NaiveDateTime",
+                t.boarding_sequence,
+                fr.departure_city,
+                fr.destination_city,
+                fr.departure_time as "departure_time: NaiveTime",
+                da.timezone as "departure_timezone?",
+                f.delay_minutes,
+                f.status as "status: FlightStatus",
+                f.closed_at IS NOT NULL as "closed!: bool",
+                f.gate,
+                c.name as passenger_name,
+                b.status as "booking_status?"
+            FROM ticket t
+            JOIN flight f ON t.flight_id = f.flight_id
+            JOIN flight_route fr ON f.flight_number = fr.flight_number
+            LEFT JOIN airport da ON fr.departure_airport = da.code
+            JOIN customer_info c ON t.customer_id = c.id
+            LEFT JOIN booking b ON t.booking_id = b.id
+            WHERE t.booking_reference = ? AND t.customer_id = ?
+            "#,
+            booking_reference,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("No ticket with this booking reference".into()))?;
+
+        let delay = chrono::Duration::minutes(ticket.delay_minutes as i64);
+        let departure = ticket.flight_date.and_time(ticket.departure_time) + delay;
+        let departure_at = departure_utc(
+            ticket.flight_date,
+            ticket.departure_time,
+            ticket.departure_timezone.as_deref(),
+        ) + delay;
+        let mut pass = BoardingPass {
+            ticket_id: ticket.id,
+            booking_reference,
+            passenger_name: ticket.passenger_name,
+            flight_number: ticket.flight_number,
+            flight_date: ticket.flight_date,
+            departure_city: ticket.departure_city,
+            destination_city: ticket.destination_city,
+            departure,
+            gate: ticket.gate,
+            seat_number: 0,
+            fare_class: ticket.fare_class,
+            sequence_number: 0,
+            barcode: String::new(),
+            checked_in_at: now,
+        };
+
+        if let (Some(checked_in_at), Some(sequence_number), Some(seat_number)) = (
+            ticket.checked_in_at,
+            ticket.boarding_sequence,
+            ticket.seat_number,
+        ) {
+            pass.checked_in_at = checked_in_at;
+            pass.sequence_number = sequence_number;
+            pass.seat_number = seat_number;
+            pass.barcode = pass.barcode_data();
+            return Ok(pass);
+        }
+
+        if ticket.status == FlightStatus::Cancelled {
+            return Err(AppError::Conflict(format!(
+                "Flight {} on {} is cancelled",
+                ticket.flight_number, ticket.flight_date
+            )));
+        }
+        if ticket.closed || ticket.status == FlightStatus::Departed {
+            return Err(AppError::Conflict(format!(
+                "Flight {} on {} is closed",
+                ticket.flight_number, ticket.flight_date
+            )));
+        }
+        if ticket.booking_status.as_deref() == Some("PENDING_PAYMENT") {
+            return Err(AppError::Conflict(
+                "The booking must be paid before check-in".into(),
+            ));
+        }
+
+        let opens_at = departure_at - chrono::Duration::hours(CHECKIN_OPENS_HOURS);
+        let closes_at = departure_at - chrono::Duration::minutes(CHECKIN_CLOSES_MINUTES);
+        if now < opens_at {
+            return Err(AppError::BadRequest(format!(
+                "Check-in opens at {} UTC",
+                opens_at
+            )));
+        }
+        if now > closes_at {
+            return Err(AppError::BadRequest(format!(
+                "Check-in closed at {} UTC",
+                closes_at
+            )));
+        }
+
+        pass.seat_number = match ticket.seat_number {
+            Some(seat_number) => seat_number,
+            None => {
+                let fare = self
+                    .fare_service
+                    .fare(ticket.flight_number, ticket.fare_class)
+                    .await?;
+                self.assign_first_free_seat(user_id, ticket.id, ticket.flight_id, &fare)
+                    .await?
+                    .ok_or_else(|| {
+                        AppError::Conflict(
+                            "No seat is left on the flight, please see an agent at the airport"
+                                .into(),
+                        )
+                    })?
+            }
+        };
+
+        let mut tx = self.pool.begin().await?;
+
+        // Locking the flight hands out the sequence numbers one check-in at a time
+        sqlx::query!(
+            "SELECT flight_id FROM flight WHERE flight_id = ? FOR UPDATE",
+            ticket.flight_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let checked_in = sqlx::query!(
+            r#"
+            SELECT
+                checked_in_at as "checked_in_at: NaiveDateTime",
+                boarding_sequence
+            FROM ticket
+            WHERE id = ?
+            "#,
+            ticket.id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        // A concurrent check-in of the same ticket got there first
+        if let (Some(checked_in_at), Some(sequence_number)) =
+            (checked_in.checked_in_at, checked_in.boarding_sequence)
+        {
+            tx.rollback().await?;
+            pass.checked_in_at = checked_in_at;
+            pass.sequence_number = sequence_number;
+            pass.barcode = pass.barcode_data();
+            return Ok(pass);
+        }
+
+        pass.sequence_number = sqlx::query_scalar!(
+            r#"
+            SELECT CAST(COALESCE(MAX(boarding_sequence), 0) + 1 AS SIGNED) as "next!: i32"
+            FROM ticket
+            WHERE flight_id = ?
+            "#,
+            ticket.flight_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE ticket
+            SET checked_in_at = ?, boarding_sequence = ?
+            WHERE id = ?
+            "#,
+            now,
+            pass.sequence_number,
+            ticket.id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        pass.barcode = pass.barcode_data();
+        Ok(pass)
+    }
+
+    // Boarding pass of a ticket already checked in, rendered for printing or the gate
+    // scanner, with dates in the passenger's locale. Does not check in, a ticket not
+    // checked in yet is a conflict.
+    #[instrument(skip(self))]
+    pub async fn boarding_pass_document(
+        &self,
+        user_id: i32,
+        booking_reference: &str,
+        format: DocumentFormat,
+        locale: DocumentLocale,
+    ) -> AppResult<Document> {
+        let booking_reference = normalize_booking_reference(booking_reference)?;
+
+        let checked_in = sqlx::query_scalar!(
             r#"
-            SELECT flight_id, flight_number, flight_date, available_tickets, version 
-            FROM flight 
-            WHERE flight_number = ? 
-            AND flight_date = ?
+            SELECT checked_in_at IS NOT NULL as "checked_in!: bool"
+            FROM ticket
+            WHERE booking_reference = ? AND customer_id = ?
             "#,
-            request.flight_number,
-            request.flight_date
+            booking_reference,
+            user_id
         )
         .fetch_optional(&self.pool)
-        .await?;
+        .await?
+        .ok_or_else(|| AppError::NotFound("No ticket with this booking reference".into()))?;
+        if !checked_in {
+            return Err(AppError::Conflict(
+                "The passenger must check in before getting a boarding pass".into(),
+            ));
+        }
 
-        match flight {
-            Some(_) => {}
-            None => {
-                return Err(AppError::NotFound(format!(
-                    "Flight {} does not exist on {}\n",
-                    request.flight_number, request.flight_date
-                )))
+        // Checking in again only reads back the boarding pass issued at check-in
+        let pass = self
+            .check_in(user_id, CheckinRequest { booking_reference })
+            .await?;
+        let file_name = format!("boarding-pass-{}", pass.booking_reference);
+        match format {
+            DocumentFormat::Png => Ok(Document::png(&file_name, document::qr_png(&pass.barcode)?)),
+            DocumentFormat::Pdf => {
+                let fields = [
+                    ("PASSENGER", pass.passenger_name.clone()),
+                    ("FLIGHT", format!("{} {}", CARRIER_CODE, pass.flight_number)),
+                    ("FROM", pass.departure_city.clone()),
+                    ("TO", pass.destination_city.clone()),
+                    ("DATE", locale.date(pass.departure.date())),
+                    // Local time of the departure airport, as scheduled
+                    ("DEPARTURE", locale.time(pass.departure.time())),
+                    ("GATE", pass.gate.clone().unwrap_or_else(|| "TBA".into())),
+                    ("SEAT", pass.seat_number.to_string()),
+                    ("CLASS", format!("{:?}", pass.fare_class)),
+                    ("BOARDING", format!("{:03}", pass.sequence_number)),
+                    ("BOOKING REF", pass.booking_reference.clone()),
+                ];
+                let bytes = document::pdf_with_qr("BOARDING PASS", &fields, &pass.barcode)?;
+                Ok(Document::pdf(&file_name, bytes))
             }
         }
+    }
 
-        let flight = sqlx::query!(
-            r#"SELECT flight_id FROM flight 
-            WHERE flight_number = ? AND flight_date = ?"#,
-            request.flight_number,
-            request.flight_date
-        )
-        .fetch_one(&self.pool)
-        .await?;
-
-        let ticket = sqlx::query!(
-            r#"SELECT id, seat_number FROM ticket 
-            WHERE customer_id = ? AND flight_id = ?"#,
-            customer_id,
-            flight.flight_id
+    // Give the passenger the first free seat of the fare section, or None when none is left
+    async fn assign_first_free_seat(
+        &self,
+        user_id: i32,
+        ticket_id: i32,
+        flight_id: i32,
+        fare: &Fare,
+    ) -> AppResult<Option<i32>> {
+        let seats = sqlx::query_scalar!(
+            r#"
+            SELECT seat_number
+            FROM seat_info
+            WHERE flight_id = ? AND seat_status = 'AVAILABLE'
+            ORDER BY seat_number
+            "#,
+            flight_id
         )
-        .fetch_optional(&self.pool)
+        .fetch_all(&self.pool)
         .await?;
 
-        // println!("ticket: {:?}", ticket);
-
-        let ticket = match ticket {
-            Some(t) => t,
-            None => {
-                return Err(AppError::BadRequest(
-                    "Customer does not have a ticket for this flight".into(),
-                ))
+        for seat_number in seats {
+            if !self
+                .seat_in_fare_section(flight_id, seat_number, fare)
+                .await?
+            {
+                continue;
             }
-        };
-
-        if let Some(current_seat) = ticket.seat_number {
-            if current_seat == request.seat_number {
-                return Err(AppError::BadRequest(
-                    "Cannot book the same seat you already have".into(),
-                ));
+            match self
+                .book_seat(user_id, ticket_id, flight_id, seat_number, None)
+                .await
+            {
+                Ok(_) => return Ok(Some(seat_number)),
+                // Taken in the meantime
+                Err(AppError::SeatTaken { .. }) => continue,
+                Err(e) => return Err(e),
             }
         }
-
-        // book the seat
-        self.book_seat(
-            customer_id,
-            flight.flight_id,
-            request.seat_number,
-            ticket.seat_number,
-        )
-        .await
+        Ok(None)
     }
 
+    #[instrument(skip(self))]
     pub async fn get_history(&self, user_id: i32) -> AppResult<BookingHistoryResponse> {
         let rows = sqlx::query!(
             r#"
             SELECT 
+                t.booking_reference,
                 f.flight_number, 
                 t.seat_number,
                 fr.departure_city, 
                 fr.destination_city, 
                 f.flight_date,
                 fr.departure_time, 
-                fr.arrival_time
+                fr.arrival_time,
+                da.timezone as "departure_timezone?",
+                aa.timezone as "destination_timezone?",
+                f.status as "status: FlightStatus",
+                f.delay_minutes,
+                t.needs_rebooking as "needs_rebooking: bool",
+                t.booking_id,
+                IF(b.status = 'PENDING_PAYMENT', fh.expires_at, NULL)
+                    as "fare_held_until: NaiveDateTime",
+                t.ssr_codes
             FROM ticket t
             INNER JOIN flight f ON t.flight_id = f.flight_id
             INNER JOIN flight_route fr ON f.flight_number = fr.flight_number
+            LEFT JOIN airport da ON fr.departure_airport = da.code
+            LEFT JOIN airport aa ON fr.destination_airport = aa.code
+            LEFT JOIN booking b ON t.booking_id = b.id
+            LEFT JOIN fare_hold fh ON fh.booking_id = b.id
             WHERE t.customer_id = ?
             ORDER BY f.flight_date DESC
             "#,
@@ -486,37 +3054,797 @@ impl TicketService {
 
         let flights: Vec<BookingHistoryDetail> = rows
             .iter()
-            .map(|row| BookingHistoryDetail {
-                flight_number: row.flight_number,
-                seat_number: if let Some(s) = row.seat_number {
-                    s.to_string()
-                } else {
-                    String::from("Not Selected")
-                },
-                departure_city: row.departure_city.clone(),
-                destination_city: row.destination_city.clone(),
-                flight_date: NaiveDate::from_ymd_opt(
+            .map(|row| {
+                let flight_date = NaiveDate::from_ymd_opt(
                     row.flight_date.year() as i32,
                     row.flight_date.month() as u32,
                     row.flight_date.day() as u32,
                 )
-                .unwrap(),
-                departure_time: NaiveTime::from_hms_opt(
+                .unwrap();
+                let departure_time = NaiveTime::from_hms_opt(
                     row.departure_time.hour() as u32,
                     row.departure_time.minute() as u32,
                     row.departure_time.second() as u32,
                 )
-                .unwrap(),
-                arrival_time: NaiveTime::from_hms_opt(
+                .unwrap();
+                let arrival_time = NaiveTime::from_hms_opt(
                     row.arrival_time.hour() as u32,
                     row.arrival_time.minute() as u32,
                     row.arrival_time.second() as u32,
                 )
-                .unwrap(),
+                .unwrap();
+                let times = FlightTimes::new(
+                    flight_date,
+                    departure_time,
+                    arrival_time,
+                    row.departure_timezone.as_deref(),
+                    row.destination_timezone.as_deref(),
+                );
+                BookingHistoryDetail {
+                    booking_reference: row.booking_reference.clone(),
+                    flight_number: row.flight_number,
+                    seat_number: if let Some(s) = row.seat_number {
+                        s.to_string()
+                    } else {
+                        String::from("Not Selected")
+                    },
+                    departure_city: row.departure_city.clone(),
+                    destination_city: row.destination_city.clone(),
+                    flight_date,
+                    departure_time,
+                    arrival_time,
+                    departure_at: times.map(|times| times.departure_at),
+                    arrival_at: times.map(|times| times.arrival_at),
+                    duration_minutes: times.map(|times| times.duration_minutes),
+                    flight_status: row.status,
+                    delay_minutes: row.delay_minutes,
+                    needs_rebooking: row.needs_rebooking,
+                    booking_id: row.booking_id,
+                    fare_held_until: row.fare_held_until,
+                    ssr_codes: ssr::ssr_codes_from_db(&row.ssr_codes),
+                }
             })
             .collect();
 
         // Build the response
         Ok(BookingHistoryResponse { flights })
     }
+
+    // Move the passengers of a cancelled flight to the next flight of the route with
+    // tickets left. Each passenger is handled in its own transaction and the outcome is
+    // recorded, so passengers that could not be rebooked are retried on the next run.
+    #[instrument(skip(self))]
+    pub async fn rebook_cancelled_flight(&self, flight_id: i32) -> AppResult<RebookingSummary> {
+        let flight = sqlx::query!(
+            r#"
+            SELECT
+                flight_number,
+                flight_date as "flight_date: NaiveDate",
+                status as "status: FlightStatus"
+            FROM flight
+            WHERE flight_id = ?
+            "#,
+            flight_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Flight {} not found", flight_id)))?;
+
+        if flight.status != FlightStatus::Cancelled {
+            return Err(AppError::Conflict(format!(
+                "Flight {} is not cancelled",
+                flight_id
+            )));
+        }
+
+        let tickets = sqlx::query!(
+            r#"
+            SELECT id, customer_id
+            FROM ticket
+            WHERE flight_id = ? AND needs_rebooking = TRUE
+            ORDER BY id
+            "#,
+            flight_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut passengers = Vec::new();
+        for ticket in tickets {
+            passengers.push(
+                self.rebook_ticket(
+                    ticket.id,
+                    ticket.customer_id,
+                    flight_id,
+                    flight.flight_number,
+                    flight.flight_date,
+                )
+                .await?,
+            );
+        }
+
+        let rebooked = passengers
+            .iter()
+            .filter(|passenger| passenger.status == RebookingStatus::Rebooked)
+            .count();
+        Ok(RebookingSummary {
+            flight_id,
+            rebooked,
+            failed: passengers.len() - rebooked,
+            passengers,
+        })
+    }
+
+    async fn rebook_ticket(
+        &self,
+        ticket_id: i32,
+        customer_id: i32,
+        flight_id: i32,
+        flight_number: i32,
+        flight_date: NaiveDate,
+    ) -> AppResult<RebookedPassenger> {
+        let mut tx = self.pool.begin().await?;
+
+        // Next flight of the route the passenger holds no ticket for, that still takes
+        // unaccompanied minors if the passenger is one
+        let target = sqlx::query!(
+            r#"
+            SELECT f.flight_id, f.flight_date as "flight_date: NaiveDate"
+            FROM flight f
+            JOIN flight_route fr ON f.flight_number = fr.flight_number
+            JOIN ticket old ON old.id = ?
+            WHERE f.flight_number = ?
+            AND f.flight_date > ?
+            AND f.status NOT IN ('CANCELLED', 'DEPARTED')
+            AND f.closed_at IS NULL
+            AND f.available_tickets > 0
+            AND NOT EXISTS (
+                SELECT 1 FROM ticket t
+                WHERE t.flight_id = f.flight_id AND t.customer_id = ?
+            )
+            AND (
+                old.unaccompanied_minor = FALSE
+                OR (
+                    SELECT COUNT(*) FROM ticket t
+                    WHERE t.flight_id = f.flight_id AND t.unaccompanied_minor = TRUE
+                ) < fr.um_quota
+            )
+            ORDER BY f.flight_date
+            LIMIT 1
+            FOR UPDATE
+            "#,
+            ticket_id,
+            flight_number,
+            flight_date,
+            customer_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let target_flight = target.as_ref().map(|target| target.flight_id);
+        let passenger = match target {
+            None => {
+                let reason = "No later flight on the route has tickets left".to_string();
+                sqlx::query!(
+                    r#"
+                    INSERT INTO rebooking (ticket_id, customer_id, flight_id, status, reason, created_at)
+                    VALUES (?, ?, ?, ?, ?, UTC_TIMESTAMP())
+                    "#,
+                    ticket_id,
+                    customer_id,
+                    flight_id,
+                    RebookingStatus::Failed.as_db_str(),
+                    reason
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                RebookedPassenger {
+                    customer_id,
+                    ticket_id,
+                    status: RebookingStatus::Failed,
+                    rebooked_ticket_id: None,
+                    rebooked_flight_date: None,
+                    reason: Some(reason),
+                }
+            }
+            Some(target) => {
+                let rebooked_ticket_id = self
+                    .move_ticket(
+                        &mut tx,
+                        ticket_id,
+                        customer_id,
+                        flight_id,
+                        target.flight_id,
+                        target.flight_date,
+                    )
+                    .await?;
+
+                RebookedPassenger {
+                    customer_id,
+                    ticket_id,
+                    status: RebookingStatus::Rebooked,
+                    rebooked_ticket_id: Some(rebooked_ticket_id),
+                    rebooked_flight_date: Some(target.flight_date),
+                    reason: None,
+                }
+            }
+        };
+
+        tx.commit().await?;
+        self.invalidate_cached(flight_id);
+        if let Some(target_flight) = target_flight {
+            self.invalidate_cached(target_flight);
+        }
+        Ok(passenger)
+    }
+
+    // Move a ticket to another flight of its route on a new ticket that keeps the fare,
+    // booking, booking reference, seat credit and op-up record, and record the rebooking.
+    // The target flight must be locked and have a ticket left. The seat and ticket of the
+    // old flight are not given back, callers release them when the old flight still
+    // sells. Callers refresh the cached availability of both flights once committed.
+    async fn move_ticket(
+        &self,
+        tx: &mut Transaction<'_, MySql>,
+        ticket_id: i32,
+        customer_id: i32,
+        flight_id: i32,
+        target_flight_id: i32,
+        target_flight_date: NaiveDate,
+    ) -> AppResult<i32> {
+        sqlx::query!(
+            r#"
+            UPDATE flight
+            SET available_tickets = available_tickets - 1,
+                version = version + 1
+            WHERE flight_id = ?
+            "#,
+            target_flight_id
+        )
+        .execute(&mut **tx)
+        .await?;
+        let tickets_left = tickets_left_on(tx, target_flight_id).await?;
+        let overbooked = is_overbooked(tx, target_flight_id, tickets_left).await?;
+
+        // The new ticket keeps the fare and booking of the old one
+        let rebooked_ticket_id = sqlx::query!(
+            r#"
+            INSERT INTO ticket (
+                customer_id, flight_id, flight_date, flight_number, unaccompanied_minor,
+                booking_id, fare_class, price, currency, public_id, ssr_codes, overbooked
+            )
+            SELECT customer_id, ?, ?, flight_number, unaccompanied_minor,
+                booking_id, fare_class, price, currency, ?, ssr_codes, ?
+            FROM ticket
+            WHERE id = ?
+            "#,
+            target_flight_id,
+            target_flight_date,
+            new_public_id(),
+            overbooked,
+            ticket_id
+        )
+        .execute(&mut **tx)
+        .await?
+        .last_insert_id() as i32;
+
+        // The guardian, the seat credit and the op-up record of the passenger follow the
+        // ticket rather than being dropped with the old one
+        sqlx::query!(
+            "UPDATE unaccompanied_minor SET ticket_id = ? WHERE ticket_id = ?",
+            rebooked_ticket_id,
+            ticket_id
+        )
+        .execute(&mut **tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE seat_charge SET ticket_id = ? WHERE ticket_id = ?",
+            rebooked_ticket_id,
+            ticket_id
+        )
+        .execute(&mut **tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE op_up SET ticket_id = ? WHERE ticket_id = ?",
+            rebooked_ticket_id,
+            ticket_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO rebooking (ticket_id, customer_id, flight_id, rebooked_ticket_id, status, created_at)
+            VALUES (?, ?, ?, ?, ?, UTC_TIMESTAMP())
+            "#,
+            ticket_id,
+            customer_id,
+            flight_id,
+            rebooked_ticket_id,
+            RebookingStatus::Rebooked.as_db_str()
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        let old = sqlx::query!(
+            r#"
+            SELECT
+                booking_reference,
+                flight_number,
+                flight_date as "flight_date: NaiveDate",
+                seat_number
+            FROM ticket
+            WHERE id = ?
+            "#,
+            ticket_id
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+        sqlx::query!("DELETE FROM ticket WHERE id = ?", ticket_id)
+            .execute(&mut **tx)
+            .await?;
+        // The passenger keeps their booking reference
+        sqlx::query!(
+            "UPDATE ticket SET booking_reference = ? WHERE id = ?",
+            old.booking_reference,
+            rebooked_ticket_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        outbox::enqueue(
+            tx,
+            &DomainEvent::TicketRebooked {
+                ticket_id,
+                rebooked_ticket_id,
+                customer_id,
+                flight_number: old.flight_number,
+                flight_date: old.flight_date,
+                seat_number: old.seat_number,
+                rebooked_flight_id: target_flight_id,
+                rebooked_flight_date: target_flight_date,
+            },
+        )
+        .await?;
+
+        Ok(rebooked_ticket_id)
+    }
+
+    // Flights of the route a passenger of a cancelled or long delayed flight may move to
+    #[instrument(skip(self))]
+    pub async fn get_rebooking_options(
+        &self,
+        user_id: i32,
+        ticket_id: i32,
+    ) -> AppResult<RebookingOptionsResponse> {
+        let mut conn = self.pool.acquire().await?;
+        let ticket = disrupted_ticket(&mut conn, user_id, ticket_id).await?;
+        let options = rebooking_options(&mut conn, &ticket).await?;
+        Ok(RebookingOptionsResponse {
+            ticket_id,
+            flight_status: ticket.status,
+            delay_minutes: ticket.delay_minutes,
+            options,
+        })
+    }
+
+    // Move a passenger of a cancelled or long delayed flight to one of its rebooking
+    // options, in one transaction. A delayed flight gets the seat and ticket back.
+    #[instrument(skip(self))]
+    pub async fn self_rebook(
+        &self,
+        user_id: i32,
+        ticket_id: i32,
+        request: SelfRebookingRequest,
+    ) -> AppResult<RebookedPassenger> {
+        let mut tx = self.pool.begin().await?;
+        let ticket = disrupted_ticket(&mut tx, user_id, ticket_id).await?;
+        let target = rebooking_options(&mut tx, &ticket)
+            .await?
+            .into_iter()
+            .find(|option| option.flight_id == request.flight_id)
+            .ok_or_else(|| {
+                AppError::Conflict(format!(
+                    "Flight {} is not a rebooking option of this ticket",
+                    request.flight_id
+                ))
+            })?;
+
+        if ticket.status != FlightStatus::Cancelled {
+            sqlx::query!(
+                r#"
+                UPDATE flight
+                SET available_tickets = available_tickets + 1,
+                    version = version + 1
+                WHERE flight_id = ?
+                "#,
+                ticket.flight_id
+            )
+            .execute(&mut *tx)
+            .await?;
+            if let Some(seat_number) = ticket.seat_number {
+                sqlx::query!(
+                    r#"
+                    UPDATE seat_info
+                    SET seat_status = 'AVAILABLE',
+                        version = version + 1
+                    WHERE flight_id = ? AND seat_number = ?
+                    "#,
+                    ticket.flight_id,
+                    seat_number
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        let rebooked_ticket_id = self
+            .move_ticket(
+                &mut tx,
+                ticket_id,
+                user_id,
+                ticket.flight_id,
+                target.flight_id,
+                target.flight_date,
+            )
+            .await?;
+        tx.commit().await?;
+        self.invalidate_cached(ticket.flight_id);
+        self.invalidate_cached(target.flight_id);
+
+        Ok(RebookedPassenger {
+            customer_id: user_id,
+            ticket_id,
+            status: RebookingStatus::Rebooked,
+            rebooked_ticket_id: Some(rebooked_ticket_id),
+            rebooked_flight_date: Some(target.flight_date),
+            reason: None,
+        })
+    }
+}
+
+struct DisruptedTicket {
+    id: i32,
+    customer_id: i32,
+    flight_id: i32,
+    flight_number: i32,
+    flight_date: NaiveDate,
+    seat_number: Option<i32>,
+    status: FlightStatus,
+    delay_minutes: i32,
+}
+
+// Ticket of the customer on a flight that is cancelled, or delayed by at least
+// SIGNIFICANT_DELAY_MINUTES and not departed yet, locked until the transaction ends
+async fn disrupted_ticket(
+    conn: &mut MySqlConnection,
+    user_id: i32,
+    ticket_id: i32,
+) -> AppResult<DisruptedTicket> {
+    let ticket = sqlx::query!(
+        r#"
+        SELECT
+            t.id,
+            t.customer_id,
+            t.flight_id,
+            t.flight_number,
+            t.flight_date as "flight_date: NaiveDate",
+            t.seat_number,
+            t.checked_in_at IS NOT NULL as "checked_in!: bool",
+            f.status as "status: FlightStatus",
+            f.delay_minutes
+        FROM ticket t
+        JOIN flight f ON t.flight_id = f.flight_id
+        WHERE t.id = ? AND t.customer_id = ?
+        FOR UPDATE
+        "#,
+        ticket_id,
+        user_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    // Do not reveal tickets of other customers
+    .ok_or_else(|| AppError::NotFound("Ticket not found".into()))?;
+
+    let disrupted = match ticket.status {
+        FlightStatus::Cancelled => true,
+        FlightStatus::Scheduled | FlightStatus::Delayed => {
+            ticket.delay_minutes >= SIGNIFICANT_DELAY_MINUTES
+        }
+        _ => false,
+    };
+    if !disrupted {
+        return Err(AppError::Conflict(format!(
+            "Only cancelled flights or flights delayed by {} minutes or more can be rebooked",
+            SIGNIFICANT_DELAY_MINUTES
+        )));
+    }
+    if ticket.checked_in {
+        return Err(AppError::Conflict(
+            "Checked in passengers cannot rebook".into(),
+        ));
+    }
+
+    Ok(DisruptedTicket {
+        id: ticket.id,
+        customer_id: ticket.customer_id,
+        flight_id: ticket.flight_id,
+        flight_number: ticket.flight_number,
+        flight_date: ticket.flight_date,
+        seat_number: ticket.seat_number,
+        status: ticket.status,
+        delay_minutes: ticket.delay_minutes,
+    })
+}
+
+// Flights of the route within SELF_REBOOKING_WINDOW_DAYS of the disrupted one, not yet
+// departed, with tickets left and taking the passenger as rebook_ticket does, locked
+// until the transaction ends
+async fn rebooking_options(
+    conn: &mut MySqlConnection,
+    ticket: &DisruptedTicket,
+) -> AppResult<Vec<RebookingOption>> {
+    let window = chrono::Duration::days(SELF_REBOOKING_WINDOW_DAYS);
+    let options = sqlx::query!(
+        r#"
+        SELECT
+            f.flight_id,
+            f.flight_number,
+            f.flight_date as "flight_date: NaiveDate",
+            fr.departure_time as "departure_time: NaiveTime",
+            fr.arrival_time as "arrival_time: NaiveTime",
+            f.status as "status: FlightStatus",
+            da.timezone as "departure_timezone?",
+            f.delay_minutes,
+            f.available_tickets
+        FROM flight f
+        JOIN flight_route fr ON f.flight_number = fr.flight_number
+        LEFT JOIN airport da ON fr.departure_airport = da.code
+        JOIN ticket old ON old.id = ?
+        WHERE f.flight_number = ?
+        AND f.flight_date BETWEEN ? AND ?
+        AND f.flight_id <> ?
+        AND f.status NOT IN ('CANCELLED', 'DEPARTED')
+        AND f.closed_at IS NULL
+        AND f.available_tickets > 0
+        AND NOT EXISTS (
+            SELECT 1 FROM ticket t
+            WHERE t.flight_id = f.flight_id AND t.customer_id = ?
+        )
+        AND (
+            old.unaccompanied_minor = FALSE
+            OR (
+                SELECT COUNT(*) FROM ticket t
+                WHERE t.flight_id = f.flight_id AND t.unaccompanied_minor = TRUE
+            ) < fr.um_quota
+        )
+        ORDER BY f.flight_date
+        FOR UPDATE
+        "#,
+        ticket.id,
+        ticket.flight_number,
+        ticket.flight_date - window,
+        ticket.flight_date + window,
+        ticket.flight_id,
+        ticket.customer_id
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let now = chrono::Utc::now().naive_utc();
+    let options = options
+        .into_iter()
+        .filter(|row| {
+            departure_utc(
+                row.flight_date,
+                row.departure_time,
+                row.departure_timezone.as_deref(),
+            ) > now
+        })
+        .map(|row| RebookingOption {
+            flight_id: row.flight_id,
+            flight_number: row.flight_number,
+            flight_date: row.flight_date,
+            departure_time: row.departure_time,
+            arrival_time: row.arrival_time,
+            flight_status: row.status,
+            delay_minutes: row.delay_minutes,
+            available_tickets: row.available_tickets,
+        })
+        .collect();
+    Ok(options)
+}
+
+// Record a ticket taken from the flight inventory for a booking saga, in the
+// transaction that took it
+async fn claim_for_saga(
+    tx: &mut Transaction<'_, MySql>,
+    saga_id: i32,
+    flight_id: i32,
+) -> AppResult<()> {
+    sqlx::query!(
+        "INSERT INTO booking_saga_claim (saga_id, flight_id) VALUES (?, ?)",
+        saga_id,
+        flight_id
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+// Checks of a booking request as a whole, shared by the booking and its validation
+struct RequestCheck {
+    issues: Vec<AppError>,
+    unaccompanied_minor: bool,
+}
+
+// Checks of one leg of a booking request, with the flight and fare they looked up
+struct LegCheck {
+    flight: Option<Flight>,
+    fare: Option<Fare>,
+    issues: Vec<AppError>,
+}
+
+// Collect a failed check as an issue of the request. Database errors are not about
+// the request, so they are returned instead.
+fn collect_issue<T>(issues: &mut Vec<AppError>, result: AppResult<T>) -> AppResult<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e @ AppError::DatabaseError(_)) => Err(e),
+        Err(e) => {
+            issues.push(e);
+            Ok(None)
+        }
+    }
+}
+
+// Tickets left on a flight, read in the transaction that locked it
+async fn tickets_left_on(tx: &mut Transaction<'_, MySql>, flight_id: i32) -> AppResult<i32> {
+    let tickets_left = sqlx::query_scalar!(
+        "SELECT available_tickets FROM flight WHERE flight_id = ?",
+        flight_id
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+    Ok(tickets_left)
+}
+
+// Whether the ticket sold last, leaving `tickets_left` on the flight, is overbooked and
+// may be bumped: sold beyond the physical seats. The tickets sold are those the flight
+// can sell less those left.
+async fn is_overbooked(
+    tx: &mut Transaction<'_, MySql>,
+    flight_id: i32,
+    tickets_left: i32,
+) -> AppResult<bool> {
+    let route = sqlx::query!(
+        r#"
+        SELECT a.capacity, fr.overbooking
+        FROM flight f
+        JOIN flight_route fr ON f.flight_number = fr.flight_number
+        JOIN aircraft a ON COALESCE(f.aircraft_id, fr.aircraft_id) = a.aircraft_id
+        WHERE f.flight_id = ?
+        "#,
+        flight_id
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+    let sold = overbooked_capacity(route.capacity, route.overbooking) - tickets_left;
+    Ok(sold > route.capacity)
+}
+
+// A ticket released in a transaction, to announce once the transaction commits
+pub(crate) struct ReleasedTicket {
+    customer_id: i32,
+    flight_id: i32,
+    flight_number: i32,
+    flight_date: NaiveDate,
+}
+
+// Take the price of a released ticket off the payment of its booking while it is still
+// pending, so confirming the payment does not charge for the ticket. Once nothing is
+// left to pay the payment expires, and a booking left without tickets is cancelled.
+async fn reduce_pending_payment(
+    tx: &mut Transaction<'_, MySql>,
+    booking_id: i32,
+    price: Decimal,
+) -> AppResult<()> {
+    let payment = sqlx::query!(
+        r#"
+        SELECT id, amount
+        FROM payment
+        WHERE booking_id = ? AND status = 'PENDING'
+        ORDER BY id DESC
+        LIMIT 1
+        FOR UPDATE
+        "#,
+        booking_id
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+    let Some(payment) = payment else {
+        return Ok(());
+    };
+
+    let amount = (payment.amount - price).max(Decimal::ZERO);
+    let tickets_left = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM ticket WHERE booking_id = ?",
+        booking_id
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+    if tickets_left > 0 && amount > Decimal::ZERO {
+        sqlx::query!(
+            "UPDATE payment SET amount = ? WHERE id = ?",
+            amount,
+            payment.id
+        )
+        .execute(&mut **tx)
+        .await?;
+        return Ok(());
+    }
+
+    sqlx::query!(
+        "UPDATE payment SET amount = ?, status = 'EXPIRED' WHERE id = ?",
+        amount,
+        payment.id
+    )
+    .execute(&mut **tx)
+    .await?;
+    let status = if tickets_left > 0 {
+        "CONFIRMED"
+    } else {
+        "CANCELLED"
+    };
+    sqlx::query!(
+        "UPDATE booking SET status = ? WHERE id = ?",
+        status,
+        booking_id
+    )
+    .execute(&mut **tx)
+    .await?;
+    // The promo code of a cancelled booking can be used again
+    if tickets_left == 0 {
+        promo_code_service::release_redemption(tx, booking_id).await?;
+    }
+    Ok(())
+}
+
+// Error of a failed leg reported for the whole itinerary, keeping the retry hints of the leg
+fn itinerary_error(e: AppError) -> AppError {
+    let message = format!(
+        "Failed to book some of your flights, please try again: {}",
+        e.to_string()
+    );
+    match e {
+        AppError::ConflictWithHints(_, hints) => AppError::ConflictWithHints(message, hints),
+        _ => AppError::ValidationError(message),
+    }
+}
+
+// Age in full years on the given date
+fn age_on(birth_date: NaiveDate, date: NaiveDate) -> i32 {
+    let mut age = date.year() - birth_date.year();
+    if (date.month(), date.day()) < (birth_date.month(), birth_date.day()) {
+        age -= 1;
+    }
+    age
+}
+
+fn is_valid_guardian(guardian: &GuardianContact) -> bool {
+    !guardian.name.trim().is_empty()
+        && !guardian.phone.trim().is_empty()
+        && !guardian.relationship.trim().is_empty()
+}
+
+// Booking reference as stored, accepting any case and surrounding spaces
+fn normalize_booking_reference(booking_reference: &str) -> AppResult<String> {
+    let booking_reference = booking_reference.trim().to_ascii_uppercase();
+    if booking_reference.len() != BOOKING_REFERENCE_LENGTH
+        || !booking_reference.chars().all(|c| c.is_ascii_alphanumeric())
+    {
+        return Err(AppError::BadRequest("Invalid booking reference".into()));
+    }
+    Ok(booking_reference)
 }
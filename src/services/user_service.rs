@@ -1,21 +1,91 @@
-use crate::models::user::{User, UserLoginRequest, UserLoginResponse, UserRegistrationRequest, Role};
-use crate::utils::error::{AppError, AppResult};
+use crate::models::db_enum::DbEnum;
+use crate::models::user::{
+    ChangePasswordRequest, ResetPasswordRequest, UpdateProfileRequest, UserLoginRequest,
+    SupportTokenResponse, UserLoginResponse, UserProfile, UserRegistrationRequest,
+    EMAIL_VERIFICATION_TOKEN_HOURS, PASSWORD_RESET_TOKEN_MINUTES, SUPPORT_TOKEN_MINUTES,
+};
+use crate::services::event_bus::{DomainEvent, EventBus};
+use crate::utils::error::{is_unique_violation, AppError, AppResult};
 use crate::utils::jwt;
+use crate::utils::region::DEFAULT_DATA_REGION;
 use bcrypt::{hash, verify, DEFAULT_COST};
+use chrono::{NaiveDate, NaiveDateTime};
 use sqlx::MySqlPool;
+use tracing::instrument;
 use validator::Validate;
 
+// Consecutive failed logins that lock an account, and for how long
+pub const DEFAULT_LOCKOUT_THRESHOLD: u32 = 5;
+pub const DEFAULT_LOCKOUT_MINUTES: i64 = 15;
+
 #[derive(Clone)]
 pub struct UserService {
     pool: MySqlPool,
+    event_bus: Option<EventBus>,
+    bcrypt_cost: u32,
+    data_region: String,
+    lockout_threshold: u32,
+    lockout_duration: chrono::Duration,
 }
 
 impl UserService {
     pub fn new(pool: MySqlPool) -> Self {
-        UserService { pool }
+        UserService {
+            pool,
+            event_bus: None,
+            bcrypt_cost: DEFAULT_COST,
+            data_region: DEFAULT_DATA_REGION.to_string(),
+            lockout_threshold: DEFAULT_LOCKOUT_THRESHOLD,
+            lockout_duration: chrono::Duration::minutes(DEFAULT_LOCKOUT_MINUTES),
+        }
+    }
+
+    // Lock an account for the duration after this many consecutive failed logins,
+    // never when the threshold is 0
+    pub fn with_lockout(mut self, threshold: u32, duration: chrono::Duration) -> Self {
+        self.lockout_threshold = threshold;
+        self.lockout_duration = duration;
+        self
+    }
+
+    // Data region new customers are tagged with
+    pub fn with_data_region(mut self, data_region: String) -> Self {
+        self.data_region = data_region;
+        self
+    }
+
+    // Work factor of the password and token hashes
+    pub fn with_bcrypt_cost(mut self, bcrypt_cost: u32) -> Self {
+        self.bcrypt_cost = bcrypt_cost;
+        self
+    }
+
+    // Publish domain events (password reset and email verification tokens) to the given event bus
+    pub fn with_event_bus(mut self, event_bus: EventBus) -> Self {
+        self.event_bus = Some(event_bus);
+        self
+    }
+
+    // The same account policy against another database, e.g. the sandbox, without the
+    // event bus of this one
+    pub fn for_database(&self, pool: MySqlPool) -> Self {
+        UserService {
+            bcrypt_cost: self.bcrypt_cost,
+            data_region: self.data_region.clone(),
+            lockout_threshold: self.lockout_threshold,
+            lockout_duration: self.lockout_duration,
+            ..UserService::new(pool)
+        }
+    }
+
+    fn publish(&self, event: DomainEvent) {
+        if let Some(event_bus) = &self.event_bus {
+            event_bus.publish(event);
+        }
     }
 
     // Register a new user
+    #[instrument(skip_all, fields(username = %request.username))]
     pub async fn register_user(&self, request: UserRegistrationRequest) -> AppResult<i32> {
         // Validate the request
         request
@@ -33,65 +103,516 @@ impl UserService {
         }
 
         // Hash password
-        let hashed_password = hash(request.password.as_bytes(), DEFAULT_COST)
+        let hashed_password = hash(request.password.as_bytes(), self.bcrypt_cost)
             .map_err(|e| AppError::ValidationError(e.to_string()))?;
 
         // Convert role to string for database insertion
-        let role_str = match request.role {
-            Role::Admin => "ADMIN",
-            Role::User => "USER",
-        };
+        let role_str = request.role.as_db_str();
+
+        // The user and its customer info are created together or not at all, a user
+        // without customer info would keep the username taken
+        let mut tx = self.pool.begin().await?;
 
-        // Insert user with role
+        // Insert user with role. A concurrent registration of the same username can get
+        // past the check above, the unique key then rejects this one.
         let result = sqlx::query!(
             "INSERT INTO user (username, password, role) VALUES (?, ?, ?)",
             request.username,
             hashed_password,
             role_str
         )
-        .execute(&self.pool)
-        .await?;
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            if is_unique_violation(&e) {
+                AppError::Conflict("Username already exists".into())
+            } else {
+                e.into()
+            }
+        })?;
 
         // Insert customer info to customer_info table
         let _customer_info_result = sqlx::query!(
-            "INSERT INTO customer_info (id, name, birth_date, gender) 
-            VALUES(?, ?, ?, ?)",
+            "INSERT INTO customer_info (id, name, birth_date, gender, email, data_region) 
+            VALUES(?, ?, ?, ?, ?, ?)",
             result.last_insert_id(),
             request.name,
             request.birth_date,
             request.gender,
+            request.email,
+            self.data_region,
         )
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
+        tx.commit().await?;
+
+        let user_id = result.last_insert_id() as i32;
+        if let Some(email) = &request.email {
+            self.issue_email_verification(user_id, email).await?;
+        }
 
-        Ok(result.last_insert_id() as i32)
+        Ok(user_id)
     }
 
     // Login user
     pub async fn login_user(&self, request: UserLoginRequest) -> AppResult<UserLoginResponse> {
-        let user = sqlx::query_as!(
-            User,
-            "SELECT id, username, password, role FROM user WHERE username = ?",
+        self.login_user_from(request, None).await
+    }
+
+    // Login user from the given client IP. Every attempt is recorded, and too many
+    // consecutive failures lock the account for a while, even for the right password.
+    #[instrument(skip_all, fields(username = %request.username))]
+    pub async fn login_user_from(
+        &self,
+        request: UserLoginRequest,
+        client_ip: Option<&str>,
+    ) -> AppResult<UserLoginResponse> {
+        let user = sqlx::query!(
+            r#"
+            SELECT id, username, password, role,
+                locked_until as "locked_until: NaiveDateTime"
+            FROM user
+            WHERE username = ?
+            "#,
             request.username
         )
         .fetch_optional(&self.pool)
-        .await?
-        .ok_or_else(|| AppError::AuthError("Invalid credentials".into()))?;
+        .await?;
+        let user = match user {
+            Some(user) => user,
+            None => {
+                self.record_login_attempt(&request.username, client_ip, false)
+                    .await?;
+                return Err(AppError::AuthError("Invalid credentials".into()));
+            }
+        };
+
+        let now = chrono::Utc::now().naive_utc();
+        if let Some(locked_until) = user.locked_until.filter(|until| *until > now) {
+            self.record_login_attempt(&request.username, client_ip, false)
+                .await?;
+            return Err(AppError::AuthError(format!(
+                "Account locked after too many failed logins, try again after {}",
+                locked_until.format("%Y-%m-%d %H:%M:%S UTC")
+            )));
+        }
 
         // Verify password
         let password_matches = verify(request.password.as_bytes(), &user.password)
             .map_err(|e| AppError::AuthError(e.to_string()))?;
 
+        self.record_login_attempt(&request.username, client_ip, password_matches)
+            .await?;
         if !password_matches {
+            self.count_failed_login(user.id, now).await?;
             return Err(AppError::AuthError("Invalid credentials".into()));
         }
+        sqlx::query!(
+            "UPDATE user SET failed_login_count = 0, locked_until = NULL WHERE id = ?",
+            user.id
+        )
+        .execute(&self.pool)
+        .await?;
 
         // Generate JWT token
-        let token = jwt::generate_token(user.id).map_err(|e| AppError::AuthError(e.to_string()))?;
+        let token = jwt::generate_token(user.id, &user.role)
+            .map_err(|e| AppError::AuthError(e.to_string()))?;
 
         Ok(UserLoginResponse {
             token,
             user_id: user.id,
         })
     }
+
+    async fn record_login_attempt(
+        &self,
+        username: &str,
+        client_ip: Option<&str>,
+        succeeded: bool,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO login_attempt (username, ip_address, succeeded, attempted_at)
+            VALUES (?, ?, ?, UTC_TIMESTAMP())
+            "#,
+            username,
+            client_ip,
+            succeeded
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // Count a failed login, locking the account when it reaches the threshold. The
+    // count starts over so the account gets the same number of tries once unlocked.
+    async fn count_failed_login(&self, user_id: i32, now: NaiveDateTime) -> AppResult<()> {
+        sqlx::query!(
+            "UPDATE user SET failed_login_count = failed_login_count + 1 WHERE id = ?",
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+        if self.lockout_threshold == 0 {
+            return Ok(());
+        }
+        let locked = sqlx::query!(
+            r#"
+            UPDATE user
+            SET failed_login_count = 0, locked_until = ?
+            WHERE id = ? AND failed_login_count >= ?
+            "#,
+            now + self.lockout_duration,
+            user_id,
+            self.lockout_threshold
+        )
+        .execute(&self.pool)
+        .await?;
+        if locked.rows_affected() > 0 {
+            tracing::warn!(user_id, "account locked after too many failed logins");
+        }
+        Ok(())
+    }
+
+    #[instrument(skip(self, request))]
+    pub async fn change_password(
+        &self,
+        user_id: i32,
+        request: ChangePasswordRequest,
+    ) -> AppResult<()> {
+        request
+            .validate()
+            .map_err(|e| AppError::ValidationError(format!("{:?}", e)))?;
+
+        let user = sqlx::query!("SELECT password FROM user WHERE id = ?", user_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("User not found".into()))?;
+
+        let password_matches = verify(request.old_password.as_bytes(), &user.password)
+            .map_err(|e| AppError::AuthError(e.to_string()))?;
+        if !password_matches {
+            return Err(AppError::AuthError("Invalid credentials".into()));
+        }
+        if request.old_password == request.new_password {
+            return Err(AppError::ValidationError(
+                "New password must differ from the old one".into(),
+            ));
+        }
+
+        let hashed_password = hash(request.new_password.as_bytes(), self.bcrypt_cost)
+            .map_err(|e| AppError::ValidationError(e.to_string()))?;
+        sqlx::query!(
+            "UPDATE user SET password = ? WHERE id = ?",
+            hashed_password,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Issue a single-use reset token and publish it for delivery to the user. Returns None
+    // for unknown usernames, which callers must not reveal.
+    // The token is "<id>.<secret>", only a hash of the secret is stored.
+    #[instrument(skip(self))]
+    pub async fn request_password_reset(&self, username: &str) -> AppResult<Option<String>> {
+        let user = match sqlx::query!("SELECT id FROM user WHERE username = ?", username)
+            .fetch_optional(&self.pool)
+            .await?
+        {
+            Some(user) => user,
+            None => return Ok(None),
+        };
+
+        let (secret, token_hash) = new_token_secret(self.bcrypt_cost)?;
+        let token_id = sqlx::query!(
+            r#"
+            INSERT INTO password_reset_token (user_id, token_hash, created_at, expires_at)
+            VALUES (?, ?, UTC_TIMESTAMP(), UTC_TIMESTAMP() + INTERVAL ? MINUTE)
+            "#,
+            user.id,
+            token_hash,
+            PASSWORD_RESET_TOKEN_MINUTES
+        )
+        .execute(&self.pool)
+        .await?
+        .last_insert_id();
+
+        let token = format!("{}.{}", token_id, secret);
+        self.publish(DomainEvent::PasswordResetRequested {
+            user_id: user.id,
+            token: token.clone(),
+        });
+        Ok(Some(token))
+    }
+
+    // Set a new password with a reset token. Using a token spends every other
+    // outstanding token of the user as well.
+    #[instrument(skip_all)]
+    pub async fn reset_password(&self, request: ResetPasswordRequest) -> AppResult<()> {
+        request
+            .validate()
+            .map_err(|e| AppError::ValidationError(format!("{:?}", e)))?;
+
+        let invalid_token = || AppError::AuthError("Invalid or expired reset token".into());
+        let (token_id, secret) = request.token.split_once('.').ok_or_else(invalid_token)?;
+        let token_id: i32 = token_id.parse().map_err(|_| invalid_token())?;
+
+        let mut tx = self.pool.begin().await?;
+
+        let token = sqlx::query!(
+            r#"
+            SELECT user_id, token_hash
+            FROM password_reset_token
+            WHERE id = ? AND used_at IS NULL AND expires_at > UTC_TIMESTAMP()
+            FOR UPDATE
+            "#,
+            token_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(invalid_token)?;
+
+        if !verify(secret.as_bytes(), &token.token_hash).unwrap_or(false) {
+            return Err(invalid_token());
+        }
+
+        let hashed_password = hash(request.new_password.as_bytes(), self.bcrypt_cost)
+            .map_err(|e| AppError::ValidationError(e.to_string()))?;
+        sqlx::query!(
+            "UPDATE user SET password = ? WHERE id = ?",
+            hashed_password,
+            token.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE password_reset_token
+            SET used_at = UTC_TIMESTAMP()
+            WHERE user_id = ? AND used_at IS NULL
+            "#,
+            token.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Send a new verification token for the current email of the user. Returns None
+    // when there is no email to verify.
+    #[instrument(skip(self))]
+    pub async fn resend_email_verification(&self, user_id: i32) -> AppResult<Option<String>> {
+        let profile = self.get_profile(user_id).await?;
+        match profile.email {
+            Some(email) if !profile.email_verified => {
+                Ok(Some(self.issue_email_verification(user_id, &email).await?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // Issue a single-use token for the given address and publish it for delivery,
+    // in the same "<id>.<secret>" form as password reset tokens
+    async fn issue_email_verification(&self, user_id: i32, email: &str) -> AppResult<String> {
+        let (secret, token_hash) = new_token_secret(self.bcrypt_cost)?;
+        let token_id = sqlx::query!(
+            r#"
+            INSERT INTO email_verification_token
+                (user_id, email, token_hash, created_at, expires_at)
+            VALUES (?, ?, ?, UTC_TIMESTAMP(), UTC_TIMESTAMP() + INTERVAL ? HOUR)
+            "#,
+            user_id,
+            email,
+            token_hash,
+            EMAIL_VERIFICATION_TOKEN_HOURS
+        )
+        .execute(&self.pool)
+        .await?
+        .last_insert_id();
+
+        let token = format!("{}.{}", token_id, secret);
+        self.publish(DomainEvent::EmailVerificationRequested {
+            user_id,
+            email: email.to_string(),
+            token: token.clone(),
+        });
+        Ok(token)
+    }
+
+    // Mark the email of the user as verified. The token is only good for the address
+    // it was sent to, and using it spends every other outstanding token of the user.
+    #[instrument(skip_all)]
+    pub async fn verify_email(&self, token: &str) -> AppResult<()> {
+        let invalid_token = || AppError::AuthError("Invalid or expired verification token".into());
+        let (token_id, secret) = token.split_once('.').ok_or_else(invalid_token)?;
+        let token_id: i32 = token_id.parse().map_err(|_| invalid_token())?;
+
+        let mut tx = self.pool.begin().await?;
+
+        let token = sqlx::query!(
+            r#"
+            SELECT user_id, email, token_hash
+            FROM email_verification_token
+            WHERE id = ? AND used_at IS NULL AND expires_at > UTC_TIMESTAMP()
+            FOR UPDATE
+            "#,
+            token_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(invalid_token)?;
+
+        if !verify(secret.as_bytes(), &token.token_hash).unwrap_or(false) {
+            return Err(invalid_token());
+        }
+
+        // The address may have changed since the token was sent
+        let customer = sqlx::query!(
+            "SELECT email FROM customer_info WHERE id = ? FOR UPDATE",
+            token.user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(invalid_token)?;
+        if customer.email.as_deref() != Some(token.email.as_str()) {
+            return Err(invalid_token());
+        }
+
+        sqlx::query!(
+            "UPDATE customer_info SET email_verified = TRUE WHERE id = ?",
+            token.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE email_verification_token
+            SET used_at = UTC_TIMESTAMP()
+            WHERE user_id = ? AND used_at IS NULL
+            "#,
+            token.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Issue a support code giving read-only access to the bookings of the user.
+    // It is not stored, so it stays valid until it expires.
+    #[instrument(skip(self))]
+    pub async fn create_support_token(&self, user_id: i32) -> AppResult<SupportTokenResponse> {
+        sqlx::query!("SELECT id FROM user WHERE id = ?", user_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("User not found".into()))?;
+
+        let expires_at =
+            chrono::Utc::now().naive_utc() + chrono::Duration::minutes(SUPPORT_TOKEN_MINUTES);
+        let token = jwt::generate_support_token(user_id, expires_at)
+            .map_err(|e| AppError::AuthError(e.to_string()))?;
+
+        Ok(SupportTokenResponse {
+            token,
+            scope: jwt::SUPPORT_SCOPE_BOOKINGS_READ.to_string(),
+            expires_at,
+        })
+    }
+
+    #[instrument(skip(self))]
+    pub async fn get_profile(&self, user_id: i32) -> AppResult<UserProfile> {
+        sqlx::query_as!(
+            UserProfile,
+            r#"
+            SELECT
+                u.id as user_id,
+                u.username,
+                c.name,
+                c.birth_date as "birth_date: NaiveDate",
+                c.gender,
+                c.email,
+                c.email_verified as "email_verified: bool",
+                c.phone
+            FROM user u
+            JOIN customer_info c ON c.id = u.id
+            WHERE u.id = ?
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".into()))
+    }
+
+    // Update the given customer details and return the resulting profile
+    #[instrument(skip(self, request))]
+    pub async fn update_profile(
+        &self,
+        user_id: i32,
+        request: UpdateProfileRequest,
+    ) -> AppResult<UserProfile> {
+        request
+            .validate()
+            .map_err(|e| AppError::ValidationError(format!("{:?}", e)))?;
+
+        if request
+            .birth_date
+            .map_or(false, |birth_date| birth_date > chrono::Utc::now().date_naive())
+        {
+            return Err(AppError::ValidationError(
+                "Birth date cannot be in the future".into(),
+            ));
+        }
+
+        // A new address has to be verified again
+        let current = self.get_profile(user_id).await?;
+        let new_email = request
+            .email
+            .clone()
+            .filter(|email| current.email.as_ref() != Some(email));
+
+        sqlx::query!(
+            r#"
+            UPDATE customer_info
+            SET name = COALESCE(?, name),
+                birth_date = COALESCE(?, birth_date),
+                gender = COALESCE(?, gender),
+                email_verified = email_verified AND NOT ?,
+                email = COALESCE(?, email),
+                phone = COALESCE(?, phone)
+            WHERE id = ?
+            "#,
+            request.name,
+            request.birth_date,
+            request.gender,
+            new_email.is_some(),
+            request.email,
+            request.phone,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if let Some(email) = &new_email {
+            self.issue_email_verification(user_id, email).await?;
+        }
+
+        self.get_profile(user_id).await
+    }
+}
+
+// Random token secret and the hash stored in its place
+fn new_token_secret(bcrypt_cost: u32) -> AppResult<(String, String)> {
+    let secret = uuid::Uuid::new_v4().simple().to_string();
+    let token_hash = hash(secret.as_bytes(), bcrypt_cost)
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+    Ok((secret, token_hash))
 }
@@ -0,0 +1,75 @@
+use crate::models::sandbox::SANDBOX_HEADER;
+use crate::services::partner_service::{Partner, PartnerService};
+use crate::services::sandbox_service::SandboxService;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use rocket::State;
+use rocket_okapi::request::OpenApiFromRequest;
+
+// Header carrying the API key of a partner
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+// Request guard for the partner endpoints, which take an API key instead of a user token
+#[derive(Debug, OpenApiFromRequest)]
+pub struct PartnerKey(pub Partner);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for PartnerKey {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let api_key = match request.headers().get_one(API_KEY_HEADER) {
+            Some(api_key) => api_key.trim(),
+            None => return Outcome::Error((Status::Unauthorized, ())),
+        };
+        let partner_service = match request.guard::<&State<PartnerService>>().await {
+            Outcome::Success(partner_service) => partner_service,
+            _ => return Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        match partner_service.authenticate(api_key).await {
+            Ok(Some(partner)) => Outcome::Success(PartnerKey(partner)),
+            Ok(None) => Outcome::Error((Status::Unauthorized, ())),
+            Err(e) => {
+                tracing::error!(error = %e, "failed to check partner API key");
+                Outcome::Error((Status::InternalServerError, ()))
+            }
+        }
+    }
+}
+
+// Sandbox of a request made with an API key and the X-Sandbox: true header. Requests
+// without the header use the live services.
+#[derive(OpenApiFromRequest)]
+pub struct Sandbox(pub Option<SandboxService>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Sandbox {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let requested = request
+            .headers()
+            .get_one(SANDBOX_HEADER)
+            .map_or(false, |value| {
+                value.trim() == "1" || value.trim().eq_ignore_ascii_case("true")
+            });
+        if !requested {
+            return Outcome::Success(Sandbox(None));
+        }
+        // The sandbox is for partners, users always see the live flights
+        if request.headers().get_one(API_KEY_HEADER).is_none() {
+            return Outcome::Error((Status::BadRequest, ()));
+        }
+
+        match request.guard::<&State<Option<SandboxService>>>().await {
+            Outcome::Success(sandbox) => match sandbox.inner() {
+                Some(sandbox_service) => Outcome::Success(Sandbox(Some(sandbox_service.clone()))),
+                // No sandbox database is configured
+                None => Outcome::Error((Status::ServiceUnavailable, ())),
+            },
+            _ => Outcome::Error((Status::InternalServerError, ())),
+        }
+    }
+}
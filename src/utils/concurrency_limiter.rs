@@ -0,0 +1,129 @@
+use crate::utils::jwt::AuthenticatedUser;
+use crate::utils::tunables::SharedTunables;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use rocket::State;
+use rocket_okapi::request::OpenApiFromRequest;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+// Default number of booking requests a single user may have in flight
+pub const DEFAULT_PER_USER_LIMIT: usize = 1;
+
+// Default number of booking requests a single IP address may have in flight
+pub const DEFAULT_PER_IP_LIMIT: usize = 10;
+
+// Prune idle entries once the map grows beyond this many keys
+const PRUNE_THRESHOLD: usize = 10_000;
+
+// Limits the number of concurrent requests per key with one semaphore per key
+#[derive(Default)]
+pub struct ConcurrencyLimiter {
+    // Semaphore of each key with the limit it was created for
+    semaphores: Mutex<HashMap<String, (usize, Arc<Semaphore>)>>,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Take one of the `limit` slots of the key, or None when the key already uses all
+    // its slots. The slot is given back when the permit is dropped. When the limit
+    // changes the key starts over with a new semaphore, requests still holding slots
+    // of the old one are not counted against the new limit.
+    pub fn try_acquire(&self, key: &str, limit: usize) -> Option<OwnedSemaphorePermit> {
+        let limit = limit.max(1);
+        let semaphore = {
+            let mut semaphores = self.semaphores.lock().unwrap();
+            if semaphores.len() > PRUNE_THRESHOLD {
+                // Nobody else holds a reference to idle semaphores
+                semaphores.retain(|_, (_, semaphore)| Arc::strong_count(semaphore) > 1);
+            }
+            let entry = semaphores
+                .entry(key.to_string())
+                .or_insert_with(|| (limit, Arc::new(Semaphore::new(limit))));
+            if entry.0 != limit {
+                *entry = (limit, Arc::new(Semaphore::new(limit)));
+            }
+            entry.1.clone()
+        };
+        semaphore.try_acquire_owned().ok()
+    }
+}
+
+// Limiters applied to the booking endpoints, with the limits of the current tunables
+pub struct BookingLimiters {
+    pub per_user: ConcurrencyLimiter,
+    pub per_ip: ConcurrencyLimiter,
+    tunables: SharedTunables,
+}
+
+impl BookingLimiters {
+    pub fn new(tunables: SharedTunables) -> Self {
+        BookingLimiters {
+            per_user: ConcurrencyLimiter::new(),
+            per_ip: ConcurrencyLimiter::new(),
+            tunables,
+        }
+    }
+
+    pub fn try_acquire_user(&self, user_id: i32) -> Option<OwnedSemaphorePermit> {
+        let limit = self.tunables.current().booking_concurrency_per_user;
+        self.per_user.try_acquire(&user_id.to_string(), limit)
+    }
+
+    pub fn try_acquire_ip(&self, ip: &str) -> Option<OwnedSemaphorePermit> {
+        let limit = self.tunables.current().booking_concurrency_per_ip;
+        self.per_ip.try_acquire(ip, limit)
+    }
+}
+
+// Request guard holding the booking slots of the caller for the duration of the request.
+// Overlapping attempts (e.g. double submits) are rejected with 409 Conflict.
+#[derive(Debug, OpenApiFromRequest)]
+pub struct BookingSlot {
+    _user_permit: Option<OwnedSemaphorePermit>,
+    _ip_permit: Option<OwnedSemaphorePermit>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BookingSlot {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let limiters = match request.guard::<&State<BookingLimiters>>().await {
+            Outcome::Success(limiters) => limiters,
+            // Limiting is disabled when no limiters are managed
+            _ => {
+                return Outcome::Success(BookingSlot {
+                    _user_permit: None,
+                    _ip_permit: None,
+                })
+            }
+        };
+
+        let ip_permit = match request.client_ip() {
+            Some(ip) => match limiters.try_acquire_ip(&ip.to_string()) {
+                Some(permit) => Some(permit),
+                None => return Outcome::Error((Status::Conflict, ())),
+            },
+            None => None,
+        };
+
+        let user_permit = match request.guard::<AuthenticatedUser>().await {
+            Outcome::Success(user) => match limiters.try_acquire_user(user.user_id) {
+                Some(permit) => Some(permit),
+                None => return Outcome::Error((Status::Conflict, ())),
+            },
+            _ => None,
+        };
+
+        Outcome::Success(BookingSlot {
+            _user_permit: user_permit,
+            _ip_permit: ip_permit,
+        })
+    }
+}
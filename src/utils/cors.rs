@@ -0,0 +1,75 @@
+use crate::utils::config::CorsConfig;
+use crate::utils::telemetry::REQUEST_ID_HEADER;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, Method, Status};
+use rocket::{Request, Response};
+use std::io::Cursor;
+
+// Adds the CORS headers to responses for allowed origins and answers preflight
+// requests, which no route handles, with 204 No Content
+pub struct Cors {
+    config: CorsConfig,
+}
+
+impl Cors {
+    pub fn new(config: CorsConfig) -> Self {
+        Cors { config }
+    }
+}
+
+// OPTIONS request a browser sends before a cross-origin request it may not make
+// without asking
+fn is_preflight(request: &Request<'_>) -> bool {
+    request.method() == Method::Options
+        && request
+            .headers()
+            .contains("Access-Control-Request-Method")
+}
+
+#[rocket::async_trait]
+impl Fairing for Cors {
+    fn info(&self) -> Info {
+        Info {
+            name: "CORS",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let origin = request.headers().get_one("Origin");
+        let Some(allowed_origin) = self.config.allowed_origin(origin) else {
+            // Without the headers the browser refuses the response
+            return;
+        };
+        if allowed_origin != "*" {
+            // The answer depends on the origin, caches must keep one per origin
+            response.adjoin_header(Header::new("Vary", "Origin"));
+        }
+        response.set_header(Header::new("Access-Control-Allow-Origin", allowed_origin));
+
+        if !is_preflight(request) {
+            response.set_header(Header::new(
+                "Access-Control-Expose-Headers",
+                REQUEST_ID_HEADER,
+            ));
+            return;
+        }
+
+        response.set_header(Header::new(
+            "Access-Control-Allow-Methods",
+            self.config.allowed_methods.join(", "),
+        ));
+        response.set_header(Header::new(
+            "Access-Control-Allow-Headers",
+            self.config.allowed_headers.join(", "),
+        ));
+        response.set_header(Header::new(
+            "Access-Control-Max-Age",
+            self.config.max_age_seconds.to_string(),
+        ));
+        // Replace the 404 of the missing OPTIONS route
+        response.set_status(Status::NoContent);
+        response.set_sized_body(0, Cursor::new(""));
+        response.remove_header("Content-Type");
+    }
+}
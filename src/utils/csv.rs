@@ -0,0 +1,86 @@
+use crate::utils::ndjson::{NdjsonStream, NDJSON_MEDIA_TYPE};
+use rocket::http::{ContentType, Header};
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::Request;
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::okapi::openapi3::{MediaType, RefOr, Responses};
+use rocket_okapi::response::OpenApiResponderInner;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+// Media type of comma separated values
+pub const CSV_MEDIA_TYPE: &str = "text/csv";
+
+// A row of a CSV export, its fields in the order of the header
+pub trait CsvRow {
+    const HEADER: &'static [&'static str];
+
+    fn fields(&self) -> Vec<String>;
+}
+
+// Render the rows with a header line, quoting fields as RFC 4180 asks
+pub fn to_csv<R: CsvRow>(rows: &[R]) -> String {
+    let mut csv = String::new();
+    push_line(&mut csv, R::HEADER.iter().map(|name| name.to_string()));
+    for row in rows {
+        push_line(&mut csv, row.fields());
+    }
+    csv
+}
+
+fn push_line(csv: &mut String, fields: impl IntoIterator<Item = String>) {
+    for (index, field) in fields.into_iter().enumerate() {
+        if index > 0 {
+            csv.push(',');
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            csv.push('"');
+            csv.push_str(&field.replace('"', "\"\""));
+            csv.push('"');
+        } else {
+            csv.push_str(&field);
+        }
+    }
+    csv.push_str("\r\n");
+}
+
+// Either a plain JSON body or a CSV file of its rows, downloaded under the file name,
+// or its rows streamed as NDJSON
+pub enum JsonOrCsv<T> {
+    Json(Json<T>),
+    Csv { file_name: String, csv: String },
+    Ndjson(NdjsonStream),
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for JsonOrCsv<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        match self {
+            JsonOrCsv::Json(json) => json.respond_to(request),
+            JsonOrCsv::Csv { file_name, csv } => {
+                let disposition = format!("attachment; filename=\"{}.csv\"", file_name);
+                let mut response = (ContentType::CSV, csv).respond_to(request)?;
+                response.set_header(Header::new("Content-Disposition", disposition));
+                Ok(response)
+            }
+            JsonOrCsv::Ndjson(stream) => stream.respond_to(request),
+        }
+    }
+}
+
+impl<T: Serialize + JsonSchema> OpenApiResponderInner for JsonOrCsv<T> {
+    fn responses(gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        let mut responses = Json::<T>::responses(gen)?;
+        for response in responses.responses.values_mut() {
+            if let RefOr::Object(response) = response {
+                response
+                    .content
+                    .insert(CSV_MEDIA_TYPE.to_string(), MediaType::default());
+                response
+                    .content
+                    .insert(NDJSON_MEDIA_TYPE.to_string(), MediaType::default());
+            }
+        }
+        Ok(responses)
+    }
+}
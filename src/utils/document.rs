@@ -0,0 +1,361 @@
+use crate::models::aircraft::SeatLayout;
+use crate::utils::error::{AppError, AppResult};
+use pdf_writer::{Content, Name, Pdf, Rect, Ref, Str};
+use qrcode::{Color, EcLevel, QrCode};
+use rocket::http::{ContentType, Header};
+use rocket::response::{self, Responder};
+use rocket::{FromFormField, Request};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::okapi::openapi3::{MediaType, Response, Responses};
+use rocket_okapi::response::OpenApiResponderInner;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::fmt::Write;
+use std::io::Cursor;
+
+// Pixels per QR code module in PNG images
+const PNG_MODULE_PIXELS: u32 = 8;
+
+// Light modules around the code that scanners need to find it
+const QUIET_ZONE_MODULES: u32 = 4;
+
+// A6 page in points, the usual size of a printed boarding pass
+const PAGE_WIDTH: f32 = 298.0;
+const PAGE_HEIGHT: f32 = 420.0;
+const MARGIN: f32 = 24.0;
+
+// Seat map drawing, in SVG user units
+const SEAT_SIZE: i32 = 28;
+const SEAT_GAP: i32 = 6;
+const AISLE_WIDTH: i32 = 24;
+const ROW_LABEL_WIDTH: i32 = 36;
+const SEAT_MAP_HEADER: i32 = 56;
+const SEAT_MAP_LEGEND: i32 = 48;
+
+// Seat map colors, readable for the common kinds of color blindness
+const SEAT_AVAILABLE_COLOR: &str = "#2e7d32";
+const SEAT_EXIT_ROW_COLOR: &str = "#ef6c00";
+const SEAT_ACCESSIBLE_COLOR: &str = "#1565c0";
+const SEAT_UNAVAILABLE_COLOR: &str = "#bdbdbd";
+
+// Machine-readable formats a document can be rendered in
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, JsonSchema, FromFormField)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentFormat {
+    Pdf,
+    Png,
+}
+
+// Rendered document, sent inline with its media type
+pub struct Document {
+    content_type: ContentType,
+    file_name: String,
+    bytes: Vec<u8>,
+}
+
+impl Document {
+    pub fn pdf(file_name: &str, bytes: Vec<u8>) -> Self {
+        Document {
+            content_type: ContentType::PDF,
+            file_name: format!("{}.pdf", file_name),
+            bytes,
+        }
+    }
+
+    pub fn svg(file_name: &str, svg: String) -> Self {
+        Document {
+            content_type: ContentType::SVG,
+            file_name: format!("{}.svg", file_name),
+            bytes: svg.into_bytes(),
+        }
+    }
+
+    pub fn png(file_name: &str, bytes: Vec<u8>) -> Self {
+        Document {
+            content_type: ContentType::PNG,
+            file_name: format!("{}.png", file_name),
+            bytes,
+        }
+    }
+
+    // Document read back from storage, typed by the extension of its name
+    pub fn from_file(file_name: &str, bytes: Vec<u8>) -> Self {
+        let content_type = file_name
+            .rsplit_once('.')
+            .and_then(|(_, extension)| ContentType::from_extension(extension))
+            .unwrap_or(ContentType::Binary);
+        Document {
+            content_type,
+            file_name: file_name.to_string(),
+            bytes,
+        }
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    pub fn content_type(&self) -> &ContentType {
+        &self.content_type
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Document {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let disposition = format!("inline; filename=\"{}\"", self.file_name);
+        let mut response = (self.content_type, self.bytes).respond_to(request)?;
+        response.set_header(Header::new("Content-Disposition", disposition));
+        Ok(response)
+    }
+}
+
+impl OpenApiResponderInner for Document {
+    fn responses(_gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        let mut response = Response {
+            description: "PDF document, PNG or SVG image".to_string(),
+            ..Default::default()
+        };
+        for media_type in ["application/pdf", "image/png", "image/svg+xml"] {
+            response
+                .content
+                .insert(media_type.to_string(), MediaType::default());
+        }
+        let mut responses = Responses::default();
+        responses
+            .responses
+            .insert("200".to_string(), response.into());
+        Ok(responses)
+    }
+}
+
+// Modules of the QR code of the data, row by row, true for dark
+fn qr_modules(data: &str) -> AppResult<(usize, Vec<bool>)> {
+    // Medium error correction still scans from a creased or smudged printout
+    let code = QrCode::with_error_correction_level(data.as_bytes(), EcLevel::M)
+        .map_err(|e| AppError::ValidationError(format!("Cannot encode QR code: {}", e)))?;
+    let modules = code
+        .to_colors()
+        .into_iter()
+        .map(|color| color == Color::Dark)
+        .collect();
+    Ok((code.width(), modules))
+}
+
+// QR code of the data as a black and white PNG image
+pub fn qr_png(data: &str) -> AppResult<Vec<u8>> {
+    let (width, modules) = qr_modules(data)?;
+    let side = (width as u32 + 2 * QUIET_ZONE_MODULES) * PNG_MODULE_PIXELS;
+
+    let mut pixels = Vec::with_capacity((side * side) as usize);
+    for y in 0..side {
+        for x in 0..side {
+            let module_x = (x / PNG_MODULE_PIXELS) as i64 - QUIET_ZONE_MODULES as i64;
+            let module_y = (y / PNG_MODULE_PIXELS) as i64 - QUIET_ZONE_MODULES as i64;
+            let inside =
+                (0..width as i64).contains(&module_x) && (0..width as i64).contains(&module_y);
+            let dark = inside && modules[module_y as usize * width + module_x as usize];
+            pixels.push(if dark { 0 } else { 255 });
+        }
+    }
+
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(Cursor::new(&mut bytes), side, side);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let encoding_error = |e: png::EncodingError| {
+        AppError::ValidationError(format!("Cannot encode PNG image: {}", e))
+    };
+    let mut writer = encoder.write_header().map_err(encoding_error)?;
+    writer.write_image_data(&pixels).map_err(encoding_error)?;
+    writer.finish().map_err(encoding_error)?;
+    Ok(bytes)
+}
+
+// One page PDF with a title, labelled fields and the QR code of the data below them
+pub fn pdf_with_qr(title: &str, fields: &[(&str, String)], qr_data: &str) -> AppResult<Vec<u8>> {
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let page_id = Ref::new(3);
+    let font_id = Ref::new(4);
+    let bold_font_id = Ref::new(5);
+    let content_id = Ref::new(6);
+    let font = Name(b"F1");
+    let bold_font = Name(b"F2");
+
+    let mut content = Content::new();
+    let mut y = PAGE_HEIGHT - MARGIN - 16.0;
+    content
+        .begin_text()
+        .set_font(bold_font, 16.0)
+        .next_line(MARGIN, y)
+        .show(Str(&latin1(title)))
+        .end_text();
+    y -= 10.0;
+    for (label, value) in fields {
+        y -= 16.0;
+        content
+            .begin_text()
+            .set_font(font, 8.0)
+            .next_line(MARGIN, y)
+            .show(Str(&latin1(label)))
+            .end_text();
+        content
+            .begin_text()
+            .set_font(bold_font, 10.0)
+            .next_line(MARGIN + 96.0, y)
+            .show(Str(&latin1(value)))
+            .end_text();
+    }
+
+    // The code fills the width left between the margins and the fields above
+    let (width, modules) = qr_modules(qr_data)?;
+    let available = (y - MARGIN - 8.0).min(PAGE_WIDTH - 2.0 * MARGIN);
+    let module_size = available / width as f32;
+    let left = (PAGE_WIDTH - module_size * width as f32) / 2.0;
+    let top = y - 8.0;
+    content.set_fill_gray(0.0);
+    for (index, dark) in modules.iter().enumerate() {
+        if *dark {
+            let (column, row) = (index % width, index / width);
+            content.rect(
+                left + column as f32 * module_size,
+                top - (row + 1) as f32 * module_size,
+                module_size,
+                module_size,
+            );
+        }
+    }
+    content.fill_nonzero();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids([page_id]).count(1);
+    let mut page = pdf.page(page_id);
+    page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+        .parent(page_tree_id)
+        .contents(content_id);
+    let mut fonts = page.resources().fonts();
+    fonts.pair(font, font_id);
+    fonts.pair(bold_font, bold_font_id);
+    fonts.finish();
+    page.finish();
+    pdf.type1_font(font_id)
+        .base_font(Name(b"Helvetica"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+    pdf.type1_font(bold_font_id)
+        .base_font(Name(b"Helvetica-Bold"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+    pdf.stream(content_id, &content.finish());
+    Ok(pdf.finish())
+}
+
+// Text in the encoding of the standard PDF fonts, characters outside of it become "?"
+fn latin1(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?'))
+        .collect()
+}
+
+// Seat map of a cabin as a standalone SVG image. Free seats are colored by kind, the
+// others are greyed out without telling booked from out of service seats.
+pub fn seat_map_svg(title: &str, layout: &SeatLayout, capacity: i32, available: &[i32]) -> String {
+    let seats_per_row = layout.seats_per_row();
+    let rows = (capacity + seats_per_row - 1) / seats_per_row;
+    let pitch = SEAT_SIZE + SEAT_GAP;
+    let width = ROW_LABEL_WIDTH + seats_per_row * pitch + AISLE_WIDTH + SEAT_GAP;
+    let height = SEAT_MAP_HEADER + rows.max(1) * pitch + SEAT_MAP_LEGEND;
+    // The aisle runs down the middle of the cabin
+    let column_x = |column: i32| {
+        let aisle = if column >= seats_per_row / 2 {
+            AISLE_WIDTH
+        } else {
+            0
+        };
+        ROW_LABEL_WIDTH + column * pitch + aisle
+    };
+
+    let mut svg = String::new();
+    // Writing to a String cannot fail
+    let _ = write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}" font-family="Helvetica, Arial, sans-serif">"#
+    );
+    let _ = write!(
+        svg,
+        r##"<rect width="100%" height="100%" fill="#ffffff"/><text x="{}" y="24" font-size="16" font-weight="bold">{}</text>"##,
+        ROW_LABEL_WIDTH,
+        xml_escape(title)
+    );
+    for column in 0..seats_per_row {
+        let letter = (b'A' + (column % 26) as u8) as char;
+        let _ = write!(
+            svg,
+            r#"<text x="{}" y="{}" font-size="11" text-anchor="middle">{}</text>"#,
+            column_x(column) + SEAT_SIZE / 2,
+            SEAT_MAP_HEADER - 8,
+            letter
+        );
+    }
+
+    for seat_number in 1..=capacity {
+        let attributes = layout.attributes(seat_number);
+        let column = (seat_number - 1) % seats_per_row;
+        let x = column_x(column);
+        let y = SEAT_MAP_HEADER + (attributes.row - 1) * pitch;
+        if column == 0 {
+            let _ = write!(
+                svg,
+                r#"<text x="{}" y="{}" font-size="11" text-anchor="end">{}</text>"#,
+                ROW_LABEL_WIDTH - 8,
+                y + SEAT_SIZE / 2 + 4,
+                attributes.row
+            );
+        }
+        let color = if !available.contains(&seat_number) {
+            SEAT_UNAVAILABLE_COLOR
+        } else if attributes.accessible {
+            SEAT_ACCESSIBLE_COLOR
+        } else if attributes.exit_row {
+            SEAT_EXIT_ROW_COLOR
+        } else {
+            SEAT_AVAILABLE_COLOR
+        };
+        let _ = write!(
+            svg,
+            r##"<rect x="{x}" y="{y}" width="{SEAT_SIZE}" height="{SEAT_SIZE}" rx="5" fill="{color}"><title>Seat {seat_number}</title></rect><text x="{}" y="{}" font-size="9" fill="#ffffff" text-anchor="middle">{seat_number}</text>"##,
+            x + SEAT_SIZE / 2,
+            y + SEAT_SIZE / 2 + 3
+        );
+    }
+
+    let legend_y = height - SEAT_MAP_LEGEND + 20;
+    let legend = [
+        ("Available", SEAT_AVAILABLE_COLOR),
+        ("Exit row", SEAT_EXIT_ROW_COLOR),
+        ("Accessible", SEAT_ACCESSIBLE_COLOR),
+        ("Taken", SEAT_UNAVAILABLE_COLOR),
+    ];
+    let legend_pitch = (width - ROW_LABEL_WIDTH) / legend.len() as i32;
+    for (index, (label, color)) in legend.iter().enumerate() {
+        let x = ROW_LABEL_WIDTH + index as i32 * legend_pitch;
+        let _ = write!(
+            svg,
+            r#"<rect x="{x}" y="{legend_y}" width="12" height="12" rx="2" fill="{color}"/><text x="{}" y="{}" font-size="11">{label}</text>"#,
+            x + 16,
+            legend_y + 10
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use rocket_okapi::request::OpenApiFromRequest;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+// Version of the envelope format reported in meta
+pub const ENVELOPE_VERSION: &str = "1";
+
+// Accept profile that opts a client into the envelope, e.g.
+// `Accept: application/json; profile="envelope"`
+pub const ENVELOPE_PROFILE: &str = "envelope";
+
+// Standard response envelope: payload, soft warnings and response metadata
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Envelope<T> {
+    pub data: T,
+    pub warnings: Vec<String>,
+    pub meta: EnvelopeMeta,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct EnvelopeMeta {
+    pub version: String,
+    pub generated_at: DateTime<Utc>,
+}
+
+impl<T> Envelope<T> {
+    pub fn new(data: T, warnings: Vec<String>) -> Self {
+        Envelope {
+            data,
+            warnings,
+            meta: EnvelopeMeta {
+                version: ENVELOPE_VERSION.to_string(),
+                generated_at: Utc::now(),
+            },
+        }
+    }
+}
+
+// Request guard telling whether the client asked for the envelope,
+// either by the Accept profile or by the `envelope=true` query flag
+#[derive(Debug, OpenApiFromRequest)]
+pub struct EnvelopeRequested(pub bool);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for EnvelopeRequested {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let by_query = matches!(request.query_value::<bool>("envelope"), Some(Ok(true)));
+
+        let by_accept = request
+            .headers()
+            .get("Accept")
+            .flat_map(|accept| accept.split(','))
+            .flat_map(|media_type| media_type.split(';').skip(1))
+            .filter_map(|param| param.trim().split_once('='))
+            .any(|(name, value)| {
+                name.trim().eq_ignore_ascii_case("profile")
+                    && value.trim().trim_matches('"') == ENVELOPE_PROFILE
+            });
+
+        Outcome::Success(EnvelopeRequested(by_query || by_accept))
+    }
+}
@@ -8,8 +8,17 @@ use std::io::Cursor;
 use serde_json::json;
 use serde::Serialize;
 use rocket_okapi::JsonSchema;
+use chrono::NaiveDate;
+use strum_macros::Display;
+use crate::utils::telemetry;
 
+// Suggested wait before repeating a request that ran out of optimistic lock retries
+const RETRY_EXHAUSTED_RETRY_AFTER_MS: u64 = 500;
+
+// New variants can be added without breaking the crates matching on it, which should
+// match on kind() or have a catch-all arm
 #[derive(Error, Debug, Serialize, JsonSchema)]
+#[non_exhaustive]
 pub enum AppError {
     #[error("Database error")]
     DatabaseError(String),
@@ -26,6 +35,21 @@ pub enum AppError {
     #[error("Conflict: {0}")]
     Conflict(String),
 
+    #[error("Conflict: {0}")]
+    ConflictWithHints(String, RetryHints),
+
+    // The seat is booked, held or blocked, with other free seats of the flight
+    #[error("Conflict: Seat {seat_number} of flight {flight_id} is booked, held or unavailable")]
+    SeatTaken {
+        flight_id: i32,
+        seat_number: i32,
+        alternative_seats: Vec<i32>,
+    },
+
+    // Every attempt of an optimistic update lost its version check to concurrent writers
+    #[error("Conflict: {operation} gave up after {attempts} attempts, please try again")]
+    RetryExhausted { operation: String, attempts: u32 },
+
     #[error("Unprocessable: {0}")]
     Unprocessable(String),
 
@@ -33,13 +57,98 @@ pub enum AppError {
     BadRequest(String),
 }
 
-// Convert sqlx::Error (database error) to AppError::DatabaseError
+// Stable kind of an error, sent to clients as its code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Display, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+#[non_exhaustive]
+pub enum ErrorKind {
+    Database,
+    Auth,
+    Validation,
+    NotFound,
+    Conflict,
+    SeatTaken,
+    RetryExhausted,
+    Unprocessable,
+    BadRequest,
+}
+
+impl AppError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            AppError::DatabaseError(_) => ErrorKind::Database,
+            AppError::AuthError(_) => ErrorKind::Auth,
+            AppError::ValidationError(_) => ErrorKind::Validation,
+            AppError::NotFound(_) => ErrorKind::NotFound,
+            AppError::Conflict(_) | AppError::ConflictWithHints(_, _) => ErrorKind::Conflict,
+            AppError::SeatTaken { .. } => ErrorKind::SeatTaken,
+            AppError::RetryExhausted { .. } => ErrorKind::RetryExhausted,
+            AppError::Unprocessable(_) => ErrorKind::Unprocessable,
+            AppError::BadRequest(_) => ErrorKind::BadRequest,
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        match self.kind() {
+            ErrorKind::Validation | ErrorKind::BadRequest => Status::BadRequest,
+            ErrorKind::NotFound => Status::NotFound,
+            ErrorKind::Database => Status::InternalServerError,
+            ErrorKind::Auth => Status::Unauthorized,
+            ErrorKind::Conflict | ErrorKind::SeatTaken | ErrorKind::RetryExhausted => {
+                Status::Conflict
+            }
+            ErrorKind::Unprocessable => Status::UnprocessableEntity,
+        }
+    }
+
+    // What the client can try instead, when known
+    pub fn retry_hints(&self) -> Option<RetryHints> {
+        match self {
+            AppError::ConflictWithHints(_, hints) => Some(hints.clone()),
+            AppError::SeatTaken {
+                alternative_seats, ..
+            } => Some(RetryHints {
+                alternative_seats: alternative_seats.clone(),
+                ..Default::default()
+            }),
+            // The contention is usually over by then
+            AppError::RetryExhausted { .. } => Some(RetryHints {
+                retry_after_ms: Some(RETRY_EXHAUSTED_RETRY_AFTER_MS),
+                ..Default::default()
+            }),
+            _ => None,
+        }
+    }
+}
+
+// Hints computed by the service layer to help clients recover from contention
+#[derive(Debug, Default, Clone, Serialize, JsonSchema)]
+pub struct RetryHints {
+    pub retry_after_ms: Option<u64>,
+    pub alternative_seats: Vec<i32>,
+    pub alternative_dates: Vec<NaiveDate>,
+}
+
+// Convert sqlx::Error (database error) to AppError::DatabaseError.
+// The cause is logged here since clients only get "Database error".
 impl From<sqlx::Error> for AppError {
     fn from(err: sqlx::Error) -> Self {
+        let sql_state = err
+            .as_database_error()
+            .and_then(|e| e.code())
+            .map(|code| code.into_owned());
+        tracing::error!(cause = %err, sql_state = sql_state.as_deref(), "database error");
         AppError::DatabaseError(err.to_string())
     }
 }
 
+// Whether the statement failed on a unique key, e.g. because a random code is taken
+pub fn is_unique_violation(err: &sqlx::Error) -> bool {
+    err.as_database_error()
+        .map_or(false, |e| e.is_unique_violation())
+}
+
 // Define a type alias for the result type
 pub type AppResult<T> = Result<T, AppError>;
 
@@ -47,20 +156,30 @@ pub type AppResult<T> = Result<T, AppError>;
 // Format all error from route level to a Http Response at route level
 #[rocket::async_trait]
 impl<'r> Responder<'r, 'static> for AppError {
-    fn respond_to(self, _: &'r Request<'_>) -> rocket::response::Result<'static> {
-        let status = match self {
-            AppError::ValidationError(_) => Status::BadRequest,
-            AppError::NotFound(_) => Status::NotFound,
-            AppError::DatabaseError(_) => Status::InternalServerError,
-            AppError::AuthError(_) => Status::Unauthorized,
-            AppError::Conflict(_) => Status::Conflict,
-            AppError::Unprocessable(_) => Status::UnprocessableEntity,
-            AppError::BadRequest(_) => Status::BadRequest,
-        };
+    fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let status = self.status();
 
-        let json = json!({
-            "error": self.to_string()
-        });
+        // Quoted by clients when reporting a failure, to find it in the logs
+        let request_id = telemetry::request_id(request);
+        if status.code >= 500 {
+            tracing::error!(request_id, error = ?self, "request error");
+        } else {
+            tracing::debug!(request_id, error = %self, "request error");
+        }
+
+        let json = match self.retry_hints() {
+            Some(hints) => json!({
+                "error": self.to_string(),
+                "code": self.kind(),
+                "hints": hints,
+                "request_id": request_id
+            }),
+            None => json!({
+                "error": self.to_string(),
+                "code": self.kind(),
+                "request_id": request_id
+            }),
+        };
 
         Response::build()
             .status(status)
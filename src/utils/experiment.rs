@@ -0,0 +1,63 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// An experiment splits users evenly between its variants. A user always gets the
+// same variant of an experiment, without storing anything.
+#[derive(Debug, Clone, Copy)]
+pub struct Experiment {
+    pub key: &'static str,
+    // The first variant is the control
+    pub variants: &'static [&'static str],
+}
+
+pub const NEAREST_SEAT_VARIANT: &str = "nearest_seat";
+
+// When the preferred seat is taken, the control books the ticket without a seat
+// and the nearest_seat variant assigns the closest available seat instead
+pub const SEAT_ASSIGNMENT: Experiment = Experiment {
+    key: "seat_assignment",
+    variants: &["control", NEAREST_SEAT_VARIANT],
+};
+
+// Experiments currently running
+pub const EXPERIMENTS: &[Experiment] = &[SEAT_ASSIGNMENT];
+
+// Variant of an experiment a user is in, attached to events for analysis
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ExperimentAssignment {
+    pub experiment: String,
+    pub variant: String,
+}
+
+impl Experiment {
+    pub fn variant_for(&self, user_id: i32) -> &'static str {
+        let hash = fnv1a(format!("{}:{}", self.key, user_id).as_bytes());
+        self.variants[(hash % self.variants.len() as u64) as usize]
+    }
+
+    pub fn assignment_for(&self, user_id: i32) -> ExperimentAssignment {
+        ExperimentAssignment {
+            experiment: self.key.to_string(),
+            variant: self.variant_for(user_id).to_string(),
+        }
+    }
+}
+
+// Variants of every running experiment for the user
+pub fn assignments_for(user_id: i32) -> Vec<ExperimentAssignment> {
+    EXPERIMENTS
+        .iter()
+        .map(|experiment| experiment.assignment_for(user_id))
+        .collect()
+}
+
+// FNV-1a is stable across Rust versions and platforms, unlike the std hasher,
+// so assignments survive upgrades
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(PRIME)
+    })
+}
@@ -0,0 +1,34 @@
+use crate::services::flight_service::FlightService;
+use crate::utils::error::{AppError, AppResult};
+use chrono::NaiveDate;
+use rocket::FromForm;
+use schemars::JsonSchema;
+
+// Query parameters identifying a flight, shared by the endpoints working on a
+// single flight. Use as `?<flight..>` in the route.
+#[derive(Debug, Clone, FromForm, JsonSchema)]
+pub struct FlightRef {
+    pub flight_number: i32,
+    // Date of the flight, as YYYY-MM-DD
+    pub flight_date: String,
+}
+
+impl FlightRef {
+    pub fn date(&self) -> AppResult<NaiveDate> {
+        parse_flight_date(&self.flight_date)
+    }
+
+    // Id of the referenced flight, NotFound when no flight has this number and date.
+    // Endpoints whose service looks the flight up anyway do not need it.
+    pub async fn flight_id(&self, flight_service: &FlightService) -> AppResult<i32> {
+        flight_service
+            .resolve_flight(self.flight_number, self.date()?)
+            .await
+    }
+}
+
+// Parse a flight date, with the same error message for every endpoint
+pub fn parse_flight_date(date: &str) -> AppResult<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| AppError::BadRequest("Invalid flight date format, expected YYYY-MM-DD".into()))
+}
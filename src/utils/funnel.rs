@@ -0,0 +1,49 @@
+use crate::models::funnel::FunnelStep;
+use crate::services::event_bus::{DomainEvent, EventBus};
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use rocket_okapi::request::OpenApiFromRequest;
+
+// Header carrying the anonymous session id generated by the client for funnel analytics
+pub const SESSION_ID_HEADER: &str = "X-Session-Id";
+
+// Longer ids are ignored rather than truncated, so two sessions never merge
+const MAX_SESSION_ID_LENGTH: usize = 64;
+
+// Reports the funnel steps of the request's session on the event bus.
+// Requests without a session id are not tracked, nor is anything by the default tracker.
+#[derive(OpenApiFromRequest, Default)]
+pub struct FunnelTracker {
+    session_id: Option<String>,
+    event_bus: Option<EventBus>,
+}
+
+impl FunnelTracker {
+    pub fn track(&self, step: FunnelStep, flight_number: Option<i32>) {
+        if let (Some(session_id), Some(event_bus)) = (&self.session_id, &self.event_bus) {
+            event_bus.publish(DomainEvent::FunnelStepReached {
+                session_id: session_id.clone(),
+                step,
+                flight_number,
+            });
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for FunnelTracker {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let session_id = request
+            .headers()
+            .get_one(SESSION_ID_HEADER)
+            .map(str::trim)
+            .filter(|id| !id.is_empty() && id.len() <= MAX_SESSION_ID_LENGTH)
+            .map(str::to_string);
+        Outcome::Success(FunnelTracker {
+            session_id,
+            event_bus: request.rocket().state::<EventBus>().cloned(),
+        })
+    }
+}
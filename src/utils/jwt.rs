@@ -1,41 +1,223 @@
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use crate::models::db_enum::DbEnum;
+use crate::models::user::Role;
+use crate::utils::config::AuthConfig;
+use chrono::NaiveDateTime;
+use jsonwebtoken::{decode, decode_header, encode, DecodingKey, EncodingKey, Header, Validation};
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome};
 use rocket::Request;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::env;
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
 use rocket_okapi::request::OpenApiFromRequest;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: i32,  // user_id
     pub exp: usize,
+    // Tokens issued before roles were added have no role
+    #[serde(default)]
+    pub role: String,
+    // Set on tokens restricted to another use, which are not login tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+}
+
+// Audience of the codes users hand to support agents
+pub const SUPPORT_AUDIENCE: &str = "support";
+
+// Header the support agent enters the code of the user in
+pub const SUPPORT_TOKEN_HEADER: &str = "X-Support-Token";
+
+// The only access a support code grants
+pub const SUPPORT_SCOPE_BOOKINGS_READ: &str = "bookings:read";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SupportClaims {
+    pub sub: i32, // user_id
+    pub exp: usize,
+    pub aud: String,
+    pub scope: String,
+}
+
+// Audience of the signed links to stored files
+pub const FILE_AUDIENCE: &str = "file";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileClaims {
+    pub sub: String, // key of the file in storage
+    pub exp: usize,
+    pub aud: String,
 }
 
 #[derive(Debug, OpenApiFromRequest)]
 pub struct AuthenticatedUser {
     pub user_id: i32,
+    pub is_admin: bool,
 }
 
+// Authenticated user with the ADMIN role
+#[derive(Debug, OpenApiFromRequest)]
+pub struct AdminUser {
+    pub user_id: i32,
+}
+
+// Admin acting as support agent, with read access to the bookings of the user
+// who gave them a support code
+#[derive(Debug, OpenApiFromRequest)]
+pub struct SupportAccess {
+    pub agent_id: i32,
+    pub user_id: i32,
+}
 
-pub fn generate_token(user_id: i32) -> Result<String, jsonwebtoken::errors::Error> {
+// Secrets and token lifetime, set from the configuration at startup
+static SETTINGS: OnceLock<AuthConfig> = OnceLock::new();
+
+// Sign and check tokens with the given settings. Only the first call has an effect.
+pub fn configure(config: AuthConfig) {
+    let _ = SETTINGS.set(config);
+}
+
+// Settings from configure, or from the environment when it was not called, as in tests
+fn settings() -> &'static AuthConfig {
+    SETTINGS.get_or_init(|| AuthConfig::from_env().expect("Invalid JWT configuration"))
+}
+
+// A secret tokens are signed with, named by the kid header of the tokens
+struct SigningKey {
+    kid: String,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+// The current secret first, then the previous ones still accepted
+static KEYS: OnceLock<Vec<SigningKey>> = OnceLock::new();
+
+fn keys() -> &'static [SigningKey] {
+    KEYS.get_or_init(|| {
+        let settings = settings();
+        std::iter::once(&settings.jwt_secret)
+            .chain(&settings.jwt_previous_secrets)
+            .map(|secret| SigningKey {
+                kid: key_id(secret),
+                encoding: EncodingKey::from_secret(secret.as_bytes()),
+                decoding: DecodingKey::from_secret(secret.as_bytes()),
+            })
+            .collect()
+    })
+}
+
+// Key id of a secret, the start of its SHA-256 so every server derives the same id
+// without the id giving the secret away
+pub fn key_id(secret: &str) -> String {
+    Sha256::digest(secret.as_bytes())
+        .iter()
+        .take(8)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// Sign the claims with the current secret, naming it in the kid header
+fn sign<T: Serialize>(claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+    let key = &keys()[0];
+    let header = Header {
+        kid: Some(key.kid.clone()),
+        ..Header::default()
+    };
+    encode(&header, claims, &key.encoding)
+}
+
+// Claims of a token signed with one of the accepted secrets. Tokens issued before
+// key ids were added have no kid and are tried against every secret.
+fn verify<T: DeserializeOwned>(token: &str, validation: &Validation) -> Option<T> {
+    let kid = decode_header(token).ok()?.kid;
+    keys()
+        .iter()
+        .filter(|key| kid.as_ref().map_or(true, |kid| *kid == key.kid))
+        .find_map(|key| decode::<T>(token, &key.decoding, validation).ok())
+        .map(|token_data| token_data.claims)
+}
+
+pub fn generate_token(user_id: i32, role: &str) -> Result<String, jsonwebtoken::errors::Error> {
     let expiration = chrono::Utc::now()
-        // Set expiration time to 24 hours
-        .checked_add_signed(chrono::Duration::hours(24))
+        .checked_add_signed(chrono::Duration::hours(settings().jwt_expiry_hours))
         .expect("valid timestamp")
         .timestamp() as usize;
 
     let claims = Claims {
         sub: user_id,
         exp: expiration,
+        role: role.to_string(),
+        aud: None,
+    };
+
+    sign(&claims)
+}
+
+// Short-lived code letting a support agent view the bookings of the user
+pub fn generate_support_token(
+    user_id: i32,
+    expires_at: NaiveDateTime,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let claims = SupportClaims {
+        sub: user_id,
+        exp: expires_at.and_utc().timestamp() as usize,
+        aud: SUPPORT_AUDIENCE.to_string(),
+        scope: SUPPORT_SCOPE_BOOKINGS_READ.to_string(),
     };
 
-    let secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret.as_bytes()),
-    )
+    sign(&claims)
+}
+
+// Validate a support code, which must be meant for support and carry the given scope
+pub fn decode_support_token(token: &str, scope: &str) -> Option<SupportClaims> {
+    let mut validation = Validation::default();
+    validation.set_audience(&[SUPPORT_AUDIENCE]);
+
+    verify::<SupportClaims>(token, &validation).filter(|claims| claims.scope == scope)
+}
+
+// Token of a link that downloads the stored file until it expires, without logging in
+pub fn generate_file_token(
+    key: &str,
+    expires_at: NaiveDateTime,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let claims = FileClaims {
+        sub: key.to_string(),
+        exp: expires_at.and_utc().timestamp() as usize,
+        aud: FILE_AUDIENCE.to_string(),
+    };
+
+    sign(&claims)
+}
+
+// Key of the file a download link is for, None when the link is invalid or expired
+pub fn decode_file_token(token: &str) -> Option<String> {
+    let mut validation = Validation::default();
+    validation.set_audience(&[FILE_AUDIENCE]);
+
+    verify::<FileClaims>(token, &validation).map(|claims| claims.sub)
+}
+
+// Claims of a valid login token
+pub fn decode_token(token: &str) -> Option<Claims> {
+    verify::<Claims>(token, &Validation::default())
+        // Support codes and other restricted tokens do not log in
+        .filter(|claims| claims.aud.is_none())
+}
+
+// Decode and validate the bearer token of the request
+fn decode_claims(request: &Request<'_>) -> Option<Claims> {
+    match request.headers().get_one("Authorization") {
+        Some(token) if token.starts_with("Bearer ") => decode_token(&token[7..]),
+        _ => None,
+    }
+}
+
+// User of the request when it carries a valid token, for logging
+pub fn authenticated_user_id(request: &Request<'_>) -> Option<i32> {
+    decode_claims(request).map(|claims| claims.sub)
 }
 
 #[rocket::async_trait]
@@ -43,23 +225,54 @@ impl<'r> FromRequest<'r> for AuthenticatedUser {
     type Error = ();
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        let token = match request.headers().get_one("Authorization") {
-            Some(token) if token.starts_with("Bearer ") => token[7..].to_string(),
-            _ => return Outcome::Error((Status::Unauthorized, ())),
-        };
+        match decode_claims(request) {
+            Some(claims) => Outcome::Success(AuthenticatedUser {
+                user_id: claims.sub,
+                is_admin: Role::from_db_str(&claims.role) == Some(Role::Admin),
+            }),
+            None => Outcome::Error((Status::Unauthorized, ())),
+        }
+    }
+}
 
-        let secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-        let token_data = match decode::<Claims>(
-            &token,
-            &DecodingKey::from_secret(secret.as_bytes()),
-            &Validation::default(),
-        ) {
-            Ok(token) => token,
-            Err(_) => return Outcome::Error((Status::Unauthorized, ())),
-        };
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminUser {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match decode_claims(request) {
+            Some(claims) if Role::from_db_str(&claims.role) == Some(Role::Admin) => {
+                Outcome::Success(AdminUser {
+                    user_id: claims.sub,
+                })
+            }
+            Some(_) => Outcome::Error((Status::Forbidden, ())),
+            None => Outcome::Error((Status::Unauthorized, ())),
+        }
+    }
+}
 
-        Outcome::Success(AuthenticatedUser {
-            user_id: token_data.claims.sub,
-        })
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SupportAccess {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let agent = match AdminUser::from_request(request).await {
+            Outcome::Success(admin) => admin,
+            Outcome::Error(error) => return Outcome::Error(error),
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+        let claims = request
+            .headers()
+            .get_one(SUPPORT_TOKEN_HEADER)
+            .and_then(|token| decode_support_token(token.trim(), SUPPORT_SCOPE_BOOKINGS_READ));
+        match claims {
+            Some(claims) => Outcome::Success(SupportAccess {
+                agent_id: agent.user_id,
+                user_id: claims.sub,
+            }),
+            // The code is missing, expired or not a support code
+            None => Outcome::Error((Status::Forbidden, ())),
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,112 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime};
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use rocket_okapi::request::OpenApiFromRequest;
+
+// Request guard extracting the caller's preferred language from Accept-Language,
+// e.g. "fr-CA,fr;q=0.9,en;q=0.8" yields "fr"
+#[derive(Debug, OpenApiFromRequest)]
+pub struct AcceptLanguage(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AcceptLanguage {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let language = request
+            .headers()
+            .get_one("Accept-Language")
+            .and_then(parse_accept_language);
+        Outcome::Success(AcceptLanguage(language))
+    }
+}
+
+// Pick the language with the highest quality value, keeping only the primary subtag
+pub fn parse_accept_language(header: &str) -> Option<String> {
+    header
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let tag = parts.next()?.trim();
+            if tag.is_empty() || tag == "*" {
+                return None;
+            }
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let primary = tag.split('-').next()?.to_lowercase();
+            Some((primary, quality))
+        })
+        .fold(None, |best: Option<(String, f32)>, (tag, quality)| match best {
+            Some((_, best_quality)) if best_quality >= quality => best,
+            _ => Some((tag, quality)),
+        })
+        .map(|(tag, _)| tag)
+}
+
+// Month abbreviations of the languages documents are rendered in
+const MONTHS_EN: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+const MONTHS_FR: [&str; 12] = [
+    "janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.", "nov.",
+    "déc.",
+];
+const MONTHS_DE: [&str; 12] = [
+    "Jan.", "Feb.", "März", "Apr.", "Mai", "Juni", "Juli", "Aug.", "Sep.", "Okt.", "Nov.", "Dez.",
+];
+const MONTHS_ES: [&str; 12] = [
+    "ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic",
+];
+
+// Dates and times in the passenger's language, shared by the document generators.
+// Schedule times are stored in the local time of the airport and are shown as is,
+// with the 24-hour clock used on every timetable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DocumentLocale {
+    #[default]
+    En,
+    Fr,
+    De,
+    Es,
+}
+
+impl DocumentLocale {
+    // Locale of a language from Accept-Language, English for any other language
+    pub fn from_language(language: Option<&str>) -> Self {
+        match language {
+            Some("fr") => DocumentLocale::Fr,
+            Some("de") => DocumentLocale::De,
+            Some("es") => DocumentLocale::Es,
+            _ => DocumentLocale::En,
+        }
+    }
+
+    // e.g. "23 Dec 2024", "23 déc. 2024", "23. Dez. 2024"
+    pub fn date(&self, date: NaiveDate) -> String {
+        let month = date.month0() as usize;
+        match self {
+            DocumentLocale::En => format!("{} {} {}", date.day(), MONTHS_EN[month], date.year()),
+            DocumentLocale::Fr => format!("{} {} {}", date.day(), MONTHS_FR[month], date.year()),
+            DocumentLocale::De => format!("{}. {} {}", date.day(), MONTHS_DE[month], date.year()),
+            DocumentLocale::Es => format!("{} {} {}", date.day(), MONTHS_ES[month], date.year()),
+        }
+    }
+
+    // e.g. "09:05", or "09 h 05" in French
+    pub fn time(&self, time: NaiveTime) -> String {
+        match self {
+            DocumentLocale::Fr => time.format("%H h %M").to_string(),
+            _ => time.format("%H:%M").to_string(),
+        }
+    }
+
+    pub fn date_time(&self, date_time: NaiveDateTime) -> String {
+        format!(
+            "{} {}",
+            self.date(date_time.date()),
+            self.time(date_time.time())
+        )
+    }
+}
@@ -0,0 +1,131 @@
+use crate::utils::telemetry;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Request, Response};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Most minutes of counts kept, a day
+pub const MAX_WINDOW_MINUTES: u64 = 24 * 60;
+
+// Routes whose requests count as bookings
+pub const BOOKING_ROUTES: &[&str] = &["/api/tickets/book", "/api/partners/bookings"];
+
+// Requests that finished during one minute since the epoch
+#[derive(Debug, Clone, Default)]
+struct MinuteCounts {
+    minute: u64,
+    counts: RequestCounts,
+}
+
+// Requests counted over some minutes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RequestCounts {
+    pub requests: u64,
+    // Requests answered with a 5xx status
+    pub server_errors: u64,
+    pub bookings: u64,
+    // Booking requests that took longer than the slow booking time
+    pub slow_bookings: u64,
+}
+
+impl RequestCounts {
+    fn add(&mut self, other: &RequestCounts) {
+        self.requests += other.requests;
+        self.server_errors += other.server_errors;
+        self.bookings += other.bookings;
+        self.slow_bookings += other.slow_bookings;
+    }
+}
+
+// Counts of the requests this server answered, per minute for the last day. Each
+// server counts its own requests.
+#[derive(Clone)]
+pub struct RequestMetrics {
+    minutes: Arc<Mutex<VecDeque<MinuteCounts>>>,
+    slow_booking: Duration,
+}
+
+impl RequestMetrics {
+    // Bookings taking longer than `slow_booking` are counted as slow
+    pub fn new(slow_booking: Duration) -> Self {
+        RequestMetrics {
+            minutes: Arc::new(Mutex::new(VecDeque::new())),
+            slow_booking,
+        }
+    }
+
+    pub fn record(&self, booking: bool, status: u16, latency: Duration) {
+        let minute = current_minute();
+        let mut minutes = self.minutes.lock().unwrap();
+        if minutes.back().map_or(true, |last| last.minute < minute) {
+            minutes.push_back(MinuteCounts {
+                minute,
+                counts: RequestCounts::default(),
+            });
+        }
+        while minutes
+            .front()
+            .map_or(false, |first| first.minute + MAX_WINDOW_MINUTES <= minute)
+        {
+            minutes.pop_front();
+        }
+
+        let counts = &mut minutes.back_mut().unwrap().counts;
+        counts.requests += 1;
+        if status >= 500 {
+            counts.server_errors += 1;
+        }
+        if booking {
+            counts.bookings += 1;
+            if latency > self.slow_booking {
+                counts.slow_bookings += 1;
+            }
+        }
+    }
+
+    // Requests of the last minutes, the current one included
+    pub fn counts(&self, minutes: u64) -> RequestCounts {
+        let since = (current_minute() + 1).saturating_sub(minutes);
+        let mut total = RequestCounts::default();
+        for counted in self.minutes.lock().unwrap().iter().rev() {
+            if counted.minute < since {
+                break;
+            }
+            total.add(&counted.counts);
+        }
+        total
+    }
+}
+
+fn current_minute() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 60)
+}
+
+// Counts the status and latency of every request that reached a route. Attached after
+// the request tracing, which starts the clock of the request.
+pub struct RecordMetrics(pub RequestMetrics);
+
+#[rocket::async_trait]
+impl Fairing for RecordMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request metrics",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Some(route) = request.route() else {
+            return;
+        };
+        let booking = BOOKING_ROUTES.contains(&route.uri.to_string().as_str());
+        self.0.record(
+            booking,
+            response.status().code,
+            telemetry::request_elapsed(request),
+        );
+    }
+}
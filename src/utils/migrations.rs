@@ -0,0 +1,14 @@
+use crate::utils::error::{AppError, AppResult};
+use sqlx::migrate::Migrator;
+use sqlx::MySqlPool;
+
+// Schema migrations in migrations/, embedded in the binary
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+// Bring the schema up to date by applying the migrations the database has not seen yet
+pub async fn run(pool: &MySqlPool) -> AppResult<()> {
+    MIGRATOR
+        .run(pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to run migrations: {}", e)))
+}
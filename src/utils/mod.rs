@@ -1,3 +1,26 @@
+pub mod api_key;
+pub mod concurrency_limiter;
+pub mod config;
+pub mod cors;
+pub mod csv;
+pub mod document;
+pub mod envelope;
+pub mod flight_ref;
+pub mod funnel;
 pub mod error;
+pub mod experiment;
 pub mod jwt;
+pub mod locale;
+pub mod metrics;
+pub mod migrations;
+pub mod ndjson;
+pub mod public_id;
+pub mod rate_limiter;
+pub mod region;
+pub mod retry;
+pub mod schema_check;
+pub mod seat_lock;
+pub mod statement_budget;
 pub mod swagger_doc;
+pub mod telemetry;
+pub mod tunables;
@@ -0,0 +1,148 @@
+use crate::utils::error::AppResult;
+use rocket::futures::stream::{self, BoxStream, StreamExt};
+use rocket::http::ContentType;
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::stream::TextStream;
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::Request;
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::okapi::openapi3::{MediaType, RefOr, Responses};
+use rocket_okapi::request::OpenApiFromRequest;
+use rocket_okapi::response::OpenApiResponderInner;
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::json;
+use std::future::Future;
+use tokio::sync::{mpsc, oneshot};
+
+// Media type of newline-delimited JSON
+pub const NDJSON_MEDIA_TYPE: &str = "application/x-ndjson";
+
+// Rows buffered between the database and a slow client
+const CHANNEL_CAPACITY: usize = 256;
+
+// Request guard telling whether the client asked for NDJSON with the Accept header
+#[derive(OpenApiFromRequest)]
+pub struct NdjsonRequested(pub bool);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for NdjsonRequested {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let requested = request
+            .headers()
+            .get("Accept")
+            .any(|accept| accept.contains(NDJSON_MEDIA_TYPE));
+        Outcome::Success(NdjsonRequested(requested))
+    }
+}
+
+// Receives the rows of an export one by one as they are fetched
+pub struct RowSink<T> {
+    tx: mpsc::Sender<T>,
+}
+
+impl<T> RowSink<T> {
+    // Returns false when nobody listens anymore and the export should stop
+    pub async fn send(&self, row: T) -> bool {
+        self.tx.send(row).await.is_ok()
+    }
+}
+
+// Run an export to completion and collect its rows, for clients that want a JSON array
+pub async fn collect_rows<T, F, Fut>(export: F) -> AppResult<Vec<T>>
+where
+    F: FnOnce(RowSink<T>) -> Fut,
+    Fut: Future<Output = AppResult<()>>,
+{
+    let (tx, mut rx) = mpsc::channel(CHANNEL_CAPACITY);
+    let collect = async move {
+        let mut rows = Vec::new();
+        while let Some(row) = rx.recv().await {
+            rows.push(row);
+        }
+        rows
+    };
+    let (result, rows) = tokio::join!(export(RowSink { tx }), collect);
+    result.map(|_| rows)
+}
+
+// Response body streaming one JSON document per line while the export runs,
+// so large exports never have to fit in memory
+pub struct NdjsonStream(BoxStream<'static, String>);
+
+impl NdjsonStream {
+    // Run the export in a background task. Errors after the response has started can
+    // only be reported as a last {"error": ...} line.
+    pub fn spawn<T, F, Fut>(export: F) -> Self
+    where
+        T: Serialize + Send + 'static,
+        F: FnOnce(RowSink<T>) -> Fut,
+        Fut: Future<Output = AppResult<()>> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let (error_tx, error_rx) = oneshot::channel();
+        let export = export(RowSink { tx });
+        tokio::spawn(async move {
+            if let Err(e) = export.await {
+                let _ = error_tx.send(e.to_string());
+            }
+        });
+
+        let lines = stream::unfold(
+            (rx, Some(error_rx)),
+            |(mut rx, error_rx): (mpsc::Receiver<T>, Option<oneshot::Receiver<String>>)| async move {
+                if let Some(row) = rx.recv().await {
+                    let line = serde_json::to_string(&row)
+                        .unwrap_or_else(|e| json!({ "error": e.to_string() }).to_string());
+                    return Some((line + "\n", (rx, error_rx)));
+                }
+                match error_rx?.await {
+                    Ok(error) => Some((json!({ "error": error }).to_string() + "\n", (rx, None))),
+                    // The export finished without error
+                    Err(_) => None,
+                }
+            },
+        );
+        NdjsonStream(lines.boxed())
+    }
+}
+
+impl<'r> Responder<'r, 'static> for NdjsonStream {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = TextStream(self.0).respond_to(request)?;
+        response.set_header(ContentType::new("application", "x-ndjson"));
+        Ok(response)
+    }
+}
+
+// Either a plain JSON body or an NDJSON stream of the same rows
+pub enum JsonOrNdjson<T> {
+    Json(Json<T>),
+    Ndjson(NdjsonStream),
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for JsonOrNdjson<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        match self {
+            JsonOrNdjson::Json(json) => json.respond_to(request),
+            JsonOrNdjson::Ndjson(stream) => stream.respond_to(request),
+        }
+    }
+}
+
+impl<T: Serialize + JsonSchema> OpenApiResponderInner for JsonOrNdjson<T> {
+    fn responses(gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        let mut responses = Json::<T>::responses(gen)?;
+        for response in responses.responses.values_mut() {
+            if let RefOr::Object(response) = response {
+                response
+                    .content
+                    .insert(NDJSON_MEDIA_TYPE.to_string(), MediaType::default());
+            }
+        }
+        Ok(responses)
+    }
+}
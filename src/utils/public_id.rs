@@ -0,0 +1,30 @@
+use std::sync::{Mutex, OnceLock};
+use ulid::{Generator, Ulid};
+
+// Length of a public id, a ULID in Crockford base32
+pub const PUBLIC_ID_LENGTH: usize = 26;
+
+static GENERATOR: OnceLock<Mutex<Generator>> = OnceLock::new();
+
+// Public id of a new record such as a ticket. ULIDs sort by creation time like the
+// auto-increment ids, but their 80 random bits keep them from being guessed from one
+// another. Ids made in the same millisecond still sort in the order they were made.
+pub fn new_public_id() -> String {
+    let generator = GENERATOR.get_or_init(|| Mutex::new(Generator::new()));
+    generator
+        .lock()
+        .unwrap()
+        .generate()
+        // The random part ran out within one millisecond, order is not kept then
+        .unwrap_or_else(|_| Ulid::new())
+        .to_string()
+}
+
+// The public id in its canonical form, None when the text is not one
+pub fn parse_public_id(text: &str) -> Option<String> {
+    let text = text.trim();
+    if text.len() != PUBLIC_ID_LENGTH {
+        return None;
+    }
+    Ulid::from_string(text).ok().map(|ulid| ulid.to_string())
+}
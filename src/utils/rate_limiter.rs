@@ -0,0 +1,301 @@
+use crate::utils::jwt::AuthenticatedUser;
+use crate::utils::telemetry;
+use crate::utils::tunables::{SharedTunables, Tunables};
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::{self, Responder, Response};
+use rocket::{Request, State};
+use rocket_okapi::request::OpenApiFromRequest;
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Prune full buckets once the map grows beyond this many keys
+const PRUNE_THRESHOLD: usize = 10_000;
+
+// Where the token buckets are kept
+#[rocket::async_trait]
+pub trait RateLimitStore: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Take a token from the bucket of the key, which holds up to `per_minute` tokens
+    // and gains that many a minute. Returns how long to wait when the bucket is empty.
+    async fn take(&self, key: &str, per_minute: u32) -> Result<Option<Duration>, String>;
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+// Buckets in the memory of this server, so each server has its own limits
+#[derive(Default)]
+pub struct InMemoryRateLimitStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl InMemoryRateLimitStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[rocket::async_trait]
+impl RateLimitStore for InMemoryRateLimitStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn take(&self, key: &str, per_minute: u32) -> Result<Option<Duration>, String> {
+        let capacity = per_minute as f64;
+        let per_second = capacity / 60.0;
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > PRUNE_THRESHOLD {
+            // A bucket that has refilled is the same as no bucket
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated_at).as_secs_f64() * per_second
+                    < capacity
+            });
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(capacity);
+        bucket.updated_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(None)
+        } else {
+            Ok(Some(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / per_second,
+            )))
+        }
+    }
+}
+
+// Buckets in Redis, shared by all the servers using it
+#[cfg(feature = "redis")]
+pub struct RedisRateLimitStore {
+    connection: redis::aio::ConnectionManager,
+    script: redis::Script,
+}
+
+#[cfg(feature = "redis")]
+impl RedisRateLimitStore {
+    pub async fn connect(redis_url: &str) -> Result<Self, String> {
+        let client = redis::Client::open(redis_url).map_err(|e| e.to_string())?;
+        let connection = redis::aio::ConnectionManager::new(client)
+            .await
+            .map_err(|e| e.to_string())?;
+        // The refill and the take happen in one step, on the clock of the Redis server
+        let script = redis::Script::new(
+            r#"
+            local capacity = tonumber(ARGV[1])
+            local per_ms = capacity / 60000
+            local time = redis.call('TIME')
+            local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+            local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'updated_at')
+            local tokens = tonumber(bucket[1]) or capacity
+            local updated_at = tonumber(bucket[2]) or now
+            tokens = math.min(capacity, tokens + (now - updated_at) * per_ms)
+            local wait_ms = 0
+            if tokens >= 1 then
+                tokens = tokens - 1
+            else
+                wait_ms = math.ceil((1 - tokens) / per_ms)
+            end
+            redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'updated_at', now)
+            redis.call('PEXPIRE', KEYS[1], 60000)
+            return wait_ms
+            "#,
+        );
+        Ok(RedisRateLimitStore { connection, script })
+    }
+}
+
+#[cfg(feature = "redis")]
+#[rocket::async_trait]
+impl RateLimitStore for RedisRateLimitStore {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn take(&self, key: &str, per_minute: u32) -> Result<Option<Duration>, String> {
+        let mut connection = self.connection.clone();
+        let wait_ms: u64 = self
+            .script
+            .key(format!("rate_limit:{}", key))
+            .arg(per_minute)
+            .invoke_async(&mut connection)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok((wait_ms > 0).then(|| Duration::from_millis(wait_ms)))
+    }
+}
+
+// Requests limited together, each with its rate in the tunables
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateLimitScope {
+    Login,
+    Registration,
+    Booking,
+}
+
+impl RateLimitScope {
+    fn name(&self) -> &'static str {
+        match self {
+            RateLimitScope::Login => "login",
+            RateLimitScope::Registration => "registration",
+            RateLimitScope::Booking => "booking",
+        }
+    }
+
+    fn per_minute(&self, tunables: &Tunables) -> u32 {
+        match self {
+            RateLimitScope::Login => tunables.login_per_minute,
+            RateLimitScope::Registration => tunables.registration_per_minute,
+            RateLimitScope::Booking => tunables.booking_per_minute,
+        }
+    }
+}
+
+// Token bucket rate limits per client, with the rates of the current tunables
+pub struct RateLimiter {
+    store: Arc<dyn RateLimitStore>,
+    tunables: SharedTunables,
+}
+
+impl RateLimiter {
+    pub fn new(store: Arc<dyn RateLimitStore>, tunables: SharedTunables) -> Self {
+        RateLimiter { store, tunables }
+    }
+
+    // Take a request of the client from the scope's budget, returning how long to wait
+    // when it is used up. A failing store lets the request through.
+    pub async fn check(&self, scope: RateLimitScope, client: &str) -> Option<Duration> {
+        let per_minute = scope.per_minute(&self.tunables.current());
+        if per_minute == 0 {
+            return None;
+        }
+        let key = format!("{}:{}", scope.name(), client);
+        match self.store.take(&key, per_minute).await {
+            Ok(wait) => wait,
+            Err(e) => {
+                tracing::warn!(store = self.store.name(), error = %e, "rate limit check failed");
+                None
+            }
+        }
+    }
+}
+
+// Seconds the rejected request should wait before retrying, for the 429 catcher
+struct RetryAfter(Option<u64>);
+
+// Check the client IP, and the user when logged in, against the limits of the scope
+async fn limit(request: &Request<'_>, scope: RateLimitScope) -> Outcome<(), ()> {
+    let limiter = match request.guard::<&State<RateLimiter>>().await {
+        Outcome::Success(limiter) => limiter,
+        // Rate limiting is disabled when no limiter is managed
+        _ => return Outcome::Success(()),
+    };
+
+    let mut clients = Vec::new();
+    if let Some(ip) = request.client_ip() {
+        clients.push(format!("ip:{}", ip));
+    }
+    if let Outcome::Success(user) = request.guard::<AuthenticatedUser>().await {
+        clients.push(format!("user:{}", user.user_id));
+    }
+    for client in clients {
+        if let Some(wait) = limiter.check(scope, &client).await {
+            // Whole seconds, rounded up so the retry does not come too early
+            let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            request.local_cache(|| RetryAfter(Some(seconds.max(1))));
+            return Outcome::Error((Status::TooManyRequests, ()));
+        }
+    }
+    Outcome::Success(())
+}
+
+// Request guard applying the login rate limit. Requests over it get 429 Too Many
+// Requests with a Retry-After header.
+#[derive(Debug, OpenApiFromRequest)]
+pub struct LoginRateLimit;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LoginRateLimit {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        limit(request, RateLimitScope::Login)
+            .await
+            .map(|_| LoginRateLimit)
+    }
+}
+
+// Request guard applying the registration rate limit
+#[derive(Debug, OpenApiFromRequest)]
+pub struct RegistrationRateLimit;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RegistrationRateLimit {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        limit(request, RateLimitScope::Registration)
+            .await
+            .map(|_| RegistrationRateLimit)
+    }
+}
+
+// Request guard applying the booking rate limit
+#[derive(Debug, OpenApiFromRequest)]
+pub struct BookingRateLimit;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BookingRateLimit {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        limit(request, RateLimitScope::Booking)
+            .await
+            .map(|_| BookingRateLimit)
+    }
+}
+
+// Body of 429 responses, telling the client when to retry
+pub struct TooManyRequests {
+    retry_after: Option<u64>,
+}
+
+impl<'r> Responder<'r, 'static> for TooManyRequests {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let body = json!({
+            "error": "Too many requests",
+            "request_id": telemetry::request_id(request)
+        })
+        .to_string();
+        let mut response = Response::build()
+            .status(Status::TooManyRequests)
+            .header(ContentType::JSON)
+            .sized_body(body.len(), Cursor::new(body))
+            .finalize();
+        if let Some(seconds) = self.retry_after {
+            response.set_header(Header::new("Retry-After", seconds.to_string()));
+        }
+        Ok(response)
+    }
+}
+
+#[rocket::catch(429)]
+pub fn too_many_requests(request: &Request<'_>) -> TooManyRequests {
+    TooManyRequests {
+        retry_after: request.local_cache(|| RetryAfter(None)).0,
+    }
+}
@@ -0,0 +1,78 @@
+use crate::utils::error::{AppError, AppResult};
+
+// Region of the data when the deployment does not set one
+pub const DEFAULT_DATA_REGION: &str = "global";
+
+// Longest region name the data_region columns hold
+pub const MAX_REGION_LENGTH: usize = 16;
+
+// Region names are short lowercase identifiers such as "eu" or "ca-east"
+pub fn is_region_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_REGION_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+// Data regions an export may contain: only the included ones when any are given,
+// never the excluded ones
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RegionFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl RegionFilter {
+    // Filter from comma separated lists of regions, e.g. "eu,uk"
+    pub fn parse(include: Option<&str>, exclude: Option<&str>) -> AppResult<Self> {
+        Ok(RegionFilter {
+            include: parse_list(include)?,
+            exclude: parse_list(exclude)?,
+        })
+    }
+
+    // Also leave out the given regions
+    pub fn excluding(mut self, regions: &[String]) -> Self {
+        for region in regions {
+            if !self.exclude.contains(region) {
+                self.exclude.push(region.clone());
+            }
+        }
+        self
+    }
+
+    pub fn allows(&self, region: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|included| included == region))
+            && !self.exclude.iter().any(|excluded| excluded == region)
+    }
+
+    // The lists joined with commas, for FIND_IN_SET in queries
+    pub fn include_list(&self) -> String {
+        self.include.join(",")
+    }
+
+    pub fn exclude_list(&self) -> String {
+        self.exclude.join(",")
+    }
+}
+
+fn parse_list(list: Option<&str>) -> AppResult<Vec<String>> {
+    let Some(list) = list else {
+        return Ok(Vec::new());
+    };
+    list.split(',')
+        .map(str::trim)
+        .filter(|region| !region.is_empty())
+        .map(|region| {
+            if is_region_name(region) {
+                Ok(region.to_string())
+            } else {
+                Err(AppError::BadRequest(format!(
+                    "Invalid data region {}",
+                    region
+                )))
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,124 @@
+use rand::Rng;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Longest wait before the first retry
+pub const DEFAULT_RETRY_BASE_DELAY: Duration = Duration::from_millis(5);
+
+// Longest wait between two attempts, however many failed before
+pub const DEFAULT_RETRY_MAX_DELAY: Duration = Duration::from_millis(200);
+
+// How an operation that lost a race to another request is retried: at most
+// `max_attempts` attempts, waiting a random time between them up to a ceiling that
+// doubles after every failed attempt (exponential backoff with full jitter). Requests
+// that collided once spread out instead of colliding again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    // At least one attempt is always made
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy {
+            max_attempts: max_attempts.max(1),
+            base_delay: DEFAULT_RETRY_BASE_DELAY,
+            max_delay: DEFAULT_RETRY_MAX_DELAY,
+        }
+    }
+
+    // Longest wait after the given failed attempt, counted from 1
+    pub fn delay_ceiling(&self, attempt: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    // Random wait after the given failed attempt, up to its ceiling
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let ceiling = self.delay_ceiling(attempt).as_micros() as u64;
+        Duration::from_micros(rand::thread_rng().gen_range(0..=ceiling))
+    }
+
+    // Start the attempts of one run of the operation
+    pub fn start(&self, metrics: &RetryMetrics) -> Retry {
+        Retry {
+            policy: *self,
+            metrics: metrics.clone(),
+            failed_attempts: 0,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct RetryCounters {
+    retries: AtomicU64,
+    exhausted: AtomicU64,
+    backoff_micros: AtomicU64,
+}
+
+// Retries of an operation since the service started, shared by the clones
+#[derive(Debug, Clone, Default)]
+pub struct RetryMetrics {
+    counters: Arc<RetryCounters>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryStats {
+    // Failed attempts followed by another one
+    pub retries: u64,
+    // Runs that gave up after their last attempt failed
+    pub exhausted: u64,
+    // Time spent waiting between attempts
+    pub backoff: Duration,
+}
+
+impl RetryMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stats(&self) -> RetryStats {
+        RetryStats {
+            retries: self.counters.retries.load(Ordering::Relaxed),
+            exhausted: self.counters.exhausted.load(Ordering::Relaxed),
+            backoff: Duration::from_micros(self.counters.backoff_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+// One run of an operation under a retry policy
+pub struct Retry {
+    policy: RetryPolicy,
+    metrics: RetryMetrics,
+    failed_attempts: u32,
+}
+
+impl Retry {
+    // Attempts that failed so far
+    pub fn failed_attempts(&self) -> u32 {
+        self.failed_attempts
+    }
+
+    // Call when an attempt lost its race. Waits and returns true when another attempt
+    // may be made, returns false right away once the attempts are used up.
+    pub async fn backoff(&mut self) -> bool {
+        self.failed_attempts += 1;
+        let counters = &self.metrics.counters;
+        if self.failed_attempts >= self.policy.max_attempts {
+            counters.exhausted.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        let delay = self.policy.delay(self.failed_attempts);
+        counters.retries.fetch_add(1, Ordering::Relaxed);
+        counters
+            .backoff_micros
+            .fetch_add(delay.as_micros() as u64, Ordering::Relaxed);
+        tokio::time::sleep(delay).await;
+        true
+    }
+}
@@ -0,0 +1,267 @@
+use crate::utils::error::AppResult;
+use crate::utils::migrations::MIGRATOR;
+use sqlx::MySqlPool;
+use std::collections::HashMap;
+use std::fmt;
+
+// Column definition expected by the application
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpectedColumn {
+    pub table: String,
+    pub column: String,
+    // Base type as reported by information_schema, e.g. "int" or "enum"
+    pub data_type: String,
+    // None when the definition does not say
+    pub nullable: Option<bool>,
+    // Column definition in the migrations
+    pub definition: String,
+}
+
+// Difference between the live database and the expected schema
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaDrift {
+    MissingTable {
+        table: String,
+    },
+    MissingColumn {
+        table: String,
+        definition: String,
+    },
+    TypeMismatch {
+        table: String,
+        column: String,
+        expected: String,
+        actual: String,
+        definition: String,
+    },
+    NullabilityMismatch {
+        table: String,
+        column: String,
+        expected_nullable: bool,
+        definition: String,
+    },
+}
+
+impl fmt::Display for SchemaDrift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaDrift::MissingTable { table } => write!(
+                f,
+                "Table {} is missing. Create it by running the migrations in migrations/",
+                table
+            ),
+            SchemaDrift::MissingColumn { table, definition } => write!(
+                f,
+                "Column {}.{} is missing. Fix: ALTER TABLE {} ADD COLUMN {};",
+                table,
+                definition.split_whitespace().next().unwrap_or_default(),
+                table,
+                definition
+            ),
+            SchemaDrift::TypeMismatch {
+                table,
+                column,
+                expected,
+                actual,
+                definition,
+            } => write!(
+                f,
+                "Column {}.{} has type {} but {} is expected. Fix: ALTER TABLE {} MODIFY COLUMN {};",
+                table, column, actual, expected, table, definition
+            ),
+            SchemaDrift::NullabilityMismatch {
+                table,
+                column,
+                expected_nullable,
+                definition,
+            } => write!(
+                f,
+                "Column {}.{} should be {}. Fix: ALTER TABLE {} MODIFY COLUMN {};",
+                table,
+                column,
+                if *expected_nullable { "NULL" } else { "NOT NULL" },
+                table,
+                definition
+            ),
+        }
+    }
+}
+
+// Columns defined by the migrations, in order: the create table statements, then the
+// columns added or modified by alter table statements with one clause per line
+pub fn expected_columns() -> Vec<ExpectedColumn> {
+    let mut columns: Vec<ExpectedColumn> = Vec::new();
+
+    let migrations = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration());
+    for statement in migrations.flat_map(|migration| migration.sql.split(';')) {
+        let lower = statement.to_lowercase();
+        if lower.contains("alter table") {
+            for column in parse_alter_table(statement) {
+                columns.retain(|c| !(c.table == column.table && c.column == column.column));
+                columns.push(column);
+            }
+            continue;
+        }
+        let start = match lower.find("create table") {
+            Some(start) => start,
+            None => continue,
+        };
+        let (open, close) = match (lower[start..].find('('), lower.rfind(')')) {
+            (Some(open), Some(close)) => (start + open, close),
+            _ => continue,
+        };
+        let table = match lower[start..open].split_whitespace().last() {
+            Some(table) => table.trim_matches('`').to_string(),
+            None => continue,
+        };
+
+        for line in statement[open + 1..close].lines() {
+            if let Some(column) = parse_column(&table, line) {
+                columns.push(column);
+            }
+        }
+    }
+
+    columns
+}
+
+// Columns added or modified by an alter table statement
+fn parse_alter_table(statement: &str) -> Vec<ExpectedColumn> {
+    let lower = statement.to_lowercase();
+    let start = match lower.find("alter table") {
+        Some(start) => start + "alter table".len(),
+        None => return Vec::new(),
+    };
+    let table = match lower[start..].split_whitespace().next() {
+        Some(table) => table.trim_matches('`').to_string(),
+        None => return Vec::new(),
+    };
+
+    let mut columns = Vec::new();
+    for line in statement[start..].lines() {
+        let line_lower = line.to_lowercase();
+        let clause = ["add column", "modify column"]
+            .iter()
+            .find_map(|keyword| line_lower.find(keyword).map(|at| at + keyword.len()));
+        if let Some(at) = clause {
+            if let Some(column) = parse_column(&table, &line[at..]) {
+                columns.push(column);
+            }
+        }
+    }
+    columns
+}
+
+// Parse a column definition line, skipping keys, constraints and their continuation lines
+fn parse_column(table: &str, line: &str) -> Option<ExpectedColumn> {
+    let definition = line.trim().trim_end_matches(',').trim();
+    let mut tokens = definition.split_whitespace();
+    let column = tokens.next()?.trim_matches('`');
+    let data_type = tokens.next()?;
+
+    let keyword = column.to_lowercase();
+    if definition.starts_with("--")
+        || matches!(
+            keyword.as_str(),
+            "constraint" | "primary" | "foreign" | "unique" | "key" | "index" | "references" | "on"
+        )
+    {
+        return None;
+    }
+
+    let data_type = data_type
+        .split('(')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    let data_type = match data_type.as_str() {
+        "boolean" | "bool" => "tinyint".to_string(),
+        "integer" => "int".to_string(),
+        _ => data_type,
+    };
+
+    let lower = definition.to_lowercase();
+    let nullable = if lower.contains("not null") {
+        Some(false)
+    } else if lower.ends_with(" null") {
+        Some(true)
+    } else {
+        None
+    };
+
+    Some(ExpectedColumn {
+        table: table.to_string(),
+        column: column.to_string(),
+        data_type,
+        nullable,
+        definition: definition.split_whitespace().collect::<Vec<_>>().join(" "),
+    })
+}
+
+// Compare the live database with the expected schema. Extra tables and columns are allowed.
+pub async fn check_schema(pool: &MySqlPool) -> AppResult<Vec<SchemaDrift>> {
+    let rows: Vec<(String, String, String, String)> = sqlx::query_as(
+        r#"
+        SELECT TABLE_NAME, COLUMN_NAME, DATA_TYPE, IS_NULLABLE
+        FROM information_schema.COLUMNS
+        WHERE TABLE_SCHEMA = DATABASE()
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    // (table, column) -> (data type, nullable)
+    let live: HashMap<(String, String), (String, bool)> = rows
+        .into_iter()
+        .map(|(table, column, data_type, is_nullable)| {
+            (
+                (table.to_lowercase(), column.to_lowercase()),
+                (data_type.to_lowercase(), is_nullable == "YES"),
+            )
+        })
+        .collect();
+
+    let mut drifts = Vec::new();
+    let mut missing_tables: Vec<String> = Vec::new();
+    for expected in expected_columns() {
+        if missing_tables.contains(&expected.table) {
+            continue;
+        }
+        if !live.keys().any(|(table, _)| *table == expected.table) {
+            missing_tables.push(expected.table.clone());
+            drifts.push(SchemaDrift::MissingTable {
+                table: expected.table,
+            });
+            continue;
+        }
+
+        match live.get(&(expected.table.clone(), expected.column.to_lowercase())) {
+            None => drifts.push(SchemaDrift::MissingColumn {
+                table: expected.table,
+                definition: expected.definition,
+            }),
+            Some((data_type, _)) if *data_type != expected.data_type => {
+                drifts.push(SchemaDrift::TypeMismatch {
+                    table: expected.table,
+                    column: expected.column,
+                    expected: expected.data_type,
+                    actual: data_type.clone(),
+                    definition: expected.definition,
+                })
+            }
+            Some((_, nullable)) if expected.nullable.map_or(false, |n| n != *nullable) => {
+                drifts.push(SchemaDrift::NullabilityMismatch {
+                    table: expected.table,
+                    column: expected.column,
+                    expected_nullable: !*nullable,
+                    definition: expected.definition,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+
+    Ok(drifts)
+}
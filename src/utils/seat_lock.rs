@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Longest a seat stays locked by a booking that never releases it, e.g. a server that
+// stopped halfway
+pub const SEAT_LOCK_TTL: Duration = Duration::from_secs(5);
+
+// Prune expired locks once the map grows beyond this many seats
+const PRUNE_THRESHOLD: usize = 10_000;
+
+// Locks a seat booking takes before touching the seat in the database, so only one of
+// the customers picking the same seat at once reads and writes it while the others
+// wait outside MySQL. Without a lock, seats are booked with the version check alone.
+#[rocket::async_trait]
+pub trait SeatLock: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Take the lock of the key for at most `ttl`. Returns the token to release it with,
+    // or None while another booking holds it.
+    async fn acquire(&self, key: &str, ttl: Duration) -> Result<Option<String>, String>;
+
+    // Release the lock if it is still the one taken with the token
+    async fn release(&self, key: &str, token: &str) -> Result<(), String>;
+}
+
+pub fn seat_lock_key(flight_id: i32, seat_number: i32) -> String {
+    format!("seat_lock:{}:{}", flight_id, seat_number)
+}
+
+// Locks in the memory of this server, which only keep its own bookings apart
+#[derive(Default)]
+pub struct InMemorySeatLock {
+    locks: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl InMemorySeatLock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[rocket::async_trait]
+impl SeatLock for InMemorySeatLock {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn acquire(&self, key: &str, ttl: Duration) -> Result<Option<String>, String> {
+        let now = Instant::now();
+        let mut locks = self.locks.lock().unwrap();
+        if locks.len() > PRUNE_THRESHOLD {
+            locks.retain(|_, (_, expires_at)| *expires_at > now);
+        }
+        if let Some((_, expires_at)) = locks.get(key) {
+            if *expires_at > now {
+                return Ok(None);
+            }
+        }
+        let token = uuid::Uuid::new_v4().to_string();
+        locks.insert(key.to_string(), (token.clone(), now + ttl));
+        Ok(Some(token))
+    }
+
+    async fn release(&self, key: &str, token: &str) -> Result<(), String> {
+        let mut locks = self.locks.lock().unwrap();
+        if locks.get(key).map_or(false, |(held, _)| held == token) {
+            locks.remove(key);
+        }
+        Ok(())
+    }
+}
+
+// Locks in Redis, shared by all the servers using it
+#[cfg(feature = "redis")]
+pub struct RedisSeatLock {
+    connection: redis::aio::ConnectionManager,
+    release_script: redis::Script,
+}
+
+#[cfg(feature = "redis")]
+impl RedisSeatLock {
+    pub async fn connect(redis_url: &str) -> Result<Self, String> {
+        let client = redis::Client::open(redis_url).map_err(|e| e.to_string())?;
+        let connection = redis::aio::ConnectionManager::new(client)
+            .await
+            .map_err(|e| e.to_string())?;
+        // A lock that expired and was taken by another booking is left alone
+        let release_script = redis::Script::new(
+            r#"
+            if redis.call('GET', KEYS[1]) == ARGV[1] then
+                return redis.call('DEL', KEYS[1])
+            end
+            return 0
+            "#,
+        );
+        Ok(RedisSeatLock {
+            connection,
+            release_script,
+        })
+    }
+}
+
+#[cfg(feature = "redis")]
+#[rocket::async_trait]
+impl SeatLock for RedisSeatLock {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn acquire(&self, key: &str, ttl: Duration) -> Result<Option<String>, String> {
+        let mut connection = self.connection.clone();
+        let token = uuid::Uuid::new_v4().to_string();
+        let set: Option<String> = redis::cmd("SET")
+            .arg(key)
+            .arg(&token)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async(&mut connection)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(set.map(|_| token))
+    }
+
+    async fn release(&self, key: &str, token: &str) -> Result<(), String> {
+        let mut connection = self.connection.clone();
+        let _: i64 = self
+            .release_script
+            .key(key)
+            .arg(token)
+            .invoke_async(&mut connection)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use crate::utils::telemetry;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Status};
+use rocket::{Request, Response};
+use serde_json::json;
+use std::io::Cursor;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Metadata, Span, Subscriber};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::{LookupSpan, Registry};
+
+// Target of the event sqlx logs for every statement it runs
+const STATEMENT_TARGET: &str = "sqlx::query";
+
+// Name of the span of a request, see telemetry
+const REQUEST_SPAN: &str = "request";
+
+// Statements run so far inside a request span, kept in the span's extensions
+struct StatementCount(AtomicUsize);
+
+// Counts the statements sqlx runs inside each request span. It sees the statement
+// events whatever the log level, so RUST_LOG does not change the counts.
+pub struct StatementCounter;
+
+impl<S> Layer<S> for StatementCounter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() == REQUEST_SPAN {
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut()
+                    .insert(StatementCount(AtomicUsize::new(0)));
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if event.metadata().target() != STATEMENT_TARGET {
+            return;
+        }
+        // Counted in the closest request span around the statement
+        let Some(scope) = ctx.event_scope(event) else {
+            return;
+        };
+        for span in scope {
+            if let Some(count) = span.extensions().get::<StatementCount>() {
+                count.0.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+    }
+}
+
+// The counter with a filter of its own, so the statement events are enabled for it
+// without reaching the log output
+pub fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    StatementCounter.with_filter(filter_fn(|metadata: &Metadata<'_>| {
+        metadata.target() == STATEMENT_TARGET || metadata.name() == REQUEST_SPAN
+    }))
+}
+
+// Statements run inside the request span so far. None when the counter is not
+// installed in the global subscriber.
+pub fn statements_in(span: &Span) -> Option<usize> {
+    span.with_subscriber(|(id, dispatch)| {
+        let registry = dispatch.downcast_ref::<Registry>()?;
+        let span = registry.span(id)?;
+        let extensions = span.extensions();
+        let count = extensions.get::<StatementCount>()?;
+        Some(count.0.load(Ordering::Relaxed))
+    })
+    .flatten()
+}
+
+// Logs the requests that run more statements than the budget, which usually means a
+// query per row or per leg where one query would do. Debug builds also fail them
+// with a 500, so the pattern is noticed in development and tests.
+pub struct StatementBudget {
+    budget: usize,
+}
+
+impl StatementBudget {
+    // A budget of 0 turns the check off
+    pub fn new(budget: usize) -> Self {
+        StatementBudget { budget }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for StatementBudget {
+    fn info(&self) -> Info {
+        Info {
+            name: "Statement budget",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if self.budget == 0 {
+            return;
+        }
+        let span = telemetry::request_span(request);
+        let Some(statements) = statements_in(span) else {
+            return;
+        };
+        if statements <= self.budget {
+            return;
+        }
+
+        let route = request
+            .route()
+            .map(|route| route.uri.to_string())
+            .unwrap_or_default();
+        span.in_scope(|| {
+            tracing::warn!(
+                route = %route,
+                statements,
+                budget = self.budget,
+                "request ran more SQL statements than its budget"
+            );
+        });
+
+        if cfg!(debug_assertions) {
+            let body = json!({
+                "error": format!(
+                    "Request ran {} SQL statements, more than the budget of {}",
+                    statements, self.budget
+                ),
+                "request_id": telemetry::request_id(request)
+            })
+            .to_string();
+            response.set_status(Status::InternalServerError);
+            response.set_header(ContentType::JSON);
+            response.set_sized_body(body.len(), Cursor::new(body));
+        }
+    }
+}
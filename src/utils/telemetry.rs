@@ -0,0 +1,144 @@
+use crate::utils::jwt;
+use crate::utils::statement_budget;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Request, Response};
+use rocket_okapi::request::OpenApiFromRequest;
+use std::time::{Duration, Instant};
+use tracing::Span;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter, Layer};
+
+// Header carrying the request id, taken from the client when it sends a usable one
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+// Longer ids from clients are replaced rather than truncated
+const MAX_REQUEST_ID_LENGTH: usize = 64;
+
+// Install the global subscriber. The level comes from RUST_LOG (info by default),
+// and LOG_FORMAT=json writes one JSON object per event for log collectors. The
+// level only applies to the output, the statement counter sees every statement.
+pub fn init() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let output = if std::env::var("LOG_FORMAT").map_or(false, |format| format == "json") {
+        fmt::layer().json().boxed()
+    } else {
+        fmt::layer().boxed()
+    };
+    // Fails when a subscriber is already installed, e.g. by tests
+    let _ = tracing_subscriber::registry()
+        .with(output.with_filter(filter))
+        .with(statement_budget::layer())
+        .try_init();
+}
+
+// Per request state kept in the request-local cache
+struct RequestContext {
+    id: String,
+    started: Instant,
+    span: Span,
+}
+
+fn context<'r>(request: &'r Request<'_>) -> &'r RequestContext {
+    request.local_cache(|| {
+        let id = request
+            .headers()
+            .get_one(REQUEST_ID_HEADER)
+            .map(str::trim)
+            .filter(|id| {
+                !id.is_empty()
+                    && id.len() <= MAX_REQUEST_ID_LENGTH
+                    && id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            })
+            .map(str::to_string)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let span = tracing::info_span!(
+            "request",
+            request_id = %id,
+            method = %request.method(),
+            path = %request.uri().path(),
+            user_id = tracing::field::Empty,
+        );
+        RequestContext {
+            id,
+            started: Instant::now(),
+            span,
+        }
+    })
+}
+
+// Id of the request, to quote in logs and error responses
+pub fn request_id<'r>(request: &'r Request<'_>) -> &'r str {
+    &context(request).id
+}
+
+// Time since the request arrived
+pub fn request_elapsed(request: &Request<'_>) -> Duration {
+    context(request).started.elapsed()
+}
+
+// Span of the request, which its service calls run inside
+pub fn request_span<'r>(request: &'r Request<'_>) -> &'r Span {
+    &context(request).span
+}
+
+// Assigns every request an id, returned in the X-Request-Id header, and logs
+// its route, user, status and latency once the response is ready
+pub struct RequestTracing;
+
+#[rocket::async_trait]
+impl Fairing for RequestTracing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request tracing",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        // Start the clock before the handler runs
+        context(request);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let context = context(request);
+        let route = request
+            .route()
+            .map(|route| route.uri.to_string())
+            .unwrap_or_default();
+        let user_id = jwt::authenticated_user_id(request);
+        if let Some(user_id) = user_id {
+            context.span.record("user_id", &user_id);
+        }
+
+        let status = response.status().code;
+        let latency_ms = context.started.elapsed().as_millis() as u64;
+        context.span.in_scope(|| {
+            if status >= 500 {
+                tracing::error!(route = %route, status, latency_ms, "request failed");
+            } else {
+                tracing::info!(route = %route, status, latency_ms, "request completed");
+            }
+        });
+
+        response.set_header(Header::new(REQUEST_ID_HEADER, context.id.clone()));
+    }
+}
+
+// Span of the current request. Service calls run inside it so their spans and
+// events carry the request id.
+#[derive(OpenApiFromRequest)]
+pub struct RequestSpan(pub Span);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestSpan {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RequestSpan(context(request).span.clone()))
+    }
+}
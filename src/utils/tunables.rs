@@ -0,0 +1,205 @@
+use crate::models::config::ConfigReloadResponse;
+use crate::utils::config::{AppConfig, SeatMapView};
+use crate::utils::error::AppResult;
+use arc_swap::ArcSwap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+// Settings that take effect while the server runs. The rest of AppConfig is only
+// read at startup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tunables {
+    pub booking_concurrency_per_user: usize,
+    pub booking_concurrency_per_ip: usize,
+    pub availability_cache_ttl: Duration,
+    pub change_settle_time: Duration,
+    pub search_cache_ttl: Duration,
+    pub seat_map_cache_ttl: Duration,
+    pub seat_map_view: SeatMapView,
+    pub login_per_minute: u32,
+    pub registration_per_minute: u32,
+    pub booking_per_minute: u32,
+}
+
+impl Default for Tunables {
+    fn default() -> Self {
+        AppConfig::default().tunables()
+    }
+}
+
+impl Tunables {
+    // Names of the settings that differ from the other tunables
+    pub fn changes(&self, other: &Tunables) -> Vec<String> {
+        let mut changes = Vec::new();
+        let mut compare = |name: &str, changed: bool| {
+            if changed {
+                changes.push(name.to_string());
+            }
+        };
+        compare(
+            "limits.booking_concurrency_per_user",
+            self.booking_concurrency_per_user != other.booking_concurrency_per_user,
+        );
+        compare(
+            "limits.booking_concurrency_per_ip",
+            self.booking_concurrency_per_ip != other.booking_concurrency_per_ip,
+        );
+        compare(
+            "partner.availability_cache_ttl_seconds",
+            self.availability_cache_ttl != other.availability_cache_ttl,
+        );
+        compare(
+            "partner.change_settle_millis",
+            self.change_settle_time != other.change_settle_time,
+        );
+        compare(
+            "cache.search_ttl_seconds",
+            self.search_cache_ttl != other.search_cache_ttl,
+        );
+        compare(
+            "cache.seat_map_ttl_seconds",
+            self.seat_map_cache_ttl != other.seat_map_cache_ttl,
+        );
+        compare("seat_map.view", self.seat_map_view != other.seat_map_view);
+        compare(
+            "limits.login_per_minute",
+            self.login_per_minute != other.login_per_minute,
+        );
+        compare(
+            "limits.registration_per_minute",
+            self.registration_per_minute != other.registration_per_minute,
+        );
+        compare(
+            "limits.booking_per_minute",
+            self.booking_per_minute != other.booking_per_minute,
+        );
+        changes
+    }
+}
+
+// Tunables shared by the services. A reload swaps in a whole new set, so a reader
+// never sees half of an update and never waits on a lock.
+#[derive(Clone)]
+pub struct SharedTunables(Arc<ArcSwap<Tunables>>);
+
+impl SharedTunables {
+    pub fn new(tunables: Tunables) -> Self {
+        SharedTunables(Arc::new(ArcSwap::from_pointee(tunables)))
+    }
+
+    pub fn current(&self) -> Arc<Tunables> {
+        self.0.load_full()
+    }
+
+    pub fn replace(&self, tunables: Tunables) {
+        self.0.store(Arc::new(tunables));
+    }
+}
+
+impl Default for SharedTunables {
+    fn default() -> Self {
+        SharedTunables::new(Tunables::default())
+    }
+}
+
+// Reads the configuration again and applies its tunables. Other settings keep the
+// value the server started with, changes to them are reported as needing a restart.
+#[derive(Clone)]
+pub struct ConfigReloader {
+    startup: Arc<AppConfig>,
+    tunables: SharedTunables,
+}
+
+impl ConfigReloader {
+    pub fn new(startup: AppConfig, tunables: SharedTunables) -> Self {
+        ConfigReloader {
+            startup: Arc::new(startup),
+            tunables,
+        }
+    }
+
+    // Reload from the configuration file and the environment. An invalid configuration
+    // is rejected as a whole and the current settings stay.
+    pub fn reload(&self) -> AppResult<ConfigReloadResponse> {
+        Ok(self.apply(AppConfig::load()?))
+    }
+
+    pub fn apply(&self, config: AppConfig) -> ConfigReloadResponse {
+        let tunables = config.tunables();
+        let changed = self.tunables.current().changes(&tunables);
+        self.tunables.replace(tunables);
+
+        let startup = &self.startup;
+        let restart_required = [
+            ("database", startup.database != config.database),
+            ("auth", startup.auth != config.auth),
+            ("cors", startup.cors != config.cors),
+            ("notification", startup.notification != config.notification),
+            ("residency", startup.residency != config.residency),
+            ("storage", startup.storage != config.storage),
+            ("slo", startup.slo != config.slo),
+            ("booking", startup.booking != config.booking),
+            (
+                "limits.json_bytes",
+                startup.limits.json_bytes != config.limits.json_bytes,
+            ),
+            (
+                "limits.rate_limit_redis_url",
+                startup.limits.rate_limit_redis_url != config.limits.rate_limit_redis_url,
+            ),
+            (
+                "limits.seat_lock_redis_url",
+                startup.limits.seat_lock_redis_url != config.limits.seat_lock_redis_url,
+            ),
+            (
+                "limits.statements_per_request",
+                startup.limits.statements_per_request != config.limits.statements_per_request,
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(name, _)| name.to_string())
+        .collect();
+
+        ConfigReloadResponse {
+            changed,
+            restart_required,
+        }
+    }
+
+    // Reload whenever the configuration file changes, checking every period
+    pub fn spawn_watcher(&self, period: Duration) {
+        let reloader = self.clone();
+        tokio::spawn(async move {
+            let mut last_modified = modified_at();
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                let modified = modified_at();
+                if modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+
+                match reloader.reload() {
+                    Ok(response) => tracing::info!(
+                        changed = ?response.changed,
+                        restart_required = ?response.restart_required,
+                        "configuration reloaded"
+                    ),
+                    Err(e) => tracing::error!(
+                        error = %e,
+                        "configuration file changed but is invalid, keeping the current settings"
+                    ),
+                }
+            }
+        });
+    }
+}
+
+fn modified_at() -> Option<SystemTime> {
+    let path = AppConfig::path()?;
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
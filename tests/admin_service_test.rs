@@ -0,0 +1,1515 @@
+use airline_booking_system::{
+    models::{
+        aircraft::{SeatReassignmentStatus, SwapAircraftRequest},
+        flight::{
+            BumpRequest, FlightStatus, UpdateFlightStatusRequest, UpdateOverbookingRequest,
+        },
+        ssr::SsrCode,
+        ticket::{
+            BookingRequestStatus, CorrectionReason, FlightBookingRequest, RebookingStatus,
+            SeatBookingRequest, SeatHoldRequest, SelfRebookingRequest, TicketBookingRequest,
+            TicketCorrectionRequest,
+        },
+        user::{DuplicateUserGroup, MergeUsersRequest, Role, UserRegistrationRequest},
+    },
+    services::{
+        admin_service::AdminService,
+        booking_queue::BookingRequests,
+        schedule_service::ScheduleService,
+        ticket_service::{TicketService, SIGNIFICANT_DELAY_MINUTES},
+        user_service::UserService,
+    },
+    utils::{
+        error::AppError, ndjson::collect_rows, public_id::new_public_id, region::RegionFilter,
+    },
+};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use ctor::dtor;
+use rust_decimal::Decimal;
+use sqlx::mysql::MySqlPool as Pool;
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct AdminServiceContext {
+    pool: Pool,
+    admin_service: AdminService,
+    ticket_service: TicketService,
+    user_service: UserService,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for AdminServiceContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+
+        let admin_service = AdminService::new(pool.clone());
+        let ticket_service = TicketService::new(pool.clone());
+        let user_service = UserService::new(pool.clone());
+
+        AdminServiceContext {
+            pool,
+            admin_service,
+            ticket_service,
+            user_service,
+        }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+impl AdminServiceContext {
+    async fn register(&self, username: &str, role: Role) -> Result<i32, AppError> {
+        self.user_service
+            .register_user(UserRegistrationRequest {
+                username: username.to_string(),
+                password: "test_password".to_string(),
+                role,
+                name: "Admin Test User".to_string(),
+                birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+                gender: "male".to_string(),
+                email: None,
+            })
+            .await
+    }
+}
+
+#[test_context(AdminServiceContext)]
+#[tokio::test]
+async fn test_bump_overbooked_passengers(ctx: &AdminServiceContext) -> Result<(), AppError> {
+    let flight_number = 901;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 27).unwrap();
+    let next_flight_date = NaiveDate::from_ymd_opt(2024, 12, 28).unwrap();
+
+    // Two seats, with 50% overbooking a third ticket can be sold
+    sqlx::query!(
+        r#"INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 2)"#,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, overbooking, start_date, end_date)
+        VALUES
+        (?, 'New York', 'London', '10:00:00', '22:00:00',
+            ?, 0.50, ?, ?)
+        "#,
+        flight_number,
+        flight_number,
+        flight_date,
+        next_flight_date
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    for date in [flight_date, next_flight_date] {
+        sqlx::query!(
+            r#"
+            INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+            VALUES (?, ?, 3, 1)
+            "#,
+            flight_number,
+            date
+        )
+        .execute(&ctx.pool)
+        .await?;
+    }
+
+    let admin_id = ctx.register("bump_test_admin", Role::Admin).await?;
+
+    let mut overbooked = Vec::new();
+    for username in ["bump_test_user1", "bump_test_user2", "bump_test_user3"] {
+        let user_id = ctx.register(username, Role::User).await?;
+        let response = ctx
+            .ticket_service
+            .book_ticket(
+                user_id,
+                TicketBookingRequest {
+                    flights: vec![FlightBookingRequest {
+                        flight_number,
+                        flight_date,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            )
+            .await?;
+        overbooked.push(response.flight_bookings[0].overbooked);
+    }
+    assert_eq!(overbooked, vec![false, false, true]);
+
+    let response = ctx
+        .admin_service
+        .bump_overbooked_passengers(admin_id, flight_number, flight_date, BumpRequest::default())
+        .await?;
+
+    assert_eq!(response.tickets_sold, 3);
+    assert_eq!(response.bumped.len(), 1);
+    assert_eq!(response.bumped[0].rebooked_flight_date, Some(next_flight_date));
+
+    let remaining = sqlx::query!(
+        r#"
+        SELECT COUNT(*) as count
+        FROM ticket
+        WHERE flight_number = ? AND flight_date = ?
+        "#,
+        flight_number,
+        flight_date
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(remaining.count, 2);
+
+    // Nothing left to bump
+    let response = ctx
+        .admin_service
+        .bump_overbooked_passengers(admin_id, flight_number, flight_date, BumpRequest::default())
+        .await?;
+    assert!(response.bumped.is_empty());
+
+    Ok(())
+}
+
+#[test_context(AdminServiceContext)]
+#[tokio::test]
+async fn test_export_route_audit(ctx: &AdminServiceContext) -> Result<(), AppError> {
+    let flight_number = 902;
+
+    sqlx::query!(
+        r#"INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 10)"#,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, overbooking, start_date, end_date)
+        VALUES
+        (?, 'Paris', 'Rome', '08:00:00', '10:00:00',
+            ?, 0.00, '2024-12-01', '2024-12-31')
+        "#,
+        flight_number,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    let admin_id = ctx.register("audit_test_admin", Role::Admin).await?;
+    for overbooking in [Decimal::new(5, 2), Decimal::new(10, 2)] {
+        ctx.admin_service
+            .update_route_overbooking(
+                admin_id,
+                flight_number,
+                UpdateOverbookingRequest {
+                    overbooking,
+                    apply_to_open_flights: false,
+                },
+            )
+            .await?;
+    }
+
+    let entries = collect_rows(|sink| {
+        ctx.admin_service
+            .export_route_audit(Some(flight_number), sink)
+    })
+    .await?;
+
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().all(|entry| entry.field == "overbooking"));
+    assert_eq!(entries[1].new_value.as_deref(), Some("0.10"));
+
+    Ok(())
+}
+
+#[test_context(AdminServiceContext)]
+#[tokio::test]
+async fn test_cancel_flight_rebooks_passengers(
+    ctx: &AdminServiceContext,
+) -> Result<(), AppError> {
+    let flight_number = 903;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 27).unwrap();
+
+    sqlx::query!(
+        r#"INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 10)"#,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, overbooking, start_date, end_date)
+        VALUES
+        (?, 'Toronto', 'Vancouver', '09:00:00', '14:00:00',
+            ?, 0.00, ?, ?)
+        "#,
+        flight_number,
+        flight_number,
+        flight_date,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    let flight_id = sqlx::query!(
+        r#"
+        INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+        VALUES (?, ?, 10, 1)
+        "#,
+        flight_number,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?
+    .last_insert_id() as i32;
+
+    let user_id = ctx.register("status_test_user", Role::User).await?;
+    let booking = TicketBookingRequest {
+        flights: vec![FlightBookingRequest {
+            flight_number,
+            flight_date,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    ctx.ticket_service.book_ticket(user_id, booking).await?;
+
+    // A delay needs a number of minutes
+    let result = ctx
+        .admin_service
+        .update_flight_status(
+            flight_id,
+            UpdateFlightStatusRequest {
+                status: FlightStatus::Delayed,
+                delay_minutes: None,
+                reason: None,
+                gate: None,
+            },
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+    let response = ctx
+        .admin_service
+        .update_flight_status(
+            flight_id,
+            UpdateFlightStatusRequest {
+                status: FlightStatus::Delayed,
+                delay_minutes: Some(45),
+                reason: Some("Weather".to_string()),
+                gate: Some(" b12".to_string()),
+            },
+        )
+        .await?;
+    assert_eq!(response.previous_status, FlightStatus::Scheduled);
+    assert_eq!(response.delay_minutes, 45);
+    assert_eq!(response.gate.as_deref(), Some("B12"));
+
+    let response = ctx
+        .admin_service
+        .update_flight_status(
+            flight_id,
+            UpdateFlightStatusRequest {
+                status: FlightStatus::Cancelled,
+                delay_minutes: None,
+                reason: None,
+                gate: None,
+            },
+        )
+        .await?;
+    assert_eq!(response.tickets_to_rebook, 1);
+    assert_eq!(response.delay_reason.as_deref(), Some("Weather"));
+    assert_eq!(response.gate.as_deref(), Some("B12"));
+
+    let history = ctx.ticket_service.get_history(user_id).await?;
+    assert_eq!(history.flights[0].flight_status, FlightStatus::Cancelled);
+    assert!(history.flights[0].needs_rebooking);
+
+    // The flight can no longer be booked or change status
+    let other_user_id = ctx.register("status_test_user2", Role::User).await?;
+    let booking = TicketBookingRequest {
+        flights: vec![FlightBookingRequest {
+            flight_number,
+            flight_date,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    assert!(ctx
+        .ticket_service
+        .book_ticket(other_user_id, booking)
+        .await
+        .is_err());
+
+    let result = ctx
+        .admin_service
+        .update_flight_status(
+            flight_id,
+            UpdateFlightStatusRequest {
+                status: FlightStatus::Scheduled,
+                delay_minutes: None,
+                reason: None,
+                gate: None,
+            },
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+
+    // No later flight yet, the passenger stays marked for rebooking
+    let summary = ctx.ticket_service.rebook_cancelled_flight(flight_id).await?;
+    assert_eq!((summary.rebooked, summary.failed), (0, 1));
+
+    // Nor onto a flight already closed for boarding
+    let next_flight_date = flight_date + chrono::Duration::days(1);
+    let next_flight_id = sqlx::query!(
+        r#"
+        INSERT INTO flight (flight_number, flight_date, available_tickets, version, closed_at)
+        VALUES (?, ?, 10, 1, UTC_TIMESTAMP())
+        "#,
+        flight_number,
+        next_flight_date
+    )
+    .execute(&ctx.pool)
+    .await?
+    .last_insert_id() as i32;
+    let summary = ctx
+        .ticket_service
+        .rebook_cancelled_flight(flight_id)
+        .await?;
+    assert_eq!((summary.rebooked, summary.failed), (0, 1));
+
+    sqlx::query!(
+        "UPDATE flight SET closed_at = NULL WHERE flight_id = ?",
+        next_flight_id
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    // What the passenger paid for a seat is credited to the new ticket
+    let ticket_id = sqlx::query_scalar!("SELECT id FROM ticket WHERE customer_id = ?", user_id)
+        .fetch_one(&ctx.pool)
+        .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO seat_charge
+        (ticket_id, customer_id, flight_id, seat_number, amount, currency, status, created_at)
+        VALUES (?, ?, ?, 1, 25.00, 'USD', 'CAPTURED', UTC_TIMESTAMP())
+        "#,
+        ticket_id,
+        user_id,
+        flight_id
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    let summary = ctx.ticket_service.rebook_cancelled_flight(flight_id).await?;
+    assert_eq!((summary.rebooked, summary.failed), (1, 0));
+    let rebooked_ticket_id = summary.passengers[0].rebooked_ticket_id;
+    let charged_ticket_id = sqlx::query_scalar!(
+        "SELECT ticket_id FROM seat_charge WHERE customer_id = ?",
+        user_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(charged_ticket_id, rebooked_ticket_id);
+    let rebooked_events = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*)
+        FROM outbox_event
+        WHERE event_type = 'TicketRebooked'
+        AND JSON_EXTRACT(payload, '$.TicketRebooked.customer_id') = ?
+        "#,
+        user_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(rebooked_events, 1);
+    assert_eq!(summary.passengers[0].status, RebookingStatus::Rebooked);
+    assert_eq!(
+        summary.passengers[0].rebooked_flight_date,
+        Some(next_flight_date)
+    );
+
+    let history = ctx.ticket_service.get_history(user_id).await?;
+    assert_eq!(history.flights.len(), 1);
+    assert_eq!(history.flights[0].flight_date, next_flight_date);
+    assert!(!history.flights[0].needs_rebooking);
+
+    let outcomes = sqlx::query!(
+        r#"SELECT status FROM rebooking WHERE flight_id = ? ORDER BY id"#,
+        flight_id
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+    let outcomes: Vec<String> = outcomes.into_iter().map(|row| row.status).collect();
+    assert_eq!(outcomes, vec!["FAILED", "FAILED", "REBOOKED"]);
+
+    Ok(())
+}
+
+#[test_context(AdminServiceContext)]
+#[tokio::test]
+async fn test_swap_aircraft_reassigns_seats(ctx: &AdminServiceContext) -> Result<(), AppError> {
+    let flight_number = 904;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 27).unwrap();
+
+    // Two rows of six, then two rows of four, then a single row of two
+    for (aircraft_id, capacity, seats_per_row) in [(904, 12, 6), (905, 8, 4), (906, 2, 2)] {
+        sqlx::query!(
+            r#"INSERT INTO aircraft (aircraft_id, capacity, seats_per_row) VALUES (?, ?, ?)"#,
+            aircraft_id,
+            capacity,
+            seats_per_row
+        )
+        .execute(&ctx.pool)
+        .await?;
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, overbooking, start_date, end_date)
+        VALUES
+        (?, 'Montreal', 'Calgary', '07:00:00', '12:00:00',
+            904, 0.00, ?, ?)
+        "#,
+        flight_number,
+        flight_date,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    ScheduleService::new(ctx.pool.clone())
+        .generate_flights_for_route(flight_number, flight_date, flight_date)
+        .await?;
+    let flight_id = sqlx::query!(
+        "SELECT flight_id FROM flight WHERE flight_number = ?",
+        flight_number
+    )
+    .fetch_one(&ctx.pool)
+    .await?
+    .flight_id;
+
+    let admin_id = ctx.register("swap_test_admin", Role::Admin).await?;
+    for (username, seat) in [
+        ("swap_test_user1", 1),
+        ("swap_test_user2", 8),
+        ("swap_test_user3", 12),
+    ] {
+        let user_id = ctx.register(username, Role::User).await?;
+        ctx.ticket_service
+            .book_ticket(
+                user_id,
+                TicketBookingRequest {
+                    flights: vec![FlightBookingRequest {
+                        flight_number,
+                        flight_date,
+                        preferred_seat: Some(seat),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            )
+            .await?;
+    }
+
+    // Seats 1 and 8 exist on the smaller aircraft, the window seat 12 becomes the
+    // window seat 5 in the same row
+    let response = ctx
+        .admin_service
+        .swap_aircraft(admin_id, flight_id, SwapAircraftRequest { aircraft_id: 905 })
+        .await?;
+    assert_eq!(response.previous_aircraft_id, 904);
+    assert_eq!(response.available_tickets, 5);
+    let seats: Vec<_> = response
+        .reassignments
+        .iter()
+        .map(|r| (r.old_seat_number, r.new_seat_number, r.status))
+        .collect();
+    assert_eq!(
+        seats,
+        vec![
+            (1, Some(1), SeatReassignmentStatus::Same),
+            (8, Some(8), SeatReassignmentStatus::Same),
+            (12, Some(5), SeatReassignmentStatus::Moved),
+        ]
+    );
+
+    let result = ctx
+        .admin_service
+        .swap_aircraft(admin_id, flight_id, SwapAircraftRequest { aircraft_id: 905 })
+        .await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+    // Only two seats left, the last passenger has to be seated by an agent
+    let response = ctx
+        .admin_service
+        .swap_aircraft(admin_id, flight_id, SwapAircraftRequest { aircraft_id: 906 })
+        .await?;
+    assert_eq!(response.unmapped, 1);
+    assert_eq!(response.available_tickets, 0);
+    assert_eq!(response.reassignments[1].new_seat_number, Some(2));
+    assert_eq!(response.reassignments[2].status, SeatReassignmentStatus::Unmapped);
+
+    let seats = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM seat_info WHERE flight_id = ?) as "seats!: i64",
+            (SELECT COUNT(*) FROM ticket WHERE flight_id = ? AND seat_number IS NULL)
+                as "unseated!: i64"
+        "#,
+        flight_id,
+        flight_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!((seats.seats, seats.unseated), (2, 1));
+
+    Ok(())
+}
+
+#[test_context(AdminServiceContext)]
+#[tokio::test]
+async fn test_closed_flight_is_frozen(ctx: &AdminServiceContext) -> Result<(), AppError> {
+    let flight_number = 907;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 27).unwrap();
+
+    sqlx::query!(
+        r#"INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 6)"#,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, overbooking, start_date, end_date)
+        VALUES
+        (?, 'Ottawa', 'Halifax', '06:00:00', '09:00:00',
+            ?, 0.00, ?, ?)
+        "#,
+        flight_number,
+        flight_number,
+        flight_date,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    ScheduleService::new(ctx.pool.clone())
+        .generate_flights_for_route(flight_number, flight_date, flight_date)
+        .await?;
+    let flight_id = sqlx::query!(
+        "SELECT flight_id FROM flight WHERE flight_number = ?",
+        flight_number
+    )
+    .fetch_one(&ctx.pool)
+    .await?
+    .flight_id;
+
+    let admin_id = ctx.register("close_test_admin", Role::Admin).await?;
+    let mut passengers = Vec::new();
+    for (username, seat) in [("close_test_user1", 1), ("close_test_user2", 2)] {
+        let user_id = ctx.register(username, Role::User).await?;
+        let response = ctx
+            .ticket_service
+            .book_ticket(
+                user_id,
+                TicketBookingRequest {
+                    flights: vec![FlightBookingRequest {
+                        flight_number,
+                        flight_date,
+                        preferred_seat: Some(seat),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            )
+            .await?;
+        passengers.push((user_id, response.flight_bookings[0].ticket_id));
+    }
+    let (user_id, ticket_id) = passengers[0];
+
+    let response = ctx.admin_service.close_flight(flight_id).await?;
+    assert_eq!(response.tickets, 2);
+    let result = ctx.admin_service.close_flight(flight_id).await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+
+    // Late calls cannot change the flown flight
+    let result = ctx
+        .ticket_service
+        .book_seat_for_ticket(
+            user_id,
+            SeatBookingRequest {
+                flight_number,
+                flight_date,
+                seat_number: 3,
+            },
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+
+    let result = ctx
+        .ticket_service
+        .hold_seat(
+            user_id,
+            SeatHoldRequest {
+                flight_number,
+                flight_date,
+                seat_number: 3,
+                minutes: None,
+            },
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+
+    let result = ctx.ticket_service.release_ticket(ticket_id).await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+
+    let late_user_id = ctx.register("close_test_user3", Role::User).await?;
+    let booking = TicketBookingRequest {
+        flights: vec![FlightBookingRequest {
+            flight_number,
+            flight_date,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    assert!(ctx
+        .ticket_service
+        .book_ticket(late_user_id, booking)
+        .await
+        .is_err());
+
+    // Admin corrections still go through, but not onto another passenger's seat
+    let result = ctx
+        .ticket_service
+        .correct_ticket(
+            admin_id,
+            ticket_id,
+            TicketCorrectionRequest {
+                reason: CorrectionReason::SeatChangedOnboard,
+                seat_number: Some(2),
+                note: None,
+            },
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+
+    let response = ctx
+        .ticket_service
+        .correct_ticket(
+            admin_id,
+            ticket_id,
+            TicketCorrectionRequest {
+                reason: CorrectionReason::SeatChangedOnboard,
+                seat_number: Some(3),
+                note: Some("Moved away from a broken seat".to_string()),
+            },
+        )
+        .await?;
+    assert_eq!(response.old_seat_number, Some(1));
+    assert_eq!(response.new_seat_number, Some(3));
+
+    let corrections = sqlx::query!(
+        r#"SELECT reason FROM ticket_correction WHERE ticket_id = ?"#,
+        ticket_id
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+    assert_eq!(corrections.len(), 1);
+    assert_eq!(corrections[0].reason, "SEAT_CHANGED_ONBOARD");
+
+    Ok(())
+}
+
+#[test_context(AdminServiceContext)]
+#[tokio::test]
+async fn test_duplicate_users_filtered_by_region(
+    ctx: &AdminServiceContext,
+) -> Result<(), AppError> {
+    let eu_users = UserService::new(ctx.pool.clone()).with_data_region("eu".to_string());
+    for username in ["region_twin_a", "region_twin_b"] {
+        eu_users
+            .register_user(UserRegistrationRequest {
+                username: username.to_string(),
+                password: "test_password".to_string(),
+                role: Role::User,
+                name: "Region Twin".to_string(),
+                birth_date: NaiveDate::from_ymd_opt(1985, 5, 5).unwrap(),
+                gender: "female".to_string(),
+                email: None,
+            })
+            .await?;
+    }
+    let has_twins = |groups: &[DuplicateUserGroup]| {
+        groups.iter().any(|group| {
+            group
+                .users
+                .iter()
+                .all(|user| user.name == "Region Twin" && user.data_region == "eu")
+        })
+    };
+
+    let all = ctx
+        .admin_service
+        .find_duplicate_users(&RegionFilter::default())
+        .await?;
+    assert!(has_twins(&all.groups));
+
+    let only_eu = ctx
+        .admin_service
+        .find_duplicate_users(&RegionFilter::parse(Some("eu"), None)?)
+        .await?;
+    assert!(has_twins(&only_eu.groups));
+    assert!(only_eu
+        .groups
+        .iter()
+        .flat_map(|group| &group.users)
+        .all(|user| user.data_region == "eu"));
+
+    let without_eu = ctx
+        .admin_service
+        .find_duplicate_users(&RegionFilter::parse(None, Some("eu,uk"))?)
+        .await?;
+    assert!(!has_twins(&without_eu.groups));
+
+    Ok(())
+}
+
+#[test_context(AdminServiceContext)]
+#[tokio::test]
+async fn test_flight_manifest(ctx: &AdminServiceContext) -> Result<(), AppError> {
+    let flight_number = 908;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 29).unwrap();
+
+    sqlx::query!(
+        r#"INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 3)"#,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, overbooking, start_date, end_date)
+        VALUES
+        (?, 'Toronto', 'Calgary', '08:00:00', '10:30:00', ?, 0.00, ?, ?)
+        "#,
+        flight_number,
+        flight_number,
+        flight_date,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?;
+    let flight_id = sqlx::query!(
+        r#"
+        INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+        VALUES (?, ?, 3, 1)
+        "#,
+        flight_number,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?
+    .last_insert_id() as i32;
+    for seat_number in 1..=3 {
+        sqlx::query!(
+            "INSERT INTO seat_info (flight_id, seat_number) VALUES (?, ?)",
+            flight_id,
+            seat_number
+        )
+        .execute(&ctx.pool)
+        .await?;
+    }
+
+    let seated = ctx.register("manifest_seated_user", Role::User).await?;
+    let unseated = ctx.register("manifest_unseated_user", Role::User).await?;
+    for (user_id, preferred_seat, ssr_codes) in [
+        (unseated, None, vec![]),
+        (seated, Some(2), vec![SsrCode::WheelchairRamp]),
+    ] {
+        ctx.ticket_service
+            .book_ticket(
+                user_id,
+                TicketBookingRequest {
+                    flights: vec![FlightBookingRequest {
+                        flight_number,
+                        flight_date,
+                        preferred_seat,
+                        ssr_codes,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            )
+            .await?;
+    }
+    sqlx::query!(
+        r#"
+        UPDATE ticket
+        SET checked_in_at = UTC_TIMESTAMP(), boarding_sequence = 1
+        WHERE customer_id = ? AND flight_id = ?
+        "#,
+        seated,
+        flight_id
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    let manifest = ctx.admin_service.flight_manifest(flight_id).await?;
+    assert_eq!(manifest.flight_number, flight_number);
+    assert_eq!(manifest.checked_in, 1);
+    assert_eq!(manifest.passengers.len(), 2);
+
+    // Seated passengers come first
+    let first = &manifest.passengers[0];
+    assert_eq!(first.customer_id, seated);
+    assert_eq!(first.seat_number, Some(2));
+    assert!(first.checked_in);
+    assert_eq!(first.boarding_sequence, Some(1));
+    assert_eq!(first.ssr_codes, vec![SsrCode::WheelchairRamp]);
+    let second = &manifest.passengers[1];
+    assert_eq!(second.customer_id, unseated);
+    assert_eq!(second.seat_number, None);
+    assert!(!second.checked_in);
+    assert!(second.ssr_codes.is_empty());
+
+    let result = ctx.admin_service.flight_manifest(-1).await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    // The passengers stream in the same order, for the NDJSON manifest
+    let streamed = collect_rows(|sink| {
+        ctx.admin_service
+            .export_manifest_passengers(flight_id, sink)
+    })
+    .await?;
+    let customers: Vec<_> = streamed
+        .iter()
+        .map(|passenger| passenger.customer_id)
+        .collect();
+    assert_eq!(customers, vec![seated, unseated]);
+    let result = ctx.admin_service.ensure_flight_exists(-1).await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    Ok(())
+}
+
+#[test_context(AdminServiceContext)]
+#[tokio::test]
+async fn test_self_rebooking_of_delayed_flight(ctx: &AdminServiceContext) -> Result<(), AppError> {
+    let flight_number = 1961;
+    let flight_date = (chrono::Utc::now() + chrono::Duration::days(10)).date_naive();
+
+    sqlx::query!(
+        r#"INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 3)"#,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, overbooking, start_date, end_date)
+        VALUES
+        (?, 'Winnipeg', 'Thunder Bay', '11:00:00', '13:30:00',
+            ?, 0.00, ?, ?)
+        "#,
+        flight_number,
+        flight_number,
+        flight_date,
+        flight_date + chrono::Duration::days(5)
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    // The flight, one the next day, one closed for boarding and one past the rebooking
+    // window
+    let mut flight_ids = Vec::new();
+    for days in [0, 1, 2, 5] {
+        let flight_id = sqlx::query!(
+            r#"
+            INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+            VALUES (?, ?, 3, 1)
+            "#,
+            flight_number,
+            flight_date + chrono::Duration::days(days)
+        )
+        .execute(&ctx.pool)
+        .await?
+        .last_insert_id() as i32;
+        flight_ids.push(flight_id);
+    }
+    sqlx::query!(
+        "UPDATE flight SET closed_at = UTC_TIMESTAMP() WHERE flight_id = ?",
+        flight_ids[2]
+    )
+    .execute(&ctx.pool)
+    .await?;
+    let flight_id = flight_ids[0];
+
+    let user_id = ctx.register("self_rebooking_user", Role::User).await?;
+    let booking = TicketBookingRequest {
+        flights: vec![FlightBookingRequest {
+            flight_number,
+            flight_date,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    ctx.ticket_service.book_ticket(user_id, booking).await?;
+    let ticket_id = sqlx::query_scalar!(
+        "SELECT id FROM ticket WHERE customer_id = ? AND flight_id = ?",
+        user_id,
+        flight_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+
+    // A flight on time cannot be rebooked
+    let result = ctx
+        .ticket_service
+        .get_rebooking_options(user_id, ticket_id)
+        .await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+
+    ctx.admin_service
+        .update_flight_status(
+            flight_id,
+            UpdateFlightStatusRequest {
+                status: FlightStatus::Delayed,
+                delay_minutes: Some(SIGNIFICANT_DELAY_MINUTES),
+                reason: Some("Crew".to_string()),
+                gate: None,
+            },
+        )
+        .await?;
+
+    // Other customers do not see the ticket
+    let other_user_id = ctx.register("self_rebooking_other", Role::User).await?;
+    let result = ctx
+        .ticket_service
+        .get_rebooking_options(other_user_id, ticket_id)
+        .await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    let options = ctx
+        .ticket_service
+        .get_rebooking_options(user_id, ticket_id)
+        .await?;
+    assert_eq!(options.flight_status, FlightStatus::Delayed);
+    let option_ids: Vec<i32> = options
+        .options
+        .iter()
+        .map(|option| option.flight_id)
+        .collect();
+    assert_eq!(option_ids, vec![flight_ids[1]]);
+
+    let result = ctx
+        .ticket_service
+        .self_rebook(
+            user_id,
+            ticket_id,
+            SelfRebookingRequest {
+                flight_id: flight_ids[3],
+            },
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+
+    let rebooked = ctx
+        .ticket_service
+        .self_rebook(
+            user_id,
+            ticket_id,
+            SelfRebookingRequest {
+                flight_id: flight_ids[1],
+            },
+        )
+        .await?;
+    assert_eq!(rebooked.status, RebookingStatus::Rebooked);
+    assert_eq!(
+        rebooked.rebooked_flight_date,
+        Some(flight_date + chrono::Duration::days(1))
+    );
+
+    // The delayed flight gets its ticket back
+    let available = sqlx::query_scalar!(
+        "SELECT available_tickets FROM flight WHERE flight_number = ? ORDER BY flight_date",
+        flight_number
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+    assert_eq!(available, vec![3, 2, 3, 3]);
+
+    let history = ctx.ticket_service.get_history(user_id).await?;
+    assert_eq!(history.flights.len(), 1);
+    assert_eq!(
+        history.flights[0].flight_date,
+        flight_date + chrono::Duration::days(1)
+    );
+
+    let rebooked_events = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*)
+        FROM outbox_event
+        WHERE event_type = 'TicketRebooked'
+        AND JSON_EXTRACT(payload, '$.TicketRebooked.rebooked_ticket_id') = ?
+        "#,
+        rebooked.rebooked_ticket_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(rebooked_events, 1);
+
+    Ok(())
+}
+
+#[test_context(AdminServiceContext)]
+#[tokio::test]
+async fn test_merge_users_keeps_booking_in_progress(
+    ctx: &AdminServiceContext,
+) -> Result<(), AppError> {
+    let flight_number = 909;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 27).unwrap();
+
+    sqlx::query!(
+        r#"INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 3)"#,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, overbooking, start_date, end_date)
+        VALUES
+        (?, 'New York', 'London', '10:00:00', '22:00:00',
+            ?, 0.00, ?, ?)
+        "#,
+        flight_number,
+        flight_number,
+        flight_date,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?;
+    let flight_id = sqlx::query!(
+        r#"
+        INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+        VALUES (?, ?, 3, 1)
+        "#,
+        flight_number,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?
+    .last_insert_id() as i32;
+
+    let surviving_user_id = ctx.register("merge_saga_user", Role::User).await?;
+    let duplicate_user_id = ctx.register("merge_saga_duplicate", Role::User).await?;
+
+    // The duplicate account was interrupted after claiming a ticket
+    let saga_id = sqlx::query!(
+        r#"
+        INSERT INTO booking_saga (customer_id, created_at)
+        VALUES (?, UTC_TIMESTAMP() - INTERVAL 1 HOUR)
+        "#,
+        duplicate_user_id
+    )
+    .execute(&ctx.pool)
+    .await?
+    .last_insert_id() as i32;
+    sqlx::query!(
+        "INSERT INTO booking_saga_claim (saga_id, flight_id) VALUES (?, ?)",
+        saga_id,
+        flight_id
+    )
+    .execute(&ctx.pool)
+    .await?;
+    sqlx::query!(
+        "UPDATE flight SET available_tickets = available_tickets - 1 WHERE flight_id = ?",
+        flight_id
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    let response = ctx
+        .admin_service
+        .merge_users(MergeUsersRequest {
+            surviving_user_id,
+            duplicate_user_id,
+            dry_run: false,
+        })
+        .await?;
+    assert!(response.merged);
+    let saga_owner =
+        sqlx::query_scalar!("SELECT customer_id FROM booking_saga WHERE id = ?", saga_id)
+            .fetch_one(&ctx.pool)
+            .await?;
+    assert_eq!(saga_owner, surviving_user_id);
+
+    // The recovery task still gives the claimed ticket back
+    assert!(ctx.ticket_service.revert_interrupted_bookings().await? >= 1);
+    let available = sqlx::query_scalar!(
+        "SELECT available_tickets FROM flight WHERE flight_id = ?",
+        flight_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(available, 3);
+
+    Ok(())
+}
+
+#[test_context(AdminServiceContext)]
+#[tokio::test]
+async fn test_merge_users_keeps_queued_bookings(ctx: &AdminServiceContext) -> Result<(), AppError> {
+    let surviving_user_id = ctx.register("merge_queue_user", Role::User).await?;
+    let duplicate_user_id = ctx.register("merge_queue_duplicate", Role::User).await?;
+    let request_id = new_public_id();
+    sqlx::query!(
+        r#"
+        INSERT INTO booking_request (request_id, customer_id, status, created_at)
+        VALUES (?, ?, 'PENDING', UTC_TIMESTAMP())
+        "#,
+        request_id,
+        duplicate_user_id
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    ctx.admin_service
+        .merge_users(MergeUsersRequest {
+            surviving_user_id,
+            duplicate_user_id,
+            dry_run: false,
+        })
+        .await?;
+
+    // The surviving account can still poll the booking
+    let status = BookingRequests::new(ctx.pool.clone())
+        .status(surviving_user_id, &request_id)
+        .await?;
+    assert_eq!(status.status, BookingRequestStatus::Pending);
+
+    Ok(())
+}
+
+#[test_context(AdminServiceContext)]
+#[tokio::test]
+async fn test_duplicate_users_by_username(ctx: &AdminServiceContext) -> Result<(), AppError> {
+    let mut user_ids = Vec::new();
+    for (username, name) in [
+        ("John.Doe", "John Doe"),
+        ("johndoe2", "Johnny Doe"),
+        ("20240101", "Numeric One"),
+        ("19991231", "Numeric Two"),
+    ] {
+        let user_id = ctx
+            .user_service
+            .register_user(UserRegistrationRequest {
+                username: username.to_string(),
+                password: "test_password".to_string(),
+                role: Role::User,
+                name: name.to_string(),
+                birth_date: NaiveDate::from_ymd_opt(1979, 3, 3).unwrap(),
+                gender: "male".to_string(),
+                email: None,
+            })
+            .await?;
+        user_ids.push(user_id);
+    }
+    let group_of = |groups: &[DuplicateUserGroup], user_id: i32| {
+        groups
+            .iter()
+            .find(|group| {
+                group.reason == "Similar usernames"
+                    && group.users.iter().any(|user| user.user_id == user_id)
+            })
+            .map(|group| {
+                let mut users: Vec<i32> = group.users.iter().map(|user| user.user_id).collect();
+                users.sort();
+                users
+            })
+    };
+
+    let duplicates = ctx
+        .admin_service
+        .find_duplicate_users(&RegionFilter::default())
+        .await?;
+    assert_eq!(
+        group_of(&duplicates.groups, user_ids[0]),
+        Some(vec![user_ids[0], user_ids[1]])
+    );
+    // Usernames of only digits share nothing but the digits dropped from them
+    assert_eq!(group_of(&duplicates.groups, user_ids[2]), None);
+    assert_eq!(group_of(&duplicates.groups, user_ids[3]), None);
+
+    Ok(())
+}
+
+#[test_context(AdminServiceContext)]
+#[tokio::test]
+async fn test_merge_users(ctx: &AdminServiceContext) -> Result<(), AppError> {
+    let flight_number = 910;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 27).unwrap();
+
+    sqlx::query!(
+        r#"INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 3)"#,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, overbooking, start_date, end_date)
+        VALUES
+        (?, 'New York', 'London', '10:00:00', '22:00:00',
+            ?, 0.00, ?, ?)
+        "#,
+        flight_number,
+        flight_number,
+        flight_date,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+        VALUES (?, ?, 3, 1)
+        "#,
+        flight_number,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    let surviving_user_id = ctx.register("merge_user", Role::User).await?;
+    let duplicate_user_id = ctx.register("merge_duplicate", Role::User).await?;
+    let response = ctx
+        .ticket_service
+        .book_ticket(
+            duplicate_user_id,
+            TicketBookingRequest {
+                flights: vec![FlightBookingRequest {
+                    flight_number,
+                    flight_date,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .await?;
+    let ticket_id = response.flight_bookings[0].ticket_id;
+    let request = |dry_run| MergeUsersRequest {
+        surviving_user_id,
+        duplicate_user_id,
+        dry_run,
+    };
+    let ticket_owner = || {
+        sqlx::query_scalar!("SELECT customer_id FROM ticket WHERE id = ?", ticket_id)
+            .fetch_one(&ctx.pool)
+    };
+
+    // A dry run reports the tickets that would move, and moves nothing
+    let response = ctx.admin_service.merge_users(request(true)).await?;
+    assert!(!response.merged);
+    assert_eq!(response.reassigned_tickets, vec![ticket_id]);
+    assert!(response.conflicting_tickets.is_empty());
+    assert_eq!(ticket_owner().await?, duplicate_user_id);
+
+    let response = ctx.admin_service.merge_users(request(false)).await?;
+    assert!(response.merged);
+    assert_eq!(response.reassigned_tickets, vec![ticket_id]);
+    assert_eq!(ticket_owner().await?, surviving_user_id);
+    let duplicate_left =
+        sqlx::query_scalar!("SELECT COUNT(*) FROM user WHERE id = ?", duplicate_user_id)
+            .fetch_one(&ctx.pool)
+            .await?;
+    assert_eq!(duplicate_left, 0);
+
+    // Nobody is left to merge
+    let result = ctx.admin_service.merge_users(request(false)).await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    Ok(())
+}
+
+#[test_context(AdminServiceContext)]
+#[tokio::test]
+async fn test_update_route_overbooking(ctx: &AdminServiceContext) -> Result<(), AppError> {
+    let flight_number = 911;
+    let past_date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+    let open_date = NaiveDate::from_ymd_opt(2099, 1, 1).unwrap();
+
+    sqlx::query!(
+        r#"INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 10)"#,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, overbooking, start_date, end_date)
+        VALUES
+        (?, 'Lisbon', 'Madrid', '07:00:00', '09:00:00',
+            ?, 0.00, ?, ?)
+        "#,
+        flight_number,
+        flight_number,
+        past_date,
+        open_date
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    for flight_date in [past_date, open_date] {
+        sqlx::query!(
+            r#"
+            INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+            VALUES (?, ?, 10, 1)
+            "#,
+            flight_number,
+            flight_date
+        )
+        .execute(&ctx.pool)
+        .await?;
+    }
+
+    let admin_id = ctx.register("overbooking_test_admin", Role::Admin).await?;
+
+    for overbooking in [Decimal::new(-1, 2), Decimal::new(51, 2)] {
+        let result = ctx
+            .admin_service
+            .update_route_overbooking(
+                admin_id,
+                flight_number,
+                UpdateOverbookingRequest {
+                    overbooking,
+                    apply_to_open_flights: true,
+                },
+            )
+            .await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+    }
+
+    let result = ctx
+        .admin_service
+        .update_route_overbooking(
+            admin_id,
+            9110,
+            UpdateOverbookingRequest {
+                overbooking: Decimal::new(10, 2),
+                apply_to_open_flights: true,
+            },
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    // 20% of 10 seats adds two tickets to the flight that has not departed
+    let response = ctx
+        .admin_service
+        .update_route_overbooking(
+            admin_id,
+            flight_number,
+            UpdateOverbookingRequest {
+                overbooking: Decimal::new(20, 2),
+                apply_to_open_flights: true,
+            },
+        )
+        .await?;
+    assert_eq!(response.previous_overbooking, Decimal::new(0, 2));
+    assert_eq!(response.overbooking, Decimal::new(20, 2));
+    assert_eq!(response.updated_flights, 1);
+
+    let available: Vec<i32> = sqlx::query_scalar!(
+        r#"
+        SELECT available_tickets
+        FROM flight
+        WHERE flight_number = ?
+        ORDER BY flight_date
+        "#,
+        flight_number
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+    assert_eq!(available, vec![10, 12]);
+
+    // Without applying it, only flights generated later use the new ratio
+    let response = ctx
+        .admin_service
+        .update_route_overbooking(
+            admin_id,
+            flight_number,
+            UpdateOverbookingRequest {
+                overbooking: Decimal::new(10, 2),
+                apply_to_open_flights: false,
+            },
+        )
+        .await?;
+    assert_eq!(response.updated_flights, 0);
+
+    let available: Vec<i32> = sqlx::query_scalar!(
+        r#"
+        SELECT available_tickets
+        FROM flight
+        WHERE flight_number = ?
+        ORDER BY flight_date
+        "#,
+        flight_number
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+    assert_eq!(available, vec![10, 12]);
+
+    let entries = collect_rows(|sink| {
+        ctx.admin_service
+            .export_route_audit(Some(flight_number), sink)
+    })
+    .await?;
+    assert_eq!(entries.len(), 2, "Rejected updates are not audited");
+    assert_eq!(entries[0].old_value.as_deref(), Some("0.00"));
+    assert_eq!(entries[0].new_value.as_deref(), Some("0.20"));
+
+    Ok(())
+}
@@ -0,0 +1,231 @@
+use airline_booking_system::{
+    models::{
+        aircraft::{AircraftLayoutRequest, CreateAircraftRequest},
+        ticket::{FlightBookingRequest, TicketBookingRequest},
+        user::{Role, UserRegistrationRequest},
+    },
+    services::{
+        aircraft_service::AircraftService, schedule_service::insert_seat_range,
+        ticket_service::TicketService, user_service::UserService,
+    },
+    utils::error::AppError,
+};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use ctor::dtor;
+use sqlx::mysql::MySqlPool as Pool;
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct AircraftContext {
+    pool: Pool,
+    aircraft_service: AircraftService,
+    ticket_service: TicketService,
+    user_service: UserService,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for AircraftContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+
+        AircraftContext {
+            aircraft_service: AircraftService::new(pool.clone()),
+            ticket_service: TicketService::new(pool.clone()),
+            user_service: UserService::new(pool.clone()),
+            pool,
+        }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+fn layout(capacity: i32) -> AircraftLayoutRequest {
+    AircraftLayoutRequest {
+        capacity,
+        seats_per_row: 2,
+        exit_rows: vec![],
+        accessible_rows: vec![],
+        extra_legroom_rows: vec![],
+    }
+}
+
+impl AircraftContext {
+    // Route flown by the aircraft with one flight on the date, with all its seats
+    async fn create_flight(
+        &self,
+        flight_number: i32,
+        flight_date: NaiveDate,
+        capacity: i32,
+    ) -> Result<i32, AppError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO flight_route
+            (flight_number, departure_city, destination_city, departure_time, arrival_time,
+                aircraft_id, overbooking, start_date, end_date)
+            VALUES
+            (?, 'Halifax', 'Moncton', '07:30:00', '08:20:00', ?, 0.00, ?, ?)
+            "#,
+            flight_number,
+            flight_number,
+            flight_date,
+            flight_date
+        )
+        .execute(&self.pool)
+        .await?;
+        let flight_id = sqlx::query!(
+            r#"
+            INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+            VALUES (?, ?, ?, 1)
+            "#,
+            flight_number,
+            flight_date,
+            capacity
+        )
+        .execute(&self.pool)
+        .await?
+        .last_insert_id() as i32;
+        let mut conn = self.pool.acquire().await?;
+        insert_seat_range(&mut conn, flight_id, 1, capacity).await?;
+        Ok(flight_id)
+    }
+
+    async fn available_tickets(&self, flight_id: i32) -> Result<(i32, i64), AppError> {
+        let flight = sqlx::query!(
+            r#"
+            SELECT
+                available_tickets,
+                (SELECT COUNT(*) FROM seat_info s WHERE s.flight_id = f.flight_id)
+                    as "seats!: i64"
+            FROM flight f
+            WHERE flight_id = ?
+            "#,
+            flight_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok((flight.available_tickets, flight.seats))
+    }
+}
+
+#[test_context(AircraftContext)]
+#[tokio::test]
+async fn test_create_aircraft(ctx: &AircraftContext) -> Result<(), AppError> {
+    let response = ctx
+        .aircraft_service
+        .create_aircraft(CreateAircraftRequest {
+            aircraft_id: 1951,
+            layout: AircraftLayoutRequest {
+                exit_rows: vec![3],
+                ..layout(8)
+            },
+        })
+        .await?;
+    assert_eq!(response.capacity, 8);
+    assert_eq!(response.exit_rows, vec![3]);
+    assert_eq!(response.updated_flights, 0);
+
+    let duplicate = ctx
+        .aircraft_service
+        .create_aircraft(CreateAircraftRequest {
+            aircraft_id: 1951,
+            layout: layout(8),
+        })
+        .await;
+    assert!(matches!(duplicate, Err(AppError::Conflict(_))));
+
+    // Eight seats in rows of two end with row 4
+    let unknown_row = ctx
+        .aircraft_service
+        .create_aircraft(CreateAircraftRequest {
+            aircraft_id: 1952,
+            layout: AircraftLayoutRequest {
+                exit_rows: vec![5],
+                ..layout(8)
+            },
+        })
+        .await;
+    assert!(matches!(unknown_row, Err(AppError::ValidationError(_))));
+
+    Ok(())
+}
+
+#[test_context(AircraftContext)]
+#[tokio::test]
+async fn test_update_aircraft_capacity(ctx: &AircraftContext) -> Result<(), AppError> {
+    let flight_number = 1953;
+    let flight_date = (chrono::Utc::now() + chrono::Duration::days(15)).date_naive();
+    ctx.aircraft_service
+        .create_aircraft(CreateAircraftRequest {
+            aircraft_id: flight_number,
+            layout: layout(4),
+        })
+        .await?;
+    let flight_id = ctx.create_flight(flight_number, flight_date, 4).await?;
+
+    for username in ["aircraft_user_1", "aircraft_user_2", "aircraft_user_3"] {
+        let user_id = ctx
+            .user_service
+            .register_user(UserRegistrationRequest {
+                username: username.to_string(),
+                password: "test_password".to_string(),
+                role: Role::User,
+                name: "Aircraft Test User".to_string(),
+                birth_date: NaiveDate::from_ymd_opt(1988, 9, 3).unwrap(),
+                gender: "female".to_string(),
+                email: None,
+            })
+            .await?;
+        ctx.ticket_service
+            .book_ticket(
+                user_id,
+                TicketBookingRequest {
+                    flights: vec![FlightBookingRequest {
+                        flight_number,
+                        flight_date,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            )
+            .await?;
+    }
+    assert_eq!(ctx.available_tickets(flight_id).await?, (1, 4));
+
+    // Growing the cabin adds seats and tickets to the flight
+    let response = ctx
+        .aircraft_service
+        .update_aircraft(flight_number, layout(6))
+        .await?;
+    assert_eq!(response.capacity, 6);
+    assert_eq!(response.updated_flights, 1);
+    assert_eq!(ctx.available_tickets(flight_id).await?, (3, 6));
+
+    // Three tickets are sold, two seats are not enough
+    let result = ctx
+        .aircraft_service
+        .update_aircraft(flight_number, layout(2))
+        .await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+    assert_eq!(ctx.available_tickets(flight_id).await?, (3, 6));
+
+    let missing = ctx.aircraft_service.update_aircraft(1954, layout(4)).await;
+    assert!(matches!(missing, Err(AppError::NotFound(_))));
+
+    Ok(())
+}
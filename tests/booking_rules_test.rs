@@ -0,0 +1,114 @@
+use airline_booking_system::models::booking_rules::{
+    BookingRules, DuplicatePolicy, LegFacts, DEFAULT_BOOKING_RULES_PATH,
+};
+use airline_booking_system::utils::error::AppError;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+fn now() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2030, 5, 1)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap()
+}
+
+fn leg(flight_number: i32, departure: NaiveDateTime) -> LegFacts {
+    LegFacts {
+        flight_number,
+        departure: Some(departure),
+    }
+}
+
+#[test]
+fn test_shipped_rules_match_built_in_policy() {
+    let path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), DEFAULT_BOOKING_RULES_PATH);
+    assert_eq!(BookingRules::load(path).unwrap(), BookingRules::default());
+}
+
+#[test]
+fn test_parse_rules() {
+    let rules = BookingRules::from_toml(
+        r#"
+        max_legs = 2
+        duplicate_booking = "allow"
+
+        [advance_purchase]
+        min_hours_before_departure = 3
+
+        [minors]
+        direct_flights_only = false
+        "#,
+    )
+    .unwrap();
+    assert_eq!(rules.max_legs, Some(2));
+    assert_eq!(rules.duplicate_booking, DuplicatePolicy::Allow);
+    assert_eq!(rules.advance_purchase.min_hours_before_departure, Some(3));
+    assert_eq!(rules.advance_purchase.max_days_before_departure, None);
+    assert!(!rules.minors.direct_flights_only);
+    // Left out settings keep their default
+    assert_eq!(
+        rules.minors.unaccompanied_minor_age,
+        BookingRules::default().minors.unaccompanied_minor_age
+    );
+
+    let json = BookingRules::from_json(
+        r#"{
+            "max_legs": 2,
+            "duplicate_booking": "allow",
+            "advance_purchase": {"min_hours_before_departure": 3},
+            "minors": {"direct_flights_only": false}
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(json, rules);
+
+    // Typos are reported instead of silently ignored
+    for invalid in [
+        "max_leg = 2",
+        "max_legs = 0",
+        "duplicate_booking = \"sometimes\"",
+        "[minors]\nmin_unaccompanied_age = 14",
+    ] {
+        assert!(matches!(
+            BookingRules::from_toml(invalid),
+            Err(AppError::ValidationError(_))
+        ));
+    }
+}
+
+#[test]
+fn test_evaluate_rules() {
+    let rules = BookingRules::from_toml(
+        r#"
+        max_legs = 2
+
+        [advance_purchase]
+        min_hours_before_departure = 2
+        max_days_before_departure = 30
+        "#,
+    )
+    .unwrap();
+
+    let tomorrow = leg(1, now() + Duration::days(1));
+    assert!(rules.evaluate(&[tomorrow.clone()], now()).is_empty());
+
+    let too_soon = leg(2, now() + Duration::hours(1));
+    let too_far = leg(3, now() + Duration::days(31));
+    let violations = rules.evaluate(&[too_soon, too_far], now());
+    assert_eq!(violations.len(), 2);
+    assert!(violations[0].contains("Flight 2"));
+    assert!(violations[1].contains("Flight 3"));
+
+    let violations = rules.evaluate(&[tomorrow.clone(), tomorrow.clone(), tomorrow], now());
+    assert_eq!(violations, vec!["At most 2 flights can be booked at once".to_string()]);
+
+    // Unknown flights count as legs but have no departure to check
+    let unknown = LegFacts {
+        flight_number: 4,
+        departure: None,
+    };
+    assert!(rules.evaluate(&[unknown], now()).is_empty());
+
+    // The built-in policy has no purchase window or leg limit
+    let far_and_past = [leg(5, now() - Duration::days(1)), leg(6, now() + Duration::days(400))];
+    assert!(BookingRules::default().evaluate(&far_and_past, now()).is_empty());
+}
@@ -0,0 +1,46 @@
+use airline_booking_system::models::changelog::{self, ApiChangeKind, API_CHANGES};
+use chrono::NaiveDate;
+
+#[test]
+fn test_changelog_generated_from_annotations() {
+    let change = API_CHANGES
+        .iter()
+        .find(|change| change.item == "get_seat_map_svg")
+        .expect("the seat map route is annotated");
+    assert_eq!(change.kind, ApiChangeKind::Added);
+    assert_eq!(
+        change.endpoint,
+        Some("GET /api/flights/<flight_id>/seatmap.svg")
+    );
+    assert_eq!(change.source, "src/routes/flight_route.rs");
+
+    // Fields are named with their model
+    assert!(API_CHANGES.iter().any(|change| {
+        change.item == "TicketBookingResponse.legacy_booking_status"
+            && change.kind == ApiChangeKind::Deprecated
+            && change.endpoint.is_none()
+    }));
+
+    // Newest first
+    assert!(API_CHANGES
+        .windows(2)
+        .all(|pair| pair[0].date >= pair[1].date));
+}
+
+#[test]
+fn test_changelog_filters() {
+    let deprecated = changelog::changelog(None, Some(ApiChangeKind::Deprecated));
+    assert!(!deprecated.changes.is_empty());
+    assert!(deprecated
+        .changes
+        .iter()
+        .all(|change| change.kind == ApiChangeKind::Deprecated));
+
+    let latest = API_CHANGES[0].date;
+    let since = NaiveDate::parse_from_str(latest, "%Y-%m-%d").unwrap() + chrono::Duration::days(1);
+    assert!(changelog::changelog(Some(since), None).changes.is_empty());
+    assert_eq!(
+        changelog::changelog(None, None).changes.len(),
+        API_CHANGES.len()
+    );
+}
@@ -1,3 +1,4 @@
+use airline_booking_system::utils::migrations::MIGRATOR;
 use dotenv::dotenv;
 use once_cell::sync::OnceCell;
 use sqlx::mysql::MySqlPool as Pool;
@@ -22,12 +23,22 @@ macro_rules! test_println {
 
 static TEST_DB: OnceCell<Mutex<Option<TestDb>>> = OnceCell::new();
 static DB_NAME: OnceCell<String> = OnceCell::new();
+// Databases holding snapshots, dropped together with the test database
+static SNAPSHOT_DBS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
 
 #[derive(Debug)]
 pub struct TestDb {
     pub db_name: String,
 }
 
+// Copy of every table of the test database taken by TestDb::snapshot
+#[allow(dead_code)] // Not every test file takes snapshots
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    db_name: String,
+    tables: Vec<String>,
+}
+
 // Create a connection pool without a database, used to create a new database
 async fn create_connection_pool_without_db() -> Result<Pool, Error> {
     dotenv().ok();
@@ -121,85 +132,9 @@ impl TestDb {
         Ok(Self { db_name })
     }
 
+    // Same migrations as the application runs at startup, so tests use the production schema
     async fn create_tables(pool: &Pool) -> Result<(), Error> {
-        let tables = vec![
-            "CREATE TABLE IF NOT EXISTS aircraft (
-                aircraft_id INT NOT NULL PRIMARY KEY,
-                capacity INT NOT NULL
-            )",
-            "CREATE TABLE IF NOT EXISTS user (
-                id INT AUTO_INCREMENT PRIMARY KEY,
-                username CHAR(255) NOT NULL,
-                password CHAR(255) NOT NULL,
-                role ENUM('ADMIN', 'USER') DEFAULT 'USER' NOT NULL,
-                CONSTRAINT user_username_uindex UNIQUE (username)
-            )",
-            "CREATE TABLE IF NOT EXISTS customer_info (
-                id INT NOT NULL PRIMARY KEY,
-                name CHAR(255) NOT NULL,
-                birth_date DATE NOT NULL,
-                gender ENUM('male', 'female') NOT NULL,
-                CONSTRAINT customer_info_user_id_fk
-                    FOREIGN KEY (id) REFERENCES user(id)
-                    ON DELETE CASCADE
-            )",
-            "CREATE TABLE IF NOT EXISTS flight_route (
-                flight_number INT NOT NULL PRIMARY KEY,
-                departure_city CHAR(255) NOT NULL,
-                destination_city CHAR(255) NOT NULL,
-                departure_time TIME NOT NULL,
-                arrival_time TIME NOT NULL,
-                aircraft_id INT NOT NULL,
-                overbooking DECIMAL(4,2) DEFAULT 0.00 NOT NULL,
-                start_date DATE NOT NULL,
-                end_date DATE NULL,
-                CONSTRAINT flight_route_aircraft_aircraft_id_fk
-                    FOREIGN KEY (aircraft_id) REFERENCES aircraft(aircraft_id)
-                    ON UPDATE CASCADE ON DELETE CASCADE
-            )",
-            "CREATE TABLE IF NOT EXISTS flight (
-                flight_id INT AUTO_INCREMENT PRIMARY KEY,
-                flight_number INT NOT NULL,
-                flight_date DATE NOT NULL,
-                available_tickets INT NOT NULL,
-                version INT NULL,
-                CONSTRAINT flight_flight_route_flight_number_fk
-                    FOREIGN KEY (flight_number) REFERENCES flight_route(flight_number)
-                    ON UPDATE CASCADE ON DELETE CASCADE
-            )",
-            "CREATE TABLE IF NOT EXISTS seat_info (
-                flight_id INT NOT NULL,
-                seat_number INT NOT NULL,
-                seat_status ENUM('AVAILABLE', 'UNAVAILABLE', 'BOOKED') DEFAULT 'AVAILABLE' NOT NULL,
-                version INT DEFAULT 0 NOT NULL,
-                PRIMARY KEY (flight_id, seat_number),
-                CONSTRAINT seat_info_flight_flight_id_fk
-                    FOREIGN KEY (flight_id) REFERENCES flight(flight_id)
-                    ON DELETE CASCADE
-            )",
-            "CREATE TABLE IF NOT EXISTS ticket (
-                id INT AUTO_INCREMENT PRIMARY KEY,
-                customer_id INT NOT NULL,
-                flight_id INT NOT NULL,
-                seat_number INT NULL,
-                flight_date DATE NOT NULL,
-                flight_number INT NOT NULL,
-                CONSTRAINT ticket_customer_info_id_fk
-                    FOREIGN KEY (customer_id) REFERENCES customer_info(id)
-                    ON DELETE CASCADE,
-                CONSTRAINT ticket_flight_id_fk
-                    FOREIGN KEY (flight_id) REFERENCES flight(flight_id)
-                    ON DELETE CASCADE,
-                CONSTRAINT ticket_seat_info_flight_id_seat_number_fk
-                    FOREIGN KEY (flight_id, seat_number) 
-                    REFERENCES seat_info(flight_id, seat_number)
-            )",
-        ];
-
-        for create_sql in tables {
-            sqlx::query(create_sql).execute(pool).await?;
-        }
-
+        MIGRATOR.run(pool).await?;
         Ok(())
     }
 
@@ -221,14 +156,18 @@ impl TestDb {
         let username = auth[0];
         let password = auth[1];
 
-        // Get the database name and drop the database
+        // Get the database name and drop the database along with its snapshots
         if let Some(db_name) = DB_NAME.get() {
+            let mut statements = format!("DROP DATABASE IF EXISTS {};", db_name);
+            for snapshot_db in SNAPSHOT_DBS.lock().unwrap().iter() {
+                statements.push_str(&format!(" DROP DATABASE IF EXISTS {};", snapshot_db));
+            }
             let output = std::process::Command::new("mysql")
                 .arg("-u")
                 .arg(username)
                 .arg(format!("-p{}", password))
                 .arg("-e")
-                .arg(format!("DROP DATABASE IF EXISTS {};", db_name))
+                .arg(statements)
                 .output()?;
 
             if !output.status.success() {
@@ -242,3 +181,104 @@ impl TestDb {
         Ok(())
     }
 }
+
+#[allow(dead_code)] // Not every test file takes snapshots
+impl TestDb {
+    // Copy every table of the test database into a database of its own, so state that
+    // is expensive to seed can be set up once and brought back with restore. Taking a
+    // snapshot under a name already used replaces it.
+    pub async fn snapshot(pool: &Pool, name: &str) -> Result<Snapshot, Error> {
+        let source = DB_NAME.get().expect("test database not set up");
+        let db_name = format!("{}_snap_{}", source, name);
+
+        sqlx::query(&format!("DROP DATABASE IF EXISTS {}", db_name))
+            .execute(pool)
+            .await?;
+        sqlx::query(&format!("CREATE DATABASE {}", db_name))
+            .execute(pool)
+            .await?;
+        {
+            let mut snapshot_dbs = SNAPSHOT_DBS.lock().unwrap();
+            if !snapshot_dbs.contains(&db_name) {
+                snapshot_dbs.push(db_name.clone());
+            }
+        }
+
+        // The migration history is not test data
+        let tables: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT CAST(table_name AS CHAR) FROM information_schema.tables
+            WHERE table_schema = ? AND table_type = 'BASE TABLE'
+            AND table_name <> '_sqlx_migrations'
+            "#,
+        )
+        .bind(source)
+        .fetch_all(pool)
+        .await?;
+
+        for table in &tables {
+            sqlx::query(&format!(
+                "CREATE TABLE {}.`{}` LIKE {}.`{}`",
+                db_name, table, source, table
+            ))
+            .execute(pool)
+            .await?;
+            sqlx::query(&format!(
+                "INSERT INTO {}.`{}` SELECT * FROM {}.`{}`",
+                db_name, table, source, table
+            ))
+            .execute(pool)
+            .await?;
+        }
+
+        Ok(Snapshot { db_name, tables })
+    }
+
+    // Pool of the database holding the snapshot, for tests needing a second database
+    pub async fn connect_snapshot(snapshot: &Snapshot) -> Result<Pool, Error> {
+        create_connection_pool_with_db(&snapshot.db_name).await
+    }
+
+    // Put every table back to its content at the snapshot. Rows of all tests of the
+    // file are replaced, so tests restoring a snapshot must not run alongside others
+    // sharing the database.
+    pub async fn restore(pool: &Pool, snapshot: &Snapshot) -> Result<(), Error> {
+        // Foreign key checks are per session, the tables are refilled on one connection
+        let mut conn = pool.acquire().await?;
+        sqlx::query("SET FOREIGN_KEY_CHECKS = 0")
+            .execute(&mut *conn)
+            .await?;
+
+        let mut result = Ok(());
+        for table in &snapshot.tables {
+            result = Self::restore_table(&mut conn, &snapshot.db_name, table).await;
+            if result.is_err() {
+                break;
+            }
+        }
+
+        sqlx::query("SET FOREIGN_KEY_CHECKS = 1")
+            .execute(&mut *conn)
+            .await?;
+        result
+    }
+
+    async fn restore_table(
+        conn: &mut sqlx::MySqlConnection,
+        snapshot_db: &str,
+        table: &str,
+    ) -> Result<(), Error> {
+        // Truncating also resets the auto increment counters, refilling the table moves
+        // them past the restored ids
+        sqlx::query(&format!("TRUNCATE TABLE `{}`", table))
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(&format!(
+            "INSERT INTO `{}` SELECT * FROM {}.`{}`",
+            table, snapshot_db, table
+        ))
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use airline_booking_system::utils::concurrency_limiter::{
+    BookingLimiters, BookingSlot, ConcurrencyLimiter,
+};
+use airline_booking_system::utils::tunables::{SharedTunables, Tunables};
+use rocket::http::Status;
+use rocket::local::asynchronous::Client;
+
+#[rocket::post("/bookings")]
+fn create_booking(_slot: BookingSlot) -> &'static str {
+    "booked"
+}
+
+fn limiters(per_user: usize, per_ip: usize) -> BookingLimiters {
+    BookingLimiters::new(SharedTunables::new(Tunables {
+        booking_concurrency_per_user: per_user,
+        booking_concurrency_per_ip: per_ip,
+        ..Tunables::default()
+    }))
+}
+
+#[test]
+fn test_slot_rejected_at_limit_and_freed_on_drop() {
+    let limiter = ConcurrencyLimiter::new();
+    let first = limiter.try_acquire("user:1", 2);
+    let second = limiter.try_acquire("user:1", 2);
+    assert!(first.is_some() && second.is_some());
+    assert!(limiter.try_acquire("user:1", 2).is_none());
+    // Other keys have slots of their own
+    assert!(limiter.try_acquire("user:2", 2).is_some());
+
+    drop(first);
+    assert!(limiter.try_acquire("user:1", 2).is_some());
+}
+
+#[test]
+fn test_user_and_ip_slots() {
+    let limiters = limiters(1, 2);
+
+    let user_slot = limiters.try_acquire_user(7);
+    assert!(user_slot.is_some());
+    assert!(limiters.try_acquire_user(7).is_none());
+    assert!(limiters.try_acquire_user(8).is_some());
+    drop(user_slot);
+    assert!(limiters.try_acquire_user(7).is_some());
+
+    let ip_slots = [
+        limiters.try_acquire_ip("10.0.0.1"),
+        limiters.try_acquire_ip("10.0.0.1"),
+    ];
+    assert!(ip_slots.iter().all(Option::is_some));
+    assert!(limiters.try_acquire_ip("10.0.0.1").is_none());
+    assert!(limiters.try_acquire_ip("10.0.0.2").is_some());
+    drop(ip_slots);
+    assert!(limiters.try_acquire_ip("10.0.0.1").is_some());
+}
+
+#[rocket::async_test]
+async fn test_booking_slot_guard() {
+    let limiters = limiters(1, 1);
+    // A booking of this IP is still in flight
+    let in_flight = limiters.try_acquire_ip("10.0.0.1");
+    assert!(in_flight.is_some());
+
+    let rocket = rocket::build()
+        .mount("/", rocket::routes![create_booking])
+        .manage(limiters);
+    let client = Client::tracked(rocket).await.expect("valid rocket");
+    let from = |ip: &str| {
+        client
+            .post("/bookings")
+            .remote(format!("{}:4000", ip).parse().unwrap())
+    };
+
+    assert_eq!(from("10.0.0.1").dispatch().await.status(), Status::Conflict);
+    assert_eq!(from("10.0.0.2").dispatch().await.status(), Status::Ok);
+    // The slot of a request is given back when it completes
+    assert_eq!(from("10.0.0.2").dispatch().await.status(), Status::Ok);
+
+    drop(in_flight);
+    assert_eq!(from("10.0.0.1").dispatch().await.status(), Status::Ok);
+}
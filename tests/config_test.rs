@@ -0,0 +1,203 @@
+use airline_booking_system::utils::concurrency_limiter::BookingLimiters;
+use airline_booking_system::utils::config::{AppConfig, CorsConfig};
+use airline_booking_system::utils::error::AppError;
+use airline_booking_system::utils::tunables::{ConfigReloader, SharedTunables};
+use std::collections::HashMap;
+use std::time::Duration;
+
+fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    move |name| vars.get(name).cloned()
+}
+
+#[test]
+fn test_config_from_env() {
+    let config = AppConfig::from_sources(
+        None,
+        env(&[
+            ("DATABASE_URL", "mysql://localhost/airline"),
+            ("JWT_SECRET", "secret"),
+            ("CORS_ALLOWED_ORIGINS", "https://a.example, https://b.example"),
+            ("BOOKING_CONCURRENCY_PER_IP", "4"),
+            ("LOGIN_RATE_PER_MINUTE", "3"),
+        ]),
+    )
+    .unwrap();
+    assert_eq!(config.database.url, "mysql://localhost/airline");
+    assert_eq!(config.auth.jwt_secret, "secret");
+    assert_eq!(
+        config.cors.allowed_origins,
+        vec!["https://a.example", "https://b.example"]
+    );
+    assert_eq!(config.limits.booking_concurrency_per_ip, 4);
+    assert_eq!(config.tunables().login_per_minute, 3);
+    // Settings not given keep their default
+    assert_eq!(config.database.pool_size, 10);
+    assert_eq!(config.auth.jwt_expiry_hours, 24);
+    assert_eq!(config.auth.bcrypt_cost, bcrypt::DEFAULT_COST);
+}
+
+#[test]
+fn test_config_file_overridden_by_env() {
+    let file = r#"
+        [database]
+        url = "mysql://file/airline"
+        pool_size = 5
+
+        [auth]
+        jwt_secret = "from-file"
+        jwt_expiry_hours = 2
+    "#;
+    let config =
+        AppConfig::from_sources(Some(file), env(&[("DATABASE_POOL_SIZE", "20")])).unwrap();
+    assert_eq!(config.database.url, "mysql://file/airline");
+    assert_eq!(config.database.pool_size, 20);
+    assert_eq!(config.auth.jwt_secret, "from-file");
+    assert_eq!(config.auth.jwt_expiry_hours, 2);
+
+    let shipped = include_str!("../util/config.example.toml");
+    assert!(AppConfig::from_sources(Some(shipped), env(&[])).is_ok());
+
+    let result = AppConfig::from_sources(Some("[database]\nport = 3306"), env(&[]));
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+}
+
+#[test]
+fn test_config_reports_every_error() {
+    let result = AppConfig::from_sources(
+        None,
+        env(&[
+            ("DATABASE_POOL_SIZE", "many"),
+            ("BCRYPT_COST", "2"),
+            ("CORS_ALLOWED_ORIGINS", "example.com"),
+            ("SMTP_URL", "mail.example.com"),
+            ("DATA_REGION", "EU West"),
+        ]),
+    );
+    let message = match result {
+        Err(AppError::ValidationError(message)) => message,
+        other => panic!("Expected a validation error, got {:?}", other),
+    };
+    for expected in [
+        "DATABASE_URL",
+        "JWT_SECRET",
+        "DATABASE_POOL_SIZE must be a number",
+        "bcrypt_cost",
+        "example.com",
+        "notification.smtp_url",
+        "residency region EU West",
+    ] {
+        assert!(message.contains(expected), "{} missing from {}", expected, message);
+    }
+}
+
+#[test]
+fn test_cors_allowed_origin() {
+    let any = CorsConfig::default();
+    assert_eq!(any.allowed_origin(None), Some("*".to_string()));
+
+    let listed = CorsConfig {
+        allowed_origins: vec!["https://app.example".to_string()],
+        ..CorsConfig::default()
+    };
+    assert_eq!(
+        listed.allowed_origin(Some("https://app.example")),
+        Some("https://app.example".to_string())
+    );
+    assert_eq!(listed.allowed_origin(Some("https://evil.example")), None);
+    assert_eq!(listed.allowed_origin(None), None);
+}
+
+#[test]
+fn test_config_reload_applies_tunables() {
+    let startup = AppConfig::from_sources(
+        None,
+        env(&[
+            ("DATABASE_URL", "mysql://localhost/airline"),
+            ("JWT_SECRET", "secret"),
+        ]),
+    )
+    .unwrap();
+    let tunables = SharedTunables::new(startup.tunables());
+    let reloader = ConfigReloader::new(startup.clone(), tunables.clone());
+    let limiters = BookingLimiters::new(tunables.clone());
+
+    let first = limiters.try_acquire_user(7);
+    assert!(first.is_some());
+    assert!(limiters.try_acquire_user(7).is_none());
+
+    let file = r#"
+        [database]
+        url = "mysql://elsewhere/airline"
+
+        [limits]
+        booking_concurrency_per_user = 2
+
+        [partner]
+        availability_cache_ttl_seconds = 5
+    "#;
+    let reloaded = AppConfig::from_sources(Some(file), env(&[("JWT_SECRET", "secret")])).unwrap();
+    let response = reloader.apply(reloaded);
+    assert_eq!(
+        response.changed,
+        vec![
+            "limits.booking_concurrency_per_user",
+            "partner.availability_cache_ttl_seconds"
+        ]
+    );
+    assert_eq!(response.restart_required, vec!["database"]);
+
+    // Services see the new values right away
+    let current = tunables.current();
+    assert_eq!(current.booking_concurrency_per_user, 2);
+    assert_eq!(current.availability_cache_ttl, Duration::from_secs(5));
+    let second = limiters.try_acquire_user(7);
+    let third = limiters.try_acquire_user(7);
+    assert!(second.is_some() && third.is_some());
+    assert!(limiters.try_acquire_user(7).is_none());
+
+    // Going back to the startup configuration needs no restart
+    let response = reloader.apply(startup);
+    assert!(response.restart_required.is_empty());
+    assert_eq!(tunables.current().booking_concurrency_per_user, 1);
+}
+
+#[test]
+fn test_seat_lock_redis_needs_the_redis_feature() {
+    let result = AppConfig::from_sources(
+        None,
+        env(&[
+            ("DATABASE_URL", "mysql://localhost/airline"),
+            ("JWT_SECRET", "secret"),
+            ("SEAT_LOCK_REDIS_URL", "redis://localhost:6379"),
+        ]),
+    );
+    if cfg!(feature = "redis") {
+        assert!(result.is_ok());
+    } else {
+        // Started without the feature the seats would silently go unlocked
+        match result {
+            Err(AppError::ValidationError(message)) => assert!(
+                message.contains(
+                    "limits.seat_lock_redis_url needs the server built with the redis feature"
+                ),
+                "{}",
+                message
+            ),
+            other => panic!("Expected a validation error, got {:?}", other),
+        }
+    }
+
+    let result = AppConfig::from_sources(
+        None,
+        env(&[
+            ("DATABASE_URL", "mysql://localhost/airline"),
+            ("JWT_SECRET", "secret"),
+            ("SEAT_LOCK_REDIS_URL", "localhost:6379"),
+        ]),
+    );
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+}
@@ -0,0 +1,104 @@
+use airline_booking_system::utils::config::CorsConfig;
+use airline_booking_system::utils::cors::Cors;
+use rocket::http::{Header, Status};
+use rocket::local::asynchronous::Client;
+
+#[rocket::post("/bookings")]
+fn create_booking() -> &'static str {
+    "booked"
+}
+
+async fn client(config: CorsConfig) -> Client {
+    let rocket = rocket::build()
+        .mount("/", rocket::routes![create_booking])
+        .attach(Cors::new(config));
+    Client::tracked(rocket).await.expect("valid rocket")
+}
+
+fn listed_origin() -> CorsConfig {
+    CorsConfig {
+        allowed_origins: vec!["https://app.example".to_string()],
+        max_age_seconds: 600,
+        ..CorsConfig::default()
+    }
+}
+
+#[rocket::async_test]
+async fn test_preflight_is_answered() {
+    let client = client(listed_origin()).await;
+    let response = client
+        .options("/bookings")
+        .header(Header::new("Origin", "https://app.example"))
+        .header(Header::new("Access-Control-Request-Method", "POST"))
+        .header(Header::new(
+            "Access-Control-Request-Headers",
+            "authorization, content-type",
+        ))
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::NoContent);
+    let headers = response.headers();
+    assert_eq!(
+        headers.get_one("Access-Control-Allow-Origin"),
+        Some("https://app.example")
+    );
+    assert!(headers
+        .get_one("Access-Control-Allow-Methods")
+        .unwrap()
+        .contains("POST"));
+    assert!(headers
+        .get_one("Access-Control-Allow-Headers")
+        .unwrap()
+        .contains("Authorization"));
+    assert_eq!(headers.get_one("Access-Control-Max-Age"), Some("600"));
+    assert_eq!(headers.get_one("Vary"), Some("Origin"));
+}
+
+#[rocket::async_test]
+async fn test_actual_request_gets_cors_headers() {
+    let client = client(listed_origin()).await;
+    let response = client
+        .post("/bookings")
+        .header(Header::new("Origin", "https://app.example"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        response.headers().get_one("Access-Control-Allow-Origin"),
+        Some("https://app.example")
+    );
+    assert!(response
+        .headers()
+        .get_one("Access-Control-Allow-Methods")
+        .is_none());
+    assert_eq!(response.into_string().await.as_deref(), Some("booked"));
+}
+
+#[rocket::async_test]
+async fn test_unlisted_origin_gets_no_cors_headers() {
+    let client = client(listed_origin()).await;
+    let response = client
+        .options("/bookings")
+        .header(Header::new("Origin", "https://evil.example"))
+        .header(Header::new("Access-Control-Request-Method", "POST"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+    assert!(response
+        .headers()
+        .get_one("Access-Control-Allow-Origin")
+        .is_none());
+
+    // Any origin is allowed by default
+    let client = client(CorsConfig::default()).await;
+    let response = client
+        .post("/bookings")
+        .header(Header::new("Origin", "https://evil.example"))
+        .dispatch()
+        .await;
+    assert_eq!(
+        response.headers().get_one("Access-Control-Allow-Origin"),
+        Some("*")
+    );
+}
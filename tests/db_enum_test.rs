@@ -0,0 +1,162 @@
+use airline_booking_system::{
+    models::{
+        aircraft::SeatReassignmentStatus, db_enum::DbEnum, fare::FareClass,
+        flight::{FlightStatus, SeatStatus},
+        funnel::FunnelStep,
+        loyalty::LoyaltyEntryType,
+        payment::{PaymentStatus, RefundStatus},
+        promo::DiscountType,
+        seat_block::SeatBlockRuleKind,
+        ssr::{self, SsrCode},
+        ticket::{BookingRequestStatus, CorrectionReason, RebookingStatus},
+        user::Role,
+    },
+    utils::schema_check,
+};
+use std::fmt::Debug;
+
+// Every variant maps to a distinct string that maps back to it, in any case
+fn assert_round_trip<T: DbEnum + PartialEq + Debug>() {
+    for variant in T::VARIANTS {
+        let value = variant.as_db_str();
+        assert_eq!(T::from_db_str(value), Some(*variant));
+        assert_eq!(T::from_db_str(&value.to_lowercase()), Some(*variant));
+        assert_eq!(
+            T::VARIANTS
+                .iter()
+                .filter(|other| other.as_db_str() == value)
+                .count(),
+            1,
+            "{} is used by more than one variant",
+            value
+        );
+    }
+    assert_eq!(T::from_db_str("NOT_A_VALUE"), None);
+}
+
+// The variants match the values of the ENUM column in the migrations exactly
+fn assert_matches_column<T: DbEnum>(table: &str, column: &str) {
+    let definition = schema_check::expected_columns()
+        .into_iter()
+        .find(|expected| expected.table == table && expected.column == column)
+        .unwrap_or_else(|| panic!("Column {}.{} not found", table, column))
+        .definition;
+
+    let start = definition.find('(').unwrap();
+    let end = definition.find(')').unwrap();
+    let mut values: Vec<String> = definition[start + 1..end]
+        .split(',')
+        .map(|value| value.trim().trim_matches('\'').to_string())
+        .collect();
+    let mut variants: Vec<String> = T::VARIANTS
+        .iter()
+        .map(|variant| variant.as_db_str().to_string())
+        .collect();
+    values.sort();
+    variants.sort();
+
+    assert_eq!(variants, values, "{}.{}", table, column);
+}
+
+#[test]
+fn test_seat_status_mapping() {
+    assert_round_trip::<SeatStatus>();
+    assert_matches_column::<SeatStatus>("seat_info", "seat_status");
+    assert_eq!(SeatStatus::Booked.as_db_str(), "BOOKED");
+}
+
+#[test]
+fn test_role_mapping() {
+    assert_round_trip::<Role>();
+    assert_matches_column::<Role>("user", "role");
+    assert_eq!(Role::from_db_str("admin"), Some(Role::Admin));
+}
+
+#[test]
+fn test_payment_status_mapping() {
+    assert_round_trip::<PaymentStatus>();
+    assert_matches_column::<PaymentStatus>("payment", "status");
+}
+
+#[test]
+fn test_refund_status_mapping() {
+    assert_round_trip::<RefundStatus>();
+    assert_matches_column::<RefundStatus>("refund", "status");
+}
+
+#[test]
+fn test_loyalty_entry_type_mapping() {
+    assert_round_trip::<LoyaltyEntryType>();
+    assert_matches_column::<LoyaltyEntryType>("loyalty_ledger", "entry_type");
+}
+
+#[test]
+fn test_discount_type_mapping() {
+    assert_round_trip::<DiscountType>();
+    assert_matches_column::<DiscountType>("promo_code", "discount_type");
+}
+
+#[test]
+fn test_seat_block_rule_kind_mapping() {
+    assert_round_trip::<SeatBlockRuleKind>();
+    assert_matches_column::<SeatBlockRuleKind>("seat_block_rule", "rule");
+}
+
+#[test]
+fn test_ssr_code_mapping() {
+    assert_round_trip::<SsrCode>();
+    // Requests and the database use the same IATA codes
+    for code in SsrCode::VARIANTS {
+        assert_eq!(
+            serde_json::to_value(code).unwrap(),
+            serde_json::json!(code.as_db_str())
+        );
+    }
+    let codes = vec![SsrCode::WheelchairRamp, SsrCode::VegetarianMeal];
+    assert_eq!(ssr::ssr_codes_to_db(&codes), "WCHR,VGML");
+    assert_eq!(ssr::ssr_codes_from_db("WCHR,VGML"), codes);
+    assert!(ssr::ssr_codes_from_db("").is_empty());
+}
+
+#[test]
+fn test_fare_class_mapping() {
+    assert_round_trip::<FareClass>();
+    assert_matches_column::<FareClass>("fare", "fare_class");
+    assert_matches_column::<FareClass>("ticket", "fare_class");
+}
+
+#[test]
+fn test_funnel_step_mapping() {
+    assert_round_trip::<FunnelStep>();
+    assert_matches_column::<FunnelStep>("funnel_event", "step");
+}
+
+#[test]
+fn test_flight_status_mapping() {
+    assert_round_trip::<FlightStatus>();
+    assert_matches_column::<FlightStatus>("flight", "status");
+}
+
+#[test]
+fn test_rebooking_status_mapping() {
+    assert_round_trip::<RebookingStatus>();
+    assert_matches_column::<RebookingStatus>("rebooking", "status");
+}
+
+#[test]
+fn test_booking_request_status_mapping() {
+    assert_round_trip::<BookingRequestStatus>();
+    assert_matches_column::<BookingRequestStatus>("booking_request", "status");
+}
+
+#[test]
+fn test_seat_reassignment_status_mapping() {
+    assert_round_trip::<SeatReassignmentStatus>();
+    assert_matches_column::<SeatReassignmentStatus>("seat_reassignment", "status");
+}
+
+#[test]
+fn test_correction_reason_mapping() {
+    assert_round_trip::<CorrectionReason>();
+    assert_matches_column::<CorrectionReason>("ticket_correction", "reason");
+}
@@ -0,0 +1,107 @@
+use airline_booking_system::utils::envelope::{Envelope, EnvelopeRequested, ENVELOPE_VERSION};
+use rocket::http::{Accept, Header};
+use rocket::local::asynchronous::Client;
+use rocket::serde::json::{json, Json, Value};
+
+#[rocket::get("/booking")]
+fn booking(envelope: EnvelopeRequested) -> Json<Value> {
+    let booking = json!({ "booking_id": 7 });
+    if envelope.0 {
+        let warnings = vec!["Preferred seat unavailable".to_string()];
+        return Json(json!(Envelope::new(booking, warnings)));
+    }
+    Json(booking)
+}
+
+async fn client() -> Client {
+    let rocket = rocket::build().mount("/", rocket::routes![booking]);
+    Client::tracked(rocket).await.expect("valid rocket")
+}
+
+fn is_enveloped(body: &Value) -> bool {
+    body.get("data").is_some()
+}
+
+#[rocket::async_test]
+async fn test_envelope_requested_by_accept_profile() {
+    let client = client().await;
+    for accept in [
+        r#"application/json; profile="envelope""#,
+        "application/json;profile=envelope",
+        r#"text/html, application/json; q=0.9; PROFILE="envelope""#,
+    ] {
+        let body: Value = client
+            .get("/booking")
+            .header(Header::new("Accept", accept))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        assert!(is_enveloped(&body), "{}", accept);
+    }
+
+    for accept in [r#"application/json; profile="other""#, "application/json"] {
+        let body: Value = client
+            .get("/booking")
+            .header(Header::new("Accept", accept))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        assert!(!is_enveloped(&body), "{}", accept);
+    }
+}
+
+#[rocket::async_test]
+async fn test_envelope_requested_by_query() {
+    let client = client().await;
+    let body = |uri: &'static str| {
+        let client = &client;
+        async move {
+            client
+                .get(uri)
+                .header(Accept::JSON)
+                .dispatch()
+                .await
+                .into_json::<Value>()
+                .await
+                .unwrap()
+        }
+    };
+
+    assert!(is_enveloped(&body("/booking?envelope=true").await));
+    assert!(!is_enveloped(&body("/booking?envelope=false").await));
+    assert!(!is_enveloped(&body("/booking?envelope=yes-please").await));
+    assert!(!is_enveloped(&body("/booking").await));
+}
+
+#[rocket::async_test]
+async fn test_enveloped_response_shape() {
+    let client = client().await;
+    let body: Value = client
+        .get("/booking?envelope=true")
+        .dispatch()
+        .await
+        .into_json()
+        .await
+        .unwrap();
+
+    assert_eq!(body["data"], json!({ "booking_id": 7 }));
+    assert_eq!(body["warnings"], json!(["Preferred seat unavailable"]));
+    assert_eq!(body["meta"]["version"], json!(ENVELOPE_VERSION));
+    let generated_at = body["meta"]["generated_at"].as_str().unwrap();
+    assert!(chrono::DateTime::parse_from_rfc3339(generated_at).is_ok());
+    assert_eq!(body.as_object().unwrap().len(), 3);
+
+    // Without the envelope the payload is the whole body
+    let body: Value = client
+        .get("/booking")
+        .dispatch()
+        .await
+        .into_json()
+        .await
+        .unwrap();
+    assert_eq!(body, json!({ "booking_id": 7 }));
+}
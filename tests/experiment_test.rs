@@ -0,0 +1,53 @@
+use airline_booking_system::utils::experiment::{
+    assignments_for, Experiment, EXPERIMENTS, SEAT_ASSIGNMENT,
+};
+
+#[test]
+fn test_assignment_is_deterministic() {
+    for user_id in 1..100 {
+        assert_eq!(
+            SEAT_ASSIGNMENT.variant_for(user_id),
+            SEAT_ASSIGNMENT.variant_for(user_id)
+        );
+    }
+    assert_eq!(assignments_for(42), assignments_for(42));
+    assert_eq!(assignments_for(42).len(), EXPERIMENTS.len());
+}
+
+#[test]
+fn test_users_are_split_between_variants() {
+    let experiment = Experiment {
+        key: "split_test",
+        variants: &["a", "b", "c"],
+    };
+
+    let mut counts = [0; 3];
+    for user_id in 1..=3000 {
+        let variant = experiment.variant_for(user_id);
+        let index = experiment
+            .variants
+            .iter()
+            .position(|v| *v == variant)
+            .unwrap();
+        counts[index] += 1;
+    }
+
+    // Roughly a third each
+    for count in counts {
+        assert!((800..=1200).contains(&count), "{:?}", counts);
+    }
+}
+
+#[test]
+fn test_experiments_are_independent() {
+    let other = Experiment {
+        key: "other_experiment",
+        variants: SEAT_ASSIGNMENT.variants,
+    };
+
+    // The same user does not land in the same variant of every experiment
+    let differing = (1..200)
+        .filter(|user_id| other.variant_for(*user_id) != SEAT_ASSIGNMENT.variant_for(*user_id))
+        .count();
+    assert!(differing > 0);
+}
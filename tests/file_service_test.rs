@@ -0,0 +1,78 @@
+use airline_booking_system::{
+    models::file::FILE_ROUTE_PREFIX,
+    services::file_service::{FileService, FileStorage, LocalFileStorage},
+    utils::{config::AuthConfig, document::Document, error::AppError, jwt},
+};
+use rocket::http::ContentType;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn local_storage() -> LocalFileStorage {
+    // Links are signed with the auth secret
+    jwt::configure(AuthConfig {
+        jwt_secret: "file-service-test".to_string(),
+        ..Default::default()
+    });
+    let root = std::env::temp_dir().join(format!("file_service_test_{}", uuid::Uuid::new_v4()));
+    LocalFileStorage::new(root)
+}
+
+fn token(url: &str) -> &str {
+    url.strip_prefix(FILE_ROUTE_PREFIX)
+        .expect("Link to the file route")
+}
+
+#[tokio::test]
+async fn test_store_and_download() -> Result<(), AppError> {
+    let file_service = FileService::new(Arc::new(local_storage()));
+    let document = Document::pdf("boarding-pass-ABC123", b"%PDF-1.7 test".to_vec());
+
+    let link = file_service.store("boarding-passes", &document).await?;
+    let now = chrono::Utc::now().naive_utc();
+    assert!(link.expires_at > now && link.expires_at <= now + chrono::Duration::minutes(15));
+
+    let downloaded = file_service.open(token(&link.url)).await?;
+    assert_eq!(downloaded.file_name(), "boarding-pass-ABC123.pdf");
+    assert_eq!(downloaded.content_type(), &ContentType::PDF);
+    assert_eq!(downloaded.bytes(), document.bytes());
+
+    // Storing the same document again gives another file and link
+    let again = file_service.store("boarding-passes", &document).await?;
+    assert_ne!(again.url, link.url);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_invalid_links_rejected() -> Result<(), AppError> {
+    let file_service = FileService::new(Arc::new(local_storage()));
+
+    let result = file_service.open("not-a-token").await;
+    assert!(matches!(result, Err(AppError::AuthError(_))));
+
+    // A link to a file that was never stored
+    let link = file_service.link("exports/missing.ndjson")?;
+    let result = file_service.open(token(&link.url)).await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    // The TTL sets the expiry of the link
+    let short = FileService::new(Arc::new(local_storage())).with_url_ttl(Duration::from_secs(30));
+    let link = short.link("exports/missing.ndjson")?;
+    assert!(link.expires_at <= chrono::Utc::now().naive_utc() + chrono::Duration::seconds(30));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_local_storage_stays_in_its_directory() {
+    let storage = local_storage();
+    for key in ["../outside", "/etc/passwd", "exports/../../outside"] {
+        assert!(
+            storage.put(key, b"data").await.is_err(),
+            "{} was stored",
+            key
+        );
+        assert!(storage.get(key).await.is_err(), "{} was read", key);
+    }
+    assert_eq!(storage.get("exports/missing").await, Ok(None));
+}
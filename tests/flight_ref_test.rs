@@ -0,0 +1,62 @@
+use airline_booking_system::utils::{
+    error::AppError,
+    flight_ref::{parse_flight_date, FlightRef},
+};
+use chrono::NaiveDate;
+use rocket::http::Status;
+use rocket::local::asynchronous::Client;
+
+#[rocket::get("/flight?<flight..>")]
+fn flight_date(flight: FlightRef) -> Result<String, AppError> {
+    Ok(format!("{} {}", flight.flight_number, flight.date()?))
+}
+
+async fn client() -> Client {
+    let rocket = rocket::build().mount("/", rocket::routes![flight_date]);
+    Client::tracked(rocket).await.expect("valid rocket")
+}
+
+#[test]
+fn test_parse_flight_date() {
+    assert_eq!(
+        parse_flight_date("2025-03-01").unwrap(),
+        NaiveDate::from_ymd_opt(2025, 3, 1).unwrap()
+    );
+
+    for date in ["", "2025-3-1x", "01/03/2025", "2025-02-30", "2025-13-01"] {
+        match parse_flight_date(date) {
+            Err(AppError::BadRequest(message)) => {
+                assert_eq!(message, "Invalid flight date format, expected YYYY-MM-DD")
+            }
+            other => panic!("Expected BadRequest for {:?}, got {:?}", date, other),
+        }
+    }
+}
+
+#[rocket::async_test]
+async fn test_flight_ref_query() {
+    let client = client().await;
+
+    let response = client
+        .get("/flight?flight_number=42&flight_date=2025-03-01")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        response.into_string().await.as_deref(),
+        Some("42 2025-03-01")
+    );
+
+    let response = client
+        .get("/flight?flight_number=42&flight_date=2025-02-30")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::BadRequest);
+
+    // Without a flight number the route does not match
+    let response = client
+        .get("/flight?flight_date=2025-03-01")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+}
@@ -1,12 +1,26 @@
 use airline_booking_system::{
-    models::flight::FlightSearchQuery,
-    services::flight_service::FlightService,
-    utils::error::AppError,
+    models::{
+        aircraft::SeatPosition,
+        flight::{FlightSearchQuery, PriceCalendarQuery, SearchSort},
+    },
+    services::{
+        flight_service::{FlightService, RECENT_FLIGHTS_LIMIT},
+        search_cache::SearchCache,
+    },
+    utils::{
+        config::SeatMapView,
+        error::AppError,
+        flight_ref::FlightRef,
+        ndjson::collect_rows,
+        tunables::{SharedTunables, Tunables},
+    },
 };
 use async_trait::async_trait;
-use chrono::{NaiveDate, NaiveTime};
+use chrono::{DateTime, NaiveDate, NaiveTime};
 use ctor::dtor;
+use rust_decimal::Decimal;
 use sqlx::mysql::MySqlPool as Pool;
+use std::time::Duration;
 use test_context::{test_context, AsyncTestContext};
 
 mod common {
@@ -146,6 +160,7 @@ async fn test_search_flights_single_date(ctx: &FlightServiceContext) -> Result<(
         destination_city: "Shanghai".to_string(),
         departure_date,
         end_date: None,
+        ..Default::default()
     };
 
     let result = ctx.flight_service.search_flights(search_query).await?;
@@ -157,6 +172,11 @@ async fn test_search_flights_single_date(ctx: &FlightServiceContext) -> Result<(
     assert_eq!(result.flights[0].destination_city, "Shanghai");
     assert_eq!(result.flights[0].available_tickets, 100);
 
+    // Routes without configured fares sell economy at the base fare
+    assert_eq!(result.fares.len(), 1);
+    assert_eq!(result.fares[0].flight_number, 101);
+    assert_eq!(result.fares[0].fares.len(), 1);
+
     Ok(())
 }
 
@@ -182,6 +202,7 @@ async fn test_search_flights_date_range(ctx: &FlightServiceContext) -> Result<()
         destination_city: "Ottawa".to_string(),
         departure_date: start_date,
         end_date: Some(end_date),
+        ..Default::default()
     };
 
     let result = ctx.flight_service.search_flights(search_query).await?;
@@ -199,6 +220,564 @@ async fn test_search_flights_date_range(ctx: &FlightServiceContext) -> Result<()
     Ok(())
 }
 
+#[test_context(FlightServiceContext)]
+#[tokio::test]
+async fn test_export_search_streams_flights(ctx: &FlightServiceContext) -> Result<(), AppError> {
+    let first_date = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+    let second_date = NaiveDate::from_ymd_opt(2024, 2, 2).unwrap();
+    ctx.create_test_flight(241, "Winnipeg", "Saskatoon", first_date, 100)
+        .await?;
+    sqlx::query!(
+        "INSERT INTO flight (flight_number, flight_date, available_tickets) VALUES (?, ?, ?)",
+        241,
+        second_date,
+        100
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    let search_query = || FlightSearchQuery {
+        departure_city: "Winnipeg".to_string(),
+        destination_city: "Saskatoon".to_string(),
+        departure_date: first_date,
+        end_date: Some(second_date),
+        ..Default::default()
+    };
+    let rows =
+        collect_rows(|sink| ctx.flight_service.export_search(search_query(), sink)).await?;
+
+    // Flights come in date order, the fares of the route with the first one only
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].flight.flight_date, first_date);
+    assert_eq!(rows[1].flight.flight_date, second_date);
+    assert!(rows[0].fares.is_some());
+    assert!(rows[1].fares.is_none());
+
+    // The JSON response is built from the same rows
+    let result = ctx.flight_service.search_flights(search_query()).await?;
+    assert_eq!(result.flights.len(), 2);
+    assert_eq!(result.fares.len(), 1);
+    assert_eq!(result.fares[0].flight_number, 241);
+
+    Ok(())
+}
+
+#[test_context(FlightServiceContext)]
+#[tokio::test]
+async fn test_search_flights_codeshares(ctx: &FlightServiceContext) -> Result<(), AppError> {
+    let flight_date = NaiveDate::from_ymd_opt(2024, 2, 10).unwrap();
+    ctx.create_test_flight(251, "Halifax", "Calgary", flight_date, 100)
+        .await?;
+    for (carrier, number) in [("XY", 3251), ("QZ", 88)] {
+        sqlx::query!(
+            r#"
+            INSERT INTO codeshare (flight_number, marketing_carrier, marketing_flight_number)
+            VALUES (251, ?, ?)
+            "#,
+            carrier,
+            number
+        )
+        .execute(&ctx.pool)
+        .await?;
+    }
+    let search_query = |collapse_codeshares| FlightSearchQuery {
+        departure_city: "Halifax".to_string(),
+        destination_city: "Calgary".to_string(),
+        departure_date: flight_date,
+        collapse_codeshares,
+        ..Default::default()
+    };
+
+    // Collapsed by default: the flight once, listing the partner flight numbers
+    let result = ctx
+        .flight_service
+        .search_flights(search_query(None))
+        .await?;
+    assert_eq!(result.flights.len(), 1);
+    let codeshares: Vec<(String, i32)> = result.flights[0]
+        .codeshares
+        .iter()
+        .map(|codeshare| (codeshare.carrier_code.clone(), codeshare.flight_number))
+        .collect();
+    assert_eq!(
+        codeshares,
+        vec![("QZ".to_string(), 88), ("XY".to_string(), 3251)]
+    );
+
+    // Expanded: once per flight number, all for the same physical flight
+    let result = ctx
+        .flight_service
+        .search_flights(search_query(Some(false)))
+        .await?;
+    assert_eq!(result.flights.len(), 3);
+    assert!(result.flights[0].marketed_as.is_none());
+    assert_eq!(
+        result.flights[1]
+            .marketed_as
+            .as_ref()
+            .map(|m| m.flight_number),
+        Some(88)
+    );
+    assert!(result
+        .flights
+        .iter()
+        .all(|flight| flight.flight_id == result.flights[0].flight_id
+            && flight.codeshares.is_empty()));
+    // The route is priced once all the same
+    assert_eq!(result.fares.len(), 1);
+
+    Ok(())
+}
+
+#[test_context(FlightServiceContext)]
+#[tokio::test]
+async fn test_search_flights_follows_route_schedule(
+    ctx: &FlightServiceContext,
+) -> Result<(), AppError> {
+    let flight_number = 301;
+    // Monday
+    let start_date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let end_date = NaiveDate::from_ymd_opt(2024, 1, 4).unwrap();
+    ctx.create_test_flight(flight_number, "Montreal", "Halifax", start_date, 100)
+        .await?;
+
+    // Flights exist every day around the active period of the route
+    for day in [-1, 1, 2, 3, 4] {
+        sqlx::query!(
+            r#"
+            INSERT INTO flight (flight_number, flight_date, available_tickets)
+            VALUES (?, ?, 100)
+            "#,
+            flight_number,
+            start_date + chrono::Duration::days(day)
+        )
+        .execute(&ctx.pool)
+        .await?;
+    }
+
+    // Monday, Wednesday and Thursday until the end date
+    sqlx::query!(
+        r#"
+        UPDATE flight_route
+        SET end_date = ?, operating_days = '134'
+        WHERE flight_number = ?
+        "#,
+        end_date,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    let search_query = FlightSearchQuery {
+        departure_city: "Montreal".to_string(),
+        destination_city: "Halifax".to_string(),
+        departure_date: start_date - chrono::Duration::days(1),
+        end_date: Some(end_date + chrono::Duration::days(1)),
+        ..Default::default()
+    };
+
+    let result = ctx.flight_service.search_flights(search_query).await?;
+
+    let mut dates: Vec<NaiveDate> = result
+        .flights
+        .iter()
+        .map(|flight| flight.flight_date)
+        .collect();
+    dates.sort();
+    assert_eq!(
+        dates,
+        vec![
+            start_date,
+            NaiveDate::from_ymd_opt(2024, 1, 3).unwrap(),
+            end_date
+        ]
+    );
+
+    // A single date outside the schedule finds nothing
+    let search_query = FlightSearchQuery {
+        departure_city: "Montreal".to_string(),
+        destination_city: "Halifax".to_string(),
+        departure_date: NaiveDate::from_ymd_opt(2024, 1, 2).unwrap(),
+        end_date: None,
+        ..Default::default()
+    };
+    let result = ctx.flight_service.search_flights(search_query).await?;
+    assert!(result.flights.is_empty());
+
+    Ok(())
+}
+
+#[test_context(FlightServiceContext)]
+#[tokio::test]
+async fn test_search_flights_sorted_and_filtered(
+    ctx: &FlightServiceContext,
+) -> Result<(), AppError> {
+    let flight_date = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+    // Departure, arrival and base fare of each route
+    let routes = [
+        (261, (7, 0), (9, 30), 300),
+        (262, (12, 0), (13, 0), 150),
+        (263, (18, 0), (20, 0), 100),
+    ];
+    for (flight_number, departure, arrival, base_fare) in routes {
+        ctx.create_test_flight(flight_number, "Edmonton", "Kelowna", flight_date, 100)
+            .await?;
+        sqlx::query!(
+            r#"
+            UPDATE flight_route
+            SET departure_time = ?, arrival_time = ?, base_fare = ?
+            WHERE flight_number = ?
+            "#,
+            NaiveTime::from_hms_opt(departure.0, departure.1, 0).unwrap(),
+            NaiveTime::from_hms_opt(arrival.0, arrival.1, 0).unwrap(),
+            Decimal::from(base_fare),
+            flight_number
+        )
+        .execute(&ctx.pool)
+        .await?;
+    }
+
+    let search = |sort_by, departure_after, departure_before| {
+        let search_query = FlightSearchQuery {
+            departure_city: "Edmonton".to_string(),
+            destination_city: "Kelowna".to_string(),
+            departure_date: flight_date,
+            sort_by,
+            departure_after,
+            departure_before,
+            max_stops: Some(0),
+            ..Default::default()
+        };
+        async move {
+            let result = ctx.flight_service.search_flights(search_query).await?;
+            Ok::<Vec<i32>, AppError>(
+                result
+                    .flights
+                    .iter()
+                    .map(|flight| flight.flight_number)
+                    .collect(),
+            )
+        }
+    };
+
+    assert_eq!(search(None, None, None).await?, vec![261, 262, 263]);
+    assert_eq!(
+        search(Some(SearchSort::Price), None, None).await?,
+        vec![263, 262, 261]
+    );
+    assert_eq!(
+        search(Some(SearchSort::Duration), None, None).await?,
+        vec![262, 263, 261]
+    );
+
+    // Leaving between 11:00 and 18:00, both included
+    let after = NaiveTime::from_hms_opt(11, 0, 0);
+    let before = NaiveTime::from_hms_opt(18, 0, 0);
+    assert_eq!(search(None, after, before).await?, vec![262, 263]);
+
+    let result = search(None, before, after).await;
+    assert!(matches!(result, Err(AppError::BadRequest(_))));
+
+    // Pages of the results count every flight found
+    let page = |limit, offset| FlightSearchQuery {
+        departure_city: "Edmonton".to_string(),
+        destination_city: "Kelowna".to_string(),
+        departure_date: flight_date,
+        limit: Some(limit),
+        offset: Some(offset),
+        ..Default::default()
+    };
+    let result = ctx.flight_service.search_flights(page(2, 1)).await?;
+    assert_eq!(result.total, 3);
+    let flight_numbers: Vec<i32> = result
+        .flights
+        .iter()
+        .map(|flight| flight.flight_number)
+        .collect();
+    assert_eq!(flight_numbers, vec![262, 263]);
+    let result = ctx.flight_service.search_flights(page(2, 3)).await?;
+    assert_eq!((result.total, result.flights.len()), (3, 0));
+    let result = ctx.flight_service.search_flights(page(0, 0)).await;
+    assert!(matches!(result, Err(AppError::BadRequest(_))));
+
+    Ok(())
+}
+
+#[test_context(FlightServiceContext)]
+#[tokio::test]
+async fn test_search_flights_by_airport_code(ctx: &FlightServiceContext) -> Result<(), AppError> {
+    let departure_date = NaiveDate::from_ymd_opt(2024, 5, 2).unwrap();
+    ctx.create_test_flight(291, "Calgary", "Vancouver", departure_date, 100)
+        .await?;
+    sqlx::query!(
+        r#"
+        UPDATE flight_route
+        SET departure_airport = 'YYC', destination_airport = 'YVR'
+        WHERE flight_number = 291
+        "#
+    )
+    .execute(&ctx.pool)
+    .await?;
+    // Route without known airports
+    ctx.create_test_flight(292, "Calgary", "Vancouver", departure_date, 100)
+        .await?;
+
+    let search = |departure_city: &str, destination_city: &str| {
+        ctx.flight_service.search_flights(FlightSearchQuery {
+            departure_city: departure_city.to_string(),
+            destination_city: destination_city.to_string(),
+            departure_date,
+            ..Default::default()
+        })
+    };
+
+    // Codes and city names match whatever their case
+    for (departure_city, destination_city) in [
+        ("Calgary", "Vancouver"),
+        ("calgary", "VANCOUVER"),
+        ("YYC", "yvr"),
+        ("yyc", "Vancouver"),
+    ] {
+        let result = search(departure_city, destination_city).await?;
+        let flight_numbers: Vec<i32> = result
+            .flights
+            .iter()
+            .map(|flight| flight.flight_number)
+            .collect();
+        assert_eq!(flight_numbers, vec![291, 292], "{}", departure_city);
+        assert_eq!(result.flights[0].departure_airport.as_deref(), Some("YYC"));
+        assert_eq!(
+            result.flights[0].destination_airport.as_deref(),
+            Some("YVR")
+        );
+        assert_eq!(result.flights[1].departure_airport, None);
+    }
+
+    // Airports of other cities find nothing
+    let result = search("YEG", "YVR").await?;
+    assert!(result.flights.is_empty());
+
+    Ok(())
+}
+
+#[test_context(FlightServiceContext)]
+#[tokio::test]
+async fn test_search_flights_with_timezones(ctx: &FlightServiceContext) -> Result<(), AppError> {
+    let departure_date = NaiveDate::from_ymd_opt(2024, 7, 10).unwrap();
+    // Overnight flight landing three timezones east the next morning
+    ctx.create_test_flight(295, "Vancouver", "Toronto", departure_date, 100)
+        .await?;
+    sqlx::query!(
+        r#"
+        UPDATE flight_route
+        SET departure_airport = 'YVR', destination_airport = 'YYZ',
+            departure_time = '23:30:00', arrival_time = '07:15:00'
+        WHERE flight_number = 295
+        "#
+    )
+    .execute(&ctx.pool)
+    .await?;
+    // Route without known airports
+    ctx.create_test_flight(296, "Vancouver", "Toronto", departure_date, 100)
+        .await?;
+
+    let result = ctx
+        .flight_service
+        .search_flights(FlightSearchQuery {
+            departure_city: "Vancouver".to_string(),
+            destination_city: "Toronto".to_string(),
+            departure_date,
+            ..Default::default()
+        })
+        .await?;
+    // The route without airports leaves at 10:00, before the overnight flight
+    assert_eq!(result.flights.len(), 2);
+
+    let flight = &result.flights[1];
+    assert_eq!(flight.flight_number, 295);
+    assert_eq!(
+        flight.departure_at,
+        Some(DateTime::parse_from_rfc3339("2024-07-10T23:30:00-07:00").unwrap())
+    );
+    assert_eq!(
+        flight.arrival_at,
+        Some(DateTime::parse_from_rfc3339("2024-07-11T07:15:00-04:00").unwrap())
+    );
+    assert_eq!(flight.duration_minutes, Some(285));
+
+    let flight = &result.flights[0];
+    assert_eq!(flight.flight_number, 296);
+    assert_eq!(flight.departure_at, None);
+    assert_eq!(flight.duration_minutes, None);
+
+    Ok(())
+}
+
+#[test_context(FlightServiceContext)]
+#[tokio::test]
+async fn test_search_cache(ctx: &FlightServiceContext) -> Result<(), AppError> {
+    let flight_date = NaiveDate::from_ymd_opt(2024, 8, 14).unwrap();
+    ctx.create_test_flight(297, "Regina", "Saskatoon", flight_date, 10)
+        .await?;
+    let search_cache = SearchCache::new(SharedTunables::default());
+    let flight_service =
+        FlightService::new(ctx.pool.clone()).with_search_cache(search_cache.clone());
+    let query = FlightSearchQuery {
+        departure_city: "Regina".to_string(),
+        destination_city: "Saskatoon".to_string(),
+        departure_date: flight_date,
+        ..Default::default()
+    };
+
+    let result = flight_service.search_flights(query.clone()).await?;
+    assert_eq!(result.flights.len(), 1);
+    let flight_id = result.flights[0].flight_id;
+    let seats = flight_service.get_available_seats(297, flight_date).await?;
+    assert_eq!(seats.available_seats.len(), 10);
+
+    // Sold out and seated without the booking path, the cached answers stay
+    sqlx::query!(
+        "UPDATE flight SET available_tickets = 0 WHERE flight_id = ?",
+        flight_id
+    )
+    .execute(&ctx.pool)
+    .await?;
+    sqlx::query!(
+        "UPDATE seat_info SET seat_status = 'BOOKED' WHERE flight_id = ?",
+        flight_id
+    )
+    .execute(&ctx.pool)
+    .await?;
+    let result = flight_service.search_flights(query.clone()).await?;
+    assert_eq!(result.flights.len(), 1);
+    let seats = flight_service.get_available_seats(297, flight_date).await?;
+    assert_eq!(seats.available_seats.len(), 10);
+
+    // Until the flight is invalidated, as bookings do
+    search_cache.invalidate_flight(flight_id);
+    let result = flight_service.search_flights(query.clone()).await?;
+    assert!(result.flights.is_empty());
+    let seats = flight_service.get_available_seats(297, flight_date).await?;
+    assert!(seats.available_seats.is_empty());
+
+    // A TTL of 0 caches nothing
+    let uncached = FlightService::new(ctx.pool.clone()).with_search_cache(SearchCache::new(
+        SharedTunables::new(Tunables {
+            search_cache_ttl: Duration::ZERO,
+            ..Tunables::default()
+        }),
+    ));
+    uncached.search_flights(query.clone()).await?;
+    sqlx::query!(
+        "UPDATE flight SET available_tickets = 10 WHERE flight_id = ?",
+        flight_id
+    )
+    .execute(&ctx.pool)
+    .await?;
+    let result = uncached.search_flights(query).await?;
+    assert_eq!(result.flights.len(), 1);
+
+    Ok(())
+}
+
+#[test_context(FlightServiceContext)]
+#[tokio::test]
+async fn test_price_calendar(ctx: &FlightServiceContext) -> Result<(), AppError> {
+    let month = NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
+    let day = |day| NaiveDate::from_ymd_opt(2024, 4, day).unwrap();
+    // Route 281 leaves first but costs more
+    for (flight_number, departure, base_fare) in [(281, 7, 200), (282, 9, 120)] {
+        ctx.create_test_flight(flight_number, "Winnipeg", "Thunder Bay", day(10), 100)
+            .await?;
+        sqlx::query!(
+            "UPDATE flight_route SET departure_time = ?, base_fare = ? WHERE flight_number = ?",
+            NaiveTime::from_hms_opt(departure, 0, 0).unwrap(),
+            Decimal::from(base_fare),
+            flight_number
+        )
+        .execute(&ctx.pool)
+        .await?;
+    }
+    sqlx::query!(
+        r#"
+        INSERT INTO flight (flight_number, flight_date, available_tickets)
+        VALUES (281, ?, 100)
+        "#,
+        day(11)
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    let calendar = |sort_by| {
+        ctx.flight_service.price_calendar(PriceCalendarQuery {
+            departure_city: "Winnipeg".to_string(),
+            destination_city: "Thunder Bay".to_string(),
+            month,
+            language: None,
+            sort_by,
+        })
+    };
+
+    let result = calendar(None).await?;
+    assert_eq!(result.month, "2024-04");
+    assert_eq!(result.days.len(), 30);
+    let cheapest = &result.days[9];
+    assert_eq!((cheapest.date, cheapest.flights), (day(10), 2));
+    assert_eq!(cheapest.flight.as_ref().unwrap().flight_number, 282);
+    assert_eq!(
+        cheapest.lowest_fare.as_ref().unwrap().price,
+        Decimal::from(120)
+    );
+    let next_day = &result.days[10];
+    assert_eq!(next_day.flight.as_ref().unwrap().flight_number, 281);
+    assert_eq!(
+        next_day.lowest_fare.as_ref().unwrap().price,
+        Decimal::from(200)
+    );
+    assert!(result.days[11].flight.is_none());
+    assert_eq!(result.days[11].flights, 0);
+
+    let result = calendar(Some(SearchSort::DepartureTime)).await?;
+    assert_eq!(result.days[9].flight.as_ref().unwrap().flight_number, 281);
+
+    Ok(())
+}
+
+#[test_context(FlightServiceContext)]
+#[tokio::test]
+async fn test_search_flights_localized(ctx: &FlightServiceContext) -> Result<(), AppError> {
+    let departure_date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    ctx.create_test_flight(211, "Montreal", "Quebec City", departure_date, 100)
+        .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO location (city, language, name)
+        VALUES ('Montreal', 'fr', 'Montréal'), ('Quebec City', 'fr', 'Québec')
+        "#
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    // Search with French city names and ask for French results
+    let search_query = FlightSearchQuery {
+        departure_city: "montréal".to_string(),
+        destination_city: "Québec".to_string(),
+        departure_date,
+        end_date: None,
+        language: Some("fr".to_string()),
+        ..Default::default()
+    };
+
+    let result = ctx.flight_service.search_flights(search_query).await?;
+
+    assert_eq!(result.flights.len(), 1);
+    assert_eq!(result.flights[0].departure_city, "Montréal");
+    assert_eq!(result.flights[0].destination_city, "Québec");
+
+    Ok(())
+}
+
 #[test_context(FlightServiceContext)]
 #[tokio::test]
 async fn test_get_available_seats(ctx: &FlightServiceContext) -> Result<(), AppError> {
@@ -224,6 +803,13 @@ async fn test_get_available_seats(ctx: &FlightServiceContext) -> Result<(), AppE
         assert!(result.available_seats.contains(&i));
     }
 
+    // Seats are laid out 6 abreast by default
+    assert_eq!(result.seats.len(), 10);
+    assert_eq!(result.seats[0].position, SeatPosition::Window);
+    assert_eq!(result.seats[1].position, SeatPosition::Middle);
+    assert_eq!(result.seats[2].position, SeatPosition::Aisle);
+    assert_eq!(result.seats[6].row, 2);
+
     Ok(())
 }
 
@@ -323,5 +909,139 @@ async fn test_get_available_seats_with_mixed_status(ctx: &FlightServiceContext)
         );
     }
 
+    // The full seat map also shows the booked seats, but not the blocked ones
+    assert_eq!(result.occupied_seats, Some(vec![2, 4, 6]));
+
+    // Operators can hide the occupied seats from passengers, admins still see them
+    let private_service =
+        FlightService::new(ctx.pool.clone()).with_tunables(SharedTunables::new(Tunables {
+            seat_map_view: SeatMapView::AvailabilityOnly,
+            ..Tunables::default()
+        }));
+    let result = private_service
+        .get_available_seats(flight_number, flight_date)
+        .await?;
+    assert_eq!(result.available_seats.len(), 5);
+    assert_eq!(result.occupied_seats, None);
+    let result = private_service
+        .get_seat_map(flight_number, flight_date, SeatMapView::Full)
+        .await?;
+    assert_eq!(result.occupied_seats, Some(vec![2, 4, 6]));
+
+    // The SVG seat map greys out every seat that is not available
+    let svg = ctx.flight_service.seat_map_svg(flight.flight_id).await?;
+    assert_eq!(svg.content_type().to_string(), "image/svg+xml");
+    let svg = String::from_utf8(svg.bytes().to_vec()).unwrap();
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.contains(&format!("Flight {} on 2024-01-01", flight_number)));
+    assert_eq!(svg.matches("<title>Seat ").count(), 10);
+    assert_eq!(svg.matches(r##"fill="#bdbdbd"><title>"##).count(), 5);
+
+    let result = ctx.flight_service.seat_map_svg(-1).await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    Ok(())
+}
+#[test_context(FlightServiceContext)]
+#[tokio::test]
+async fn test_recent_flights(ctx: &FlightServiceContext) -> Result<(), AppError> {
+    let mut user_ids = Vec::new();
+    for username in ["recent_flights_user", "recent_flights_other"] {
+        let user_id = sqlx::query!(
+            "INSERT INTO user (username, password, role) VALUES (?, ?, ?)",
+            username,
+            "not_a_password_hash",
+            "USER"
+        )
+        .execute(&ctx.pool)
+        .await?
+        .last_insert_id() as i32;
+        user_ids.push(user_id);
+    }
+    let (user_id, other_user_id) = (user_ids[0], user_ids[1]);
+
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 1).unwrap();
+    let flight_numbers: Vec<i32> = (311..=322).collect();
+    for &flight_number in &flight_numbers {
+        ctx.create_test_flight(flight_number, "Toronto", "Ottawa", flight_date, 10)
+            .await?;
+        ctx.flight_service
+            .record_flight_view(user_id, flight_number, flight_date)
+            .await?;
+    }
+    // Flights that do not exist are not recorded
+    ctx.flight_service
+        .record_flight_view(user_id, 399, flight_date)
+        .await?;
+    // Viewing a flight again makes it the most recent
+    ctx.flight_service
+        .record_flight_view(user_id, 315, flight_date)
+        .await?;
+
+    let recent = ctx.flight_service.get_recent_flights(user_id).await?;
+    let viewed: Vec<i32> = recent
+        .flights
+        .iter()
+        .map(|flight| flight.flight_number)
+        .collect();
+    // Only the most recent views are kept, most recent first
+    assert_eq!(
+        viewed,
+        vec![315, 322, 321, 320, 319, 318, 317, 316, 314, 313]
+    );
+    assert_eq!(recent.flights[0].flight_date, flight_date);
+    assert_eq!(recent.flights[0].departure_city, "Toronto");
+
+    let kept = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM flight_view WHERE user_id = ?",
+        user_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(kept, RECENT_FLIGHTS_LIMIT);
+
+    // Views are per user
+    let recent = ctx.flight_service.get_recent_flights(other_user_id).await?;
+    assert!(recent.flights.is_empty());
+
     Ok(())
-}
\ No newline at end of file
+}
+
+#[test_context(FlightServiceContext)]
+#[tokio::test]
+async fn test_flight_ref_exists_check(ctx: &FlightServiceContext) -> Result<(), AppError> {
+    let flight_date = NaiveDate::from_ymd_opt(2024, 1, 5).unwrap();
+    ctx.create_test_flight(331, "Toronto", "Ottawa", flight_date, 10)
+        .await?;
+
+    let flight = FlightRef {
+        flight_number: 331,
+        flight_date: "2024-01-05".to_string(),
+    };
+    let flight_id = flight.flight_id(&ctx.flight_service).await?;
+    assert_eq!(
+        flight_id,
+        ctx.flight_service.resolve_flight(331, flight_date).await?
+    );
+
+    let no_flight = FlightRef {
+        flight_date: "2024-01-06".to_string(),
+        ..flight.clone()
+    };
+    assert!(matches!(
+        no_flight.flight_id(&ctx.flight_service).await,
+        Err(AppError::NotFound(_))
+    ));
+
+    // The date is checked before the flight is looked up
+    let bad_date = FlightRef {
+        flight_date: "2024-01-32".to_string(),
+        ..flight
+    };
+    assert!(matches!(
+        bad_date.flight_id(&ctx.flight_service).await,
+        Err(AppError::BadRequest(_))
+    ));
+
+    Ok(())
+}
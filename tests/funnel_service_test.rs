@@ -0,0 +1,92 @@
+use airline_booking_system::{
+    models::funnel::FunnelStep, services::funnel_service::FunnelService, utils::error::AppError,
+};
+use async_trait::async_trait;
+use ctor::dtor;
+use sqlx::mysql::MySqlPool as Pool;
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct FunnelServiceContext {
+    pool: Pool,
+    funnel_service: FunnelService,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for FunnelServiceContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+
+        let funnel_service = FunnelService::new(pool.clone());
+
+        FunnelServiceContext {
+            pool,
+            funnel_service,
+        }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+#[test_context(FunnelServiceContext)]
+#[tokio::test]
+async fn test_conversion_report(ctx: &FunnelServiceContext) -> Result<(), AppError> {
+    // Four sessions search, two view a seat map, one of them books
+    for session_id in ["session-a", "session-b", "session-c", "session-d"] {
+        ctx.funnel_service
+            .record(session_id, FunnelStep::Search, None)
+            .await?;
+    }
+    // Searching again in the same session does not count twice
+    ctx.funnel_service
+        .record("session-a", FunnelStep::Search, None)
+        .await?;
+    for session_id in ["session-a", "session-b"] {
+        ctx.funnel_service
+            .record(session_id, FunnelStep::SeatMapViewed, Some(101))
+            .await?;
+    }
+    ctx.funnel_service
+        .record("session-a", FunnelStep::BookingAttempted, Some(101))
+        .await?;
+    ctx.funnel_service
+        .record("session-a", FunnelStep::BookingConfirmed, Some(101))
+        .await?;
+
+    let report = ctx.funnel_service.conversion_report(7).await?;
+
+    let sessions: Vec<(FunnelStep, i64)> = report
+        .steps
+        .iter()
+        .map(|step| (step.step, step.sessions))
+        .collect();
+    assert_eq!(
+        sessions,
+        vec![
+            (FunnelStep::Search, 4),
+            (FunnelStep::SeatMapViewed, 2),
+            (FunnelStep::BookingAttempted, 1),
+            (FunnelStep::BookingConfirmed, 1),
+        ]
+    );
+    assert_eq!(report.steps[0].conversion_rate, None);
+    assert_eq!(report.steps[1].conversion_rate, Some(0.5));
+    assert_eq!(report.overall_conversion_rate, Some(0.25));
+
+    Ok(())
+}
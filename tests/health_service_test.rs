@@ -0,0 +1,90 @@
+use airline_booking_system::models::health::CheckStatus;
+use airline_booking_system::services::event_bus::EventBus;
+use airline_booking_system::services::health_service::HealthService;
+use async_trait::async_trait;
+use ctor::dtor;
+use sqlx::mysql::MySqlPool as Pool;
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct HealthServiceContext {
+    pool: Pool,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for HealthServiceContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+
+        HealthServiceContext { pool }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+#[test_context(HealthServiceContext)]
+#[tokio::test]
+async fn test_health_check(ctx: &HealthServiceContext) {
+    let health = HealthService::new(ctx.pool.clone()).check().await;
+    assert_eq!(health.status, "ok");
+    assert!(health.database);
+    assert!(health.database_latency_ms.is_some());
+    assert_eq!(health.build.version, env!("CARGO_PKG_VERSION"));
+
+    // A pool that cannot reach the database reports it instead of failing
+    let closed_pool = Pool::connect_lazy(&std::env::var("ADMIN_DATABASE_URL").unwrap()).unwrap();
+    closed_pool.close().await;
+    let health = HealthService::new(closed_pool).check().await;
+    assert_eq!(health.status, "unavailable");
+    assert!(!health.database);
+    assert!(health.database_latency_ms.is_none());
+}
+
+#[test_context(HealthServiceContext)]
+#[tokio::test]
+async fn test_diagnostics(ctx: &HealthServiceContext) {
+    let health_service = HealthService::new(ctx.pool.clone()).with_event_bus(EventBus::new());
+    let diagnostics = health_service.diagnostics().await;
+    let status = |name: &str| {
+        diagnostics
+            .checks
+            .iter()
+            .find(|check| check.name == name)
+            .unwrap_or_else(|| panic!("Check {} missing", name))
+            .status
+    };
+    assert_eq!(diagnostics.status, CheckStatus::Pass);
+    assert_eq!(status("database_pool"), CheckStatus::Pass);
+    assert_eq!(status("job_backlog"), CheckStatus::Pass);
+    assert_eq!(status("event_backlog"), CheckStatus::Pass);
+    // No replica or external cache in the test setup
+    assert_eq!(status("replication_lag"), CheckStatus::Skipped);
+    assert_eq!(status("cache"), CheckStatus::Skipped);
+
+    let closed_pool = Pool::connect_lazy(&std::env::var("ADMIN_DATABASE_URL").unwrap()).unwrap();
+    closed_pool.close().await;
+    let diagnostics = HealthService::new(closed_pool).diagnostics().await;
+    assert_eq!(diagnostics.status, CheckStatus::Fail);
+    let failed: Vec<&str> = diagnostics
+        .checks
+        .iter()
+        .filter(|check| check.status == CheckStatus::Fail)
+        .map(|check| check.name.as_str())
+        .collect();
+    assert_eq!(failed, vec!["database_pool", "job_backlog"]);
+}
@@ -0,0 +1,149 @@
+use airline_booking_system::utils::{
+    config::AuthConfig,
+    jwt::{self, Claims},
+};
+use jsonwebtoken::{decode_header, encode, EncodingKey, Header};
+use rocket::http::{Header as RocketHeader, Status};
+use rocket::local::asynchronous::Client;
+
+const CURRENT_SECRET: &str = "current-secret";
+const PREVIOUS_SECRET: &str = "previous-secret";
+
+fn configure() {
+    jwt::configure(AuthConfig {
+        jwt_secret: CURRENT_SECRET.to_string(),
+        jwt_previous_secrets: vec![PREVIOUS_SECRET.to_string()],
+        ..Default::default()
+    });
+}
+
+// Login token of the user signed with the secret, as an earlier server would have
+fn token_signed_with(secret: &str, kid: Option<String>, user_id: i32) -> String {
+    let claims = Claims {
+        sub: user_id,
+        exp: (chrono::Utc::now() + chrono::Duration::hours(1)).timestamp() as usize,
+        role: "USER".to_string(),
+        aud: None,
+    };
+    let header = Header {
+        kid,
+        ..Header::default()
+    };
+    encode(
+        &header,
+        &claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_new_tokens_name_the_current_secret() {
+    configure();
+    let token = jwt::generate_token(7, "USER").unwrap();
+
+    let header = decode_header(&token).unwrap();
+    assert_eq!(header.kid, Some(jwt::key_id(CURRENT_SECRET)));
+    assert_eq!(jwt::decode_token(&token).map(|claims| claims.sub), Some(7));
+}
+
+#[test]
+fn test_tokens_of_previous_secret_still_accepted() {
+    configure();
+    let with_kid = token_signed_with(PREVIOUS_SECRET, Some(jwt::key_id(PREVIOUS_SECRET)), 8);
+    assert_eq!(
+        jwt::decode_token(&with_kid).map(|claims| claims.sub),
+        Some(8)
+    );
+
+    // Issued before tokens had key ids
+    let without_kid = token_signed_with(PREVIOUS_SECRET, None, 9);
+    assert_eq!(
+        jwt::decode_token(&without_kid).map(|claims| claims.sub),
+        Some(9)
+    );
+}
+
+#[test]
+fn test_tokens_of_unknown_secret_rejected() {
+    configure();
+    let unknown = token_signed_with("retired-secret", None, 10);
+    assert!(jwt::decode_token(&unknown).is_none());
+
+    let unknown_kid = token_signed_with("retired-secret", Some(jwt::key_id("retired-secret")), 10);
+    assert!(jwt::decode_token(&unknown_kid).is_none());
+
+    // The kid must name the secret the token was signed with
+    let wrong_kid = token_signed_with(PREVIOUS_SECRET, Some(jwt::key_id(CURRENT_SECRET)), 10);
+    assert!(jwt::decode_token(&wrong_kid).is_none());
+}
+
+#[test]
+fn test_support_codes_do_not_log_in() {
+    configure();
+    let expires_at = chrono::Utc::now().naive_utc() + chrono::Duration::minutes(10);
+    let code = jwt::generate_support_token(11, expires_at).unwrap();
+
+    assert!(jwt::decode_token(&code).is_none());
+    let claims = jwt::decode_support_token(&code, jwt::SUPPORT_SCOPE_BOOKINGS_READ).unwrap();
+    assert_eq!(claims.sub, 11);
+}
+
+#[rocket::get("/admin")]
+fn admin_only(admin: jwt::AdminUser) -> String {
+    admin.user_id.to_string()
+}
+
+async fn admin_status(token: Option<String>) -> Status {
+    let rocket = rocket::build().mount("/", rocket::routes![admin_only]);
+    let client = Client::tracked(rocket).await.expect("valid rocket");
+    let mut request = client.get("/admin");
+    if let Some(token) = token {
+        request = request.header(RocketHeader::new(
+            "Authorization",
+            format!("Bearer {}", token),
+        ));
+    }
+    request.dispatch().await.status()
+}
+
+#[test]
+fn test_tokens_carry_the_role() {
+    configure();
+    let token = jwt::generate_token(12, "ADMIN").unwrap();
+    assert_eq!(
+        jwt::decode_token(&token).map(|claims| claims.role),
+        Some("ADMIN".to_string())
+    );
+}
+
+#[rocket::async_test]
+async fn test_admin_guard() {
+    configure();
+    assert_eq!(
+        admin_status(Some(jwt::generate_token(13, "ADMIN").unwrap())).await,
+        Status::Ok
+    );
+    assert_eq!(
+        admin_status(Some(jwt::generate_token(14, "USER").unwrap())).await,
+        Status::Forbidden
+    );
+    assert_eq!(admin_status(None).await, Status::Unauthorized);
+
+    // Issued before tokens had a role
+    let claims = serde_json::json!({
+        "sub": 15,
+        "exp": (chrono::Utc::now() + chrono::Duration::hours(1)).timestamp(),
+    });
+    let header = Header {
+        kid: Some(jwt::key_id(CURRENT_SECRET)),
+        ..Header::default()
+    };
+    let without_role = encode(
+        &header,
+        &claims,
+        &EncodingKey::from_secret(CURRENT_SECRET.as_bytes()),
+    )
+    .unwrap();
+    assert_eq!(admin_status(Some(without_role)).await, Status::Forbidden);
+}
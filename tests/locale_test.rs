@@ -0,0 +1,41 @@
+use airline_booking_system::utils::locale::{parse_accept_language, DocumentLocale};
+use chrono::{NaiveDate, NaiveTime};
+
+#[test]
+fn test_parse_accept_language() {
+    assert_eq!(
+        parse_accept_language("fr-CA,fr;q=0.9,en;q=0.8"),
+        Some("fr".to_string())
+    );
+    assert_eq!(
+        parse_accept_language("en;q=0.5,de;q=0.7"),
+        Some("de".to_string())
+    );
+    assert_eq!(parse_accept_language("*"), None);
+}
+
+#[test]
+fn test_document_locale_formats() {
+    let date = NaiveDate::from_ymd_opt(2024, 8, 3).unwrap();
+    let time = NaiveTime::from_hms_opt(9, 5, 0).unwrap();
+
+    let cases = [
+        (Some("en"), "3 Aug 2024", "09:05"),
+        (Some("fr"), "3 août 2024", "09 h 05"),
+        (Some("de"), "3. Aug. 2024", "09:05"),
+        (Some("es"), "3 ago 2024", "09:05"),
+        // Languages without a format fall back to English
+        (Some("ja"), "3 Aug 2024", "09:05"),
+        (None, "3 Aug 2024", "09:05"),
+    ];
+    for (language, expected_date, expected_time) in cases {
+        let locale = DocumentLocale::from_language(language);
+        assert_eq!(locale.date(date), expected_date, "{:?}", language);
+        assert_eq!(locale.time(time), expected_time, "{:?}", language);
+    }
+
+    assert_eq!(
+        DocumentLocale::Fr.date_time(date.and_time(time)),
+        "3 août 2024 09 h 05"
+    );
+}
@@ -0,0 +1,275 @@
+use airline_booking_system::{
+    models::{
+        loyalty::{earned_points, points_for_amount, LoyaltyEntryType, LOYALTY_PROVIDER},
+        payment::{ConfirmPaymentRequest, PaymentStatus, RefundStatus},
+        ticket::{FlightBookingRequest, TicketBookingRequest, TicketBookingResponse},
+        user::{Role, UserRegistrationRequest},
+    },
+    services::{
+        loyalty_service::{self, LedgerChange, LoyaltyService},
+        payment_service::{MockPaymentProvider, PaymentService},
+        ticket_service::TicketService,
+        user_service::UserService,
+    },
+    utils::error::AppError,
+};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use ctor::dtor;
+use sqlx::mysql::MySqlPool as Pool;
+use std::sync::Arc;
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct LoyaltyContext {
+    pool: Pool,
+    loyalty_service: LoyaltyService,
+    payment_service: PaymentService,
+    ticket_service: TicketService,
+    user_service: UserService,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for LoyaltyContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+
+        LoyaltyContext {
+            loyalty_service: LoyaltyService::new(pool.clone()),
+            payment_service: PaymentService::new(pool.clone(), Arc::new(MockPaymentProvider)),
+            ticket_service: TicketService::new(pool.clone()),
+            user_service: UserService::new(pool.clone()),
+            pool,
+        }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+impl LoyaltyContext {
+    // Helper method to register a user booking a flight with a base fare of 250.00
+    async fn book_flight(
+        &self,
+        flight_number: i32,
+        username: &str,
+    ) -> Result<(i32, TicketBookingResponse), AppError> {
+        let flight_date = (chrono::Utc::now() + chrono::Duration::days(10)).date_naive();
+        sqlx::query!(
+            "INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 5)",
+            flight_number
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO flight_route
+            (flight_number, departure_city, destination_city, departure_time, arrival_time,
+                aircraft_id, overbooking, start_date, end_date, base_fare)
+            VALUES
+            (?, 'Winnipeg', 'Toronto', '06:00:00', '09:30:00', ?, 0.00, ?, ?, 250.00)
+            "#,
+            flight_number,
+            flight_number,
+            flight_date,
+            flight_date
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+            VALUES (?, ?, 5, 1)
+            "#,
+            flight_number,
+            flight_date
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let user_id = self
+            .user_service
+            .register_user(UserRegistrationRequest {
+                username: username.to_string(),
+                password: "test_password".to_string(),
+                role: Role::User,
+                name: "Loyalty Test User".to_string(),
+                birth_date: NaiveDate::from_ymd_opt(1985, 6, 1).unwrap(),
+                gender: "female".to_string(),
+                email: None,
+            })
+            .await?;
+        let response = self
+            .ticket_service
+            .book_ticket(
+                user_id,
+                TicketBookingRequest {
+                    flights: vec![FlightBookingRequest {
+                        flight_number,
+                        flight_date,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            )
+            .await?;
+        Ok((user_id, response))
+    }
+
+    async fn depart(&self, flight_number: i32) -> Result<(), AppError> {
+        sqlx::query!(
+            "UPDATE flight SET status = 'DEPARTED' WHERE flight_number = ?",
+            flight_number
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn give_points(&self, customer_id: i32, points: i32) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        loyalty_service::add_entry(
+            &mut tx,
+            LedgerChange {
+                customer_id,
+                entry_type: LoyaltyEntryType::Earn,
+                points,
+                ticket_id: None,
+                booking_id: None,
+                description: "Welcome bonus".to_string(),
+            },
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[test_context(LoyaltyContext)]
+#[tokio::test]
+async fn test_points_accrue_for_flown_tickets(ctx: &LoyaltyContext) -> Result<(), AppError> {
+    let (user_id, booking) = ctx.book_flight(1501, "loyalty_accrual_user").await?;
+    ctx.payment_service
+        .confirm_payment(
+            user_id,
+            booking.booking_id,
+            ConfirmPaymentRequest {
+                payment_token: "tok_visa".to_string(),
+            },
+        )
+        .await?;
+
+    // Nothing is earned before departure
+    ctx.loyalty_service.accrue_completed_flights().await?;
+    assert_eq!(ctx.loyalty_service.balance(user_id).await?.balance, 0);
+
+    ctx.depart(1501).await?;
+    ctx.loyalty_service.accrue_completed_flights().await?;
+    let leg = &booking.flight_bookings[0];
+    let expected = earned_points(leg.fare_class, leg.price);
+    assert!(expected > 0);
+
+    let balance = ctx.loyalty_service.balance(user_id).await?;
+    assert_eq!(balance.balance, expected);
+    assert_eq!(balance.recent_entries.len(), 1);
+    assert_eq!(balance.recent_entries[0].entry_type, LoyaltyEntryType::Earn);
+    assert_eq!(balance.recent_entries[0].ticket_id, Some(leg.ticket_id));
+
+    // Each ticket earns once
+    ctx.loyalty_service.accrue_completed_flights().await?;
+    assert_eq!(
+        ctx.loyalty_service.balance(user_id).await?.balance,
+        expected
+    );
+
+    Ok(())
+}
+
+#[test_context(LoyaltyContext)]
+#[tokio::test]
+async fn test_pay_with_points(ctx: &LoyaltyContext) -> Result<(), AppError> {
+    let (user_id, booking) = ctx.book_flight(1502, "loyalty_payment_user").await?;
+    let amount = booking.payment.as_ref().expect("the fare is due").amount;
+    let points = points_for_amount(amount);
+
+    let result = ctx
+        .payment_service
+        .pay_with_points(user_id, booking.booking_id)
+        .await;
+    assert!(matches!(result, Err(AppError::Unprocessable(_))));
+
+    ctx.give_points(user_id, points + 100).await?;
+    let payment = ctx
+        .payment_service
+        .pay_with_points(user_id, booking.booking_id)
+        .await?;
+    assert_eq!(payment.status, PaymentStatus::Captured);
+
+    let balance = ctx.loyalty_service.balance(user_id).await?;
+    assert_eq!(balance.balance, 100);
+    assert_eq!(
+        balance.recent_entries[0].entry_type,
+        LoyaltyEntryType::Redeem
+    );
+    assert_eq!(balance.recent_entries[0].points, -points);
+
+    let provider = sqlx::query_scalar!(
+        "SELECT provider FROM payment WHERE id = ?",
+        payment.payment_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(provider.as_deref(), Some(LOYALTY_PROVIDER));
+
+    // Fares paid with points earn no points
+    ctx.depart(1502).await?;
+    ctx.loyalty_service.accrue_completed_flights().await?;
+    assert_eq!(ctx.loyalty_service.balance(user_id).await?.balance, 100);
+
+    Ok(())
+}
+
+#[test_context(LoyaltyContext)]
+#[tokio::test]
+async fn test_refund_of_points_payment(ctx: &LoyaltyContext) -> Result<(), AppError> {
+    let (user_id, booking) = ctx.book_flight(1503, "loyalty_refund_user").await?;
+    let amount = booking.payment.as_ref().expect("the fare is due").amount;
+    ctx.give_points(user_id, points_for_amount(amount)).await?;
+    ctx.payment_service
+        .pay_with_points(user_id, booking.booking_id)
+        .await?;
+
+    let cancellation = ctx
+        .payment_service
+        .cancel_ticket(
+            &ctx.ticket_service,
+            user_id,
+            &booking.flight_bookings[0].booking_reference,
+        )
+        .await?;
+    let refund = cancellation.refund.expect("paid tickets are refunded");
+    assert_eq!(refund.status, RefundStatus::Refunded);
+
+    let balance = ctx.loyalty_service.balance(user_id).await?;
+    assert_eq!(balance.balance, points_for_amount(refund.amount));
+    assert_eq!(
+        balance.recent_entries[0].entry_type,
+        LoyaltyEntryType::Refund
+    );
+
+    Ok(())
+}
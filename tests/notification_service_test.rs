@@ -0,0 +1,256 @@
+use airline_booking_system::{
+    models::{
+        flight::FlightStatus,
+        ticket::{FlightBookingRequest, TicketBookingRequest},
+        user::{Role, UserRegistrationRequest},
+    },
+    services::{
+        event_bus::{DomainEvent, EventBus},
+        notification_service::{EmailMessage, EmailTransport, NotificationService},
+        outbox::OutboxDispatcher,
+        ticket_service::TicketService,
+        user_service::UserService,
+    },
+    utils::error::AppError,
+};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use ctor::dtor;
+use sqlx::mysql::MySqlPool as Pool;
+use std::sync::{Arc, Mutex};
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+// Keeps the emails instead of sending them
+#[derive(Default)]
+struct RecordingTransport {
+    sent: Mutex<Vec<EmailMessage>>,
+}
+
+impl RecordingTransport {
+    fn take(&self) -> Vec<EmailMessage> {
+        std::mem::take(&mut *self.sent.lock().unwrap())
+    }
+}
+
+#[rocket::async_trait]
+impl EmailTransport for RecordingTransport {
+    fn name(&self) -> &'static str {
+        "recording"
+    }
+
+    async fn send(&self, message: &EmailMessage) -> Result<(), String> {
+        self.sent.lock().unwrap().push(message.clone());
+        Ok(())
+    }
+}
+
+struct NotificationServiceContext {
+    pool: Pool,
+    event_bus: EventBus,
+    outbox: OutboxDispatcher,
+    transport: Arc<RecordingTransport>,
+    notification_service: NotificationService,
+    ticket_service: TicketService,
+    user_service: UserService,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for NotificationServiceContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+
+        let event_bus = EventBus::new();
+        let transport = Arc::new(RecordingTransport::default());
+        let notification_service = NotificationService::new(pool.clone(), transport.clone());
+        let outbox = OutboxDispatcher::new(pool.clone(), event_bus.clone());
+        let ticket_service = TicketService::new(pool.clone());
+        let user_service = UserService::new(pool.clone());
+
+        NotificationServiceContext {
+            pool,
+            event_bus,
+            outbox,
+            transport,
+            notification_service,
+            ticket_service,
+            user_service,
+        }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+async fn setup_flight(
+    ctx: &NotificationServiceContext,
+    flight_number: i32,
+    flight_date: NaiveDate,
+) -> Result<i32, AppError> {
+    sqlx::query!(
+        "INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 10)",
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, overbooking, start_date, end_date)
+        VALUES
+        (?, 'Toronto', 'Paris', '18:30:00', '07:45:00', ?, 0.00, ?, ?)
+        "#,
+        flight_number,
+        flight_number,
+        flight_date,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?;
+    let flight_id = sqlx::query!(
+        r#"
+        INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+        VALUES (?, ?, 10, 1)
+        "#,
+        flight_number,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?
+    .last_insert_id() as i32;
+    for seat_number in 1..=10 {
+        sqlx::query!(
+            r#"
+            INSERT INTO seat_info (flight_id, seat_number, seat_status, version)
+            VALUES (?, ?, 'AVAILABLE', 0)
+            "#,
+            flight_id,
+            seat_number
+        )
+        .execute(&ctx.pool)
+        .await?;
+    }
+    Ok(flight_id)
+}
+
+async fn register(
+    ctx: &NotificationServiceContext,
+    username: &str,
+    email: Option<&str>,
+) -> Result<i32, AppError> {
+    let user_id = ctx
+        .user_service
+        .register_user(UserRegistrationRequest {
+            username: username.to_string(),
+            password: "test_password".to_string(),
+            role: Role::User,
+            name: "Grace Hopper".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+            gender: "female".to_string(),
+            email: email.map(str::to_string),
+        })
+        .await?;
+    sqlx::query!(
+        "UPDATE customer_info SET email_verified = email IS NOT NULL WHERE id = ?",
+        user_id
+    )
+    .execute(&ctx.pool)
+    .await?;
+    Ok(user_id)
+}
+
+// Notify about every event committed so far, returning the number of emails sent
+async fn notify_published(
+    ctx: &NotificationServiceContext,
+    receiver: &mut tokio::sync::broadcast::Receiver<DomainEvent>,
+) -> Result<usize, AppError> {
+    ctx.outbox.dispatch_pending().await?;
+    let mut sent = 0;
+    while let Ok(event) = receiver.try_recv() {
+        sent += ctx.notification_service.notify(&event).await?;
+    }
+    Ok(sent)
+}
+
+#[test_context(NotificationServiceContext)]
+#[tokio::test]
+async fn test_booking_and_flight_notifications(
+    ctx: &NotificationServiceContext,
+) -> Result<(), AppError> {
+    let flight_number = 101;
+    let flight_date = NaiveDate::from_ymd_opt(2025, 3, 14).unwrap();
+    let flight_id = setup_flight(ctx, flight_number, flight_date).await?;
+    let mut receiver = ctx.event_bus.subscribe();
+
+    // Only the passenger with a verified address gets emails
+    let notified = register(ctx, "notified_user", Some("grace@example.com")).await?;
+    let silent = register(ctx, "silent_user", None).await?;
+    for user_id in [notified, silent] {
+        ctx.ticket_service
+            .book_ticket(
+                user_id,
+                TicketBookingRequest {
+                    flights: vec![FlightBookingRequest {
+                        flight_number,
+                        flight_date,
+                        preferred_seat: None,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            )
+            .await?;
+    }
+
+    assert_eq!(notify_published(ctx, &mut receiver).await?, 1);
+    let confirmation = ctx.transport.take().remove(0);
+    assert_eq!(confirmation.to_address, "grace@example.com");
+    assert!(confirmation.subject.starts_with("Booking confirmed"));
+    assert!(confirmation.body.contains("Toronto to Paris"));
+
+    // A delay alert gives the new departure time
+    let delayed = |previous_delay_minutes, delay_minutes| DomainEvent::FlightStatusChanged {
+        flight_id,
+        flight_number,
+        flight_date,
+        previous_status: FlightStatus::Scheduled,
+        status: FlightStatus::Delayed,
+        previous_delay_minutes,
+        delay_minutes,
+        reason: Some("Weather".to_string()),
+    };
+    assert_eq!(ctx.notification_service.notify(&delayed(0, 45)).await?, 1);
+    let alert = ctx.transport.take().remove(0);
+    assert!(alert.body.contains("delayed by 45 minutes"));
+    assert!(alert.body.contains("19:15"));
+    assert!(alert.body.contains("Weather"));
+
+    // A shorter delay is no news
+    assert_eq!(ctx.notification_service.notify(&delayed(45, 20)).await?, 0);
+
+    // Cancelling the ticket sends a notice
+    let ticket_id = sqlx::query_scalar!("SELECT id FROM ticket WHERE customer_id = ?", notified)
+        .fetch_one(&ctx.pool)
+        .await?;
+    ctx.ticket_service.release_ticket(ticket_id).await?;
+    assert_eq!(notify_published(ctx, &mut receiver).await?, 1);
+    let notice = ctx.transport.take().remove(0);
+    assert!(notice.subject.starts_with("Booking cancelled"));
+
+    Ok(())
+}
@@ -0,0 +1,312 @@
+use airline_booking_system::{
+    models::{
+        payment::ConfirmPaymentRequest,
+        ticket::{BookingStatus, FlightBookingRequest, TicketBookingRequest},
+        user::{Role, UserRegistrationRequest},
+    },
+    services::{
+        operation_log::{self, Operation, OperationLog, OperationOutcome},
+        payment_service::{MockPaymentProvider, PaymentService},
+        ticket_service::TicketService,
+        user_service::UserService,
+    },
+    utils::error::AppError,
+};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use ctor::dtor;
+use sqlx::mysql::MySqlPool as Pool;
+use std::path::PathBuf;
+use std::sync::Arc;
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct OperationLogContext {
+    pool: Pool,
+    log_path: PathBuf,
+    ticket_service: TicketService,
+    payment_service: PaymentService,
+    user_service: UserService,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for OperationLogContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+
+        let log_path =
+            std::env::temp_dir().join(format!("operation_log_{}.jsonl", uuid::Uuid::new_v4()));
+        let operation_log = OperationLog::open(&log_path)
+            .await
+            .expect("Failed to open operation log");
+
+        let ticket_service =
+            TicketService::new(pool.clone()).with_operation_log(operation_log.clone());
+        let payment_service = PaymentService::new(pool.clone(), Arc::new(MockPaymentProvider))
+            .with_operation_log(operation_log);
+        let user_service = UserService::new(pool.clone());
+
+        OperationLogContext {
+            pool,
+            log_path,
+            ticket_service,
+            payment_service,
+            user_service,
+        }
+    }
+
+    async fn teardown(self) {
+        let _ = std::fs::remove_file(&self.log_path);
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+#[test_context(OperationLogContext)]
+#[tokio::test]
+async fn test_replay_recovers_bookings(ctx: &OperationLogContext) -> Result<(), AppError> {
+    let flight_number = 701;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 26).unwrap();
+
+    sqlx::query!(
+        r#"INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 5)"#,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, overbooking, start_date, end_date)
+        VALUES
+        (?, 'New York', 'London', '10:00:00', '22:00:00',
+            ?, 0.00, ?, ?)
+        "#,
+        flight_number,
+        flight_number,
+        flight_date,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+        VALUES (?, ?, 5, 1)
+        "#,
+        flight_number,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    let user_id = ctx
+        .user_service
+        .register_user(UserRegistrationRequest {
+            username: "operation_log_user".to_string(),
+            password: "test_password".to_string(),
+            role: Role::User,
+            name: "Operation Log User".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+            gender: "male".to_string(),
+            email: None,
+        })
+        .await?;
+
+    let request = TicketBookingRequest {
+        flights: vec![FlightBookingRequest {
+            flight_number,
+            flight_date,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    ctx.ticket_service.book_ticket(user_id, request.clone()).await?;
+    // Booking the same flight twice fails and is logged as failed
+    let result = ctx.ticket_service.book_ticket(user_id, request).await;
+    assert!(result.is_err());
+
+    let records = OperationLog::read(&ctx.log_path).await.unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].outcome, OperationOutcome::Succeeded);
+    assert!(matches!(records[1].outcome, OperationOutcome::Failed { .. }));
+
+    // Simulate restoring a snapshot taken before the booking
+    sqlx::query!("DELETE FROM booking WHERE customer_id = ?", user_id)
+        .execute(&ctx.pool)
+        .await?;
+    sqlx::query!("DELETE FROM ticket WHERE customer_id = ?", user_id)
+        .execute(&ctx.pool)
+        .await?;
+    sqlx::query!(
+        "UPDATE flight SET available_tickets = 5 WHERE flight_number = ?",
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    let summary = operation_log::replay(
+        &TicketService::new(ctx.pool.clone()),
+        &PaymentService::new(ctx.pool.clone(), Arc::new(MockPaymentProvider)),
+        &records,
+        None,
+    )
+    .await?;
+    assert_eq!(summary.replayed, 1);
+    assert_eq!(summary.skipped, 1);
+    assert!(summary.failed.is_empty());
+
+    let ticket = sqlx::query!(
+        "SELECT COUNT(*) as count FROM ticket WHERE customer_id = ?",
+        user_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(ticket.count, 1);
+
+    Ok(())
+}
+
+#[test_context(OperationLogContext)]
+#[tokio::test]
+async fn test_replay_recovers_paid_bookings(ctx: &OperationLogContext) -> Result<(), AppError> {
+    let flight_number = 702;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 26).unwrap();
+
+    sqlx::query!(
+        r#"INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 5)"#,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, overbooking, start_date, end_date, base_fare)
+        VALUES
+        (?, 'New York', 'London', '10:00:00', '22:00:00',
+            ?, 0.00, ?, ?, 450.00)
+        "#,
+        flight_number,
+        flight_number,
+        flight_date,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+        VALUES (?, ?, 5, 1)
+        "#,
+        flight_number,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    let user_id = ctx
+        .user_service
+        .register_user(UserRegistrationRequest {
+            username: "operation_log_payer".to_string(),
+            password: "test_password".to_string(),
+            role: Role::User,
+            name: "Operation Log User".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+            gender: "male".to_string(),
+            email: None,
+        })
+        .await?;
+
+    let response = ctx
+        .ticket_service
+        .book_ticket(
+            user_id,
+            TicketBookingRequest {
+                flights: vec![FlightBookingRequest {
+                    flight_number,
+                    flight_date,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .await?;
+    assert_eq!(response.booking_status, BookingStatus::PendingPayment);
+    let payment = ctx
+        .payment_service
+        .confirm_payment(
+            user_id,
+            response.booking_id,
+            ConfirmPaymentRequest {
+                payment_token: "tok_visa".to_string(),
+            },
+        )
+        .await?;
+
+    let records = OperationLog::read(&ctx.log_path).await.unwrap();
+    let payments: Vec<_> = records
+        .iter()
+        .filter(|record| matches!(record.operation, Operation::ConfirmPayment { .. }))
+        .collect();
+    assert_eq!(payments.len(), 1);
+    assert_eq!(payments[0].outcome, OperationOutcome::Succeeded);
+
+    // Simulate restoring a snapshot taken before the booking
+    sqlx::query!("DELETE FROM ticket WHERE customer_id = ?", user_id)
+        .execute(&ctx.pool)
+        .await?;
+    sqlx::query!("DELETE FROM booking WHERE customer_id = ?", user_id)
+        .execute(&ctx.pool)
+        .await?;
+    sqlx::query!(
+        "UPDATE flight SET available_tickets = 5 WHERE flight_number = ?",
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    // The booking comes back paid, without charging the customer again
+    let summary = operation_log::replay(
+        &TicketService::new(ctx.pool.clone()),
+        &PaymentService::new(ctx.pool.clone(), Arc::new(MockPaymentProvider)),
+        &records,
+        None,
+    )
+    .await?;
+    assert!(summary.failed.is_empty(), "{:?}", summary.failed);
+    let booking = sqlx::query!(
+        r#"
+        SELECT b.status, p.status as payment_status, p.provider_reference
+        FROM booking b
+        JOIN payment p ON p.booking_id = b.id
+        WHERE b.customer_id = ?
+        "#,
+        user_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(booking.status, "CONFIRMED");
+    assert_eq!(booking.payment_status, "CAPTURED");
+    assert_eq!(booking.provider_reference, payment.provider_reference);
+
+    Ok(())
+}
@@ -0,0 +1,206 @@
+use airline_booking_system::{
+    models::{
+        payment::ConfirmPaymentRequest,
+        ticket::{FlightBookingRequest, TicketBookingRequest},
+        user::{Role, UserRegistrationRequest},
+    },
+    services::{
+        operation_service::OperationService,
+        payment_service::{MockPaymentProvider, PaymentService},
+        ticket_service::TicketService,
+        user_service::UserService,
+    },
+    utils::error::AppError,
+};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use ctor::dtor;
+use sqlx::mysql::MySqlPool as Pool;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct OperationServiceContext {
+    pool: Pool,
+    operation_service: OperationService,
+    payment_service: PaymentService,
+    ticket_service: TicketService,
+    user_service: UserService,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for OperationServiceContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+
+        OperationServiceContext {
+            operation_service: OperationService::new(pool.clone()),
+            payment_service: PaymentService::new(pool.clone(), Arc::new(MockPaymentProvider)),
+            ticket_service: TicketService::new(pool.clone()),
+            user_service: UserService::new(pool.clone()),
+            pool,
+        }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+impl OperationServiceContext {
+    // Helper method to create a paid flight and a user holding a pending booking on it
+    async fn book_paid_flight(
+        &self,
+        flight_number: i32,
+        username: &str,
+    ) -> Result<(i32, i32), AppError> {
+        let flight_date = (chrono::Utc::now() + chrono::Duration::days(10)).date_naive();
+        sqlx::query!(
+            "INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 5)",
+            flight_number
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO flight_route
+            (flight_number, departure_city, destination_city, departure_time, arrival_time,
+                aircraft_id, overbooking, start_date, end_date, base_fare)
+            VALUES
+            (?, 'Ottawa', 'Calgary', '08:00:00', '12:00:00', ?, 0.00, ?, ?, 300.00)
+            "#,
+            flight_number,
+            flight_number,
+            flight_date,
+            flight_date
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+            VALUES (?, ?, 5, 1)
+            "#,
+            flight_number,
+            flight_date
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let user_id = self
+            .user_service
+            .register_user(UserRegistrationRequest {
+                username: username.to_string(),
+                password: "test_password".to_string(),
+                role: Role::User,
+                name: "Operation Test User".to_string(),
+                birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+                gender: "female".to_string(),
+                email: None,
+            })
+            .await?;
+
+        let response = self
+            .ticket_service
+            .book_ticket(
+                user_id,
+                TicketBookingRequest {
+                    flights: vec![FlightBookingRequest {
+                        flight_number,
+                        flight_date,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            )
+            .await?;
+        Ok((user_id, response.booking_id))
+    }
+}
+
+#[test_context(OperationServiceContext)]
+#[tokio::test]
+async fn test_wait_returns_when_booking_is_paid(
+    ctx: &OperationServiceContext,
+) -> Result<(), AppError> {
+    let (user_id, booking_id) = ctx.book_paid_flight(1301, "operation_wait_user").await?;
+    let operation_id = format!("booking-{}", booking_id);
+
+    let status = ctx.operation_service.status(user_id, &operation_id).await?;
+    assert_eq!(status.status, "PENDING_PAYMENT");
+    assert!(!status.done);
+
+    let operation_service = ctx.operation_service.clone();
+    let waiting_id = operation_id.clone();
+    let started = Instant::now();
+    let waiting = tokio::spawn(async move {
+        operation_service
+            .wait(user_id, &waiting_id, Duration::from_secs(20))
+            .await
+    });
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    ctx.payment_service
+        .confirm_payment(
+            user_id,
+            booking_id,
+            ConfirmPaymentRequest {
+                payment_token: "tok_visa".to_string(),
+            },
+        )
+        .await?;
+
+    let status = waiting.await.expect("wait task panicked")?;
+    assert_eq!(status.operation_id, operation_id);
+    assert_eq!(status.status, "CONFIRMED");
+    assert!(status.done);
+    assert!(
+        started.elapsed() < Duration::from_secs(10),
+        "The wait ends with the operation, not the timeout"
+    );
+
+    Ok(())
+}
+
+#[test_context(OperationServiceContext)]
+#[tokio::test]
+async fn test_wait_times_out_while_pending(ctx: &OperationServiceContext) -> Result<(), AppError> {
+    let (user_id, booking_id) = ctx.book_paid_flight(1302, "operation_timeout_user").await?;
+    let operation_id = format!("booking-{}", booking_id);
+
+    let status = ctx
+        .operation_service
+        .wait(user_id, &operation_id, Duration::from_millis(1500))
+        .await?;
+    assert_eq!(status.status, "PENDING_PAYMENT");
+    assert!(!status.done);
+
+    // Operations of other customers are not found
+    let result = ctx
+        .operation_service
+        .wait(user_id + 1, &operation_id, Duration::ZERO)
+        .await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    let result = ctx
+        .operation_service
+        .wait(user_id, "flight-1", Duration::ZERO)
+        .await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use airline_booking_system::{
+    models::{
+        ticket::{FlightBookingRequest, TicketBookingRequest},
+        user::{Role, UserRegistrationRequest},
+    },
+    services::{
+        event_bus::{DomainEvent, EventBus},
+        outbox::{self, OutboxDispatcher},
+        ticket_service::TicketService,
+        user_service::UserService,
+    },
+    utils::error::AppError,
+};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use ctor::dtor;
+use sqlx::mysql::MySqlPool as Pool;
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct OutboxContext {
+    pool: Pool,
+    event_bus: EventBus,
+    dispatcher: OutboxDispatcher,
+    ticket_service: TicketService,
+    user_service: UserService,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for OutboxContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+
+        let event_bus = EventBus::new();
+        let dispatcher = OutboxDispatcher::new(pool.clone(), event_bus.clone());
+        let ticket_service = TicketService::new(pool.clone());
+        let user_service = UserService::new(pool.clone());
+
+        OutboxContext {
+            pool,
+            event_bus,
+            dispatcher,
+            ticket_service,
+            user_service,
+        }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+async fn setup_flight(
+    ctx: &OutboxContext,
+    flight_number: i32,
+    flight_date: NaiveDate,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        "INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 5)",
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, overbooking, start_date, end_date)
+        VALUES
+        (?, 'Montreal', 'Vancouver', '08:00:00', '10:45:00', ?, 0.00, ?, ?)
+        "#,
+        flight_number,
+        flight_number,
+        flight_date,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+        VALUES (?, ?, 5, 1)
+        "#,
+        flight_number,
+        flight_date
+    )
+    .execute(&ctx.pool)
+    .await?;
+    Ok(())
+}
+
+#[test_context(OutboxContext)]
+#[tokio::test]
+async fn test_events_published_after_commit(ctx: &OutboxContext) -> Result<(), AppError> {
+    let flight_number = 111;
+    let flight_date = NaiveDate::from_ymd_opt(2025, 4, 2).unwrap();
+    setup_flight(ctx, flight_number, flight_date).await?;
+    let mut receiver = ctx.event_bus.subscribe();
+
+    let user_id = ctx
+        .user_service
+        .register_user(UserRegistrationRequest {
+            username: "outbox_user".to_string(),
+            password: "test_password".to_string(),
+            role: Role::User,
+            name: "Outbox User".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+            gender: "female".to_string(),
+            email: None,
+        })
+        .await?;
+    let response = ctx
+        .ticket_service
+        .book_ticket(
+            user_id,
+            TicketBookingRequest {
+                flights: vec![FlightBookingRequest {
+                    flight_number,
+                    flight_date,
+                    preferred_seat: None,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .await?;
+    let ticket_id = response.flight_bookings[0].ticket_id;
+
+    // Nothing reaches the bus until the dispatcher runs
+    assert!(receiver.try_recv().is_err());
+    assert_eq!(ctx.dispatcher.dispatch_pending().await?, 1);
+    match receiver.try_recv() {
+        Ok(DomainEvent::TicketBooked {
+            ticket_id: booked,
+            customer_id,
+            ..
+        }) => {
+            assert_eq!(booked, ticket_id);
+            assert_eq!(customer_id, user_id);
+        }
+        other => panic!("Expected TicketBooked, got {:?}", other),
+    }
+
+    // Published events are not published again
+    assert_eq!(ctx.dispatcher.dispatch_pending().await?, 0);
+
+    ctx.ticket_service.release_ticket(ticket_id).await?;
+    assert_eq!(ctx.dispatcher.dispatch_pending().await?, 1);
+    assert!(matches!(
+        receiver.try_recv(),
+        Ok(DomainEvent::TicketCancelled { .. })
+    ));
+
+    Ok(())
+}
+
+#[test_context(OutboxContext)]
+#[tokio::test]
+async fn test_rolled_back_event_not_published(ctx: &OutboxContext) -> Result<(), AppError> {
+    let mut tx = ctx.pool.begin().await?;
+    outbox::enqueue(
+        &mut tx,
+        &DomainEvent::TicketCancelled {
+            ticket_id: 0,
+            customer_id: 0,
+            flight_number: 112,
+            flight_date: NaiveDate::from_ymd_opt(2025, 4, 3).unwrap(),
+            seat_number: None,
+        },
+    )
+    .await?;
+    tx.rollback().await?;
+
+    // Dispatching is left to the other test, which expects to publish its own events
+    let events = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*)
+        FROM outbox_event
+        WHERE JSON_EXTRACT(payload, '$.TicketCancelled.flight_number') = 112
+        "#
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(events, 0, "A rolled back event is never published");
+
+    Ok(())
+}
@@ -0,0 +1,293 @@
+use airline_booking_system::{
+    models::partner::{ChangeCursor, CreatePartnerKeyRequest, FlightChange, RouteAvailabilityQuery},
+    services::partner_service::PartnerService,
+    utils::{error::AppError, ndjson::collect_rows},
+};
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveTime};
+use ctor::dtor;
+use sqlx::mysql::MySqlPool as Pool;
+use std::time::Duration;
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct PartnerServiceContext {
+    pool: Pool,
+    partner_service: PartnerService,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for PartnerServiceContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+
+        let partner_service = PartnerService::new(pool.clone());
+
+        PartnerServiceContext {
+            pool,
+            partner_service,
+        }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+impl PartnerServiceContext {
+    async fn create_route(
+        &self,
+        flight_number: i32,
+        departure_city: &str,
+        destination_city: &str,
+        start_date: NaiveDate,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO aircraft (aircraft_id, capacity)
+            VALUES (?, ?)
+            ON DUPLICATE KEY UPDATE capacity = capacity
+            "#,
+            901,
+            100
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO flight_route (
+                flight_number,
+                departure_city,
+                destination_city,
+                departure_time,
+                arrival_time,
+                aircraft_id,
+                start_date
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+            flight_number,
+            departure_city,
+            destination_city,
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+            901,
+            start_date
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_flight(
+        &self,
+        flight_number: i32,
+        flight_date: NaiveDate,
+        available_tickets: i32,
+        status: &str,
+    ) -> Result<i32, AppError> {
+        let flight_id = sqlx::query!(
+            r#"
+            INSERT INTO flight (flight_number, flight_date, available_tickets, status)
+            VALUES (?, ?, ?, ?)
+            "#,
+            flight_number,
+            flight_date,
+            available_tickets,
+            status
+        )
+        .execute(&self.pool)
+        .await?
+        .last_insert_id() as i32;
+        Ok(flight_id)
+    }
+}
+
+#[test_context(PartnerServiceContext)]
+#[tokio::test]
+async fn test_partner_api_keys(ctx: &PartnerServiceContext) -> Result<(), AppError> {
+    let key = ctx
+        .partner_service
+        .create_api_key(CreatePartnerKeyRequest {
+            partner_name: "Example Travel".to_string(),
+        })
+        .await?;
+
+    let partner = ctx
+        .partner_service
+        .authenticate(&key.api_key)
+        .await?
+        .expect("new key should authenticate");
+    assert_eq!(partner.key_id, key.key_id);
+    assert_eq!(partner.partner_name, "Example Travel");
+
+    // Wrong secret, malformed and unknown keys are all rejected
+    let wrong_secret = format!("{}.not-the-secret", key.key_id);
+    assert!(ctx.partner_service.authenticate(&wrong_secret).await?.is_none());
+    assert!(ctx.partner_service.authenticate("garbage").await?.is_none());
+    assert!(ctx.partner_service.authenticate("0.abc").await?.is_none());
+
+    ctx.partner_service.revoke_api_key(key.key_id).await?;
+    assert!(ctx.partner_service.authenticate(&key.api_key).await?.is_none());
+    let result = ctx.partner_service.revoke_api_key(key.key_id).await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    let result = ctx
+        .partner_service
+        .create_api_key(CreatePartnerKeyRequest {
+            partner_name: String::new(),
+        })
+        .await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+    Ok(())
+}
+
+#[test_context(PartnerServiceContext)]
+#[tokio::test]
+async fn test_route_availability(ctx: &PartnerServiceContext) -> Result<(), AppError> {
+    let start_date = NaiveDate::from_ymd_opt(2031, 3, 1).unwrap();
+    let next_day = start_date.succ_opt().unwrap();
+    ctx.create_route(3101, "Halifax", "Calgary", start_date).await?;
+    ctx.create_route(3102, "Halifax", "Calgary", start_date).await?;
+    ctx.create_route(3103, "Calgary", "Halifax", start_date).await?;
+
+    ctx.create_flight(3101, start_date, 40, "SCHEDULED").await?;
+    ctx.create_flight(3102, start_date, 15, "DELAYED").await?;
+    ctx.create_flight(3101, next_day, 20, "CANCELLED").await?;
+    ctx.create_flight(3103, next_day, 50, "SCHEDULED").await?;
+
+    let query = RouteAvailabilityQuery {
+        departure_city: "Halifax".to_string(),
+        destination_city: "Calgary".to_string(),
+        start_date,
+        days: 3,
+    };
+    let response = ctx.partner_service.route_availability(query.clone()).await?;
+    assert_eq!(response.end_date, NaiveDate::from_ymd_opt(2031, 3, 3).unwrap());
+    assert_eq!(response.days.len(), 3);
+    assert_eq!(response.days[0].date, start_date);
+    assert_eq!(response.days[0].flights, 2);
+    assert_eq!(response.days[0].available_seats, 55);
+    // Cancelled flights and the return route do not count
+    assert_eq!(response.days[1].flights, 0);
+    assert_eq!(response.days[1].available_seats, 0);
+    assert_eq!(response.days[2].flights, 0);
+
+    // Repeated requests are answered from the cache for a while
+    ctx.create_flight(3102, next_day, 30, "SCHEDULED").await?;
+    let cached = ctx.partner_service.route_availability(query.clone()).await?;
+    assert_eq!(cached.days[1].flights, 0);
+
+    let uncached = PartnerService::new(ctx.pool.clone()).with_cache_ttl(Duration::ZERO);
+    let fresh = uncached.route_availability(query.clone()).await?;
+    assert_eq!(fresh.days[1].flights, 1);
+    assert_eq!(fresh.days[1].available_seats, 30);
+
+    for days in [0, 91] {
+        let result = ctx
+            .partner_service
+            .route_availability(RouteAvailabilityQuery { days, ..query.clone() })
+            .await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+    }
+
+    Ok(())
+}
+
+#[test_context(PartnerServiceContext)]
+#[tokio::test]
+async fn test_partner_changes(ctx: &PartnerServiceContext) -> Result<(), AppError> {
+    let partner_service =
+        PartnerService::new(ctx.pool.clone()).with_change_settle_time(Duration::ZERO);
+    let flight_date = NaiveDate::from_ymd_opt(2031, 4, 1).unwrap();
+    ctx.create_route(3201, "Regina", "Victoria", flight_date).await?;
+    let first = ctx.create_flight(3201, flight_date, 10, "SCHEDULED").await?;
+    let second = ctx
+        .create_flight(3201, flight_date.succ_opt().unwrap(), 10, "SCHEDULED")
+        .await?;
+    for flight_id in [first, second] {
+        for seat_number in 1..=3 {
+            sqlx::query!(
+                "INSERT INTO seat_info (flight_id, seat_number) VALUES (?, ?)",
+                flight_id,
+                seat_number
+            )
+            .execute(&ctx.pool)
+            .await?;
+        }
+    }
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    // Other tests of this file create flights concurrently
+    let ours = |changes: Vec<FlightChange>| -> Vec<FlightChange> {
+        changes
+            .into_iter()
+            .filter(|change| change.flight_id == first || change.flight_id == second)
+            .collect()
+    };
+
+    // A full sync returns every flight, each change after the previous one
+    let changes = collect_rows(|sink| partner_service.export_changes(None, 5000, sink)).await?;
+    assert_eq!(ours(changes.clone()).len(), 2);
+    assert!(ours(changes.clone())
+        .iter()
+        .all(|change| change.available_seats == 3));
+    let cursors: Vec<ChangeCursor> = changes
+        .iter()
+        .map(|change| ChangeCursor::parse(&change.cursor))
+        .collect::<Result<_, _>>()?;
+    let position = |cursor: &ChangeCursor| (cursor.changed_at, cursor.flight_id);
+    assert!(cursors
+        .windows(2)
+        .all(|pair| position(&pair[0]) < position(&pair[1])));
+    let cursor = *cursors.last().unwrap();
+    assert_eq!(ChangeCursor::parse(&cursor.to_string())?, cursor);
+
+    // Nothing changed since the last cursor
+    let changes =
+        collect_rows(|sink| partner_service.export_changes(Some(cursor), 5000, sink)).await?;
+    assert!(ours(changes).is_empty());
+
+    // Selling a seat changes only the flight it belongs to
+    sqlx::query!(
+        "UPDATE seat_info SET seat_status = 'BOOKED' WHERE flight_id = ? AND seat_number = 2",
+        second
+    )
+    .execute(&ctx.pool)
+    .await?;
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let changes =
+        ours(collect_rows(|sink| partner_service.export_changes(Some(cursor), 5000, sink)).await?);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].flight_id, second);
+    assert_eq!(changes[0].available_seats, 2);
+
+    // Fresh changes are held back until they settle
+    let settling = PartnerService::new(ctx.pool.clone());
+    let changes = collect_rows(|sink| settling.export_changes(Some(cursor), 5000, sink)).await?;
+    assert!(ours(changes).is_empty());
+
+    assert!(matches!(
+        ChangeCursor::parse("not-a-cursor"),
+        Err(AppError::BadRequest(_))
+    ));
+    let result = collect_rows(|sink| partner_service.export_changes(None, 0, sink)).await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+    Ok(())
+}
@@ -0,0 +1,809 @@
+use airline_booking_system::{
+    models::{
+        booking_rules::{BookingRules, DuplicatePolicy},
+        payment::{
+            ConfirmPaymentRequest, FareHoldRequest, PaymentStatus, RefundStatus, SeatChangeRequest,
+            SeatSelectionRequest,
+        },
+        ticket::{BookingStatus, FlightBookingRequest, SeatBookingRequest, TicketBookingRequest},
+        user::{Role, UserRegistrationRequest},
+    },
+    services::{
+        payment_service::{MockPaymentProvider, PaymentService},
+        ticket_service::TicketService,
+        user_service::UserService,
+    },
+    utils::error::AppError,
+};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use ctor::dtor;
+use rust_decimal::Decimal;
+use sqlx::mysql::MySqlPool as Pool;
+use std::sync::Arc;
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct PaymentServiceContext {
+    pool: Pool,
+    payment_service: PaymentService,
+    ticket_service: TicketService,
+    user_service: UserService,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for PaymentServiceContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+
+        let payment_service = PaymentService::new(pool.clone(), Arc::new(MockPaymentProvider));
+        let ticket_service = TicketService::new(pool.clone());
+        let user_service = UserService::new(pool.clone());
+
+        PaymentServiceContext {
+            pool,
+            payment_service,
+            ticket_service,
+            user_service,
+        }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+impl PaymentServiceContext {
+    // Helper method to create a paid flight and a user holding a pending booking on it
+    async fn book_paid_flight(
+        &self,
+        flight_number: i32,
+        username: &str,
+    ) -> Result<(i32, i32), AppError> {
+        let flight_date = NaiveDate::from_ymd_opt(2024, 12, 24).unwrap();
+        self.book_paid_flight_on(flight_number, username, flight_date)
+            .await
+    }
+
+    async fn book_paid_flight_on(
+        &self,
+        flight_number: i32,
+        username: &str,
+        flight_date: NaiveDate,
+    ) -> Result<(i32, i32), AppError> {
+        let capacity = 5;
+
+        sqlx::query!(
+            r#"INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, ?)"#,
+            flight_number,
+            capacity
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO flight_route
+            (flight_number, departure_city, destination_city, departure_time, arrival_time,
+                aircraft_id, overbooking, start_date, end_date, base_fare)
+            VALUES
+            (?, 'New York', 'London', '10:00:00', '22:00:00',
+                ?, 0.00, ?, ?, 450.00)
+            "#,
+            flight_number,
+            flight_number,
+            flight_date,
+            flight_date
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+            VALUES (?, ?, ?, 1)
+            "#,
+            flight_number,
+            flight_date,
+            capacity
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let user_id = self
+            .user_service
+            .register_user(UserRegistrationRequest {
+                username: username.to_string(),
+                password: "test_password".to_string(),
+                role: Role::User,
+                name: "Payment Test User".to_string(),
+                birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+                gender: "male".to_string(),
+                email: None,
+            })
+            .await?;
+
+        let response = self
+            .ticket_service
+            .book_ticket(
+                user_id,
+                TicketBookingRequest {
+                    flights: vec![FlightBookingRequest {
+                        flight_number,
+                        flight_date,
+                        preferred_seat: None,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        assert_eq!(response.booking_status, BookingStatus::PendingPayment);
+        assert!(response.payment.is_some());
+
+        Ok((user_id, response.booking_id))
+    }
+}
+
+#[test_context(PaymentServiceContext)]
+#[tokio::test]
+async fn test_confirm_payment(ctx: &PaymentServiceContext) -> Result<(), AppError> {
+    let (user_id, booking_id) = ctx.book_paid_flight(701, "payment_confirm_user").await?;
+
+    // A declined payment keeps the booking pending
+    let result = ctx
+        .payment_service
+        .confirm_payment(
+            user_id,
+            booking_id,
+            ConfirmPaymentRequest {
+                payment_token: "declined".to_string(),
+            },
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::Unprocessable(_))));
+
+    let response = ctx
+        .payment_service
+        .confirm_payment(
+            user_id,
+            booking_id,
+            ConfirmPaymentRequest {
+                payment_token: "tok_visa".to_string(),
+            },
+        )
+        .await?;
+    assert_eq!(response.status, PaymentStatus::Captured);
+
+    let booking = sqlx::query!("SELECT status FROM booking WHERE id = ?", booking_id)
+        .fetch_one(&ctx.pool)
+        .await?;
+    assert_eq!(booking.status, "CONFIRMED");
+
+    Ok(())
+}
+
+#[test_context(PaymentServiceContext)]
+#[tokio::test]
+async fn test_expire_unpaid_booking(ctx: &PaymentServiceContext) -> Result<(), AppError> {
+    let (_, booking_id) = ctx.book_paid_flight(702, "payment_expire_user").await?;
+
+    // Move the payment window to the past
+    sqlx::query!(
+        "UPDATE payment SET expires_at = UTC_TIMESTAMP() - INTERVAL 1 MINUTE WHERE booking_id = ?",
+        booking_id
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    ctx.payment_service
+        .expire_unpaid_bookings(&ctx.ticket_service)
+        .await?;
+
+    let tickets = sqlx::query!(
+        "SELECT COUNT(*) as count FROM ticket WHERE booking_id = ?",
+        booking_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(tickets.count, 0, "Tickets of expired bookings are released");
+
+    let flight = sqlx::query!("SELECT available_tickets FROM flight WHERE flight_number = 702")
+        .fetch_one(&ctx.pool)
+        .await?;
+    assert_eq!(flight.available_tickets, 5);
+
+    Ok(())
+}
+
+#[test_context(PaymentServiceContext)]
+#[tokio::test]
+async fn test_hold_fare(ctx: &PaymentServiceContext) -> Result<(), AppError> {
+    let flight_date = (chrono::Utc::now() + chrono::Duration::days(10)).date_naive();
+    let (user_id, booking_id) = ctx
+        .book_paid_flight_on(703, "payment_hold_user", flight_date)
+        .await?;
+    let hold = |hours, payment_token: &str| FareHoldRequest {
+        hours,
+        payment_token: payment_token.to_string(),
+    };
+
+    // Only the offered hold lengths can be bought
+    let result = ctx
+        .payment_service
+        .hold_fare(user_id, booking_id, hold(12, "tok_visa"))
+        .await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+    // A declined fee keeps the normal payment window
+    let result = ctx
+        .payment_service
+        .hold_fare(user_id, booking_id, hold(24, "declined"))
+        .await;
+    assert!(matches!(result, Err(AppError::Unprocessable(_))));
+
+    // Other customers cannot hold the booking
+    let result = ctx
+        .payment_service
+        .hold_fare(user_id + 1, booking_id, hold(24, "tok_visa"))
+        .await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    let response = ctx
+        .payment_service
+        .hold_fare(user_id, booking_id, hold(24, "tok_visa"))
+        .await?;
+    assert_eq!(response.fee.to_string(), "15.00");
+
+    let payment = sqlx::query!(
+        r#"
+        SELECT status, TIMESTAMPDIFF(MINUTE, UTC_TIMESTAMP(), expires_at) as "minutes_left!: i64"
+        FROM payment
+        WHERE booking_id = ?
+        "#,
+        booking_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(payment.status, "PENDING");
+    assert!(
+        payment.minutes_left > 23 * 60,
+        "The payment window is extended"
+    );
+
+    // The hold shows in the booking history
+    let history = ctx.ticket_service.get_history(user_id).await?;
+    assert_eq!(
+        history.flights[0].fare_held_until,
+        Some(response.expires_at)
+    );
+
+    // A fare is held once
+    let result = ctx
+        .payment_service
+        .hold_fare(user_id, booking_id, hold(48, "tok_visa"))
+        .await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+
+    // Paying the fare confirms the booking as usual
+    let response = ctx
+        .payment_service
+        .confirm_payment(
+            user_id,
+            booking_id,
+            ConfirmPaymentRequest {
+                payment_token: "tok_visa".to_string(),
+            },
+        )
+        .await?;
+    assert_eq!(response.status, PaymentStatus::Captured);
+
+    let history = ctx.ticket_service.get_history(user_id).await?;
+    assert_eq!(history.flights[0].fare_held_until, None);
+
+    Ok(())
+}
+
+#[test_context(PaymentServiceContext)]
+#[tokio::test]
+async fn test_cancel_paid_ticket_refunds_by_fare_rules(
+    ctx: &PaymentServiceContext,
+) -> Result<(), AppError> {
+    let flight_date = (chrono::Utc::now() + chrono::Duration::days(10)).date_naive();
+    let (user_id, booking_id) = ctx
+        .book_paid_flight_on(704, "payment_refund_user", flight_date)
+        .await?;
+    ctx.payment_service
+        .confirm_payment(
+            user_id,
+            booking_id,
+            ConfirmPaymentRequest {
+                payment_token: "tok_visa".to_string(),
+            },
+        )
+        .await?;
+
+    let booking_reference = sqlx::query_scalar!(
+        r#"SELECT booking_reference as "booking_reference!" FROM ticket WHERE booking_id = ?"#,
+        booking_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+
+    // Other customers cannot cancel the ticket
+    let result = ctx
+        .payment_service
+        .cancel_ticket(&ctx.ticket_service, user_id + 1, &booking_reference)
+        .await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    let response = ctx
+        .payment_service
+        .cancel_ticket(
+            &ctx.ticket_service,
+            user_id,
+            &booking_reference.to_lowercase(),
+        )
+        .await?;
+    assert_eq!(response.booking_reference, booking_reference);
+
+    // Economy tickets cancelled more than a week ahead get 80% back
+    let refund = response.refund.expect("paid tickets are refunded");
+    assert_eq!(refund.status, RefundStatus::Refunded);
+    assert_eq!(
+        refund.amount,
+        (refund.ticket_price * Decimal::new(80, 2)).round_dp(2)
+    );
+    assert_eq!(
+        refund.provider_reference,
+        Some(format!("mock-refund-{}", refund.refund_id))
+    );
+
+    let flight = sqlx::query!("SELECT available_tickets FROM flight WHERE flight_number = 704")
+        .fetch_one(&ctx.pool)
+        .await?;
+    assert_eq!(flight.available_tickets, 5);
+
+    // The ticket is gone, so it is neither cancelled nor refunded twice
+    let result = ctx
+        .payment_service
+        .cancel_ticket(&ctx.ticket_service, user_id, &booking_reference)
+        .await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    let refunds = ctx.payment_service.list_refunds(user_id).await?;
+    assert_eq!(refunds.len(), 1);
+    assert_eq!(refunds[0].refund_id, refund.refund_id);
+    assert!(ctx
+        .payment_service
+        .list_refunds(user_id + 1)
+        .await?
+        .is_empty());
+
+    Ok(())
+}
+
+#[test_context(PaymentServiceContext)]
+#[tokio::test]
+async fn test_cancel_unpaid_ticket_without_refund(
+    ctx: &PaymentServiceContext,
+) -> Result<(), AppError> {
+    let flight_date = (chrono::Utc::now() + chrono::Duration::days(10)).date_naive();
+    let (user_id, booking_id) = ctx
+        .book_paid_flight_on(705, "payment_unpaid_cancel_user", flight_date)
+        .await?;
+    let booking_reference = sqlx::query_scalar!(
+        r#"SELECT booking_reference as "booking_reference!" FROM ticket WHERE booking_id = ?"#,
+        booking_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+
+    let response = ctx
+        .payment_service
+        .cancel_ticket(&ctx.ticket_service, user_id, &booking_reference)
+        .await?;
+    assert!(response.refund.is_none());
+    assert!(ctx.payment_service.list_refunds(user_id).await?.is_empty());
+
+    // Nothing is left to pay for, so the booking cannot be confirmed any more
+    let booking = sqlx::query!("SELECT status FROM booking WHERE id = ?", booking_id)
+        .fetch_one(&ctx.pool)
+        .await?;
+    assert_eq!(booking.status, "CANCELLED");
+    let result = ctx
+        .payment_service
+        .confirm_payment(
+            user_id,
+            booking_id,
+            ConfirmPaymentRequest {
+                payment_token: "tok_visa".to_string(),
+            },
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+
+    Ok(())
+}
+
+#[test_context(PaymentServiceContext)]
+#[tokio::test]
+async fn test_select_seat_charges_seat_price(ctx: &PaymentServiceContext) -> Result<(), AppError> {
+    let flight_number = 706;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 24).unwrap();
+    let (user_id, _) = ctx
+        .book_paid_flight_on(flight_number, "payment_seat_price_user", flight_date)
+        .await?;
+
+    // Two seats a row: row 2 is an exit row and row 3 has extra legroom
+    sqlx::query!(
+        r#"
+        UPDATE aircraft
+        SET seats_per_row = 2, exit_rows = '2', extra_legroom_rows = '3'
+        WHERE aircraft_id = ?
+        "#,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+    let flight_id = sqlx::query_scalar!(
+        "SELECT flight_id FROM flight WHERE flight_number = ?",
+        flight_number
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    for seat_number in 1..=5 {
+        sqlx::query!(
+            r#"
+            INSERT INTO seat_info (flight_id, seat_number, seat_status, version)
+            VALUES (?, ?, 'AVAILABLE', 0)
+            "#,
+            flight_id,
+            seat_number
+        )
+        .execute(&ctx.pool)
+        .await?;
+    }
+
+    let select = |seat_number, payment_token: Option<&str>| SeatSelectionRequest {
+        seat: SeatBookingRequest {
+            flight_number,
+            flight_date,
+            seat_number,
+        },
+        payment_token: payment_token.map(str::to_string),
+    };
+
+    // A seat in a plain row is free
+    let response = ctx
+        .payment_service
+        .select_seat(&ctx.ticket_service, user_id, select(1, None))
+        .await?;
+    assert!(response.success);
+    assert_eq!(response.amount_charged, Decimal::ZERO);
+    assert!(response.provider_reference.is_none());
+
+    // An extra legroom seat has to be paid for
+    let result = ctx
+        .payment_service
+        .select_seat(&ctx.ticket_service, user_id, select(5, None))
+        .await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+    // A declined payment gives the seat back
+    let result = ctx
+        .payment_service
+        .select_seat(&ctx.ticket_service, user_id, select(5, Some("declined")))
+        .await;
+    assert!(matches!(result, Err(AppError::Unprocessable(_))));
+    let status = sqlx::query_scalar!(
+        "SELECT seat_status FROM seat_info WHERE flight_id = ? AND seat_number = 5",
+        flight_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(status, "AVAILABLE");
+
+    let response = ctx
+        .payment_service
+        .select_seat(&ctx.ticket_service, user_id, select(5, Some("tok_visa")))
+        .await?;
+    assert!(response.success);
+    assert_eq!(response.seat_price, Decimal::new(1500, 2));
+    assert_eq!(response.amount_charged, Decimal::new(1500, 2));
+    assert!(response.provider_reference.is_some());
+
+    // Moving to an exit row seat only charges the difference
+    let response = ctx
+        .payment_service
+        .select_seat(&ctx.ticket_service, user_id, select(3, Some("tok_visa")))
+        .await?;
+    assert_eq!(response.seat_price, Decimal::new(2500, 2));
+    assert_eq!(response.amount_charged, Decimal::new(1000, 2));
+
+    let seat_number = sqlx::query_scalar!(
+        "SELECT seat_number FROM ticket WHERE customer_id = ? AND flight_id = ?",
+        user_id,
+        flight_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(seat_number, Some(3));
+
+    Ok(())
+}
+
+#[test_context(PaymentServiceContext)]
+#[tokio::test]
+async fn test_change_seat_by_ticket_id(ctx: &PaymentServiceContext) -> Result<(), AppError> {
+    let flight_number = 707;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 24).unwrap();
+    let (user_id, _) = ctx
+        .book_paid_flight_on(flight_number, "payment_seat_change_user", flight_date)
+        .await?;
+    let flight_id = sqlx::query_scalar!(
+        "SELECT flight_id FROM flight WHERE flight_number = ?",
+        flight_number
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    for seat_number in 1..=5 {
+        sqlx::query!(
+            r#"
+            INSERT INTO seat_info (flight_id, seat_number, seat_status, version)
+            VALUES (?, ?, 'AVAILABLE', 0)
+            "#,
+            flight_id,
+            seat_number
+        )
+        .execute(&ctx.pool)
+        .await?;
+    }
+    let ticket_id = sqlx::query_scalar!(
+        "SELECT id FROM ticket WHERE customer_id = ? AND flight_id = ?",
+        user_id,
+        flight_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    let seat_status = |seat_number: i32| {
+        sqlx::query_scalar!(
+            "SELECT seat_status FROM seat_info WHERE flight_id = ? AND seat_number = ?",
+            flight_id,
+            seat_number
+        )
+        .fetch_one(&ctx.pool)
+    };
+    let change = |seat_number| SeatChangeRequest {
+        seat_number,
+        payment_token: None,
+    };
+
+    let response = ctx
+        .payment_service
+        .change_seat(&ctx.ticket_service, user_id, ticket_id, change(Some(1)))
+        .await?;
+    assert_eq!(response.ticket_id, ticket_id);
+    assert_eq!(response.old_seat_number, None);
+    assert_eq!(response.new_seat_number, Some(1));
+    assert_eq!(response.amount_charged, Decimal::ZERO);
+
+    // Asking for the same seat again changes nothing
+    let response = ctx
+        .payment_service
+        .change_seat(&ctx.ticket_service, user_id, ticket_id, change(Some(1)))
+        .await?;
+    assert_eq!(response.old_seat_number, Some(1));
+    assert_eq!(response.new_seat_number, Some(1));
+
+    let response = ctx
+        .payment_service
+        .change_seat(&ctx.ticket_service, user_id, ticket_id, change(Some(2)))
+        .await?;
+    assert_eq!(response.old_seat_number, Some(1));
+    assert_eq!(response.new_seat_number, Some(2));
+    assert_eq!(seat_status(1).await?, "AVAILABLE");
+    assert_eq!(seat_status(2).await?, "BOOKED");
+
+    // Tickets of other customers are not found
+    let other_user_id = ctx
+        .user_service
+        .register_user(UserRegistrationRequest {
+            username: "payment_seat_change_other".to_string(),
+            password: "test_password".to_string(),
+            role: Role::User,
+            name: "Payment Test User".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+            gender: "male".to_string(),
+            email: None,
+        })
+        .await?;
+    let result = ctx
+        .payment_service
+        .change_seat(
+            &ctx.ticket_service,
+            other_user_id,
+            ticket_id,
+            change(Some(3)),
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+    let result = ctx
+        .payment_service
+        .change_seat(&ctx.ticket_service, other_user_id, ticket_id, change(None))
+        .await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+    assert_eq!(seat_status(2).await?, "BOOKED");
+
+    // A null seat gives the seat up and keeps the ticket
+    let response = ctx
+        .payment_service
+        .change_seat(&ctx.ticket_service, user_id, ticket_id, change(None))
+        .await?;
+    assert_eq!(response.old_seat_number, Some(2));
+    assert_eq!(response.new_seat_number, None);
+    assert_eq!(seat_status(2).await?, "AVAILABLE");
+    let seat_number = sqlx::query_scalar!("SELECT seat_number FROM ticket WHERE id = ?", ticket_id)
+        .fetch_one(&ctx.pool)
+        .await?;
+    assert_eq!(seat_number, None);
+
+    Ok(())
+}
+
+#[test_context(PaymentServiceContext)]
+#[tokio::test]
+async fn test_change_seat_of_one_of_two_tickets(
+    ctx: &PaymentServiceContext,
+) -> Result<(), AppError> {
+    let flight_number = 708;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 24).unwrap();
+    let (user_id, _) = ctx
+        .book_paid_flight_on(flight_number, "payment_two_tickets_user", flight_date)
+        .await?;
+    let ticket_service = TicketService::new(ctx.pool.clone()).with_rules(BookingRules {
+        duplicate_booking: DuplicatePolicy::Allow,
+        ..Default::default()
+    });
+    ticket_service
+        .book_ticket(
+            user_id,
+            TicketBookingRequest {
+                flights: vec![FlightBookingRequest {
+                    flight_number,
+                    flight_date,
+                    preferred_seat: None,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    // Row 2 is an exit row
+    sqlx::query!(
+        "UPDATE aircraft SET seats_per_row = 2, exit_rows = '2' WHERE aircraft_id = ?",
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+    let flight_id = sqlx::query_scalar!(
+        "SELECT flight_id FROM flight WHERE flight_number = ?",
+        flight_number
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    for seat_number in 1..=5 {
+        sqlx::query!(
+            r#"
+            INSERT INTO seat_info (flight_id, seat_number, seat_status, version)
+            VALUES (?, ?, 'AVAILABLE', 0)
+            "#,
+            flight_id,
+            seat_number
+        )
+        .execute(&ctx.pool)
+        .await?;
+    }
+    let ticket_ids = sqlx::query_scalar!(
+        "SELECT id FROM ticket WHERE customer_id = ? AND flight_id = ? ORDER BY id",
+        user_id,
+        flight_id
+    )
+    .fetch_all(&ctx.pool)
+    .await?;
+    assert_eq!(ticket_ids.len(), 2);
+    let (first, second) = (ticket_ids[0], ticket_ids[1]);
+    let seat_of = |ticket_id: i32| {
+        sqlx::query_scalar!("SELECT seat_number FROM ticket WHERE id = ?", ticket_id)
+            .fetch_one(&ctx.pool)
+    };
+
+    // The seat goes to the ticket in the path, not the customer's first one
+    let response = ctx
+        .payment_service
+        .change_seat(
+            &ticket_service,
+            user_id,
+            second,
+            SeatChangeRequest {
+                seat_number: Some(1),
+                payment_token: None,
+            },
+        )
+        .await?;
+    assert_eq!(response.ticket_id, second);
+    assert_eq!(seat_of(first).await?, None);
+    assert_eq!(seat_of(second).await?, Some(1));
+
+    let response = ctx
+        .payment_service
+        .change_seat(
+            &ticket_service,
+            user_id,
+            first,
+            SeatChangeRequest {
+                seat_number: Some(2),
+                payment_token: None,
+            },
+        )
+        .await?;
+    assert_eq!(response.old_seat_number, None);
+    assert_eq!(seat_of(first).await?, Some(2));
+    assert_eq!(seat_of(second).await?, Some(1));
+
+    // A priced seat is charged to the ticket that takes it
+    let response = ctx
+        .payment_service
+        .change_seat(
+            &ticket_service,
+            user_id,
+            second,
+            SeatChangeRequest {
+                seat_number: Some(3),
+                payment_token: Some("tok_visa".to_string()),
+            },
+        )
+        .await?;
+    assert_eq!(response.old_seat_number, Some(1));
+    assert_eq!(response.amount_charged, Decimal::new(2500, 2));
+    let charged_ticket = sqlx::query_scalar!(
+        "SELECT ticket_id FROM seat_charge WHERE flight_id = ? AND status = 'CAPTURED'",
+        flight_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(charged_ticket, second);
+    assert_eq!(seat_of(first).await?, Some(2));
+
+    // Giving up the seat of one ticket keeps the other's
+    ctx.payment_service
+        .change_seat(
+            &ticket_service,
+            user_id,
+            first,
+            SeatChangeRequest {
+                seat_number: None,
+                payment_token: None,
+            },
+        )
+        .await?;
+    assert_eq!(seat_of(first).await?, None);
+    assert_eq!(seat_of(second).await?, Some(3));
+
+    Ok(())
+}
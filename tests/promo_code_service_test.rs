@@ -0,0 +1,338 @@
+use airline_booking_system::{
+    models::{
+        promo::{CreatePromoCodeRequest, DiscountType},
+        ticket::{BookingStatus, FlightBookingRequest, TicketBookingRequest},
+        user::{Role, UserRegistrationRequest},
+    },
+    services::{
+        payment_service::{MockPaymentProvider, PaymentService},
+        promo_code_service::PromoCodeService,
+        ticket_service::TicketService,
+        user_service::UserService,
+    },
+    utils::error::AppError,
+};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use ctor::dtor;
+use rust_decimal::Decimal;
+use sqlx::mysql::MySqlPool as Pool;
+use std::sync::Arc;
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct PromoCodeContext {
+    pool: Pool,
+    promo_code_service: PromoCodeService,
+    payment_service: PaymentService,
+    ticket_service: TicketService,
+    user_service: UserService,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for PromoCodeContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+
+        PromoCodeContext {
+            promo_code_service: PromoCodeService::new(pool.clone()),
+            payment_service: PaymentService::new(pool.clone(), Arc::new(MockPaymentProvider)),
+            ticket_service: TicketService::new(pool.clone()),
+            user_service: UserService::new(pool.clone()),
+            pool,
+        }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+impl PromoCodeContext {
+    async fn register(&self, username: &str, role: Role) -> Result<i32, AppError> {
+        self.user_service
+            .register_user(UserRegistrationRequest {
+                username: username.to_string(),
+                password: "test_password".to_string(),
+                role,
+                name: "Promo Test User".to_string(),
+                birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+                gender: "male".to_string(),
+                email: None,
+            })
+            .await
+    }
+
+    // Helper method to create a flight with a base fare of 200.00
+    async fn setup_flight(&self, flight_number: i32) -> Result<NaiveDate, AppError> {
+        let flight_date = (chrono::Utc::now() + chrono::Duration::days(10)).date_naive();
+        sqlx::query!(
+            "INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 5)",
+            flight_number
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO flight_route
+            (flight_number, departure_city, destination_city, departure_time, arrival_time,
+                aircraft_id, overbooking, start_date, end_date, base_fare)
+            VALUES
+            (?, 'Montreal', 'Vancouver', '09:00:00', '14:00:00', ?, 0.00, ?, ?, 200.00)
+            "#,
+            flight_number,
+            flight_number,
+            flight_date,
+            flight_date
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+            VALUES (?, ?, 5, 1)
+            "#,
+            flight_number,
+            flight_date
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(flight_date)
+    }
+
+    fn booking(
+        flight_number: i32,
+        flight_date: NaiveDate,
+        promo_code: &str,
+    ) -> TicketBookingRequest {
+        TicketBookingRequest {
+            flights: vec![FlightBookingRequest {
+                flight_number,
+                flight_date,
+                ..Default::default()
+            }],
+            promo_code: Some(promo_code.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+fn promo_code(
+    code: &str,
+    discount_type: DiscountType,
+    discount_value: i64,
+) -> CreatePromoCodeRequest {
+    CreatePromoCodeRequest {
+        code: code.to_string(),
+        discount_type,
+        discount_value: Decimal::new(discount_value, 0),
+        currency: None,
+        expires_at: None,
+        max_redemptions: None,
+        max_redemptions_per_customer: None,
+    }
+}
+
+#[test_context(PromoCodeContext)]
+#[tokio::test]
+async fn test_create_promo_code(ctx: &PromoCodeContext) -> Result<(), AppError> {
+    let admin_id = ctx.register("promo_create_admin", Role::Admin).await?;
+
+    let response = ctx
+        .promo_code_service
+        .create_promo_code(
+            admin_id,
+            promo_code(" spring25 ", DiscountType::Percentage, 25),
+        )
+        .await?;
+    assert_eq!(response.code, "SPRING25");
+    assert_eq!(response.redemptions, 0);
+
+    // Codes are unique regardless of case
+    let result = ctx
+        .promo_code_service
+        .create_promo_code(
+            admin_id,
+            promo_code("Spring25", DiscountType::Percentage, 10),
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+
+    let result = ctx
+        .promo_code_service
+        .create_promo_code(
+            admin_id,
+            promo_code("TOOMUCH", DiscountType::Percentage, 120),
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+    // A fixed discount needs its currency
+    let result = ctx
+        .promo_code_service
+        .create_promo_code(admin_id, promo_code("FIFTYOFF", DiscountType::Fixed, 50))
+        .await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+    let listed = ctx.promo_code_service.list_promo_codes().await?;
+    assert!(listed.iter().any(|code| code.code == "SPRING25"));
+
+    Ok(())
+}
+
+#[test_context(PromoCodeContext)]
+#[tokio::test]
+async fn test_booking_with_promo_code(ctx: &PromoCodeContext) -> Result<(), AppError> {
+    let admin_id = ctx.register("promo_booking_admin", Role::Admin).await?;
+    let user_id = ctx.register("promo_booking_user", Role::User).await?;
+    let other_user_id = ctx.register("promo_booking_other", Role::User).await?;
+    let flight_number = 1401;
+    let flight_date = ctx.setup_flight(flight_number).await?;
+
+    ctx.promo_code_service
+        .create_promo_code(
+            admin_id,
+            CreatePromoCodeRequest {
+                currency: Some("cad".to_string()),
+                max_redemptions: Some(1),
+                ..promo_code("FIFTYOFF", DiscountType::Fixed, 50)
+            },
+        )
+        .await?;
+
+    let response = ctx
+        .ticket_service
+        .book_ticket(
+            user_id,
+            PromoCodeContext::booking(flight_number, flight_date, "fiftyoff"),
+        )
+        .await?;
+    let applied = response.promo_code.expect("the promo code is applied");
+    assert_eq!(applied.discount, Decimal::new(50, 0));
+    let price = response.flight_bookings[0].price;
+    let payment = response.payment.expect("the rest of the fare is due");
+    assert_eq!(payment.amount, price);
+
+    let stored_price = sqlx::query_scalar!(
+        "SELECT price FROM ticket WHERE id = ?",
+        response.flight_bookings[0].ticket_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(
+        stored_price, price,
+        "Refunds give back the discounted price"
+    );
+
+    // The code was good for one booking only, and the failed booking takes no ticket
+    let result = ctx
+        .ticket_service
+        .book_ticket(
+            other_user_id,
+            PromoCodeContext::booking(flight_number, flight_date, "FIFTYOFF"),
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+    let available = sqlx::query_scalar!(
+        "SELECT available_tickets FROM flight WHERE flight_number = ?",
+        flight_number
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(available, 4);
+
+    let result = ctx
+        .ticket_service
+        .book_ticket(
+            other_user_id,
+            PromoCodeContext::booking(flight_number, flight_date, "NOSUCHCODE"),
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+    // An unpaid booking gives its redemption back when it expires
+    sqlx::query!(
+        "UPDATE payment SET expires_at = UTC_TIMESTAMP() - INTERVAL 1 MINUTE WHERE booking_id = ?",
+        response.booking_id
+    )
+    .execute(&ctx.pool)
+    .await?;
+    ctx.payment_service
+        .expire_unpaid_bookings(&ctx.ticket_service)
+        .await?;
+
+    let response = ctx
+        .ticket_service
+        .book_ticket(
+            other_user_id,
+            PromoCodeContext::booking(flight_number, flight_date, "FIFTYOFF"),
+        )
+        .await?;
+    assert!(response.promo_code.is_some());
+
+    Ok(())
+}
+
+#[test_context(PromoCodeContext)]
+#[tokio::test]
+async fn test_full_discount_confirms_booking(ctx: &PromoCodeContext) -> Result<(), AppError> {
+    let admin_id = ctx.register("promo_free_admin", Role::Admin).await?;
+    let user_id = ctx.register("promo_free_user", Role::User).await?;
+    let flight_number = 1402;
+    let flight_date = ctx.setup_flight(flight_number).await?;
+
+    ctx.promo_code_service
+        .create_promo_code(
+            admin_id,
+            CreatePromoCodeRequest {
+                max_redemptions_per_customer: Some(1),
+                ..promo_code("FREEFLIGHT", DiscountType::Percentage, 100)
+            },
+        )
+        .await?;
+
+    let response = ctx
+        .ticket_service
+        .book_ticket(
+            user_id,
+            PromoCodeContext::booking(flight_number, flight_date, "FREEFLIGHT"),
+        )
+        .await?;
+    assert!(response.payment.is_none());
+    assert_eq!(response.booking_status, BookingStatus::Confirmed);
+    assert_eq!(response.flight_bookings[0].price, Decimal::ZERO);
+
+    let status = sqlx::query_scalar!(
+        "SELECT status FROM booking WHERE id = ?",
+        response.booking_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(status, "CONFIRMED");
+
+    // Once per customer
+    let other_flight_date = ctx.setup_flight(1403).await?;
+    let result = ctx
+        .ticket_service
+        .book_ticket(
+            user_id,
+            PromoCodeContext::booking(1403, other_flight_date, "FREEFLIGHT"),
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+
+    Ok(())
+}
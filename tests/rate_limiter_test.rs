@@ -0,0 +1,91 @@
+use airline_booking_system::utils::rate_limiter::{
+    too_many_requests, BookingRateLimit, InMemoryRateLimitStore, RateLimitScope, RateLimitStore,
+    RateLimiter,
+};
+use airline_booking_system::utils::tunables::{SharedTunables, Tunables};
+use rocket::http::Status;
+use rocket::local::asynchronous::Client;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[rocket::post("/bookings")]
+fn create_booking(_rate_limit: BookingRateLimit) -> &'static str {
+    "booked"
+}
+
+fn limiter(booking_per_minute: u32) -> RateLimiter {
+    let tunables = SharedTunables::new(Tunables {
+        booking_per_minute,
+        ..Tunables::default()
+    });
+    RateLimiter::new(Arc::new(InMemoryRateLimitStore::new()), tunables)
+}
+
+async fn client(limiter: RateLimiter) -> Client {
+    let rocket = rocket::build()
+        .mount("/", rocket::routes![create_booking])
+        .register("/", rocket::catchers![too_many_requests])
+        .manage(limiter);
+    Client::tracked(rocket).await.expect("valid rocket")
+}
+
+#[rocket::async_test]
+async fn test_requests_over_the_rate_get_retry_after() {
+    let client = client(limiter(2)).await;
+    let from = |ip: &str| {
+        client
+            .post("/bookings")
+            .remote(format!("{}:4000", ip).parse().unwrap())
+    };
+
+    for _ in 0..2 {
+        assert_eq!(from("10.0.0.1").dispatch().await.status(), Status::Ok);
+    }
+    let response = from("10.0.0.1").dispatch().await;
+    assert_eq!(response.status(), Status::TooManyRequests);
+    // A token comes back every 30 seconds at 2 per minute
+    let retry_after: u64 = response
+        .headers()
+        .get_one("Retry-After")
+        .expect("Retry-After header")
+        .parse()
+        .unwrap();
+    assert!((1..=30).contains(&retry_after));
+    assert!(response
+        .into_string()
+        .await
+        .unwrap()
+        .contains("Too many requests"));
+
+    // Other clients have their own budget
+    assert_eq!(from("10.0.0.2").dispatch().await.status(), Status::Ok);
+}
+
+#[rocket::async_test]
+async fn test_zero_rate_disables_the_limit() {
+    let limiter = limiter(0);
+    for _ in 0..100 {
+        assert_eq!(
+            limiter.check(RateLimitScope::Booking, "ip:10.0.0.3").await,
+            None
+        );
+    }
+}
+
+#[rocket::async_test]
+async fn test_bucket_refills_over_time() {
+    let store = InMemoryRateLimitStore::new();
+    // 60 a minute is one every second
+    for _ in 0..60 {
+        assert_eq!(store.take("refill", 60).await, Ok(None));
+    }
+    let wait = store
+        .take("refill", 60)
+        .await
+        .unwrap()
+        .expect("empty bucket");
+    assert!(wait <= Duration::from_secs(1));
+
+    tokio::time::sleep(wait + Duration::from_millis(10)).await;
+    assert_eq!(store.take("refill", 60).await, Ok(None));
+}
@@ -0,0 +1,239 @@
+use airline_booking_system::{
+    models::{
+        payment::ConfirmPaymentRequest,
+        report::{FlightRevenue, ReportQuery},
+        ticket::{FlightBookingRequest, TicketBookingRequest, TicketBookingResponse},
+        user::{Role, UserRegistrationRequest},
+    },
+    services::{
+        payment_service::{MockPaymentProvider, PaymentService},
+        report_service::{check_range, ReportService},
+        ticket_service::TicketService,
+        user_service::UserService,
+    },
+    utils::{
+        csv::{self, CsvRow},
+        error::AppError,
+        ndjson::collect_rows,
+    },
+};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use ctor::dtor;
+use rust_decimal::Decimal;
+use sqlx::mysql::MySqlPool as Pool;
+use std::sync::Arc;
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct ReportContext {
+    pool: Pool,
+    report_service: ReportService,
+    payment_service: PaymentService,
+    ticket_service: TicketService,
+    user_service: UserService,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for ReportContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+
+        ReportContext {
+            report_service: ReportService::new(pool.clone()),
+            payment_service: PaymentService::new(pool.clone(), Arc::new(MockPaymentProvider)),
+            ticket_service: TicketService::new(pool.clone()),
+            user_service: UserService::new(pool.clone()),
+            pool,
+        }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+impl ReportContext {
+    // Route with a base fare of 250.00 flown by an aircraft of 5 seats on the date
+    async fn create_flight(
+        &self,
+        flight_number: i32,
+        flight_date: NaiveDate,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            "INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 5)",
+            flight_number
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO flight_route
+            (flight_number, departure_city, destination_city, departure_time, arrival_time,
+                aircraft_id, overbooking, start_date, end_date, base_fare)
+            VALUES
+            (?, 'Regina', 'Calgary', '08:00:00', '09:45:00', ?, 0.00, ?, ?, 250.00)
+            "#,
+            flight_number,
+            flight_number,
+            flight_date,
+            flight_date
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+            VALUES (?, ?, 5, 1)
+            "#,
+            flight_number,
+            flight_date
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn book(
+        &self,
+        flight_number: i32,
+        flight_date: NaiveDate,
+        username: &str,
+    ) -> Result<(i32, TicketBookingResponse), AppError> {
+        let user_id = self
+            .user_service
+            .register_user(UserRegistrationRequest {
+                username: username.to_string(),
+                password: "test_password".to_string(),
+                role: Role::User,
+                name: "Report Test User".to_string(),
+                birth_date: NaiveDate::from_ymd_opt(1979, 2, 14).unwrap(),
+                gender: "male".to_string(),
+                email: None,
+            })
+            .await?;
+        let response = self
+            .ticket_service
+            .book_ticket(
+                user_id,
+                TicketBookingRequest {
+                    flights: vec![FlightBookingRequest {
+                        flight_number,
+                        flight_date,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            )
+            .await?;
+        Ok((user_id, response))
+    }
+}
+
+#[test_context(ReportContext)]
+#[tokio::test]
+async fn test_occupancy_and_revenue_reports(ctx: &ReportContext) -> Result<(), AppError> {
+    let flight_number = 1901;
+    let flight_date = (chrono::Utc::now() + chrono::Duration::days(20)).date_naive();
+    ctx.create_flight(flight_number, flight_date).await?;
+
+    let (paid_user, paid) = ctx
+        .book(flight_number, flight_date, "report_paid_user")
+        .await?;
+    ctx.payment_service
+        .confirm_payment(
+            paid_user,
+            paid.booking_id,
+            ConfirmPaymentRequest {
+                payment_token: "tok_visa".to_string(),
+            },
+        )
+        .await?;
+    ctx.book(flight_number, flight_date, "report_unpaid_user")
+        .await?;
+
+    let query = ReportQuery {
+        from: flight_date,
+        to: flight_date,
+        flight_number: Some(flight_number),
+    };
+
+    // Both tickets take a seat
+    let occupancy = ctx.report_service.occupancy_report(query.clone()).await?;
+    assert_eq!(occupancy.flights.len(), 1);
+    assert_eq!(occupancy.flights[0].capacity, 5);
+    assert_eq!(occupancy.flights[0].tickets_sold, 2);
+    assert!((occupancy.flights[0].load_factor - 0.4).abs() < 1e-9);
+    assert_eq!(occupancy.routes.len(), 1);
+    assert_eq!(occupancy.routes[0].flights, 1);
+    assert_eq!(occupancy.routes[0].tickets_sold, 2);
+
+    // Only the paid one is revenue
+    let revenue = ctx.report_service.revenue_report(query.clone()).await?;
+    assert_eq!(revenue.flights.len(), 1);
+    let flight = &revenue.flights[0];
+    assert_eq!(flight.tickets_sold, 1);
+    assert_eq!(flight.ticket_revenue, paid.flight_bookings[0].price);
+    assert_eq!(flight.seat_revenue, Decimal::ZERO);
+    assert_eq!(flight.total_revenue, flight.ticket_revenue);
+    assert_eq!(revenue.routes[0].total_revenue, flight.total_revenue);
+
+    // The NDJSON reports stream the same flights
+    let streamed =
+        collect_rows(|sink| ctx.report_service.export_revenue(query.clone(), sink)).await?;
+    assert_eq!(streamed.len(), 1);
+    assert_eq!(streamed[0].total_revenue, flight.total_revenue);
+    let streamed =
+        collect_rows(|sink| ctx.report_service.export_occupancy(query.clone(), sink)).await?;
+    assert_eq!(streamed.len(), 1);
+    assert_eq!(streamed[0].tickets_sold, 2);
+
+    let csv = csv::to_csv(&revenue.flights);
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some(FlightRevenue::HEADER.join(",").as_str()));
+    assert!(lines.next().unwrap().starts_with(&format!(
+        "{},{},Regina,Calgary,",
+        flight_number, flight_date
+    )));
+    assert_eq!(lines.next(), None);
+
+    Ok(())
+}
+
+#[test_context(ReportContext)]
+#[tokio::test]
+async fn test_report_range_is_checked(ctx: &ReportContext) -> Result<(), AppError> {
+    let from = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+    let backwards = ReportQuery {
+        from,
+        to: from - chrono::Duration::days(1),
+        flight_number: None,
+    };
+    let result = ctx.report_service.occupancy_report(backwards).await;
+    assert!(matches!(result, Err(AppError::BadRequest(_))));
+
+    let too_long = ReportQuery {
+        from,
+        to: from + chrono::Duration::days(400),
+        flight_number: None,
+    };
+    let result = ctx.report_service.revenue_report(too_long.clone()).await;
+    assert!(matches!(result, Err(AppError::BadRequest(_))));
+    let result = check_range(&too_long);
+    assert!(matches!(result, Err(AppError::BadRequest(_))));
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use airline_booking_system::utils::retry::{RetryMetrics, RetryPolicy};
+use std::time::Duration;
+
+#[test]
+fn test_backoff_doubles_up_to_the_max_delay() {
+    let policy = RetryPolicy {
+        max_attempts: 10,
+        base_delay: Duration::from_millis(5),
+        max_delay: Duration::from_millis(30),
+    };
+    let ceilings: Vec<u128> = (1..=6)
+        .map(|attempt| policy.delay_ceiling(attempt).as_millis())
+        .collect();
+    assert_eq!(ceilings, vec![5, 10, 20, 30, 30, 30]);
+    assert_eq!(policy.delay_ceiling(u32::MAX), Duration::from_millis(30));
+
+    // The jitter stays under the ceiling
+    for attempt in 1..=6 {
+        assert!(policy.delay(attempt) <= policy.delay_ceiling(attempt));
+    }
+}
+
+#[tokio::test]
+async fn test_retries_stop_after_max_attempts() {
+    let policy = RetryPolicy {
+        base_delay: Duration::from_millis(1),
+        ..RetryPolicy::new(3)
+    };
+    let metrics = RetryMetrics::new();
+
+    let mut retry = policy.start(&metrics);
+    assert!(retry.backoff().await);
+    assert!(retry.backoff().await);
+    assert!(!retry.backoff().await);
+    assert_eq!(retry.failed_attempts(), 3);
+
+    // Another run that succeeds on its second attempt
+    let mut retry = policy.start(&metrics);
+    assert!(retry.backoff().await);
+
+    let stats = metrics.stats();
+    assert_eq!((stats.retries, stats.exhausted), (3, 1));
+    assert!(stats.backoff <= Duration::from_millis(1 + 2 + 1));
+
+    // A single attempt is never retried
+    let mut retry = RetryPolicy::new(0).start(&metrics);
+    assert!(!retry.backoff().await);
+    assert_eq!(metrics.stats().exhausted, 2);
+}
@@ -0,0 +1,311 @@
+use airline_booking_system::{
+    models::forecast::PaceFlag,
+    services::{
+        event_bus::{DomainEvent, EventBus},
+        route_stats_service::RouteStatsService,
+    },
+    utils::error::AppError,
+};
+use async_trait::async_trait;
+use chrono::{Duration, NaiveDate};
+use ctor::dtor;
+use sqlx::mysql::MySqlPool as Pool;
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct RouteStatsContext {
+    pool: Pool,
+    route_stats_service: RouteStatsService,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for RouteStatsContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+        RouteStatsContext {
+            route_stats_service: RouteStatsService::new(pool.clone()),
+            pool,
+        }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+async fn setup_route(
+    pool: &Pool,
+    flight_number: i32,
+    start_date: NaiveDate,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        "INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 10)",
+        flight_number
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, overbooking, start_date)
+        VALUES
+        (?, 'Toronto', 'Winnipeg', '08:00:00', '10:30:00', ?, 0.00, ?)
+        "#,
+        flight_number,
+        flight_number,
+        start_date
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Flight of the route with the bookings made on each number of days before departure
+async fn setup_flight(
+    pool: &Pool,
+    flight_number: i32,
+    flight_date: NaiveDate,
+    status: &str,
+    curve: &[(i32, i32)],
+) -> Result<(), AppError> {
+    let flight_id = sqlx::query!(
+        r#"
+        INSERT INTO flight (flight_number, flight_date, available_tickets, version, status)
+        VALUES (?, ?, 10, 1, ?)
+        "#,
+        flight_number,
+        flight_date,
+        status
+    )
+    .execute(pool)
+    .await?
+    .last_insert_id() as i32;
+    for (days_before_departure, bookings) in curve {
+        sqlx::query!(
+            r#"
+            INSERT INTO booking_curve (flight_id, days_before_departure, bookings)
+            VALUES (?, ?, ?)
+            "#,
+            flight_id,
+            days_before_departure,
+            bookings
+        )
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+#[test_context(RouteStatsContext)]
+#[tokio::test]
+async fn test_route_forecast_flags_unusual_pace(ctx: &RouteStatsContext) -> Result<(), AppError> {
+    let flight_number = 1601;
+    let today = chrono::Utc::now().date_naive();
+    setup_route(&ctx.pool, flight_number, today - Duration::days(40)).await?;
+
+    // Two departed flights ended with 10 and 8 bookings, 4 of each made a week out
+    setup_flight(
+        &ctx.pool,
+        flight_number,
+        today - Duration::days(30),
+        "DEPARTED",
+        &[(20, 2), (10, 2), (3, 4), (0, 2)],
+    )
+    .await?;
+    setup_flight(
+        &ctx.pool,
+        flight_number,
+        today - Duration::days(23),
+        "DEPARTED",
+        &[(14, 2), (7, 2), (1, 4)],
+    )
+    .await?;
+    // Cancelled flights are not part of the history
+    setup_flight(
+        &ctx.pool,
+        flight_number,
+        today - Duration::days(16),
+        "CANCELLED",
+        &[(30, 10)],
+    )
+    .await?;
+
+    setup_flight(
+        &ctx.pool,
+        flight_number,
+        today + Duration::days(7),
+        "SCHEDULED",
+        &[(10, 8)],
+    )
+    .await?;
+    setup_flight(
+        &ctx.pool,
+        flight_number,
+        today + Duration::days(14),
+        "SCHEDULED",
+        &[(20, 1)],
+    )
+    .await?;
+    setup_flight(
+        &ctx.pool,
+        flight_number,
+        today + Duration::days(30),
+        "SCHEDULED",
+        &[],
+    )
+    .await?;
+
+    let forecast = ctx
+        .route_stats_service
+        .route_forecast(flight_number)
+        .await?;
+    assert_eq!(forecast.departure_city, "Toronto");
+    assert_eq!(forecast.history_flights, 2);
+    assert_eq!(forecast.flights.len(), 3);
+
+    // 8 booked against 4 expected a week out, and 5 more bookings to come
+    let busy = &forecast.flights[0];
+    assert_eq!(busy.days_to_departure, 7);
+    assert_eq!(busy.booked, 8);
+    assert_eq!(busy.projection.expected_booked, Some(4.0));
+    assert_eq!(busy.projection.pace, Some(2.0));
+    assert_eq!(busy.projection.flag, PaceFlag::High);
+    assert_eq!(busy.projection.projected_bookings, 13);
+    assert!((busy.projected_load_factor - 1.3).abs() < 1e-9);
+
+    // 1 booked against 2 expected two weeks out
+    let slow = &forecast.flights[1];
+    assert_eq!(slow.booked, 1);
+    assert_eq!(slow.projection.pace, Some(0.5));
+    assert_eq!(slow.projection.flag, PaceFlag::Low);
+    assert_eq!(slow.projection.projected_bookings, 8);
+
+    // Nothing was ever booked a month out
+    let early = &forecast.flights[2];
+    assert_eq!(early.booked, 0);
+    assert_eq!(early.projection.expected_booked, Some(0.0));
+    assert_eq!(early.projection.pace, None);
+    assert_eq!(early.projection.flag, PaceFlag::Normal);
+    assert_eq!(early.projection.projected_bookings, 9);
+
+    Ok(())
+}
+
+#[test_context(RouteStatsContext)]
+#[tokio::test]
+async fn test_route_forecast_without_history(ctx: &RouteStatsContext) -> Result<(), AppError> {
+    let flight_number = 1602;
+    let today = chrono::Utc::now().date_naive();
+    setup_route(&ctx.pool, flight_number, today).await?;
+    setup_flight(
+        &ctx.pool,
+        flight_number,
+        today + Duration::days(5),
+        "SCHEDULED",
+        &[(6, 3)],
+    )
+    .await?;
+
+    let forecast = ctx
+        .route_stats_service
+        .route_forecast(flight_number)
+        .await?;
+    assert_eq!(forecast.history_flights, 0);
+    let flight = &forecast.flights[0];
+    assert_eq!(flight.projection.expected_booked, None);
+    assert_eq!(flight.projection.flag, PaceFlag::Normal);
+    assert_eq!(flight.projection.projected_bookings, 3);
+
+    let result = ctx.route_stats_service.route_forecast(-1).await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    Ok(())
+}
+
+#[test_context(RouteStatsContext)]
+#[tokio::test]
+async fn test_booking_event_updates_route_stats(ctx: &RouteStatsContext) -> Result<(), AppError> {
+    let flight_number = 1603;
+    let today = chrono::Utc::now().date_naive();
+    let flight_date = today + Duration::days(5);
+    setup_route(&ctx.pool, flight_number, today).await?;
+    sqlx::query!(
+        r#"
+        UPDATE flight_route SET departure_city = 'Regina', destination_city = 'Saskatoon'
+        WHERE flight_number = ?
+        "#,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+    setup_flight(&ctx.pool, flight_number, flight_date, "SCHEDULED", &[]).await?;
+    let flight_id = sqlx::query_scalar!(
+        "SELECT flight_id FROM flight WHERE flight_number = ?",
+        flight_number
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+
+    let event_bus = EventBus::new();
+    ctx.route_stats_service.spawn_aggregator(&event_bus);
+    event_bus.publish(DomainEvent::TicketBooked {
+        ticket_id: 1,
+        customer_id: 1,
+        flight_id,
+        flight_number,
+        flight_date,
+        experiments: Vec::new(),
+    });
+
+    // The aggregator counts the booking in the background, on the booking curve last
+    let mut booked = 0;
+    for _ in 0..50 {
+        booked = ctx
+            .route_stats_service
+            .route_forecast(flight_number)
+            .await?
+            .flights[0]
+            .booked;
+        if booked > 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(booked, 1);
+
+    let destinations = ctx
+        .route_stats_service
+        .trending_destinations(1, Some("Regina".to_string()), 10)
+        .await?
+        .destinations;
+    assert_eq!(destinations.len(), 1);
+    assert_eq!(destinations[0].destination_city, "Saskatoon");
+    assert_eq!(destinations[0].bookings, 1);
+    assert_eq!(destinations[0].searches, 0);
+
+    // Booked five days before departure
+    let curve = sqlx::query!(
+        "SELECT days_before_departure, bookings FROM booking_curve WHERE flight_id = ?",
+        flight_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(curve.days_before_departure, 5);
+    assert_eq!(curve.bookings, 1);
+
+    Ok(())
+}
@@ -0,0 +1,240 @@
+use airline_booking_system::{
+    models::{
+        booking_rules::BookingRules,
+        sandbox::{SandboxBookingRequest, SandboxPassenger},
+        ticket::{FlightBookingRequest, TicketBookingRequest},
+    },
+    services::{
+        partner_service::{Partner, PartnerService},
+        sandbox_service::SandboxService,
+        ticket_service::TicketService,
+        user_service::UserService,
+    },
+    utils::error::AppError,
+};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use ctor::dtor;
+use sqlx::mysql::MySqlPool as Pool;
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct SandboxContext {
+    pool: Pool,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for SandboxContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+        SandboxContext { pool }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+async fn setup_flight(
+    pool: &Pool,
+    flight_number: i32,
+    flight_date: NaiveDate,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        "INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, 5)",
+        flight_number
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, overbooking, start_date, end_date)
+        VALUES
+        (?, 'Toronto', 'Halifax', '07:00:00', '09:30:00', ?, 0.00, ?, ?)
+        "#,
+        flight_number,
+        flight_number,
+        flight_date,
+        flight_date
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+        VALUES (?, ?, 5, 1)
+        "#,
+        flight_number,
+        flight_date
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn available_tickets(
+    pool: &Pool,
+    flight_number: i32,
+    flight_date: NaiveDate,
+) -> Result<i32, AppError> {
+    let available = sqlx::query_scalar!(
+        "SELECT available_tickets FROM flight WHERE flight_number = ? AND flight_date = ?",
+        flight_number,
+        flight_date
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(available)
+}
+
+async fn tickets(pool: &Pool, flight_number: i32) -> Result<i64, AppError> {
+    let tickets = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM ticket WHERE flight_number = ?",
+        flight_number
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(tickets)
+}
+
+#[test_context(SandboxContext)]
+#[tokio::test]
+async fn test_sandbox_bookings_stay_in_sandbox(ctx: &SandboxContext) -> Result<(), AppError> {
+    let flight_number = 881;
+    let flight_date = NaiveDate::from_ymd_opt(2025, 5, 6).unwrap();
+    setup_flight(&ctx.pool, flight_number, flight_date).await?;
+
+    // A second database with the same schema stands in for the sandbox database
+    let snapshot = TestDb::snapshot(&ctx.pool, "sandbox").await?;
+    let sandbox_pool = TestDb::connect_snapshot(&snapshot).await?;
+    let sandbox_service = SandboxService::new(ctx.pool.clone(), sandbox_pool.clone());
+
+    let reset = sandbox_service.reset().await?;
+    assert!(reset
+        .copied
+        .iter()
+        .any(|copy| copy.table == "flight" && copy.rows >= 1));
+
+    let partner = Partner {
+        key_id: 1,
+        partner_name: "Sandbox Travel".to_string(),
+    };
+    let response = sandbox_service
+        .book(
+            &partner,
+            SandboxBookingRequest {
+                passenger: SandboxPassenger {
+                    name: "Sandbox Passenger".to_string(),
+                    birth_date: NaiveDate::from_ymd_opt(1985, 3, 4).unwrap(),
+                    gender: "female".to_string(),
+                },
+                booking: TicketBookingRequest {
+                    flights: vec![FlightBookingRequest {
+                        flight_number,
+                        flight_date,
+                        preferred_seat: None,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            },
+        )
+        .await?;
+    assert_eq!(response.flight_bookings.len(), 1);
+
+    assert_eq!(tickets(&sandbox_pool, flight_number).await?, 1);
+    assert_eq!(
+        available_tickets(&sandbox_pool, flight_number, flight_date).await?,
+        4
+    );
+    // The live flight did not change
+    assert_eq!(tickets(&ctx.pool, flight_number).await?, 0);
+    assert_eq!(
+        available_tickets(&ctx.pool, flight_number, flight_date).await?,
+        5
+    );
+
+    // Resetting throws the sandbox bookings away
+    sandbox_service.reset().await?;
+    assert_eq!(tickets(&sandbox_pool, flight_number).await?, 0);
+    assert_eq!(
+        available_tickets(&sandbox_pool, flight_number, flight_date).await?,
+        5
+    );
+
+    Ok(())
+}
+
+#[test_context(SandboxContext)]
+#[tokio::test]
+async fn test_live_database_is_never_reset(ctx: &SandboxContext) -> Result<(), AppError> {
+    let sandbox_service = SandboxService::new(ctx.pool.clone(), ctx.pool.clone());
+    let result = sandbox_service.reset().await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+    Ok(())
+}
+
+#[test_context(SandboxContext)]
+#[tokio::test]
+async fn test_sandbox_books_like_live_service(ctx: &SandboxContext) -> Result<(), AppError> {
+    let flight_number = 882;
+    let flight_date = NaiveDate::from_ymd_opt(2025, 5, 7).unwrap();
+    setup_flight(&ctx.pool, flight_number, flight_date).await?;
+
+    let partner = Partner {
+        key_id: 1,
+        partner_name: "Sandbox Travel".to_string(),
+    };
+    let request = || SandboxBookingRequest {
+        passenger: SandboxPassenger {
+            name: "Sandbox Passenger".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1985, 3, 4).unwrap(),
+            gender: "female".to_string(),
+        },
+        booking: TicketBookingRequest {
+            flights: vec![FlightBookingRequest {
+                flight_number,
+                flight_date,
+                preferred_seat: None,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    };
+
+    // The live service closed sales of this past flight under a purchase window
+    let ticket_service = TicketService::new(ctx.pool.clone()).with_rules(BookingRules::from_toml(
+        "[advance_purchase]\nmin_hours_before_departure = 2",
+    )?);
+    let sandbox_service = SandboxService::new(ctx.pool.clone(), ctx.pool.clone())
+        .with_live_configuration(
+            &ticket_service,
+            &UserService::new(ctx.pool.clone()),
+            &PartnerService::new(ctx.pool.clone()),
+        );
+    let result = sandbox_service.book(&partner, request()).await;
+    assert!(matches!(result, Err(AppError::BadRequest(_))));
+    assert_eq!(tickets(&ctx.pool, flight_number).await?, 0);
+
+    // A sandbox left with the built-in policy books it
+    let sandbox_service = SandboxService::new(ctx.pool.clone(), ctx.pool.clone());
+    sandbox_service.book(&partner, request()).await?;
+    assert_eq!(tickets(&ctx.pool, flight_number).await?, 1);
+
+    Ok(())
+}
@@ -0,0 +1,226 @@
+use airline_booking_system::{
+    services::schedule_service::ScheduleService, utils::error::AppError,
+};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use ctor::dtor;
+use sqlx::mysql::MySqlPool as Pool;
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct ScheduleServiceContext {
+    pool: Pool,
+    schedule_service: ScheduleService,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for ScheduleServiceContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+
+        let schedule_service = ScheduleService::new(pool.clone());
+
+        ScheduleServiceContext {
+            pool,
+            schedule_service,
+        }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+impl ScheduleServiceContext {
+    // Helper method to create a route operating between start_date and end_date
+    async fn create_route(
+        &self,
+        flight_number: i32,
+        capacity: i32,
+        start_date: NaiveDate,
+        end_date: Option<NaiveDate>,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, ?)"#,
+            flight_number,
+            capacity
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO flight_route
+            (flight_number, departure_city, destination_city, departure_time, arrival_time,
+                aircraft_id, overbooking, start_date, end_date)
+            VALUES
+            (?, 'Toronto', 'Vancouver', '08:00:00', '10:30:00',
+                ?, 0.00, ?, ?)
+            "#,
+            flight_number,
+            flight_number,
+            start_date,
+            end_date
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[test_context(ScheduleServiceContext)]
+#[tokio::test]
+async fn test_generate_flights_within_route_dates(
+    ctx: &ScheduleServiceContext,
+) -> Result<(), AppError> {
+    let flight_number = 601;
+    let capacity = 12;
+    let start_date = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+    let end_date = NaiveDate::from_ymd_opt(2025, 3, 5).unwrap();
+    ctx.create_route(flight_number, capacity, start_date, Some(end_date))
+        .await?;
+
+    // The requested window exceeds the route dates on both sides
+    let generated = ctx
+        .schedule_service
+        .generate_flights_for_route(
+            flight_number,
+            NaiveDate::from_ymd_opt(2025, 2, 25).unwrap(),
+            NaiveDate::from_ymd_opt(2025, 3, 10).unwrap(),
+        )
+        .await?;
+    assert_eq!(generated, 5, "One flight per active day should be created");
+
+    let seats = sqlx::query!(
+        r#"
+        SELECT COUNT(*) as count
+        FROM seat_info s
+        JOIN flight f ON s.flight_id = f.flight_id
+        WHERE f.flight_number = ?
+        "#,
+        flight_number
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(seats.count, (5 * capacity) as i64);
+
+    // Running again does not duplicate flights
+    let generated = ctx
+        .schedule_service
+        .generate_flights_for_route(flight_number, start_date, end_date)
+        .await?;
+    assert_eq!(generated, 0);
+
+    Ok(())
+}
+
+#[test_context(ScheduleServiceContext)]
+#[tokio::test]
+async fn test_generate_flights_on_operating_days(
+    ctx: &ScheduleServiceContext,
+) -> Result<(), AppError> {
+    let flight_number = 602;
+    // Saturday to Saturday, both ends included
+    let start_date = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+    let end_date = NaiveDate::from_ymd_opt(2025, 3, 15).unwrap();
+    ctx.create_route(flight_number, 4, start_date, Some(end_date))
+        .await?;
+
+    // Weekends only
+    sqlx::query!(
+        "UPDATE flight_route SET operating_days = '67' WHERE flight_number = ?",
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    let generated = ctx
+        .schedule_service
+        .generate_flights_for_route(
+            flight_number,
+            NaiveDate::from_ymd_opt(2025, 2, 22).unwrap(),
+            NaiveDate::from_ymd_opt(2025, 3, 22).unwrap(),
+        )
+        .await?;
+    assert_eq!(generated, 5);
+
+    let dates: Vec<NaiveDate> = sqlx::query!(
+        r#"
+        SELECT flight_date as "flight_date: NaiveDate"
+        FROM flight
+        WHERE flight_number = ?
+        ORDER BY flight_date
+        "#,
+        flight_number
+    )
+    .fetch_all(&ctx.pool)
+    .await?
+    .into_iter()
+    .map(|row| row.flight_date)
+    .collect();
+    assert_eq!(
+        dates,
+        [1, 2, 8, 9, 15]
+            .iter()
+            .map(|day| NaiveDate::from_ymd_opt(2025, 3, *day).unwrap())
+            .collect::<Vec<_>>()
+    );
+
+    Ok(())
+}
+
+#[test_context(ScheduleServiceContext)]
+#[tokio::test]
+async fn test_generated_flights_are_overbooked(
+    ctx: &ScheduleServiceContext,
+) -> Result<(), AppError> {
+    let flight_number = 603;
+    let flight_date = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+    ctx.create_route(flight_number, 10, flight_date, Some(flight_date))
+        .await?;
+
+    sqlx::query!(
+        "UPDATE flight_route SET overbooking = 0.15 WHERE flight_number = ?",
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    let generated = ctx
+        .schedule_service
+        .generate_flights_for_route(flight_number, flight_date, flight_date)
+        .await?;
+    assert_eq!(generated, 1);
+
+    // 15% of 10 seats rounds down to one extra ticket, seats stay those of the aircraft
+    let flight = sqlx::query!(
+        r#"
+        SELECT
+            f.available_tickets,
+            (SELECT COUNT(*) FROM seat_info s WHERE s.flight_id = f.flight_id) as "seats!: i64"
+        FROM flight f
+        WHERE f.flight_number = ?
+        "#,
+        flight_number
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(flight.available_tickets, 11);
+    assert_eq!(flight.seats, 10);
+
+    Ok(())
+}
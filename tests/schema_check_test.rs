@@ -0,0 +1,72 @@
+use airline_booking_system::utils::{
+    error::AppError,
+    schema_check::{self, SchemaDrift},
+};
+use async_trait::async_trait;
+use ctor::dtor;
+use sqlx::mysql::MySqlPool as Pool;
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct SchemaCheckContext {
+    pool: Pool,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for SchemaCheckContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+
+        SchemaCheckContext { pool }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+#[test_context(SchemaCheckContext)]
+#[tokio::test]
+async fn test_schema_drift(ctx: &SchemaCheckContext) -> Result<(), AppError> {
+    let expected = schema_check::expected_columns();
+    assert!(expected
+        .iter()
+        .any(|column| column.table == "ticket" && column.column == "seat_number"));
+
+    // The test database is created by the same migrations
+    let drifts = schema_check::check_schema(&ctx.pool).await?;
+    assert!(drifts.is_empty(), "Unexpected drift: {:?}", drifts);
+
+    sqlx::query("ALTER TABLE location DROP COLUMN name")
+        .execute(&ctx.pool)
+        .await?;
+    sqlx::query("ALTER TABLE aircraft MODIFY COLUMN exit_rows INT NULL")
+        .execute(&ctx.pool)
+        .await?;
+
+    let drifts = schema_check::check_schema(&ctx.pool).await?;
+    assert_eq!(drifts.len(), 2);
+    assert!(drifts.iter().any(|drift| matches!(
+        drift,
+        SchemaDrift::MissingColumn { table, .. } if table == "location"
+    )));
+    assert!(drifts.iter().any(|drift| matches!(
+        drift,
+        SchemaDrift::TypeMismatch { table, column, .. } if table == "aircraft" && column == "exit_rows"
+    )));
+
+    Ok(())
+}
@@ -0,0 +1,289 @@
+use airline_booking_system::{
+    models::{
+        seat_block::{CreateSeatBlockRuleRequest, SeatBlockRuleKind},
+        user::{Role, UserRegistrationRequest},
+    },
+    services::{
+        schedule_service::ScheduleService, seat_block_service::SeatBlockService,
+        user_service::UserService,
+    },
+    utils::error::AppError,
+};
+use async_trait::async_trait;
+use chrono::{Duration, NaiveDate};
+use ctor::dtor;
+use sqlx::mysql::MySqlPool as Pool;
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct SeatBlockContext {
+    pool: Pool,
+    seat_block_service: SeatBlockService,
+    schedule_service: ScheduleService,
+    user_service: UserService,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for SeatBlockContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+
+        SeatBlockContext {
+            seat_block_service: SeatBlockService::new(pool.clone()),
+            schedule_service: ScheduleService::new(pool.clone()),
+            user_service: UserService::new(pool.clone()),
+            pool,
+        }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+impl SeatBlockContext {
+    async fn register_admin(&self, username: &str) -> Result<i32, AppError> {
+        self.user_service
+            .register_user(UserRegistrationRequest {
+                username: username.to_string(),
+                password: "test_password".to_string(),
+                role: Role::Admin,
+                name: "Seat Block Admin".to_string(),
+                birth_date: NaiveDate::from_ymd_opt(1985, 6, 1).unwrap(),
+                gender: "female".to_string(),
+                email: None,
+            })
+            .await
+    }
+
+    // Daily route flown by an aircraft of 12 seats in two rows of 6, so seats 2, 5, 8
+    // and 11 are middle seats and 7 to 12 make up the last row
+    async fn create_route(
+        &self,
+        flight_number: i32,
+        start_date: NaiveDate,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            "INSERT INTO aircraft (aircraft_id, capacity, seats_per_row) VALUES (?, 12, 6)",
+            flight_number
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO flight_route
+            (flight_number, departure_city, destination_city, departure_time, arrival_time,
+                aircraft_id, overbooking, start_date)
+            VALUES
+            (?, 'Toronto', 'Ottawa', '08:00:00', '09:00:00', ?, 0.00, ?)
+            "#,
+            flight_number,
+            flight_number,
+            start_date
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn generate(&self, flight_number: i32, flight_date: NaiveDate) -> Result<(), AppError> {
+        self.schedule_service
+            .generate_flights_for_route(flight_number, flight_date, flight_date)
+            .await?;
+        Ok(())
+    }
+
+    // Tickets left for sale and the seats taken out of sale on the flight
+    async fn inventory(
+        &self,
+        flight_number: i32,
+        flight_date: NaiveDate,
+    ) -> Result<(i32, Vec<i32>), AppError> {
+        let flight = sqlx::query!(
+            "SELECT flight_id, available_tickets FROM flight WHERE flight_number = ? AND flight_date = ?",
+            flight_number,
+            flight_date
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        let unavailable = sqlx::query_scalar!(
+            r#"
+            SELECT seat_number
+            FROM seat_info
+            WHERE flight_id = ? AND seat_status = 'UNAVAILABLE'
+            ORDER BY seat_number
+            "#,
+            flight.flight_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok((flight.available_tickets, unavailable))
+    }
+}
+
+#[test_context(SeatBlockContext)]
+#[tokio::test]
+async fn test_rules_block_seats_of_generated_flights(
+    ctx: &SeatBlockContext,
+) -> Result<(), AppError> {
+    let admin_id = ctx.register_admin("seat_block_admin").await?;
+    let flight_number = 1701;
+    let today = chrono::Utc::now().date_naive();
+    ctx.create_route(flight_number, today).await?;
+    let in_range = today + Duration::days(5);
+    let after_range = today + Duration::days(11);
+
+    let middle = ctx
+        .seat_block_service
+        .create_rule(
+            admin_id,
+            flight_number,
+            CreateSeatBlockRuleRequest {
+                rule: SeatBlockRuleKind::MiddleSeats,
+                start_date: today + Duration::days(1),
+                end_date: Some(today + Duration::days(10)),
+            },
+        )
+        .await?;
+    assert_eq!(middle.updated_flights, 0);
+
+    // Flights generated in the date range of the rule get it
+    ctx.generate(flight_number, in_range).await?;
+    ctx.generate(flight_number, after_range).await?;
+    assert_eq!(
+        ctx.inventory(flight_number, in_range).await?,
+        (8, vec![2, 5, 8, 11])
+    );
+    assert_eq!(
+        ctx.inventory(flight_number, after_range).await?,
+        (12, vec![])
+    );
+
+    // A new rule is applied to the open flights, sharing no seat with the other rule
+    let last_row = ctx
+        .seat_block_service
+        .create_rule(
+            admin_id,
+            flight_number,
+            CreateSeatBlockRuleRequest {
+                rule: SeatBlockRuleKind::LastRow,
+                start_date: today,
+                end_date: None,
+            },
+        )
+        .await?;
+    assert_eq!(last_row.updated_flights, 2);
+    assert_eq!(last_row.seats, 10);
+    assert_eq!(
+        ctx.inventory(flight_number, in_range).await?,
+        (4, vec![2, 5, 7, 8, 9, 10, 11, 12])
+    );
+
+    // Disabling the middle seat rule gives back the seats the last row rule does not cover
+    let disabled = ctx
+        .seat_block_service
+        .set_enabled(middle.rule.rule_id, false)
+        .await?;
+    assert!(!disabled.rule.enabled);
+    assert_eq!(disabled.updated_flights, 1);
+    assert_eq!(disabled.seats, 2);
+    assert_eq!(
+        ctx.inventory(flight_number, in_range).await?,
+        (6, vec![7, 8, 9, 10, 11, 12])
+    );
+
+    let rules = ctx.seat_block_service.list_rules(flight_number).await?;
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[0].blocked_seats, 0);
+    assert_eq!(rules[1].blocked_seats, 12);
+
+    // Turning it back on blocks the middle seats of the first row again
+    let enabled = ctx
+        .seat_block_service
+        .set_enabled(middle.rule.rule_id, true)
+        .await?;
+    assert_eq!(enabled.seats, 2);
+    assert_eq!(ctx.inventory(flight_number, in_range).await?.0, 4);
+
+    Ok(())
+}
+
+#[test_context(SeatBlockContext)]
+#[tokio::test]
+async fn test_rules_leave_booked_seats_alone(ctx: &SeatBlockContext) -> Result<(), AppError> {
+    let admin_id = ctx.register_admin("seat_block_booked_admin").await?;
+    let flight_number = 1702;
+    let today = chrono::Utc::now().date_naive();
+    let flight_date = today + Duration::days(3);
+    ctx.create_route(flight_number, today).await?;
+    ctx.generate(flight_number, flight_date).await?;
+
+    // Seat 2 was sold before the rule
+    sqlx::query!(
+        r#"
+        UPDATE seat_info s
+        JOIN flight f ON s.flight_id = f.flight_id
+        SET s.seat_status = 'BOOKED'
+        WHERE f.flight_number = ? AND s.seat_number = 2
+        "#,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+    sqlx::query!(
+        "UPDATE flight SET available_tickets = 11 WHERE flight_number = ?",
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    let rule = ctx
+        .seat_block_service
+        .create_rule(
+            admin_id,
+            flight_number,
+            CreateSeatBlockRuleRequest {
+                rule: SeatBlockRuleKind::MiddleSeats,
+                start_date: today,
+                end_date: None,
+            },
+        )
+        .await?;
+    assert_eq!(rule.seats, 3);
+    assert_eq!(
+        ctx.inventory(flight_number, flight_date).await?,
+        (8, vec![5, 8, 11])
+    );
+
+    let result = ctx
+        .seat_block_service
+        .create_rule(
+            admin_id,
+            flight_number,
+            CreateSeatBlockRuleRequest {
+                rule: SeatBlockRuleKind::LastRow,
+                start_date: today,
+                end_date: Some(today - Duration::days(1)),
+            },
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+    let result = ctx.seat_block_service.set_enabled(-1, false).await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use airline_booking_system::models::slo::SloAlert;
+use airline_booking_system::services::slo_service::{SloAlerter, SloService};
+use airline_booking_system::utils::config::SloConfig;
+use airline_booking_system::utils::metrics::{RecordMetrics, RequestCounts, RequestMetrics};
+use airline_booking_system::utils::telemetry::RequestTracing;
+use rocket::http::Status;
+use rocket::local::asynchronous::Client;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[rocket::post("/tickets/book")]
+fn book() -> &'static str {
+    "booked"
+}
+
+#[rocket::get("/flights/search")]
+fn search() -> Status {
+    Status::InternalServerError
+}
+
+// Keeps the alerts instead of sending them
+#[derive(Default)]
+struct RecordingAlerter {
+    alerts: Mutex<Vec<SloAlert>>,
+}
+
+#[rocket::async_trait]
+impl SloAlerter for RecordingAlerter {
+    fn name(&self) -> &'static str {
+        "recording"
+    }
+
+    async fn alert(&self, alert: &SloAlert) -> Result<(), String> {
+        self.alerts.lock().unwrap().push(alert.clone());
+        Ok(())
+    }
+}
+
+#[rocket::async_test]
+async fn test_requests_are_counted() {
+    let metrics = RequestMetrics::new(Duration::from_secs(2));
+    let rocket = rocket::build()
+        .mount("/api", rocket::routes![book, search])
+        .attach(RequestTracing)
+        .attach(RecordMetrics(metrics.clone()));
+    let client = Client::tracked(rocket).await.expect("valid rocket");
+
+    for _ in 0..2 {
+        let response = client.post("/api/tickets/book").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+    let response = client.get("/api/flights/search").dispatch().await;
+    assert_eq!(response.status(), Status::InternalServerError);
+    // Requests matching no route are left out
+    let response = client.get("/api/unknown").dispatch().await;
+    assert_eq!(response.status(), Status::NotFound);
+
+    assert_eq!(
+        metrics.counts(1),
+        RequestCounts {
+            requests: 3,
+            server_errors: 1,
+            bookings: 2,
+            slow_bookings: 0,
+        }
+    );
+}
+
+#[rocket::async_test]
+async fn test_slo_alerts_once_while_burning() {
+    let metrics = RequestMetrics::new(Duration::from_millis(500));
+    let alerter = Arc::new(RecordingAlerter::default());
+    let config = SloConfig {
+        alert_min_requests: 20,
+        ..SloConfig::default()
+    };
+    let slo_service = SloService::new(metrics.clone(), config, alerter.clone());
+
+    // Too few requests to alert on
+    for _ in 0..10 {
+        metrics.record(true, 200, Duration::from_secs(1));
+    }
+    assert!(slo_service.check().await.is_empty());
+
+    // A third of the bookings slow is 33 times the 1% the objective allows
+    for _ in 0..20 {
+        metrics.record(true, 201, Duration::from_millis(100));
+    }
+    let alerts = slo_service.check().await;
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].objective, "booking_latency");
+    assert_eq!((alerts[0].requests, alerts[0].good_requests), (30, 20));
+    assert!((alerts[0].burn_rate - 100.0 / 3.0).abs() < 1e-9);
+    assert_eq!(*alerter.alerts.lock().unwrap(), alerts);
+
+    // Still burning, but already alerted about
+    assert!(slo_service.check().await.is_empty());
+
+    let report = slo_service.report();
+    let latency = &report.objectives[0];
+    assert!(latency.alerting);
+    assert_eq!(latency.compliance, Some(20.0 / 30.0));
+    assert!(latency.error_budget_remaining < 0.0);
+    let errors = &report.objectives[1];
+    assert_eq!(errors.name, "error_rate");
+    assert!(!errors.alerting);
+    assert_eq!(errors.compliance, Some(1.0));
+    assert_eq!(errors.error_budget_remaining, 1.0);
+}
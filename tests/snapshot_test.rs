@@ -0,0 +1,106 @@
+use async_trait::async_trait;
+use ctor::dtor;
+use sqlx::mysql::MySqlPool as Pool;
+use test_context::{test_context, AsyncTestContext};
+
+mod common {
+    pub mod test_utils;
+}
+use common::test_utils::TestDb;
+
+struct SnapshotContext {
+    pool: Pool,
+}
+
+#[dtor]
+fn cleanup() {
+    if let Err(e) = TestDb::cleanup_database_sync() {
+        eprintln!("Failed to cleanup test database: {}", e);
+    }
+}
+
+#[async_trait]
+impl AsyncTestContext for SnapshotContext {
+    async fn setup() -> Self {
+        let pool = TestDb::get_instance(file!())
+            .await
+            .expect("Failed to get test database instance");
+        SnapshotContext { pool }
+    }
+
+    async fn teardown(self) {
+        let _ = sqlx::query("SELECT 1").execute(&self.pool).await;
+    }
+}
+
+async fn aircraft(pool: &Pool) -> Result<Vec<(i32, i32)>, sqlx::Error> {
+    sqlx::query_as("SELECT aircraft_id, capacity FROM aircraft ORDER BY aircraft_id")
+        .fetch_all(pool)
+        .await
+}
+
+#[test_context(SnapshotContext)]
+#[tokio::test]
+async fn test_snapshot_restore(ctx: &SnapshotContext) -> Result<(), sqlx::Error> {
+    for (aircraft_id, capacity) in [(11, 100), (12, 150)] {
+        sqlx::query!(
+            "INSERT INTO aircraft (aircraft_id, capacity) VALUES (?, ?)",
+            aircraft_id,
+            capacity
+        )
+        .execute(&ctx.pool)
+        .await?;
+    }
+    sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, start_date)
+        VALUES (1, 'Toronto', 'Ottawa', '08:00:00', '09:00:00', 11, '2030-01-01')
+        "#
+    )
+    .execute(&ctx.pool)
+    .await?;
+    let snapshot = TestDb::snapshot(&ctx.pool, "seeded").await?;
+
+    // Change, add and remove rows
+    sqlx::query!("UPDATE aircraft SET capacity = 1 WHERE aircraft_id = 12")
+        .execute(&ctx.pool)
+        .await?;
+    sqlx::query!("INSERT INTO aircraft (aircraft_id, capacity) VALUES (13, 200)")
+        .execute(&ctx.pool)
+        .await?;
+    // Removing the aircraft also removes its route
+    sqlx::query!("DELETE FROM aircraft WHERE aircraft_id = 11")
+        .execute(&ctx.pool)
+        .await?;
+
+    TestDb::restore(&ctx.pool, &snapshot).await?;
+    assert_eq!(aircraft(&ctx.pool).await?, vec![(11, 100), (12, 150)]);
+    let routes: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM flight_route")
+        .fetch_one(&ctx.pool)
+        .await?;
+    assert_eq!(routes, 1);
+
+    // A snapshot can be restored any number of times
+    sqlx::query!("DELETE FROM flight_route")
+        .execute(&ctx.pool)
+        .await?;
+    TestDb::restore(&ctx.pool, &snapshot).await?;
+    assert_eq!(aircraft(&ctx.pool).await?, vec![(11, 100), (12, 150)]);
+
+    // Foreign keys are enforced again after a restore
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO flight_route
+        (flight_number, departure_city, destination_city, departure_time, arrival_time,
+            aircraft_id, start_date)
+        VALUES (2, 'Ottawa', 'Toronto', '10:00:00', '11:00:00', 99, '2030-01-01')
+        "#
+    )
+    .execute(&ctx.pool)
+    .await;
+    assert!(result.is_err());
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use airline_booking_system::utils::statement_budget::StatementBudget;
+use airline_booking_system::utils::telemetry::{self, RequestSpan, RequestTracing};
+use rocket::http::Status;
+use rocket::local::asynchronous::Client;
+
+// Runs as many statements as asked, as sqlx reports them
+#[rocket::get("/statements/<count>")]
+fn run_statements(count: usize, span: RequestSpan) -> &'static str {
+    span.0.in_scope(|| {
+        for _ in 0..count {
+            tracing::debug!(target: "sqlx::query", "SELECT 1");
+        }
+    });
+    "done"
+}
+
+async fn client(budget: usize) -> Client {
+    telemetry::init();
+    let rocket = rocket::build()
+        .mount("/", rocket::routes![run_statements])
+        .attach(StatementBudget::new(budget))
+        .attach(RequestTracing);
+    Client::tracked(rocket).await.expect("valid rocket")
+}
+
+#[rocket::async_test]
+async fn test_request_within_budget() {
+    let client = client(3).await;
+    let response = client.get("/statements/3").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.into_string().await.as_deref(), Some("done"));
+}
+
+#[rocket::async_test]
+async fn test_request_over_budget_fails_in_debug_builds() {
+    let client = client(3).await;
+    let response = client.get("/statements/4").dispatch().await;
+    if cfg!(debug_assertions) {
+        assert_eq!(response.status(), Status::InternalServerError);
+        let body = response.into_string().await.unwrap();
+        assert!(body.contains("4 SQL statements"), "{}", body);
+    } else {
+        assert_eq!(response.status(), Status::Ok);
+    }
+}
+
+#[rocket::async_test]
+async fn test_zero_budget_turns_the_check_off() {
+    let client = client(0).await;
+    let response = client.get("/statements/50").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+}
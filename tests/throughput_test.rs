@@ -20,8 +20,10 @@ use tokio::task::JoinSet;
 mod common {
     pub mod test_utils;
 }
-use common::test_utils::TestDb;
+use common::test_utils::{Snapshot, TestDb};
 use ctor::dtor;
+use once_cell::sync::OnceCell;
+use tokio::sync::Mutex;
 
 struct ThroughputContext {
     pool: MySqlPool,
@@ -29,6 +31,10 @@ struct ThroughputContext {
     user_service: UserService,
 }
 
+// Seeded state shared by the cases of this file. Restoring it replaces the rows of
+// the whole database, so the cases hold the lock and run one at a time.
+static SEEDED: OnceCell<Mutex<Option<(Snapshot, Vec<i32>)>>> = OnceCell::new();
+
 #[derive(Debug, Clone)]
 enum MixedRequest {
     Booking((i32, i32, NaiveDate)),
@@ -264,18 +270,8 @@ async fn setup_test_data(ctx: &ThroughputContext) -> Result<(), AppError> {
     Ok(())
 }
 
-#[test_context(ThroughputContext)]
-#[tokio::test(flavor = "multi_thread", worker_threads = 16)]
-async fn test_massive_concurrent_booking(ctx: &ThroughputContext) -> Result<(), AppError> {
-    let test_name = "test_massive_concurrent_booking";
-    let num_users = 100;
-    let requests_per_user = 20;
-
-    test_println!(test_name, "Setting up test data...");
-    setup_test_data(ctx).await?;
-
-    // Create users concurrently
-    test_println!(test_name, "Creating {} users concurrently...", num_users);
+async fn create_users(ctx: &ThroughputContext, num_users: usize) -> Result<Vec<i32>, AppError> {
+    test_println!("setup", "Creating {} users concurrently...", num_users);
     // Create users in batches
     const USER_BATCH_SIZE: usize = 50;
     let mut user_ids = Vec::with_capacity(num_users);
@@ -293,6 +289,7 @@ async fn test_massive_concurrent_booking(ctx: &ThroughputContext) -> Result<(),
                     name: format!("Performance Test User {}", i),
                     birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
                     gender: "male".to_string(),
+                    email: None,
                 };
                 let user_id = user_service.register_user(user).await?;
                 Ok::<_, AppError>(user_id)
@@ -304,7 +301,7 @@ async fn test_massive_concurrent_booking(ctx: &ThroughputContext) -> Result<(),
                 Ok(Ok(user_id)) => {
                     user_ids.push(user_id);
                     if user_ids.len() % 50 == 0 {
-                        test_println!(test_name, "Created {} users so far...", user_ids.len());
+                        test_println!("setup", "Created {} users so far...", user_ids.len());
                     }
                 }
                 Ok(Err(e)) => return Err(e),
@@ -313,7 +310,42 @@ async fn test_massive_concurrent_booking(ctx: &ThroughputContext) -> Result<(),
         }
     }
 
-    test_println!(test_name, "Successfully created {} users", user_ids.len());
+    test_println!("setup", "Successfully created {} users", user_ids.len());
+
+    Ok(user_ids)
+}
+
+// Seed the flights and users on the first call and take a snapshot of them, later
+// calls restore the snapshot instead of seeding again
+async fn seed_or_restore(
+    ctx: &ThroughputContext,
+    seeded: &mut Option<(Snapshot, Vec<i32>)>,
+    num_users: usize,
+) -> Result<Vec<i32>, AppError> {
+    if let Some((snapshot, user_ids)) = seeded.as_ref() {
+        test_println!("setup", "Restoring seeded test data...");
+        TestDb::restore(&ctx.pool, snapshot).await?;
+        return Ok(user_ids.clone());
+    }
+
+    test_println!("setup", "Setting up test data...");
+    setup_test_data(ctx).await?;
+    let user_ids = create_users(ctx, num_users).await?;
+    let snapshot = TestDb::snapshot(&ctx.pool, "seeded").await?;
+    *seeded = Some((snapshot, user_ids.clone()));
+    Ok(user_ids)
+}
+
+#[test_context(ThroughputContext)]
+#[tokio::test(flavor = "multi_thread", worker_threads = 16)]
+async fn test_massive_concurrent_booking(ctx: &ThroughputContext) -> Result<(), AppError> {
+    let test_name = "test_massive_concurrent_booking";
+    let num_users = 100;
+    let requests_per_user = 20;
+
+    // Every case starts from the same flights and users, restored between cases
+    let mut seeded = SEEDED.get_or_init(|| Mutex::new(None)).lock().await;
+    let user_ids = seed_or_restore(ctx, &mut seeded, num_users).await?;
 
     test_println!(test_name, "Generating booking requests...");
     let mut booking_requests = Vec::with_capacity(num_users * requests_per_user);
@@ -348,9 +380,10 @@ async fn test_massive_concurrent_booking(ctx: &ThroughputContext) -> Result<(),
                 flight_number: flight_number,
                 flight_date: flight_date,
                 preferred_seat: None,
+                ..Default::default()
             }];
 
-            let result = ticket_service.book_ticket(user_id, TicketBookingRequest {flights: booking_request},).await;
+            let result = ticket_service.book_ticket(user_id, TicketBookingRequest { flights: booking_request, ..Default::default() },).await;
 
             match &result {
                 Ok(_) => {
@@ -465,9 +498,10 @@ async fn test_massive_concurrent_booking(ctx: &ThroughputContext) -> Result<(),
                         flight_number: flight_number,
                         flight_date: flight_date,
                         preferred_seat: None,
+                        ..Default::default()
                     }];
 
-                    match ticket_service.book_ticket(user_id, TicketBookingRequest {flights: booking_request}).await {
+                    match ticket_service.book_ticket(user_id, TicketBookingRequest { flights: booking_request, ..Default::default() }).await {
                         Ok(_) => {
                             Ok(())
                         }
@@ -1,17 +1,42 @@
 use airline_booking_system::{
     models::{
+        booking_rules::BookingRules,
+        checkin::CheckinRequest,
+        fare::FareClass,
+        ssr::SsrCode,
+        ticket::BookingRequestStatus,
+        ticket::BookingStatus,
         ticket::FlightBookingRequest,
+        ticket::GuardianContact,
+        ticket::LegStatus,
         ticket::SeatBookingRequest,
+        ticket::SeatHoldRequest,
         ticket::TicketBookingRequest,
         user::{Role, UserRegistrationRequest},
     },
-    services::{ticket_service::TicketService, user_service::UserService},
-    utils::error::AppError,
+    services::{
+        booking_queue::{
+            BookingQueue, BookingRequests, QueueCaller, BOOKING_REQUEST_TIMEOUT_MINUTES,
+        },
+        flight_service::FlightService,
+        ticket_service::TicketService,
+        user_service::UserService,
+    },
+    utils::{
+        document::DocumentFormat,
+        error::{AppError, ErrorKind},
+        funnel::FunnelTracker,
+        locale::DocumentLocale,
+        public_id::new_public_id,
+        seat_lock::{seat_lock_key, InMemorySeatLock, SeatLock},
+    },
 };
 use async_trait::async_trait;
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
 use rand::Rng;
 use sqlx::mysql::MySqlPool as Pool;
+use std::sync::Arc;
+use std::time::Duration;
 use test_context::{test_context, AsyncTestContext};
 use tokio::task::JoinSet;
 
@@ -143,6 +168,7 @@ async fn test_concurrent_ticket_booking_capacity1(
             name: format!("Test User {}", i),
             birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
             gender: "male".to_string(),
+            email: None,
         };
         let user_id = ctx.user_service.register_user(user).await?;
         user_ids.push(user_id);
@@ -153,6 +179,7 @@ async fn test_concurrent_ticket_booking_capacity1(
         flight_number,
         flight_date,
         preferred_seat: None,
+        ..Default::default()
     }];
 
     test_println!(test_name, "Starting concurrent booking attempts...");
@@ -169,7 +196,7 @@ async fn test_concurrent_ticket_booking_capacity1(
     for (user_id, ticket_service, request) in tasks {
         join_set.spawn(async move {
             let result = ticket_service
-                .book_ticket(user_id, TicketBookingRequest { flights: request })
+                .book_ticket(user_id, TicketBookingRequest { flights: request, ..Default::default() })
                 .await;
             (user_id, result)
         });
@@ -254,6 +281,7 @@ async fn test_concurrent_ticket_booking_capacity5(
             name: format!("Test User {}", i),
             birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
             gender: "male".to_string(),
+            email: None,
         };
         let user_id = ctx.user_service.register_user(user).await?;
         user_ids.push(user_id);
@@ -264,6 +292,7 @@ async fn test_concurrent_ticket_booking_capacity5(
         flight_number,
         flight_date,
         preferred_seat: None,
+        ..Default::default()
     }];
 
     test_println!(test_name, "Starting concurrent booking attempts...");
@@ -280,7 +309,7 @@ async fn test_concurrent_ticket_booking_capacity5(
     for (user_id, ticket_service, request) in tasks {
         join_set.spawn(async move {
             let result = ticket_service
-                .book_ticket(user_id, TicketBookingRequest { flights: request })
+                .book_ticket(user_id, TicketBookingRequest { flights: request, ..Default::default() })
                 .await;
             (user_id, result)
         });
@@ -386,6 +415,7 @@ async fn test_concurrent_seat_booking1(ctx: &TicketServiceContext) -> Result<(),
             name: format!("Test User {}", i),
             birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
             gender: "male".to_string(),
+            email: None,
         };
         let user_id = ctx.user_service.register_user(user).await?;
 
@@ -394,12 +424,14 @@ async fn test_concurrent_seat_booking1(ctx: &TicketServiceContext) -> Result<(),
             flight_number,
             flight_date,
             preferred_seat: None,
+            ..Default::default()
         }];
         ctx.ticket_service
             .book_ticket(
                 user_id,
                 TicketBookingRequest {
                     flights: booking_request,
+                    ..Default::default()
                 },
             )
             .await?;
@@ -535,6 +567,7 @@ async fn test_concurrent_seat_booking5(ctx: &TicketServiceContext) -> Result<(),
             name: format!("Test User {}", i),
             birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
             gender: "male".to_string(),
+            email: None,
         };
         let user_id = ctx.user_service.register_user(user).await?;
 
@@ -543,12 +576,14 @@ async fn test_concurrent_seat_booking5(ctx: &TicketServiceContext) -> Result<(),
             flight_number,
             flight_date,
             preferred_seat: None,
+            ..Default::default()
         }];
         ctx.ticket_service
             .book_ticket(
                 user_id,
                 TicketBookingRequest {
                     flights: booking_request,
+                    ..Default::default()
                 },
             )
             .await?;
@@ -671,6 +706,7 @@ async fn test_get_booking_history(ctx: &TicketServiceContext) -> Result<(), AppE
         name: "History Test User".to_string(),
         birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
         gender: "male".to_string(),
+        email: None,
     };
     let user_id = ctx.user_service.register_user(user).await?;
 
@@ -690,12 +726,14 @@ async fn test_get_booking_history(ctx: &TicketServiceContext) -> Result<(), AppE
         flight_number: flight_number1,
         flight_date: flight_date1,
         preferred_seat: Some(1),
+        ..Default::default()
     }];
 
     let booking_request2 = vec![FlightBookingRequest {
         flight_number: flight_number2,
         flight_date: flight_date2,
         preferred_seat: None,
+        ..Default::default()
     }];
 
     // Book tickets
@@ -704,6 +742,7 @@ async fn test_get_booking_history(ctx: &TicketServiceContext) -> Result<(), AppE
             user_id,
             TicketBookingRequest {
                 flights: booking_request1,
+                ..Default::default()
             },
         )
         .await?;
@@ -712,6 +751,7 @@ async fn test_get_booking_history(ctx: &TicketServiceContext) -> Result<(), AppE
             user_id,
             TicketBookingRequest {
                 flights: booking_request2,
+                ..Default::default()
             },
         )
         .await?;
@@ -739,3 +779,1880 @@ async fn test_get_booking_history(ctx: &TicketServiceContext) -> Result<(), AppE
 
     Ok(())
 }
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_unaccompanied_minor_booking(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    // Create a 10-year-old test user
+    let user = UserRegistrationRequest {
+        username: "minor_test_user".to_string(),
+        password: "test_password".to_string(),
+        role: Role::User,
+        name: "Minor Test User".to_string(),
+        birth_date: NaiveDate::from_ymd_opt(2014, 6, 1).unwrap(),
+        gender: "female".to_string(),
+        email: None,
+    };
+    let user_id = ctx.user_service.register_user(user).await?;
+
+    let flight_number = 401;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 20).unwrap();
+    setup_database(ctx, flight_number, 10, flight_date).await?;
+
+    let booking_request = vec![FlightBookingRequest {
+        flight_number,
+        flight_date,
+        preferred_seat: None,
+        ..Default::default()
+    }];
+
+    // Booking without guardian contact should be rejected
+    let result = ctx
+        .ticket_service
+        .book_ticket(
+            user_id,
+            TicketBookingRequest {
+                flights: booking_request.clone(),
+                ..Default::default()
+            },
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::BadRequest(_))));
+
+    // Booking with guardian contact should be confirmed and flagged
+    let response = ctx
+        .ticket_service
+        .book_ticket(
+            user_id,
+            TicketBookingRequest {
+                flights: booking_request,
+                guardian: Some(GuardianContact {
+                    name: "Parent".to_string(),
+                    phone: "416-555-0100".to_string(),
+                    relationship: "mother".to_string(),
+                }),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    assert_eq!(response.booking_status, BookingStatus::Confirmed);
+    assert_eq!(
+        response.legacy_booking_status,
+        "Confirmed (Unaccompanied Minor)"
+    );
+    assert!(response.unaccompanied_minor);
+    assert!(response.flight_bookings[0].unaccompanied_minor);
+
+    let ticket = sqlx::query!(
+        "SELECT unaccompanied_minor FROM ticket WHERE id = ?",
+        response.flight_bookings[0].ticket_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert!(ticket.unaccompanied_minor);
+    let guardian = sqlx::query_scalar!(
+        "SELECT guardian_name FROM unaccompanied_minor WHERE ticket_id = ?",
+        response.flight_bookings[0].ticket_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(guardian, "Parent");
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_partial_booking(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let user = UserRegistrationRequest {
+        username: "partial_test_user".to_string(),
+        password: "test_password".to_string(),
+        role: Role::User,
+        name: "Partial Test User".to_string(),
+        birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+        gender: "male".to_string(),
+        email: None,
+    };
+    let user_id = ctx.user_service.register_user(user).await?;
+
+    let flight_number = 501;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 21).unwrap();
+    setup_database(ctx, flight_number, 10, flight_date).await?;
+
+    // The second leg does not exist
+    let booking_request = vec![
+        FlightBookingRequest {
+            flight_number,
+            flight_date,
+            preferred_seat: None,
+            ..Default::default()
+        },
+        FlightBookingRequest {
+            flight_number: 502,
+            flight_date,
+            preferred_seat: None,
+            ..Default::default()
+        },
+    ];
+
+    let response = ctx
+        .ticket_service
+        .book_ticket(
+            user_id,
+            TicketBookingRequest {
+                flights: booking_request,
+                allow_partial: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    assert_eq!(response.booking_status, BookingStatus::PartiallyFailed);
+    assert_eq!(response.flight_bookings.len(), 1);
+    assert_eq!(response.failed_legs.len(), 1);
+    assert_eq!(response.failed_legs[0].flight_number, 502);
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_connecting_booking_with_full_leg(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let user = UserRegistrationRequest {
+        username: "connecting_test_user".to_string(),
+        password: "test_password".to_string(),
+        role: Role::User,
+        name: "Connecting Test User".to_string(),
+        birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+        gender: "male".to_string(),
+        email: None,
+    };
+    let user_id = ctx.user_service.register_user(user).await?;
+
+    let first_leg = 511;
+    let second_leg = 512;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 22).unwrap();
+    setup_database(ctx, first_leg, 10, flight_date).await?;
+    setup_database(ctx, second_leg, 10, flight_date).await?;
+
+    // The connecting flight is sold out
+    sqlx::query!(
+        "UPDATE flight SET available_tickets = 0 WHERE flight_number = ?",
+        second_leg
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    let result = ctx
+        .ticket_service
+        .book_ticket(
+            user_id,
+            TicketBookingRequest {
+                flights: vec![
+                    FlightBookingRequest {
+                        flight_number: first_leg,
+                        flight_date,
+                        preferred_seat: Some(1),
+                        ..Default::default()
+                    },
+                    FlightBookingRequest {
+                        flight_number: second_leg,
+                        flight_date,
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            },
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::ConflictWithHints(_, _))));
+
+    // The first leg was never confirmed and its inventory is untouched
+    let first = sqlx::query!(
+        r#"
+        SELECT
+            f.available_tickets,
+            (SELECT COUNT(*) FROM ticket t WHERE t.flight_id = f.flight_id) as "tickets!: i64",
+            (SELECT seat_status FROM seat_info s
+                WHERE s.flight_id = f.flight_id AND s.seat_number = 1) as seat_status
+        FROM flight f
+        WHERE f.flight_number = ?
+        "#,
+        first_leg
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(first.available_tickets, 10);
+    assert_eq!(first.tickets, 0);
+    assert_eq!(first.seat_status.as_deref(), Some("AVAILABLE"));
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_failed_booking_is_reverted(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let user = UserRegistrationRequest {
+        username: "revert_test_user".to_string(),
+        password: "test_password".to_string(),
+        role: Role::User,
+        name: "Revert Test User".to_string(),
+        birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+        gender: "female".to_string(),
+        email: None,
+    };
+    let user_id = ctx.user_service.register_user(user).await?;
+
+    let first_leg = 513;
+    let second_leg = 514;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 22).unwrap();
+    setup_database(ctx, first_leg, 10, flight_date).await?;
+    setup_database(ctx, second_leg, 10, flight_date).await?;
+    let leg = |flight_number, preferred_seat| FlightBookingRequest {
+        flight_number,
+        flight_date,
+        preferred_seat,
+        ..Default::default()
+    };
+    // Tickets, available tickets and status of seat 1 of a flight
+    let inventory = |flight_number| {
+        sqlx::query!(
+            r#"
+            SELECT
+                f.available_tickets,
+                (SELECT COUNT(*) FROM ticket t WHERE t.flight_id = f.flight_id) as "tickets!: i64",
+                (SELECT seat_status FROM seat_info s
+                    WHERE s.flight_id = f.flight_id AND s.seat_number = 1) as seat_status
+            FROM flight f
+            WHERE f.flight_number = ?
+            "#,
+            flight_number
+        )
+        .fetch_one(&ctx.pool)
+    };
+
+    // The customer already flies the second leg, so the itinerary fails on it after the
+    // first leg was booked with its seat
+    ctx.ticket_service
+        .book_ticket(
+            user_id,
+            TicketBookingRequest {
+                flights: vec![leg(second_leg, None)],
+                ..Default::default()
+            },
+        )
+        .await?;
+    let result = ctx
+        .ticket_service
+        .book_ticket(
+            user_id,
+            TicketBookingRequest {
+                flights: vec![leg(first_leg, Some(1)), leg(second_leg, None)],
+                ..Default::default()
+            },
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+    let first = inventory(first_leg).await?;
+    assert_eq!(first.available_tickets, 10);
+    assert_eq!(first.tickets, 0);
+    assert_eq!(first.seat_status.as_deref(), Some("AVAILABLE"));
+    // Only the ticket booked before is left, and the ticket claimed for the itinerary
+    // was given back
+    let second = inventory(second_leg).await?;
+    assert_eq!(second.available_tickets, 9);
+    assert_eq!(second.tickets, 1);
+
+    // The leg is booked, then the booking grouping it fails on the promo code
+    let result = ctx
+        .ticket_service
+        .book_ticket(
+            user_id,
+            TicketBookingRequest {
+                flights: vec![leg(first_leg, Some(1))],
+                promo_code: Some("NO_SUCH_CODE".to_string()),
+                ..Default::default()
+            },
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+    let first = inventory(first_leg).await?;
+    assert_eq!(first.available_tickets, 10);
+    assert_eq!(first.tickets, 0);
+    assert_eq!(first.seat_status.as_deref(), Some("AVAILABLE"));
+
+    // Nothing was left behind, so the same itinerary books once the conflict is gone
+    ctx.ticket_service
+        .release_ticket_for_flight(user_id, second_leg, flight_date)
+        .await?;
+    let response = ctx
+        .ticket_service
+        .book_ticket(
+            user_id,
+            TicketBookingRequest {
+                flights: vec![leg(first_leg, Some(1)), leg(second_leg, None)],
+                ..Default::default()
+            },
+        )
+        .await?;
+    assert_eq!(response.flight_bookings.len(), 2);
+    assert_eq!(response.flight_bookings[0].seat_number, Some(1));
+    // The sagas of the bookings ended with them
+    let sagas = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM booking_saga WHERE customer_id = ?",
+        user_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(sagas, 0);
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_interrupted_booking_is_reverted(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let user = UserRegistrationRequest {
+        username: "interrupted_test_user".to_string(),
+        password: "test_password".to_string(),
+        role: Role::User,
+        name: "Interrupted Test User".to_string(),
+        birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+        gender: "male".to_string(),
+        email: None,
+    };
+    let user_id = ctx.user_service.register_user(user).await?;
+
+    let first_leg = 515;
+    let second_leg = 516;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 22).unwrap();
+    setup_database(ctx, first_leg, 10, flight_date).await?;
+    setup_database(ctx, second_leg, 10, flight_date).await?;
+    let flight_id = |flight_number| {
+        sqlx::query_scalar!(
+            "SELECT flight_id FROM flight WHERE flight_number = ?",
+            flight_number
+        )
+        .fetch_one(&ctx.pool)
+    };
+    let (first_id, second_id) = (flight_id(first_leg).await?, flight_id(second_leg).await?);
+
+    // Claim a ticket of every leg for a saga, as a connecting booking does
+    let start_saga = |started_at: NaiveDateTime| async move {
+        let saga_id = sqlx::query!(
+            "INSERT INTO booking_saga (customer_id, created_at) VALUES (?, ?)",
+            user_id,
+            started_at
+        )
+        .execute(&ctx.pool)
+        .await?
+        .last_insert_id() as i32;
+        for flight_id in [first_id, second_id] {
+            sqlx::query!(
+                "UPDATE flight SET available_tickets = available_tickets - 1 WHERE flight_id = ?",
+                flight_id
+            )
+            .execute(&ctx.pool)
+            .await?;
+            sqlx::query!(
+                "INSERT INTO booking_saga_claim (saga_id, flight_id) VALUES (?, ?)",
+                saga_id,
+                flight_id
+            )
+            .execute(&ctx.pool)
+            .await?;
+        }
+        Ok::<i32, AppError>(saga_id)
+    };
+
+    // A server stopped after booking the first leg with seat 1 an hour ago
+    let now = chrono::Utc::now().naive_utc();
+    let interrupted = start_saga(now - chrono::Duration::hours(1)).await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO ticket
+            (customer_id, flight_id, seat_number, flight_date, flight_number, booking_saga_id)
+        VALUES (?, ?, 1, ?, ?, ?)
+        "#,
+        user_id,
+        first_id,
+        flight_date,
+        first_leg,
+        interrupted
+    )
+    .execute(&ctx.pool)
+    .await?;
+    sqlx::query!(
+        "UPDATE seat_info SET seat_status = 'BOOKED' WHERE flight_id = ? AND seat_number = 1",
+        first_id
+    )
+    .execute(&ctx.pool)
+    .await?;
+    // Another booking is still being placed
+    let in_progress = start_saga(now).await?;
+
+    assert_eq!(ctx.ticket_service.revert_interrupted_bookings().await?, 1);
+
+    let first = sqlx::query!(
+        r#"
+        SELECT
+            f.available_tickets,
+            (SELECT COUNT(*) FROM ticket t WHERE t.flight_id = f.flight_id) as "tickets!: i64",
+            (SELECT seat_status FROM seat_info s
+                WHERE s.flight_id = f.flight_id AND s.seat_number = 1) as seat_status
+        FROM flight f
+        WHERE f.flight_id = ?
+        "#,
+        first_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(first.tickets, 0);
+    assert_eq!(first.seat_status.as_deref(), Some("AVAILABLE"));
+    // Only the claims of the booking in progress are left
+    assert_eq!(first.available_tickets, 9);
+    let second = sqlx::query_scalar!(
+        "SELECT available_tickets FROM flight WHERE flight_id = ?",
+        second_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(second, 9);
+    let sagas = sqlx::query_scalar!("SELECT id FROM booking_saga WHERE customer_id = ?", user_id)
+        .fetch_all(&ctx.pool)
+        .await?;
+    assert_eq!(sagas, vec![in_progress]);
+
+    // Reverting twice changes nothing
+    assert_eq!(ctx.ticket_service.revert_interrupted_bookings().await?, 0);
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_fare_class_booking(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let user = UserRegistrationRequest {
+        username: "fare_test_user".to_string(),
+        password: "test_password".to_string(),
+        role: Role::User,
+        name: "Fare Test User".to_string(),
+        birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+        gender: "male".to_string(),
+        email: None,
+    };
+    let user_id = ctx.user_service.register_user(user).await?;
+
+    let flight_number = 601;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 22).unwrap();
+    setup_database(ctx, flight_number, 10, flight_date).await?;
+
+    // Business is sold for the first row (seats 1-6), economy for the rest
+    sqlx::query!(
+        r#"
+        INSERT INTO fare (flight_number, fare_class, base_price, currency, first_row, last_row)
+        VALUES (?, 'BUSINESS', 400.00, 'CAD', 1, 1), (?, 'ECONOMY', 100.00, 'CAD', 2, NULL)
+        "#,
+        flight_number,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    // First class is not offered on this flight
+    let result = ctx
+        .ticket_service
+        .book_ticket(
+            user_id,
+            TicketBookingRequest {
+                flights: vec![FlightBookingRequest {
+                    flight_number,
+                    flight_date,
+                    fare_class: FareClass::First,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .await;
+    assert!(result.is_err());
+
+    // A business ticket cannot take an economy seat
+    let response = ctx
+        .ticket_service
+        .book_ticket(
+            user_id,
+            TicketBookingRequest {
+                flights: vec![FlightBookingRequest {
+                    flight_number,
+                    flight_date,
+                    preferred_seat: Some(8),
+                    fare_class: FareClass::Business,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    assert_eq!(response.booking_status, BookingStatus::PendingPayment);
+    assert_eq!(
+        response.flight_bookings[0].status,
+        LegStatus::ConfirmedSeatUnavailable
+    );
+    assert_eq!(response.flight_bookings[0].fare_class, FareClass::Business);
+    assert_eq!(response.flight_bookings[0].price.to_string(), "400.00");
+    assert_eq!(response.payment.unwrap().amount.to_string(), "400.00");
+
+    // Choosing a seat later is held to the same cabin section
+    let seat_request = |seat_number| SeatBookingRequest {
+        flight_number,
+        flight_date,
+        seat_number,
+    };
+    let result = ctx
+        .ticket_service
+        .book_seat_for_ticket(user_id, seat_request(8))
+        .await;
+    match result {
+        Err(AppError::BadRequest(message)) => assert_eq!(
+            message,
+            "Seat 8 is in the ECONOMY cabin, a BUSINESS ticket can only take seats in row 1"
+        ),
+        other => panic!("expected a bad request, got {:?}", other),
+    }
+    assert!(
+        ctx.ticket_service
+            .book_seat_for_ticket(user_id, seat_request(3))
+            .await?
+    );
+
+    // The seat map tells the sections apart
+    let seat_map = FlightService::new(ctx.pool.clone())
+        .get_available_seats(flight_number, flight_date)
+        .await?;
+    let seat_class = |seat_number| {
+        seat_map
+            .seats
+            .iter()
+            .find(|seat| seat.seat_number == seat_number)
+            .and_then(|seat| seat.fare_class)
+    };
+    assert_eq!(seat_class(1), Some(FareClass::Business));
+    assert_eq!(seat_class(8), Some(FareClass::Economy));
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_seat_hold(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let flight_number = 801;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 23).unwrap();
+    setup_database(ctx, flight_number, 10, flight_date).await?;
+
+    let mut user_ids = Vec::new();
+    for username in ["hold_test_user1", "hold_test_user2"] {
+        let user_id = ctx
+            .user_service
+            .register_user(UserRegistrationRequest {
+                username: username.to_string(),
+                password: "test_password".to_string(),
+                role: Role::User,
+                name: "Hold Test User".to_string(),
+                birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+                gender: "female".to_string(),
+                email: None,
+            })
+            .await?;
+        ctx.ticket_service
+            .book_ticket(
+                user_id,
+                TicketBookingRequest {
+                    flights: vec![FlightBookingRequest {
+                        flight_number,
+                        flight_date,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            )
+            .await?;
+        user_ids.push(user_id);
+    }
+
+    let hold = ctx
+        .ticket_service
+        .hold_seat(
+            user_ids[0],
+            SeatHoldRequest {
+                flight_number,
+                flight_date,
+                seat_number: 3,
+                minutes: None,
+            },
+        )
+        .await?;
+    assert_eq!(hold.seat_number, 3);
+
+    // The held seat cannot be taken by someone else
+    let seat_request = SeatBookingRequest {
+        flight_number,
+        flight_date,
+        seat_number: 3,
+    };
+    let result = ctx
+        .ticket_service
+        .book_seat_for_ticket(user_ids[1], seat_request.clone())
+        .await;
+    let error = result.unwrap_err();
+    assert!(matches!(error, AppError::SeatTaken { seat_number: 3, .. }));
+    assert_eq!(error.kind(), ErrorKind::SeatTaken);
+    assert_eq!(error.status().code, 409);
+    assert!(error
+        .retry_hints()
+        .map_or(false, |hints| !hints.alternative_seats.contains(&3)));
+
+    // but the holder can book it
+    assert!(
+        ctx.ticket_service
+            .book_seat_for_ticket(user_ids[0], seat_request)
+            .await?
+    );
+
+    // Expired holds are released
+    ctx.ticket_service
+        .hold_seat(
+            user_ids[1],
+            SeatHoldRequest {
+                flight_number,
+                flight_date,
+                seat_number: 4,
+                minutes: Some(1),
+            },
+        )
+        .await?;
+    sqlx::query!(
+        r#"
+        UPDATE seat_info
+        SET held_until = UTC_TIMESTAMP() - INTERVAL 1 MINUTE
+        WHERE seat_status = 'HELD'
+        "#
+    )
+    .execute(&ctx.pool)
+    .await?;
+    assert_eq!(ctx.ticket_service.release_expired_holds().await?, 1);
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_booking_rules(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let user = UserRegistrationRequest {
+        username: "rules_test_user".to_string(),
+        password: "test_password".to_string(),
+        role: Role::User,
+        name: "Rules Test User".to_string(),
+        birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+        gender: "female".to_string(),
+        email: None,
+    };
+    let user_id = ctx.user_service.register_user(user).await?;
+
+    let flight_number = 1101;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 23).unwrap();
+    setup_database(ctx, flight_number, 10, flight_date).await?;
+    let request = || TicketBookingRequest {
+        flights: vec![FlightBookingRequest {
+            flight_number,
+            flight_date,
+            preferred_seat: None,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    // Sales of this past flight closed under a purchase window
+    let strict = TicketService::new(ctx.pool.clone()).with_rules(BookingRules::from_toml(
+        "[advance_purchase]\nmin_hours_before_departure = 2",
+    )?);
+    let validation = strict.validate_booking(user_id, &request()).await?;
+    assert!(!validation.valid);
+    assert_eq!(validation.issues.len(), 1);
+    let result = strict.book_ticket(user_id, request()).await;
+    assert!(matches!(result, Err(AppError::BadRequest(_))));
+
+    // The built-in policy rejects a second ticket on the same flight
+    ctx.ticket_service.book_ticket(user_id, request()).await?;
+    let result = ctx.ticket_service.book_ticket(user_id, request()).await;
+    assert!(matches!(result, Err(AppError::BadRequest(_))));
+
+    let lenient = TicketService::new(ctx.pool.clone())
+        .with_rules(BookingRules::from_toml("duplicate_booking = \"allow\"")?);
+    let response = lenient.book_ticket(user_id, request()).await?;
+    assert_eq!(response.flight_bookings.len(), 1);
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_booking_reference(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let mut user_ids = Vec::new();
+    for username in ["pnr_owner", "pnr_other"] {
+        let user = UserRegistrationRequest {
+            username: username.to_string(),
+            password: "test_password".to_string(),
+            role: Role::User,
+            name: "PNR Test User".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+            gender: "male".to_string(),
+            email: None,
+        };
+        user_ids.push(ctx.user_service.register_user(user).await?);
+    }
+    let (owner, other) = (user_ids[0], user_ids[1]);
+
+    let flight_number = 1201;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 22).unwrap();
+    setup_database(ctx, flight_number, 10, flight_date).await?;
+
+    let response = ctx
+        .ticket_service
+        .book_ticket(
+            owner,
+            TicketBookingRequest {
+                flights: vec![FlightBookingRequest {
+                    flight_number,
+                    flight_date,
+                    preferred_seat: None,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .await?;
+    let booking = &response.flight_bookings[0];
+    assert_eq!(booking.booking_reference.len(), 6);
+    assert!(booking
+        .booking_reference
+        .chars()
+        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()));
+
+    // References are looked up regardless of case and surrounding spaces
+    let lookup = format!(" {} ", booking.booking_reference.to_lowercase());
+    let ticket = ctx
+        .ticket_service
+        .get_ticket_by_reference(owner, &lookup)
+        .await?;
+    assert_eq!(ticket.ticket_id, booking.ticket_id);
+    assert_eq!(ticket.booking_reference, booking.booking_reference);
+    assert_eq!(ticket.flight_number, flight_number);
+    assert_eq!(ticket.flight_date, flight_date);
+
+    let history = ctx.ticket_service.get_history(owner).await?;
+    assert!(history
+        .flights
+        .iter()
+        .any(|flight| flight.booking_reference.as_deref() == Some(&booking.booking_reference)));
+
+    // Other customers cannot tell whether a reference exists
+    let result = ctx
+        .ticket_service
+        .get_ticket_by_reference(other, &booking.booking_reference)
+        .await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    for malformed in ["", "ABC", "ABCDEFG", "AB-CDE"] {
+        let result = ctx
+            .ticket_service
+            .get_ticket_by_reference(owner, malformed)
+            .await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+    }
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_public_ticket_id(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let user = UserRegistrationRequest {
+        username: "public_id_user".to_string(),
+        password: "test_password".to_string(),
+        role: Role::User,
+        name: "Public Id User".to_string(),
+        birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+        gender: "female".to_string(),
+        email: None,
+    };
+    let user_id = ctx.user_service.register_user(user).await?;
+
+    let flight_number = 1251;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 23).unwrap();
+    setup_database(ctx, flight_number, 10, flight_date).await?;
+
+    let mut bookings = Vec::new();
+    for _ in 0..2 {
+        let response = ctx
+            .ticket_service
+            .book_ticket(
+                user_id,
+                TicketBookingRequest {
+                    flights: vec![FlightBookingRequest {
+                        flight_number,
+                        flight_date,
+                        preferred_seat: None,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            )
+            .await?;
+        bookings.extend(response.flight_bookings);
+    }
+    let (first, second) = (&bookings[0], &bookings[1]);
+    assert_eq!(first.public_id.len(), 26);
+    // Public ids sort like the tickets
+    assert!(first.public_id < second.public_id);
+
+    // Both ids name the ticket, and public ids regardless of case
+    let by_public_id = ctx
+        .ticket_service
+        .ticket_id_for(&first.public_id.to_lowercase())
+        .await?;
+    assert_eq!(by_public_id, first.ticket_id);
+    let by_ticket_id = ctx
+        .ticket_service
+        .ticket_id_for(&first.ticket_id.to_string())
+        .await?;
+    assert_eq!(by_ticket_id, first.ticket_id);
+
+    let ticket = ctx
+        .ticket_service
+        .get_ticket_by_reference(user_id, &first.booking_reference)
+        .await?;
+    assert_eq!(ticket.public_id.as_ref(), Some(&first.public_id));
+
+    let result = ctx.ticket_service.ticket_id_for("not-a-ticket").await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+    let result = ctx
+        .ticket_service
+        .ticket_id_for("01ARZ3NDEKTSV4RRFFQ69G5FAV")
+        .await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    // Tickets sold before public ids get one
+    sqlx::query!(
+        "UPDATE ticket SET public_id = NULL WHERE id = ?",
+        second.ticket_id
+    )
+    .execute(&ctx.pool)
+    .await?;
+    assert!(ctx.ticket_service.assign_public_ids().await? >= 1);
+    let public_id = sqlx::query_scalar!(
+        "SELECT public_id FROM ticket WHERE id = ?",
+        second.ticket_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert!(public_id.is_some_and(|public_id| public_id != second.public_id));
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_check_in(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let flight_number = 1301;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 23).unwrap();
+    setup_database(ctx, flight_number, 10, flight_date).await?;
+
+    // Three passengers, the second picked a seat when booking
+    let mut references = Vec::new();
+    for (username, preferred_seat) in [
+        ("checkin_first", None),
+        ("checkin_second", Some(5)),
+        ("checkin_late", None),
+    ] {
+        let user = UserRegistrationRequest {
+            username: username.to_string(),
+            password: "test_password".to_string(),
+            role: Role::User,
+            name: "Ada Lovelace".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+            gender: "female".to_string(),
+            email: None,
+        };
+        let user_id = ctx.user_service.register_user(user).await?;
+        let response = ctx
+            .ticket_service
+            .book_ticket(
+                user_id,
+                TicketBookingRequest {
+                    flights: vec![FlightBookingRequest {
+                        flight_number,
+                        flight_date,
+                        preferred_seat,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            )
+            .await?;
+        references.push((
+            user_id,
+            response.flight_bookings[0].booking_reference.clone(),
+        ));
+    }
+    let checkin = |index: usize| CheckinRequest {
+        booking_reference: references[index].1.clone(),
+    };
+    let at = |date: &str| NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M").unwrap();
+
+    // The flight leaves at 10:00, check-in opens 24 hours before
+    let result = ctx
+        .ticket_service
+        .check_in_at(references[0].0, checkin(0), at("2024-12-22 09:59"))
+        .await;
+    assert!(matches!(result, Err(AppError::BadRequest(_))));
+
+    // A passenger without a seat gets the first free one
+    let first = ctx
+        .ticket_service
+        .check_in_at(references[0].0, checkin(0), at("2024-12-22 12:00"))
+        .await?;
+    assert_eq!(first.seat_number, 1);
+    assert_eq!(first.sequence_number, 1);
+    assert_eq!(first.passenger_name, "Ada Lovelace");
+    assert!(first.gate.is_none());
+    assert!(first.barcode.starts_with("M1LOVELACE/ADA"));
+    assert!(first.barcode.contains(&references[0].1));
+    let seat = sqlx::query!(
+        "SELECT seat_number FROM ticket WHERE id = ?",
+        first.ticket_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(seat.seat_number, Some(1));
+
+    let second = ctx
+        .ticket_service
+        .check_in_at(references[1].0, checkin(1), at("2024-12-22 13:00"))
+        .await?;
+    assert_eq!(second.seat_number, 5);
+    assert_eq!(second.sequence_number, 2);
+
+    // Checking in again returns the same boarding pass
+    let again = ctx
+        .ticket_service
+        .check_in_at(references[0].0, checkin(0), at("2024-12-22 14:00"))
+        .await?;
+    assert_eq!(again.sequence_number, 1);
+    assert_eq!(again.checked_in_at, first.checked_in_at);
+    assert_eq!(again.barcode, first.barcode);
+
+    // The boarding pass renders as a PDF or as the PNG of its QR code
+    let pdf = ctx
+        .ticket_service
+        .boarding_pass_document(
+            references[0].0,
+            &references[0].1,
+            DocumentFormat::Pdf,
+            DocumentLocale::De,
+        )
+        .await?;
+    assert_eq!(pdf.content_type().to_string(), "application/pdf");
+    assert!(pdf.bytes().starts_with(b"%PDF-"));
+    // Dates are written in the passenger's language
+    assert!(pdf.bytes().windows(15).any(|text| text == b"(23. Dez. 2024)"));
+    let png = ctx
+        .ticket_service
+        .boarding_pass_document(
+            references[0].0,
+            &references[0].1,
+            DocumentFormat::Png,
+            DocumentLocale::En,
+        )
+        .await?;
+    assert_eq!(png.content_type().to_string(), "image/png");
+    assert!(png.bytes().starts_with(b"\x89PNG"));
+
+    // There is no boarding pass before check-in
+    let result = ctx
+        .ticket_service
+        .boarding_pass_document(
+            references[2].0,
+            &references[2].1,
+            DocumentFormat::Pdf,
+            DocumentLocale::En,
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+
+    // Only the passenger can check in with their reference
+    let result = ctx
+        .ticket_service
+        .check_in_at(references[0].0, checkin(1), at("2024-12-22 14:00"))
+        .await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    // Check-in closes shortly before departure
+    let result = ctx
+        .ticket_service
+        .check_in_at(references[2].0, checkin(2), at("2024-12-23 09:30"))
+        .await;
+    assert!(matches!(result, Err(AppError::BadRequest(_))));
+
+    // Route times are local to the departure airport: leaving Vancouver at 10:00 is
+    // 18:00 UTC in December, so check-in opens and closes eight hours later
+    sqlx::query!(
+        "UPDATE flight_route SET departure_airport = 'YVR' WHERE flight_number = ?",
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+    let result = ctx
+        .ticket_service
+        .check_in_at(references[2].0, checkin(2), at("2024-12-22 17:59"))
+        .await;
+    assert!(matches!(result, Err(AppError::BadRequest(_))));
+    let late = ctx
+        .ticket_service
+        .check_in_at(references[2].0, checkin(2), at("2024-12-23 09:30"))
+        .await?;
+    assert_eq!(late.sequence_number, 3);
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_special_service_requests(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let user = UserRegistrationRequest {
+        username: "ssr_user".to_string(),
+        password: "test_password".to_string(),
+        role: Role::User,
+        name: "SSR Test User".to_string(),
+        birth_date: NaiveDate::from_ymd_opt(1950, 1, 1).unwrap(),
+        gender: "female".to_string(),
+        email: None,
+    };
+    let user_id = ctx.user_service.register_user(user).await?;
+
+    let flight_number = 1801;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 22).unwrap();
+    setup_database(ctx, flight_number, 10, flight_date).await?;
+    let request = |ssr_codes| TicketBookingRequest {
+        flights: vec![FlightBookingRequest {
+            flight_number,
+            flight_date,
+            ssr_codes,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    // A passenger gets a single special meal
+    let two_meals = request(vec![SsrCode::VegetarianMeal, SsrCode::KosherMeal]);
+    let validation = ctx
+        .ticket_service
+        .validate_booking(user_id, &two_meals)
+        .await?;
+    assert!(!validation.valid);
+    let result = ctx.ticket_service.book_ticket(user_id, two_meals).await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+    let codes = vec![SsrCode::WheelchairRamp, SsrCode::VegetarianMeal];
+    let response = ctx
+        .ticket_service
+        .book_ticket(user_id, request(codes.clone()))
+        .await?;
+    let booking = &response.flight_bookings[0];
+
+    let ticket = ctx
+        .ticket_service
+        .get_ticket_by_reference(user_id, &booking.booking_reference)
+        .await?;
+    assert_eq!(ticket.ssr_codes, codes);
+    let history = ctx.ticket_service.get_history(user_id).await?;
+    assert_eq!(history.flights[0].ssr_codes, codes);
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_seat_booking_retries_exhausted(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let user = UserRegistrationRequest {
+        username: "retry_user".to_string(),
+        password: "test_password".to_string(),
+        role: Role::User,
+        name: "Retry Test User".to_string(),
+        birth_date: NaiveDate::from_ymd_opt(1970, 1, 1).unwrap(),
+        gender: "male".to_string(),
+        email: None,
+    };
+    let user_id = ctx.user_service.register_user(user).await?;
+
+    let flight_number = 1802;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 23).unwrap();
+    setup_database(ctx, flight_number, 10, flight_date).await?;
+    ctx.ticket_service
+        .book_ticket(
+            user_id,
+            TicketBookingRequest {
+                flights: vec![FlightBookingRequest {
+                    flight_number,
+                    flight_date,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .await?;
+    let seat_request = |seat_number| SeatBookingRequest {
+        flight_number,
+        flight_date,
+        seat_number,
+    };
+
+    // Every attempt loses the version check
+    let contended = TicketService::new(ctx.pool.clone())
+        .with_seat_booking_attempts(3)
+        .with_forced_seat_conflicts(3);
+    let result = contended
+        .book_seat_for_ticket(user_id, seat_request(4))
+        .await;
+    let error = result.unwrap_err();
+    assert!(matches!(
+        error,
+        AppError::RetryExhausted { attempts: 3, .. }
+    ));
+    assert_eq!(error.kind(), ErrorKind::RetryExhausted);
+    assert_eq!(error.status().code, 409);
+    assert!(error
+        .retry_hints()
+        .map_or(false, |hints| hints.retry_after_ms.is_some()));
+
+    let stats = contended.seat_conflict_stats();
+    assert_eq!(stats.conflicts, 3);
+    assert_eq!(stats.retries_exhausted, 1);
+    let audited = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) FROM outbox_event
+        WHERE event_type = 'SeatBookingRetriesExhausted'
+        AND JSON_EXTRACT(payload, '$.SeatBookingRetriesExhausted.customer_id') = ?
+        "#,
+        user_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(audited, 1);
+
+    // The seat was left alone
+    let seat_status = sqlx::query_scalar!(
+        r#"
+        SELECT s.seat_status FROM seat_info s
+        JOIN flight f ON s.flight_id = f.flight_id
+        WHERE f.flight_number = ? AND s.seat_number = 4
+        "#,
+        flight_number
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(seat_status, "AVAILABLE");
+
+    // Fewer conflicts than attempts are retried away
+    let recovering = TicketService::new(ctx.pool.clone())
+        .with_seat_booking_attempts(3)
+        .with_forced_seat_conflicts(2);
+    assert!(
+        recovering
+            .book_seat_for_ticket(user_id, seat_request(4))
+            .await?
+    );
+    let stats = recovering.seat_conflict_stats();
+    assert_eq!(stats.conflicts, 2);
+    assert_eq!(stats.retries_exhausted, 0);
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_seat_booking_with_seat_lock(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let user = UserRegistrationRequest {
+        username: "seat_lock_user".to_string(),
+        password: "test_password".to_string(),
+        role: Role::User,
+        name: "Seat Lock Test User".to_string(),
+        birth_date: NaiveDate::from_ymd_opt(1975, 6, 1).unwrap(),
+        gender: "female".to_string(),
+        email: None,
+    };
+    let user_id = ctx.user_service.register_user(user).await?;
+
+    let flight_number = 1804;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 26).unwrap();
+    setup_database(ctx, flight_number, 10, flight_date).await?;
+    ctx.ticket_service
+        .book_ticket(
+            user_id,
+            TicketBookingRequest {
+                flights: vec![FlightBookingRequest {
+                    flight_number,
+                    flight_date,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .await?;
+    let flight_id = sqlx::query_scalar!(
+        "SELECT flight_id FROM flight WHERE flight_number = ?",
+        flight_number
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    let seat_request = SeatBookingRequest {
+        flight_number,
+        flight_date,
+        seat_number: 5,
+    };
+
+    let seat_lock = Arc::new(InMemorySeatLock::new());
+    let locking = TicketService::new(ctx.pool.clone())
+        .with_seat_booking_attempts(2)
+        .with_seat_lock(seat_lock.clone());
+
+    // Another booking holds the seat for as long as the booking waits
+    let key = seat_lock_key(flight_id, 5);
+    let token = seat_lock
+        .acquire(&key, Duration::from_secs(60))
+        .await
+        .unwrap()
+        .unwrap();
+    let result = locking
+        .book_seat_for_ticket(user_id, seat_request.clone())
+        .await;
+    assert!(matches!(
+        result,
+        Err(AppError::RetryExhausted { attempts: 2, .. })
+    ));
+    assert_eq!(locking.seat_conflict_stats().conflicts, 2);
+
+    // Once released, the seat is booked and the lock given back
+    seat_lock.release(&key, &token).await.unwrap();
+    assert!(locking.book_seat_for_ticket(user_id, seat_request).await?);
+    let ticket_seat = sqlx::query_scalar!(
+        "SELECT seat_number FROM ticket WHERE customer_id = ? AND flight_id = ?",
+        user_id,
+        flight_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(ticket_seat, Some(5));
+    assert!(seat_lock
+        .acquire(&key, Duration::from_secs(60))
+        .await
+        .unwrap()
+        .is_some());
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_queued_bookings(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let flight_number = 1805;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 27).unwrap();
+    setup_database(ctx, flight_number, 2, flight_date).await?;
+    let booking_requests = BookingRequests::new(ctx.pool.clone());
+    let booking_queue =
+        BookingQueue::start(ctx.ticket_service.clone(), booking_requests.clone(), 2, 10);
+
+    // Three customers for two tickets, queued at once
+    let mut queued_bookings = Vec::new();
+    for index in 0..3 {
+        let user_id = ctx
+            .user_service
+            .register_user(UserRegistrationRequest {
+                username: format!("queued_user_{}", index),
+                password: "test_password".to_string(),
+                role: Role::User,
+                name: "Queued Test User".to_string(),
+                birth_date: NaiveDate::from_ymd_opt(1982, 2, 14).unwrap(),
+                gender: "male".to_string(),
+                email: None,
+            })
+            .await?;
+        let queued = booking_queue
+            .enqueue(
+                user_id,
+                QueueCaller::User(user_id),
+                TicketBookingRequest {
+                    flights: vec![FlightBookingRequest {
+                        flight_number,
+                        flight_date,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                FunnelTracker::default(),
+            )
+            .await?;
+        assert_eq!(
+            queued.status_url,
+            format!("/api/bookings/{}/status", queued.request_id)
+        );
+        queued_bookings.push((user_id, queued.request_id));
+    }
+
+    // The bookings of the flight are applied one after the other in the background,
+    // each one pending until then
+    let mut statuses = Vec::new();
+    for _ in 0..100 {
+        statuses.clear();
+        for (user_id, request_id) in &queued_bookings {
+            statuses.push(booking_requests.status(*user_id, request_id).await?);
+        }
+        if statuses
+            .iter()
+            .all(|status| status.status != BookingRequestStatus::Pending)
+        {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let confirmed: Vec<_> = statuses
+        .iter()
+        .filter(|status| status.status == BookingRequestStatus::Confirmed)
+        .collect();
+    assert_eq!(confirmed.len(), 2);
+    for status in &confirmed {
+        assert!(status.booking_id.is_some());
+        assert_eq!(status.tickets.len(), 1);
+        assert_eq!(status.tickets[0].flight_number, flight_number);
+        assert!(status.error.is_none());
+    }
+    let failed: Vec<_> = statuses
+        .iter()
+        .filter(|status| status.status == BookingRequestStatus::Failed)
+        .collect();
+    assert_eq!(failed.len(), 1);
+    assert!(failed[0].tickets.is_empty());
+    assert!(failed[0].error.is_some());
+
+    let sold = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM ticket WHERE flight_number = ?",
+        flight_number
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(sold, 2);
+    let available_tickets = sqlx::query_scalar!(
+        "SELECT available_tickets FROM flight WHERE flight_number = ?",
+        flight_number
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(available_tickets, 0);
+
+    // Other customers and unknown ids are told nothing
+    let (user_id, request_id) = &queued_bookings[0];
+    let (other_user_id, _) = &queued_bookings[1];
+    let result = booking_requests.status(*other_user_id, request_id).await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+    let result = booking_requests.status(*user_id, "not-a-request-id").await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_abandoned_queued_bookings_fail(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let user_id = ctx
+        .user_service
+        .register_user(UserRegistrationRequest {
+            username: "abandoned_queue_user".to_string(),
+            password: "test_password".to_string(),
+            role: Role::User,
+            name: "Queued Test User".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1982, 2, 14).unwrap(),
+            gender: "male".to_string(),
+            email: None,
+        })
+        .await?;
+
+    // A server still running, with a long queue, and one that has since stopped
+    let now = chrono::Utc::now().naive_utc();
+    let timeout = chrono::Duration::minutes(BOOKING_REQUEST_TIMEOUT_MINUTES);
+    let long_ago = now - timeout - chrono::Duration::minutes(1);
+    let running_server = new_public_id();
+    let stopped_server = new_public_id();
+    for (server_id, seen_at) in [(&running_server, now), (&stopped_server, long_ago)] {
+        sqlx::query!(
+            "INSERT INTO booking_server (server_id, seen_at) VALUES (?, ?)",
+            server_id,
+            seen_at
+        )
+        .execute(&ctx.pool)
+        .await?;
+    }
+
+    // Bookings long queued on each server, one queued before servers were recorded and
+    // one just queued on the stopped server
+    let waiting = new_public_id();
+    let abandoned = new_public_id();
+    let unrecorded = new_public_id();
+    let recent = new_public_id();
+    for (request_id, server_id, created_at) in [
+        (&waiting, Some(&running_server), long_ago),
+        (&abandoned, Some(&stopped_server), long_ago),
+        (&unrecorded, None, long_ago),
+        (&recent, Some(&stopped_server), now),
+    ] {
+        sqlx::query!(
+            r#"
+            INSERT INTO booking_request (request_id, customer_id, status, created_at, server_id)
+            VALUES (?, ?, 'PENDING', ?, ?)
+            "#,
+            request_id,
+            user_id,
+            created_at,
+            server_id
+        )
+        .execute(&ctx.pool)
+        .await?;
+    }
+
+    let booking_requests = BookingRequests::new(ctx.pool.clone());
+    assert_eq!(booking_requests.fail_abandoned(now - timeout).await?, 2);
+
+    for request_id in [&abandoned, &unrecorded] {
+        let status = booking_requests.status(user_id, request_id).await?;
+        assert_eq!(status.status, BookingRequestStatus::Failed);
+        assert!(status.error.is_some());
+    }
+    // The running server still applies the bookings in its queue
+    for request_id in [&waiting, &recent] {
+        let status = booking_requests.status(user_id, request_id).await?;
+        assert_eq!(status.status, BookingRequestStatus::Pending);
+    }
+    let servers = sqlx::query_scalar!("SELECT server_id FROM booking_server")
+        .fetch_all(&ctx.pool)
+        .await?;
+    assert_eq!(servers, vec![running_server]);
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_queued_bookings_user_share(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let flight_number = 1807;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 27).unwrap();
+    setup_database(ctx, flight_number, 10, flight_date).await?;
+    let booking_requests = BookingRequests::new(ctx.pool.clone());
+    // A queue of 10 bookings, of which a single user may take one
+    let booking_queue =
+        BookingQueue::start(ctx.ticket_service.clone(), booking_requests.clone(), 1, 10)
+            .with_user_share(10);
+
+    let mut user_ids = Vec::new();
+    for username in [
+        "share_agent",
+        "share_customer",
+        "share_key_first",
+        "share_key_second",
+    ] {
+        let user_id = ctx
+            .user_service
+            .register_user(UserRegistrationRequest {
+                username: username.to_string(),
+                password: "test_password".to_string(),
+                role: Role::User,
+                name: "Queued Test User".to_string(),
+                birth_date: NaiveDate::from_ymd_opt(1982, 2, 14).unwrap(),
+                gender: "male".to_string(),
+                email: None,
+            })
+            .await?;
+        user_ids.push(user_id);
+    }
+    let (agent, customer) = (user_ids[0], user_ids[1]);
+    let (key_first, key_second) = (user_ids[2], user_ids[3]);
+    let request = || TicketBookingRequest {
+        flights: vec![FlightBookingRequest {
+            flight_number,
+            flight_date,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    // Hold the flight so the queued bookings wait at the worker
+    let mut tx = ctx.pool.begin().await?;
+    sqlx::query!(
+        "SELECT flight_id FROM flight WHERE flight_number = ? FOR UPDATE",
+        flight_number
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let agent_booking = booking_queue
+        .enqueue(
+            agent,
+            QueueCaller::User(agent),
+            request(),
+            FunnelTracker::default(),
+        )
+        .await?;
+    let result = booking_queue
+        .enqueue(
+            agent,
+            QueueCaller::User(agent),
+            request(),
+            FunnelTracker::default(),
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::ConflictWithHints(_, hints))
+        if hints.retry_after_ms.is_some()));
+    // Other customers still get into the queue
+    booking_queue
+        .enqueue(
+            customer,
+            QueueCaller::User(customer),
+            request(),
+            FunnelTracker::default(),
+        )
+        .await?;
+    // Users booking with the same API key share its part of the queue
+    booking_queue
+        .enqueue(
+            key_first,
+            QueueCaller::ApiKey(1),
+            request(),
+            FunnelTracker::default(),
+        )
+        .await?;
+    let result = booking_queue
+        .enqueue(
+            key_second,
+            QueueCaller::ApiKey(1),
+            request(),
+            FunnelTracker::default(),
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::ConflictWithHints(_, _))));
+    tx.rollback().await?;
+
+    // The agent's share is given back once their booking is applied
+    let mut status = booking_requests
+        .status(agent, &agent_booking.request_id)
+        .await?;
+    for _ in 0..100 {
+        if status.status != BookingRequestStatus::Pending {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        status = booking_requests
+            .status(agent, &agent_booking.request_id)
+            .await?;
+    }
+    assert_eq!(status.status, BookingRequestStatus::Confirmed);
+    let mut result = booking_queue
+        .enqueue(
+            agent,
+            QueueCaller::User(agent),
+            request(),
+            FunnelTracker::default(),
+        )
+        .await;
+    for _ in 0..100 {
+        if result.is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        result = booking_queue
+            .enqueue(
+                agent,
+                QueueCaller::User(agent),
+                request(),
+                FunnelTracker::default(),
+            )
+            .await;
+    }
+    assert!(result.is_ok());
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_op_up_when_cabin_sold_out(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let flight_number = 1803;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 24).unwrap();
+    setup_database(ctx, flight_number, 4, flight_date).await?;
+
+    // Rows of two seats, business in row 1 and economy in row 2
+    sqlx::query!(
+        "UPDATE aircraft SET seats_per_row = 2 WHERE aircraft_id = ?",
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO fare (flight_number, fare_class, base_price, currency, first_row, last_row)
+        VALUES (?, 'BUSINESS', 400.00, 'CAD', 1, 1), (?, 'ECONOMY', 100.00, 'CAD', 2, 2)
+        "#,
+        flight_number,
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+
+    let op_up = TicketService::new(ctx.pool.clone())
+        .with_rules(BookingRules::from_toml("[op_up]\nenabled = true")?);
+    let mut bookings = Vec::new();
+    for username in ["op_up_user1", "op_up_user2", "op_up_user3", "op_up_user4"] {
+        let user_id = ctx
+            .user_service
+            .register_user(UserRegistrationRequest {
+                username: username.to_string(),
+                password: "test_password".to_string(),
+                role: Role::User,
+                name: "Op-up Test User".to_string(),
+                birth_date: NaiveDate::from_ymd_opt(1985, 6, 1).unwrap(),
+                gender: "female".to_string(),
+                email: None,
+            })
+            .await?;
+        // The last passenger is booked without the op-up policy
+        let ticket_service = if bookings.len() < 3 {
+            &op_up
+        } else {
+            &ctx.ticket_service
+        };
+        let response = ticket_service
+            .book_ticket(
+                user_id,
+                TicketBookingRequest {
+                    flights: vec![FlightBookingRequest {
+                        flight_number,
+                        flight_date,
+                        fare_class: FareClass::Economy,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            )
+            .await?;
+        bookings.push(response.flight_bookings.into_iter().next().unwrap());
+    }
+
+    assert_eq!(bookings[1].fare_class, FareClass::Economy);
+    assert_eq!(bookings[1].upgraded_from, None);
+
+    // Economy is sold out, the third passenger flies business at the economy fare
+    assert_eq!(bookings[2].fare_class, FareClass::Business);
+    assert_eq!(bookings[2].upgraded_from, Some(FareClass::Economy));
+    assert_eq!(bookings[2].price.to_string(), "100.00");
+    let reason = sqlx::query_scalar!(
+        "SELECT reason FROM op_up WHERE ticket_id = ?",
+        bookings[2].ticket_id
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(reason, "ECONOMY cabin sold out");
+
+    assert_eq!(bookings[3].fare_class, FareClass::Economy);
+    assert_eq!(bookings[3].upgraded_from, None);
+
+    // The op-up seat is not left to sell in business
+    let flight_id = sqlx::query_scalar!(
+        "SELECT flight_id FROM flight WHERE flight_number = ? AND flight_date = ?",
+        flight_number,
+        flight_date
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    let cabins = ctx.ticket_service.cabin_inventory(flight_id).await?;
+    let business = cabins
+        .iter()
+        .find(|cabin| cabin.fare_class == FareClass::Business)
+        .unwrap();
+    assert_eq!(
+        (business.seats, business.tickets_sold, business.op_ups),
+        (2, 1, 1)
+    );
+    assert_eq!(business.free_seats, 1);
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_validation_matches_booking(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let mut user_ids = Vec::new();
+    for (username, birth_date) in [
+        (
+            "validation_adult",
+            NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+        ),
+        (
+            "validation_minor",
+            NaiveDate::from_ymd_opt(2014, 6, 1).unwrap(),
+        ),
+    ] {
+        let user = UserRegistrationRequest {
+            username: username.to_string(),
+            password: "test_password".to_string(),
+            role: Role::User,
+            name: "Validation Test User".to_string(),
+            birth_date,
+            gender: "female".to_string(),
+            email: None,
+        };
+        user_ids.push(ctx.user_service.register_user(user).await?);
+    }
+    let (adult, minor) = (user_ids[0], user_ids[1]);
+
+    // No unaccompanied minors accepted on this flight
+    let flight_number = 1806;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 20).unwrap();
+    setup_database(ctx, flight_number, 10, flight_date).await?;
+    sqlx::query!(
+        "UPDATE flight_route SET um_quota = 0 WHERE flight_number = ?",
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+    let request = || TicketBookingRequest {
+        flights: vec![FlightBookingRequest {
+            flight_number,
+            flight_date,
+            preferred_seat: Some(3),
+            ..Default::default()
+        }],
+        guardian: Some(GuardianContact {
+            name: "Parent".to_string(),
+            phone: "416-555-0100".to_string(),
+            relationship: "mother".to_string(),
+        }),
+        ..Default::default()
+    };
+
+    // A request that validates books
+    let validation = ctx
+        .ticket_service
+        .validate_booking(adult, &request())
+        .await?;
+    assert!(validation.valid);
+    assert!(validation.legs[0].warnings.is_empty());
+    let response = ctx.ticket_service.book_ticket(adult, request()).await?;
+    assert_eq!(response.flight_bookings.len(), 1);
+
+    // One that does not validate fails to book, for the reason validation gave
+    let validation = ctx
+        .ticket_service
+        .validate_booking(minor, &request())
+        .await?;
+    assert!(!validation.valid);
+    assert_eq!(
+        validation.legs[0].issues,
+        vec!["Validation error: This flight has no more capacity for unaccompanied minors."]
+    );
+    let result = ctx.ticket_service.book_ticket(minor, request()).await;
+    assert!(matches!(result, Err(AppError::ValidationError(message))
+        if message.contains("no more capacity for unaccompanied minors")));
+
+    // The seat the adult took is only a warning, the booking goes through without it
+    let validation = ctx
+        .ticket_service
+        .validate_booking(adult, &request())
+        .await?;
+    assert!(!validation.valid);
+    assert_eq!(
+        validation.legs[0].issues,
+        vec!["Bad request: Cannot re-book the same flight"]
+    );
+    assert_eq!(validation.legs[0].warnings, vec!["Seat 3 is not available"]);
+
+    Ok(())
+}
+
+#[test_context(TicketServiceContext)]
+#[tokio::test]
+async fn test_full_flight_suggests_other_dates(ctx: &TicketServiceContext) -> Result<(), AppError> {
+    let user = UserRegistrationRequest {
+        username: "full_flight_user".to_string(),
+        password: "test_password".to_string(),
+        role: Role::User,
+        name: "Full Flight Test User".to_string(),
+        birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+        gender: "female".to_string(),
+        email: None,
+    };
+    let user_id = ctx.user_service.register_user(user).await?;
+
+    let flight_number = 1808;
+    let flight_date = NaiveDate::from_ymd_opt(2024, 12, 20).unwrap();
+    setup_database(ctx, flight_number, 10, flight_date).await?;
+    sqlx::query!(
+        "UPDATE flight SET available_tickets = 0 WHERE flight_number = ?",
+        flight_number
+    )
+    .execute(&ctx.pool)
+    .await?;
+    // Other dates of the flight: one full, two with tickets, one too far away
+    for (day, available_tickets) in [(19, 0), (22, 5), (21, 5), (30, 5)] {
+        sqlx::query!(
+            r#"
+            INSERT INTO flight (flight_number, flight_date, available_tickets, version)
+            VALUES (?, ?, ?, 1)
+            "#,
+            flight_number,
+            NaiveDate::from_ymd_opt(2024, 12, day).unwrap(),
+            available_tickets
+        )
+        .execute(&ctx.pool)
+        .await?;
+    }
+
+    let error = ctx
+        .ticket_service
+        .book_ticket(
+            user_id,
+            TicketBookingRequest {
+                flights: vec![FlightBookingRequest {
+                    flight_number,
+                    flight_date,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(error, AppError::ConflictWithHints(_, _)));
+    assert_eq!(error.status().code, 409);
+    let hints = error.retry_hints().unwrap();
+    assert!(hints.retry_after_ms.is_some());
+    // Closest first
+    assert_eq!(
+        hints.alternative_dates,
+        vec![
+            NaiveDate::from_ymd_opt(2024, 12, 21).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 12, 22).unwrap(),
+        ]
+    );
+
+    Ok(())
+}
@@ -1,13 +1,18 @@
 use airline_booking_system::{
-    models::user::{Role, UserLoginRequest, UserRegistrationRequest},
-    services::user_service::UserService,
+    models::user::{
+        ChangePasswordRequest, ResetPasswordRequest, Role, UpdateProfileRequest,
+        UserLoginRequest, UserRegistrationRequest,
+    },
+    services::{admin_service::AdminService, user_service::UserService},
     utils::error::AppError,
+    utils::jwt,
 };
 use async_trait::async_trait;
 use chrono::NaiveDate;
 use ctor::dtor;
 use sqlx::mysql::MySqlPool as Pool;
 use test_context::{test_context, AsyncTestContext};
+use tokio::task::JoinSet;
 
 mod common {
     pub mod test_utils;
@@ -60,6 +65,7 @@ async fn test_user_registration_success(ctx: &UserServiceContext) -> Result<(),
         name: "Test User".to_string(),
         birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
         gender: "male".to_string(),
+        email: None,
     };
 
     let expected_username = test_user.username.clone();
@@ -119,6 +125,7 @@ async fn test_user_registration_duplicate_username(
         name: "Test User".to_string(),
         birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
         gender: "male".to_string(),
+        email: None,
     };
 
     let result = ctx.user_service.register_user(test_user).await;
@@ -133,6 +140,84 @@ async fn test_user_registration_duplicate_username(
     }
 }
 
+#[test_context(UserServiceContext)]
+#[tokio::test]
+async fn test_failed_registration_leaves_no_user(ctx: &UserServiceContext) -> Result<(), AppError> {
+    let registration = |name: String| UserRegistrationRequest {
+        username: "half_registered_user".to_string(),
+        password: "test_password123".to_string(),
+        role: Role::User,
+        name,
+        birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+        gender: "female".to_string(),
+        email: None,
+    };
+
+    // The name does not fit in customer_info, after the user row was written
+    let result = ctx
+        .user_service
+        .register_user(registration("x".repeat(300)))
+        .await;
+    assert!(matches!(result, Err(AppError::DatabaseError(_))));
+    let users =
+        sqlx::query_scalar!("SELECT COUNT(*) FROM user WHERE username = 'half_registered_user'")
+            .fetch_one(&ctx.pool)
+            .await?;
+    assert_eq!(users, 0, "The user row must be rolled back");
+
+    // The username is still free
+    ctx.user_service
+        .register_user(registration("Half Registered".to_string()))
+        .await?;
+
+    Ok(())
+}
+
+#[test_context(UserServiceContext)]
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn test_concurrent_registration_same_username(
+    ctx: &UserServiceContext,
+) -> Result<(), AppError> {
+    let username = "concurrent_registration_user";
+    let attempts = 8;
+
+    // Every registration passes the existence check before any of them inserts
+    let mut join_set = JoinSet::new();
+    for i in 0..attempts {
+        let user_service = ctx.user_service.clone();
+        join_set.spawn(async move {
+            user_service
+                .register_user(UserRegistrationRequest {
+                    username: username.to_string(),
+                    password: "test_password".to_string(),
+                    role: Role::User,
+                    name: format!("Racing User {}", i),
+                    birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+                    gender: "male".to_string(),
+                    email: None,
+                })
+                .await
+        });
+    }
+
+    let mut registered = 0;
+    while let Some(result) = join_set.join_next().await {
+        match result.unwrap() {
+            Ok(_) => registered += 1,
+            Err(AppError::Conflict(msg)) => assert_eq!(msg, "Username already exists"),
+            Err(e) => panic!("Expected Conflict error, got {:?}", e),
+        }
+    }
+    assert_eq!(registered, 1, "Only one registration should succeed");
+
+    let users = sqlx::query_scalar!("SELECT COUNT(*) FROM user WHERE username = ?", username)
+        .fetch_one(&ctx.pool)
+        .await?;
+    assert_eq!(users, 1);
+
+    Ok(())
+}
+
 #[test_context(UserServiceContext)]
 #[tokio::test]
 async fn test_user_login_success(ctx: &UserServiceContext) -> Result<(), AppError> {
@@ -237,3 +322,352 @@ async fn test_user_login_wrong_password(ctx: &UserServiceContext) -> Result<(),
         _ => panic!("Expected AuthError for wrong password"),
     }
 }
+
+#[test_context(UserServiceContext)]
+#[tokio::test]
+async fn test_get_and_update_profile(ctx: &UserServiceContext) -> Result<(), AppError> {
+    let user_id = ctx
+        .user_service
+        .register_user(UserRegistrationRequest {
+            username: "profile_test_user".to_string(),
+            password: "test_password123".to_string(),
+            role: Role::User,
+            name: "Profile User".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1985, 6, 15).unwrap(),
+            gender: "female".to_string(),
+            email: None,
+        })
+        .await?;
+
+    let profile = ctx.user_service.get_profile(user_id).await?;
+    assert_eq!(profile.username, "profile_test_user");
+    assert_eq!(profile.name, "Profile User");
+    assert_eq!(profile.email, None);
+
+    // Only the given fields change
+    let profile = ctx
+        .user_service
+        .update_profile(
+            user_id,
+            UpdateProfileRequest {
+                email: Some("profile@example.com".to_string()),
+                phone: Some("+1 555 0100".to_string()),
+                ..Default::default()
+            },
+        )
+        .await?;
+    assert_eq!(profile.name, "Profile User");
+    assert_eq!(profile.gender, "female");
+    assert_eq!(profile.email.as_deref(), Some("profile@example.com"));
+    assert_eq!(profile.phone.as_deref(), Some("+1 555 0100"));
+
+    let result = ctx
+        .user_service
+        .update_profile(
+            user_id,
+            UpdateProfileRequest {
+                email: Some("not an email".to_string()),
+                ..Default::default()
+            },
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+    let result = ctx.user_service.get_profile(user_id + 1000).await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    Ok(())
+}
+
+#[test_context(UserServiceContext)]
+#[tokio::test]
+async fn test_change_and_reset_password(ctx: &UserServiceContext) -> Result<(), AppError> {
+    let username = "password_change_user";
+    let user_id = ctx
+        .user_service
+        .register_user(UserRegistrationRequest {
+            username: username.to_string(),
+            password: "first_password".to_string(),
+            role: Role::User,
+            name: "Password User".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+            gender: "male".to_string(),
+            email: None,
+        })
+        .await?;
+
+    let login = |password: &str| UserLoginRequest {
+        username: username.to_string(),
+        password: password.to_string(),
+    };
+
+    // The old password must be right
+    let result = ctx
+        .user_service
+        .change_password(
+            user_id,
+            ChangePasswordRequest {
+                old_password: "wrong_password".to_string(),
+                new_password: "second_password".to_string(),
+            },
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::AuthError(_))));
+
+    ctx.user_service
+        .change_password(
+            user_id,
+            ChangePasswordRequest {
+                old_password: "first_password".to_string(),
+                new_password: "second_password".to_string(),
+            },
+        )
+        .await?;
+    assert!(ctx.user_service.login_user(login("first_password")).await.is_err());
+    ctx.user_service.login_user(login("second_password")).await?;
+
+    // Unknown users get no token
+    assert_eq!(
+        ctx.user_service
+            .request_password_reset("no_such_user")
+            .await?,
+        None
+    );
+
+    let first_token = ctx
+        .user_service
+        .request_password_reset(username)
+        .await?
+        .unwrap();
+    let token = ctx
+        .user_service
+        .request_password_reset(username)
+        .await?
+        .unwrap();
+
+    // A forged secret is rejected
+    let (token_id, _) = token.split_once('.').unwrap();
+    let result = ctx
+        .user_service
+        .reset_password(ResetPasswordRequest {
+            token: format!("{}.forged", token_id),
+            new_password: "third_password".to_string(),
+        })
+        .await;
+    assert!(matches!(result, Err(AppError::AuthError(_))));
+
+    ctx.user_service
+        .reset_password(ResetPasswordRequest {
+            token: token.clone(),
+            new_password: "third_password".to_string(),
+        })
+        .await?;
+    ctx.user_service.login_user(login("third_password")).await?;
+
+    // Tokens are single use, and using one spends the others
+    for token in [token, first_token] {
+        let result = ctx
+            .user_service
+            .reset_password(ResetPasswordRequest {
+                token,
+                new_password: "fourth_password".to_string(),
+            })
+            .await;
+        assert!(matches!(result, Err(AppError::AuthError(_))));
+    }
+
+    Ok(())
+}
+
+#[test_context(UserServiceContext)]
+#[tokio::test]
+async fn test_email_verification(ctx: &UserServiceContext) -> Result<(), AppError> {
+    let registration = |username: &str, email: &str| UserRegistrationRequest {
+        username: username.to_string(),
+        password: "test_password123".to_string(),
+        role: Role::User,
+        name: "Email User".to_string(),
+        birth_date: NaiveDate::from_ymd_opt(1992, 3, 4).unwrap(),
+        gender: "female".to_string(),
+        email: Some(email.to_string()),
+    };
+
+    let result = ctx
+        .user_service
+        .register_user(registration("bad_email_user", "not an email"))
+        .await;
+    assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+    let user_id = ctx
+        .user_service
+        .register_user(registration("email_test_user", "first@example.com"))
+        .await?;
+    let profile = ctx.user_service.get_profile(user_id).await?;
+    assert_eq!(profile.email.as_deref(), Some("first@example.com"));
+    assert!(!profile.email_verified);
+
+    let result = ctx.user_service.verify_email("garbage").await;
+    assert!(matches!(result, Err(AppError::AuthError(_))));
+
+    let token = ctx
+        .user_service
+        .resend_email_verification(user_id)
+        .await?
+        .unwrap();
+    ctx.user_service.verify_email(&token).await?;
+    assert!(ctx.user_service.get_profile(user_id).await?.email_verified);
+
+    // Nothing left to verify, and the token is spent
+    assert_eq!(
+        ctx.user_service.resend_email_verification(user_id).await?,
+        None
+    );
+    let result = ctx.user_service.verify_email(&token).await;
+    assert!(matches!(result, Err(AppError::AuthError(_))));
+
+    // Changing the address needs a new verification
+    let update_email = |email: &str| UpdateProfileRequest {
+        email: Some(email.to_string()),
+        ..Default::default()
+    };
+    let profile = ctx
+        .user_service
+        .update_profile(user_id, update_email("second@example.com"))
+        .await?;
+    assert!(!profile.email_verified);
+    let stale_token = ctx
+        .user_service
+        .resend_email_verification(user_id)
+        .await?
+        .unwrap();
+
+    // A token only verifies the address it was sent to
+    ctx.user_service
+        .update_profile(user_id, update_email("third@example.com"))
+        .await?;
+    let result = ctx.user_service.verify_email(&stale_token).await;
+    assert!(matches!(result, Err(AppError::AuthError(_))));
+
+    let token = ctx
+        .user_service
+        .resend_email_verification(user_id)
+        .await?
+        .unwrap();
+    ctx.user_service.verify_email(&token).await?;
+    let profile = ctx.user_service.get_profile(user_id).await?;
+    assert_eq!(profile.email.as_deref(), Some("third@example.com"));
+    assert!(profile.email_verified);
+
+    Ok(())
+}
+
+#[test_context(UserServiceContext)]
+#[tokio::test]
+async fn test_support_token(ctx: &UserServiceContext) -> Result<(), AppError> {
+    let user_id = ctx
+        .user_service
+        .register_user(UserRegistrationRequest {
+            username: "support_user".to_string(),
+            password: "test_password123".to_string(),
+            role: Role::User,
+            name: "Support User".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1988, 7, 9).unwrap(),
+            gender: "male".to_string(),
+            email: None,
+        })
+        .await?;
+
+    let support = ctx.user_service.create_support_token(user_id).await?;
+    assert_eq!(support.scope, jwt::SUPPORT_SCOPE_BOOKINGS_READ);
+    assert!(support.expires_at > chrono::Utc::now().naive_utc());
+
+    let claims = jwt::decode_support_token(&support.token, jwt::SUPPORT_SCOPE_BOOKINGS_READ)
+        .expect("support code should be valid");
+    assert_eq!(claims.sub, user_id);
+    assert_eq!(claims.aud, jwt::SUPPORT_AUDIENCE);
+
+    // The code grants nothing beyond reading bookings
+    assert!(jwt::decode_support_token(&support.token, "bookings:write").is_none());
+
+    // A login token is not a support code
+    let login = ctx
+        .user_service
+        .login_user(UserLoginRequest {
+            username: "support_user".to_string(),
+            password: "test_password123".to_string(),
+        })
+        .await?;
+    assert!(jwt::decode_support_token(&login.token, jwt::SUPPORT_SCOPE_BOOKINGS_READ).is_none());
+
+    let result = ctx.user_service.create_support_token(i32::MAX).await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    Ok(())
+}
+
+#[test_context(UserServiceContext)]
+#[tokio::test]
+async fn test_account_lockout(ctx: &UserServiceContext) -> Result<(), AppError> {
+    let user_service =
+        UserService::new(ctx.pool.clone()).with_lockout(3, chrono::Duration::minutes(5));
+    let user_id = user_service
+        .register_user(UserRegistrationRequest {
+            username: "lockout_user".to_string(),
+            password: "right_password".to_string(),
+            role: Role::User,
+            name: "Lockout User".to_string(),
+            birth_date: NaiveDate::from_ymd_opt(1991, 3, 4).unwrap(),
+            gender: "female".to_string(),
+            email: None,
+        })
+        .await?;
+    let login = |password: &str| UserLoginRequest {
+        username: "lockout_user".to_string(),
+        password: password.to_string(),
+    };
+
+    // A successful login starts the count over
+    for _ in 0..2 {
+        let result = user_service
+            .login_user_from(login("wrong_password"), Some("192.0.2.7"))
+            .await;
+        assert!(matches!(result, Err(AppError::AuthError(_))));
+    }
+    user_service.login_user(login("right_password")).await?;
+
+    for _ in 0..3 {
+        let result = user_service
+            .login_user_from(login("wrong_password"), Some("192.0.2.7"))
+            .await;
+        assert!(matches!(result, Err(AppError::AuthError(_))));
+    }
+    // Locked, even for the right password
+    match user_service.login_user(login("right_password")).await {
+        Err(AppError::AuthError(msg)) => assert!(msg.contains("locked"), "{}", msg),
+        other => panic!("Expected the account to be locked, got {:?}", other),
+    }
+
+    let attempts = sqlx::query!(
+        r#"
+        SELECT COUNT(*) as "total!: i64",
+            CAST(COALESCE(SUM(succeeded), 0) AS SIGNED) as "succeeded!: i64",
+            CAST(COALESCE(SUM(ip_address = '192.0.2.7'), 0) AS SIGNED) as "from_ip!: i64"
+        FROM login_attempt
+        WHERE username = 'lockout_user'
+        "#
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+    assert_eq!(attempts.total, 7);
+    assert_eq!(attempts.succeeded, 1);
+    assert_eq!(attempts.from_ip, 5);
+
+    let admin_service = AdminService::new(ctx.pool.clone());
+    admin_service.unlock_user(user_id).await?;
+    user_service.login_user(login("right_password")).await?;
+
+    let result = admin_service.unlock_user(i32::MAX).await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    Ok(())
+}
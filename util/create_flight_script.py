@@ -17,6 +17,22 @@ def connect_to_db():
         database=db_url.path[1:]  # Remove '/'
     )
 
+# Default aircraft, as (aircraft_id, capacity)
+DEFAULT_AIRCRAFT = [(737, 30), (777, 400), (320, 25), (900, 76), (200, 50)]
+
+def add_default_aircraft():
+    conn = connect_to_db()
+    cursor = conn.cursor()
+    try:
+        cursor.executemany(
+            "INSERT IGNORE INTO aircraft (aircraft_id, capacity) VALUES (%s, %s)",
+            DEFAULT_AIRCRAFT
+        )
+        conn.commit()
+    finally:
+        cursor.close()
+        conn.close()
+
 def add_flight_route_and_flights(
     flight_number,
     departure_city,
@@ -26,7 +42,9 @@ def add_flight_route_and_flights(
     aircraft_id,
     overbooking,
     start_date,
-    end_date
+    end_date,
+    departure_airport=None,
+    destination_airport=None
 ):
     try:
         conn = connect_to_db()
@@ -43,11 +61,12 @@ def add_flight_route_and_flights(
         insert_route_sql = """
         INSERT INTO flight_route 
         (flight_number, departure_city, destination_city, departure_time, arrival_time, 
-         aircraft_id, overbooking, start_date, end_date)
-        VALUES (%s, %s, %s, %s, %s, %s, %s, %s, %s)
+         aircraft_id, overbooking, start_date, end_date, departure_airport, destination_airport)
+        VALUES (%s, %s, %s, %s, %s, %s, %s, %s, %s, %s, %s)
         """
         route_data = (flight_number, departure_city, destination_city, departure_time, 
-                     arrival_time, aircraft_id, overbooking, start_date, end_date)
+                     arrival_time, aircraft_id, overbooking, start_date, end_date,
+                     departure_airport, destination_airport)
         cursor.execute(insert_route_sql, route_data)
         
         # 4. Create flights for each day
@@ -105,8 +124,10 @@ if __name__ == "__main__":
     flight_routes = [
         {
             "flight_number": 590,
-            "departure_city": "JFK",
-            "destination_city": "YYZ",
+            "departure_city": "New York",
+            "destination_city": "Toronto",
+            "departure_airport": "JFK",
+            "destination_airport": "YYZ",
             "departure_time": "07:20:00",
             "arrival_time": "08:50:00",
             "aircraft_id": 320,
@@ -116,8 +137,10 @@ if __name__ == "__main__":
         },
         {
             "flight_number": 1284,
-            "departure_city": "LAX",
-            "destination_city": "JFK",
+            "departure_city": "Los Angeles",
+            "destination_city": "New York",
+            "departure_airport": "LAX",
+            "destination_airport": "JFK",
             "departure_time": "23:55:00",
             "arrival_time": "07:00:00",
             "aircraft_id": 737,
@@ -127,5 +150,6 @@ if __name__ == "__main__":
         }
     ]
 
+    add_default_aircraft()
     for route in flight_routes:
         add_flight_route_and_flights(**route)
\ No newline at end of file